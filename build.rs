@@ -0,0 +1,22 @@
+use std::process::Command;
+
+
+/// Embeds the commit the binary was built from so bug reports and distributed testing
+/// can identify the exact version that is running. The bench signature can't be computed
+/// here, as it needs the engine being built. scripts/make-release.sh runs the bench and
+/// passes its node count in RUSTCHESS_BENCH, other builds have no signature.
+fn main() {
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short=10", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+        .unwrap_or_else(|| String::from("unknown"));
+
+    println!("cargo:rustc-env=RUSTCHESS_GIT_COMMIT={}", commit);
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+    println!("cargo:rerun-if-env-changed=RUSTCHESS_BENCH");
+}
@@ -0,0 +1,40 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use chess::{Position, Variant};
+use chess::protocol::uci::Uci;
+use chess::search::SearchEngine;
+
+const VARIANTS: [Variant; 5] = [Variant::Standard, Variant::Gardner, Variant::Crazyhouse, Variant::Bughouse, Variant::ThreeCheck];
+
+// Feeds arbitrary input to everything that parses text sent by a GUI or read from a file. None
+// of it may panic, invalid input has to be rejected.
+fuzz_target!(|data: &[u8]| {
+    let input = String::from_utf8_lossy(data);
+
+    for variant in VARIANTS {
+        if let Some(position) = Position::from_variant_fen(variant, input.to_string()) {
+            position.to_fen();
+        }
+    }
+
+    let position = Position::starting_position();
+
+    for word in input.split_whitespace() {
+        position.uci_to_move(word);
+        position.string_to_move(word);
+        position.parse_san(word);
+    }
+
+    // Searching only one ply keeps go commands fast enough for fuzzing.
+    let mut engine = SearchEngine::new(1);
+    let mut output = Vec::new();
+    let mut uci = Uci::new(&mut engine, &mut output);
+
+    for line in input.lines() {
+        if !uci.handle(line.trim()).unwrap() {
+            break;
+        }
+    }
+});
@@ -0,0 +1,36 @@
+#!/bin/sh
+# Builds an engine binary optimized with profile-guided optimization (PGO). An instrumented
+# build runs the bench to record which code is hot, then the final build is optimized with
+# the recorded profile. The bench node count is embedded as the signature of the binary.
+#
+# Usage: scripts/make-release.sh [<output>]
+# Needs llvm-profdata matching the LLVM version of rustc, e.g. from the llvm-tools rustup
+# component. Its path can be given in the LLVM_PROFDATA environment variable.
+set -eu
+
+cd "$(dirname "$0")/.."
+
+output="${1:-chess-release}"
+profiles="$PWD/target/pgo-profiles"
+profdata="${LLVM_PROFDATA:-$(ls "$(rustc --print sysroot)"/lib/rustlib/*/bin/llvm-profdata 2>/dev/null || command -v llvm-profdata || true)}"
+
+if [ -z "$profdata" ]; then
+    echo "llvm-profdata not found, install it with: rustup component add llvm-tools" >&2
+    exit 1
+fi
+
+rm -rf "$profiles"
+
+echo "Building the instrumented binary"
+RUSTFLAGS="-Cprofile-generate=$profiles" cargo build --profile release-pgo --target-dir target/pgo-instrumented
+
+echo "Running the bench as training workload"
+nodes=$(target/pgo-instrumented/release-pgo/chess bench | sed -n 's/^Nodes searched: //p')
+
+"$profdata" merge -o "$profiles/merged.profdata" "$profiles"
+
+echo "Building the optimized binary (bench $nodes)"
+RUSTCHESS_BENCH="$nodes" RUSTFLAGS="-Cprofile-use=$profiles/merged.profdata" cargo build --profile release-pgo
+
+cp target/release-pgo/chess "$output"
+echo "Wrote $output"
@@ -0,0 +1,369 @@
+use std::fmt;
+
+use crate::bitboards::bb_ops;
+use crate::bitboards::geometry::Orientation;
+use crate::position::{self, Position, WHITE};
+
+
+/// The size of a square of the SVG board in pixels.
+const SQUARE_SIZE: u32 = 45;
+
+/// The unicode symbols of the white and black pieces ordered by piece type.
+const PIECE_SYMBOLS: [[char; 6]; 2] = [['♙', '♘', '♗', '♖', '♕', '♔'], ['♟', '♞', '♝', '♜', '♛', '♚']];
+
+
+/// The colors squares and arrows can be marked with. These are the colors supported by
+/// lichess.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Brush {
+    Green,
+    Red,
+    Yellow,
+    Blue
+}
+
+impl Brush {
+    /// Returns the letter encoding the brush in PGN comments.
+    pub fn to_char(self) -> char {
+        return match self {
+            Brush::Green  => 'G',
+            Brush::Red    => 'R',
+            Brush::Yellow => 'Y',
+            Brush::Blue   => 'B'
+        };
+    }
+
+    /// Parses the letter encoding a brush in PGN comments.
+    pub fn from_char(c: char) -> Option<Brush> {
+        return match c {
+            'G' => Some(Brush::Green),
+            'R' => Some(Brush::Red),
+            'Y' => Some(Brush::Yellow),
+            'B' => Some(Brush::Blue),
+            _   => None
+        };
+    }
+
+    /// Returns the color used for drawing the brush.
+    pub fn to_rgb(self) -> &'static str {
+        return match self {
+            Brush::Green  => "#15781b",
+            Brush::Red    => "#882020",
+            Brush::Yellow => "#e68f00",
+            Brush::Blue   => "#003088"
+        };
+    }
+}
+
+impl fmt::Display for Brush {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Brush::Green  => "green",
+            Brush::Red    => "red",
+            Brush::Yellow => "yellow",
+            Brush::Blue   => "blue"
+        };
+
+        return write!(f, "{}", name);
+    }
+}
+
+/// Visual annotations of a position: colored squares, arrows between squares and short text
+/// labels attached to squares. Squares are given by their index.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct Annotations {
+    pub squares: Vec<(u8, Brush)>,
+    pub arrows: Vec<(u8, u8, Brush)>,
+    pub labels: Vec<(u8, String)>
+}
+
+impl Annotations {
+    pub fn new() -> Annotations {
+        return Annotations::default();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.squares.is_empty() && self.arrows.is_empty() && self.labels.is_empty();
+    }
+
+    /// Marks the square with the given brush.
+    pub fn highlight(mut self, sq: u8, brush: Brush) -> Annotations {
+        self.squares.push((sq, brush));
+        return self;
+    }
+
+    /// Draws an arrow from one square to another with the given brush.
+    pub fn arrow(mut self, from: u8, to: u8, brush: Brush) -> Annotations {
+        self.arrows.push((from, to, brush));
+        return self;
+    }
+
+    /// Attaches a text label to the square.
+    pub fn label(mut self, sq: u8, text: &str) -> Annotations {
+        self.labels.push((sq, text.to_string()));
+        return self;
+    }
+
+    /// Adds all annotations of the other set to this one.
+    pub fn extend(&mut self, other: Annotations) {
+        self.squares.extend(other.squares);
+        self.arrows.extend(other.arrows);
+        self.labels.extend(other.labels);
+    }
+
+    /// Encodes the squares and arrows as the commands lichess uses in PGN comments, e.g.
+    /// `[%csl Gd4,Re5] [%cal Ge2e4]`. Labels have no PGN encoding and are left out.
+    pub fn to_pgn_commands(&self) -> String {
+        let mut commands = Vec::new();
+
+        if !self.squares.is_empty() {
+            let squares: Vec<String> = self.squares.iter().map(|&(sq, brush)| format!("{}{}", brush.to_char(), square_name(sq))).collect();
+            commands.push(format!("[%csl {}]", squares.join(",")));
+        }
+
+        if !self.arrows.is_empty() {
+            let arrows: Vec<String> = self.arrows.iter().map(|&(from, to, brush)| format!("{}{}{}", brush.to_char(), square_name(from), square_name(to))).collect();
+            commands.push(format!("[%cal {}]", arrows.join(",")));
+        }
+
+        return commands.join(" ");
+    }
+
+    /// Splits the squares and arrows encoded in a PGN comment off the comment's text. Commands
+    /// that can't be parsed are kept in the text.
+    pub fn from_pgn_comment(comment: &str) -> (Annotations, String) {
+        let mut annotations = Annotations::new();
+        let mut text = Vec::new();
+        let mut rest = comment;
+
+        while let Some(start) = rest.find("[%") {
+            let end = match rest[start..].find(']') {
+                Some(end) => start + end,
+                None      => break
+            };
+
+            if !annotations.parse_command(&rest[start + 2..end]) {
+                text.push(rest[..=end].trim());
+            } else {
+                text.push(rest[..start].trim());
+            }
+
+            rest = &rest[end + 1..];
+        }
+
+        text.push(rest.trim());
+
+        return (annotations, text.into_iter().filter(|t| !t.is_empty()).collect::<Vec<&str>>().join(" "));
+    }
+
+    /// Parses a single csl or cal command without its brackets. Returns false if it isn't one.
+    fn parse_command(&mut self, command: &str) -> bool {
+        let (name, args) = match command.split_once(' ') {
+            Some(split) => split,
+            None        => return false
+        };
+
+        let mut squares = Vec::new();
+        let mut arrows = Vec::new();
+
+        for arg in args.split(',').map(str::trim) {
+            let brush = arg.chars().next().and_then(Brush::from_char);
+            let from = arg.get(1..3).and_then(|s| position::string_to_index(s.to_string()));
+            let to = arg.get(3..5).and_then(|s| position::string_to_index(s.to_string()));
+
+            match (name, brush, from, to, arg.len()) {
+                ("csl", Some(brush), Some(sq), _, 3)          => squares.push((sq, brush)),
+                ("cal", Some(brush), Some(from), Some(to), 5) => arrows.push((from, to, brush)),
+                _                                             => return false
+            }
+        }
+
+        self.squares.extend(squares);
+        self.arrows.extend(arrows);
+
+        return true;
+    }
+
+    /// Draws the position as text like its Display implementation but with highlighted
+    /// squares in brackets, followed by a list of the arrows and labels.
+    pub fn render_text(&self, position: &Position) -> String {
+        let geometry = position.variant().geometry();
+        let mut lines: Vec<String> = Vec::new();
+
+        for rk in (0..geometry.ranks).rev() {
+            let squares: Vec<String> = (0..geometry.files).map(|fl| {
+                let idx = bb_ops::coords_to_index(rk, fl);
+                let piece = piece_char(position, idx).unwrap_or('_');
+
+                return match self.squares.iter().any(|&(sq, _)| sq == idx) {
+                    true  => format!("[{}]", piece),
+                    false => format!(" {} ", piece)
+                };
+            }).collect();
+
+            lines.push(squares.concat().trim_end().to_string());
+        }
+
+        for &(from, to, brush) in &self.arrows {
+            lines.push(format!("{}-{} ({})", square_name(from), square_name(to), brush));
+        }
+
+        for (sq, text) in &self.labels {
+            lines.push(format!("{}: {}", square_name(*sq), text));
+        }
+
+        return lines.join("\n");
+    }
+
+    /// Renders the position as an SVG board from white's point of view with the annotations
+    /// drawn on top of it.
+    pub fn to_svg(&self, position: &Position) -> String {
+        let geometry = position.variant().geometry();
+        let size = SQUARE_SIZE as f64;
+        let width = geometry.files as u32 * SQUARE_SIZE;
+        let height = geometry.ranks as u32 * SQUARE_SIZE;
+
+        // The top left corner and the center of a square.
+        let corner = |sq: u8| -> (f64, f64) {
+            let (row, col) = geometry.square_to_screen(sq, Orientation::WhiteBottom);
+            return (col as f64 * size, row as f64 * size);
+        };
+        let center = |sq: u8| -> (f64, f64) {
+            let (x, y) = corner(sq);
+            return (x + size / 2.0, y + size / 2.0);
+        };
+
+        let mut svg = String::new();
+
+        svg.push_str(&format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n", w=width, h=height));
+
+        for brush in [Brush::Green, Brush::Red, Brush::Yellow, Brush::Blue] {
+            svg.push_str(&format!("  <marker id=\"arrowhead-{}\" orient=\"auto\" markerWidth=\"4\" markerHeight=\"8\" refX=\"2.05\" refY=\"2.01\">\
+                                   <path d=\"M0,0 V4 L3,2 Z\" fill=\"{}\"/></marker>\n", brush, brush.to_rgb()));
+        }
+
+        for rk in 0..geometry.ranks {
+            for fl in 0..geometry.files {
+                let sq = bb_ops::coords_to_index(rk, fl);
+                let (x, y) = corner(sq);
+                let fill = if (rk + fl) % 2 == 0 { "#b58863" } else { "#f0d9b5" };
+
+                svg.push_str(&format!("  <rect x=\"{}\" y=\"{}\" width=\"{s}\" height=\"{s}\" fill=\"{}\"/>\n", x, y, fill, s=SQUARE_SIZE));
+            }
+        }
+
+        for &(sq, brush) in &self.squares {
+            let (x, y) = corner(sq);
+            svg.push_str(&format!("  <rect x=\"{}\" y=\"{}\" width=\"{s}\" height=\"{s}\" fill=\"{}\" opacity=\"0.5\"/>\n", x, y, brush.to_rgb(), s=SQUARE_SIZE));
+        }
+
+        for rk in 0..geometry.ranks {
+            for fl in 0..geometry.files {
+                let sq = bb_ops::coords_to_index(rk, fl);
+
+                if let Some((piece_type, color)) = position.get_piece_and_color_at(sq) {
+                    let (x, y) = center(sq);
+                    let symbol = PIECE_SYMBOLS[color as usize][piece_type as usize];
+
+                    svg.push_str(&format!("  <text x=\"{}\" y=\"{}\" font-size=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>\n", x, y, SQUARE_SIZE - 5, symbol));
+                }
+            }
+        }
+
+        for &(from, to, brush) in &self.arrows {
+            let (x1, y1) = center(from);
+            let (x2, y2) = center(to);
+
+            // End the line before the center, so the tip of the arrow head ends there.
+            let len = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt().max(1.0);
+            let shorten = size / 4.0;
+            let (x2, y2) = (x2 - (x2 - x1) * shorten / len, y2 - (y2 - y1) * shorten / len);
+
+            svg.push_str(&format!("  <line x1=\"{}\" y1=\"{}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"{}\" stroke-width=\"{}\" stroke-linecap=\"round\" opacity=\"0.8\" marker-end=\"url(#arrowhead-{})\"/>\n",
+                                  x1, y1, x2, y2, brush.to_rgb(), SQUARE_SIZE / 5, brush));
+        }
+
+        for (sq, text) in &self.labels {
+            let (x, y) = corner(*sq);
+            svg.push_str(&format!("  <text x=\"{}\" y=\"{}\" font-size=\"12\" fill=\"#000000\">{}</text>\n", x + 2.0, y + 12.0, escape(text)));
+        }
+
+        svg.push_str("</svg>\n");
+
+        return svg;
+    }
+}
+
+/// Returns the notation of the square with the given index.
+fn square_name(sq: u8) -> String {
+    return position::index_to_string(sq).unwrap_or_default();
+}
+
+/// Returns the letter of the piece on the square as used by the Display implementation of
+/// positions.
+fn piece_char(position: &Position, sq: u8) -> Option<char> {
+    let (piece_type, color) = position.get_piece_and_color_at(sq)?;
+    let c = match piece_type {
+        position::PAWN => 'P',
+        _              => position::piece_to_string(piece_type)
+    };
+
+    return Some(if color == WHITE { c } else { c.to_ascii_lowercase() });
+}
+
+/// Escapes the characters that have a special meaning in XML.
+fn escape(text: &str) -> String {
+    return text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sq(name: &str) -> u8 {
+        return position::string_to_index(name.to_string()).unwrap();
+    }
+
+    #[test]
+    fn encodes_pgn_commands() {
+        let annotations = Annotations::new()
+            .highlight(sq("d4"), Brush::Green)
+            .highlight(sq("e5"), Brush::Red)
+            .arrow(sq("e2"), sq("e4"), Brush::Blue)
+            .label(sq("f7"), "weak");
+
+        assert_eq!(annotations.to_pgn_commands(), "[%csl Gd4,Re5] [%cal Be2e4]");
+        assert_eq!(Annotations::new().to_pgn_commands(), "");
+    }
+
+    #[test]
+    fn parses_pgn_comments() {
+        let (annotations, text) = Annotations::from_pgn_comment("Central control [%csl Gd4,Re5] [%cal Be2e4] [%clk 0:05:00]");
+
+        assert_eq!(annotations, Annotations::new().highlight(sq("d4"), Brush::Green).highlight(sq("e5"), Brush::Red).arrow(sq("e2"), sq("e4"), Brush::Blue));
+        assert_eq!(text, "Central control [%clk 0:05:00]");
+
+        let (annotations, text) = Annotations::from_pgn_comment("[%csl Xd4]");
+        assert!(annotations.is_empty());
+        assert_eq!(text, "[%csl Xd4]");
+    }
+
+    #[test]
+    fn renders_annotations() {
+        let position = Position::starting_position();
+        let annotations = Annotations::new().highlight(sq("e2"), Brush::Yellow).arrow(sq("e2"), sq("e4"), Brush::Green).label(sq("e4"), "<center>");
+
+        let text = annotations.render_text(&position);
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines[6], " P  P  P  P [P] P  P  P");
+        assert_eq!(&lines[8..], ["e2-e4 (green)", "e4: <center>"]);
+
+        let svg = annotations.to_svg(&position);
+
+        assert_eq!(svg.matches("<rect").count(), 65);
+        assert_eq!(svg.matches("<line").count(), 1);
+        assert!(svg.contains("&lt;center&gt;"));
+    }
+}
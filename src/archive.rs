@@ -0,0 +1,204 @@
+use std::fs::OpenOptions;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::pgn::{self, PgnGame};
+use crate::storage::Storage;
+
+
+lazy_static! {
+    /// The URLs of the monthly archives in the archive list of chess.com.
+    static ref ARCHIVE_URL: Regex = Regex::new(r#""(https://api\.chess\.com/pub/player/[^"]+/games/\d{4}/\d{2})""#).unwrap();
+}
+
+
+/// The sites whose public APIs game archives can be imported from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Site {
+    Lichess,
+    ChessCom
+}
+
+impl Site {
+    /// Returns the site with the given name, lichess or chess.com.
+    pub fn from_name(name: &str) -> Option<Site> {
+        return match name.to_ascii_lowercase().as_str() {
+            "lichess" | "lichess.org" => Some(Site::Lichess),
+            "chess.com" | "chesscom"  => Some(Site::ChessCom),
+            _                         => None
+        };
+    }
+}
+
+/// Checks if the user name can be put into the URLs of the APIs as it is. Both sites only
+/// allow letters, digits, underscores and hyphens.
+pub fn is_valid_user(user: &str) -> bool {
+    return !user.is_empty() && user.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+}
+
+/// Returns the URL of the games of a lichess user as PGN, the newest max games if given.
+pub fn lichess_games_url(user: &str, max: Option<usize>) -> String {
+    let url = format!("https://lichess.org/api/games/user/{}?clocks=true&opening=true", user);
+
+    return match max {
+        Some(max) => format!("{}&max={}", url, max),
+        None      => url
+    };
+}
+
+/// Returns the URL of the list of monthly game archives of a chess.com user.
+pub fn chesscom_archives_url(user: &str) -> String {
+    return format!("https://api.chess.com/pub/player/{}/games/archives", user.to_ascii_lowercase());
+}
+
+/// Extracts the URLs of the monthly archives from the JSON archive list of chess.com, oldest
+/// month first. Appending /pgn to them gives the games of the month as PGN.
+pub fn chesscom_archive_urls(json: &str) -> Vec<String> {
+    return ARCHIVE_URL.captures_iter(json).map(|captures| captures[1].to_string()).collect();
+}
+
+/// Fetches the games of a user from the site, the newest max games if given. The get
+/// function opens a download of a URL, accepting the given content type. The PGN is read
+/// from the download while it arrives, so its size isn't limited. Games are returned oldest
+/// first.
+pub fn fetch_games_with<F: FnMut(&str, &str) -> io::Result<Box<dyn Read>>>(site: Site, user: &str, max: Option<usize>, mut get: F) -> io::Result<Vec<PgnGame>> {
+    if !is_valid_user(user) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid user name: {}", user)));
+    }
+
+    if site == Site::Lichess {
+        // Lichess sends the newest games first.
+        let mut games = pgn::read_games_from(BufReader::new(get(&lichess_games_url(user, max), "application/x-chess-pgn")?))?;
+        games.reverse();
+
+        return Ok(games);
+    }
+
+    // Chess.com has an archive per month, so they're fetched newest first until there are
+    // enough games.
+    let mut list = String::new();
+    get(&chesscom_archives_url(user), "application/json")?.read_to_string(&mut list)?;
+
+    let archives = chesscom_archive_urls(&list);
+    let mut months = Vec::new();
+    let mut count = 0;
+
+    for url in archives.iter().rev() {
+        if max.is_some_and(|max| count >= max) {
+            break;
+        }
+
+        let games = pgn::read_games_from(BufReader::new(get(&format!("{}/pgn", url), "application/x-chess-pgn")?))?;
+
+        count += games.len();
+        months.push(games);
+    }
+
+    let mut games: Vec<PgnGame> = months.into_iter().rev().flatten().collect();
+
+    if let Some(max) = max {
+        games.drain(..games.len().saturating_sub(max));
+    }
+
+    return Ok(games);
+}
+
+/// Fetches the games of a user from the site over the internet, see fetch_games_with.
+#[cfg(feature = "online")]
+pub fn fetch_games(site: Site, user: &str, max: Option<usize>) -> io::Result<Vec<PgnGame>> {
+    return fetch_games_with(site, user, max, |url, accept| {
+        let response = ureq::get(url).set("Accept", accept).call().map_err(io::Error::other)?;
+
+        // Unlike into_string, the reader has no size limit.
+        return Ok(Box::new(response.into_reader()) as Box<dyn Read>);
+    });
+}
+
+/// Saves imported games in the storage and appends them to the PGN file, whichever are given.
+pub fn save_games(games: &[PgnGame], storage: Option<&mut dyn Storage>, pgn_file: Option<&Path>) -> io::Result<()> {
+    if let Some(storage) = storage {
+        for game in games {
+            storage.save_game(game)?;
+        }
+    }
+
+    if let Some(path) = pgn_file {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        for game in games {
+            let result = game.result.clone().or_else(|| game.tag("Result").map(String::from)).unwrap_or_else(|| String::from("*"));
+
+            writeln!(file, "{}", pgn::write_game(&game.game, &game.tags, &result))?;
+        }
+    }
+
+    return Ok(());
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{GameQuery, MemoryStorage};
+
+    fn month(games: &[(&str, &str)]) -> String {
+        return games.iter().map(|(white, moves)| format!("[White \"{}\"]\n[Result \"*\"]\n\n{} *\n\n", white, moves)).collect();
+    }
+
+    fn download(text: String) -> io::Result<Box<dyn Read>> {
+        return Ok(Box::new(io::Cursor::new(text)));
+    }
+
+    #[test]
+    fn fetches_the_newest_chesscom_months_first() {
+        let list = r#"{"archives":["https://api.chess.com/pub/player/anna/games/2024/01","https://api.chess.com/pub/player/anna/games/2024/02"]}"#;
+        let mut requested = Vec::new();
+
+        let games = fetch_games_with(Site::ChessCom, "Anna", Some(2), |url, _| {
+            requested.push(url.to_string());
+
+            return download(match url {
+                "https://api.chess.com/pub/player/anna/games/archives"    => list.to_string(),
+                "https://api.chess.com/pub/player/anna/games/2024/02/pgn" => month(&[("Feb", "1. d4")]),
+                _                                                         => month(&[("Jan1", "1. e4"), ("Jan2", "1. c4")])
+            });
+        }).unwrap();
+
+        assert_eq!(games.iter().map(|game| game.tag("White").unwrap()).collect::<Vec<_>>(), vec!["Jan2", "Feb"]);
+        assert_eq!(requested.len(), 3);
+        assert_eq!(chesscom_archive_urls(list).len(), 2);
+    }
+
+    #[test]
+    fn fetches_lichess_games_as_pgn() {
+        let games = fetch_games_with(Site::Lichess, "anna", Some(5), |url, accept| {
+            assert_eq!((url, accept), ("https://lichess.org/api/games/user/anna?clocks=true&opening=true&max=5", "application/x-chess-pgn"));
+
+            return download(month(&[("Newest", "1. e4 { [%clk 0:03:00] }"), ("Oldest", "1. d4")]));
+        }).unwrap();
+
+        assert_eq!(games[0].tag("White"), Some("Oldest"));
+        assert!(fetch_games_with(Site::Lichess, "anna/../x", None, |_, _| download(String::new())).is_err());
+        assert_eq!(Site::from_name("Chess.com"), Some(Site::ChessCom));
+        assert_eq!(Site::from_name("fics"), None);
+    }
+
+    #[test]
+    fn saves_games_in_storage_and_pgn() {
+        let games = pgn::read_games(&month(&[("Anna", "1. e4 e5"), ("Ben", "1. d4")]));
+        let path = std::env::temp_dir().join(format!("chess-archive-{}.pgn", std::process::id()));
+        let mut storage = MemoryStorage::new();
+
+        save_games(&games, Some(&mut storage), Some(&path)).unwrap();
+        save_games(&games[..1], None, Some(&path)).unwrap();
+
+        let saved = std::fs::read_to_string(&path);
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(pgn::read_games(&saved.unwrap()).len(), 3);
+        assert_eq!(storage.games(&GameQuery::default()).unwrap().len(), 2);
+    }
+}
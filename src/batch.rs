@@ -0,0 +1,79 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::position::Position;
+use crate::runner::concurrency;
+use crate::search::{self, Iteration, Limits};
+
+
+/// Searches every position with the given limits, spreading the positions over one thread per
+/// available core. The limits apply to each position on its own, e.g. a time limit is the
+/// time per position. Returns the last completed iteration of each position in the order of
+/// the positions, None for positions without legal moves.
+pub fn analyze_batch(positions: &[Position], limits: &Limits) -> Vec<Option<Iteration>> {
+    return analyze_batch_with_threads(positions, limits, concurrency::available_cores());
+}
+
+/// Like analyze_batch, but with the given number of threads.
+pub fn analyze_batch_with_threads(positions: &[Position], limits: &Limits, threads: usize) -> Vec<Option<Iteration>> {
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1).min(positions.len()) {
+            let sender = sender.clone();
+            let next = &next;
+
+            scope.spawn(move || {
+                loop {
+                    let idx = next.fetch_add(1, Ordering::SeqCst);
+
+                    let position = match positions.get(idx) {
+                        Some(position) => position,
+                        None           => break
+                    };
+
+                    if sender.send((idx, search::iterative_deepening(position, limits, |_| ()))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+
+    drop(sender);
+
+    let mut results: Vec<(usize, Option<Iteration>)> = receiver.iter().collect();
+    results.sort_by_key(|(idx, _)| *idx);
+
+    return results.into_iter().map(|(_, result)| result).collect();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn returns_the_results_in_order() {
+        let fens = [
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1",
+            "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1",
+            "4k3/2p5/3n4/8/8/8/3R4/4K3 w - - 0 1"
+        ];
+
+        let positions: Vec<Position> = fens.iter().map(|fen| Position::from_fen(fen.to_string()).unwrap()).collect();
+        let limits = Limits { depth: Some(3), ..Limits::default() };
+        let expected: Vec<Option<Iteration>> = positions.iter().map(|position| search::iterative_deepening(position, &limits, |_| ())).collect();
+
+        for threads in [1, 3, 8] {
+            assert_eq!(analyze_batch_with_threads(&positions, &limits, threads), expected);
+        }
+
+        // The stalemate has no result.
+        assert_eq!(expected[2], None);
+        assert!(analyze_batch(&[], &limits).is_empty());
+    }
+}
@@ -0,0 +1,75 @@
+use std::time::{Duration, Instant};
+
+use crate::position::Position;
+use crate::search::{self, Limits};
+use crate::tt::TranspositionTable;
+
+
+/// The depth each position is searched to by default.
+pub const DEFAULT_DEPTH: u8 = 6;
+
+/// A mix of opening, middlegame and endgame positions with tactics, so the bench exercises
+/// all parts of the search.
+pub const POSITIONS: [&str; 8] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP2BPPP/R2QKB1R w KQ - 0 1",
+    "2r3k1/pp3ppp/2n5/3p4/3P4/2P2N2/P4PPP/R5K1 b - - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+    "8/8/4k3/8/2p5/8/B2K4/8 w - - 0 1"
+];
+
+/// The outcome of a bench run.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BenchResult {
+    /// The number of positions searched. It only depends on the search, so it serves as a
+    /// signature of the engine's behaviour.
+    pub nodes: u64,
+    /// The nodes searched for each of the bench positions, which tell which positions a
+    /// change of the signature comes from.
+    pub positions: Vec<u64>,
+    pub elapsed: Duration
+}
+
+impl BenchResult {
+    pub fn nodes_per_second(&self) -> u64 {
+        return (self.nodes as f64 / self.elapsed.as_secs_f64().max(1e-9)) as u64;
+    }
+}
+
+/// Searches all bench positions to the given depth with a fresh transposition table each,
+/// exactly like the engine does when playing.
+pub fn run(depth: u8) -> BenchResult {
+    let start = Instant::now();
+    let mut positions = Vec::new();
+
+    for fen in POSITIONS {
+        let position = Position::from_fen(fen.to_string()).unwrap();
+        let limits = Limits { depth: Some(depth), null_move: true, futility: true, probcut: true, ..Limits::default() };
+        let mut tt = TranspositionTable::default();
+        let mut nodes = 0;
+
+        search::iterative_deepening_with_table(&position, &limits, &mut tt, |iteration| nodes += iteration.nodes);
+        positions.push(nodes);
+    }
+
+    return BenchResult { nodes: positions.iter().sum(), positions, elapsed: start.elapsed() };
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_count_is_reproducible() {
+        let first = run(2);
+
+        assert!(first.positions.iter().all(|&nodes| nodes > 0));
+        assert_eq!(first.positions.len(), POSITIONS.len());
+        assert_eq!(first.positions.iter().sum::<u64>(), first.nodes);
+        assert_eq!(run(2).positions, first.positions);
+    }
+}
@@ -1,4 +1,7 @@
-use crate::patterns;
+use crate::bitboards::patterns;
+#[cfg(target_arch = "x86_64")]
+use crate::cpu;
+use crate::bitboards::geometry::{self, RANKS, FILES};
 
 pub fn set_idx_bit(bb: u64, idx: u8) -> u64 {
     return bb | index_lookup_mask(idx);
@@ -25,9 +28,7 @@ pub fn set_idx_bits(mut bb: u64, idxs: Vec<u8>) -> u64 {
 }
 
 pub fn index_lookup_mask(idx: u8) -> u64 {
-    unsafe{
-        return patterns::IDX_MASKS[idx as usize];
-    }
+    return patterns::IDX_MASKS[idx as usize];
 }
 
 pub fn coords_lookup_mask(y: u8, x: u8) -> u64 {
@@ -42,13 +43,30 @@ pub fn coords_lookup(bb: u64, y: u8, x: u8) -> bool {
     return index_lookup(bb, coords_to_index(y, x));
 }
 
+/// Returns the indices of all set bits in ascending order. CPUs with POPCNT and BMI1 use an
+/// implementation compiled for them.
 pub fn idx_bitscan(bb: u64) -> Vec<u8> {
-    let mut idxs = Vec::new();
+    #[cfg(target_arch = "x86_64")]
+    if cpu::features().popcnt {
+        return unsafe { idx_bitscan_popcnt(bb) };
+    }
 
-    for idx in 0..64 {
-        if index_lookup(bb, idx) {
-            idxs.push(idx);
-        }
+    return idx_bitscan_generic(bb);
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "popcnt,bmi1")]
+unsafe fn idx_bitscan_popcnt(bb: u64) -> Vec<u8> {
+    return idx_bitscan_generic(bb);
+}
+
+#[inline(always)]
+fn idx_bitscan_generic(mut bb: u64) -> Vec<u8> {
+    let mut idxs = Vec::with_capacity(bb.count_ones() as usize);
+
+    while bb != 0 {
+        idxs.push(bb.trailing_zeros() as u8);
+        bb &= bb - 1;
     }
 
     return idxs;
@@ -57,8 +75,8 @@ pub fn idx_bitscan(bb: u64) -> Vec<u8> {
 pub fn coords_bitscan(bb: u64) -> Vec<(u8, u8)> {
     let mut coords = Vec::new();
 
-    for y in 0..8 {
-        for x in 0..8 {
+    for y in 0..RANKS {
+        for x in 0..FILES {
             if coords_lookup(bb, y, x) {
                 coords.push((y, x));
             }
@@ -69,26 +87,26 @@ pub fn coords_bitscan(bb: u64) -> Vec<(u8, u8)> {
 }
 
 pub fn coords_to_index(y: u8, x: u8) -> u8 {
-    return y * 8 + x;
+    return y * FILES + x;
 }
 
 pub fn index_to_coords(idx: u8) -> (u8, u8) {
-    return (idx / 8, idx % 8);
+    return (idx / FILES, idx % FILES);
 }
 
 pub fn is_legal_square(y: i8, x: i8) -> bool {
-    return y >= 0 && y < 8 && x >= 0 && x < 8;
+    return geometry::STANDARD.contains(y, x);
 }
 
 pub fn print_bitboard(bb: u64) {
-    for rank in (0..8).rev() {
-        for file in 0..8 {
+    for rank in (0..RANKS).rev() {
+        for file in 0..FILES {
             if coords_lookup(bb, rank, file) {
                 print!("X ");
             } else {
                 print!(". ");
             }
         }
-        println!("");
+        println!();
     }
 }
@@ -1,4 +1,5 @@
-use crate::bitboards::bb_ops;
+use crate::bitboards::{bb_ops, magic_bb};
+use crate::bitboards::geometry::{RANKS, FILES};
 use std::cmp;
 
 
@@ -7,26 +8,26 @@ use std::cmp;
 /// map with all attacked squares set to 1.
 ///
 /// Code adapted from: https://www.chessprogramming.org/Looking_for_Magics
-fn bishop_attacks(sq: u8, blockers: u64) -> u64 {
+pub(crate) fn bishop_attacks(sq: u8, blockers: u64) -> u64 {
     let mut result: u64 = 0;
 
     let (rk, fl) = bb_ops::index_to_coords(sq);
 
-    for d in 1..cmp::min(8-rk, 8-fl) {
+    for d in 1..cmp::min(RANKS-rk, FILES-fl) {
         result = bb_ops::set_coords_bit(result, rk+d, fl+d);
         if bb_ops::coords_lookup(blockers, rk+d, fl+d) {
             break;
         }
     }
 
-    for d in 1..cmp::min(8-rk, fl+1) {
+    for d in 1..cmp::min(RANKS-rk, fl+1) {
         result = bb_ops::set_coords_bit(result, rk+d, fl-d);
         if bb_ops::coords_lookup(blockers, rk+d, fl-d) {
             break;
         }
     }
 
-    for d in 1..cmp::min(rk+1, 8-fl) {
+    for d in 1..cmp::min(rk+1, FILES-fl) {
         result = bb_ops::set_coords_bit(result, rk-d, fl+d);
         if bb_ops::coords_lookup(blockers, rk-d, fl+d) {
             break;
@@ -43,6 +44,8 @@ fn bishop_attacks(sq: u8, blockers: u64) -> u64 {
     return result;
 }
 
+/// Looks up all squares attacked by a bishop on the given square in the magic
+/// bitboard table.
 pub fn get_bishop_attacks(sq: u8, blockers: u64) -> u64 {
-    return bishop_attacks(sq, blockers);
+    return magic_bb::bishop_attacks(sq, blockers);
 }
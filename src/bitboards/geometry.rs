@@ -0,0 +1,172 @@
+/// The number of ranks and files of the bitboard layout. Every square is stored at the
+/// index rank * FILES + file. Boards smaller than 8x8 are embedded into the lower left
+/// corner of this layout.
+pub const RANKS: u8 = 8;
+pub const FILES: u8 = 8;
+pub const NUM_SQUARES: u8 = RANKS * FILES;
+
+
+/// Describes the dimensions of the board a game is played on.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Geometry {
+    pub ranks: u8,
+    pub files: u8
+}
+
+/// How a board is shown on the screen, given by the side of the board facing the viewer.
+/// The rotated orientations show the board turned by 90 degrees, e.g. for boards lying sideways
+/// between the players.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Orientation {
+    WhiteBottom,
+    BlackBottom,
+    /// White's pieces on the left, the a-file at the top.
+    WhiteLeft,
+    /// White's pieces on the right, the a-file at the bottom.
+    WhiteRight
+}
+
+impl Orientation {
+    /// Returns the orientation seen from the other side of the board.
+    pub fn flipped(self) -> Orientation {
+        return match self {
+            Orientation::WhiteBottom => Orientation::BlackBottom,
+            Orientation::BlackBottom => Orientation::WhiteBottom,
+            Orientation::WhiteLeft   => Orientation::WhiteRight,
+            Orientation::WhiteRight  => Orientation::WhiteLeft
+        };
+    }
+}
+
+/// The regular 8x8 chess board.
+pub const STANDARD: Geometry = Geometry { ranks: RANKS, files: FILES };
+
+impl Geometry {
+    /// Describes a board with the given number of ranks and files. Boards can't be larger
+    /// than the bitboard layout.
+    pub const fn new(ranks: u8, files: u8) -> Geometry {
+        assert!(ranks <= RANKS && files <= FILES);
+
+        return Geometry { ranks, files };
+    }
+
+    /// Checks if the coordinates describe a square on this board.
+    pub fn contains(&self, rk: i8, fl: i8) -> bool {
+        return (0..self.ranks as i8).contains(&rk) && (0..self.files as i8).contains(&fl);
+    }
+
+    /// Returns a bitboard marking all squares of this board.
+    pub fn board_mask(&self) -> u64 {
+        let mut mask = 0;
+
+        for rk in 0..self.ranks {
+            mask |= self.rank_mask(rk);
+        }
+
+        return mask;
+    }
+
+    /// Returns a bitboard marking all squares on the given rank of this board.
+    pub fn rank_mask(&self, rk: u8) -> u64 {
+        let rank: u64 = (1 << self.files) - 1;
+
+        return rank << (rk * FILES);
+    }
+
+    /// Returns the number of rows and columns of the board on the screen.
+    pub fn screen_size(&self, orientation: Orientation) -> (u8, u8) {
+        return match orientation {
+            Orientation::WhiteBottom | Orientation::BlackBottom => (self.ranks, self.files),
+            Orientation::WhiteLeft | Orientation::WhiteRight    => (self.files, self.ranks)
+        };
+    }
+
+    /// Converts rank and file into the row and column on the screen, counted from the top left
+    /// corner.
+    pub fn coords_to_screen(&self, rk: u8, fl: u8, orientation: Orientation) -> (u8, u8) {
+        return match orientation {
+            Orientation::WhiteBottom => (self.ranks - 1 - rk, fl),
+            Orientation::BlackBottom => (rk, self.files - 1 - fl),
+            Orientation::WhiteLeft   => (fl, rk),
+            Orientation::WhiteRight  => (self.files - 1 - fl, self.ranks - 1 - rk)
+        };
+    }
+
+    /// Converts a row and column on the screen into rank and file. Returns None if they're
+    /// outside of the board.
+    pub fn screen_to_coords(&self, row: u8, col: u8, orientation: Orientation) -> Option<(u8, u8)> {
+        let (rows, cols) = self.screen_size(orientation);
+
+        if row >= rows || col >= cols {
+            return None;
+        }
+
+        let (rk, fl) = match orientation {
+            Orientation::WhiteBottom => (self.ranks - 1 - row, col),
+            Orientation::BlackBottom => (row, self.files - 1 - col),
+            Orientation::WhiteLeft   => (col, row),
+            Orientation::WhiteRight  => (self.ranks - 1 - col, self.files - 1 - row)
+        };
+
+        return Some((rk, fl));
+    }
+
+    /// Converts the index of a square into the row and column on the screen.
+    pub fn square_to_screen(&self, sq: u8, orientation: Orientation) -> (u8, u8) {
+        return self.coords_to_screen(sq / FILES, sq % FILES, orientation);
+    }
+
+    /// Converts a row and column on the screen into the index of the square shown there.
+    pub fn screen_to_square(&self, row: u8, col: u8, orientation: Orientation) -> Option<u8> {
+        return self.screen_to_coords(row, col, orientation).map(|(rk, fl)| rk * FILES + fl);
+    }
+
+    /// Returns the rank the pieces of the given color start on (0 for white).
+    pub fn back_rank(&self, color: u8) -> u8 {
+        return if color == 0 { 0 } else { self.ranks - 1 };
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIENTATIONS: [Orientation; 4] = [Orientation::WhiteBottom, Orientation::BlackBottom, Orientation::WhiteLeft, Orientation::WhiteRight];
+
+    #[test]
+    fn places_the_corners_on_the_screen() {
+        // a1 and h1 on the regular board.
+        assert_eq!(STANDARD.coords_to_screen(0, 0, Orientation::WhiteBottom), (7, 0));
+        assert_eq!(STANDARD.coords_to_screen(0, 0, Orientation::BlackBottom), (0, 7));
+        assert_eq!(STANDARD.coords_to_screen(0, 0, Orientation::WhiteLeft), (0, 0));
+        assert_eq!(STANDARD.coords_to_screen(0, 0, Orientation::WhiteRight), (7, 7));
+        assert_eq!(STANDARD.coords_to_screen(0, 7, Orientation::WhiteLeft), (7, 0));
+
+        let small = Geometry::new(5, 4);
+        assert_eq!(small.screen_size(Orientation::WhiteLeft), (4, 5));
+        assert_eq!(small.coords_to_screen(4, 3, Orientation::WhiteRight), (0, 0));
+    }
+
+    #[test]
+    fn screen_coordinates_round_trip() {
+        for geometry in [STANDARD, Geometry::new(5, 4)] {
+            for orientation in ORIENTATIONS {
+                for rk in 0..geometry.ranks {
+                    for fl in 0..geometry.files {
+                        let (row, col) = geometry.coords_to_screen(rk, fl, orientation);
+
+                        assert_eq!(geometry.screen_to_coords(row, col, orientation), Some((rk, fl)));
+                    }
+                }
+
+                let (rows, cols) = geometry.screen_size(orientation);
+                assert_eq!(geometry.screen_to_coords(rows, 0, orientation), None);
+                assert_eq!(geometry.screen_to_coords(0, cols, orientation), None);
+            }
+        }
+
+        assert_eq!(STANDARD.screen_to_square(0, 0, Orientation::BlackBottom), Some(7));
+        assert_eq!(Orientation::WhiteLeft.flipped(), Orientation::WhiteRight);
+    }
+}
@@ -1,10 +1,8 @@
-use crate::patterns;
+use crate::bitboards::patterns;
 
 
 pub fn get_king_attacks(sq: u8) -> u64 {
-    unsafe {
-        return patterns::KING_MASKS[sq as usize];
-    }
+    return patterns::KING_MASKS[sq as usize];
 }
 
 
@@ -15,4 +13,9 @@ pub fn get_ks_castling_squares(color: u8) -> u64 {
 
 pub fn get_qs_castling_squares(color: u8) -> u64 {
     return patterns::QS_CASTLING_MASKS[color as usize];
-}
\ No newline at end of file
+}
+
+
+pub fn get_qs_castling_safe_squares(color: u8) -> u64 {
+    return patterns::QS_CASTLING_SAFE_MASKS[color as usize];
+}
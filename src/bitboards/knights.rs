@@ -1,4 +1,4 @@
-use crate::patterns;
+use crate::bitboards::patterns;
 
 
 pub fn get_knight_attacks(sq: u8) -> u64 {
@@ -1,38 +1,448 @@
-use crate::bb_ops;
-use crate::patterns;
-use crate::std::vec;
+use std::mem;
 
-// Magic numbers for all rook positions;
-pub static ROOK_MAGICS: [u32; 64] = [0; 64];
+use crate::bitboards::{bb_ops, rooks, bishops};
+use crate::bitboards::geometry::{RANKS, FILES, NUM_SQUARES};
+use crate::cpu::{self, Features};
 
-// Magic numbers for all bishop positions;
-pub static BISHOP_MAGICS: [u32; 64] = [0; 64];
+use lazy_static::lazy_static;
 
-fn find_rook_magic(idx: u8) -> u64 {
-    let block_att_tble = vec::Vec::new();
 
-    
+/// Looks up the attacks of a slider on one square. The relevant blockers are hashed by
+/// multiplying them with the magic number, which gives the index of the attacks in the slice
+/// of the shared attack table starting at the offset. CPUs with BMI2 extract the relevant
+/// blockers with PEXT instead, which needs no magic number.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Magic {
+    /// The squares whose blockers change the attacks, i.e. the rays without the edge squares.
+    pub mask: u64,
+    pub magic: u64,
+    pub shift: u32,
+    pub offset: usize
 }
 
-fn find_rook_magics() {
-    for i in 0..64 {
-        unsafe {
-            ROOK_MAGICS[i] = find_rook_magic(i);
+impl Magic {
+    fn index(&self, blockers: u64, pext: bool) -> usize {
+        if pext {
+            return self.offset + pext_index(blockers, self.mask) as usize;
         }
+
+        return self.offset + ((blockers & self.mask).wrapping_mul(self.magic) >> self.shift) as usize;
+    }
+}
+
+fn pext_index(blockers: u64, mask: u64) -> u64 {
+    // Tables are only indexed with PEXT if the CPU supports BMI2.
+    #[cfg(target_arch = "x86_64")]
+    return unsafe { cpu::pext(blockers, mask) };
+
+    #[cfg(not(target_arch = "x86_64"))]
+    return cpu::pext_generic(blockers, mask);
+}
+
+/// The attacks of rooks and bishops for all squares and blockers, packed into a single
+/// allocation so lookups of different squares and pieces stay close in memory.
+pub struct AttackTable {
+    rooks: [Magic; NUM_SQUARES as usize],
+    bishops: [Magic; NUM_SQUARES as usize],
+    attacks: Vec<u64>,
+    pext: bool
+}
+
+/// How much memory the attack table uses.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MemoryUsage {
+    pub rook_entries: usize,
+    pub bishop_entries: usize,
+    /// The size of the attacks and the magics in bytes.
+    pub bytes: usize
+}
+
+lazy_static! {
+    static ref TABLE: AttackTable = AttackTable::new(&ROOK_MAGICS, &BISHOP_MAGICS, cpu::features());
+}
+
+impl AttackTable {
+    /// Builds the table from the magic numbers of all squares using the slow ray generators.
+    /// PEXT is used if the given features and the CPU support BMI2.
+    pub fn new(rook_magics: &[u64; NUM_SQUARES as usize], bishop_magics: &[u64; NUM_SQUARES as usize], features: Features) -> AttackTable {
+        let mut table = AttackTable {
+            rooks: [Magic::default(); NUM_SQUARES as usize],
+            bishops: [Magic::default(); NUM_SQUARES as usize],
+            attacks: Vec::new(),
+            pext: features.bmi2 && cpu::features().bmi2
+        };
+
+        for sq in 0..NUM_SQUARES {
+            table.rooks[sq as usize] = table.add(sq, rook_magics[sq as usize], rooks::rook_attacks);
+        }
+
+        for sq in 0..NUM_SQUARES {
+            table.bishops[sq as usize] = table.add(sq, bishop_magics[sq as usize], bishops::bishop_attacks);
+        }
+
+        return table;
+    }
+
+    /// Appends the attacks of a slider on the given square to the table. Panics if the magic
+    /// number maps blockers with different attacks to the same entry.
+    fn add(&mut self, sq: u8, magic: u64, attacks: fn(u8, u64) -> u64) -> Magic {
+        let mask = relevant_blockers(sq, attacks);
+        let magic = Magic { mask, magic, shift: 64 - mask.count_ones(), offset: self.attacks.len() };
+
+        self.attacks.resize(self.attacks.len() + (1 << mask.count_ones()), 0);
+
+        for blockers in subsets(mask) {
+            let entry = &mut self.attacks[magic.index(blockers, self.pext)];
+
+            assert!(*entry == 0 || *entry == attacks(sq, blockers), "Bad magic number for square {}", sq);
+            *entry = attacks(sq, blockers);
+        }
+
+        return magic;
+    }
+
+    pub fn rook_attacks(&self, sq: u8, blockers: u64) -> u64 {
+        return self.attacks[self.rooks[sq as usize].index(blockers, self.pext)];
+    }
+
+    pub fn bishop_attacks(&self, sq: u8, blockers: u64) -> u64 {
+        return self.attacks[self.bishops[sq as usize].index(blockers, self.pext)];
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let rook_entries = self.rooks.iter().map(|magic| 1 << (64 - magic.shift)).sum();
+
+        return MemoryUsage {
+            rook_entries,
+            bishop_entries: self.attacks.len() - rook_entries,
+            bytes: self.attacks.len() * mem::size_of::<u64>() + mem::size_of::<[Magic; 2 * NUM_SQUARES as usize]>()
+        };
+    }
+}
+
+/// Returns the squares attacked by a rook on the given square.
+pub fn rook_attacks(sq: u8, blockers: u64) -> u64 {
+    return TABLE.rook_attacks(sq, blockers);
+}
+
+/// Returns the squares attacked by a bishop on the given square.
+pub fn bishop_attacks(sq: u8, blockers: u64) -> u64 {
+    return TABLE.bishop_attacks(sq, blockers);
+}
+
+/// Returns the squares a rook on the given square attacks through the first blockers on its
+/// rays that are also in the given set (x-ray attacks), without the squares it attacks
+/// directly.
+pub fn rook_xray_attacks(sq: u8, blockers: u64, through: u64) -> u64 {
+    let attacks = rook_attacks(sq, blockers);
+
+    return attacks ^ rook_attacks(sq, blockers ^ (attacks & blockers & through));
+}
+
+/// Returns the squares a bishop on the given square attacks through the first blockers on its
+/// rays that are also in the given set (x-ray attacks), without the squares it attacks
+/// directly.
+pub fn bishop_xray_attacks(sq: u8, blockers: u64, through: u64) -> u64 {
+    let attacks = bishop_attacks(sq, blockers);
+
+    return attacks ^ bishop_attacks(sq, blockers ^ (attacks & blockers & through));
+}
+
+/// Returns how much memory the shared attack table uses.
+pub fn memory_usage() -> MemoryUsage {
+    return TABLE.memory_usage();
+}
+
+/// Returns the squares whose blockers change the attacks of a slider on the given square.
+/// Blockers on the last square of a ray don't matter because it's attacked either way.
+fn relevant_blockers(sq: u8, attacks: fn(u8, u64) -> u64) -> u64 {
+    let (rk, fl) = bb_ops::index_to_coords(sq);
+    let mut edges = 0;
+
+    for i in 0..NUM_SQUARES {
+        let (r, f) = bb_ops::index_to_coords(i);
+
+        if (r != rk && (r == 0 || r == RANKS - 1)) || (f != fl && (f == 0 || f == FILES - 1)) {
+            edges = bb_ops::set_idx_bit(edges, i);
+        }
+    }
+
+    return attacks(sq, 0) & !edges;
+}
+
+/// Returns all subsets of the given mask, starting with the empty set.
+fn subsets(mask: u64) -> Vec<u64> {
+    let mut subsets = vec![0];
+    let mut subset: u64 = 0;
+
+    // The Carry-Rippler trick: adding one to the bits of the mask enumerates all subsets.
+    loop {
+        subset = subset.wrapping_sub(mask) & mask;
+
+        if subset == 0 {
+            return subsets;
+        }
+
+        subsets.push(subset);
     }
 }
 
-fn find_bishop_magic(idx: u8) {}
+/// Searches a magic number for a slider on the given square by trying random numbers with few
+/// set bits. The same seed always finds the same number.
+pub fn find_magic(sq: u8, attacks: fn(u8, u64) -> u64, seed: u64) -> u64 {
+    let mask = relevant_blockers(sq, attacks);
+    let shift = 64 - mask.count_ones();
+    let blockers = subsets(mask);
+    let expected: Vec<u64> = blockers.iter().map(|b| attacks(sq, *b)).collect();
+
+    let mut state = seed.max(1);
+    let mut random = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    let mut used = vec![0; 1 << mask.count_ones()];
 
-fn find_bishop_magics() {
-    for i in 0..64 {
-        unsafe {
-            BISHOP_MAGICS[i] = find_bishop_magic(i);
+    loop {
+        let magic = random() & random() & random();
+
+        // Good magic numbers map the mask to many of the high bits used as index.
+        if (mask.wrapping_mul(magic) >> 56).count_ones() < 6 {
+            continue;
+        }
+
+        used.iter_mut().for_each(|entry| *entry = 0);
+
+        let collides = blockers.iter().zip(&expected).any(|(b, attacks)| {
+            let entry = &mut used[(b.wrapping_mul(magic) >> shift) as usize];
+            let collision = *entry != 0 && *entry != *attacks;
+
+            *entry = *attacks;
+
+            return collision;
+        });
+
+        if !collides {
+            return magic;
         }
     }
 }
 
-pub fn find_magics() {
-    find_rook_magics();
-    find_bishop_magics();
+/// The magic numbers of rooks on all squares, found by find_magic.
+pub const ROOK_MAGICS: [u64; NUM_SQUARES as usize] = [
+    0x2080002080400010,
+    0xc140022000401000,
+    0xa100084012200300,
+    0x1080041000800800,
+    0x8080080004008002,
+    0x200100108040200,
+    0x4000114020800b0,
+    0x80032480005100,
+    0x808004c0033080,
+    0x8480c02010004000,
+    0x4081002000104100,
+    0xd000810010220,
+    0x1001001004080100,
+    0x22000942000490,
+    0xa002104220008,
+    0xd002000382004415,
+    0x423c208004400084,
+    0x410004000200040,
+    0x20008020801000,
+    0x80808010000800,
+    0xa01818008001400,
+    0x1000808004000200,
+    0x40002080110,
+    0x80080a0003064084,
+    0x20410100208000,
+    0x200040401000,
+    0x8000200100401100,
+    0x4011900100082500,
+    0x9008040180080080,
+    0x20080800400,
+    0x81021400500821,
+    0x200040200208051,
+    0x40400032800084,
+    0x10802008804004,
+    0x1002001004010,
+    0x14800800801000,
+    0x2001022000805,
+    0x52000400808002,
+    0x1c080104000210,
+    0x240e110882000044,
+    0x2c00069808002,
+    0x140006810006000,
+    0x20200010008080,
+    0x82080a0020120040,
+    0x44110008010004,
+    0x2000204008080,
+    0x400011008040002,
+    0x255000040810002,
+    0x42182243008200,
+    0x20404000201000c0,
+    0x220048801200,
+    0x2008221002090100,
+    0x293000608001100,
+    0x810800200040080,
+    0x410810210080400,
+    0x1001310400804200,
+    0x16254100820412,
+    0x600300801a400221,
+    0x4202004020100882,
+    0x1b0090020051001,
+    0x211001408001013,
+    0x221000400020801,
+    0xc488100142081094,
+    0x2c4004100208c02,
+];
+
+/// The magic numbers of bishops on all squares, found by find_magic.
+pub const BISHOP_MAGICS: [u64; NUM_SQUARES as usize] = [
+    0x8008029802002200,
+    0x2820012102048104,
+    0x800d502004009,
+    0x82a0020424000,
+    0x20d4242080052200,
+    0x2113008008008,
+    0x40440208400200,
+    0x140108401201008,
+    0xc809461004410400,
+    0x149888801040822,
+    0x2400102080890100,
+    0x20040400846000,
+    0x4008020210200020,
+    0x7401220110090004,
+    0x8581040402880480,
+    0x220209044100480,
+    0x422c010224210,
+    0x10001290164082,
+    0x104100208220600,
+    0x4202802430020,
+    0x4004040480a00005,
+    0x6008241100100,
+    0x9500920100909000,
+    0x2301022045082140,
+    0x2402220010208220,
+    0x810083210020080,
+    0x10c100002048154,
+    0x40040010410020,
+    0x159010008104000,
+    0x2001220001004100,
+    0x88040060c4011c00,
+    0x40841002f0402,
+    0x2028441004420200,
+    0x1162011023214203,
+    0x201080100081040,
+    0x8422500820040400,
+    0x8028020400001100,
+    0xc02009100220041,
+    0x2808010100084808,
+    0x2001010025010c00,
+    0x2823840102049,
+    0x40511102d0820,
+    0x1008c41009000,
+    0x1a00084200920800,
+    0x8443180103001014,
+    0x8500080200200,
+    0x4002082101008410,
+    0x8480860800840,
+    0x400808808c00400,
+    0x2240118980000,
+    0x2024a08242002,
+    0x20500508420a190c,
+    0x81042020880,
+    0x81a0100310010500,
+    0x1020201408888412,
+    0x10140804802100,
+    0x4041003210040400,
+    0xc428002108021040,
+    0x100028042084400,
+    0x10002502228800,
+    0x508502030821202,
+    0x1c0804a00401820a,
+    0x8218410108100,
+    0xa0041000404080,
+];
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::training::Rng;
+
+    #[test]
+    fn lookups_match_the_slow_generators() {
+        let mut rng = Rng::new(3);
+
+        for sq in 0..NUM_SQUARES {
+            for _ in 0..100 {
+                let blockers = rng.next_u64() & rng.next_u64();
+
+                assert_eq!(rook_attacks(sq, blockers), rooks::rook_attacks(sq, blockers));
+                assert_eq!(bishop_attacks(sq, blockers), bishops::bishop_attacks(sq, blockers));
+            }
+        }
+    }
+
+    #[test]
+    fn attacks_through_the_given_blockers() {
+        let sq = |y, x| bb_ops::coords_to_index(y, x);
+
+        // A rook on a1 behind a piece on a3 and another one on a6.
+        let blockers = bb_ops::set_idx_bits(0, vec![sq(0, 0), sq(2, 0), sq(5, 0)]);
+        let through = bb_ops::index_lookup_mask(sq(2, 0));
+
+        assert_eq!(rook_xray_attacks(sq(0, 0), blockers, through), bb_ops::set_idx_bits(0, vec![sq(3, 0), sq(4, 0), sq(5, 0)]));
+        assert_eq!(rook_xray_attacks(sq(0, 0), blockers, 0), 0);
+
+        // A bishop on c1 sees through b2 to a3, but b2 isn't in its way to h6.
+        let blockers = bb_ops::set_idx_bits(0, vec![sq(0, 2), sq(1, 1)]);
+        let through = bb_ops::index_lookup_mask(sq(1, 1));
+
+        assert_eq!(bishop_xray_attacks(sq(0, 2), blockers, through), bb_ops::index_lookup_mask(sq(2, 0)));
+    }
+
+    #[test]
+    fn magic_and_pext_tables_agree() {
+        let mut rng = Rng::new(5);
+        let magic = AttackTable::new(&ROOK_MAGICS, &BISHOP_MAGICS, Features::GENERIC);
+        let pext = AttackTable::new(&ROOK_MAGICS, &BISHOP_MAGICS, Features { bmi2: true, ..Features::GENERIC });
+
+        assert!(!magic.pext);
+        assert_eq!(pext.pext, cpu::features().bmi2);
+
+        for sq in 0..NUM_SQUARES {
+            let blockers = rng.next_u64() & rng.next_u64();
+
+            assert_eq!(magic.rook_attacks(sq, blockers), pext.rook_attacks(sq, blockers));
+            assert_eq!(magic.bishop_attacks(sq, blockers), pext.bishop_attacks(sq, blockers));
+        }
+    }
+
+    #[test]
+    fn packs_all_attacks_into_one_table() {
+        let usage = memory_usage();
+
+        assert_eq!((usage.rook_entries, usage.bishop_entries), (102400, 5248));
+        assert_eq!(usage.bytes, 107648 * 8 + 128 * mem::size_of::<Magic>());
+        assert_eq!(TABLE.bishops[0].offset, usage.rook_entries);
+    }
+
+    #[test]
+    fn finds_magic_numbers() {
+        let d4 = bb_ops::coords_to_index(3, 3);
+        let magic = find_magic(d4, bishops::bishop_attacks, 1);
+
+        assert_eq!(relevant_blockers(d4, bishops::bishop_attacks).count_ones(), 9);
+        assert_eq!(relevant_blockers(0, rooks::rook_attacks).count_ones(), 12);
+
+        let mut magics = BISHOP_MAGICS;
+        magics[d4 as usize] = magic;
+
+        let table = AttackTable::new(&ROOK_MAGICS, &magics, Features::GENERIC);
+
+        assert_eq!(table.bishop_attacks(d4, 0), bishops::bishop_attacks(d4, 0));
+    }
 }
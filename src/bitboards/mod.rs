@@ -1,7 +1,10 @@
 pub mod bb_ops;
 pub mod bishops;
+pub mod geometry;
 pub mod kings;
+pub mod magic_bb;
 pub mod knights;
 pub mod patterns;
 pub mod pawns;
-pub mod rooks;
\ No newline at end of file
+pub mod rooks;
+pub mod selfcheck;
\ No newline at end of file
@@ -1,198 +1,215 @@
-use crate::bitboards::bb_ops;
+use crate::bitboards::geometry::{RANKS, FILES, NUM_SQUARES};
 use crate::position;
 
 
 /// Masks for looking up or setting the bit of a square
 /// given by an index.
-pub static mut IDX_MASKS: [u64; 64] = [0;64];
+pub static IDX_MASKS: [u64; NUM_SQUARES as usize] = generate_idx_masks();
 
 /// Masks for masking out all squares that aren't potential
 /// blockers for a rook at a certain square.
-pub static mut ROOK_MASKS: [u64; 64] = [0; 64];
+pub static ROOK_MASKS: [u64; NUM_SQUARES as usize] = generate_rook_masks();
 
 /// Masks for masking out all squares that aren't potential
 /// blockers for a bishop at a certain square.
-pub static mut BISHOP_MASKS: [u64; 64] = [0; 64];
+pub static BISHOP_MASKS: [u64; NUM_SQUARES as usize] = generate_bishop_masks();
 
 /// Masks for looking up squares a king can move to from a
 /// given square.
-pub static mut KING_MASKS: [u64; 64] = [0; 64];
+pub static KING_MASKS: [u64; NUM_SQUARES as usize] = generate_king_masks();
 
 /// Masks for checking if all squares between the king and the rook
-/// are empty such that castling is possible.
-pub static KS_CASTLING_MASKS: [u64; 2] = [0x6000000000000000, 0x60];
-pub static QS_CASTLING_MASKS: [u64; 2] = [0xE00000000000000, 0xE];
+/// are empty such that castling is possible, indexed by color.
+pub static KS_CASTLING_MASKS: [u64; 2] = [0x60, 0x6000000000000000];
+pub static QS_CASTLING_MASKS: [u64; 2] = [0xE, 0xE00000000000000];
+
+/// Masks for the squares the king crosses or lands on when castling
+/// queenside, which must not be attacked. The square next to the rook
+/// only has to be empty. Kingside these are the squares of
+/// KS_CASTLING_MASKS.
+pub static QS_CASTLING_SAFE_MASKS: [u64; 2] = [0xC, 0xC00000000000000];
 
 /// Masks for looking up squares a pawn can move to from a
 /// given square.
-pub static mut PAWN_MOVE_MASKS: [[u64; 64]; 2] = [[0; 64]; 2];
+pub static PAWN_MOVE_MASKS: [[u64; NUM_SQUARES as usize]; 2] = generate_pawn_move_masks();
 
 /// Masks for looking up squares a pawn can capture to from a
 /// given square.
-pub static mut PAWN_CAPTURE_MASKS: [[u64; 64]; 2] = [[0; 64]; 2];
+pub static PAWN_CAPTURE_MASKS: [[u64; NUM_SQUARES as usize]; 2] = generate_pawn_capture_masks();
 
 /// Masks for squares a pawn of a given color can promote at.
 pub static PAWN_PROMOTION_SQUARES: [u64; 2] = [0xFF00000000000000, 0xFF];
 
 /// Masks for squares where a pawn can move two extra steps.
-pub static mut PAWN_DOUBLE_STEP_MASKS: [[u64; 64]; 2] = [[0; 64]; 2];
+pub static PAWN_DOUBLE_STEP_MASKS: [[u64; NUM_SQUARES as usize]; 2] = generate_pawn_double_step_masks();
+
+
+// The masks are generated by const fns at compile time, so they can be used without any
+// initialization. Iterators and most helpers of bb_ops aren't available in const fns, hence
+// the while loops and plain bit operations.
 
+/// Returns the mask of the square with the given coordinates or 0 if they are off the board.
+const fn coords_mask(y: i8, x: i8) -> u64 {
+    if y < 0 || y >= RANKS as i8 || x < 0 || x >= FILES as i8 {
+        return 0;
+    }
+
+    return 1 << (y as u8 * FILES + x as u8);
+}
 
 /// Generates the masks for looking up or setting the bit
 /// of a square given by an index.
-fn generate_idx_masks() {
-    for i in 0..64 {
-        unsafe {
-            IDX_MASKS[i as usize] = 1 << i;
-        }
+const fn generate_idx_masks() -> [u64; NUM_SQUARES as usize] {
+    let mut masks = [0; NUM_SQUARES as usize];
+    let mut i = 0;
+
+    while i < NUM_SQUARES as usize {
+        masks[i] = 1 << i;
+        i += 1;
     }
+
+    return masks;
 }
 
-/// Generates the rook mask for a square given by the parameter idx.
-fn generate_rook_mask(idx: u8) -> u64 {
+/// Generates the mask of all squares reached from the square given by the parameter idx by
+/// repeatedly stepping into the given directions.
+const fn generate_ray_mask(idx: u8, directions: [(i8, i8); 4]) -> u64 {
     let mut mask: u64 = 0;
-    let coords = bb_ops::index_to_coords(idx);
+    let (y, x) = ((idx / FILES) as i8, (idx % FILES) as i8);
+    let mut d = 0;
 
-    for i in 0..64 {
-        let curr_coords = bb_ops::index_to_coords(i);
+    while d < directions.len() {
+        let (dy, dx) = directions[d];
+        let (mut ny, mut nx) = (y + dy, x + dx);
 
-        if (curr_coords.0 == coords.0) ^ (curr_coords.1 == coords.1) {
-            mask = bb_ops::set_idx_bit(mask, i);
+        while coords_mask(ny, nx) != 0 {
+            mask |= coords_mask(ny, nx);
+            ny += dy;
+            nx += dx;
         }
+
+        d += 1;
     }
 
     return mask;
 }
 
-/// Generates the rook masks for all squares on the board and stores
-/// them in the static array ROOK_MASKS.
-fn generate_rook_masks() {
-    for i in 0..64 {
-        unsafe {
-            ROOK_MASKS[i as usize] = generate_rook_mask(i);
-        }
-    }
-}
-
-/// Generates the bishop mask for a square given by the parameter idx.
-fn generate_bishop_mask(idx: u8) -> u64 {
-    let mut mask: u64 = 0;
-    let coords = bb_ops::index_to_coords(idx);
-
-    for i in 0..64 {
-        let curr_coords = bb_ops::index_to_coords(i);
-        let dy = (curr_coords.0 as i16) - (coords.0 as i16);
-        let dx = (curr_coords.1 as i16) - (coords.1 as i16);
+/// Generates the rook masks for all squares on the board.
+const fn generate_rook_masks() -> [u64; NUM_SQUARES as usize] {
+    let mut masks = [0; NUM_SQUARES as usize];
+    let mut i = 0;
 
-        if dx.abs() == dy.abs() && dx != 0 {
-            mask = bb_ops::set_idx_bit(mask, i);
-        }
+    while i < NUM_SQUARES {
+        masks[i as usize] = generate_ray_mask(i, [(1, 0), (-1, 0), (0, 1), (0, -1)]);
+        i += 1;
     }
 
-    return mask;
+    return masks;
 }
 
-/// Generates the bishop masks for all squares on the board and stores
-/// them in the static array BISHOP_MASKS.
-fn generate_bishop_masks() {
-    for i in 0..64 {
-        unsafe {
-            BISHOP_MASKS[i as usize] = generate_bishop_mask(i);
-        }
+/// Generates the bishop masks for all squares on the board.
+const fn generate_bishop_masks() -> [u64; NUM_SQUARES as usize] {
+    let mut masks = [0; NUM_SQUARES as usize];
+    let mut i = 0;
+
+    while i < NUM_SQUARES {
+        masks[i as usize] = generate_ray_mask(i, [(1, 1), (1, -1), (-1, 1), (-1, -1)]);
+        i += 1;
     }
+
+    return masks;
 }
 
 /// Generates the king mask for a square given by the parameter idx.
-fn generate_king_mask(idx: u8) -> u64 {
-    let mask: u64 = 0;
-    let (y, x) = bb_ops::index_to_coords(idx);
+const fn generate_king_mask(idx: u8) -> u64 {
+    let mut mask: u64 = 0;
+    let (y, x) = ((idx / FILES) as i8, (idx % FILES) as i8);
+    let mut dy = -1;
 
-    for dy in [-1, 0, 1] {
-        for dx in [-1, 0, 1] {
-            let ny = (y as i8) + dy;
-            let nx = (x as i8) + dx;
+    while dy <= 1 {
+        let mut dx = -1;
 
-            if bb_ops::is_legal_square(ny, nx) {
-                bb_ops::set_coords_bit(mask, ny as u8, nx as u8);
+        while dx <= 1 {
+            if dy != 0 || dx != 0 {
+                mask |= coords_mask(y + dy, x + dx);
             }
+
+            dx += 1;
         }
+
+        dy += 1;
     }
 
     return mask;
 }
 
-/// Generates the king masks for all squares on the board and stores
-/// them in the static array KING_MASKS.
-fn generate_king_masks() {
-    for i in 0..64 {
-        unsafe {
-            KING_MASKS[i as usize] = generate_king_mask(i);
-        }
+/// Generates the king masks for all squares on the board.
+const fn generate_king_masks() -> [u64; NUM_SQUARES as usize] {
+    let mut masks = [0; NUM_SQUARES as usize];
+    let mut i = 0;
+
+    while i < NUM_SQUARES {
+        masks[i as usize] = generate_king_mask(i);
+        i += 1;
     }
+
+    return masks;
 }
 
-/// Generates the pawns masks for all squares on the board. This involves move masks
-/// and capture masks.
-fn generate_pawn_masks() {
-    for c in [position::WHITE, position::BLACK] {
-        for i in 0..64 {
-            let (row, col) = bb_ops::index_to_coords(i);
-
-            // The next rank for the pawn to move to.
-            let next_row = ((row + 1) as i8) + (c as i8) * (-2i8);
-
-            // Check if the next square is inside the board.
-            if next_row >= 0 && next_row < 8 {
-                unsafe {
-                    let idx = bb_ops::coords_to_index(next_row as u8, col);
-
-                    PAWN_MOVE_MASKS[c as usize][i as usize] = IDX_MASKS[idx as usize];
-                }
-
-                // Assemble a mask for potential capture moves.
-                let mut attack_mask: u64 = 0;
-
-                // Set the bits on the left and right side of the pawn's front to 1.
-                for dx in [-1i8, 1i8] {
-                    if (col as i8) + dx >= 0 && (col as i8) + dx < 8 {
-                        let next_col = (col as i8 + dx) as u8;
-
-                        let idx = bb_ops::coords_to_index(next_row as u8, next_col);
-
-                        unsafe {
-                            attack_mask |= IDX_MASKS[idx as usize];
-                        }
-                    }
-                }
-                unsafe {
-                    PAWN_CAPTURE_MASKS[c as usize][i as usize] = attack_mask;
-                }
-            }
-        }
+/// Returns the direction in which pawns of the given color move.
+const fn pawn_direction(color: u8) -> i8 {
+    return if color == position::WHITE { 1 } else { -1 };
+}
+
+/// Generates the masks of the squares pawns move to for all squares on the board.
+const fn generate_pawn_move_masks() -> [[u64; NUM_SQUARES as usize]; 2] {
+    let mut masks = [[0; NUM_SQUARES as usize]; 2];
+    let mut i = 0;
+
+    while i < 2 * NUM_SQUARES as usize {
+        let (c, sq) = (i / NUM_SQUARES as usize, (i % NUM_SQUARES as usize) as u8);
+        let (y, x) = ((sq / FILES) as i8, (sq % FILES) as i8);
+
+        masks[c][sq as usize] = coords_mask(y + pawn_direction(c as u8), x);
+        i += 1;
     }
 
-    for col in 0..8 {
-        unsafe {
-            PAWN_DOUBLE_STEP_MASKS[position::WHITE as usize][(position::FILES + col) as usize] = bb_ops::coords_lookup_mask(3, col);
-            PAWN_DOUBLE_STEP_MASKS[position::BLACK as usize][(6 * position::FILES + col) as usize] = bb_ops::coords_lookup_mask(4, col);
-        }
+    return masks;
+}
+
+/// Generates the masks of the squares pawns capture on for all squares on the board.
+const fn generate_pawn_capture_masks() -> [[u64; NUM_SQUARES as usize]; 2] {
+    let mut masks = [[0; NUM_SQUARES as usize]; 2];
+    let mut i = 0;
+
+    while i < 2 * NUM_SQUARES as usize {
+        let (c, sq) = (i / NUM_SQUARES as usize, (i % NUM_SQUARES as usize) as u8);
+        let (y, x) = ((sq / FILES) as i8, (sq % FILES) as i8);
+        let ny = y + pawn_direction(c as u8);
+
+        masks[c][sq as usize] = coords_mask(ny, x - 1) | coords_mask(ny, x + 1);
+        i += 1;
     }
+
+    return masks;
 }
 
+/// Generates the masks of the squares pawns on their starting rank reach with a double step.
+const fn generate_pawn_double_step_masks() -> [[u64; NUM_SQUARES as usize]; 2] {
+    let mut masks = [[0; NUM_SQUARES as usize]; 2];
+    let mut col = 0;
+
+    while col < FILES {
+        masks[position::WHITE as usize][(FILES + col) as usize] = coords_mask(3, col as i8);
+        masks[position::BLACK as usize][((RANKS - 2) * FILES + col) as usize] = coords_mask(RANKS as i8 - 4, col as i8);
+        col += 1;
+    }
 
-/// Generates all patterns that are used for piece attacks.
-/// The masks are precomputed and stored static arrays in order to save
-/// computation time during move generation.
-pub fn generate_patterns() {
-    generate_idx_masks();
-    generate_rook_masks();
-    generate_bishop_masks();
-    generate_king_masks();
-    generate_pawn_masks();
+    return masks;
 }
 
 /// The attack patterns for knights on all squares on the board.
-pub const KNIGHT_MASKS: [u64; 64] = [
+pub const KNIGHT_MASKS: [u64; NUM_SQUARES as usize] = [
     0x20400,
     0x50800,
     0xa1100,
@@ -1,17 +1,15 @@
-use crate::patterns;
+use crate::bitboards::geometry::FILES;
+use crate::bitboards::patterns;
+use crate::position::WHITE;
 
 
 pub fn get_pawn_moves(color: u8, sq: u8) -> u64 {
-    unsafe {
-        return patterns::PAWN_MOVE_MASKS[color as usize][sq as usize];
-    }
+    return patterns::PAWN_MOVE_MASKS[color as usize][sq as usize];
 }
 
 
 pub fn get_pawn_captures(color: u8, sq: u8) -> u64 {
-    unsafe {
-        return patterns::PAWN_CAPTURE_MASKS[color as usize][sq as usize];
-    }
+    return patterns::PAWN_CAPTURE_MASKS[color as usize][sq as usize];
 }
 
 
@@ -21,7 +19,67 @@ pub fn get_pawn_promotion_squares(color: u8) -> u64 {
 
 
 pub fn get_pawn_double_steps(color: u8, sq: u8) -> u64 {
-    unsafe {
-        return patterns::PAWN_DOUBLE_STEP_MASKS[color as usize][sq as usize];
-    } 
-} 
\ No newline at end of file
+    return patterns::PAWN_DOUBLE_STEP_MASKS[color as usize][sq as usize];
+} 
+
+
+/// Returns a bitboard marking all squares of the given file.
+pub fn file_mask(fl: u8) -> u64 {
+    return 0x0101_0101_0101_0101 << fl;
+}
+
+
+/// Returns a bitboard marking the files next to the given one.
+pub fn adjacent_files(fl: u8) -> u64 {
+    let left = if fl > 0 { file_mask(fl - 1) } else { 0 };
+    let right = if fl + 1 < FILES { file_mask(fl + 1) } else { 0 };
+
+    return left | right;
+}
+
+
+/// Returns a bitboard marking the ranks up to and including the given one, seen from the given
+/// player, i.e. the rank and everything behind it.
+pub fn ranks_behind(color: u8, rk: u8) -> u64 {
+    let up_to = |last: u8| (0..=last).fold(0, |mask, r| mask | 0xff << (r * FILES));
+
+    return if color == WHITE { up_to(rk) } else { !up_to(rk) | 0xff << (rk * FILES) };
+}
+
+
+/// Returns the squares ahead of a pawn of the given player on its file, which it has to pass
+/// to promote.
+pub fn front_span(color: u8, sq: u8) -> u64 {
+    return file_mask(sq % FILES) & !ranks_behind(color, sq / FILES);
+}
+
+
+/// Returns the squares ahead of a pawn of the given player on the adjacent files, which are
+/// the squares it may attack while advancing.
+pub fn attack_span(color: u8, sq: u8) -> u64 {
+    return adjacent_files(sq % FILES) & !ranks_behind(color, sq / FILES);
+}
+
+
+/// Returns the squares an enemy pawn must not stand on for a pawn of the given player to be
+/// passed: its front span and its attack span.
+pub fn passed_span(color: u8, sq: u8) -> u64 {
+    return front_span(color, sq) | attack_span(color, sq);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitboards::bb_ops;
+    use crate::position::BLACK;
+
+    #[test]
+    fn spans_cover_the_squares_ahead() {
+        // A white pawn on d4 and a black pawn on a5.
+        assert_eq!(bb_ops::idx_bitscan(front_span(WHITE, 27)), vec![35, 43, 51, 59]);
+        assert_eq!(bb_ops::idx_bitscan(attack_span(WHITE, 27)), vec![34, 36, 42, 44, 50, 52, 58, 60]);
+        assert_eq!(bb_ops::idx_bitscan(passed_span(BLACK, 32)), vec![0, 1, 8, 9, 16, 17, 24, 25]);
+        assert_eq!(front_span(WHITE, 59), 0);
+    }
+}
@@ -1,16 +1,17 @@
-use crate::bitboards::bb_ops;
+use crate::bitboards::{bb_ops, magic_bb};
+use crate::bitboards::geometry::{RANKS, FILES};
 
 /// Computes all squares attacked by a rook on the given square
 /// by taking all possible blockers in consideration. Returns a attack
 /// map with all attacked squares set to 1.
 ///
 /// Code adapted from: https://www.chessprogramming.org/Looking_for_Magics
-fn rook_attacks(sq: u8, blockers: u64) -> u64 {
+pub(crate) fn rook_attacks(sq: u8, blockers: u64) -> u64 {
     let mut result: u64 = 0;
 
     let (rk, fl) = bb_ops::index_to_coords(sq);
 
-    for r in rk..8 {
+    for r in (rk + 1)..RANKS {
         result = bb_ops::set_coords_bit(result, r, fl);
         if bb_ops::coords_lookup(blockers, r, fl) {
             break;
@@ -24,7 +25,7 @@ fn rook_attacks(sq: u8, blockers: u64) -> u64 {
         }
     }
 
-    for f in fl..8 {
+    for f in (fl + 1)..FILES {
         result = bb_ops::set_coords_bit(result, rk, f);
         if bb_ops::coords_lookup(blockers, rk, f) {
             break;
@@ -41,6 +42,8 @@ fn rook_attacks(sq: u8, blockers: u64) -> u64 {
     return result;
 }
 
+/// Looks up all squares attacked by a rook on the given square in the magic
+/// bitboard table.
 pub fn get_rook_attacks(sq: u8, blockers: u64) -> u64 {
-    return rook_attacks(sq, blockers);
+    return magic_bb::rook_attacks(sq, blockers);
 }
@@ -0,0 +1,142 @@
+use crate::bitboards::patterns;
+use crate::bitboards::geometry::{RANKS, FILES, NUM_SQUARES};
+use crate::bitboards::bb_ops;
+use crate::position::{self, WHITE, BLACK};
+
+
+/// A table of masks indexed by square together with a slow reference telling if a mask should
+/// contain a target square, given the coordinate offsets from the square to the target.
+struct Pattern {
+    name: &'static str,
+    masks: &'static [u64; NUM_SQUARES as usize],
+    expected: fn(u8, i8, i8) -> bool,
+    /// The smallest and largest number of squares a mask may contain.
+    popcount: (u32, u32),
+    /// Symmetric patterns contain the way back, e.g. a king on e2 attacks e1 and vice versa.
+    /// Colored patterns are symmetric with the pattern of the other color instead.
+    mirror: Option<&'static [u64; NUM_SQUARES as usize]>
+}
+
+fn patterns() -> Vec<Pattern> {
+    return vec![
+        Pattern { name: "KING_MASKS", masks: &patterns::KING_MASKS, expected: |_, dy, dx| dy.abs().max(dx.abs()) == 1,
+                  popcount: (3, 8), mirror: Some(&patterns::KING_MASKS) },
+        Pattern { name: "KNIGHT_MASKS", masks: &patterns::KNIGHT_MASKS, expected: |_, dy, dx| dy.abs() * dx.abs() == 2,
+                  popcount: (2, 8), mirror: Some(&patterns::KNIGHT_MASKS) },
+        Pattern { name: "ROOK_MASKS", masks: &patterns::ROOK_MASKS, expected: |_, dy, dx| (dy == 0) != (dx == 0),
+                  popcount: (14, 14), mirror: Some(&patterns::ROOK_MASKS) },
+        Pattern { name: "BISHOP_MASKS", masks: &patterns::BISHOP_MASKS, expected: |_, dy, dx| dy.abs() == dx.abs() && dy != 0,
+                  popcount: (7, 13), mirror: Some(&patterns::BISHOP_MASKS) },
+        Pattern { name: "IDX_MASKS", masks: &patterns::IDX_MASKS, expected: |_, dy, dx| dy == 0 && dx == 0,
+                  popcount: (1, 1), mirror: Some(&patterns::IDX_MASKS) },
+        Pattern { name: "PAWN_MOVE_MASKS[WHITE]", masks: &patterns::PAWN_MOVE_MASKS[WHITE as usize],
+                  expected: |_, dy, dx| dy == 1 && dx == 0, popcount: (0, 1), mirror: Some(&patterns::PAWN_MOVE_MASKS[BLACK as usize]) },
+        Pattern { name: "PAWN_MOVE_MASKS[BLACK]", masks: &patterns::PAWN_MOVE_MASKS[BLACK as usize],
+                  expected: |_, dy, dx| dy == -1 && dx == 0, popcount: (0, 1), mirror: Some(&patterns::PAWN_MOVE_MASKS[WHITE as usize]) },
+        Pattern { name: "PAWN_CAPTURE_MASKS[WHITE]", masks: &patterns::PAWN_CAPTURE_MASKS[WHITE as usize],
+                  expected: |_, dy, dx| dy == 1 && dx.abs() == 1, popcount: (0, 2), mirror: Some(&patterns::PAWN_CAPTURE_MASKS[BLACK as usize]) },
+        Pattern { name: "PAWN_CAPTURE_MASKS[BLACK]", masks: &patterns::PAWN_CAPTURE_MASKS[BLACK as usize],
+                  expected: |_, dy, dx| dy == -1 && dx.abs() == 1, popcount: (0, 2), mirror: Some(&patterns::PAWN_CAPTURE_MASKS[WHITE as usize]) },
+        Pattern { name: "PAWN_DOUBLE_STEP_MASKS[WHITE]", masks: &patterns::PAWN_DOUBLE_STEP_MASKS[WHITE as usize],
+                  expected: |sq, dy, dx| sq / FILES == 1 && dy == 2 && dx == 0, popcount: (0, 1), mirror: None },
+        Pattern { name: "PAWN_DOUBLE_STEP_MASKS[BLACK]", masks: &patterns::PAWN_DOUBLE_STEP_MASKS[BLACK as usize],
+                  expected: |sq, dy, dx| sq / FILES == RANKS - 2 && dy == -2 && dx == 0, popcount: (0, 1), mirror: None }
+    ];
+}
+
+/// Checks the precomputed attack tables against slow generators working on coordinates, the
+/// number of squares each mask may contain and the symmetry of the patterns. Returns a
+/// description of every problem found, so an empty list means the tables are correct.
+pub fn verify_patterns() -> Vec<String> {
+    return patterns().iter().flat_map(check).collect();
+}
+
+fn check(pattern: &Pattern) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    for sq in 0..NUM_SQUARES {
+        let mask = pattern.masks[sq as usize];
+        let name = position::index_to_string(sq).unwrap_or_default();
+        let (y, x) = bb_ops::index_to_coords(sq);
+
+        let expected = (0..NUM_SQUARES).filter(|target| {
+            let (ty, tx) = bb_ops::index_to_coords(*target);
+
+            return (pattern.expected)(sq, ty as i8 - y as i8, tx as i8 - x as i8);
+        }).fold(0, |mask, target| mask | 1 << target);
+
+        if mask != expected {
+            problems.push(format!("{} of {} is {:#x} instead of {:#x}", pattern.name, name, mask, expected));
+        }
+
+        let (min, max) = pattern.popcount;
+
+        if !(min..=max).contains(&mask.count_ones()) {
+            problems.push(format!("{} of {} has {} squares, expected {} to {}", pattern.name, name, mask.count_ones(), min, max));
+        }
+
+        if let Some(mirror) = pattern.mirror {
+            for target in bb_ops::idx_bitscan(mask).into_iter().filter(|t| !bb_ops::index_lookup(mirror[*t as usize], sq)) {
+                problems.push(format!("{} of {} contains {}, but not the other way around",
+                                      pattern.name, name, position::index_to_string(target).unwrap_or_default()));
+            }
+        }
+    }
+
+    return problems;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_pass_the_self_check() {
+        assert_eq!(verify_patterns(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn king_masks_hold_the_neighbouring_squares() {
+        // Corners have 3 neighbours, other edge squares 5 and squares in the center 8.
+        let expected = [(0, 0x302), (7, 0xc040), (56, 0x0203_0000_0000_0000), (63, 0x40c0_0000_0000_0000),
+                        (4, 0x3828), (24, 0x3_0203_0000), (28, 0x38_2838_0000)];
+
+        for (sq, mask) in expected {
+            assert_eq!(patterns::KING_MASKS[sq], mask, "{}", position::index_to_string(sq as u8).unwrap_or_default());
+        }
+
+        for sq in 0..NUM_SQUARES {
+            let edges = [sq % 8 == 0, sq % 8 == 7, sq / 8 == 0, sq / 8 == 7].iter().filter(|&&edge| edge).count();
+            let squares = match edges {
+                0 => 8,
+                1 => 5,
+                _ => 3
+            };
+
+            assert_eq!(patterns::KING_MASKS[sq as usize].count_ones(), squares, "{}", position::index_to_string(sq).unwrap_or_default());
+        }
+    }
+
+    #[test]
+    fn self_check_reports_broken_patterns() {
+        static EMPTY: [u64; NUM_SQUARES as usize] = [0; NUM_SQUARES as usize];
+
+        // A king pattern without any squares, as produced by discarding the generated bits.
+        let empty = check(&Pattern { masks: &EMPTY, ..patterns().remove(0) });
+
+        assert!(empty.contains(&String::from("KING_MASKS of e1 is 0x0 instead of 0x3828")));
+        assert!(empty.contains(&String::from("KING_MASKS of e1 has 0 squares, expected 3 to 8")));
+
+        // A knight pattern that can only move away from a1.
+        static ONE_WAY: [u64; NUM_SQUARES as usize] = {
+            let mut masks = patterns::KNIGHT_MASKS;
+            masks[10] = 0;
+            masks
+        };
+
+        let one_way = check(&Pattern { masks: &ONE_WAY, mirror: Some(&ONE_WAY), ..patterns().remove(1) });
+
+        assert!(one_way.contains(&String::from("KNIGHT_MASKS of a1 contains c2, but not the other way around")));
+    }
+}
@@ -0,0 +1,50 @@
+use crate::cpu;
+
+
+/// The version of the crate.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The git commit the binary was built from.
+pub const GIT_COMMIT: &str = env!("RUSTCHESS_GIT_COMMIT");
+
+/// The node count of the bench run, which serves as a signature of the engine's
+/// functional behaviour. A build script can't run the engine it's building, so the count
+/// has to be passed in via the RUSTCHESS_BENCH environment variable at build time. Only
+/// release binaries built by scripts/make-release.sh (or the make-release command) carry
+/// one; other builds have none and don't report it. Their signature is the node count
+/// printed by the bench command.
+pub const BENCH_SIGNATURE: Option<&str> = option_env!("RUSTCHESS_BENCH");
+
+
+/// Returns the engine's name including the version and the commit.
+pub fn engine_name() -> String {
+    return format!("RustChess {} ({})", VERSION, GIT_COMMIT);
+}
+
+/// Returns the lines identifying the engine in response to the uci command. The bench
+/// signature is only reported by builds that carry one.
+pub fn uci_id_lines() -> Vec<String> {
+    let mut lines = vec![format!("id name {}", engine_name()), String::from("id author dvdb97")];
+
+    if let Some(bench) = BENCH_SIGNATURE {
+        lines.push(format!("info string bench {}", bench));
+    }
+
+    lines.push(format!("info string cpu {}", cpu::features()));
+
+    return lines;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_bench_signature_only_if_embedded() {
+        let lines = uci_id_lines();
+
+        assert_eq!(lines[0], format!("id name RustChess {} ({})", VERSION, GIT_COMMIT));
+        assert_eq!(lines.iter().any(|line| line.starts_with("info string bench ")), BENCH_SIGNATURE.is_some());
+    }
+}
@@ -0,0 +1,1742 @@
+use std::fs;
+use std::io;
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
+
+use crate::archive::{self, Site};
+use crate::bench;
+use crate::bitboards::selfcheck;
+use crate::build_info;
+use crate::coach;
+use crate::correspondence::{self, Checkpoint};
+use crate::explain;
+use crate::game::Game;
+use crate::interrupt;
+use crate::label::{self, LabelConfig};
+use crate::mcts::{MctsEngine, Simulation};
+use crate::perft;
+use crate::pgn;
+use crate::play::{self, PlayOptions};
+use crate::moves::Move;
+use crate::problem;
+use crate::proofgame::ProofGameSearch;
+use crate::repertoire::{self, OpeningTree, RepertoireOptions};
+use crate::report::{self, GameReport, Judgement};
+use crate::position::{Position, WHITE, BLACK};
+use crate::recorder::GameRecorder;
+use crate::protocol::http::{self, Access};
+use crate::protocol::ics::{Ics, IcsOptions};
+use crate::protocol::uci::Uci;
+use crate::protocol::xboard::XBoard;
+use crate::runner::Engine;
+use crate::runner::concurrency::{ConcurrencyConfig, EngineFactory};
+use crate::runner::openings::OpeningSuite;
+use crate::runner::pgn::MatchInfo;
+use crate::runner::sprt::Sprt;
+use crate::runner::tournament::Tournament;
+use crate::runner::uci_engine::{UciEngine, UciEngineSpec, UciLimit};
+use crate::runner::worker;
+use crate::search::{self, Limits, Score, SearchEngine};
+use crate::stats::{self, BranchingStats};
+use crate::storage::{self, GameQuery, Storage};
+use crate::strength::{Strength, MAX_SKILL};
+use crate::testsuite::{self, TestPosition, TestResult};
+use crate::training::{self, Rng};
+use crate::tt::{self, Replacement, TranspositionTable};
+
+
+/// The search depth used if no depth is given on the command line.
+const DEFAULT_DEPTH: u8 = 3;
+
+const USAGE: &str = "Usage: chess [--json] <command> [arguments]
+
+Commands:
+    perft <depth> [fen]        Count the leaf nodes of the move tree for each move
+    bench [<depth>]            Search a fixed set of positions and report the node
+                               count of each and the total and speed, by default at
+                               depth 6
+    analyze [-d <depth>] [--exclude <move>]... [--reply <move>]... <fen>
+                               Score all legal moves and explain the best one. Excluded
+                               moves aren't scored and the opponent has to answer the
+                               others with one of the replies if it can. Moves are in
+                               UCI notation
+    annotate [-d <depth>] [--svg <file>] <pgn>
+                               Evaluate every position of the first game of a PGN
+                               file, mark inaccuracies (?!), mistakes (?) and
+                               blunders (??), explain the best move instead of them
+                               and summarize the accuracy of both sides. The score
+                               graph is written to the SVG file
+    correspond <checkpoint> [--hours <hours>] [--interval <minutes>] [--hash <mb>]
+        [--pgn <file> | --fen <fen>]
+                               Analyze the last position of a PGN game or a FEN for
+                               correspondence play, by default for 24 hours. The
+                               analysis is saved to the checkpoint file every
+                               interval (10 minutes) and resumed from it when run
+                               again. Ctrl-C saves and stops it
+    db <database> import <pgn> Save the games of a PGN file in an SQLite database
+    db <database> games [--player <name>] [--from <date>] [--to <date>] [--fen <fen> | --pawns <fen>]
+                               List the games of a database by a player, between PGN
+                               dates like 2024.03.17, reaching a position or reaching
+                               the pawn structure of a position wherever the pieces
+                               stand. Needs a build with the sqlite feature
+    db <database> novelty <pgn>
+                               Report the first move of each game of a PGN file that
+                               leaves the games of the database
+    match [-n <games>] [-j <games>] [--pin] [--limit <limit>] [--plies <plies>] [--book <file>]
+        [--sprt <elo0> <elo1>] [--pgn <file>] [--state <file>] <engine> <engine>
+                               Play a match of n (100) games between two engines, j
+                               at a time, with every game pinned to its own core
+                               with --pin. Engines are alphabeta, mcts,
+                               mcts-playouts or the command of a UCI engine. The
+                               limit per move (depth 3) is given like in the UCI go
+                               command, e.g. nodes 5000, and sets the depth of
+                               alphabeta. Games start from the openings of the
+                               book, are drawn after 400 plies and stop early once
+                               the SPRT decides. The state (match.state) is saved
+                               after every game and an interrupted match with the
+                               same engines is resumed from it with its limit
+    make-release [<output>]    Build an engine binary with profile-guided optimization
+                               using the bench as training workload. Its node count
+                               is embedded as bench signature, which only such builds
+                               report. Needs the source tree the engine was built from
+    import <site> <user> [--max <games>] [--pgn <file>] [--db <database>]
+                               Download the games of a lichess or chess.com user and
+                               append them to a PGN file or save them in a database.
+                               Needs a build with the online feature
+    ics <user> [--password <password>] [--seek <minutes> <increment>] [--engine <engine>]
+        [<address>]            Log into an Internet Chess Server, by default
+                               freechess.org:5000, and play the games of accepted
+                               challenges and of the seek, if given. The user guest
+                               logs in as a guest
+    label [-d <depth>] [-j <threads>] <input> <output>
+                               Label a file of FENs (one per line) with their scores.
+                               An interrupted run resumes where it stopped
+    play [-d <depth>] [--coach [<threshold>]] [--black] [--skill <0-20> | --elo <elo>]
+         [--player <name> --db <database>] [--record <file>]
+                               Play a game against the engine in the terminal. The
+                               coach warns before moves losing more than threshold
+                               centipawns. Ctrl-C makes the engine move right away.
+                               A skill level or Elo rating weakens the engine.
+                               With a player, the game is saved in the database and
+                               the player's Glicko-2 rating is updated. The game is
+                               recorded to the file with the time of every move and
+                               the engine's evaluations
+    proofgame <plies> <fen>    Search a game reaching the position in exactly the
+                               given number of plies and check if it's unique
+    replay [--speed <factor>] <file>
+                               Replay a game recorded by play in real time or the
+                               given times faster, showing the board, the time and
+                               the engine's evaluation after every move
+    repertoire <pgn> [--black] [--plies <plies>] [-d <depth>] [--replies <n>]
+        [--margin <centipawns>] [--output <file>]
+                               Write a PGN repertoire for white or black from the
+                               opening tree of the games, by default 10 plies deep.
+                               The engine picks the player's moves and keeps up to n
+                               (3) replies that are at most margin (50) centipawns
+                               worse than the opponent's best. Moves are commented
+                               with their evaluation and how often they were played
+    serve [--token <token>]... [--rate <requests>] [<address>]
+                               Serve analysis over HTTP (POST /analyze and
+                               /legal-moves), by default on 127.0.0.1:8080. With
+                               tokens, clients have to send one as a bearer token.
+                               The rate limits the requests per minute of a client
+    solve [-n <moves>] <fen>   Solve a mate in n problem and show its set play
+                               and tries
+    stats [--per-ply] <pgn>    Report the legal move counts along the games of a PGN
+                               file and the branching factor at each ply
+    testsuite [-d <depth>] <epd>
+                               Search the positions of an EPD test suite and check
+                               the moves found against their bm and am operations
+    train [<rounds>]           Quiz square colors and knight paths, by default for 10
+                               rounds
+    uci [--engine <engine>]    Speak the UCI protocol on stdin/stdout
+    worker [--name <name>] [-j <games>] [--pin] <url>
+                               Fetch test jobs from a server speaking the worker
+                               protocol of this crate (POST /worker/job and
+                               /worker/results, not OpenBench's), play their games
+                               locally, j at a time, and report the results until
+                               the server runs out of work. --pin pins every game
+                               to its own core. Needs a build with the online
+                               feature
+    xboard [--engine <engine>] Speak the xboard protocol on stdin/stdout. The engine is
+                               alphabeta (the default), mcts for Monte Carlo tree
+                               search guided by the evaluation or mcts-playouts for
+                               Monte Carlo tree search with random playouts
+    help                       Print this message
+
+Options:
+    --json                     Print the results of perft, bench, analyze, annotate,
+                               label, stats and testsuite as a single JSON object";
+
+
+/// The commands that print their results as JSON if --json is given.
+const JSON_COMMANDS: [&str; 7] = ["perft", "bench", "analyze", "annotate", "label", "stats", "testsuite"];
+
+
+/// Runs the command given by the command line arguments (without the program name).
+pub fn run(args: &[String]) -> Result<(), String> {
+    // Debug builds verify the attack tables, so bugs in generating them show up right away
+    // instead of as missing moves.
+    if cfg!(debug_assertions) {
+        let problems = selfcheck::verify_patterns();
+
+        assert!(problems.is_empty(), "Broken attack tables:\n{}", problems.join("\n"));
+    }
+
+    // The --json flag may be given anywhere, so it can be added to existing invocations.
+    let json = args.iter().any(|a| a == "--json");
+    let args: Vec<String> = args.iter().filter(|a| *a != "--json").cloned().collect();
+
+    let command = args.first().map(|s| s.as_str()).unwrap_or("help");
+    let args = if args.is_empty() { &args[..] } else { &args[1..] };
+
+    if json && !JSON_COMMANDS.contains(&command) {
+        return Err(format!("The {} command has no JSON output", command));
+    }
+
+    return match command {
+        "perft"   => perft_command(args, json),
+        "bench"   => bench_command(args, json),
+        "make-release" => make_release_command(args),
+        "analyze" => analyze_command(args, json),
+        "annotate" => annotate_command(args, json),
+        "correspond" => correspond_command(args),
+        "db"      => db_command(args),
+        "import"  => import_command(args),
+        "ics"     => ics_command(args),
+        "label"   => label_command(args, json),
+        "match"   => match_command(args),
+        "play"    => play_command(args),
+        "proofgame" => proof_game_command(args),
+        "replay"  => replay_command(args),
+        "repertoire" => repertoire_command(args),
+        "serve"   => serve_command(args),
+        "solve"   => solve_command(args),
+        "stats"   => stats_command(args, json),
+        "testsuite" => test_suite_command(args, json),
+        "train"   => {
+            let rounds = match args.first() {
+                Some(rounds) => rounds.parse().map_err(|_| format!("Invalid number of rounds: {}", rounds))?,
+                None         => 10
+            };
+
+            training::quiz(&mut Rng::from_time(), rounds, io::stdin().lock(), io::stdout()).map(|_| ()).map_err(|e| e.to_string())
+        },
+        "worker"  => worker_command(args),
+        "uci"     => {
+            let mut engine = parse_engine(args)?;
+            Uci::new(engine.as_mut(), io::stdout()).run(io::BufReader::new(io::stdin())).map_err(|e| e.to_string())
+        },
+        "xboard"  => {
+            let mut engine = parse_engine(args)?;
+            XBoard::new(engine.as_mut(), io::stdout()).run(io::stdin().lock()).map_err(|e| e.to_string())
+        },
+        "help" | "-h" | "--help" => {
+            println!("{}\n\n{}", build_info::engine_name(), USAGE);
+            Ok(())
+        },
+        _ => Err(format!("Unknown command: {}\n\n{}", command, USAGE))
+    };
+}
+
+/// Writes the fields as a JSON object. The values have to be encoded as JSON already.
+fn json_object(fields: &[(&str, String)]) -> String {
+    let fields: Vec<String> = fields.iter().map(|(key, value)| format!("{}:{}", http::json_string(key), value)).collect();
+
+    return format!("{{{}}}", fields.join(","));
+}
+
+/// Splits an optional -d <depth> option off the arguments.
+fn parse_depth(args: &[String]) -> Result<(u8, &[String]), String> {
+    return match args.first().map(|s| s.as_str()) {
+        Some("-d") | Some("--depth") => {
+            let depth = args.get(1).and_then(|d| d.parse().ok()).ok_or("Expected a depth after -d")?;
+
+            Ok((depth, &args[2..]))
+        },
+        _ => Ok((DEFAULT_DEPTH, args))
+    };
+}
+
+/// Parses the FEN given by the remaining arguments or returns the starting position if
+/// there are none.
+fn parse_fen(args: &[String]) -> Result<Position, String> {
+    if args.is_empty() {
+        return Ok(Position::starting_position());
+    }
+
+    let fen = args.join(" ");
+
+    return Position::from_fen(fen.clone()).ok_or(format!("Invalid FEN: {}", fen));
+}
+
+fn perft_command(args: &[String], json: bool) -> Result<(), String> {
+    let depth: u32 = args.first().and_then(|d| d.parse().ok()).ok_or(format!("Expected a depth\n\n{}", USAGE))?;
+    let position = parse_fen(&args[1..])?;
+
+    let start = Instant::now();
+    let divided = perft::divide(&position, depth);
+    let total: u64 = divided.iter().map(|(_, nodes)| *nodes).sum();
+    let elapsed = start.elapsed().as_millis();
+
+    if json {
+        let ucis: Vec<String> = divided.iter().map(|(m, _)| position.move_to_uci(*m)).collect();
+        let moves: Vec<(&str, String)> = ucis.iter().zip(&divided).map(|(uci, (_, nodes))| (uci.as_str(), nodes.to_string())).collect();
+
+        println!("{}", json_object(&[("depth", depth.to_string()), ("moves", json_object(&moves)),
+                                     ("nodes", total.to_string()), ("time_ms", elapsed.to_string())]));
+
+        return Ok(());
+    }
+
+    for (m, nodes) in divided {
+        println!("{}: {}", position.move_to_uci(m), nodes);
+    }
+
+    println!("\nNodes searched: {}", total);
+    println!("Time: {} ms", elapsed);
+
+    return Ok(());
+}
+
+fn bench_command(args: &[String], json: bool) -> Result<(), String> {
+    let depth = match args.first() {
+        Some(depth) => depth.parse().map_err(|_| format!("Invalid depth: {}", depth))?,
+        None        => bench::DEFAULT_DEPTH
+    };
+
+    let result = bench::run(depth);
+
+    if json {
+        let positions: Vec<String> = result.positions.iter().map(|nodes| nodes.to_string()).collect();
+
+        println!("{}", json_object(&[("depth", depth.to_string()), ("nodes", result.nodes.to_string()), ("positions", format!("[{}]", positions.join(","))),
+                                     ("time_ms", result.elapsed.as_millis().to_string()), ("nps", result.nodes_per_second().to_string())]));
+
+        return Ok(());
+    }
+
+    for (i, (fen, nodes)) in bench::POSITIONS.iter().zip(&result.positions).enumerate() {
+        println!("Position {}: {} nodes ({})", i + 1, nodes, fen);
+    }
+
+    println!("\nNodes searched: {}", result.nodes);
+    println!("Time: {} ms", result.elapsed.as_millis());
+    println!("Nodes per second: {}", result.nodes_per_second());
+
+    return Ok(());
+}
+
+/// Runs the release script of the source tree the engine was built from.
+fn make_release_command(args: &[String]) -> Result<(), String> {
+    let script = Path::new(env!("CARGO_MANIFEST_DIR")).join("scripts").join("make-release.sh");
+
+    if !script.is_file() {
+        return Err(format!("The release script {} doesn't exist", script.display()));
+    }
+
+    let status = Command::new("sh").arg(&script).args(args).status().map_err(|e| e.to_string())?;
+
+    return match status.success() {
+        true  => Ok(()),
+        false => Err(format!("The release script failed ({})", status))
+    };
+}
+
+/// Parses the moves to exclude and the replies to force of an analysis, which precede the FEN.
+/// Returns them with the remaining arguments.
+fn parse_constraints(mut args: &[String]) -> (Vec<String>, Vec<String>, &[String]) {
+    let (mut excluded, mut replies) = (Vec::new(), Vec::new());
+
+    loop {
+        match args {
+            [option, m, rest @ ..] if option == "--exclude" => {
+                excluded.push(m.clone());
+                args = rest;
+            },
+            [option, m, rest @ ..] if option == "--reply" => {
+                replies.push(m.to_lowercase());
+                args = rest;
+            },
+            _ => return (excluded, replies, args)
+        }
+    }
+}
+
+fn analyze_command(args: &[String], json: bool) -> Result<(), String> {
+    let (depth, args) = parse_depth(args)?;
+    let (excluded, replies, args) = parse_constraints(args);
+    let position = parse_fen(args)?;
+
+    let excluded = excluded.iter().map(|m| position.uci_to_move(m).ok_or(format!("Illegal move: {}", m))).collect::<Result<Vec<Move>, String>>()?;
+    let constrained = !excluded.is_empty() || !replies.is_empty();
+    let scores = search::evaluate_moves(&position, depth, &Limits { excluded, replies, ..Limits::default() });
+
+    // The explanation is about the best move without constraints.
+    let explanation = if constrained { None } else { explain::explain_best_move(&position, depth) };
+
+    if json {
+        let moves: Vec<String> = scores.iter().map(|(m, score)| json_object(&[
+            ("san", http::json_string(&position.move_to_san(*m))),
+            ("uci", http::json_string(&position.move_to_uci(*m))),
+            ("score", score.to_string()),
+            ("mate", Score::new(*score).mate().map_or(String::from("null"), |n| n.to_string()))
+        ])).collect();
+
+        println!("{}", json_object(&[("fen", http::json_string(&position.to_fen())), ("depth", depth.to_string()),
+                                     ("moves", format!("[{}]", moves.join(","))),
+                                     ("explanation", explanation.map_or(String::from("null"), |e| http::json_string(&e.to_string())))]));
+
+        return Ok(());
+    }
+
+    println!("{}\n", position);
+
+    for (m, score) in scores {
+        println!("{:>8} {}", position.move_to_san(m), Score::new(score).to_pawns());
+    }
+
+    if let Some(explanation) = explanation {
+        println!("\n{}", explanation);
+    }
+
+    return Ok(());
+}
+
+/// The options of the annotate command.
+#[derive(PartialEq, Debug)]
+struct AnnotateOptions {
+    depth: u8,
+    svg: Option<String>,
+    pgn: String
+}
+
+fn parse_annotate_options(args: &[String]) -> Result<AnnotateOptions, String> {
+    let (depth, mut args) = parse_depth(args)?;
+    let mut svg = None;
+
+    loop {
+        match args {
+            [option, file, rest @ ..] if option == "--svg" => {
+                svg = Some(file.clone());
+                args = rest;
+            },
+            [option, ..] if option.starts_with('-') => return Err(format!("Unknown option: {}\n\n{}", option, USAGE)),
+            [pgn] => return Ok(AnnotateOptions { depth, svg, pgn: pgn.clone() }),
+            _     => return Err(format!("Expected a PGN file\n\n{}", USAGE))
+        }
+    }
+}
+
+/// Annotates the game and returns the moves with their evaluations from white's perspective
+/// and judgements, followed by the summary of both sides. Moves that aren't good are followed
+/// by the explanation of the best move a search of the given depth finds instead.
+fn annotate(game: &Game, report: &GameReport, depth: u8) -> String {
+    let mut output = String::new();
+
+    for (i, annotated) in annotated_moves(game, report, depth).iter().enumerate() {
+        let number = if annotated.color == WHITE { format!("{}.", i / 2 + 1) } else { format!("{}...", i / 2 + 1) };
+
+        output.push_str(&format!("{} {}{} {}\n", number, annotated.san, report.judgements[i].symbol(), annotated.eval));
+
+        if let Some(explanation) = &annotated.explanation {
+            output.push_str(&format!("    {}\n", explanation));
+        }
+    }
+
+    return format!("{}\n{}", output, report);
+}
+
+/// Like annotate, but writes the moves and the summary of both sides as a JSON object.
+fn annotate_json(game: &Game, report: &GameReport, depth: u8) -> String {
+    let moves: Vec<String> = annotated_moves(game, report, depth).into_iter().enumerate().map(|(i, annotated)| {
+        let judgement = match report.judgements[i] {
+            Judgement::Good       => "null",
+            Judgement::Inaccuracy => "\"inaccuracy\"",
+            Judgement::Mistake    => "\"mistake\"",
+            Judgement::Blunder    => "\"blunder\""
+        };
+
+        return json_object(&[("san", http::json_string(&annotated.san)), ("eval", report.evals[i + 1].to_string()),
+                             ("judgement", judgement.to_string()),
+                             ("explanation", annotated.explanation.map_or(String::from("null"), |e| http::json_string(&e.to_string())))]);
+    }).collect();
+
+    let sides: Vec<String> = report.sides.iter().map(|side| json_object(&[
+        ("accuracy", side.accuracy.map_or(String::from("null"), |a| format!("{:.1}", a))),
+        ("inaccuracies", side.inaccuracies.to_string()),
+        ("mistakes", side.mistakes.to_string()),
+        ("blunders", side.blunders.to_string())
+    ])).collect();
+
+    return json_object(&[("depth", depth.to_string()), ("moves", format!("[{}]", moves.join(","))),
+                         ("white", sides[WHITE as usize].clone()), ("black", sides[BLACK as usize].clone())]);
+}
+
+/// A move of an annotated game.
+struct AnnotatedMove {
+    color: u8,
+    san: String,
+    /// The evaluation after the move from white's perspective, formatted in pawns.
+    eval: String,
+    /// The explanation of the best move if the move wasn't good.
+    explanation: Option<explain::Explanation>
+}
+
+/// Collects the moves of the game with their evaluations and, for moves that aren't good, the
+/// explanation of the best move a search of the given depth finds instead.
+fn annotated_moves(game: &Game, report: &GameReport, depth: u8) -> Vec<AnnotatedMove> {
+    let mut position = game.starting_position().clone();
+    let mut moves = Vec::new();
+
+    for (i, &m) in game.moves().iter().enumerate() {
+        let (color, san) = (position.turn, position.move_to_san(m));
+        let explanation = if report.judgements[i] == Judgement::Good { None } else { explain::explain_best_move(&position, depth) };
+
+        position = position.make_move(m);
+        moves.push(AnnotatedMove { color, san, eval: white_pawns(report.evals[i + 1], position.turn), explanation });
+    }
+
+    return moves;
+}
+
+/// Formats an evaluation from white's perspective in pawns. Mates are counted in moves from
+/// the position, whose side to move is given by turn.
+fn white_pawns(eval: i32, turn: u8) -> String {
+    let sign = if turn == WHITE { 1 } else { -1 };
+
+    let score = match Score::new(sign * eval) {
+        Score::Mate(n)        => Score::Mate(sign * n),
+        Score::Centipawns(cp) => Score::Centipawns(sign * cp)
+    };
+
+    return score.to_pawns();
+}
+
+fn annotate_command(args: &[String], json: bool) -> Result<(), String> {
+    let options = parse_annotate_options(args)?;
+    let text = fs::read_to_string(&options.pgn).map_err(|e| format!("Can't read {}: {}", options.pgn, e))?;
+    let game = pgn::read_games(&text).into_iter().next().ok_or(format!("No game in {}", options.pgn))?.game;
+    let report = report::analyze_game(&game, options.depth);
+
+    if let Some(path) = &options.svg {
+        fs::write(path, report.to_svg()).map_err(|e| format!("Can't write {}: {}", path, e))?;
+    }
+
+    if json {
+        println!("{}", annotate_json(&game, &report, options.depth));
+        return Ok(());
+    }
+
+    print!("{}", annotate(&game, &report, options.depth));
+
+    if let Some(path) = &options.svg {
+        println!("\nScore graph written to {}", path);
+    }
+
+    return Ok(());
+}
+
+fn label_command(args: &[String], json: bool) -> Result<(), String> {
+    let (depth, args) = parse_depth(args)?;
+    let (threads, args) = match args.first().map(|s| s.as_str()) {
+        Some("-j") => (args.get(1).and_then(|j| j.parse().ok()).ok_or("Expected a number of threads after -j")?, &args[2..]),
+        _          => (1, args)
+    };
+
+    let (input, output) = match args {
+        [input, output] => (Path::new(input), Path::new(output)),
+        _               => return Err(format!("Expected an input and an output file\n\n{}", USAGE))
+    };
+
+    let config = LabelConfig { depth, threads, ..LabelConfig::default() };
+    let summary = label::label_file(input, output, config).map_err(|e| format!("Can't label {}: {}", input.display(), e))?;
+
+    if json {
+        println!("{}", json_object(&[("labeled", summary.labeled.to_string()), ("skipped", summary.skipped.to_string()),
+                                     ("invalid", summary.invalid.to_string())]));
+    } else {
+        println!("Labeled {} positions, skipped {} already labeled and {} invalid ones", summary.labeled, summary.skipped, summary.invalid);
+    }
+
+    return Ok(());
+}
+
+/// Parses the arguments of the serve command into the access settings and the address.
+fn parse_serve_options(args: &[String]) -> Result<(Access, &str), String> {
+    let mut access = Access::default();
+    let mut args = args;
+
+    loop {
+        match args {
+            [option, token, rest @ ..] if option == "--token" => {
+                access.tokens.push(token.clone());
+                args = rest;
+            },
+            [option, rate, rest @ ..] if option == "--rate" => {
+                access.requests_per_minute = Some(rate.parse().map_err(|_| format!("Invalid number of requests: {}", rate))?);
+                args = rest;
+            },
+            [option, ..] if option.starts_with("--") => return Err(format!("Unknown option: {}\n\n{}", option, USAGE)),
+            [address] => return Ok((access, address)),
+            []        => return Ok((access, "127.0.0.1:8080")),
+            _         => return Err(format!("Expected a single address\n\n{}", USAGE))
+        }
+    }
+}
+
+fn serve_command(args: &[String]) -> Result<(), String> {
+    let (access, address) = parse_serve_options(args)?;
+
+    println!("Listening on {}", address);
+
+    return http::serve(address, &access).map_err(|e| e.to_string());
+}
+
+/// The options of the worker command.
+#[derive(PartialEq, Debug)]
+struct WorkerOptions {
+    url: String,
+    name: String,
+    concurrency: usize,
+    pin_threads: bool
+}
+
+fn parse_worker_options(args: &[String]) -> Result<WorkerOptions, String> {
+    let mut options = WorkerOptions { url: String::new(), name: build_info::engine_name(), concurrency: 1, pin_threads: false };
+    let mut args = args;
+
+    loop {
+        match args {
+            [option, name, rest @ ..] if option == "--name" => {
+                options.name = name.clone();
+                args = rest;
+            },
+            [option, games, rest @ ..] if option == "-j" => {
+                options.concurrency = games.parse().ok().filter(|games| *games > 0).ok_or(format!("Invalid number of games: {}", games))?;
+                args = rest;
+            },
+            [option, rest @ ..] if option == "--pin" => {
+                options.pin_threads = true;
+                args = rest;
+            },
+            [option, ..] if option.starts_with('-') => return Err(format!("Unknown option: {}\n\n{}", option, USAGE)),
+            [url] => {
+                options.url = url.clone();
+                return Ok(options);
+            },
+            _     => return Err(format!("Expected the URL of the server\n\n{}", USAGE))
+        }
+    }
+}
+
+/// The options of the match command.
+struct MatchOptions {
+    engines: [String; 2],
+    games: usize,
+    config: ConcurrencyConfig,
+    limit: UciLimit,
+    max_plies: usize,
+    book: Option<String>,
+    sprt: Option<Sprt>,
+    pgn: Option<String>,
+    state: String
+}
+
+fn parse_match_options(args: &[String]) -> Result<MatchOptions, String> {
+    let number = |value: &String| value.parse().ok().filter(|n| *n > 0).ok_or(format!("Invalid number: {}\n\n{}", value, USAGE));
+    let elo = |value: &String| value.parse::<f64>().map_err(|_| format!("Invalid Elo: {}\n\n{}", value, USAGE));
+
+    let mut options = MatchOptions {
+        engines: [String::new(), String::new()],
+        games: 100,
+        config: ConcurrencyConfig::default(),
+        limit: UciLimit::Depth(DEFAULT_DEPTH as u32),
+        max_plies: worker::DEFAULT_MAX_PLIES,
+        book: None,
+        sprt: None,
+        pgn: None,
+        state: String::from("match.state")
+    };
+    let mut args = args;
+
+    loop {
+        match args {
+            [option, n, rest @ ..] if option == "-n" => {
+                options.games = number(n)?;
+                args = rest;
+            },
+            [option, n, rest @ ..] if option == "-j" => {
+                options.config.concurrency = number(n)?;
+                args = rest;
+            },
+            [option, rest @ ..] if option == "--pin" => {
+                options.config.pin_threads = true;
+                args = rest;
+            },
+            [option, kind, value, rest @ ..] if option == "--limit" => {
+                options.limit = UciLimit::parse(&format!("{} {}", kind, value)).ok_or(format!("Invalid limit: {} {}\n\n{}", kind, value, USAGE))?;
+                args = rest;
+            },
+            [option, plies, rest @ ..] if option == "--plies" => {
+                options.max_plies = number(plies)?;
+                args = rest;
+            },
+            [option, elo0, elo1, rest @ ..] if option == "--sprt" => {
+                options.sprt = Some(Sprt { elo0: elo(elo0)?, elo1: elo(elo1)?, alpha: 0.05, beta: 0.05 });
+                args = rest;
+            },
+            [option, file, rest @ ..] if ["--book", "--pgn", "--state"].contains(&option.as_str()) => {
+                match option.as_str() {
+                    "--book" => options.book = Some(file.clone()),
+                    "--pgn"  => options.pgn = Some(file.clone()),
+                    _        => options.state = file.clone()
+                }
+
+                args = rest;
+            },
+            [option, ..] if option.starts_with('-') => return Err(format!("Unknown option: {}\n\n{}", option, USAGE)),
+            [first, second] => {
+                options.engines = [first.clone(), second.clone()];
+                return Ok(options);
+            },
+            _ => return Err(format!("Expected two engines\n\n{}", USAGE))
+        }
+    }
+}
+
+/// Creates the engines of a match: the built-in engines by their names and UCI engines by
+/// their commands, which may carry arguments. UCI engines are started once to make sure
+/// they work before the worker threads start their own instances.
+fn match_engine_factory(engine: &str, limit: UciLimit) -> Result<EngineFactory, String> {
+    let depth = match limit {
+        UciLimit::Depth(depth) => depth.min(u8::MAX as u32) as u8,
+        _                      => DEFAULT_DEPTH
+    };
+
+    return match engine {
+        "alphabeta"     => Ok(Arc::new(move || -> Box<dyn Engine> { Box::new(SearchEngine::new(depth)) })),
+        "mcts"          => Ok(Arc::new(|| -> Box<dyn Engine> { Box::new(MctsEngine::new(Simulation::Evaluation)) })),
+        "mcts-playouts" => Ok(Arc::new(|| -> Box<dyn Engine> { Box::new(MctsEngine::new(Simulation::Playout)) })),
+        command         => {
+            let mut words = command.split_whitespace().map(String::from);
+            let spec = UciEngineSpec { name: command.to_string(), command: words.next().unwrap_or_default(), args: words.collect(), options: Vec::new() };
+
+            UciEngine::start(&spec, limit).map_err(|e| format!("Can't start {}: {}", command, e))?;
+
+            Ok(Arc::new(move || -> Box<dyn Engine> { Box::new(UciEngine::start(&spec, limit).expect("The engine stopped starting")) }))
+        }
+    };
+}
+
+fn match_command(args: &[String]) -> Result<(), String> {
+    let options = parse_match_options(args)?;
+    let limit = options.limit;
+    let mut factories = [match_engine_factory(&options.engines[0], limit)?, match_engine_factory(&options.engines[1], limit)?];
+
+    let state = PathBuf::from(&options.state);
+    let instances = [factories[0](), factories[1]()];
+
+    let mut tournament = if state.exists() {
+        let tournament = Tournament::resume(instances, state.clone()).map_err(|e| format!("Can't resume from {}: {}", options.state, e))?;
+        println!("Resuming after {} games", tournament.engine_match.records.len());
+
+        // The remaining games are played with the limit of the earlier ones.
+        if let Some(saved) = tournament.limit.filter(|saved| *saved != limit) {
+            println!("Playing with the saved limit {}", saved);
+            factories = [match_engine_factory(&options.engines[0], saved)?, match_engine_factory(&options.engines[1], saved)?];
+        }
+
+        tournament
+    } else {
+        let openings = match &options.book {
+            Some(book) => OpeningSuite::load(book).map_err(|e| format!("Can't read {}: {}", book, e))?,
+            None       => OpeningSuite::single(Position::starting_position())
+        };
+
+        let mut tournament = Tournament::new(instances, options.games, options.max_plies, openings, state);
+        tournament.sprt = options.sprt;
+        tournament.limit = Some(limit);
+        tournament.pgn = options.pgn.map(|pgn| (PathBuf::from(pgn), MatchInfo::new("RustChess match", &tournament.engine_match.engines, Some(limit))));
+
+        tournament
+    };
+
+    tournament.factories = Some(factories);
+    tournament.concurrency = options.config;
+
+    return tournament.run().map_err(|e| e.to_string());
+}
+
+#[cfg(feature = "online")]
+fn worker_command(args: &[String]) -> Result<(), String> {
+    let options = parse_worker_options(args)?;
+    let mut server = crate::runner::worker::HttpJobServer::new(&options.url, &options.name);
+    let config = crate::runner::concurrency::ConcurrencyConfig { concurrency: options.concurrency, pin_threads: options.pin_threads };
+
+    println!("Working for {} as {}", options.url, options.name);
+
+    return crate::runner::worker::run_worker(&mut server, config).map_err(|e| format!("Worker stopped: {}", e));
+}
+
+#[cfg(not(feature = "online"))]
+fn worker_command(args: &[String]) -> Result<(), String> {
+    parse_worker_options(args)?;
+
+    return Err(String::from("This build can't connect to a server, build it with the online feature"));
+}
+
+fn proof_game_command(args: &[String]) -> Result<(), String> {
+    let plies: u32 = args.first().and_then(|p| p.parse().ok()).ok_or(format!("Expected a number of plies\n\n{}", USAGE))?;
+
+    if args.len() < 2 {
+        return Err(format!("Expected a FEN\n\n{}", USAGE));
+    }
+
+    let target = parse_fen(&args[1..])?;
+    let start = Position::starting_position();
+    let games = ProofGameSearch::new(target).find(plies, 2);
+
+    match games.as_slice() {
+        []            => println!("No proof game in {} plies.", plies),
+        [game]        => println!("Unique proof game: {}", numbered_line(&start, game)),
+        [first, second, ..] => {
+            println!("Proof game: {}", numbered_line(&start, first));
+            println!("Not unique, another one is: {}", numbered_line(&start, second));
+        }
+    }
+
+    return Ok(());
+}
+
+/// Parses the arguments of the repertoire command into the PGN file, the options and the
+/// output file if any.
+fn parse_repertoire_options(args: &[String]) -> Result<(String, RepertoireOptions, Option<String>), String> {
+    let (pgn, mut args) = match args {
+        [pgn, rest @ ..] if !pgn.starts_with('-') => (pgn.clone(), rest),
+        _                                        => return Err(format!("Expected a PGN file\n\n{}", USAGE))
+    };
+
+    let mut options = RepertoireOptions::default();
+    let mut output = None;
+
+    loop {
+        match args {
+            [option, rest @ ..] if option == "--black" => {
+                options.color = BLACK;
+                args = rest;
+            },
+            [option, plies, rest @ ..] if option == "--plies" => {
+                options.plies = plies.parse().map_err(|_| format!("Invalid number of plies: {}", plies))?;
+                args = rest;
+            },
+            [option, depth, rest @ ..] if option == "-d" || option == "--depth" => {
+                options.depth = depth.parse().map_err(|_| format!("Invalid depth: {}", depth))?;
+                args = rest;
+            },
+            [option, replies, rest @ ..] if option == "--replies" => {
+                options.replies = replies.parse().ok().filter(|&replies| replies > 0).ok_or(format!("Invalid number of replies: {}", replies))?;
+                args = rest;
+            },
+            [option, margin, rest @ ..] if option == "--margin" => {
+                options.margin = margin.parse().ok().filter(|&margin| margin >= 0).ok_or(format!("Invalid margin: {}", margin))?;
+                args = rest;
+            },
+            [option, file, rest @ ..] if option == "--output" => {
+                output = Some(file.clone());
+                args = rest;
+            },
+            [] => return Ok((pgn, options, output)),
+            _  => return Err(format!("Invalid options: {}\n\n{}", args.join(" "), USAGE))
+        }
+    }
+}
+
+fn repertoire_command(args: &[String]) -> Result<(), String> {
+    let (path, options, output) = parse_repertoire_options(args)?;
+    let text = fs::read_to_string(&path).map_err(|e| format!("Can't read {}: {}", path, e))?;
+    let tree = OpeningTree::from_games(&pgn::read_games(&text), options.plies);
+
+    let repertoire = repertoire::build(&tree, Position::starting_position(), &options);
+    let pgn = repertoire::to_pgn(&repertoire, &options);
+
+    match output {
+        Some(file) => fs::write(&file, pgn).map_err(|e| format!("Can't write {}: {}", file, e))?,
+        None       => print!("{}", pgn)
+    }
+
+    return Ok(());
+}
+
+/// Writes moves played from the given position in SAN with move numbers.
+fn numbered_line(position: &Position, moves: &[Move]) -> String {
+    let mut position = position.clone();
+    let mut tokens = Vec::new();
+
+    for (i, &m) in moves.iter().enumerate() {
+        if position.turn == WHITE {
+            tokens.push(format!("{}.", i / 2 + 1));
+        }
+
+        tokens.push(position.move_to_san(m));
+        position = position.make_move(m);
+    }
+
+    return tokens.join(" ");
+}
+
+fn solve_command(args: &[String]) -> Result<(), String> {
+    let (moves, args) = match args.first().map(|s| s.as_str()) {
+        Some("-n") => (args.get(1).and_then(|n| n.parse().ok()).ok_or("Expected a number of moves after -n")?, &args[2..]),
+        _          => (2, args)
+    };
+
+    if args.is_empty() {
+        return Err(format!("Expected a FEN\n\n{}", USAGE));
+    }
+
+    let position = parse_fen(args)?;
+
+    println!("{}\n\nMate in {}\n", position, moves);
+    print!("{}", problem::solve(&position, moves));
+
+    return Ok(());
+}
+
+fn db_command(args: &[String]) -> Result<(), String> {
+    let path = args.first().ok_or(format!("Expected a database\n\n{}", USAGE))?;
+    let mut storage = open_database(path)?;
+
+    print!("{}", database(storage.as_mut(), &args[1..])?);
+
+    return Ok(());
+}
+
+#[cfg(feature = "sqlite")]
+fn open_database(path: &str) -> Result<Box<dyn Storage>, String> {
+    let storage = crate::storage::sqlite::SqliteStorage::open(path).map_err(|e| format!("Can't open {}: {}", path, e))?;
+
+    return Ok(Box::new(storage));
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn open_database(_path: &str) -> Result<Box<dyn Storage>, String> {
+    return Err(String::from("This build has no database support, build it with the sqlite feature"));
+}
+
+/// Runs a subcommand of the db command on the storage and returns its output.
+fn database(storage: &mut dyn Storage, args: &[String]) -> Result<String, String> {
+    match args {
+        [action, path] if action == "import" => {
+            let text = fs::read_to_string(path).map_err(|e| format!("Can't read {}: {}", path, e))?;
+            let games = pgn::read_games(&text);
+
+            for game in &games {
+                storage.save_game(game).map_err(|e| e.to_string())?;
+            }
+
+            return Ok(format!("Imported {} games\n", games.len()));
+        },
+        [action, options @ ..] if action == "games" => {
+            let query = parse_game_query(options)?;
+            let games = storage.games(&query).map_err(|e| e.to_string())?;
+
+            return Ok(games.iter().map(|game| format!("{:>5}  {}  {} - {}  {}\n", game.id, game.date, game.white, game.black, game.result)).collect());
+        },
+        [action, path] if action == "novelty" => {
+            let text = fs::read_to_string(path).map_err(|e| format!("Can't read {}: {}", path, e))?;
+            let mut output = String::new();
+
+            for game in pgn::read_games(&text) {
+                let title = format!("{} - {}", game.tag("White").unwrap_or("?"), game.tag("Black").unwrap_or("?"));
+
+                match storage::find_novelty(storage, &game.game).map_err(|e| e.to_string())? {
+                    Some(novelty) => output += &format!("{}: {}\n", title, novelty),
+                    None          => output += &format!("{}: No novelty, every position is known\n", title)
+                }
+            }
+
+            return Ok(output);
+        },
+        _ => return Err(format!("Expected import <pgn>, games or novelty <pgn>\n\n{}", USAGE))
+    }
+}
+
+/// The games to import and where to put them.
+#[derive(PartialEq, Eq, Debug)]
+struct ImportOptions {
+    site: Site,
+    user: String,
+    max: Option<usize>,
+    pgn: Option<String>,
+    database: Option<String>
+}
+
+fn parse_import_options(args: &[String]) -> Result<ImportOptions, String> {
+    let (site, user, mut args) = match args {
+        [site, user, rest @ ..] => (Site::from_name(site).ok_or(format!("Unknown site: {}", site))?, user.clone(), rest),
+        _                       => return Err(format!("Expected a site and a user\n\n{}", USAGE))
+    };
+
+    let mut options = ImportOptions { site, user, max: None, pgn: None, database: None };
+
+    loop {
+        match args {
+            [option, max, rest @ ..] if option == "--max" => {
+                options.max = Some(max.parse().map_err(|_| format!("Invalid number of games: {}", max))?);
+                args = rest;
+            },
+            [option, file, rest @ ..] if option == "--pgn" => {
+                options.pgn = Some(file.clone());
+                args = rest;
+            },
+            [option, file, rest @ ..] if option == "--db" => {
+                options.database = Some(file.clone());
+                args = rest;
+            },
+            [] if options.pgn.is_none() && options.database.is_none() => return Err(format!("Expected --pgn or --db\n\n{}", USAGE)),
+            []                                                        => return Ok(options),
+            _                                                         => return Err(format!("Invalid options: {}\n\n{}", args.join(" "), USAGE))
+        }
+    }
+}
+
+fn import_command(args: &[String]) -> Result<(), String> {
+    let options = parse_import_options(args)?;
+    let mut storage = match &options.database {
+        Some(path) => Some(open_database(path)?),
+        None       => None
+    };
+
+    let games = fetch_games(options.site, &options.user, options.max)?;
+
+    archive::save_games(&games, storage.as_mut().map(|storage| storage.as_mut() as &mut dyn Storage), options.pgn.as_deref().map(Path::new)).map_err(|e| e.to_string())?;
+    println!("Imported {} games of {}", games.len(), options.user);
+
+    return Ok(());
+}
+
+#[cfg(feature = "online")]
+fn fetch_games(site: Site, user: &str, max: Option<usize>) -> Result<Vec<pgn::PgnGame>, String> {
+    return archive::fetch_games(site, user, max).map_err(|e| format!("Can't fetch the games of {}: {}", user, e));
+}
+
+#[cfg(not(feature = "online"))]
+fn fetch_games(_site: Site, _user: &str, _max: Option<usize>) -> Result<Vec<pgn::PgnGame>, String> {
+    return Err(String::from("This build can't fetch games, build it with the online feature"));
+}
+
+/// The options of a correspondence analysis.
+#[derive(PartialEq, Debug)]
+struct CorrespondenceOptions {
+    checkpoint: String,
+    time: Duration,
+    interval: Duration,
+    hash: usize,
+    pgn: Option<String>,
+    fen: Option<String>
+}
+
+fn parse_correspondence_options(args: &[String]) -> Result<CorrespondenceOptions, String> {
+    let (checkpoint, mut args) = match args {
+        [checkpoint, rest @ ..] if !checkpoint.starts_with("--") => (checkpoint.clone(), rest),
+        _                                                        => return Err(format!("Expected a checkpoint file\n\n{}", USAGE))
+    };
+
+    let mut options = CorrespondenceOptions {
+        checkpoint, time: Duration::from_secs(24 * 3600), interval: Duration::from_secs(600), hash: tt::DEFAULT_SIZE_MB, pgn: None, fen: None
+    };
+
+    loop {
+        match args {
+            [option, hours, rest @ ..] if option == "--hours" => {
+                let hours: f64 = hours.parse().ok().filter(|hours: &f64| *hours > 0.0).ok_or(format!("Invalid number of hours: {}", hours))?;
+
+                options.time = Duration::from_secs_f64(hours * 3600.0);
+                args = rest;
+            },
+            [option, minutes, rest @ ..] if option == "--interval" => {
+                let minutes: u64 = minutes.parse().ok().filter(|&minutes| minutes > 0).ok_or(format!("Invalid interval: {}", minutes))?;
+
+                options.interval = Duration::from_secs(minutes * 60);
+                args = rest;
+            },
+            [option, mb, rest @ ..] if option == "--hash" => {
+                options.hash = mb.parse().map_err(|_| format!("Invalid hash size: {}", mb))?;
+                args = rest;
+            },
+            [option, file, rest @ ..] if option == "--pgn" => {
+                options.pgn = Some(file.clone());
+                args = rest;
+            },
+            // The FEN contains spaces, so it takes the remaining arguments.
+            [option, fen @ ..] if option == "--fen" && !fen.is_empty() => {
+                options.fen = Some(fen.join(" "));
+                args = &[];
+            },
+            [] if options.pgn.is_some() && options.fen.is_some() => return Err(format!("Expected either --pgn or --fen\n\n{}", USAGE)),
+            []                                                    => return Ok(options),
+            _                                                     => return Err(format!("Invalid options: {}\n\n{}", args.join(" "), USAGE))
+        }
+    }
+}
+
+fn correspond_command(args: &[String]) -> Result<(), String> {
+    let options = parse_correspondence_options(args)?;
+    let path = Path::new(&options.checkpoint);
+
+    let requested = if let Some(file) = &options.pgn {
+        let text = fs::read_to_string(file).map_err(|e| format!("Can't read {}: {}", file, e))?;
+        let game = pgn::read_games(&text).into_iter().next().ok_or(format!("No game in {}", file))?.game;
+
+        Some(Checkpoint::new(game.starting_position(), &game.main_line()))
+    } else if let Some(fen) = &options.fen {
+        Some(Checkpoint::new(&parse_fen(std::slice::from_ref(fen))?, &[]))
+    } else {
+        None
+    };
+
+    let saved = Checkpoint::load(path).map_err(|e| e.to_string())?;
+
+    // The saved analysis is resumed unless another game is given.
+    let (mut checkpoint, mut tt) = match (requested, saved) {
+        (Some(requested), Some((saved, tt))) if requested.same_game(&saved) => (saved, tt),
+        (Some(requested), _)                                                  => (requested, TranspositionTable::new(options.hash, Replacement::DepthPreferred)),
+        (None, Some(saved))                                                   => saved,
+        (None, None)                                                          => return Err(format!("No checkpoint at {}, give a game with --pgn or --fen", options.checkpoint))
+    };
+
+    if checkpoint.depth > 0 {
+        println!("Resuming at {}", correspondence::describe(&checkpoint));
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+
+    interrupt::interruptible(&stop, || {
+        return correspondence::analyze(path, &mut checkpoint, &mut tt, options.time, options.interval, &stop, |checkpoint| {
+            println!("{}", correspondence::describe(checkpoint));
+        });
+    }).map_err(|e| e.to_string())?;
+
+    return Ok(());
+}
+
+/// The address of the Free Internet Chess Server.
+const FICS_ADDRESS: &str = "freechess.org:5000";
+
+/// Parses the options of the ics command into the account and seek, the address of the
+/// server and the options selecting the engine.
+fn parse_ics_options(args: &[String]) -> Result<(IcsOptions, String, Vec<String>), String> {
+    let (user, mut args) = match args {
+        [user, rest @ ..] if !user.starts_with("--") => (user.clone(), rest),
+        _                                            => return Err(format!("Expected a user\n\n{}", USAGE))
+    };
+
+    let mut options = IcsOptions { user, password: None, seek: None };
+    let mut address = String::from(FICS_ADDRESS);
+    let mut engine = Vec::new();
+
+    loop {
+        match args {
+            [option, password, rest @ ..] if option == "--password" => {
+                options.password = Some(password.clone());
+                args = rest;
+            },
+            [option, minutes, increment, rest @ ..] if option == "--seek" => {
+                let minutes = minutes.parse().map_err(|_| format!("Invalid minutes: {}", minutes))?;
+                let increment = increment.parse().map_err(|_| format!("Invalid increment: {}", increment))?;
+
+                options.seek = Some((minutes, increment));
+                args = rest;
+            },
+            [option, name, rest @ ..] if option == "--engine" => {
+                engine = vec![option.clone(), name.clone()];
+                args = rest;
+            },
+            [server] if !server.starts_with("--") => {
+                address = server.clone();
+                args = &[];
+            },
+            []                                    => return Ok((options, address, engine)),
+            _                                     => return Err(format!("Invalid options: {}\n\n{}", args.join(" "), USAGE))
+        }
+    }
+}
+
+fn ics_command(args: &[String]) -> Result<(), String> {
+    let (options, address, engine) = parse_ics_options(args)?;
+    let mut engine = parse_engine(&engine)?;
+
+    let stream = TcpStream::connect(&address).map_err(|e| format!("Can't connect to {}: {}", address, e))?;
+    let input = stream.try_clone().map_err(|e| e.to_string())?;
+
+    return Ics::new(engine.as_mut(), options, stream).run(input, io::stdout()).map_err(|e| e.to_string());
+}
+
+/// Parses the options of the db games command into a query.
+fn parse_game_query(args: &[String]) -> Result<GameQuery, String> {
+    let mut query = GameQuery::default();
+    let mut args = args;
+
+    loop {
+        match args {
+            [option, value, rest @ ..] if option == "--player" || option == "--from" || option == "--to" => {
+                match option.as_str() {
+                    "--player" => query.player = Some(value.clone()),
+                    "--from"   => query.from = Some(value.clone()),
+                    _          => query.to = Some(value.clone())
+                }
+
+                args = rest;
+            },
+            // The FEN takes the remaining arguments, since it contains spaces.
+            [option, fen @ ..] if (option == "--fen" || option == "--pawns") && !fen.is_empty() => {
+                let fen = fen.join(" ");
+                let position = Position::from_fen(fen.clone()).ok_or(format!("Invalid FEN: {}", fen))?;
+
+                match option.as_str() {
+                    "--fen" => query.position = Some(position.hash_key()),
+                    _       => query.pawns = Some(position.pawn_key())
+                }
+
+                return Ok(query);
+            },
+            [] => return Ok(query),
+            _  => return Err(format!("Invalid query: {}\n\n{}", args.join(" "), USAGE))
+        }
+    }
+}
+
+fn stats_command(args: &[String], json: bool) -> Result<(), String> {
+    let (per_ply, args) = match args.first().map(|s| s.as_str()) {
+        Some("--per-ply") => (true, &args[1..]),
+        _                 => (false, args)
+    };
+
+    let path = args.first().ok_or(format!("Expected a PGN file\n\n{}", USAGE))?;
+    let text = fs::read_to_string(path).map_err(|e| format!("Can't read {}: {}", path, e))?;
+    let games: Vec<BranchingStats> = pgn::read_games(&text).iter().map(|g| BranchingStats::new(&g.game)).collect();
+
+    if json {
+        let per_ply: Vec<String> = stats::mean_per_ply(&games).iter().map(|mean| mean.to_string()).collect();
+        let stats: Vec<String> = games.iter().map(stats_json).collect();
+
+        println!("{}", json_object(&[("games", format!("[{}]", stats.join(","))), ("per_ply", format!("[{}]", per_ply.join(",")))]));
+
+        return Ok(());
+    }
+
+    for (i, game) in games.iter().enumerate() {
+        println!("Game {}: {}", i + 1, game);
+    }
+
+    let all = BranchingStats { counts: games.iter().flat_map(|g| g.counts.iter().copied()).collect() };
+    println!("\nTotal: {} games, {}", games.len(), all);
+
+    if per_ply {
+        println!("\nPly  Mean");
+
+        for (ply, mean) in stats::mean_per_ply(&games).iter().enumerate() {
+            println!("{:>3}  {:.1}", ply + 1, mean);
+        }
+    }
+
+    return Ok(());
+}
+
+/// Writes the branching statistics of a game as a JSON object.
+fn test_suite_command(args: &[String], json: bool) -> Result<(), String> {
+    let (depth, args) = parse_depth(args)?;
+    let path = match args {
+        [path] => path,
+        _      => return Err(format!("Expected an EPD file\n\n{}", USAGE))
+    };
+
+    let text = fs::read_to_string(path).map_err(|e| format!("Can't read {}: {}", path, e))?;
+    let suite = testsuite::read_suite(&text).map_err(|line| format!("Invalid test position in line {} of {}", line, path))?;
+    let results: Vec<TestResult> = suite.iter().map(|position| position.solve(depth)).collect();
+    let solved = results.iter().filter(|result| result.solved).count();
+
+    if json {
+        println!("{}", test_suite_json(&suite, &results, depth));
+        return Ok(());
+    }
+
+    for result in results.iter() {
+        println!("{}", result);
+    }
+
+    println!("\nSolved {} of {} positions at depth {}", solved, results.len(), depth);
+
+    return Ok(());
+}
+
+fn test_suite_json(suite: &[TestPosition], results: &[TestResult], depth: u8) -> String {
+    let sans = |sans: Vec<String>| format!("[{}]", sans.iter().map(|san| http::json_string(san)).collect::<Vec<String>>().join(","));
+
+    let positions: Vec<String> = suite.iter().zip(results).map(|(position, result)| json_object(&[
+        ("id", http::json_string(&result.id)),
+        ("found", result.found.as_deref().map_or(String::from("null"), http::json_string)),
+        ("best", sans(position.best_sans())),
+        ("avoid", sans(position.avoid_sans())),
+        ("solved", result.solved.to_string())
+    ])).collect();
+
+    return json_object(&[("depth", depth.to_string()), ("positions", format!("[{}]", positions.join(","))),
+                         ("solved", results.iter().filter(|result| result.solved).count().to_string()), ("total", results.len().to_string())]);
+}
+
+fn stats_json(stats: &BranchingStats) -> String {
+    let counts: Vec<String> = stats.counts.iter().map(|c| c.to_string()).collect();
+    let forced: Vec<String> = stats.forced_sequences().iter().map(|(start, len)| format!("[{},{}]", start + 1, len)).collect();
+
+    return json_object(&[
+        ("counts", format!("[{}]", counts.join(","))),
+        ("mean", stats.mean().map_or(String::from("null"), |mean| mean.to_string())),
+        ("forced", format!("[{}]", forced.join(",")))
+    ]);
+}
+
+/// Creates the engine selected by the options of the uci, xboard and ics commands.
+fn parse_engine(args: &[String]) -> Result<Box<dyn Engine>, String> {
+    return match args {
+        []                                         => Ok(Box::new(SearchEngine::new(DEFAULT_DEPTH))),
+        [option, name] if option == "--engine"     => match name.as_str() {
+            "alphabeta"     => Ok(Box::new(SearchEngine::new(DEFAULT_DEPTH))),
+            "mcts"          => Ok(Box::new(MctsEngine::new(Simulation::Evaluation))),
+            "mcts-playouts" => Ok(Box::new(MctsEngine::new(Simulation::Playout))),
+            _               => Err(format!("Unknown engine: {}\n\n{}", name, USAGE))
+        },
+        _ => Err(format!("Invalid options: {}\n\n{}", args.join(" "), USAGE))
+    };
+}
+
+/// The player a game against the engine is rated for and the database keeping the rating.
+#[derive(PartialEq, Eq, Debug)]
+struct RatedPlayer {
+    name: String,
+    database: String
+}
+
+/// Parses the options of the play command: the search depth, the threshold of the coach
+/// if it is enabled, the color of the human player, the engine's strength, the player
+/// whose rating is kept and the file the game is recorded to.
+fn parse_play_options(args: &[String]) -> Result<(PlayOptions, Option<RatedPlayer>, Option<String>), String> {
+    let (depth, mut args) = parse_depth(args)?;
+    let mut coach = None;
+    let mut human = WHITE;
+    let mut strength = None;
+    let (mut name, mut database, mut record) = (None, None, None);
+
+    while let Some(arg) = args.first() {
+        match arg.as_str() {
+            "--black" => {
+                human = BLACK;
+                args = &args[1..];
+            },
+            "--player" | "--db" | "--record" => {
+                let value = args.get(1).ok_or(format!("Expected a value after {}\n\n{}", arg, USAGE))?.clone();
+
+                match arg.as_str() {
+                    "--player" => name = Some(value),
+                    "--db"     => database = Some(value),
+                    _          => record = Some(value)
+                }
+
+                args = &args[2..];
+            },
+            "--skill" | "--elo" => {
+                let value: u32 = args.get(1).and_then(|value| value.parse().ok()).ok_or(format!("Expected a number after {}\n\n{}", arg, USAGE))?;
+
+                strength = Some(if arg == "--skill" { Strength::from_skill(value.min(MAX_SKILL as u32) as u8) } else { Strength::from_elo(value) });
+                args = &args[2..];
+            },
+            "--coach" => {
+                match args.get(1).and_then(|t| t.parse().ok()) {
+                    Some(threshold) => {
+                        coach = Some(threshold);
+                        args = &args[2..];
+                    },
+                    None => {
+                        coach = Some(coach::DEFAULT_THRESHOLD);
+                        args = &args[1..];
+                    }
+                }
+            },
+            _ => return Err(format!("Unknown option: {}\n\n{}", arg, USAGE))
+        }
+    }
+
+    let player = match (name, database) {
+        (Some(name), Some(database)) => Some(RatedPlayer { name, database }),
+        (None, None)                 => None,
+        _                            => return Err(format!("--player and --db have to be given together\n\n{}", USAGE))
+    };
+
+    return Ok((PlayOptions { depth, coach, human, interruptible: true, strength }, player, record));
+}
+
+fn play_command(args: &[String]) -> Result<(), String> {
+    let (options, player, record) = parse_play_options(args)?;
+    let mut engine = SearchEngine::new(options.depth);
+    engine.set_strength(options.strength);
+
+    // Open the database first, so a game isn't played for nothing.
+    let mut storage = match &player {
+        Some(player) => Some(open_database(&player.database)?),
+        None         => None
+    };
+
+    let mut recorder = GameRecorder::new(Position::starting_position());
+    let game = play::play_recorded(&mut engine, options, io::stdin().lock(), io::stdout(), &mut recorder).map_err(|e| e.to_string())?;
+
+    if let Some(path) = record {
+        let result = play::result(play::outcome(recorder.game().position()));
+        let pgn = recorder.to_pgn(&[(String::from("Result"), result.to_string())], result);
+
+        fs::write(&path, pgn).map_err(|e| format!("Can't write {}: {}", path, e))?;
+        println!("Recorded the game to {}", path);
+    }
+
+    if let (Some(player), Some(storage)) = (player, storage.as_mut()) {
+        match play::record_game(storage.as_mut(), &player.name, &options, &game).map_err(|e| e.to_string())? {
+            Some((old, new)) => println!("Rating of {}: {:.0} -> {:.0} (deviation {:.0})", player.name, old.rating, new.rating, new.deviation),
+            None             => println!("The game isn't over, so it was saved without rating it.")
+        }
+    }
+
+    return Ok(());
+}
+
+/// Parses the options of the replay command: the speed factor, 1 by default, and the file of
+/// the recording.
+fn parse_replay_options(args: &[String]) -> Result<(f64, String), String> {
+    let mut speed = 1.0;
+    let mut args = args;
+
+    loop {
+        match args {
+            [option, factor, rest @ ..] if option == "--speed" => {
+                speed = factor.parse().ok().filter(|speed: &f64| *speed > 0.0).ok_or(format!("Invalid speed: {}\n\n{}", factor, USAGE))?;
+                args = rest;
+            },
+            [option, ..] if option.starts_with('-') => return Err(format!("Unknown option: {}\n\n{}", option, USAGE)),
+            [file] => return Ok((speed, file.clone())),
+            _      => return Err(format!("Expected a recorded game\n\n{}", USAGE))
+        }
+    }
+}
+
+fn replay_command(args: &[String]) -> Result<(), String> {
+    let (speed, path) = parse_replay_options(args)?;
+    let text = fs::read_to_string(&path).map_err(|e| format!("Can't read {}: {}", path, e))?;
+    let recorder = GameRecorder::from_pgn(&text).ok_or(format!("No game found in {}", path))?;
+
+    let mut before = recorder.game().starting_position().clone();
+    let mut ply = 0;
+
+    recorder.replay(speed, |position, tm| {
+        let number = if before.turn == WHITE { format!("{}.", ply / 2 + 1) } else { format!("{}...", ply / 2 + 1) };
+
+        println!("\n{}\n", position);
+        print!("{} {} at {:.1}s", number, before.move_to_san(tm.m), tm.elapsed.as_secs_f64());
+
+        match &tm.analysis {
+            Some(analysis) => println!(" ({})", analysis),
+            None           => println!()
+        }
+
+        before = position.clone();
+        ply += 1;
+    });
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    fn args(s: &str) -> Vec<String> {
+        return s.split_whitespace().map(String::from).collect();
+    }
+
+    #[test]
+    fn parses_repertoire_options() {
+        let (pgn, options, output) = parse_repertoire_options(&args("games.pgn --black --plies 8 -d 4 --replies 2 --margin 30 --output rep.pgn")).unwrap();
+
+        assert_eq!((pgn.as_str(), options.color, options.plies, options.depth), ("games.pgn", BLACK, 8, 4));
+        assert_eq!((options.replies, options.margin, output), (2, 30, Some(String::from("rep.pgn"))));
+        assert_eq!(parse_repertoire_options(&args("games.pgn")).unwrap().1.color, WHITE);
+        assert!(parse_repertoire_options(&args("games.pgn --replies 0")).is_err());
+        assert!(parse_repertoire_options(&args("--black")).is_err());
+    }
+
+    #[test]
+    fn parses_analysis_constraints() {
+        let given = args("--exclude e2e4 --reply E7E5 --exclude d2d4 8/8/8/8/8/8/8/K6k w - - 0 1");
+        let (excluded, replies, rest) = parse_constraints(&given);
+
+        assert_eq!((excluded, replies), (vec![String::from("e2e4"), String::from("d2d4")], vec![String::from("e7e5")]));
+        assert_eq!(rest.join(" "), "8/8/8/8/8/8/8/K6k w - - 0 1");
+        assert!(analyze_command(&args("--exclude e2e5"), false).is_err());
+    }
+
+    #[test]
+    fn parses_depth_options() {
+        let a = args("-d 5 8/8/8/8/8/8/8/8 w - - 0 1");
+        let (depth, rest) = parse_depth(&a).unwrap();
+
+        assert_eq!(depth, 5);
+        assert_eq!(rest.len(), 6);
+        assert_eq!(parse_depth(&args("")).unwrap().0, DEFAULT_DEPTH);
+        assert!(parse_depth(&args("-d x")).is_err());
+    }
+
+    #[test]
+    fn selects_the_engine() {
+        let name = |a| parse_engine(&args(a)).map(|engine| engine.name());
+
+        assert_eq!(name("").unwrap(), build_info::engine_name());
+        assert_eq!(name("--engine alphabeta").unwrap(), build_info::engine_name());
+        assert!(name("--engine mcts").unwrap().ends_with("MCTS"));
+        assert_eq!(parse_engine(&args("--engine mcts-playouts")).unwrap().options()[1].1, "Playout");
+        assert!(name("--engine minimax").is_err());
+        assert!(name("--engine").is_err());
+    }
+
+    #[test]
+    fn parses_import_options() {
+        let options = parse_import_options(&args("lichess anna --max 20 --pgn anna.pgn")).unwrap();
+
+        assert_eq!(options, ImportOptions { site: Site::Lichess, user: String::from("anna"), max: Some(20), pgn: Some(String::from("anna.pgn")), database: None });
+        assert_eq!(parse_import_options(&args("chess.com anna --db games.sqlite")).unwrap().database, Some(String::from("games.sqlite")));
+        assert!(parse_import_options(&args("lichess anna")).is_err());
+        assert!(parse_import_options(&args("fics anna --pgn a.pgn")).is_err());
+        assert!(parse_import_options(&args("lichess anna --max all --pgn a.pgn")).is_err());
+    }
+
+    #[test]
+    fn parses_correspondence_options() {
+        let options = parse_correspondence_options(&args("game.txt --hours 0.5 --interval 5 --fen 8/8/8/8/8/8/8/K1k5 w - - 0 1")).unwrap();
+
+        assert_eq!(options, CorrespondenceOptions {
+            checkpoint: String::from("game.txt"), time: Duration::from_secs(1800), interval: Duration::from_secs(300), hash: tt::DEFAULT_SIZE_MB,
+            pgn: None, fen: Some(String::from("8/8/8/8/8/8/8/K1k5 w - - 0 1"))
+        });
+        assert_eq!(parse_correspondence_options(&args("game.txt --hash 64 --pgn game.pgn")).unwrap().hash, 64);
+        assert!(parse_correspondence_options(&args("game.txt --pgn game.pgn --fen 8/8/8/8/8/8/8/K1k5 w - - 0 1")).is_err());
+        assert!(parse_correspondence_options(&args("game.txt --hours -1")).is_err());
+        assert!(parse_correspondence_options(&args("--hours 1")).is_err());
+    }
+
+    #[test]
+    fn parses_ics_options() {
+        let (options, address, engine) = parse_ics_options(&args("guest --seek 5 3 --engine mcts localhost:5000")).unwrap();
+
+        assert_eq!(options, IcsOptions { user: String::from("guest"), password: None, seek: Some((5, 3)) });
+        assert_eq!((address.as_str(), engine), ("localhost:5000", args("--engine mcts")));
+        assert_eq!(parse_ics_options(&args("anna --password secret")).unwrap().1, FICS_ADDRESS);
+        assert!(parse_ics_options(&args("anna --seek 5")).is_err());
+        assert!(parse_ics_options(&args("--password secret")).is_err());
+    }
+
+    #[test]
+    fn parses_play_options() {
+        let options = |a| parse_play_options(&args(a)).map(|(options, player, _)| (options.depth, options.coach, options.human, player));
+
+        assert_eq!(options("").unwrap(), (DEFAULT_DEPTH, None, WHITE, None));
+        assert_eq!(options("-d 2 --coach").unwrap(), (2, Some(coach::DEFAULT_THRESHOLD), WHITE, None));
+        assert_eq!(options("--coach 80 --black").unwrap(), (DEFAULT_DEPTH, Some(80), BLACK, None));
+        assert_eq!(options("--player Anna --db games.sqlite").unwrap().3, Some(RatedPlayer { name: String::from("Anna"), database: String::from("games.sqlite") }));
+        assert_eq!(parse_play_options(&args("--skill 5")).unwrap().0.strength, Some(Strength::from_skill(5)));
+        assert_eq!(parse_play_options(&args("--elo 2200")).unwrap().0.strength, Some(Strength::from_skill(MAX_SKILL)));
+        assert_eq!(parse_play_options(&args("--record game.pgn")).unwrap().2, Some(String::from("game.pgn")));
+        assert!(options("--skill weak").is_err());
+        assert!(options("--player Anna").is_err());
+        assert!(options("--record").is_err());
+        assert!(options("--db").is_err());
+        assert!(options("--foo").is_err());
+    }
+
+    #[test]
+    fn parses_replay_options() {
+        assert_eq!(parse_replay_options(&args("game.pgn")).unwrap(), (1.0, String::from("game.pgn")));
+        assert_eq!(parse_replay_options(&args("--speed 4 game.pgn")).unwrap(), (4.0, String::from("game.pgn")));
+        assert!(parse_replay_options(&args("--speed 0 game.pgn")).is_err());
+        assert!(parse_replay_options(&args("--speed fast game.pgn")).is_err());
+        assert!(parse_replay_options(&args("--fast game.pgn")).is_err());
+        assert!(parse_replay_options(&args("")).is_err());
+    }
+
+    #[test]
+    fn imports_and_lists_games() {
+        let path = std::env::temp_dir().join(format!("chess-cli-{}.pgn", std::process::id()));
+        fs::write(&path, "[White \"Anna\"]\n[Black \"Ben\"]\n[Date \"2024.03.17\"]\n\n1. e4 e5 1-0\n\n[White \"Ben\"]\n\n1. d4 *\n").unwrap();
+
+        let mut storage = MemoryStorage::new();
+        let imported = database(&mut storage, &[String::from("import"), path.to_string_lossy().into_owned()]);
+        fs::write(&path, "[White \"Carl\"]\n[Black \"Anna\"]\n\n1. e4 c5 *\n").unwrap();
+        let novelty = database(&mut storage, &[String::from("novelty"), path.to_string_lossy().into_owned()]);
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(imported.unwrap(), "Imported 2 games\n");
+        assert_eq!(novelty.unwrap(), "Carl - Anna: Novelty on move 1: 1... c5, leaving 1 known game\n");
+        assert_eq!(database(&mut storage, &args("games --player Anna")).unwrap(), "    1  2024.03.17  Anna - Ben  1-0\n");
+        assert_eq!(database(&mut storage, &args("games --fen rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq - 0 1")).unwrap().lines().count(), 1);
+        assert_eq!(database(&mut storage, &args("games")).unwrap().lines().count(), 2);
+        assert!(database(&mut storage, &args("games --player")).is_err());
+        assert!(database(&mut storage, &args("games --fen nonsense")).is_err());
+        assert_eq!(database(&mut storage, &args("games --pawns 4k3/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/4K3 w - - 0 1")).unwrap().lines().count(), 1);
+        assert!(database(&mut storage, &args("novelty")).is_err());
+        assert!(database(&mut storage, &args("export")).is_err());
+    }
+
+    #[test]
+    fn annotates_games() {
+        let options = parse_annotate_options(&args("-d 2 --svg graph.svg game.pgn")).unwrap();
+
+        assert_eq!(options, AnnotateOptions { depth: 2, svg: Some(String::from("graph.svg")), pgn: String::from("game.pgn") });
+        assert!(parse_annotate_options(&args("--svg game.pgn")).is_err());
+        assert!(parse_annotate_options(&args("--color game.pgn")).is_err());
+
+        let game = pgn::read_games("1. f3 e5 2. g4 Qh4# 0-1").remove(0).game;
+        let text = annotate(&game, &report::analyze_game(&game, 2), 2);
+        let lines: Vec<&str> = text.lines().filter(|line| !line.starts_with("1.") && !line.starts_with("1...")).collect();
+
+        // The blunder is followed by the explanation of the move that keeps the game going.
+        assert_eq!(lines[0], "2. g4?? #-1");
+        assert!(lines[1].starts_with("    ") && lines[1].contains(" is the best move ("));
+        assert!(lines[2].starts_with("2... Qh4# "));
+        assert!(lines[4].starts_with("White: accuracy ") && lines[4].ends_with("1 blunders"));
+
+        let dir = std::env::temp_dir().join(format!("chess-cli-annotate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("game.pgn"), "1. f3 e5 2. g4 Qh4# 0-1\n").unwrap();
+
+        let svg = dir.join("graph.svg");
+        let result = run(&[String::from("annotate"), String::from("-d"), String::from("1"), String::from("--svg"),
+                           svg.to_string_lossy().into_owned(), dir.join("game.pgn").to_string_lossy().into_owned()]);
+
+        assert!(result.is_ok());
+        assert!(fs::read_to_string(&svg).unwrap().starts_with("<svg"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn parses_worker_options() {
+        let options = parse_worker_options(&args("--name box -j 4 --pin http://localhost:8000")).unwrap();
+
+        assert_eq!(options, WorkerOptions { url: String::from("http://localhost:8000"), name: String::from("box"), concurrency: 4, pin_threads: true });
+        assert_eq!((parse_worker_options(&args("http://a")).unwrap().concurrency, parse_worker_options(&args("http://a")).unwrap().pin_threads), (1, false));
+        assert!(parse_worker_options(&args("-j 0 http://a")).is_err());
+        assert!(parse_worker_options(&args("--threads 2 http://a")).is_err());
+        assert!(parse_worker_options(&[]).is_err());
+    }
+
+    #[test]
+    fn parses_match_options() {
+        let options = parse_match_options(&args("-n 10 -j 4 --pin --limit nodes 5000 --plies 200 --sprt 0 5 --pgn games.pgn alphabeta mcts")).unwrap();
+
+        assert_eq!(options.engines, [String::from("alphabeta"), String::from("mcts")]);
+        assert_eq!((options.games, options.config.concurrency, options.config.pin_threads, options.max_plies), (10, 4, true, 200));
+        assert!(matches!(options.limit, UciLimit::Nodes(5000)));
+        assert_eq!(options.sprt.unwrap().elo1, 5.0);
+        assert_eq!((options.pgn, options.state.as_str()), (Some(String::from("games.pgn")), "match.state"));
+
+        let defaults = parse_match_options(&args("alphabeta ./engine")).unwrap();
+
+        assert_eq!((defaults.games, defaults.config.concurrency, defaults.config.pin_threads), (100, 1, false));
+        assert!(matches!(defaults.limit, UciLimit::Depth(3)));
+        assert!(parse_match_options(&args("-j 0 alphabeta mcts")).is_err());
+        assert!(parse_match_options(&args("--limit soon 5 alphabeta mcts")).is_err());
+        assert!(parse_match_options(&args("alphabeta")).is_err());
+        assert!(parse_match_options(&args("--fast alphabeta mcts")).is_err());
+    }
+
+    #[test]
+    fn plays_matches_concurrently() {
+        let dir = std::env::temp_dir().join(format!("chess-cli-match-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let (state, pgn) = (dir.join("state"), dir.join("games.pgn"));
+        let run_match = || match_command(&args(&format!("-n 4 -j 2 --limit depth 1 --plies 10 --pgn {} --state {} alphabeta alphabeta", pgn.display(), state.display())));
+
+        run_match().unwrap();
+        assert_eq!(fs::read_to_string(&pgn).unwrap().matches("[Round ").count(), 4);
+
+        // Running it again resumes the finished match, so no games are added.
+        run_match().unwrap();
+        assert_eq!(fs::read_to_string(&pgn).unwrap().matches("[Round ").count(), 4);
+        assert!(fs::read_to_string(&state).unwrap().contains("limit depth 1\n"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn parses_serve_options() {
+        let a = args("--token a --rate 30 --token b 0.0.0.0:80");
+        let (access, address) = parse_serve_options(&a).unwrap();
+
+        assert_eq!((access.tokens, access.requests_per_minute, address), (vec![String::from("a"), String::from("b")], Some(30), "0.0.0.0:80"));
+        assert_eq!(parse_serve_options(&[]).unwrap().1, "127.0.0.1:8080");
+        assert!(parse_serve_options(&args("--rate many")).is_err());
+        assert!(parse_serve_options(&args("--token")).is_err());
+        assert!(parse_serve_options(&args("a b")).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_commands_and_invalid_fens() {
+        assert!(run(&args("foo")).is_err());
+        assert!(run(&args("perft")).is_err());
+        assert!(run(&args("analyze 8/8 w - - 0 1")).is_err());
+    }
+
+    #[test]
+    fn writes_json() {
+        let moves = json_object(&[("e2e4", String::from("20")), ("g1f3", String::from("20"))]);
+
+        assert_eq!(json_object(&[("depth", String::from("2")), ("moves", moves)]), r#"{"depth":2,"moves":{"e2e4":20,"g1f3":20}}"#);
+        assert_eq!(stats_json(&BranchingStats { counts: vec![20, 1, 1, 30] }), r#"{"counts":[20,1,1,30],"mean":13,"forced":[[2,2]]}"#);
+    }
+
+    #[test]
+    fn writes_annotations_as_json() {
+        let game = pgn::read_games("1. f3 e5 2. g4 Qh4# 0-1").remove(0).game;
+        let json: serde_json::Value = serde_json::from_str(&annotate_json(&game, &report::analyze_game(&game, 2), 2)).unwrap();
+        let moves = json["moves"].as_array().unwrap();
+
+        assert_eq!(json["depth"], 2);
+        assert_eq!(moves.len(), 4);
+        assert_eq!((moves[2]["san"].as_str(), moves[2]["judgement"].as_str()), (Some("g4"), Some("blunder")));
+        assert!(moves[2]["explanation"].as_str().unwrap().contains(" is the best move ("));
+        assert!(moves[3]["explanation"].is_null());
+        assert_eq!((json["white"]["blunders"].as_u64(), json["black"]["blunders"].as_u64()), (Some(1), Some(0)));
+        assert!(json["black"]["accuracy"].as_f64().unwrap() > json["white"]["accuracy"].as_f64().unwrap());
+    }
+
+    #[test]
+    fn writes_test_suite_results_as_json() {
+        let suite = testsuite::read_suite("6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8#; id \"mate\";\n4k3/8/8/3q4/8/8/3R4/4K3 w - - am Rxd5;").unwrap();
+        let results: Vec<TestResult> = suite.iter().map(|position| position.solve(2)).collect();
+        let json: serde_json::Value = serde_json::from_str(&test_suite_json(&suite, &results, 2)).unwrap();
+
+        assert_eq!((json["solved"].as_u64(), json["total"].as_u64()), (Some(1), Some(2)));
+        assert_eq!(json["positions"][0], serde_json::json!({"id": "mate", "found": "Ra8#", "best": ["Ra8#"], "avoid": [], "solved": true}));
+        assert_eq!(json["positions"][1]["id"], "4k3/8/8/3q4/8/8/3R4/4K3 w - -");
+        assert_eq!(json["positions"][1]["avoid"], serde_json::json!(["Rxd5"]));
+        assert_eq!(json["positions"][1]["solved"], false);
+    }
+
+    #[test]
+    fn accepts_json_only_for_commands_with_results() {
+        assert!(run(&args("perft 1 --json")).is_ok());
+        assert!(run(&args("--json train")).is_err());
+    }
+}
@@ -0,0 +1,73 @@
+//! The clock searches measure their time with. std::time::Instant panics on
+//! wasm32-unknown-unknown, which has no system clock, so there the browser's clock is read
+//! through JavaScript instead. Without the wasm feature there is no JavaScript to ask and the
+//! clock stands still: depth and node limits still work, time limits are never reached.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use std::time::Instant;
+
+#[cfg(target_arch = "wasm32")]
+pub use self::browser::Instant;
+
+
+#[cfg(target_arch = "wasm32")]
+mod browser {
+    use std::ops::{Add, Sub};
+    use std::time::Duration;
+
+    #[cfg(feature = "wasm")]
+    use wasm_bindgen::prelude::*;
+
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen]
+    extern "C" {
+        /// The milliseconds since the epoch, see Date.now() in JavaScript.
+        #[wasm_bindgen(js_namespace = Date, js_name = now)]
+        fn date_now() -> f64;
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    fn date_now() -> f64 {
+        return 0.0;
+    }
+
+    /// A point in time in milliseconds, with the interface of std::time::Instant the crate
+    /// uses.
+    #[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
+    pub struct Instant(f64);
+
+    impl Instant {
+        pub fn now() -> Instant {
+            return Instant(date_now());
+        }
+
+        pub fn elapsed(&self) -> Duration {
+            return Instant::now() - *self;
+        }
+    }
+
+    impl Add<Duration> for Instant {
+        type Output = Instant;
+
+        fn add(self, duration: Duration) -> Instant {
+            return Instant(self.0 + duration.as_secs_f64() * 1000.0);
+        }
+    }
+
+    impl Sub<Duration> for Instant {
+        type Output = Instant;
+
+        fn sub(self, duration: Duration) -> Instant {
+            return Instant(self.0 - duration.as_secs_f64() * 1000.0);
+        }
+    }
+
+    impl Sub<Instant> for Instant {
+        type Output = Duration;
+
+        /// Like std::time::Instant, an earlier instant minus a later one is zero.
+        fn sub(self, earlier: Instant) -> Duration {
+            return Duration::from_secs_f64((self.0 - earlier.0).max(0.0) / 1000.0);
+        }
+    }
+}
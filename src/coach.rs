@@ -0,0 +1,97 @@
+use std::fmt;
+
+use crate::moves::Move;
+use crate::position::Position;
+
+
+/// The number of centipawns a move may lose compared to the best move before the coach warns.
+pub const DEFAULT_THRESHOLD: i32 = 150;
+
+/// The number of plies of the refutation shown with a warning.
+const REFUTATION_LENGTH: usize = 3;
+
+
+/// A warning about a move that loses more than the threshold compared to the best move.
+#[derive(Clone, Debug)]
+pub struct CoachWarning {
+    /// The move in SAN.
+    pub san: String,
+    /// The centipawns the move loses compared to the best move.
+    pub loss: i32,
+    /// The best move in SAN.
+    pub best: String,
+    /// The line refuting the move in SAN, starting with the opponent's reply.
+    pub refutation: Vec<String>
+}
+
+/// Checks a legal move with a search of the given depth and returns a warning if it loses
+/// more than threshold centipawns compared to the best move.
+pub fn check_move(position: &Position, m: Move, depth: u8, threshold: i32) -> Option<CoachWarning> {
+    let scores = position.evaluate_all_moves(depth);
+
+    let (best, best_score) = *scores.first()?;
+    let (_, score) = *scores.iter().find(|(other, _)| *other == m)?;
+
+    if best_score - score <= threshold {
+        return None;
+    }
+
+    return Some(CoachWarning {
+        san: position.move_to_san(m),
+        loss: best_score - score,
+        best: position.move_to_san(best),
+        refutation: principal_line(&position.make_move(m), depth, REFUTATION_LENGTH)
+    });
+}
+
+/// Follows the best moves found by searches of the given depth for the given number of plies
+/// and returns them in SAN.
+fn principal_line(position: &Position, depth: u8, length: usize) -> Vec<String> {
+    let mut line = Vec::new();
+    let mut position = position.clone();
+
+    for _ in 0..length {
+        let m = match position.evaluate_all_moves(depth).first() {
+            Some((m, _)) => *m,
+            None         => break
+        };
+
+        line.push(position.move_to_san(m));
+        position = position.make_move(m);
+    }
+
+    return line;
+}
+
+impl fmt::Display for CoachWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{} loses {:.2} pawns compared to {}. A possible refutation is {}.",
+                      self.san, self.loss as f64 / 100.0, self.best, self.refutation.join(" "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(fen: &str) -> Position {
+        return Position::from_fen(fen.to_string()).unwrap();
+    }
+
+    #[test]
+    fn warns_about_hanging_the_queen() {
+        let pos = position("r5k1/5ppp/2p5/8/8/8/8/3QK3 w - - 0 1");
+        let warning = check_move(&pos, pos.san_to_move("Qd5").unwrap(), 2, DEFAULT_THRESHOLD).unwrap();
+
+        assert_eq!(warning.loss, 925);
+        assert_eq!(warning.refutation[0], "cxd5");
+        assert!(warning.to_string().starts_with("Qd5 loses 9.25 pawns compared to"));
+    }
+
+    #[test]
+    fn accepts_reasonable_moves() {
+        let pos = position("r5k1/5ppp/2p5/8/8/8/8/3QK3 w - - 0 1");
+
+        assert!(check_move(&pos, pos.san_to_move("Qd4").unwrap(), 2, DEFAULT_THRESHOLD).is_none());
+    }
+}
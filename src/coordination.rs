@@ -0,0 +1,122 @@
+use crate::bitboards::{bb_ops, magic_bb, pawns};
+use crate::bitboards::geometry::FILES;
+use crate::params::{self, DOUBLED_ROOKS, QUEEN_BISHOP_BATTERY, QUEEN_ROOK_BATTERY};
+use crate::position::{flip_color, Position, BISHOP, ROOK, QUEEN};
+
+
+/// A queen lined up with a bishop on a diagonal or with a rook on a file or rank, with nothing
+/// between them and the line going on beyond the front piece to an empty square or an enemy
+/// piece.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Battery {
+    /// The type of the piece backing up or backed up by the queen, a bishop or a rook.
+    pub piece_type: u8,
+    pub queen: u8,
+    pub piece: u8
+}
+
+/// Returns the files on which the given player has two rooks with nothing between them.
+pub fn doubled_rooks(position: &Position, color: u8) -> Vec<u8> {
+    let rooks = position.get_piece_bitboard(color, ROOK);
+    let blockers = position.get_all_piece_bitboard(color) | position.get_all_piece_bitboard(flip_color(color));
+
+    let mut files: Vec<u8> = bb_ops::idx_bitscan(rooks).into_iter()
+        .filter(|sq| magic_bb::rook_attacks(*sq, blockers) & rooks & pawns::file_mask(sq % FILES) != 0)
+        .map(|sq| sq % FILES)
+        .collect();
+
+    files.dedup();
+
+    return files;
+}
+
+/// Returns the batteries of the given player. The x-ray attacks of the two pieces through each
+/// other tell whether the line goes on beyond the front one, so pieces lined up towards the
+/// edge of the board or an own piece don't count.
+pub fn batteries(position: &Position, color: u8) -> Vec<Battery> {
+    let board = position.variant().geometry().board_mask();
+    let own = position.get_all_piece_bitboard(color);
+    let blockers = own | position.get_all_piece_bitboard(flip_color(color));
+    let mut batteries = Vec::new();
+
+    for queen in bb_ops::idx_bitscan(position.get_piece_bitboard(color, QUEEN)) {
+        for (piece_type, attacks, xray) in [
+            (BISHOP, magic_bb::bishop_attacks as fn(u8, u64) -> u64, magic_bb::bishop_xray_attacks as fn(u8, u64, u64) -> u64),
+            (ROOK, magic_bb::rook_attacks, magic_bb::rook_xray_attacks)
+        ] {
+            for piece in bb_ops::idx_bitscan(attacks(queen, blockers) & position.get_piece_bitboard(color, piece_type)) {
+                let reach = xray(queen, blockers, bb_ops::index_lookup_mask(piece))
+                    | xray(piece, blockers, bb_ops::index_lookup_mask(queen));
+
+                if reach & board & !own != 0 {
+                    batteries.push(Battery { piece_type, queen, piece });
+                }
+            }
+        }
+    }
+
+    return batteries;
+}
+
+/// Evaluates how the pieces of the given player work together: rooks doubled on a file and
+/// batteries of the queen with a bishop or a rook. The weights are taken from the parameter
+/// registry.
+pub fn evaluate(position: &Position, color: u8) -> i32 {
+    let mut score = doubled_rooks(position, color).len() as i32 * params::get(DOUBLED_ROOKS);
+
+    for battery in batteries(position, color) {
+        score += params::get(if battery.piece_type == BISHOP { QUEEN_BISHOP_BATTERY } else { QUEEN_ROOK_BATTERY });
+    }
+
+    return score;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::{WHITE, BLACK};
+
+    fn position(fen: &str) -> Position {
+        return Position::from_fen(fen.to_string()).unwrap();
+    }
+
+    #[test]
+    fn finds_rooks_doubled_on_a_file() {
+        let pos = position("3rk3/8/3n4/8/8/8/3R4/3RK3 w - - 0 1");
+        assert_eq!(doubled_rooks(&pos, WHITE), vec![3]);
+        assert!(doubled_rooks(&pos, BLACK).is_empty());
+
+        // Rooks on a rank or with a piece between them aren't doubled.
+        let pos = position("4k3/8/8/8/8/3R4/3N4/R2RK3 w - - 0 1");
+        assert!(doubled_rooks(&pos, WHITE).is_empty());
+    }
+
+    #[test]
+    fn finds_batteries_pointing_into_the_board() {
+        // The queen on d3 backs up the bishop on e4 and the rook on d2 backs up the queen.
+        let pos = position("4k3/8/8/8/4B3/3Q4/3R4/4K3 w - - 0 1");
+        let sq = |y, x| bb_ops::coords_to_index(y, x);
+
+        assert_eq!(batteries(&pos, WHITE), vec![
+            Battery { piece_type: BISHOP, queen: sq(2, 3), piece: sq(3, 4) },
+            Battery { piece_type: ROOK, queen: sq(2, 3), piece: sq(1, 3) }
+        ]);
+
+        assert_eq!(evaluate(&pos, WHITE), params::get(QUEEN_BISHOP_BATTERY) + params::get(QUEEN_ROOK_BATTERY));
+
+        // A queen in the corner behind a bishop still points along the long diagonal, but
+        // pieces lined up towards the edge or the own king don't.
+        let pos = position("4k3/8/8/8/8/8/1B6/Q3K3 w - - 0 1");
+        assert_eq!(batteries(&pos, WHITE).len(), 1);
+
+        let pos = position("4k3/8/8/8/8/8/8/QR2K3 w - - 0 1");
+        assert_eq!(batteries(&pos, WHITE).len(), 1);
+
+        let pos = position("4k3/8/8/8/8/8/Q7/1B2K3 w - - 0 1");
+        assert!(batteries(&pos, WHITE).is_empty());
+
+        let pos = position("4k3/8/8/8/8/8/8/R2QK3 w - - 0 1");
+        assert!(batteries(&pos, WHITE).is_empty());
+    }
+}
@@ -0,0 +1,305 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::game::Game;
+use crate::moves::Move;
+use crate::position::Position;
+use crate::search::{self, Limits, Score};
+use crate::tt::TranspositionTable;
+
+
+/// The state of a correspondence analysis saved at each checkpoint: the game, the result of
+/// the deepest search so far and the time spent on it. The transposition table is saved next
+/// to it, so a restarted analysis continues where it stopped instead of starting over.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Checkpoint {
+    /// The position the game started from and the moves played since in UCI notation.
+    pub fen: String,
+    pub moves: Vec<String>,
+    /// The depth of the deepest completed iteration, with its score and principal variation.
+    pub depth: u8,
+    pub score: Option<i32>,
+    pub pv: Vec<String>,
+    /// The time spent on the analysis over all sessions.
+    pub elapsed: Duration
+}
+
+impl Checkpoint {
+    /// Starts the analysis of the position reached by the moves from the starting position.
+    pub fn new(start: &Position, moves: &[Move]) -> Checkpoint {
+        let mut position = start.clone();
+        let mut uci = Vec::new();
+
+        for &m in moves {
+            uci.push(position.move_to_uci(m));
+            position = position.make_move(m);
+        }
+
+        return Checkpoint { fen: start.to_fen(), moves: uci, depth: 0, score: None, pv: Vec::new(), elapsed: Duration::ZERO };
+    }
+
+    /// Replays the moves of the game. Returns None if they aren't legal.
+    pub fn game(&self) -> Option<Game> {
+        let mut game = Game::from_position(Position::from_fen(self.fen.clone())?);
+
+        for uci in &self.moves {
+            let m = game.position().uci_to_move(uci)?;
+            game.play(m);
+        }
+
+        return Some(game);
+    }
+
+    /// Checks if the checkpoint analyzes the same game as the other one.
+    pub fn same_game(&self, other: &Checkpoint) -> bool {
+        return self.fen == other.fen && self.moves == other.moves;
+    }
+
+    /// Parses a checkpoint written by its Display implementation.
+    pub fn parse(text: &str) -> Option<Checkpoint> {
+        let mut checkpoint = Checkpoint { fen: String::new(), moves: Vec::new(), depth: 0, score: None, pv: Vec::new(), elapsed: Duration::ZERO };
+
+        for line in text.lines() {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let words = || value.split_whitespace().map(String::from).collect();
+
+            match key {
+                "fen"     => checkpoint.fen = value.to_string(),
+                "moves"   => checkpoint.moves = words(),
+                "depth"   => checkpoint.depth = value.parse().ok()?,
+                "score"   => checkpoint.score = Some(value.parse().ok()?),
+                "pv"      => checkpoint.pv = words(),
+                "elapsed" => checkpoint.elapsed = Duration::from_millis(value.parse().ok()?),
+                _         => return None
+            }
+        }
+
+        return if checkpoint.fen.is_empty() { None } else { Some(checkpoint) };
+    }
+
+    /// Reads the checkpoint at the path and the transposition table saved with it. Returns
+    /// None if there is no checkpoint yet. A missing table is replaced by an empty one.
+    pub fn load(path: &Path) -> io::Result<Option<(Checkpoint, TranspositionTable)>> {
+        let text = match fs::read_to_string(path) {
+            Ok(text)                                        => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound   => return Ok(None),
+            Err(e)                                          => return Err(e)
+        };
+
+        let checkpoint = Checkpoint::parse(&text).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid checkpoint: {}", path.display())))?;
+
+        let tt = match File::open(table_path(path)) {
+            Ok(file)                                        => TranspositionTable::read_from(BufReader::new(file))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound   => TranspositionTable::default(),
+            Err(e)                                          => return Err(e)
+        };
+
+        return Ok(Some((checkpoint, tt)));
+    }
+
+    /// Saves the checkpoint at the path and the table next to it. Both are written to
+    /// temporary files first, so an interruption never leaves a broken checkpoint behind.
+    pub fn save(&self, path: &Path, tt: &TranspositionTable) -> io::Result<()> {
+        let table = table_path(path);
+        let temporary_table = temporary_path(&table);
+
+        tt.write_to(BufWriter::new(File::create(&temporary_table)?))?;
+        fs::rename(&temporary_table, &table)?;
+
+        let temporary = temporary_path(path);
+
+        fs::write(&temporary, self.to_string())?;
+        return fs::rename(&temporary, path);
+    }
+}
+
+impl fmt::Display for Checkpoint {
+    /// Writes the checkpoint as one line per field, e.g. "depth 24". The elapsed time is
+    /// written in milliseconds.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "fen {}", self.fen)?;
+        writeln!(f, "moves {}", self.moves.join(" "))?;
+        writeln!(f, "depth {}", self.depth)?;
+
+        if let Some(score) = self.score {
+            writeln!(f, "score {}", score)?;
+        }
+
+        writeln!(f, "pv {}", self.pv.join(" "))?;
+        return writeln!(f, "elapsed {}", self.elapsed.as_millis());
+    }
+}
+
+/// Returns the path of the transposition table saved next to the checkpoint.
+pub fn table_path(checkpoint: &Path) -> PathBuf {
+    let mut path = checkpoint.as_os_str().to_owned();
+    path.push(".tt");
+
+    return PathBuf::from(path);
+}
+
+fn temporary_path(path: &Path) -> PathBuf {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+
+    return PathBuf::from(temporary);
+}
+
+/// Analyzes the game of the checkpoint for the given time, saving it every interval and when
+/// the search ends or is stopped. Each interval is a search of its own in the same table,
+/// which quickly gets back to the depth of the last one. The callback is called after each
+/// save with the checkpoint.
+pub fn analyze<F: FnMut(&Checkpoint)>(path: &Path, checkpoint: &mut Checkpoint, tt: &mut TranspositionTable, time: Duration, interval: Duration,
+                                      stop: &Arc<AtomicBool>, mut on_checkpoint: F) -> io::Result<()> {
+    let game = checkpoint.game().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "The moves of the checkpoint aren't legal"))?;
+    let position = game.position();
+    let start = Instant::now();
+
+    while !stop.load(Ordering::Relaxed) {
+        let left = time.saturating_sub(start.elapsed());
+
+        if left.is_zero() {
+            break;
+        }
+
+        let slice = left.min(interval);
+        let slice_start = Instant::now();
+        let limits = Limits { time: Some(slice), stop: Some(stop.clone()), null_move: true, futility: true, probcut: true, ..Limits::default() };
+
+        let iteration = search::iterative_deepening_with_table(position, &limits, tt, |_| ());
+
+        if let Some(iteration) = iteration.as_ref().filter(|iteration| iteration.depth >= checkpoint.depth) {
+            let mut pv_position = position.clone();
+
+            checkpoint.depth = iteration.depth;
+            checkpoint.score = Some(iteration.score);
+            checkpoint.pv = iteration.pv.iter().map(|&m| {
+                let uci = pv_position.move_to_uci(m);
+                pv_position = pv_position.make_move(m);
+
+                return uci;
+            }).collect();
+        }
+
+        checkpoint.elapsed += slice_start.elapsed();
+        checkpoint.save(path, tt)?;
+        on_checkpoint(checkpoint);
+
+        // Without legal moves or after the deepest possible search there's nothing left to do.
+        if iteration.is_none() || slice_start.elapsed() < slice && !stop.load(Ordering::Relaxed) {
+            break;
+        }
+    }
+
+    return Ok(());
+}
+
+/// Describes the state of the analysis, e.g. "depth 21 score cp 35 pv e2e4 e7e5 (2h 5m)".
+pub fn describe(checkpoint: &Checkpoint) -> String {
+    let minutes = checkpoint.elapsed.as_secs() / 60;
+    let score = checkpoint.score.map_or(String::new(), |score| format!(" score {}", Score::new(score)));
+
+    return format!("depth {}{} pv {} ({}h {}m)", checkpoint.depth, score, checkpoint.pv.join(" "), minutes / 60, minutes % 60);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tt::Replacement;
+
+    fn temp_path(name: &str) -> PathBuf {
+        return std::env::temp_dir().join(format!("chess-correspondence-{}-{}", name, std::process::id()));
+    }
+
+    /// Returns the checkpoint with the elapsed time cut to the milliseconds that are saved.
+    fn saved(checkpoint: &Checkpoint) -> Checkpoint {
+        return Checkpoint { elapsed: Duration::from_millis(checkpoint.elapsed.as_millis() as u64), ..checkpoint.clone() };
+    }
+
+    fn remove(path: &Path) {
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(table_path(path));
+    }
+
+    #[test]
+    fn checkpoints_survive_a_restart() {
+        let path = temp_path("restart");
+        let start = Position::starting_position();
+        let e4 = start.uci_to_move("e2e4").unwrap();
+        let mut checkpoint = Checkpoint::new(&start, &[e4]);
+        let mut tt = TranspositionTable::new(1, Replacement::DepthPreferred);
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut saves = 0;
+
+        assert!(Checkpoint::load(&path).unwrap().is_none());
+
+        analyze(&path, &mut checkpoint, &mut tt, Duration::from_millis(300), Duration::from_millis(100), &stop, |_| saves += 1).unwrap();
+        let loaded = Checkpoint::load(&path);
+        remove(&path);
+
+        let (loaded, loaded_tt) = loaded.unwrap().unwrap();
+
+        assert!(saves >= 2, "{} checkpoints", saves);
+        assert_eq!(loaded, saved(&checkpoint));
+        assert_eq!(loaded.moves, vec!["e2e4"]);
+        assert!(loaded.depth > 0 && !loaded.pv.is_empty());
+        assert_eq!(loaded_tt.slots(), tt.slots());
+        assert!(*loaded.game().unwrap().position() == start.make_move(e4));
+        assert!(describe(&loaded).starts_with(&format!("depth {} score cp ", loaded.depth)));
+    }
+
+    #[test]
+    fn resumed_analyses_continue_with_the_saved_table() {
+        let path = temp_path("resume");
+        let position = Position::from_fen(String::from("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")).unwrap();
+        let mut checkpoint = Checkpoint::new(&position, &[]);
+        let mut tt = TranspositionTable::new(1, Replacement::DepthPreferred);
+        let stop = Arc::new(AtomicBool::new(false));
+
+        analyze(&path, &mut checkpoint, &mut tt, Duration::from_millis(200), Duration::from_secs(1), &stop, |_| ()).unwrap();
+        let (mut resumed, mut resumed_tt) = Checkpoint::load(&path).unwrap().unwrap();
+
+        assert!(!resumed_tt.is_empty());
+
+        analyze(&path, &mut resumed, &mut resumed_tt, Duration::from_millis(200), Duration::from_secs(1), &stop, |_| ()).unwrap();
+        remove(&path);
+
+        assert!(resumed.depth >= checkpoint.depth);
+        assert!(resumed.elapsed >= checkpoint.elapsed + Duration::from_millis(150));
+        assert!(resumed.same_game(&checkpoint));
+    }
+
+    #[test]
+    fn mates_and_stops_end_the_analysis() {
+        let path = temp_path("mate");
+        let position = Position::from_fen(String::from("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1")).unwrap();
+        let mut checkpoint = Checkpoint::new(&position, &[]);
+        let mut tt = TranspositionTable::new(1, Replacement::DepthPreferred);
+        let stop = Arc::new(AtomicBool::new(false));
+        let start = Instant::now();
+
+        analyze(&path, &mut checkpoint, &mut tt, Duration::from_secs(60), Duration::from_secs(10), &stop, |_| ()).unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!(checkpoint.pv[0], "a1a8");
+
+        // A stopped analysis doesn't search or save.
+        let before = checkpoint.clone();
+        fs::remove_file(&path).unwrap();
+        stop.store(true, Ordering::Relaxed);
+        analyze(&path, &mut checkpoint, &mut tt, Duration::from_secs(1), Duration::from_secs(1), &stop, |_| ()).unwrap();
+        let reloaded = Checkpoint::load(&path);
+        remove(&path);
+
+        assert!(reloaded.unwrap().is_none());
+        assert_eq!(checkpoint, before);
+        assert_eq!(Checkpoint::parse(&checkpoint.to_string()), Some(saved(&checkpoint)));
+        assert_eq!(Checkpoint::parse("depth x"), None);
+    }
+}
@@ -0,0 +1,135 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+
+/// The CPU features the engine has faster implementations for. They are detected once when
+/// first needed, so the same binary uses the best implementation on every machine.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Features {
+    /// Counting and iterating over the bits of bitboards.
+    pub popcnt: bool,
+    /// Indexing the slider attack tables with PEXT instead of magic multiplication.
+    pub bmi2: bool
+}
+
+/// The detected features as the bits below, so asking for them in hot code is a single load.
+/// Zero until they are detected.
+static FEATURES: AtomicU8 = AtomicU8::new(0);
+
+const DETECTED: u8 = 1;
+const POPCNT: u8 = 2;
+const BMI2: u8 = 4;
+
+impl Features {
+    /// Features of a CPU without any of the optional extensions.
+    pub const GENERIC: Features = Features { popcnt: false, bmi2: false };
+
+    /// Detects the features of the CPU the engine is running on. Setting the environment
+    /// variable RUSTCHESS_GENERIC disables all of them, e.g. to compare the implementations.
+    pub fn detect() -> Features {
+        if std::env::var_os("RUSTCHESS_GENERIC").is_some() {
+            return Features::GENERIC;
+        }
+
+        #[cfg(target_arch = "x86_64")]
+        return Features {
+            popcnt: is_x86_feature_detected!("popcnt") && is_x86_feature_detected!("bmi1"),
+            bmi2: is_x86_feature_detected!("bmi2")
+        };
+
+        #[cfg(not(target_arch = "x86_64"))]
+        return Features::GENERIC;
+    }
+}
+
+impl fmt::Display for Features {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<&str> = [(self.popcnt, "popcnt"), (self.bmi2, "bmi2")].iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, name)| *name)
+            .collect();
+
+        return match names.is_empty() {
+            true  => write!(f, "generic"),
+            false => write!(f, "{}", names.join(" "))
+        };
+    }
+}
+
+/// Returns the features of the CPU the engine is running on. They are detected on the first
+/// call. Threads racing on it detect the same features, so storing them twice is harmless.
+#[inline]
+pub fn features() -> Features {
+    let mut bits = FEATURES.load(Ordering::Relaxed);
+
+    if bits == 0 {
+        let features = Features::detect();
+
+        bits = DETECTED | if features.popcnt { POPCNT } else { 0 } | if features.bmi2 { BMI2 } else { 0 };
+        FEATURES.store(bits, Ordering::Relaxed);
+    }
+
+    return Features { popcnt: bits & POPCNT != 0, bmi2: bits & BMI2 != 0 };
+}
+
+/// Extracts the bits of the value at the positions set in the mask into the lowest bits.
+///
+/// # Safety
+///
+/// The CPU has to support BMI2, which features tells.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "bmi2")]
+pub unsafe fn pext(value: u64, mask: u64) -> u64 {
+    return std::arch::x86_64::_pext_u64(value, mask);
+}
+
+/// The portable version of pext, used to build and check the tables on any CPU.
+pub fn pext_generic(value: u64, mask: u64) -> u64 {
+    let mut result = 0;
+    let mut bit = 1;
+    let mut mask = mask;
+
+    while mask != 0 {
+        if value & mask & mask.wrapping_neg() != 0 {
+            result |= bit;
+        }
+
+        mask &= mask - 1;
+        bit <<= 1;
+    }
+
+    return result;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_the_features() {
+        assert_eq!(Features::GENERIC.to_string(), "generic");
+        assert_eq!(Features { bmi2: true, ..Features::GENERIC }.to_string(), "bmi2");
+        assert_eq!(Features { popcnt: true, bmi2: true }.to_string(), "popcnt bmi2");
+    }
+
+    #[test]
+    fn caches_the_detected_features() {
+        assert_eq!(features(), Features::detect());
+        assert_eq!(features(), features());
+    }
+
+    #[test]
+    fn extracts_bits() {
+        assert_eq!(pext_generic(0b1011_0110, 0b1111_0000), 0b1011);
+        assert_eq!(pext_generic(0b1011_0110, 0b0101_0101), 0b0110);
+        assert_eq!(pext_generic(u64::MAX, 0x8000_0000_0000_0001), 0b11);
+
+        #[cfg(target_arch = "x86_64")]
+        if features().bmi2 {
+            for (value, mask) in [(0xDEAD_BEEF, 0xFF00_FF00), (u64::MAX, 0x8100_0000_0000_0081), (0x1234, 0)] {
+                assert_eq!(unsafe { pext(value, mask) }, pext_generic(value, mask));
+            }
+        }
+    }
+}
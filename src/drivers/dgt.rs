@@ -0,0 +1,155 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::bitboards::bb_ops;
+use crate::bitboards::geometry::{self, Orientation};
+use crate::drivers::{BoardDriver, Placement};
+use crate::position::{NUM_PIECE_TYPES, WHITE, BLACK, PAWN, KNIGHT, BISHOP, ROOK, QUEEN, KING};
+
+/// Command requesting a dump of the complete board.
+const DGT_SEND_BRD: u8 = 0x42;
+
+/// Message id of the answer to DGT_SEND_BRD.
+const DGT_MSG_BOARD_DUMP: u8 = 0x86;
+
+/// Size of the header preceding every message sent by the board.
+const DGT_HEADER_SIZE: usize = 3;
+
+
+/// Driver for DGT electronic boards connected via a serial port.
+///
+/// The port has to be configured beforehand to the board's settings (9600 baud, 8N1),
+/// e.g. using `stty -F /dev/ttyUSB0 9600 raw`.
+pub struct DgtBoard {
+    port: File
+}
+
+impl DgtBoard {
+    /// Opens the serial port at the given path.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<DgtBoard> {
+        let port = OpenOptions::new().read(true).write(true).open(path)?;
+
+        return Ok(DgtBoard { port });
+    }
+
+    /// Reads a single message sent by the board and returns its id and payload.
+    fn read_message(&mut self) -> io::Result<(u8, Vec<u8>)> {
+        let mut header = [0u8; DGT_HEADER_SIZE];
+        self.port.read_exact(&mut header)?;
+
+        // The size is sent as two 7 bit values and includes the header.
+        let size = ((header[1] as usize) << 7) | (header[2] as usize);
+
+        if size < DGT_HEADER_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid DGT message size"));
+        }
+
+        let mut payload = vec![0u8; size - DGT_HEADER_SIZE];
+        self.port.read_exact(&mut payload)?;
+
+        return Ok((header[0], payload));
+    }
+}
+
+impl BoardDriver for DgtBoard {
+    fn read_placement(&mut self) -> io::Result<Placement> {
+        self.port.write_all(&[DGT_SEND_BRD])?;
+
+        // Skip all messages (e.g. field updates) until the board dump arrives.
+        loop {
+            let (id, payload) = self.read_message()?;
+
+            if id == DGT_MSG_BOARD_DUMP {
+                return decode_board_dump(&payload);
+            }
+        }
+    }
+}
+
+/// Converts a piece code used by DGT boards into a piece type and color.
+fn decode_piece(code: u8) -> Option<(u8, u8)> {
+    return match code {
+        0x01 => Some((PAWN, WHITE)),
+        0x02 => Some((ROOK, WHITE)),
+        0x03 => Some((KNIGHT, WHITE)),
+        0x04 => Some((BISHOP, WHITE)),
+        0x05 => Some((KING, WHITE)),
+        0x06 => Some((QUEEN, WHITE)),
+        0x07 => Some((PAWN, BLACK)),
+        0x08 => Some((ROOK, BLACK)),
+        0x09 => Some((KNIGHT, BLACK)),
+        0x0A => Some((BISHOP, BLACK)),
+        0x0B => Some((KING, BLACK)),
+        0x0C => Some((QUEEN, BLACK)),
+        _    => None
+    };
+}
+
+/// Decodes the 64 bytes of a board dump. The board sends the squares starting at a8
+/// going to h8 and then rank by rank down to h1.
+fn decode_board_dump(payload: &[u8]) -> io::Result<Placement> {
+    if payload.len() != 64 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid DGT board dump"));
+    }
+
+    let mut placement = [0; 12];
+
+    for (i, code) in payload.iter().enumerate() {
+        let (rk, fl) = geometry::STANDARD.screen_to_coords((i / 8) as u8, (i % 8) as u8, Orientation::WhiteBottom).unwrap();
+
+        if let Some((piece_type, color)) = decode_piece(*code) {
+            let piece_idx = (color * NUM_PIECE_TYPES + piece_type) as usize;
+
+            placement[piece_idx] = bb_ops::set_coords_bit(placement[piece_idx], rk, fl);
+        }
+    }
+
+    return Ok(placement);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    /// A board dump of the starting position.
+    fn starting_dump() -> Vec<u8> {
+        let mut dump = vec![0x08, 0x09, 0x0A, 0x0C, 0x0B, 0x0A, 0x09, 0x08];
+        dump.extend([0x07; 8]);
+        dump.extend([0x00; 32]);
+        dump.extend([0x01; 8]);
+        dump.extend([0x02, 0x03, 0x04, 0x06, 0x05, 0x04, 0x03, 0x02]);
+
+        return dump;
+    }
+
+    #[test]
+    fn decodes_board_dumps() {
+        let placement = decode_board_dump(&starting_dump()).unwrap();
+        let start = Position::starting_position();
+
+        for (idx, bb) in placement.iter().enumerate() {
+            assert_eq!(*bb, start.get_bitboard(idx));
+        }
+    }
+
+    #[test]
+    fn ignores_unknown_piece_codes() {
+        let mut dump = starting_dump();
+        dump[0] = 0x0D;
+
+        let placement = decode_board_dump(&dump).unwrap();
+
+        assert_eq!(placement[(BLACK * NUM_PIECE_TYPES + ROOK) as usize], bb_ops::index_lookup_mask(63));
+    }
+
+    #[test]
+    fn rejects_truncated_dumps() {
+        let dump = starting_dump();
+
+        assert_eq!(decode_board_dump(&dump[..63]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(decode_board_dump(&[]).is_err());
+    }
+}
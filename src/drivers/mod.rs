@@ -0,0 +1,153 @@
+#[cfg(feature = "dgt")]
+pub mod dgt;
+
+use std::io;
+
+use crate::game::Game;
+use crate::moves::Move;
+use crate::position::{self, Position};
+
+
+/// The piece placement observed on a board, encoded as one bitboard per piece type and
+/// color in the same order as they are stored in a Position.
+pub type Placement = [u64; 12];
+
+/// Interface for physical or electronic boards (or anything else that observes a board,
+/// e.g. a screen scraper) that can only report the pieces currently standing on it.
+pub trait BoardDriver {
+    /// Reads the piece placement currently observed on the board.
+    fn read_placement(&mut self) -> io::Result<Placement>;
+}
+
+/// Compares the placement observed by the driver with the current position of the game
+/// and plays the move that leads to it.
+///
+/// Returns None if the board didn't change or if the observed placement can't be reached
+/// by a single legal move (e.g. because a piece is still lifted).
+pub fn poll_move(driver: &mut dyn BoardDriver, game: &mut Game) -> io::Result<Option<Move>> {
+    let placement = driver.read_placement()?;
+    let m = infer_move(game.position(), placement);
+
+    if let Some(m) = m {
+        game.play(m);
+    }
+
+    return Ok(m);
+}
+
+/// Infers the move that leads from the given position to the observed placement.
+pub fn infer_move(current: &Position, placement: Placement) -> Option<Move> {
+    let unchanged = (0..12).all(|idx| current.get_bitboard(idx) == placement[idx]);
+
+    if unchanged {
+        return None;
+    }
+
+    // A board only knows where the pieces are standing. Castling rights and en passant
+    // don't matter for inferring the move.
+    let observed = Position::new(position::flip_color(current.turn), placement, [false; 2], [false; 2], None);
+
+    return Move::infer(current, &observed);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    /// A driver that reports a scripted sequence of placements and fails once it runs out.
+    struct ScriptedBoard {
+        placements: VecDeque<Placement>
+    }
+
+    impl ScriptedBoard {
+        fn new(fens: &[&str]) -> ScriptedBoard {
+            return ScriptedBoard { placements: fens.iter().map(|fen| placement(fen)).collect() };
+        }
+    }
+
+    impl BoardDriver for ScriptedBoard {
+        fn read_placement(&mut self) -> io::Result<Placement> {
+            return self.placements.pop_front()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "No placement left"));
+        }
+    }
+
+    fn position(fen: &str) -> Position {
+        return Position::from_fen(fen.to_string()).unwrap();
+    }
+
+    /// The placement of the pieces in the given FEN. Only the piece placement field matters.
+    fn placement(fen: &str) -> Placement {
+        let pos = position(&format!("{} w - - 0 1", fen));
+        let mut placement = [0; 12];
+
+        for (idx, bb) in placement.iter_mut().enumerate() {
+            *bb = pos.get_bitboard(idx);
+        }
+
+        return placement;
+    }
+
+    #[test]
+    fn plays_the_observed_moves() {
+        let mut game = Game::new();
+        let mut board = ScriptedBoard::new(&[
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR",
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR"
+        ]);
+
+        assert_eq!(poll_move(&mut board, &mut game).unwrap(), None);
+        assert!(poll_move(&mut board, &mut game).unwrap().is_some());
+        assert_eq!(poll_move(&mut board, &mut game).unwrap(), None);
+        assert!(poll_move(&mut board, &mut game).unwrap().is_some());
+
+        assert_eq!(game.moves().len(), 2);
+        assert_eq!(game.position().to_fen(), "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1");
+        assert!(poll_move(&mut board, &mut game).is_err());
+    }
+
+    #[test]
+    fn infers_castling() {
+        let pos = position("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+
+        assert_eq!(infer_move(&pos, placement("r3k2r/8/8/8/8/8/8/R4RK1")), Some(Move::ShortCastle));
+        assert_eq!(infer_move(&pos, placement("r3k2r/8/8/8/8/8/8/2KR3R")), Some(Move::LongCastle));
+    }
+
+    #[test]
+    fn infers_en_passant_captures() {
+        let pos = position("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1");
+
+        assert_eq!(infer_move(&pos, placement("4k3/8/8/8/8/4p3/8/4K3")), pos.uci_to_move("d4e3"));
+    }
+
+    #[test]
+    fn infers_promotions() {
+        let pos = position("8/4P3/8/8/8/8/8/k3K3 w - - 0 1");
+
+        assert_eq!(infer_move(&pos, placement("4Q3/8/8/8/8/8/8/k3K3")), pos.uci_to_move("e7e8q"));
+        assert_eq!(infer_move(&pos, placement("4N3/8/8/8/8/8/8/k3K3")), pos.uci_to_move("e7e8n"));
+    }
+
+    #[test]
+    fn ignores_unchanged_and_illegal_placements() {
+        let mut game = Game::new();
+        let mut board = ScriptedBoard::new(&[
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPP1PPP/RNBQKBNR",
+            "rnbqkbnr/pppppppp/8/4P3/8/8/PPPP1PPP/RNBQKBNR",
+            "rnbqkbnr/pppp1ppp/8/8/4p3/8/PPPPPPPP/RNBQKBNR"
+        ]);
+
+        for _ in 0..4 {
+            assert_eq!(poll_move(&mut board, &mut game).unwrap(), None);
+        }
+
+        assert!(game.moves().is_empty());
+    }
+}
@@ -0,0 +1,400 @@
+use crate::coordination;
+use crate::moves::Move;
+use crate::king_safety;
+use crate::pawn_structure::{self, PawnTable};
+use crate::params::{self, BISHOP_PAIR, KNIGHT_PAWNS, ROOK_PAWNS, ROOK_REDUNDANCY, QUEEN_REDUNDANCY};
+use crate::position::{flip_color, Position, MAX_PHASE, NUM_PIECE_TYPES, WHITE, PAWN, KNIGHT, BISHOP, ROOK, QUEEN, KING};
+
+
+/// The material value of each piece type in centipawns. The king is never traded, so it
+/// doesn't have a value.
+pub const PIECE_VALUES: [i32; NUM_PIECE_TYPES as usize] = [100, 320, 330, 500, 900, 0];
+
+/// The bonus of each piece type for standing on each square in the middlegame in centipawns,
+/// from white's point of view with the 8th rank first, so the tables read like a board. Pawns
+/// are pushed towards the center, the minor pieces are developed and the king stays behind
+/// its pawns.
+const MIDDLEGAME_TABLES: [[i32; 64]; NUM_PIECE_TYPES as usize] = [
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+         50,  50,  50,  50,  50,  50,  50,  50,
+         10,  10,  20,  30,  30,  20,  10,  10,
+          5,   5,  10,  25,  25,  10,   5,   5,
+          0,   0,   0,  20,  20,   0,   0,   0,
+          5,  -5, -10,   0,   0, -10,  -5,   5,
+          5,  10,  10, -20, -20,  10,  10,   5,
+          0,   0,   0,   0,   0,   0,   0,   0
+    ],
+    [
+        -50, -40, -30, -30, -30, -30, -40, -50,
+        -40, -20,   0,   0,   0,   0, -20, -40,
+        -30,   0,  10,  15,  15,  10,   0, -30,
+        -30,   5,  15,  20,  20,  15,   5, -30,
+        -30,   0,  15,  20,  20,  15,   0, -30,
+        -30,   5,  10,  15,  15,  10,   5, -30,
+        -40, -20,   0,   5,   5,   0, -20, -40,
+        -50, -40, -30, -30, -30, -30, -40, -50
+    ],
+    [
+        -20, -10, -10, -10, -10, -10, -10, -20,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -10,   0,   5,  10,  10,   5,   0, -10,
+        -10,   5,   5,  10,  10,   5,   5, -10,
+        -10,   0,  10,  10,  10,  10,   0, -10,
+        -10,  10,  10,  10,  10,  10,  10, -10,
+        -10,   5,   0,   0,   0,   0,   5, -10,
+        -20, -10, -10, -10, -10, -10, -10, -20
+    ],
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+          5,  10,  10,  10,  10,  10,  10,   5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+          0,   0,   0,   5,   5,   0,   0,   0
+    ],
+    [
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -10,   0,   5,   5,   5,   5,   0, -10,
+         -5,   0,   5,   5,   5,   5,   0,  -5,
+          0,   0,   5,   5,   5,   5,   0,  -5,
+        -10,   5,   5,   5,   5,   5,   0, -10,
+        -10,   0,   5,   0,   0,   0,   0, -10,
+        -20, -10, -10,  -5,  -5, -10, -10, -20
+    ],
+    [
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -20, -30, -30, -40, -40, -30, -30, -20,
+        -10, -20, -20, -20, -20, -20, -20, -10,
+         20,  20,   0,   0,   0,   0,  20,  20,
+         20,  30,  10,   0,   0,  10,  30,  20
+    ]
+];
+
+/// The same for the endgame, where pawns race to promote and all pieces, the king included,
+/// belong in the center.
+const ENDGAME_TABLES: [[i32; 64]; NUM_PIECE_TYPES as usize] = [
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+         80,  80,  80,  80,  80,  80,  80,  80,
+         50,  50,  50,  50,  50,  50,  50,  50,
+         30,  30,  30,  30,  30,  30,  30,  30,
+         20,  20,  20,  20,  20,  20,  20,  20,
+         10,  10,  10,  10,  10,  10,  10,  10,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0
+    ],
+    [
+        -50, -40, -30, -30, -30, -30, -40, -50,
+        -40, -20,   0,   0,   0,   0, -20, -40,
+        -30,   0,  10,  15,  15,  10,   0, -30,
+        -30,   5,  15,  20,  20,  15,   5, -30,
+        -30,   5,  15,  20,  20,  15,   5, -30,
+        -30,   0,  10,  15,  15,  10,   0, -30,
+        -40, -20,   0,   0,   0,   0, -20, -40,
+        -50, -40, -30, -30, -30, -30, -40, -50
+    ],
+    [
+        -20, -10, -10, -10, -10, -10, -10, -20,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -10,   0,   5,  10,  10,   5,   0, -10,
+        -10,   0,  10,  15,  15,  10,   0, -10,
+        -10,   0,  10,  15,  15,  10,   0, -10,
+        -10,   0,   5,  10,  10,   5,   0, -10,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -20, -10, -10, -10, -10, -10, -10, -20
+    ],
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+          5,  10,  10,  10,  10,  10,  10,   5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+          0,   0,   0,   0,   0,   0,   0,   0
+    ],
+    [
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+        -10,   0,   5,   5,   5,   5,   0, -10,
+        -10,   5,  10,  10,  10,  10,   5, -10,
+         -5,   5,  10,  15,  15,  10,   5,  -5,
+         -5,   5,  10,  15,  15,  10,   5,  -5,
+        -10,   5,  10,  10,  10,  10,   5, -10,
+        -10,   0,   5,   5,   5,   5,   0, -10,
+        -20, -10, -10,  -5,  -5, -10, -10, -20
+    ],
+    [
+        -50, -40, -30, -20, -20, -30, -40, -50,
+        -30, -20, -10,   0,   0, -10, -20, -30,
+        -30, -10,  20,  30,  30,  20, -10, -30,
+        -30, -10,  30,  40,  40,  30, -10, -30,
+        -30, -10,  30,  40,  40,  30, -10, -30,
+        -30, -10,  20,  30,  30,  20, -10, -30,
+        -30, -30,   0,   0,   0,   0, -30, -30,
+        -50, -30, -30, -30, -30, -30, -30, -50
+    ]
+];
+
+/// Returns the material of the player with the given color in centipawns. Pieces held in
+/// a pocket count as well as they can be dropped onto the board at any time.
+pub fn material(position: &Position, color: u8) -> i32 {
+    let mut score = 0;
+
+    for piece_type in 0..NUM_PIECE_TYPES {
+        score += count(position, color, piece_type) * PIECE_VALUES[piece_type as usize];
+    }
+
+    return score;
+}
+
+/// Returns the number of pieces of the given type the player has on the board and in the
+/// pocket.
+#[inline(always)]
+fn count(position: &Position, color: u8, piece_type: u8) -> i32 {
+    return position.get_piece_bitboard(color, piece_type).count_ones() as i32 + position.pocket().count(color, piece_type) as i32;
+}
+
+/// Returns the bonus of the player with the given color for where its pieces stand. The
+/// middlegame and endgame tables are blended by the game phase, so the evaluation changes
+/// gradually as pieces are traded. Smaller boards are stretched onto the tables, so their
+/// last rank is still the 8th. Pieces in a pocket don't stand anywhere yet.
+pub fn piece_squares(position: &Position, color: u8) -> i32 {
+    let geometry = position.variant().geometry();
+    let (mut middlegame, mut endgame) = (0, 0);
+
+    for piece_type in 0..NUM_PIECE_TYPES {
+        let mut bb = position.get_piece_bitboard(color, piece_type);
+
+        while bb != 0 {
+            let sq = bb.trailing_zeros() as u8;
+            bb &= bb - 1;
+
+            // The rank as seen from the player's side of the board.
+            let rank = if color == WHITE { sq / 8 } else { geometry.ranks - 1 - sq / 8 };
+            let rank = rank as usize * 7 / (geometry.ranks as usize - 1);
+            let file = (sq % 8) as usize * 7 / (geometry.files as usize - 1);
+
+            let index = (7 - rank) * 8 + file;
+
+            middlegame += MIDDLEGAME_TABLES[piece_type as usize][index];
+            endgame += ENDGAME_TABLES[piece_type as usize][index];
+        }
+    }
+
+    return taper(middlegame, endgame, position.phase());
+}
+
+/// Blends a middlegame and an endgame score by the game phase.
+pub fn taper(middlegame: i32, endgame: i32, phase: i32) -> i32 {
+    return (middlegame * phase + endgame * (MAX_PHASE - phase)) / MAX_PHASE;
+}
+
+/// Returns the correction of the material of the player with the given color for how the
+/// pieces work together (material imbalance): the bishop pair, knights gaining and rooks
+/// losing with more pawns on the board and rooks and queens duplicating each other. The
+/// weights are taken from the parameter registry.
+pub fn imbalance(position: &Position, color: u8) -> i32 {
+    let pawns = count(position, color, PAWN) - 5;
+    let knights = count(position, color, KNIGHT);
+    let rooks = count(position, color, ROOK);
+    let mut score = knights * pawns * params::get(KNIGHT_PAWNS) - rooks * pawns * params::get(ROOK_PAWNS);
+
+    if count(position, color, BISHOP) >= 2 {
+        score += params::get(BISHOP_PAIR);
+    }
+
+    if rooks >= 2 {
+        score -= params::get(ROOK_REDUNDANCY);
+    }
+
+    if count(position, color, QUEEN) >= 1 {
+        score -= rooks * params::get(QUEEN_REDUNDANCY);
+    }
+
+    return score;
+}
+
+/// Evaluates the position statically from the point of view of the player whose turn it is.
+/// Positive scores are good for the player to move. Positions carrying the accumulator of an
+/// NNUE network are evaluated with the network.
+pub fn evaluate(position: &Position) -> i32 {
+    #[cfg(feature = "nnue")]
+    if let Some(score) = position.nnue_evaluate() {
+        return score;
+    }
+
+    let opponent = flip_color(position.turn);
+
+    return evaluate_pieces(position) + pawn_structure::evaluate(position, position.turn) - pawn_structure::evaluate(position, opponent);
+}
+
+/// Like evaluate, but the pawn structure is taken from the pawn hash table if it has been
+/// evaluated before.
+pub fn evaluate_with_pawn_table(position: &Position, pawns: &mut PawnTable) -> i32 {
+    #[cfg(feature = "nnue")]
+    if let Some(score) = position.nnue_evaluate() {
+        return score;
+    }
+
+    return evaluate_pieces(position) + pawns.evaluate(position);
+}
+
+/// Evaluates everything but the pawn structure.
+fn evaluate_pieces(position: &Position) -> i32 {
+    let opponent = flip_color(position.turn);
+
+    return material(position, position.turn) - material(position, opponent)
+        + piece_squares(position, position.turn) - piece_squares(position, opponent)
+        + imbalance(position, position.turn) - imbalance(position, opponent)
+        + king_safety::evaluate(position, position.turn) - king_safety::evaluate(position, opponent)
+        + coordination::evaluate(position, position.turn) - coordination::evaluate(position, opponent);
+}
+
+/// Computes the static exchange evaluation of a move, i.e. the material the player making
+/// it gains when both sides keep recapturing on the target square with their least valuable
+/// piece as long as it pays off.
+pub fn see(position: &Position, m: Move) -> i32 {
+    let (target, gain) = match m {
+        Move::StandardMove(_, _, target, captures, promotes_to, _) => {
+            let captured = captures.map_or(0, |t| PIECE_VALUES[t as usize]);
+            let promotion = promotes_to.map_or(0, |t| PIECE_VALUES[t as usize] - PIECE_VALUES[PAWN as usize]);
+
+            (target, captured + promotion)
+        },
+        _ => return 0
+    };
+
+    return gain - exchange_on(&position.make_move(m), target);
+}
+
+/// Returns what the player whose turn it is gains by recapturing on the given square with the
+/// least valuable piece, or 0 if recapturing doesn't pay off.
+fn exchange_on(position: &Position, sq: u8) -> i32 {
+    let recapture = position.clone().get_all_legal_moves().into_iter()
+        .filter_map(|m| match m {
+            Move::StandardMove(piece_type, _, target, Some(_), _, _) if target == sq => Some((piece_type, m)),
+            _ => None
+        })
+        .min_by_key(|(piece_type, _)| PIECE_VALUES[*piece_type as usize] + if *piece_type == KING { 10000 } else { 0 });
+
+    return match recapture {
+        Some((_, m)) => see(position, m).max(0),
+        None         => 0
+    };
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::BLACK;
+    use crate::search::MATE_BOUND;
+    use crate::training::Rng;
+    use crate::variant::Variant;
+
+    fn position(fen: &str) -> Position {
+        return Position::from_fen(fen.to_string()).unwrap();
+    }
+
+    /// Returns the positions of random games of the given variant.
+    fn random_positions(rng: &mut Rng, variant: Variant, games: usize, plies: usize) -> Vec<Position> {
+        let mut positions = Vec::new();
+
+        for _ in 0..games {
+            let mut position = Position::variant_starting_position(variant);
+
+            for _ in 0..plies {
+                let moves = position.clone().get_all_legal_moves();
+
+                if moves.is_empty() {
+                    break;
+                }
+
+                position = position.make_move(moves[rng.below(moves.len() as u64) as usize]);
+                positions.push(position.clone());
+            }
+        }
+
+        return positions;
+    }
+
+    /// Returns the evaluation from white's point of view.
+    fn white_eval(position: &Position) -> i32 {
+        return if position.turn == WHITE { evaluate(position) } else { -evaluate(position) };
+    }
+
+    #[test]
+    fn evaluation_is_symmetric() {
+        let mut rng = Rng::new(7);
+
+        for (variant, games) in [(Variant::Standard, 150), (Variant::Gardner, 30), (Variant::Crazyhouse, 30)] {
+            for position in random_positions(&mut rng, variant, games, 80) {
+                let eval = white_eval(&position);
+
+                assert_eq!(eval, -white_eval(&position.mirror()), "Asymmetric evaluation of {}", position.to_fen());
+                assert!(eval.abs() < MATE_BOUND, "Evaluation {} of {} is a mate score", eval, position.to_fen());
+            }
+        }
+    }
+
+    #[test]
+    fn corrects_the_material_for_imbalances() {
+        // Equal armies cancel out.
+        let pos = Position::starting_position();
+        assert_eq!(imbalance(&pos, WHITE), imbalance(&pos, BLACK));
+        assert_eq!(evaluate(&pos), 0);
+
+        // The bishop pair against bishop and knight with eight pawns each.
+        let pos = position("2b1kb2/pppppppp/8/8/8/8/PPPPPPPP/2B1KN2 w - - 0 1");
+        assert_eq!(imbalance(&pos, WHITE), 18);
+        assert_eq!(imbalance(&pos, BLACK), 50);
+
+        // Two rooks and a queen duplicate each other, a lone rook without pawns doesn't.
+        let pos = position("3rk3/8/8/8/8/8/8/R2QK2R w - - 0 1");
+        assert_eq!(imbalance(&pos, WHITE), 2 * 60 - 16 - 2 * 8);
+        assert_eq!(imbalance(&pos, BLACK), 60);
+        // The queen stands a bit off the center, the black rook on the central file and both
+        // kings away from it, which matters for the endgame part of the blend.
+        assert_eq!(evaluate(&pos), 1400 + 88 - 60 + taper(-5, -5 - 30, 10) - taper(5, -30, 10));
+    }
+
+    #[test]
+    fn tapers_between_middlegame_and_endgame() {
+        assert_eq!(Position::starting_position().phase(), MAX_PHASE);
+        assert_eq!(position("4k3/8/8/8/4K3/8/8/3R4 w - - 0 1").phase(), 2);
+        assert_eq!(position("4k3/8/8/8/8/8/8/4K3 w - - 0 1").phase(), 0);
+
+        // Promoted queens don't push the phase past the starting position.
+        assert_eq!(position("7k/8/8/8/8/8/PPPPPPPP/QQQQKQQQ w - - 0 1").phase(), MAX_PHASE);
+
+        assert_eq!(taper(100, 0, MAX_PHASE), 100);
+        assert_eq!(taper(100, 0, MAX_PHASE / 2), 50);
+        assert_eq!(taper(100, -20, 0), -20);
+    }
+
+    #[test]
+    fn piece_squares_reward_development_and_active_kings() {
+        let pos = Position::starting_position();
+        assert_eq!(piece_squares(&pos, WHITE), piece_squares(&pos, BLACK));
+
+        // Nf3 develops a knight.
+        let developed = pos.make_move(pos.uci_to_move("g1f3").unwrap());
+        assert_eq!(piece_squares(&developed, WHITE) - piece_squares(&pos, WHITE), 50);
+
+        // Kings hide in the middlegame and come to the center in the endgame.
+        let king = |fen: &str| piece_squares(&position(fen), WHITE);
+        assert!(king("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1RK1 w kq - 0 1") > king("rnbqkbnr/pppppppp/8/8/4K3/8/PPPPPPPP/RNBQ1R2 w kq - 0 1"));
+        assert!(king("4k3/8/8/8/4K3/8/8/3R4 w - - 0 1") > king("4k3/8/8/8/8/8/8/3R2K1 w - - 0 1"));
+
+        // Gardner's board is stretched onto the tables, so advanced pawns gain there as well.
+        let gardner = |fen: &str| piece_squares(&Position::from_variant_fen(Variant::Gardner, fen.to_string()).unwrap(), WHITE);
+        assert!(gardner("4k/P4/5/5/4K w - - 0 1") > gardner("4k/5/5/P4/4K w - - 0 1"));
+    }
+}
@@ -0,0 +1,243 @@
+use std::fmt;
+
+use crate::coordination;
+use crate::eval::{self, PIECE_VALUES};
+use crate::moves::Move;
+use crate::bitboards::bb_ops;
+use crate::position::{flip_color, file_to_string, Position, NUM_PIECE_TYPES, PAWN, KNIGHT, BISHOP, ROOK, QUEEN, KING};
+use crate::search::Score;
+
+
+/// Tactical and strategic themes a move can show.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Motif {
+    Checkmate,
+    Check,
+    /// The moved piece attacks several opposing pieces of the given types at once, each of
+    /// them either the king or more valuable than the moved piece.
+    Fork(Vec<u8>),
+    Promotion(u8),
+    Castling,
+    /// The move doubles the rooks on the given file.
+    DoubledRooks(u8),
+    /// The move lines up the queen with a piece of the given type, a bishop or a rook.
+    Battery(u8)
+}
+
+/// A short explanation of why a move was chosen.
+#[derive(Clone, Debug)]
+pub struct Explanation {
+    /// The move in SAN.
+    pub san: String,
+    /// The score of the move found by the search in centipawns, if the move was searched.
+    pub score: Option<i32>,
+    /// The material the move wins according to the static exchange evaluation.
+    pub material: i32,
+    /// The motifs the move shows.
+    pub motifs: Vec<Motif>,
+    /// The number of pseudo-legal moves the moving side has before and after the move.
+    pub mobility: (usize, usize)
+}
+
+/// Explains a legal move in the given position.
+pub fn explain(position: &Position, m: Move) -> Explanation {
+    let after = position.make_move(m);
+    let color = position.turn;
+
+    let mut motifs = Vec::new();
+
+    if after.is_checked(after.turn) {
+        if after.clone().get_all_legal_moves().is_empty() {
+            motifs.push(Motif::Checkmate);
+        } else {
+            motifs.push(Motif::Check);
+        }
+    }
+
+    match m {
+        Move::StandardMove(piece_type, _, target, _, promotes_to, _) => {
+            let forked = forked_pieces(&after, color, promotes_to.unwrap_or(piece_type), target);
+
+            if forked.len() >= 2 {
+                motifs.push(Motif::Fork(forked));
+            }
+
+            if let Some(t) = promotes_to {
+                motifs.push(Motif::Promotion(t));
+            }
+        },
+        Move::ShortCastle | Move::LongCastle => motifs.push(Motif::Castling),
+        Move::Drop(_, _) => ()
+    }
+
+    let doubled = coordination::doubled_rooks(position, color);
+
+    for fl in coordination::doubled_rooks(&after, color) {
+        if !doubled.contains(&fl) {
+            motifs.push(Motif::DoubledRooks(fl));
+        }
+    }
+
+    let batteries = coordination::batteries(position, color);
+
+    for battery in coordination::batteries(&after, color) {
+        if !batteries.contains(&battery) {
+            motifs.push(Motif::Battery(battery.piece_type));
+        }
+    }
+
+    // Count the moves the moving side would have if it was its turn again.
+    let mut own_turn = after;
+    own_turn.flip_turn();
+
+    return Explanation {
+        san: position.move_to_san(m),
+        score: None,
+        material: eval::see(position, m),
+        motifs,
+        mobility: (position.get_pseudo_legal_moves().len(), own_turn.get_pseudo_legal_moves().len())
+    };
+}
+
+/// Searches the position to the given depth and explains the best move found. Returns None if
+/// there are no legal moves.
+pub fn explain_best_move(position: &Position, depth: u8) -> Option<Explanation> {
+    let (m, score) = *position.evaluate_all_moves(depth).first()?;
+
+    let mut explanation = explain(position, m);
+    explanation.score = Some(score);
+
+    return Some(explanation);
+}
+
+/// Returns the types of all opposing pieces the piece of the given type and color on the given
+/// square attacks that are either the king or worth more than the attacking piece.
+fn forked_pieces(position: &Position, color: u8, piece_type: u8, sq: u8) -> Vec<u8> {
+    let mut forked = Vec::new();
+
+    for target_type in 0..NUM_PIECE_TYPES {
+        if target_type != KING && PIECE_VALUES[target_type as usize] <= PIECE_VALUES[piece_type as usize] {
+            continue;
+        }
+
+        for target in bb_ops::idx_bitscan(position.get_piece_bitboard(flip_color(color), target_type)) {
+            if position.attacks_square(color, sq, piece_type, target) {
+                forked.push(target_type);
+            }
+        }
+    }
+
+    return forked;
+}
+
+/// Returns the name of a piece type as used in the explanations.
+fn piece_name(piece_type: u8) -> &'static str {
+    return match piece_type {
+        PAWN   => "pawn",
+        KNIGHT => "knight",
+        BISHOP => "bishop",
+        ROOK   => "rook",
+        QUEEN  => "queen",
+        _      => "king"
+    };
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut sentences = Vec::new();
+
+        match self.score {
+            Some(score) => sentences.push(format!("{} is the best move ({}).", self.san, Score::new(score).to_pawns())),
+            None        => sentences.push(format!("{} was played.", self.san))
+        }
+
+        if self.material > 0 {
+            sentences.push(format!("It wins {} centipawns of material.", self.material));
+        } else if self.material < 0 {
+            sentences.push(format!("It gives up {} centipawns of material.", -self.material));
+        }
+
+        for motif in &self.motifs {
+            sentences.push(match motif {
+                Motif::Checkmate     => String::from("It delivers checkmate."),
+                Motif::Check         => String::from("It gives check."),
+                Motif::Fork(pieces)  => {
+                    let names: Vec<&str> = pieces.iter().map(|t| piece_name(*t)).collect();
+                    format!("It forks the {}.", names.join(" and the "))
+                },
+                Motif::Promotion(t)  => format!("It promotes to a {}.", piece_name(*t)),
+                Motif::Castling      => String::from("It brings the king to safety by castling."),
+                Motif::DoubledRooks(fl) => format!("It doubles the rooks on the {}-file.", file_to_string(*fl).unwrap_or('?')),
+                Motif::Battery(t)    => format!("It lines up the queen and the {} in a battery.", piece_name(*t))
+            });
+        }
+
+        let (before, after) = self.mobility;
+
+        if after > before {
+            sentences.push(format!("It increases mobility from {} to {} moves.", before, after));
+        } else if after < before {
+            sentences.push(format!("It reduces mobility from {} to {} moves.", before, after));
+        }
+
+        return write!(f, "{}", sentences.join(" "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(fen: &str) -> Position {
+        return Position::from_fen(fen.to_string()).unwrap();
+    }
+
+    #[test]
+    fn explains_a_winning_capture() {
+        let pos = position("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1");
+        let explanation = explain_best_move(&pos, 1).unwrap();
+
+        assert_eq!(explanation.san, "Rxd5");
+        assert_eq!(explanation.material, 900);
+        assert!(explanation.to_string().starts_with("Rxd5 is the best move (+5.60). It wins 900 centipawns of material."));
+    }
+
+    #[test]
+    fn explains_a_fork() {
+        let pos = position("r3k3/8/8/8/8/8/8/1N2K3 w - - 0 1");
+        let m = pos.san_to_move("Nc3").unwrap();
+
+        assert!(!explain(&pos, m).motifs.iter().any(|m| matches!(m, Motif::Fork(_))));
+
+        let pos = position("rb2k3/8/8/3N4/8/8/8/4K3 w - - 0 1");
+        let explanation = explain(&pos, pos.san_to_move("Nc7+").unwrap());
+
+        assert_eq!(explanation.motifs, vec![Motif::Check, Motif::Fork(vec![ROOK, KING])]);
+        assert!(explanation.to_string().contains("It forks the rook and the king."));
+    }
+
+    #[test]
+    fn explains_doubled_rooks_and_batteries() {
+        let pos = position("4k3/8/8/8/8/8/3R4/R3K3 w - - 0 1");
+        let explanation = explain(&pos, pos.san_to_move("Rad1").unwrap());
+
+        assert_eq!(explanation.motifs, vec![Motif::DoubledRooks(3)]);
+        assert!(explanation.to_string().contains("It doubles the rooks on the d-file."));
+
+        let pos = position("4k3/8/8/8/8/8/1B6/3QK3 w - - 0 1");
+        let explanation = explain(&pos, pos.san_to_move("Qa1").unwrap());
+
+        assert_eq!(explanation.motifs, vec![Motif::Battery(BISHOP)]);
+        assert!(explanation.to_string().contains("It lines up the queen and the bishop in a battery."));
+    }
+
+    #[test]
+    fn exchanges_are_evaluated_statically() {
+        // The pawn is defended, so taking it with the queen loses material.
+        let pos = position("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1");
+        let m = pos.san_to_move("Qxd5").unwrap();
+
+        assert_eq!(eval::see(&pos, m), 100 - 900);
+        assert!(explain(&pos, m).to_string().contains("It gives up 800 centipawns of material."));
+    }
+}
@@ -0,0 +1,331 @@
+use crate::annotations::Annotations;
+use crate::moves::Move;
+use crate::position::Position;
+
+
+/// Identifies a node of a game's history tree, i.e. a position reached during the game.
+pub type NodeId = usize;
+
+/// A node of the game history: a position and the move that led to it. The first child
+/// continues the main line, the others are variations.
+#[derive(Clone)]
+struct Node {
+    position: Position,
+    m: Option<Move>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    comment: Option<String>,
+    annotations: Annotations,
+    /// The child that was visited last and is followed when redoing a move.
+    last_child: Option<usize>
+}
+
+/// A game of chess consisting of a starting position and the moves played from it. Moves that
+/// were taken back are kept in a history tree, so they can be redone and playing another move
+/// branches into a side-line. The current line is the path from the root to the current node.
+#[derive(Clone)]
+pub struct Game {
+    nodes: Vec<Node>,
+    line: Vec<NodeId>,
+    moves: Vec<Move>
+}
+
+impl Game {
+    /// Construct a new game starting from the regular starting position.
+    pub fn new() -> Game {
+        return Game::from_position(Position::starting_position());
+    }
+
+    /// Construct a new game starting from the given position.
+    pub fn from_position(position: Position) -> Game {
+        let root = Node { position, m: None, parent: None, children: Vec::new(), comment: None, annotations: Annotations::new(), last_child: None };
+
+        return Game {
+            nodes: vec![root],
+            line: vec![0],
+            moves: Vec::new()
+        };
+    }
+
+    /// Returns the current position of the game.
+    pub fn position(&self) -> &Position {
+        return &self.nodes[self.current()].position;
+    }
+
+    /// Returns the position the game started from.
+    pub fn starting_position(&self) -> &Position {
+        return &self.nodes[0].position;
+    }
+
+    /// Returns all moves played so far.
+    pub fn moves(&self) -> &[Move] {
+        return &self.moves;
+    }
+
+    /// Returns the number of moves played so far.
+    pub fn ply(&self) -> usize {
+        return self.moves.len();
+    }
+
+    /// Returns the moves that were already played from the current position in the history,
+    /// i.e. the moves that can be redone.
+    pub fn continuations(&self) -> Vec<Move> {
+        return self.nodes[self.current()].children.iter().map(|&child| self.nodes[child].m.unwrap()).collect();
+    }
+
+    /// Returns the moves of the main line, which follows the first continuation of every node.
+    pub fn main_line(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        let mut node = self.root();
+
+        while let Some(&child) = self.children(node).first() {
+            moves.push(self.nodes[child].m.unwrap());
+            node = child;
+        }
+
+        return moves;
+    }
+
+    /// Returns the root of the history tree, i.e. the node of the starting position.
+    pub fn root(&self) -> NodeId {
+        return 0;
+    }
+
+    /// Returns the node of the current position.
+    pub fn current_node(&self) -> NodeId {
+        return self.current();
+    }
+
+    /// Returns the parent of the given node or None for the root.
+    pub fn parent(&self, node: NodeId) -> Option<NodeId> {
+        return self.nodes[node].parent;
+    }
+
+    /// Returns the children of the given node. The first one continues the main line.
+    pub fn children(&self, node: NodeId) -> &[NodeId] {
+        return &self.nodes[node].children;
+    }
+
+    /// Returns the move leading to the given node or None for the root.
+    pub fn node_move(&self, node: NodeId) -> Option<Move> {
+        return self.nodes[node].m;
+    }
+
+    /// Returns the position of the given node.
+    pub fn node_position(&self, node: NodeId) -> &Position {
+        return &self.nodes[node].position;
+    }
+
+    /// Returns the comment attached to the given node.
+    pub fn comment(&self, node: NodeId) -> Option<&str> {
+        return self.nodes[node].comment.as_deref();
+    }
+
+    /// Attaches a comment to the current node, i.e. to the last move played. Comments on the
+    /// root describe the whole game.
+    pub fn set_comment(&mut self, comment: String) {
+        let current = self.current();
+        self.nodes[current].comment = Some(comment);
+    }
+
+    /// Returns the squares and arrows marked in the position of the given node.
+    pub fn annotations(&self, node: NodeId) -> &Annotations {
+        return &self.nodes[node].annotations;
+    }
+
+    /// Adds annotations to the position of the current node.
+    pub fn annotate(&mut self, annotations: Annotations) {
+        let current = self.current();
+        self.nodes[current].annotations.extend(annotations);
+    }
+
+    /// Puts a piece into the pocket of the player with the given color in the current position,
+    /// e.g. when the partner captured it on the other board in Bughouse.
+    pub fn feed_pocket(&mut self, color: u8, piece_type: u8) {
+        let current = self.current();
+        self.nodes[current].position.feed_pocket(color, piece_type);
+    }
+
+    /// Plays the given move in the current position. If the move was played here before, the
+    /// game follows the existing line, otherwise a new side-line is started.
+    pub fn play(&mut self, m: Move) {
+        let current = self.current();
+        let existing = self.nodes[current].children.iter().copied().find(|&child| self.nodes[child].m == Some(m));
+
+        let child = match existing {
+            Some(child) => child,
+            None        => {
+                let position = self.nodes[current].position.make_move(m);
+                let child = self.nodes.len();
+
+                self.nodes.push(Node { position, m: Some(m), parent: Some(current), children: Vec::new(), comment: None, annotations: Annotations::new(), last_child: None });
+                self.nodes[current].children.push(child);
+
+                child
+            }
+        };
+
+        self.enter(child);
+    }
+
+    /// Takes back the last move and returns it. Returns None if no move was played yet.
+    pub fn undo(&mut self) -> Option<Move> {
+        let m = self.moves.pop()?;
+        self.line.pop();
+
+        return Some(m);
+    }
+
+    /// Plays the move that was taken back last in the current position again and returns it.
+    /// Returns None if there is no move to redo.
+    pub fn redo(&mut self) -> Option<Move> {
+        let node = &self.nodes[self.current()];
+        let child = node.last_child.or_else(|| node.children.first().copied())?;
+
+        self.enter(child);
+
+        return self.nodes[child].m;
+    }
+
+    /// Moves along the current line until the given number of moves is played. Returns false
+    /// if the line is too short, in which case the game ends up at the end of the line.
+    pub fn goto(&mut self, ply: usize) -> bool {
+        while self.ply() > ply {
+            self.undo();
+        }
+
+        while self.ply() < ply {
+            if self.redo().is_none() {
+                return false;
+            }
+        }
+
+        return true;
+    }
+
+    /// Jumps to the given node of the history tree, making the path to it the current line.
+    pub fn goto_node(&mut self, node: NodeId) {
+        let mut path = vec![node];
+
+        while let Some(parent) = self.nodes[*path.last().unwrap()].parent {
+            path.push(parent);
+        }
+
+        path.reverse();
+
+        self.line.truncate(1);
+        self.moves.clear();
+
+        for &child in &path[1..] {
+            self.enter(child);
+        }
+    }
+
+    fn current(&self) -> NodeId {
+        return *self.line.last().unwrap();
+    }
+
+    fn enter(&mut self, child: NodeId) {
+        let current = self.current();
+        self.nodes[current].last_child = Some(child);
+
+        self.line.push(child);
+        self.moves.push(self.nodes[child].m.unwrap());
+    }
+}
+
+impl Default for Game {
+    fn default() -> Game {
+        return Game::new();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(moves: &[&str]) -> Game {
+        let mut game = Game::new();
+
+        for san in moves {
+            let m = game.position().san_to_move(san).unwrap();
+            game.play(m);
+        }
+
+        return game;
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut game = game(&["e4", "e5", "Nf3"]);
+        let moves = game.moves().to_vec();
+
+        assert_eq!(game.undo(), Some(moves[2]));
+        assert_eq!(game.undo(), Some(moves[1]));
+        assert_eq!(game.ply(), 1);
+        assert_eq!(game.redo(), Some(moves[1]));
+        assert_eq!(game.redo(), Some(moves[2]));
+        assert_eq!(game.redo(), None);
+        assert_eq!(game.moves(), &moves[..]);
+    }
+
+    #[test]
+    fn goto_moves_along_the_line() {
+        let mut game = game(&["e4", "e5", "Nf3", "Nc6"]);
+        let fen = game.position().to_fen();
+
+        assert!(game.goto(1));
+        assert_eq!(game.ply(), 1);
+        assert!(game.goto(4));
+        assert_eq!(game.position().to_fen(), fen);
+        assert!(!game.goto(6));
+        assert_eq!(game.ply(), 4);
+        assert!(game.goto(0));
+        assert!(game.position() == game.starting_position());
+    }
+
+    #[test]
+    fn playing_another_move_starts_a_side_line() {
+        let mut game = game(&["e4", "e5", "Nf3"]);
+        let main_line = game.moves().to_vec();
+
+        game.undo();
+        let bc4 = game.position().san_to_move("Bc4").unwrap();
+        game.play(bc4);
+
+        assert_eq!(game.moves(), &[main_line[0], main_line[1], bc4]);
+
+        // Both lines are kept and redo follows the one visited last.
+        game.undo();
+        assert_eq!(game.continuations(), vec![main_line[2], bc4]);
+        assert_eq!(game.redo(), Some(bc4));
+
+        game.undo();
+        game.play(main_line[2]);
+        assert_eq!(game.moves(), &main_line[..]);
+        assert_eq!(game.continuations(), Vec::new());
+        assert_eq!(game.main_line(), main_line);
+    }
+
+    #[test]
+    fn goto_node_switches_lines() {
+        let mut game = game(&["e4", "e5", "Nf3"]);
+        let main_end = game.current_node();
+
+        game.goto(1);
+        let c5 = game.position().san_to_move("c5").unwrap();
+        game.play(c5);
+        game.set_comment(String::from("The Sicilian"));
+        let side_line = game.current_node();
+
+        game.goto_node(main_end);
+        assert_eq!(game.moves(), &game.main_line()[..]);
+
+        game.goto_node(side_line);
+        assert_eq!(game.ply(), 2);
+        assert_eq!(game.node_move(side_line), Some(c5));
+        assert_eq!(game.comment(side_line), Some("The Sicilian"));
+        assert_eq!(game.parent(side_line).map(|node| game.children(node).len()), Some(2));
+    }
+}
@@ -0,0 +1,117 @@
+//! Ctrl-C handling for searches started from the terminal. While a search runs under
+//! interruptible, Ctrl-C sets its stop flag instead of terminating the process, so the search
+//! still returns the best move found so far. At any other time Ctrl-C terminates the process
+//! as usual. Only Unix systems are supported, elsewhere Ctrl-C keeps its default behavior.
+
+use std::hint;
+use std::ptr;
+use std::sync::{Arc, Once};
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+
+
+/// The stop flag of the running interruptible search, null while there is none. It holds a
+/// reference of its own (see Arc::into_raw), so the flag lives as long as it's registered.
+static TARGET: AtomicPtr<AtomicBool> = AtomicPtr::new(ptr::null_mut());
+
+/// The number of signal handlers running at the moment. The flag is only released once none
+/// of them can still be using it.
+static HANDLING: AtomicUsize = AtomicUsize::new(0);
+
+static INSTALL: Once = Once::new();
+
+#[cfg(unix)]
+mod sys {
+    use std::os::raw::c_int;
+    use std::sync::atomic::Ordering;
+
+    pub const SIGINT: c_int = 2;
+
+    extern "C" {
+        fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+        fn _exit(status: c_int) -> !;
+    }
+
+    /// Only touches atomics, which is all a signal handler may safely do.
+    extern "C" fn on_interrupt(_: c_int) {
+        super::HANDLING.fetch_add(1, Ordering::SeqCst);
+        let stop = super::TARGET.load(Ordering::SeqCst);
+
+        if stop.is_null() {
+            // The exit status of processes terminated by SIGINT.
+            unsafe { _exit(128 + SIGINT) };
+        }
+
+        unsafe { (*stop).store(true, Ordering::Relaxed) };
+        super::HANDLING.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    pub fn install() {
+        unsafe { signal(SIGINT, on_interrupt) };
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    pub fn install() {}
+}
+
+/// Unregisters the stop flag even if the search panics and releases the reference the handler
+/// used, once no handler is using it anymore.
+struct Registration;
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let stop = TARGET.swap(ptr::null_mut(), Ordering::SeqCst);
+
+        // A handler that loaded the flag before it was unregistered may still be setting it.
+        while HANDLING.load(Ordering::SeqCst) != 0 {
+            hint::spin_loop();
+        }
+
+        if !stop.is_null() {
+            drop(unsafe { Arc::from_raw(stop) });
+        }
+    }
+}
+
+/// Clears the stop flag and runs f, during which Ctrl-C sets the flag instead of terminating
+/// the process.
+pub fn interruptible<T, F: FnOnce() -> T>(stop: &Arc<AtomicBool>, f: F) -> T {
+    INSTALL.call_once(sys::install);
+
+    stop.store(false, Ordering::Relaxed);
+    TARGET.store(Arc::into_raw(Arc::clone(stop)) as *mut AtomicBool, Ordering::SeqCst);
+
+    let registration = Registration;
+    let result = f();
+    drop(registration);
+
+    return result;
+}
+
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::raw::c_int;
+
+    extern "C" {
+        fn raise(signum: c_int) -> c_int;
+    }
+
+    #[test]
+    fn ctrl_c_sets_the_stop_flag() {
+        let stop = Arc::new(AtomicBool::new(true));
+
+        let stopped = interruptible(&stop, || {
+            assert!(!stop.load(Ordering::Relaxed));
+            unsafe { raise(sys::SIGINT) };
+
+            stop.load(Ordering::Relaxed)
+        });
+
+        assert!(stopped);
+        assert!(TARGET.load(Ordering::SeqCst).is_null());
+        assert_eq!(Arc::strong_count(&stop), 1);
+    }
+}
@@ -0,0 +1,169 @@
+use crate::bitboards::bb_ops;
+use crate::bitboards::pawns::file_mask;
+use crate::bitboards::geometry::FILES;
+use crate::params::{self, SHIELD_MISSING, SHIELD_ADVANCED, PAWN_STORM};
+use crate::pawn_structure::Wing;
+use crate::position::{flip_color, Position, PAWN, QUEEN, KING, WHITE};
+
+
+/// Enemy pawns at most this many ranks in front of the king count as storming it.
+const STORM_DISTANCE: i32 = 4;
+
+
+/// Returns the wing the king of the given player has castled to or walked to, or None while
+/// it stays in the center files (d and e on the regular board) or isn't on the board.
+pub fn king_wing(position: &Position, color: u8) -> Option<Wing> {
+    let king = position.get_piece_bitboard(color, KING);
+
+    if king == 0 {
+        return None;
+    }
+
+    let files = position.variant().geometry().files;
+    let fl = king.trailing_zeros() as u8 % FILES;
+
+    return if fl + 1 < files / 2 {
+        Some(Wing::Queenside)
+    } else if fl >= files - 3 {
+        Some(Wing::Kingside)
+    } else {
+        None
+    };
+}
+
+/// Returns the files in front of the king of the given player, i.e. its file and the adjacent
+/// ones, together with the rank of the king.
+fn king_files(position: &Position, color: u8) -> Option<(Vec<u8>, i32)> {
+    king_wing(position, color)?;
+
+    let sq = position.get_piece_bitboard(color, KING).trailing_zeros() as u8;
+    let files = position.variant().geometry().files;
+    let fl = sq % FILES;
+
+    return Some(((fl.saturating_sub(1)..=(fl + 1).min(files - 1)).collect(), (sq / FILES) as i32));
+}
+
+/// Returns the distance in ranks from the king to the closest pawn of the given color on the
+/// file, counted towards the opponent of the king's owner. Pawns behind the king are ignored.
+fn closest_pawn(position: &Position, king_color: u8, pawn_color: u8, fl: u8, king_rank: i32) -> Option<i32> {
+    let pawns = position.get_piece_bitboard(pawn_color, PAWN) & file_mask(fl);
+
+    return bb_ops::idx_bitscan(pawns).into_iter()
+        .map(|sq| (sq / FILES) as i32 - king_rank)
+        .map(|distance| if king_color == WHITE { distance } else { -distance })
+        .filter(|distance| *distance > 0)
+        .min();
+}
+
+/// Returns the penalty for the damage to the pawn shield of the given player's king: for each
+/// file in front of a king on a wing, a missing pawn costs SHIELD_MISSING and a pawn that has
+/// advanced two or more ranks SHIELD_ADVANCED. Kings in the center have no shield to judge.
+pub fn shield_penalty(position: &Position, color: u8) -> i32 {
+    let (files, rank) = match king_files(position, color) {
+        Some(files) => files,
+        None        => return 0
+    };
+
+    return files.into_iter().map(|fl| match closest_pawn(position, color, color, fl, rank) {
+        Some(1)    => 0,
+        Some(2)    => params::get(SHIELD_ADVANCED),
+        Some(_)    => params::get(SHIELD_ADVANCED) * 2,
+        None       => params::get(SHIELD_MISSING)
+    }).sum();
+}
+
+/// Returns the bonus for the pawns of the given player storming the enemy king: each pawn on a
+/// file in front of an enemy king on a wing gains PAWN_STORM for every rank it is closer than
+/// STORM_DISTANCE + 1 ranks.
+pub fn storm_bonus(position: &Position, color: u8) -> i32 {
+    let king_color = flip_color(color);
+
+    let (files, rank) = match king_files(position, king_color) {
+        Some(files) => files,
+        None        => return 0
+    };
+
+    return files.into_iter()
+        .filter_map(|fl| closest_pawn(position, king_color, color, fl, rank))
+        .filter(|distance| *distance <= STORM_DISTANCE)
+        .map(|distance| params::get(PAWN_STORM) * (STORM_DISTANCE + 1 - distance))
+        .sum();
+}
+
+/// Evaluates the safety of the kings from the point of view of the given player: the damage
+/// to the own pawn shield and the pawn storm against the enemy king. Both only matter while
+/// the attacking side still has a queen to exploit them.
+pub fn evaluate(position: &Position, color: u8) -> i32 {
+    let opponent = flip_color(color);
+    let mut score = 0;
+
+    if position.get_piece_bitboard(opponent, QUEEN) != 0 {
+        score -= shield_penalty(position, color);
+    }
+
+    if position.get_piece_bitboard(color, QUEEN) != 0 {
+        score += storm_bonus(position, color);
+    }
+
+    return score;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::BLACK;
+
+    fn position(fen: &str) -> Position {
+        return Position::from_fen(fen.to_string()).unwrap();
+    }
+
+    #[test]
+    fn finds_the_wing_of_the_king() {
+        let pos = position("2kr4/8/8/8/8/8/8/5RK1 w - - 0 1");
+
+        assert_eq!(king_wing(&pos, WHITE), Some(Wing::Kingside));
+        assert_eq!(king_wing(&pos, BLACK), Some(Wing::Queenside));
+        assert_eq!(king_wing(&Position::starting_position(), WHITE), None);
+    }
+
+    #[test]
+    fn penalizes_a_damaged_pawn_shield() {
+        let p = |idx| params::get(idx);
+
+        // An intact shield in front of the castled king.
+        let pos = position("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1");
+        assert_eq!(shield_penalty(&pos, WHITE), 0);
+        assert_eq!(shield_penalty(&pos, BLACK), 0);
+
+        // g3 advanced one rank too far and the h-pawn is gone.
+        let pos = position("6k1/5ppp/8/8/8/6P1/5P2/6K1 w - - 0 1");
+        assert_eq!(shield_penalty(&pos, WHITE), p(SHIELD_ADVANCED) + p(SHIELD_MISSING));
+
+        // Kings in the center aren't judged.
+        let pos = position("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(shield_penalty(&pos, WHITE), 0);
+    }
+
+    #[test]
+    fn rewards_pawn_storms_against_the_enemy_king() {
+        let p = |idx| params::get(idx);
+
+        // Black's g- and h-pawns run at the white king on g1, the f-pawn is too far away.
+        let pos = position("6k1/5p2/8/8/6pp/8/5PPP/6K1 b - - 0 1");
+        assert_eq!(storm_bonus(&pos, BLACK), 2 * 2 * p(PAWN_STORM));
+
+        let pos = position("6k1/5p2/8/8/8/6pp/5P2/6K1 b - - 0 1");
+        assert_eq!(storm_bonus(&pos, BLACK), 2 * 3 * p(PAWN_STORM));
+        assert_eq!(storm_bonus(&pos, WHITE), 0);
+    }
+
+    #[test]
+    fn only_counts_with_queens_on_the_board() {
+        let without = position("6k1/5ppp/8/8/8/6P1/5P2/6K1 w - - 0 1");
+        let with = position("3q2k1/5ppp/8/8/8/6P1/5P2/6K1 w - - 0 1");
+
+        assert_eq!(evaluate(&without, WHITE), 0);
+        assert_eq!(evaluate(&with, WHITE), -shield_penalty(&with, WHITE));
+    }
+}
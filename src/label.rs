@@ -0,0 +1,282 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+use crate::position::Position;
+use crate::search;
+
+
+/// Settings for labeling a dataset of positions.
+#[derive(Clone, Copy, Debug)]
+pub struct LabelConfig {
+    /// The depth of the search scoring each position.
+    pub depth: u8,
+    /// The number of worker threads.
+    pub threads: usize,
+    /// The number of records per chunk. Progress is saved after every chunk, so at most
+    /// one chunk per thread is searched again after an interruption.
+    pub chunk_size: usize
+}
+
+impl Default for LabelConfig {
+    fn default() -> LabelConfig {
+        return LabelConfig { depth: 3, threads: 1, chunk_size: 1000 };
+    }
+}
+
+/// The number of records handled by a labeling run.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct LabelSummary {
+    pub labeled: usize,
+    /// Records that aren't a valid FEN. They are left out of the output.
+    pub invalid: usize,
+    /// Records skipped because they were already labeled by an earlier run.
+    pub skipped: usize
+}
+
+/// Returns the path of the progress file kept next to the output file.
+pub fn progress_path(output: &Path) -> PathBuf {
+    let mut path = output.as_os_str().to_owned();
+    path.push(".progress");
+
+    return PathBuf::from(path);
+}
+
+/// Labels every FEN of the input file (one per line, empty lines are ignored) with its score
+/// from the point of view of the player to move and writes the records to the output file as
+/// `<fen>;<score>`. The input is split into chunks which are searched by several threads,
+/// so the records are written in the order their chunks finish.
+///
+/// The finished chunks are saved in a progress file, which starts with the chunk size and a
+/// fingerprint of the input. If it exists, the chunks in it are skipped and the output is
+/// truncated to the records of those chunks, dropping a partially written chunk. A progress file
+/// written for another input or chunk size is an error, since its chunks would be different
+/// ones. Without a progress file the output is overwritten. The progress file is removed once
+/// all chunks are labeled.
+pub fn label_file(input: &Path, output: &Path, config: LabelConfig) -> io::Result<LabelSummary> {
+    let text = fs::read_to_string(input)?;
+    let records: Vec<String> = text.lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    let chunk_size = config.chunk_size.max(1);
+    let progress_path = progress_path(output);
+    let header = progress_header(chunk_size, &text);
+
+    let (done, length, mut progress) = match read_progress(&progress_path, &header)? {
+        Some((done, length)) => (done, length, OpenOptions::new().append(true).open(&progress_path)?),
+        None                 => {
+            let mut progress = File::create(&progress_path)?;
+            writeln!(progress, "{}", header)?;
+            progress.flush()?;
+
+            (Vec::new(), 0, progress)
+        }
+    };
+
+    let mut out = OpenOptions::new().create(true).append(true).open(output)?;
+    out.set_len(length)?;
+
+    let chunks: Vec<Vec<String>> = records.chunks(chunk_size).map(|chunk| chunk.to_vec()).collect();
+    let pending: Vec<usize> = (0..chunks.len()).filter(|idx| !done.contains(idx)).collect();
+
+    let mut summary = LabelSummary {
+        skipped: done.iter().filter_map(|&idx| chunks.get(idx)).map(|chunk| chunk.len()).sum(),
+        ..LabelSummary::default()
+    };
+
+    let chunks = Arc::new(chunks);
+    let pending = Arc::new(pending);
+    let next = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel();
+
+    let mut workers = Vec::new();
+
+    for _ in 0..config.threads.max(1).min(pending.len().max(1)) {
+        let chunks = Arc::clone(&chunks);
+        let pending = Arc::clone(&pending);
+        let next = Arc::clone(&next);
+        let sender = sender.clone();
+
+        workers.push(thread::spawn(move || {
+            while let Some(&idx) = pending.get(next.fetch_add(1, Ordering::SeqCst)) {
+                let labels: Vec<Option<String>> = chunks[idx].iter().map(|fen| label(fen, config.depth)).collect();
+
+                if sender.send((idx, labels)).is_err() {
+                    break;
+                }
+            }
+        }));
+    }
+
+    drop(sender);
+
+    for (idx, labels) in receiver.iter() {
+        for label in &labels {
+            match label {
+                Some(label) => {
+                    writeln!(out, "{}", label)?;
+                    summary.labeled += 1;
+                },
+                None => summary.invalid += 1
+            }
+        }
+
+        out.flush()?;
+        out.sync_data()?;
+
+        // Only record the chunk once its records are on disk.
+        writeln!(progress, "{} {}", idx, out.metadata()?.len())?;
+        progress.flush()?;
+    }
+
+    for worker in workers {
+        worker.join().expect("Worker thread panicked!");
+    }
+
+    fs::remove_file(&progress_path)?;
+
+    return Ok(summary);
+}
+
+/// Labels a single FEN with the score of a search of the given depth. Returns None if the FEN
+/// is invalid.
+pub fn label(fen: &str, depth: u8) -> Option<String> {
+    let position = Position::from_fen(fen.to_string())?;
+    let score = match search::search(&position, depth) {
+        Some((_, score)) => score,
+        None             => search::negamax(&position, depth, 0)
+    };
+
+    return Some(format!("{};{}", fen, score));
+}
+
+/// Returns the first line of a progress file: the chunk size and the length and FNV-1a hash of
+/// the input, so a run only resumes the chunks of the same records.
+fn progress_header(chunk_size: usize, input: &str) -> String {
+    let hash = input.bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+
+    return format!("chunk-size {} input {} {:016x}", chunk_size, input.len(), hash);
+}
+
+/// Reads the finished chunks and the length of the output after the last of them from a
+/// progress file. Returns None if there is nothing to resume, i.e. the file is missing or was
+/// interrupted before its header was written, and an error if the header doesn't match.
+fn read_progress(path: &Path, header: &str) -> io::Result<Option<(Vec<usize>, u64)>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text)                                      => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e)                                        => return Err(e)
+    };
+
+    let (first, rest) = match text.split_once('\n') {
+        Some(lines) => lines,
+        None        => return Ok(None)
+    };
+
+    if first != header {
+        let message = format!("{} belongs to another input or chunk size, remove it to start over", path.display());
+
+        return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+    }
+
+    let mut done = Vec::new();
+    let mut length = 0;
+
+    // A line cut off by an interruption fails to parse and its chunk is labeled again.
+    for line in rest.lines() {
+        let mut fields = line.split_whitespace().map(|field| field.parse::<u64>());
+
+        if let (Some(Ok(idx)), Some(Ok(end)), None) = (fields.next(), fields.next(), fields.next()) {
+            done.push(idx as usize);
+            length = end;
+        }
+    }
+
+    return Ok(Some((done, length)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FENS: [&str; 4] = [
+        "4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1",
+        "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",
+        "not a fen",
+        "R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1"
+    ];
+
+    fn temp_paths(name: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("chess-label-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let input = dir.join("input.fen");
+        fs::write(&input, FENS.join("\n")).unwrap();
+
+        return (input, dir.join("output.txt"));
+    }
+
+    fn sorted_lines(path: &Path) -> Vec<String> {
+        let mut lines: Vec<String> = fs::read_to_string(path).unwrap().lines().map(String::from).collect();
+        lines.sort();
+
+        return lines;
+    }
+
+    #[test]
+    fn labels_positions_in_parallel() {
+        let (input, output) = temp_paths("parallel");
+        let config = LabelConfig { depth: 1, threads: 3, chunk_size: 1 };
+        let summary = label_file(&input, &output, config).unwrap();
+
+        assert_eq!(summary, LabelSummary { labeled: 3, invalid: 1, skipped: 0 });
+        assert_eq!(sorted_lines(&output), vec![
+            String::from("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1;560"),
+            format!("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1;{}", search::MATE_SCORE - 1),
+            format!("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1;{}", -search::MATE_SCORE)
+        ]);
+        assert!(!progress_path(&output).exists());
+    }
+
+    #[test]
+    fn resumes_after_an_interruption() {
+        let (input, output) = temp_paths("resume");
+        let first = label("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", 1).unwrap();
+
+        // The first chunk is done, the third one was interrupted while writing its records.
+        fs::write(&output, format!("{}\ngarbage", first)).unwrap();
+        let header = progress_header(1, &FENS.join("\n"));
+        fs::write(progress_path(&output), format!("{}\n0 {}\n2", header, first.len() + 1)).unwrap();
+
+        let config = LabelConfig { depth: 1, threads: 2, chunk_size: 1 };
+        let summary = label_file(&input, &output, config).unwrap();
+
+        assert_eq!(summary, LabelSummary { labeled: 2, invalid: 1, skipped: 1 });
+        assert_eq!(sorted_lines(&output).len(), 3);
+        assert_eq!(fs::read_to_string(&output).unwrap().lines().next(), Some(first.as_str()));
+    }
+
+    #[test]
+    fn refuses_to_resume_other_inputs_or_chunk_sizes() {
+        let (input, output) = temp_paths("mismatch");
+        let progress = format!("{}\n0 0\n", progress_header(1, &FENS.join("\n")));
+
+        fs::write(progress_path(&output), &progress).unwrap();
+        let config = LabelConfig { depth: 1, threads: 1, chunk_size: 2 };
+        let error = label_file(&input, &output, config).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        fs::write(&input, FENS[..3].join("\n")).unwrap();
+        let config = LabelConfig { depth: 1, threads: 1, chunk_size: 1 };
+
+        assert!(label_file(&input, &output, config).is_err());
+        assert_eq!(fs::read_to_string(progress_path(&output)).unwrap(), progress);
+    }
+}
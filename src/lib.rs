@@ -0,0 +1,64 @@
+//! A bitboard based chess move generator. Position encodes a position on the board and
+//! generates the legal moves in it, Move describes a single move and the bitboards module
+//! contains the lookup tables and helpers for working with bitboards.
+
+// The explicit `return` style is used throughout the crate.
+#![allow(clippy::needless_return)]
+
+pub mod annotations;
+pub mod archive;
+pub mod batch;
+pub mod bench;
+pub mod bitboards;
+pub mod build_info;
+pub mod cli;
+pub mod clock;
+pub mod coach;
+pub mod coordination;
+pub mod correspondence;
+pub mod cpu;
+pub mod drivers;
+pub mod eval;
+pub mod explain;
+pub mod game;
+pub mod interrupt;
+pub mod king_safety;
+pub mod label;
+pub mod mcts;
+#[cfg(feature = "nnue")]
+pub mod nnue;
+pub mod nonblocking;
+pub mod position;
+pub mod moves;
+pub mod params;
+pub mod pawn_structure;
+pub mod perft;
+pub mod pgn;
+pub mod play;
+pub mod pocket;
+pub mod problem;
+pub mod proofgame;
+pub mod protocol;
+pub mod rating;
+pub mod recorder;
+pub mod repertoire;
+pub mod report;
+pub mod runner;
+pub mod search;
+pub mod stats;
+pub mod storage;
+pub mod strength;
+pub mod testsuite;
+pub mod training;
+pub mod tt;
+pub mod variant;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod wdl;
+pub mod zobrist;
+
+pub use crate::bitboards::bb_ops;
+pub use crate::game::Game;
+pub use crate::moves::Move;
+pub use crate::position::Position;
+pub use crate::variant::Variant;
@@ -0,0 +1,435 @@
+use std::mem;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::build_info;
+use crate::clock::Instant;
+use crate::eval;
+use crate::game::Game;
+use crate::moves::Move;
+use crate::position::Position;
+use crate::runner::{Engine, EngineMove};
+use crate::search::{self, Iteration, Limits, Score, SearchInfo, SearchLimits, MATE_BOUND, MATE_SCORE};
+use crate::training::Rng;
+
+
+/// The number of simulations the engine runs for a move unless the limits say otherwise.
+pub const DEFAULT_SIMULATIONS: u64 = 5000;
+
+/// Balances trying rarely visited moves against searching the best ones deeper (the
+/// constant c of UCT).
+const EXPLORATION: f64 = 1.4;
+
+/// Random playouts are cut off after this many plies and the position is evaluated.
+const PLAYOUT_PLIES: usize = 40;
+
+/// The centipawn difference that makes a win ten times as likely as a loss. Converts between
+/// evaluations and expected scores.
+const LOGISTIC_SCALE: f64 = 400.0;
+
+/// The number of simulations between two checks of the time limit and the stop flag.
+const CHECK_INTERVAL: u64 = 64;
+
+/// The time between two progress reports.
+const INFO_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The number of bytes the tree may use unless the limits give a memory budget.
+pub const DEFAULT_MEMORY: usize = 256 << 20;
+
+
+/// How the value of a new leaf of the tree is estimated.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Simulation {
+    /// Play random moves until the game ends or PLAYOUT_PLIES plies were played, then
+    /// evaluate the position statically.
+    Playout,
+    /// Evaluate the leaf with the quiescence search of the alpha-beta engine.
+    Evaluation
+}
+
+/// A position of the search tree. The value is the sum of the results of all simulations
+/// through the node from the point of view of the player who made the move leading to it.
+struct Node {
+    position: Position,
+    m: Option<Move>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    /// The legal moves that don't have a child yet.
+    untried: Vec<Move>,
+    /// The result of the game if it's over in the position, from the point of view of the
+    /// player to move.
+    terminal: Option<f64>,
+    visits: u64,
+    value: f64
+}
+
+impl Node {
+    fn new(position: Position, m: Option<Move>, parent: Option<usize>) -> Node {
+        let mut moves_position = position.clone();
+        let untried = moves_position.get_all_legal_moves();
+        let terminal = game_result(&mut moves_position, untried.is_empty());
+
+        return Node { position, m, parent, children: Vec::new(), untried, terminal, visits: 0, value: 0.0 };
+    }
+
+    /// Estimates the number of bytes the node occupies. The lists of moves only shrink and
+    /// the node gets at most one child per untried move, so this doesn't grow later.
+    fn memory_usage(&self) -> usize {
+        let per_move = mem::size_of::<Move>() + mem::size_of::<usize>();
+
+        return mem::size_of::<Node>() + self.position.memory_usage() + self.untried.capacity() * per_move;
+    }
+
+    /// The average result for the player who made the move leading to the node.
+    fn mean(&self) -> f64 {
+        return self.value / self.visits.max(1) as f64;
+    }
+}
+
+/// Returns the result for the player to move if the game is over: 0 for a loss and 0.5 for
+/// a draw. A player can't have won on their own turn.
+fn game_result(position: &mut Position, no_moves: bool) -> Option<f64> {
+    if position.is_variant_loss() {
+        return Some(0.0);
+    }
+
+    if no_moves {
+        return Some(if position.is_checked(position.turn) { 0.0 } else { 0.5 });
+    }
+
+    if position.is_threefold_repetition() {
+        return Some(0.5);
+    }
+
+    return None;
+}
+
+/// Converts a score in centipawns into an expected result between 0 and 1.
+fn expected_result(score: i32) -> f64 {
+    return 1.0 / (1.0 + 10f64.powf(-score as f64 / LOGISTIC_SCALE));
+}
+
+/// Converts an expected result back into centipawns. Certain results become mate scores.
+fn centipawns(result: f64) -> i32 {
+    let result = result.clamp(1e-6, 1.0 - 1e-6);
+    let score = LOGISTIC_SCALE * (result / (1.0 - result)).log10();
+
+    return (score.round() as i32).clamp(-MATE_BOUND + 1, MATE_BOUND - 1);
+}
+
+/// Searches the position with Monte Carlo tree search: each simulation walks down the tree
+/// choosing moves by UCT, adds one new position and estimates its value, which is then
+/// added to all positions on the way. The most visited move is returned together with the
+/// expected result converted to centipawns and the most visited line. The nodes are the
+/// number of simulations and the depth is the length of the line.
+///
+/// The search stops at the node, time and stop limits. Once the tree has used up the memory
+/// limit, or DEFAULT_MEMORY without one, no more positions are added and the simulations
+/// estimate the leaves they reach instead. The depth and the other options of the alpha-beta
+/// search are ignored, as are the excluded moves and searchmoves. Returns None if there are
+/// no legal moves.
+pub fn search(position: &Position, limits: &Limits, simulation: Simulation, rng: &mut Rng, on_info: &mut dyn FnMut(&SearchInfo)) -> Option<Iteration> {
+    let start = Instant::now();
+    let deadline = limits.time.map(|time| start + time);
+    let memory = limits.memory.unwrap_or(DEFAULT_MEMORY);
+    let mut nodes = vec![Node::new(position.clone(), None, None)];
+    let mut used = nodes[0].memory_usage();
+    let mut simulations = 0;
+    let mut reported = start;
+
+    if nodes[0].untried.is_empty() {
+        return None;
+    }
+
+    while limits.nodes.is_none_or(|max| simulations < max) {
+        if simulations % CHECK_INTERVAL == 0 && simulations > 0 {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) || limits.stop.as_ref().is_some_and(|stop| stop.load(Ordering::Relaxed)) {
+                break;
+            }
+
+            if reported.elapsed() >= INFO_INTERVAL {
+                on_info(&info(&nodes, start, simulations));
+                reported = Instant::now();
+            }
+        }
+
+        used += simulate(&mut nodes, used < memory, simulation, rng);
+        simulations += 1;
+    }
+
+    let pv = principal_variation(&nodes);
+    let best = *pv.first()?;
+    let score = match nodes[0].terminal {
+        Some(_) => 0,
+        None    => mate_or_centipawns(&nodes, nodes[0].children.iter().copied().max_by_key(|&child| nodes[child].visits)?)
+    };
+
+    on_info(&info(&nodes, start, simulations));
+
+    return Some(Iteration { depth: pv.len() as u8, best, score, nodes: simulations, pv });
+}
+
+/// Runs a single simulation: selection, expansion if it's allowed, simulation and
+/// backpropagation. Returns the number of bytes the new node uses.
+fn simulate(nodes: &mut Vec<Node>, expand: bool, simulation: Simulation, rng: &mut Rng) -> usize {
+    let mut added = 0;
+    let mut current = 0;
+
+    // Follow the most promising children while all moves of a position have been tried.
+    while nodes[current].untried.is_empty() && !nodes[current].children.is_empty() {
+        let log_visits = (nodes[current].visits as f64).ln();
+
+        current = *nodes[current].children.iter().max_by(|&&a, &&b| {
+            return uct(&nodes[a], log_visits).total_cmp(&uct(&nodes[b], log_visits));
+        }).unwrap();
+    }
+
+    if expand && nodes[current].terminal.is_none() && !nodes[current].untried.is_empty() {
+        let idx = rng.below(nodes[current].untried.len() as u64) as usize;
+        let m = nodes[current].untried.swap_remove(idx);
+        let child = Node::new(nodes[current].position.make_move(m), Some(m), Some(current));
+
+        added = child.memory_usage();
+        nodes.push(child);
+        let id = nodes.len() - 1;
+        nodes[current].children.push(id);
+        current = id;
+    }
+
+    // The result for the player to move in the new position, then for the one who moved.
+    let node = &nodes[current];
+    let mut result = 1.0 - node.terminal.unwrap_or_else(|| estimate(&node.position, simulation, rng));
+    let mut next = Some(current);
+
+    while let Some(idx) = next {
+        nodes[idx].visits += 1;
+        nodes[idx].value += result;
+        result = 1.0 - result;
+        next = nodes[idx].parent;
+    }
+
+    return added;
+}
+
+/// The upper confidence bound of a child's result that UCT maximizes.
+fn uct(node: &Node, log_parent_visits: f64) -> f64 {
+    if node.visits == 0 {
+        return f64::INFINITY;
+    }
+
+    return node.mean() + EXPLORATION * (log_parent_visits / node.visits as f64).sqrt();
+}
+
+/// Estimates the result for the player to move in a position in which the game isn't over.
+fn estimate(position: &Position, simulation: Simulation, rng: &mut Rng) -> f64 {
+    if simulation == Simulation::Evaluation {
+        return expected_result(search::quiescence(position, 0, -MATE_SCORE - 1, MATE_SCORE + 1));
+    }
+
+    let mut position = position.clone();
+    let mut flipped = false;
+
+    for _ in 0..PLAYOUT_PLIES {
+        let moves = position.get_all_legal_moves();
+
+        if let Some(result) = game_result(&mut position, moves.is_empty()) {
+            return if flipped { 1.0 - result } else { result };
+        }
+
+        position = position.make_move(moves[rng.below(moves.len() as u64) as usize]);
+        flipped = !flipped;
+    }
+
+    let result = expected_result(eval::evaluate(&position));
+
+    return if flipped { 1.0 - result } else { result };
+}
+
+/// Returns the line following the most visited child from the root on.
+fn principal_variation(nodes: &[Node]) -> Vec<Move> {
+    let mut pv = Vec::new();
+    let mut current = 0;
+
+    while let Some(&child) = nodes[current].children.iter().max_by_key(|&&child| nodes[child].visits) {
+        pv.push(nodes[child].m.unwrap());
+        current = child;
+    }
+
+    return pv;
+}
+
+/// Returns the score of the move leading to the child from the point of view of the player to
+/// move at the root. Moves that mate right away get a mate score.
+fn mate_or_centipawns(nodes: &[Node], child: usize) -> i32 {
+    return match nodes[child].terminal {
+        Some(0.0) => MATE_SCORE - 1,
+        _         => centipawns(nodes[child].mean())
+    };
+}
+
+fn info(nodes: &[Node], start: Instant, simulations: u64) -> SearchInfo {
+    let pv = principal_variation(nodes);
+    let time = start.elapsed();
+    let score = pv.first().map_or(0, |_| {
+        let best = nodes[0].children.iter().copied().max_by_key(|&child| nodes[child].visits).unwrap();
+
+        return mate_or_centipawns(nodes, best);
+    });
+
+    return SearchInfo {
+        depth: pv.len() as u8, seldepth: pv.len() as u8, nodes: simulations, nps: (simulations as u128 * 1000 / time.as_millis().max(1)) as u64,
+        score: Score::new(score), pv, time
+    };
+}
+
+/// An engine choosing its moves with Monte Carlo tree search instead of alpha-beta, sharing
+/// the move generation and evaluation with it.
+pub struct MctsEngine {
+    /// The number of simulations per move if the limits don't give a time or node count.
+    pub simulations: u64,
+    pub simulation: Simulation,
+    /// Setting the flag from another thread stops the running search, which then returns the
+    /// most visited move so far. Whoever starts a search has to clear it.
+    pub stop: Arc<AtomicBool>,
+    rng: Rng
+}
+
+impl MctsEngine {
+    pub fn new(simulation: Simulation) -> MctsEngine {
+        return MctsEngine { simulations: DEFAULT_SIMULATIONS, simulation, stop: Arc::new(AtomicBool::new(false)), rng: Rng::from_time() };
+    }
+
+    /// Makes the random choices of the search reproducible.
+    pub fn with_seed(mut self, seed: u64) -> MctsEngine {
+        self.rng = Rng::new(seed);
+        return self;
+    }
+}
+
+impl Engine for MctsEngine {
+    fn name(&self) -> String {
+        return format!("{} MCTS", build_info::engine_name());
+    }
+
+    fn version(&self) -> Option<String> {
+        return Some(format!("{} ({})", build_info::VERSION, build_info::GIT_COMMIT));
+    }
+
+    fn options(&self) -> Vec<(String, String)> {
+        return vec![(String::from("Simulations"), self.simulations.to_string()), (String::from("Simulation"), format!("{:?}", self.simulation))];
+    }
+
+    fn stop_flag(&self) -> Option<Arc<AtomicBool>> {
+        return Some(self.stop.clone());
+    }
+
+    fn choose_move(&mut self, game: &Game) -> Option<EngineMove> {
+        return self.choose_move_within(game, &SearchLimits::default());
+    }
+
+    fn choose_move_within(&mut self, game: &Game, limits: &SearchLimits) -> Option<EngineMove> {
+        return self.choose_move_reporting(game, limits, &mut |_| ());
+    }
+
+    fn choose_move_reporting(&mut self, game: &Game, limits: &SearchLimits, on_info: &mut dyn FnMut(&SearchInfo)) -> Option<EngineMove> {
+        let defaults = Limits { nodes: Some(self.simulations), stop: Some(self.stop.clone()), ..Limits::default() };
+        let mut limits = limits.apply(game.position().turn, defaults);
+
+        // A depth means nothing to the tree search, so it runs the default simulations.
+        if limits.nodes.is_none() && limits.time.is_none() && limits.depth.is_some() {
+            limits.nodes = Some(self.simulations);
+        }
+
+        let result = search(game.position(), &limits, self.simulation, &mut self.rng, on_info)?;
+
+        return Some(EngineMove { m: result.best, score: Some(result.score) });
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(fen: &str) -> Position {
+        return Position::from_fen(fen.to_string()).unwrap();
+    }
+
+    fn limits(simulations: u64) -> Limits {
+        return Limits { nodes: Some(simulations), ..Limits::default() };
+    }
+
+    #[test]
+    fn finds_mate_in_one_and_wins_material() {
+        let mate = position("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        let hanging = position("4k3/8/8/3q4/8/8/3R4/3K4 w - - 0 1");
+
+        for simulation in [Simulation::Evaluation, Simulation::Playout] {
+            let result = search(&mate, &limits(500), simulation, &mut Rng::new(7), &mut |_| ()).unwrap();
+
+            assert_eq!(mate.move_to_uci(result.best), "a1a8");
+            assert_eq!(result.score, MATE_SCORE - 1);
+        }
+
+        let result = search(&hanging, &limits(2000), Simulation::Evaluation, &mut Rng::new(7), &mut |_| ()).unwrap();
+
+        assert_eq!(hanging.move_to_uci(result.best), "d2d5");
+        assert!(result.score > 200);
+        assert_eq!(result.nodes, 2000);
+        assert_eq!(result.pv[0], result.best);
+    }
+
+    #[test]
+    fn stops_at_the_limits_and_without_moves() {
+        let stalemate = position("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        let stop = Arc::new(AtomicBool::new(true));
+        let stopped = Limits { stop: Some(stop), ..Limits::default() };
+        let mut infos = Vec::new();
+
+        assert!(search(&stalemate, &limits(10), Simulation::Evaluation, &mut Rng::new(1), &mut |_| ()).is_none());
+
+        // The stop flag is checked after the first simulations.
+        let result = search(&Position::starting_position(), &stopped, Simulation::Playout, &mut Rng::new(1), &mut |info| infos.push(info.clone())).unwrap();
+        assert_eq!(result.nodes, CHECK_INTERVAL);
+        assert_eq!(infos.last().map(|info| info.nodes), Some(CHECK_INTERVAL));
+    }
+
+    #[test]
+    fn stops_growing_the_tree_at_the_memory_limit() {
+        let root = Position::starting_position();
+        let node = Node::new(root.clone(), None, None).memory_usage();
+        let limits = Limits { nodes: Some(500), memory: Some(10 * node), ..Limits::default() };
+        let mut tree = vec![Node::new(root.clone(), None, None)];
+        let mut rng = Rng::new(5);
+
+        // Without room the simulation only estimates the root.
+        assert_eq!(simulate(&mut tree, false, Simulation::Evaluation, &mut rng), 0);
+        assert_eq!((tree.len(), tree[0].visits), (1, 1));
+        assert!(simulate(&mut tree, true, Simulation::Evaluation, &mut rng) > 0);
+        assert_eq!(tree.len(), 2);
+
+        let result = search(&root, &limits, Simulation::Evaluation, &mut rng, &mut |_| ()).unwrap();
+        assert_eq!(result.nodes, 500);
+        assert!(result.pv.len() <= 10);
+    }
+
+    #[test]
+    fn engine_plays_legal_moves_within_the_limits() {
+        let game = Game::from_position(position("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1"));
+        let mut engine = MctsEngine::new(Simulation::Evaluation).with_seed(3);
+
+        engine.simulations = 300;
+        assert_eq!(engine.choose_move(&game).map(|em| game.position().move_to_uci(em.m)), Some(String::from("a1a8")));
+        assert!(engine.choose_move_within(&Game::new(), &SearchLimits::depth(5)).is_some());
+        assert!(engine.name().ends_with(" MCTS"));
+    }
+
+    #[test]
+    fn converts_between_scores_and_results() {
+        assert_eq!(expected_result(0), 0.5);
+        assert!((expected_result(400) - 10.0 / 11.0).abs() < 1e-9);
+        assert_eq!(centipawns(expected_result(-250)), -250);
+        assert!(centipawns(1.0) < MATE_BOUND);
+    }
+}
@@ -1,15 +1,163 @@
+use std::fmt;
+
+use crate::bitboards::{bb_ops, geometry, pawns};
 use crate::position;
+use crate::position::{Position, PAWN, ROOK, KING, FILES};
 
-#[derive(Clone, Copy)]
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Move {
     StandardMove(u8, u8, u8, Option<u8>, Option<u8>, Option<u8>),
     ShortCastle,
-    LongCastle
+    LongCastle,
+    /// Drops a piece of the given type from the pocket onto the given square.
+    Drop(u8, u8)
+}
+
+
+impl Move {
+    /// Infers the move that transforms the position before into the position after. This
+    /// is needed by frontends that only observe the board (e.g. electronic boards) and
+    /// therefore have to reconstruct the moves from consecutive positions.
+    ///
+    /// Returns None if the difference between the positions can't be explained by a single
+    /// legal move of the player whose turn it is in the first position.
+    pub fn infer(before: &Position, after: &Position) -> Option<Move> {
+        let m = Move::infer_candidate(before, after)?;
+
+        // The piece placement alone doesn't show e.g. that a pinned piece moved or that the
+        // king stepped into check.
+        return Some(m).filter(|m| before.clone().get_all_legal_moves().contains(m));
+    }
+
+    /// Infers the move that explains the difference between the positions by the way the
+    /// pieces moved, without checking that it's legal.
+    fn infer_candidate(before: &Position, after: &Position) -> Option<Move> {
+        let color = before.turn;
+        let opponent = position::flip_color(color);
+
+        if after.turn != opponent {
+            return None;
+        }
+
+        let own_before = before.get_all_piece_bitboard(color);
+        let own_after = after.get_all_piece_bitboard(color);
+
+        // Squares the moving side left and squares where one of its pieces appeared
+        // or changed its type (promotion).
+        let vacated = bb_ops::idx_bitscan(own_before & !own_after);
+        let arrived: Vec<u8> = (0..64).filter(|idx| {
+            let piece_after = after.get_piece_at(color, *idx);
+
+            return piece_after.is_some() && piece_after != before.get_piece_at(color, *idx);
+        }).collect();
+
+        // The opponent can only lose pieces and never gain any.
+        let opp_before = before.get_all_piece_bitboard(opponent);
+        let opp_after = after.get_all_piece_bitboard(opponent);
+
+        if opp_after & !opp_before != 0 {
+            return None;
+        }
+
+        let opp_lost = opp_before & !opp_after;
+
+        // Castling is the only move that changes the squares of two pieces at once.
+        if vacated.len() == 2 && arrived.len() == 2 {
+            return Move::infer_castling(before, after, &vacated, &arrived)
+                .filter(|_| opp_lost == 0);
+        }
+
+        // A piece appearing out of nowhere was dropped from the pocket.
+        if vacated.is_empty() && arrived.len() == 1 && opp_lost == 0 {
+            let target = arrived[0];
+            let piece_type = after.get_piece_at(color, target)?;
+
+            return Some(Move::Drop(piece_type, target)).filter(|m| before.is_drop_possible(*m));
+        }
+
+        if vacated.len() != 1 || arrived.len() != 1 {
+            return None;
+        }
+
+        let origin = vacated[0];
+        let target = arrived[0];
+
+        let piece_type = before.get_piece_at(color, origin)?;
+        let tgt_type = after.get_piece_at(color, target)?;
+
+        // A piece can only change its type if it's a pawn promoting.
+        let promotes_to = if tgt_type != piece_type {
+            if piece_type != PAWN || !bb_ops::index_lookup(pawns::get_pawn_promotion_squares(color), target) {
+                return None;
+            }
+
+            Some(tgt_type)
+        } else {
+            None
+        };
+
+        let (origin_rk, origin_fl) = bb_ops::index_to_coords(origin);
+        let (target_rk, target_fl) = bb_ops::index_to_coords(target);
+
+        let mut captures = before.get_piece_at(opponent, target);
+        let mut captured_sq = captures.map(|_| target);
+
+        // A pawn moving diagonally to an empty square captures en passant.
+        if piece_type == PAWN && origin_fl != target_fl && captures.is_none() {
+            let ep_sq = bb_ops::coords_to_index(origin_rk, target_fl);
+
+            if before.get_piece_at(opponent, ep_sq) != Some(PAWN) {
+                return None;
+            }
+
+            captures = Some(PAWN);
+            captured_sq = Some(ep_sq);
+        }
+
+        // Exactly the captured piece has to vanish from the opponent's pieces.
+        if opp_lost != captured_sq.map_or(0, bb_ops::index_lookup_mask) {
+            return None;
+        }
+
+        // A pawn's double step allows capturing en passant on the skipped square.
+        let en_passant = if piece_type == PAWN && (origin_rk as i8 - target_rk as i8).abs() == 2 {
+            before.en_passant_after_double_step(origin, target)
+        } else {
+            None
+        };
+
+        return Some(Move::StandardMove(piece_type, origin, target, captures, promotes_to, en_passant));
+    }
+
+    /// Infers a castling move from the two squares the moving side left and the two squares
+    /// its pieces appeared on.
+    fn infer_castling(before: &Position, after: &Position, vacated: &[u8], arrived: &[u8]) -> Option<Move> {
+        let color = before.turn;
+        let back_rank = geometry::STANDARD.back_rank(color) * FILES;
+
+        let moved = |sq: u8, piece_type: u8, tgt: u8| -> bool {
+            return vacated.contains(&(back_rank + sq)) && arrived.contains(&(back_rank + tgt))
+                && before.get_piece_at(color, back_rank + sq) == Some(piece_type)
+                && after.get_piece_at(color, back_rank + tgt) == Some(piece_type);
+        };
+
+        if moved(4, KING, 6) && moved(7, ROOK, 5) {
+            return Some(Move::ShortCastle);
+        }
+
+        if moved(4, KING, 2) && moved(0, ROOK, 3) {
+            return Some(Move::LongCastle);
+        }
+
+        return None;
+    }
 }
 
 
-impl ToString for Move {
-    fn to_string(&self) -> String {
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Move::StandardMove(piece_type, origin, target, captures, promotes_to, _) => {
                 let origin = position::index_to_string(*origin).unwrap();
@@ -27,10 +175,79 @@ impl ToString for Move {
                     _       => String::from("")
                 };
 
-                return format!("{piece}{origin}{captures}{target}{promote}", piece=piece_type, origin=origin, captures=captures, target=target, promote=promotes_to);
+                return write!(f, "{piece}{origin}{captures}{target}{promote}", piece=piece_type, origin=origin, captures=captures, target=target, promote=promotes_to);
             },
-            Move::ShortCastle => String::from("O-O"),
-            Move::LongCastle => String::from("O-O-O")
+            Move::ShortCastle => write!(f, "O-O"),
+            Move::LongCastle => write!(f, "O-O-O"),
+            Move::Drop(piece_type, target) => write!(f, "{}", position::drop_to_string(*piece_type, *target))
         } 
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plays the move given in UCI or SAN notation and infers it back from the positions.
+    fn infer_played(fen: &str, m: &str) -> (Move, Option<Move>) {
+        let before = Position::from_fen(String::from(fen)).unwrap();
+        let m = before.uci_to_move(m).or_else(|| before.string_to_move(m)).unwrap();
+
+        return (m, Move::infer(&before, &before.make_move(m)));
+    }
+
+    #[test]
+    fn infers_played_moves() {
+        let fens_and_moves = [
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "g1f3"),
+            ("rnbqkbnr/pppp1ppp/8/4p3/3P4/8/PPP1PPPP/RNBQKBNR w KQkq - 0 2", "d4e5"),
+            ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "O-O"),
+            ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "O-O-O"),
+            ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2", "e5d6"),
+            ("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8n"),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "e2e4")
+        ];
+
+        for (fen, m) in fens_and_moves {
+            let (played, inferred) = infer_played(fen, m);
+
+            assert_eq!(inferred, Some(played), "{} in {}", m, fen);
+        }
+    }
+
+    /// Returns the position after moving the piece of the player to move from origin to
+    /// target without checking the move, like a board observing the pieces would see it.
+    fn moved_piece(before: &Position, piece_type: u8, origin: u8, target: u8) -> Position {
+        let mut piece_bbs = [0; 12];
+
+        for (idx, bb) in piece_bbs.iter_mut().enumerate() {
+            *bb = before.get_bitboard(idx);
+        }
+
+        piece_bbs[(before.turn * position::NUM_PIECE_TYPES + piece_type) as usize] ^= (1 << origin) | (1 << target);
+
+        return Position::new(position::flip_color(before.turn), piece_bbs, [false; 2], [false; 2], None);
+    }
+
+    #[test]
+    fn rejects_changes_that_are_no_legal_move() {
+        let before = Position::from_fen(String::from("4k3/8/8/8/8/8/R7/4K3 w - - 0 1")).unwrap();
+
+        // A rook moves along the file, not diagonally.
+        assert!(Move::infer(&before, &moved_piece(&before, ROOK, 8, 16)).is_some());
+        assert_eq!(Move::infer(&before, &moved_piece(&before, ROOK, 8, 17)), None);
+        assert_eq!(Move::infer(&before, &before), None);
+
+        // The bishop is pinned and the king can't step into check.
+        let pinned = Position::from_fen(String::from("4r1k1/8/8/8/8/8/4B3/4K3 w - - 0 1")).unwrap();
+
+        assert_eq!(Move::infer(&pinned, &moved_piece(&pinned, position::BISHOP, 12, 19)), None);
+        assert!(Move::infer(&pinned, &moved_piece(&pinned, KING, 4, 5)).is_some());
+        assert_eq!(Move::infer(&pinned, &moved_piece(&pinned, KING, 4, 3)), Some(Move::StandardMove(KING, 4, 3, None, None, None)));
+
+        let checked = Position::from_fen(String::from("3r2k1/8/8/8/8/8/4B3/4K3 w - - 0 1")).unwrap();
+
+        assert_eq!(Move::infer(&checked, &moved_piece(&checked, KING, 4, 3)), None);
+    }
+}
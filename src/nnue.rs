@@ -0,0 +1,228 @@
+//! Evaluation with an efficiently updatable neural network (NNUE). The network has one hidden
+//! layer per perspective: each of the 768 inputs stands for a piece of a color on a square, seen
+//! from the point of view of one player, and the hidden layer (the accumulator) is the sum of
+//! the weights of the pieces on the board. Positions carry their accumulator and make_move
+//! adds and removes the weights of the pieces that move, so evaluating only takes the small
+//! output layer. The loaded network is global to the process like the engine's options and
+//! positions without an accumulator are evaluated classically.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use lazy_static::lazy_static;
+
+use crate::position::{flip_color, NUM_PIECE_TYPES, WHITE, BLACK};
+use crate::search::MATE_BOUND;
+
+
+/// The number of inputs per perspective: a piece of each type and color on each square.
+pub const INPUTS: usize = 2 * NUM_PIECE_TYPES as usize * 64;
+
+/// The size of the hidden layer of each perspective.
+pub const HIDDEN: usize = 128;
+
+/// The size of a network file: the input weights input by input, the hidden biases, the output
+/// weights of the player to move and its opponent and the output bias, all as little-endian
+/// 16 bit integers.
+pub const FILE_SIZE: usize = 2 * (INPUTS * HIDDEN + HIDDEN + 2 * HIDDEN + 1);
+
+/// The quantization of the hidden layer, whose activations are clipped to 0..=QA, and of the
+/// output weights.
+const QA: i64 = 255;
+const QB: i64 = 64;
+
+/// Converts the output of the network to centipawns.
+const SCALE: i64 = 400;
+
+
+lazy_static! {
+    /// The network positions are evaluated with if one is loaded.
+    static ref NETWORK: RwLock<Option<Arc<Network>>> = RwLock::new(None);
+}
+
+
+/// The quantized weights of a network.
+pub struct Network {
+    input_weights: Vec<[i16; HIDDEN]>,
+    hidden_biases: [i16; HIDDEN],
+    /// The weights of the hidden layer of the player to move and of its opponent.
+    output_weights: [[i16; HIDDEN]; 2],
+    output_bias: i16
+}
+
+impl Network {
+    /// Reads a network from the contents of a network file, see FILE_SIZE.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Network> {
+        if bytes.len() != FILE_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Expected a network of {} bytes, got {}", FILE_SIZE, bytes.len())));
+        }
+
+        let mut values = bytes.chunks_exact(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]]));
+        let mut layer = || {
+            let mut weights = [0; HIDDEN];
+            weights.iter_mut().zip(&mut values).for_each(|(weight, value)| *weight = value);
+
+            return weights;
+        };
+
+        let input_weights = (0..INPUTS).map(|_| layer()).collect();
+        let hidden_biases = layer();
+        let output_weights = [layer(), layer()];
+
+        return Ok(Network { input_weights, hidden_biases, output_weights, output_bias: values.next().unwrap_or(0) });
+    }
+
+    /// Loads a network from a network file.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Network> {
+        return Network::from_bytes(&fs::read(path)?);
+    }
+}
+
+/// The hidden layers of both perspectives for the pieces on the board.
+#[derive(Clone)]
+pub struct Accumulator {
+    network: Arc<Network>,
+    /// The hidden layers from white's and black's point of view.
+    values: [[i16; HIDDEN]; 2]
+}
+
+impl Accumulator {
+    /// The accumulator of an empty board.
+    pub fn new(network: Arc<Network>) -> Accumulator {
+        let values = [network.hidden_biases; 2];
+
+        return Accumulator { network, values };
+    }
+
+    /// Adds the weights of a piece put onto the square.
+    pub fn add(&mut self, color: u8, piece_type: u8, sq: u8) {
+        self.update(color, piece_type, sq, i16::wrapping_add);
+    }
+
+    /// Subtracts the weights of a piece taken from the square.
+    pub fn remove(&mut self, color: u8, piece_type: u8, sq: u8) {
+        self.update(color, piece_type, sq, i16::wrapping_sub);
+    }
+
+    /// The values wrap around instead of overflowing, so the order of the updates doesn't
+    /// matter and the accumulator always equals one computed from scratch.
+    fn update(&mut self, color: u8, piece_type: u8, sq: u8, op: fn(i16, i16) -> i16) {
+        for perspective in [WHITE, BLACK] {
+            let weights = &self.network.input_weights[input(perspective, color, piece_type, sq)];
+
+            for (value, weight) in self.values[perspective as usize].iter_mut().zip(weights) {
+                *value = op(*value, *weight);
+            }
+        }
+    }
+
+    /// Evaluates the position from the point of view of the player whose turn it is, in
+    /// centipawns below the range of mate scores.
+    pub fn evaluate(&self, turn: u8) -> i32 {
+        let perspectives = [&self.values[turn as usize], &self.values[flip_color(turn) as usize]];
+        let mut output = self.network.output_bias as i64;
+
+        for (values, weights) in perspectives.iter().zip(&self.network.output_weights) {
+            output += values.iter().zip(weights).map(|(&value, &weight)| (value as i64).clamp(0, QA) * weight as i64).sum::<i64>();
+        }
+
+        // A network must not claim mates, which the search alone proves.
+        let bound = (MATE_BOUND - 1) as i64;
+
+        return (output * SCALE / (QA * QB)).clamp(-bound, bound) as i32;
+    }
+}
+
+/// Returns the input standing for the piece on the square from the point of view of the
+/// given player. The board is flipped for black, so both players see their own pieces first
+/// and on the first ranks.
+fn input(perspective: u8, color: u8, piece_type: u8, sq: u8) -> usize {
+    let side = if color == perspective { 0 } else { 1 };
+    let sq = if perspective == WHITE { sq } else { sq ^ 56 };
+
+    return (side * NUM_PIECE_TYPES as usize + piece_type as usize) * 64 + sq as usize;
+}
+
+/// Sets the network positions are evaluated with. Without one the classical evaluation is
+/// used.
+pub fn set_network(network: Option<Network>) {
+    *NETWORK.write().unwrap() = network.map(Arc::new);
+}
+
+/// Returns the loaded network.
+pub fn network() -> Option<Arc<Network>> {
+    return NETWORK.read().unwrap().clone();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval;
+    use crate::position::Position;
+    use crate::training::Rng;
+
+    /// Returns a network with small random weights.
+    fn random_network(seed: u64) -> Network {
+        let mut rng = Rng::new(seed);
+        let bytes: Vec<u8> = (0..FILE_SIZE / 2).flat_map(|_| ((rng.next_u64() % 64) as i16 - 32).to_le_bytes()).collect();
+
+        return Network::from_bytes(&bytes).unwrap();
+    }
+
+    #[test]
+    fn updates_the_accumulator_incrementally() {
+        let network = Arc::new(random_network(3));
+        let mut position = Position::from_fen(String::from("r3k2r/1P6/8/8/3pP3/8/8/R3K2R b KQkq e3 0 1")).unwrap().with_network(Some(network.clone()));
+
+        // En passant, castling and a capturing promotion.
+        for m in ["d4e3", "O-O", "e3e2", "b7a8q", "e8d7", "a8h8"] {
+            position = position.make_move(position.uci_to_move(m).or_else(|| position.string_to_move(m)).unwrap());
+            let refreshed = position.with_network(Some(network.clone()));
+
+            assert_eq!(position.nnue_evaluate(), refreshed.nnue_evaluate(), "{}", m);
+        }
+
+        assert!(position.nnue_evaluate().is_some());
+        assert_eq!(eval::evaluate(&position), position.nnue_evaluate().unwrap());
+    }
+
+    #[test]
+    fn evaluates_both_colors_alike() {
+        let network = Arc::new(random_network(7));
+        let position = Position::from_fen(String::from("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4")).unwrap();
+
+        let score = position.with_network(Some(network.clone())).nnue_evaluate();
+        assert_eq!(score, position.mirror().with_network(Some(network.clone())).nnue_evaluate());
+
+        // Without a network and in variants the classical evaluation is used.
+        assert_eq!(position.with_network(None).nnue_evaluate(), None);
+        assert_eq!(Position::variant_starting_position(crate::variant::Variant::Crazyhouse).with_network(Some(network)).nnue_evaluate(), None);
+    }
+
+    #[test]
+    fn rejects_files_of_the_wrong_size() {
+        assert!(Network::from_bytes(&[0; 100]).is_err());
+        assert!(Network::load("/nonexistent/network.nnue").is_err());
+
+        let network = Network::from_bytes(&vec![0; FILE_SIZE]).unwrap();
+        assert_eq!(Accumulator::new(Arc::new(network)).evaluate(WHITE), 0);
+    }
+
+    #[test]
+    fn keeps_scores_out_of_the_mate_range() {
+        let mut bytes = vec![0x7f; FILE_SIZE];
+        let network = Network::from_bytes(&bytes).unwrap();
+
+        assert_eq!(Accumulator::new(Arc::new(network)).evaluate(WHITE), MATE_BOUND - 1);
+
+        // Negative output weights.
+        let start = 2 * (INPUTS * HIDDEN + HIDDEN);
+        bytes[start..start + 4 * HIDDEN].fill(0x80);
+        let network = Network::from_bytes(&bytes).unwrap();
+
+        assert_eq!(Accumulator::new(Arc::new(network)).evaluate(BLACK), -(MATE_BOUND - 1));
+    }
+}
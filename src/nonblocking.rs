@@ -0,0 +1,330 @@
+//! Async wrappers around the search and external UCI engines, so services running on an async
+//! runtime can use them without blocking their executor. The blocking work runs on its own
+//! thread and is exposed through std futures, so the wrappers work with any runtime.
+//!
+//! Analysis implements the Stream trait of futures-core with the stream feature. Without it,
+//! its poll_next has the signature of a stream, so it can be adapted to the Stream trait of the
+//! runtime in use with a one-line wrapper.
+
+use std::collections::VecDeque;
+use std::future::{self, Future};
+use std::io;
+use std::pin::Pin;
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use crate::game::Game;
+use crate::position::Position;
+use crate::runner::{Engine, EngineMove};
+use crate::runner::uci_engine::{UciEngine, UciEngineSpec, UciLimit};
+use crate::search::{self, Limits, SearchInfo};
+use crate::tt::TranspositionTable;
+
+
+/// The state shared by a producing thread and the future or stream waiting for its values.
+struct Channel<T> {
+    values: VecDeque<T>,
+    closed: bool,
+    waker: Option<Waker>
+}
+
+type Shared<T> = Arc<Mutex<Channel<T>>>;
+
+fn channel<T>() -> Shared<T> {
+    return Arc::new(Mutex::new(Channel { values: VecDeque::new(), closed: false, waker: None }));
+}
+
+fn push<T>(shared: &Shared<T>, value: T) {
+    let mut channel = shared.lock().unwrap();
+    channel.values.push_back(value);
+
+    if let Some(waker) = channel.waker.take() {
+        waker.wake();
+    }
+}
+
+fn close<T>(shared: &Shared<T>) {
+    let mut channel = shared.lock().unwrap();
+    channel.closed = true;
+
+    if let Some(waker) = channel.waker.take() {
+        waker.wake();
+    }
+}
+
+/// Returns the next value, None once the channel is closed and empty or Pending after
+/// registering the waker of the context.
+fn poll_channel<T>(shared: &Shared<T>, cx: &mut Context) -> Poll<Option<T>> {
+    let mut channel = shared.lock().unwrap();
+
+    if let Some(value) = channel.values.pop_front() {
+        return Poll::Ready(Some(value));
+    }
+
+    if channel.closed {
+        return Poll::Ready(None);
+    }
+
+    channel.waker = Some(cx.waker().clone());
+
+    return Poll::Pending;
+}
+
+/// Closes the channel when dropped, so the waiting side is woken even if the producing thread
+/// panics.
+struct Closer<T>(Shared<T>);
+
+impl<T> Drop for Closer<T> {
+    fn drop(&mut self) {
+        close(&self.0);
+    }
+}
+
+/// Waits for a single value of a channel, resolving to None if it's closed without one.
+struct Receive<T> {
+    shared: Shared<T>
+}
+
+impl<T> Future for Receive<T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<T>> {
+        return poll_channel(&self.shared, cx);
+    }
+}
+
+/// An iterative deepening search running on its own thread. It yields the progress of the
+/// search like the info lines of UCI, after every completed iteration and periodically during
+/// long ones, and ends after the last iteration. Dropping it stops the search.
+pub struct Analysis {
+    shared: Shared<SearchInfo>,
+    stop: Arc<AtomicBool>
+}
+
+/// Starts searching the position with the given limits and returns the stream of its
+/// progress, see search::iterative_deepening_with_info. The stop flag of the limits is used if
+/// it's given.
+pub fn analyze(position: Position, mut limits: Limits) -> Analysis {
+    let stop = limits.stop.get_or_insert_with(|| Arc::new(AtomicBool::new(false))).clone();
+    let shared = channel();
+    let producer = shared.clone();
+
+    thread::spawn(move || {
+        let closer = Closer(producer.clone());
+        let mut tt = TranspositionTable::default();
+
+        search::iterative_deepening_with_info(&position, &limits, &mut tt, |info| push(&producer, info.clone()));
+        drop(closer);
+    });
+
+    return Analysis { shared, stop };
+}
+
+impl Analysis {
+    /// Polls for the next progress report. Returns None once the search has ended.
+    pub fn poll_next(&mut self, cx: &mut Context) -> Poll<Option<SearchInfo>> {
+        return poll_channel(&self.shared, cx);
+    }
+
+    /// Waits for the next progress report. Returns None once the search has ended.
+    pub async fn next(&mut self) -> Option<SearchInfo> {
+        return future::poll_fn(|cx| self.poll_next(cx)).await;
+    }
+
+    /// Asks the search to stop. Reports made before are still yielded.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(feature = "stream")]
+impl futures_core::Stream for Analysis {
+    type Item = SearchInfo;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<SearchInfo>> {
+        return poll_channel(&self.shared, cx);
+    }
+}
+
+impl Drop for Analysis {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+type Job = Box<dyn FnOnce(&mut UciEngine) + Send>;
+
+/// An external UCI engine driven from async code. The engine runs on its own thread that
+/// handles the requests one after another.
+pub struct AsyncUciEngine {
+    jobs: mpsc::Sender<Job>
+}
+
+impl AsyncUciEngine {
+    /// Starts the engine described by the spec and initializes the UCI protocol.
+    pub async fn start(spec: UciEngineSpec, limit: UciLimit) -> io::Result<AsyncUciEngine> {
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let shared = channel();
+        let producer = shared.clone();
+
+        thread::spawn(move || {
+            let closer = Closer(producer.clone());
+
+            let mut engine = match UciEngine::start(&spec, limit) {
+                Ok(engine) => engine,
+                Err(e)     => return push(&producer, Err(e))
+            };
+
+            push(&producer, Ok(()));
+            drop(closer);
+
+            for job in receiver {
+                job(&mut engine);
+            }
+        });
+
+        return match (Receive { shared }).await {
+            Some(Ok(()))  => Ok(AsyncUciEngine { jobs }),
+            Some(Err(e))  => Err(e),
+            None          => Err(stopped())
+        };
+    }
+
+    /// Runs the function with the engine on its thread.
+    async fn run<T: Send + 'static, F: FnOnce(&mut UciEngine) -> T + Send + 'static>(&self, f: F) -> io::Result<T> {
+        let shared = channel();
+        let producer = shared.clone();
+
+        let job: Job = Box::new(move |engine| {
+            let closer = Closer(producer.clone());
+            push(&producer, f(engine));
+            drop(closer);
+        });
+
+        if self.jobs.send(job).is_err() {
+            return Err(stopped());
+        }
+
+        return match (Receive { shared }).await {
+            Some(value) => Ok(value),
+            None        => Err(stopped())
+        };
+    }
+
+    /// Returns the name the engine reported for itself.
+    pub async fn version(&self) -> io::Result<Option<String>> {
+        return self.run(|engine| engine.version()).await;
+    }
+
+    /// Tells the engine that a new game starts.
+    pub async fn new_game(&self) -> io::Result<()> {
+        return self.run(|engine| engine.new_game()).await;
+    }
+
+    /// Searches the current position of the game and returns the engine's move. Returns None
+    /// if the engine failed to produce a legal move.
+    pub async fn choose_move(&self, game: Game) -> io::Result<Option<EngineMove>> {
+        return self.run(move |engine| engine.choose_move(&game)).await;
+    }
+}
+
+fn stopped() -> io::Error {
+    return io::Error::new(io::ErrorKind::BrokenPipe, "The engine has stopped");
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::task::Wake;
+    use std::thread::Thread;
+
+    /// Wakes a thread blocked in block_on.
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// A minimal executor running a single future to completion on the current thread.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending       => thread::park()
+            }
+        }
+    }
+
+    #[test]
+    fn analysis_streams_the_search_info() {
+        let position = Position::from_fen(String::from("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1")).unwrap();
+        let limits = Limits { depth: Some(3), ..Limits::default() };
+
+        let mut tt = TranspositionTable::default();
+        let expected = search::iterative_deepening_with_info(&position, &limits, &mut tt, |_| ()).unwrap();
+
+        let streamed = block_on(async {
+            let mut analysis = analyze(position.clone(), limits);
+            let mut streamed = Vec::new();
+
+            while let Some(info) = analysis.next().await {
+                streamed.push(info);
+            }
+
+            streamed
+        });
+
+        let last = streamed.last().unwrap();
+
+        assert_eq!(streamed.iter().map(|info| info.depth).max(), Some(3));
+        assert_eq!((last.pv[0], last.score), (expected.best, search::Score::new(expected.score)));
+
+        // Stopped searches end after the iteration they're in.
+        let first = block_on(async {
+            let mut analysis = analyze(position, Limits::default());
+            let first = analysis.next().await;
+            analysis.stop();
+
+            while analysis.next().await.is_some() {}
+
+            first
+        });
+
+        assert_eq!(first.map(|info| info.depth), Some(1));
+    }
+
+    #[cfg(feature = "stream")]
+    #[test]
+    fn analysis_is_a_stream() {
+        use futures_core::Stream;
+
+        let mut analysis = analyze(Position::starting_position(), Limits { depth: Some(2), ..Limits::default() });
+        let depths = block_on(async {
+            let mut depths = Vec::new();
+
+            while let Some(info) = future::poll_fn(|cx| Pin::new(&mut analysis).poll_next(cx)).await {
+                depths.push(info.depth);
+            }
+
+            depths
+        });
+
+        assert_eq!(depths.last(), Some(&2));
+    }
+
+    #[test]
+    fn reports_engines_that_fail_to_start() {
+        let spec = UciEngineSpec { name: String::from("missing"), command: String::from("./no-such-engine"), args: Vec::new(), options: Vec::new() };
+
+        assert!(block_on(AsyncUciEngine::start(spec, UciLimit::Depth(1))).is_err());
+    }
+}
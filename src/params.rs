@@ -0,0 +1,152 @@
+//! The registry of the tunable evaluation parameters. Each parameter has a name, a default and
+//! a range, so front-ends can expose them as options (e.g. UCI spin options) and tuning runs
+//! can set them by name. The values are global to the process like the engine's options.
+
+use std::sync::atomic::{AtomicI32, Ordering};
+
+
+/// A tunable parameter and the range of values it accepts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Parameter {
+    pub name: &'static str,
+    pub default: i32,
+    pub min: i32,
+    pub max: i32
+}
+
+/// The bonus for having both bishops.
+pub const BISHOP_PAIR: usize = 0;
+/// The bonus per knight for each own pawn more than five. Knights gain in closed positions.
+pub const KNIGHT_PAWNS: usize = 1;
+/// The penalty per rook for each own pawn more than five. Rooks need open files.
+pub const ROOK_PAWNS: usize = 2;
+/// The penalty for a second rook, which duplicates what the first one does.
+pub const ROOK_REDUNDANCY: usize = 3;
+/// The penalty per rook next to a queen, which covers the same lines.
+pub const QUEEN_REDUNDANCY: usize = 4;
+
+/// The penalty per doubled pawn.
+pub const DOUBLED_PAWN: usize = 5;
+/// The extra penalty per doubled pawn on a file without enemy pawns.
+pub const DOUBLED_HALF_OPEN: usize = 6;
+/// The penalty per backward pawn.
+pub const BACKWARD_PAWN: usize = 7;
+/// The extra penalty per backward pawn on a file without enemy pawns.
+pub const BACKWARD_HALF_OPEN: usize = 8;
+
+/// The bonus per passed pawn for each rank it has advanced.
+pub const PASSED_PAWN: usize = 9;
+/// The bonus per candidate passed pawn.
+pub const CANDIDATE_PASSER: usize = 10;
+/// The bonus per wing on which the player has more pawns than the opponent.
+pub const PAWN_MAJORITY: usize = 11;
+
+/// The penalty per file in front of a castled king without an own pawn.
+pub const SHIELD_MISSING: usize = 12;
+/// The penalty per shield pawn that has advanced two ranks in front of the king. Pawns further
+/// away cost twice as much.
+pub const SHIELD_ADVANCED: usize = 13;
+/// The bonus per storming pawn for each rank it has come closer to the enemy king.
+pub const PAWN_STORM: usize = 14;
+
+/// The bonus per file on which the player has doubled its rooks.
+pub const DOUBLED_ROOKS: usize = 15;
+/// The bonus per battery of the queen with a bishop.
+pub const QUEEN_BISHOP_BATTERY: usize = 16;
+/// The bonus per battery of the queen with a rook.
+pub const QUEEN_ROOK_BATTERY: usize = 17;
+
+/// The penalty per isolated pawn.
+pub const ISOLATED_PAWN: usize = 18;
+/// The extra penalty per isolated pawn on a file without enemy pawns.
+pub const ISOLATED_HALF_OPEN: usize = 19;
+
+/// All parameters, indexed by the constants above.
+pub const PARAMETERS: [Parameter; 20] = [
+    Parameter { name: "BishopPair",       default: 50, min: 0, max: 200 },
+    Parameter { name: "KnightPawns",      default: 6,  min: 0, max: 50 },
+    Parameter { name: "RookPawns",        default: 12, min: 0, max: 50 },
+    Parameter { name: "RookRedundancy",   default: 16, min: 0, max: 200 },
+    Parameter { name: "QueenRedundancy",  default: 8,  min: 0, max: 200 },
+    Parameter { name: "DoubledPawn",      default: 12, min: 0, max: 100 },
+    Parameter { name: "DoubledHalfOpen",  default: 8,  min: 0, max: 100 },
+    Parameter { name: "BackwardPawn",     default: 10, min: 0, max: 100 },
+    Parameter { name: "BackwardHalfOpen", default: 10, min: 0, max: 100 },
+    Parameter { name: "PassedPawn",       default: 10, min: 0, max: 100 },
+    Parameter { name: "CandidatePasser",  default: 12, min: 0, max: 100 },
+    Parameter { name: "PawnMajority",     default: 8,  min: 0, max: 100 },
+    Parameter { name: "ShieldMissing",    default: 25, min: 0, max: 100 },
+    Parameter { name: "ShieldAdvanced",   default: 10, min: 0, max: 100 },
+    Parameter { name: "PawnStorm",        default: 5,  min: 0, max: 50 },
+    Parameter { name: "DoubledRooks",     default: 20, min: 0, max: 100 },
+    Parameter { name: "QueenBishopBattery", default: 12, min: 0, max: 100 },
+    Parameter { name: "QueenRookBattery", default: 10, min: 0, max: 100 },
+    Parameter { name: "IsolatedPawn",     default: 10, min: 0, max: 100 },
+    Parameter { name: "IsolatedHalfOpen", default: 8,  min: 0, max: 100 }
+];
+
+static VALUES: [AtomicI32; 20] = [
+    AtomicI32::new(PARAMETERS[0].default),
+    AtomicI32::new(PARAMETERS[1].default),
+    AtomicI32::new(PARAMETERS[2].default),
+    AtomicI32::new(PARAMETERS[3].default),
+    AtomicI32::new(PARAMETERS[4].default),
+    AtomicI32::new(PARAMETERS[5].default),
+    AtomicI32::new(PARAMETERS[6].default),
+    AtomicI32::new(PARAMETERS[7].default),
+    AtomicI32::new(PARAMETERS[8].default),
+    AtomicI32::new(PARAMETERS[9].default),
+    AtomicI32::new(PARAMETERS[10].default),
+    AtomicI32::new(PARAMETERS[11].default),
+    AtomicI32::new(PARAMETERS[12].default),
+    AtomicI32::new(PARAMETERS[13].default),
+    AtomicI32::new(PARAMETERS[14].default),
+    AtomicI32::new(PARAMETERS[15].default),
+    AtomicI32::new(PARAMETERS[16].default),
+    AtomicI32::new(PARAMETERS[17].default),
+    AtomicI32::new(PARAMETERS[18].default),
+    AtomicI32::new(PARAMETERS[19].default)
+];
+
+/// Returns the current value of the parameter with the given index.
+#[inline(always)]
+pub fn get(idx: usize) -> i32 {
+    return VALUES[idx].load(Ordering::Relaxed);
+}
+
+/// Sets the parameter with the given name (ignoring case). Returns false if there is no such
+/// parameter or the value is out of its range.
+pub fn set(name: &str, value: i32) -> bool {
+    return match PARAMETERS.iter().position(|p| p.name.eq_ignore_ascii_case(name)) {
+        Some(idx) if (PARAMETERS[idx].min..=PARAMETERS[idx].max).contains(&value) => {
+            VALUES[idx].store(value, Ordering::Relaxed);
+            true
+        },
+        _ => false
+    };
+}
+
+/// Returns the parameters together with their current values.
+pub fn values() -> Vec<(Parameter, i32)> {
+    return PARAMETERS.iter().enumerate().map(|(idx, p)| (*p, get(idx))).collect();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sets_parameters_by_name_within_their_range() {
+        // Other tests evaluate positions concurrently, so the values are only set to their
+        // defaults here.
+        assert!(set("bishoppair", PARAMETERS[BISHOP_PAIR].default));
+        assert_eq!(get(BISHOP_PAIR), PARAMETERS[BISHOP_PAIR].default);
+
+        assert!(!set("BishopPair", -1));
+        assert!(!set("RookPawns", 51));
+        assert!(!set("Unknown", 0));
+
+        assert_eq!(values().len(), PARAMETERS.len());
+    }
+}
@@ -0,0 +1,356 @@
+use std::mem;
+
+use crate::bitboards::bb_ops;
+use crate::bitboards::geometry::{FILES, RANKS};
+use crate::bitboards::pawns::{self, adjacent_files, attack_span, file_mask, front_span, passed_span, ranks_behind};
+use crate::params::{self, DOUBLED_PAWN, DOUBLED_HALF_OPEN, ISOLATED_PAWN, ISOLATED_HALF_OPEN, BACKWARD_PAWN, BACKWARD_HALF_OPEN, PASSED_PAWN, CANDIDATE_PASSER, PAWN_MAJORITY};
+use crate::position::{flip_color, Position, PAWN, WHITE, BLACK};
+
+
+/// The number of entries of the pawn hash table of a search.
+pub const PAWN_TABLE_ENTRIES: usize = 1 << 12;
+
+
+/// One half of the board. The queenside holds the files a to d on the regular board, the
+/// kingside the files e to h.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Wing {
+    Queenside,
+    Kingside
+}
+
+
+/// Checks if the given player has no pawn on the file, so it's (half-)open for the player's
+/// rooks. Enemy pawns on such a file are exposed to rooks attacking from the front.
+pub fn is_half_open(position: &Position, color: u8, fl: u8) -> bool {
+    return position.get_piece_bitboard(color, PAWN) & file_mask(fl) == 0;
+}
+
+/// Returns the pawns of the given player that stand behind another of their pawns on the same
+/// file. A file with n pawns has n - 1 doubled pawns.
+pub fn doubled_pawns(position: &Position, color: u8) -> u64 {
+    let own = position.get_piece_bitboard(color, PAWN);
+    let mut doubled = 0;
+
+    for sq in bb_ops::idx_bitscan(own) {
+        if own & front_span(color, sq) != 0 {
+            doubled |= bb_ops::index_lookup_mask(sq);
+        }
+    }
+
+    return doubled;
+}
+
+/// Returns the isolated pawns of the given player: pawns without own pawns on the adjacent
+/// files, which no pawn can ever defend.
+pub fn isolated_pawns(position: &Position, color: u8) -> u64 {
+    let own = position.get_piece_bitboard(color, PAWN);
+
+    return bb_ops::idx_bitscan(own).into_iter()
+        .filter(|&sq| own & adjacent_files(sq % FILES) == 0)
+        .fold(0, |isolated, sq| isolated | bb_ops::index_lookup_mask(sq));
+}
+
+/// Returns the backward pawns of the given player: pawns that no pawn on an adjacent file can
+/// support because all of them are further advanced, and whose stop square is attacked by an
+/// enemy pawn, so they can't advance safely either. Pawns without any neighbours are isolated
+/// rather than backward.
+pub fn backward_pawns(position: &Position, color: u8) -> u64 {
+    let own = position.get_piece_bitboard(color, PAWN);
+    let enemy = position.get_piece_bitboard(flip_color(color), PAWN);
+    let mut backward = 0;
+
+    for sq in bb_ops::idx_bitscan(own) {
+        let (rk, fl) = (sq / FILES, sq % FILES);
+        let neighbours = own & adjacent_files(fl);
+
+        if neighbours == 0 || neighbours & ranks_behind(color, rk) != 0 {
+            continue;
+        }
+
+        let stop = match color {
+            WHITE if rk + 1 < RANKS => sq + FILES,
+            WHITE                   => continue,
+            _ if rk > 0             => sq - FILES,
+            _                       => continue
+        };
+
+        if enemy & pawns::get_pawn_captures(color, stop) != 0 {
+            backward |= bb_ops::index_lookup_mask(sq);
+        }
+    }
+
+    return backward;
+}
+
+/// Returns the passed pawns of the given player: pawns that no enemy pawn can stop, because
+/// there is none ahead of them on their file or the adjacent files.
+pub fn passed_pawns(position: &Position, color: u8) -> u64 {
+    let own = position.get_piece_bitboard(color, PAWN);
+    let enemy = position.get_piece_bitboard(flip_color(color), PAWN);
+
+    return bb_ops::idx_bitscan(own).into_iter()
+        .filter(|&sq| enemy & passed_span(color, sq) == 0)
+        .fold(0, |passed, sq| passed | bb_ops::index_lookup_mask(sq));
+}
+
+/// Returns the candidate passed pawns of the given player: pawns that aren't passed yet, but
+/// have no enemy pawn in front of them on their file and at least as many own pawns on the
+/// adjacent files next to or behind them as there are enemy pawns in front of them on those
+/// files. Exchanging the pawns turns them into passed pawns.
+pub fn candidate_passers(position: &Position, color: u8) -> u64 {
+    let own = position.get_piece_bitboard(color, PAWN);
+    let enemy = position.get_piece_bitboard(flip_color(color), PAWN);
+    let mut candidates = 0;
+
+    for sq in bb_ops::idx_bitscan(own & !passed_pawns(position, color)) {
+        if enemy & front_span(color, sq) != 0 {
+            continue;
+        }
+
+        let helpers = own & adjacent_files(sq % FILES) & ranks_behind(color, sq / FILES);
+        let sentries = enemy & attack_span(color, sq);
+
+        if helpers.count_ones() >= sentries.count_ones() {
+            candidates |= bb_ops::index_lookup_mask(sq);
+        }
+    }
+
+    return candidates;
+}
+
+/// Returns the wings on which the given player has more pawns than the opponent. A majority
+/// can create a passed pawn by advancing.
+pub fn majorities(position: &Position, color: u8) -> Vec<Wing> {
+    let files = position.variant().geometry().files;
+    let queenside = (0..files / 2).fold(0, |mask, fl| mask | file_mask(fl));
+    let own = position.get_piece_bitboard(color, PAWN);
+    let enemy = position.get_piece_bitboard(flip_color(color), PAWN);
+
+    return [(Wing::Queenside, queenside), (Wing::Kingside, !queenside)].iter()
+        .filter(|(_, mask)| (own & mask).count_ones() > (enemy & mask).count_ones())
+        .map(|(wing, _)| *wing)
+        .collect();
+}
+
+/// Returns the number of ranks the pawn on the given square has advanced from its starting
+/// rank.
+fn advancement(position: &Position, color: u8, sq: u8) -> i32 {
+    let rk = (sq / FILES) as i32;
+
+    return if color == WHITE { rk - 1 } else { position.variant().geometry().ranks as i32 - 2 - rk };
+}
+
+/// Evaluates the pawn structure of the given player in centipawns. Doubled, isolated and
+/// backward pawns are penalized, more so if they stand on a file the opponent has no pawn on,
+/// where rooks can attack them from the front. Passed pawns gain with each rank they advance and candidate
+/// passers and pawn majorities get a bonus. The weights are taken from the parameter registry.
+pub fn evaluate(position: &Position, color: u8) -> i32 {
+    let opponent = flip_color(color);
+    let mut score = 0;
+
+    for sq in bb_ops::idx_bitscan(doubled_pawns(position, color)) {
+        score -= params::get(DOUBLED_PAWN);
+
+        if is_half_open(position, opponent, sq % FILES) {
+            score -= params::get(DOUBLED_HALF_OPEN);
+        }
+    }
+
+    for sq in bb_ops::idx_bitscan(isolated_pawns(position, color)) {
+        score -= params::get(ISOLATED_PAWN);
+
+        if is_half_open(position, opponent, sq % FILES) {
+            score -= params::get(ISOLATED_HALF_OPEN);
+        }
+    }
+
+    for sq in bb_ops::idx_bitscan(backward_pawns(position, color)) {
+        score -= params::get(BACKWARD_PAWN);
+
+        if is_half_open(position, opponent, sq % FILES) {
+            score -= params::get(BACKWARD_HALF_OPEN);
+        }
+    }
+
+    for sq in bb_ops::idx_bitscan(passed_pawns(position, color)) {
+        score += params::get(PASSED_PAWN) * advancement(position, color, sq).max(0);
+    }
+
+    score += params::get(CANDIDATE_PASSER) * candidate_passers(position, color).count_ones() as i32;
+    score += params::get(PAWN_MAJORITY) * majorities(position, color).len() as i32;
+
+    return score;
+}
+
+
+/// A slot of the pawn hash table. Empty slots have the key of positions without pawns, whose
+/// score is 0 as well, so they don't need to be told apart.
+#[derive(Clone, Copy, Default)]
+struct PawnEntry {
+    key: u64,
+    /// The score of white's pawn structure minus that of black's.
+    score: i32
+}
+
+/// Caches the pawn structure scores of positions by their pawn keys (pawn hash table). Most
+/// moves don't touch the pawns, so the positions of a search share a few structures. Each key
+/// has a single slot, newer structures replace older ones. The scores depend on the board
+/// size and the parameters, so a table is only meant for a single search.
+#[derive(Clone)]
+pub struct PawnTable {
+    entries: Vec<PawnEntry>
+}
+
+impl PawnTable {
+    /// Creates a table with the given number of entries, rounded up to a power of two.
+    pub fn new(entries: usize) -> PawnTable {
+        return PawnTable { entries: vec![PawnEntry::default(); entries.max(1).next_power_of_two()] };
+    }
+
+    /// Returns the number of bytes the table occupies.
+    pub fn memory_usage(&self) -> usize {
+        return self.entries.len() * mem::size_of::<PawnEntry>();
+    }
+
+    /// Evaluates the pawn structure of the player to move minus that of the opponent like
+    /// evaluate, but only if the table doesn't have the score of the structure yet.
+    pub fn evaluate(&mut self, position: &Position) -> i32 {
+        let key = position.pawn_key();
+        let slot = key as usize & (self.entries.len() - 1);
+        let entry = &mut self.entries[slot];
+
+        if entry.key != key {
+            *entry = PawnEntry { key, score: evaluate(position, WHITE) - evaluate(position, BLACK) };
+        }
+
+        return if position.turn == WHITE { entry.score } else { -entry.score };
+    }
+}
+
+impl Default for PawnTable {
+    fn default() -> PawnTable {
+        return PawnTable::new(PAWN_TABLE_ENTRIES);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::BLACK;
+
+    fn position(fen: &str) -> Position {
+        return Position::from_fen(fen.to_string()).unwrap();
+    }
+
+    fn squares(bb: u64) -> Vec<u8> {
+        return bb_ops::idx_bitscan(bb);
+    }
+
+    #[test]
+    fn finds_doubled_pawns() {
+        // Tripled c-pawns, doubled black f-pawns.
+        let pos = position("4k3/5p2/5p2/8/2P5/2P5/2P5/4K3 w - - 0 1");
+
+        assert_eq!(squares(doubled_pawns(&pos, WHITE)), vec![10, 18]);
+        assert_eq!(squares(doubled_pawns(&pos, BLACK)), vec![53]);
+
+        // White's c-file is half-open for black, black's f-file for white.
+        assert!(is_half_open(&pos, BLACK, 2));
+        assert!(is_half_open(&pos, WHITE, 5));
+        assert!(!is_half_open(&pos, WHITE, 2));
+    }
+
+    #[test]
+    fn finds_isolated_pawns() {
+        // The a-pawn and the doubled d-pawns have no neighbours, the f- and g-pawns support
+        // each other.
+        let pos = position("4k3/p7/8/3p4/3p4/8/5PP1/4K3 w - - 0 1");
+
+        assert_eq!(squares(isolated_pawns(&pos, BLACK)), vec![27, 35, 48]);
+        assert_eq!(isolated_pawns(&pos, WHITE), 0);
+    }
+
+    #[test]
+    fn finds_backward_pawns() {
+        // The d3 pawn can't be supported by the advanced c4 and e4 pawns and d4 is attacked by
+        // the pawn on e5. The c4 pawn has no such problem.
+        let pos = position("4k3/8/8/4p3/2P1P3/3P4/8/4K3 w - - 0 1");
+        assert_eq!(squares(backward_pawns(&pos, WHITE)), vec![19]);
+        assert_eq!(backward_pawns(&pos, BLACK), 0);
+
+        // Without the attacker on the stop square the pawn isn't backward.
+        let pos = position("4k3/8/8/8/2P1P3/3P4/8/4K3 w - - 0 1");
+        assert_eq!(backward_pawns(&pos, WHITE), 0);
+
+        // The same structure for black.
+        let pos = position("4k3/8/3p4/2p1p3/4P3/8/8/4K3 b - - 0 1");
+        assert_eq!(squares(backward_pawns(&pos, BLACK)), vec![43]);
+    }
+
+    #[test]
+    fn finds_passed_pawns_and_candidates() {
+        // The a-pawn is passed. The c-pawn has a helper on d2 against the sentry on d6, so it's
+        // a candidate. The f-pawn faces two sentries with one helper.
+        let pos = position("4k3/4p1p1/3p4/8/P1P2P2/8/3PP3/4K3 w - - 0 1");
+
+        assert_eq!(squares(passed_pawns(&pos, WHITE)), vec![24]);
+        assert_eq!(squares(candidate_passers(&pos, WHITE)), vec![26]);
+        assert_eq!(passed_pawns(&pos, BLACK), 0);
+
+        // A black candidate on an open file in front of it.
+        let pos = position("4k3/1pp5/8/8/1P6/8/8/4K3 b - - 0 1");
+        assert_eq!(squares(candidate_passers(&pos, BLACK)), vec![50]);
+    }
+
+    #[test]
+    fn finds_pawn_majorities() {
+        let pos = position("4k3/ppp2ppp/8/8/8/8/PPPP2PP/4K3 w - - 0 1");
+
+        assert_eq!(majorities(&pos, WHITE), vec![Wing::Queenside]);
+        assert_eq!(majorities(&pos, BLACK), vec![Wing::Kingside]);
+        assert!(majorities(&Position::starting_position(), WHITE).is_empty());
+    }
+
+    #[test]
+    fn penalizes_weaknesses_on_half_open_files_more() {
+        let p = |idx| params::get(idx);
+
+        // The backward d-pawn next to the candidate on c4 and the queenside majority.
+        let closed = position("4k3/3p4/8/4p3/2P1P3/3P4/8/4K3 w - - 0 1");
+        assert_eq!(evaluate(&closed, WHITE), -p(BACKWARD_PAWN) + p(CANDIDATE_PASSER) + p(PAWN_MAJORITY));
+
+        // Without the d7 pawn, the c4 pawn is passed two ranks ahead of its start.
+        let open = position("4k3/8/8/4p3/2P1P3/3P4/8/4K3 w - - 0 1");
+        assert_eq!(evaluate(&open, WHITE), -p(BACKWARD_PAWN) - p(BACKWARD_HALF_OPEN) + 2 * p(PASSED_PAWN) + p(PAWN_MAJORITY));
+
+        // Both doubled pawns are isolated as well.
+        let doubled = position("4k3/8/8/8/8/2P5/2P5/4K3 w - - 0 1");
+        assert_eq!(evaluate(&doubled, WHITE), -p(DOUBLED_PAWN) - p(DOUBLED_HALF_OPEN) - 2 * (p(ISOLATED_PAWN) + p(ISOLATED_HALF_OPEN)) + p(PASSED_PAWN) + p(PAWN_MAJORITY));
+    }
+
+    #[test]
+    fn caches_pawn_structures_by_their_keys() {
+        let mut table = PawnTable::new(3);
+        let pos = position("4k3/pp4p1/8/3p4/3P4/8/PP3PP1/4K3 w - - 0 1");
+        let expected = |pos: &Position| evaluate(pos, pos.turn) - evaluate(pos, flip_color(pos.turn));
+
+        assert_eq!(table.memory_usage(), 4 * mem::size_of::<PawnEntry>());
+        assert_eq!(table.evaluate(&pos), expected(&pos));
+
+        // King moves keep the pawn key, so the score is taken from the table for the other side.
+        let king_move = pos.make_move(pos.uci_to_move("e1d2").unwrap());
+        assert_eq!(king_move.pawn_key(), pos.pawn_key());
+        assert_eq!(table.evaluate(&king_move), -table.evaluate(&pos));
+
+        for m in ["f2f4", "g2g4", "b2b4"] {
+            let next = pos.make_move(pos.uci_to_move(m).unwrap());
+
+            assert_ne!(next.pawn_key(), pos.pawn_key());
+            assert_eq!(table.evaluate(&next), expected(&next));
+        }
+
+        assert_eq!(table.evaluate(&Position::starting_position()), 0);
+    }
+}
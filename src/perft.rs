@@ -0,0 +1,83 @@
+use crate::moves::Move;
+use crate::position::Position;
+
+
+/// Counts all leaf nodes of the legal move tree of the given depth starting at the
+/// position. This is the standard way of verifying and benchmarking move generation.
+///
+/// At depth 1 the number of legal moves is returned directly instead of making every
+/// move (bulk counting), which saves the most expensive part of the last ply. There is no
+/// special case at depth 2: counting the replies without making the moves would need check
+/// and pin masks, which the move generator doesn't compute, since it tests each pseudo-legal
+/// move for exposing the king instead.
+pub fn perft(position: &Position, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let moves = position.clone().get_all_legal_moves();
+
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    return moves.into_iter().map(|m| perft(&position.make_move(m), depth - 1)).sum();
+}
+
+/// Computes the perft node count separately for each legal move in the position, which
+/// helps narrowing down move generation bugs by comparing with other engines.
+pub fn divide(position: &Position, depth: u32) -> Vec<(Move, u64)> {
+    let moves = position.clone().get_all_legal_moves();
+
+    return moves.into_iter().map(|m| (m, perft(&position.make_move(m), depth.saturating_sub(1)))).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(fen: &str) -> Position {
+        return Position::from_fen(fen.to_string()).unwrap();
+    }
+
+    #[test]
+    fn perft_starting_position() {
+        let pos = position("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+
+        assert_eq!(perft(&pos, 0), 1);
+        assert_eq!(perft(&pos, 1), 20);
+        assert_eq!(perft(&pos, 2), 400);
+    }
+
+    /// The published node counts of the standard perft positions, which test castling, en
+    /// passant, promotions and checks far more thoroughly than the starting position.
+    #[test]
+    fn perft_reference_positions() {
+        let cases = [
+            // Kiwipete
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", [48, 2039, 97862]),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", [14, 191, 2812]),
+            ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", [6, 264, 9467]),
+            ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", [44, 1486, 62379])
+        ];
+
+        for (fen, counts) in cases {
+            for (depth, count) in counts.iter().enumerate() {
+                assert_eq!(perft(&position(fen), depth as u32 + 1), *count, "{} at depth {}", fen, depth + 1);
+            }
+        }
+
+        // One level deeper for the positions that are cheap enough.
+        assert_eq!(perft(&position("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1"), 4), 43238);
+        assert_eq!(perft(&position("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1"), 4), 422333);
+    }
+
+    #[test]
+    fn divide_sums_up_to_perft() {
+        let pos = position("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let total: u64 = divide(&pos, 2).iter().map(|(_, n)| n).sum();
+
+        assert_eq!(divide(&pos, 2).len(), 20);
+        assert_eq!(total, perft(&pos, 2));
+    }
+}
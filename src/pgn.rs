@@ -0,0 +1,375 @@
+use std::io::{self, BufRead};
+
+use crate::annotations::Annotations;
+use crate::game::{Game, NodeId};
+use crate::position::{Position, WHITE};
+use crate::variant::Variant;
+
+
+/// A game read from a PGN file.
+pub struct PgnGame {
+    /// The tag pairs of the game in the order they appeared in.
+    pub tags: Vec<(String, String)>,
+    pub game: Game,
+    /// The result given at the end of the movetext (e.g. 1-0) if there is one.
+    pub result: Option<String>
+}
+
+impl PgnGame {
+    /// Returns the value of the tag with the given name.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        return self.tags.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+    }
+}
+
+/// Encodes a game as PGN with the given tags and result. The main line is written with all
+/// variations, comments and annotations of the game tree. A Variant tag is added for games
+/// that aren't regular chess and a FEN tag if the game doesn't start from the variant's
+/// starting position.
+pub fn write_game(game: &Game, tags: &[(String, String)], result: &str) -> String {
+    let mut pgn = String::new();
+
+    for (name, value) in tags {
+        pgn.push_str(&format!("[{} \"{}\"]\n", name, value.replace('"', "\\\"")));
+    }
+
+    let start = game.starting_position();
+    let variant = start.variant();
+
+    if variant != Variant::Standard && !tags.iter().any(|(n, _)| n == "Variant") {
+        pgn.push_str(&format!("[Variant \"{}\"]\n", variant));
+    }
+
+    if *start != Position::variant_starting_position(variant) && !tags.iter().any(|(n, _)| n == "FEN") {
+        pgn.push_str(&format!("[SetUp \"1\"]\n[FEN \"{}\"]\n", start.to_fen()));
+    }
+
+    pgn.push('\n');
+
+    let mut tokens = Vec::new();
+
+    if let Some(comment) = node_comment(game, game.root()) {
+        tokens.push(format!("{{{}}}", comment));
+    }
+
+    // Positions don't keep track of the move number, so games are numbered from 1.
+    write_line(game, game.root(), 1, true, &mut tokens);
+    tokens.push(result.to_string());
+
+    // Wrap the movetext so lines don't get too long.
+    let mut line_len = 0;
+
+    for token in tokens {
+        if line_len > 0 && line_len + token.len() + 1 > 80 {
+            pgn.push('\n');
+            line_len = 0;
+        } else if line_len > 0 {
+            pgn.push(' ');
+            line_len += 1;
+        }
+
+        line_len += token.len();
+        pgn.push_str(&token);
+    }
+
+    pgn.push_str("\n\n");
+
+    return pgn;
+}
+
+/// Writes the main line starting after the given node with all variations. The fullmove
+/// number is the one of the node's position. Black moves need a move number at the start of
+/// the movetext and after comments and variations.
+fn write_line(game: &Game, mut node: NodeId, mut number: u32, mut needs_number: bool, tokens: &mut Vec<String>) {
+    while let Some((&main, variations)) = game.children(node).split_first() {
+        let position = game.node_position(node);
+
+        write_move(game, main, number, needs_number, tokens);
+        needs_number = node_comment(game, main).is_some() || !variations.is_empty();
+
+        for &variation in variations {
+            let mut line = Vec::new();
+
+            write_move(game, variation, number, true, &mut line);
+            let next = if position.turn == WHITE { number } else { number + 1 };
+            write_line(game, variation, next, node_comment(game, variation).is_some(), &mut line);
+
+            line[0].insert(0, '(');
+            line.last_mut().unwrap().push(')');
+            tokens.extend(line);
+        }
+
+        if position.turn != WHITE {
+            number += 1;
+        }
+
+        node = main;
+    }
+}
+
+/// Writes the move leading to the given node, preceded by its move number if needed and
+/// followed by its comment.
+fn write_move(game: &Game, node: NodeId, number: u32, needs_number: bool, tokens: &mut Vec<String>) {
+    let position = game.node_position(game.parent(node).unwrap());
+
+    if position.turn == WHITE {
+        tokens.push(format!("{}.", number));
+    } else if needs_number {
+        tokens.push(format!("{}...", number));
+    }
+
+    tokens.push(position.move_to_san(game.node_move(node).unwrap()));
+
+    if let Some(comment) = node_comment(game, node) {
+        tokens.push(format!("{{{}}}", comment));
+    }
+}
+
+/// Returns the comment of the given node with its annotations encoded as lichess commands in
+/// front of the text, or None if the node has neither.
+fn node_comment(game: &Game, node: NodeId) -> Option<String> {
+    let commands = game.annotations(node).to_pgn_commands();
+
+    return match (commands.is_empty(), game.comment(node)) {
+        (true, comment)        => comment.map(String::from),
+        (false, None)          => Some(commands),
+        (false, Some(comment)) => Some(format!("{} {}", commands, comment))
+    };
+}
+
+/// Reads all games from the content of a PGN file. Games containing moves that can't be
+/// parsed or aren't legal are skipped.
+pub fn read_games(text: &str) -> Vec<PgnGame> {
+    return read_games_from(text.as_bytes()).expect("Reading from memory can't fail");
+}
+
+/// Reads all games from a reader line by line, like read_games, so a large download doesn't
+/// have to be kept in memory as text first. Fails if reading fails or the text isn't UTF-8.
+pub fn read_games_from<R: BufRead>(reader: R) -> io::Result<Vec<PgnGame>> {
+    let mut games = Vec::new();
+    let mut tags = Vec::new();
+    let mut movetext = String::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+
+        if line.starts_with('[') {
+            // A tag after some movetext starts a new game.
+            if !movetext.trim().is_empty() {
+                games.extend(parse_game(std::mem::take(&mut tags), &movetext));
+                movetext.clear();
+            }
+
+            tags.extend(parse_tag(line));
+        } else if !line.starts_with('%') {
+            movetext.push_str(line);
+            movetext.push('\n');
+        }
+    }
+
+    if !movetext.trim().is_empty() || !tags.is_empty() {
+        games.extend(parse_game(tags, &movetext));
+    }
+
+    return Ok(games);
+}
+
+/// Parses a tag pair like [Event "Casual Game"].
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let (name, value) = inner.split_once(' ')?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    return Some((name.to_string(), value.replace("\\\"", "\"")));
+}
+
+/// Parses the movetext of a game starting at the position given by the FEN tag or
+/// the starting position of the variant given by the Variant tag.
+fn parse_game(tags: Vec<(String, String)>, movetext: &str) -> Option<PgnGame> {
+    let variant = match tags.iter().find(|(n, _)| n == "Variant") {
+        Some((_, name)) => Variant::from_name(name)?,
+        None            => Variant::Standard
+    };
+
+    let start = match tags.iter().find(|(n, _)| n == "FEN") {
+        Some((_, fen)) => Position::from_variant_fen(variant, fen.clone())?,
+        None           => Position::variant_starting_position(variant)
+    };
+
+    let mut game = Game::from_position(start);
+    let mut result = None;
+    // The nodes to return to at the end of the variations currently being read.
+    let mut stack = Vec::new();
+
+    for token in tokenize(movetext) {
+        match token {
+            Token::Move(san) => {
+                let m = game.position().san_to_move(&san)?;
+                game.play(m);
+            },
+            Token::Comment(comment) => {
+                let (annotations, text) = Annotations::from_pgn_comment(&comment);
+
+                if annotations.is_empty() || !text.is_empty() {
+                    game.set_comment(text);
+                }
+
+                game.annotate(annotations);
+            },
+            Token::StartVariation => {
+                // A variation is an alternative to the last move.
+                let node = game.current_node();
+                stack.push(node);
+                game.goto_node(game.parent(node)?);
+            },
+            Token::EndVariation => game.goto_node(stack.pop()?),
+            Token::Result(token) => result = Some(token)
+        }
+    }
+
+    if !stack.is_empty() {
+        return None;
+    }
+
+    return Some(PgnGame { tags, game, result });
+}
+
+/// A token of the movetext.
+#[derive(Debug, PartialEq)]
+enum Token {
+    Move(String),
+    Comment(String),
+    StartVariation,
+    EndVariation,
+    Result(String)
+}
+
+/// Splits movetext into moves, comments, variation delimiters and results. Move numbers and
+/// numeric annotation glyphs are dropped.
+fn tokenize(movetext: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = movetext.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let comment: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                tokens.push(Token::Comment(comment.split_whitespace().collect::<Vec<_>>().join(" ")));
+            },
+            ';' => {
+                let comment: String = chars.by_ref().take_while(|&c| c != '\n').collect();
+                tokens.push(Token::Comment(comment.trim().to_string()));
+            },
+            '(' => tokens.push(Token::StartVariation),
+            ')' => tokens.push(Token::EndVariation),
+            _ if c.is_whitespace() => (),
+            _ => {
+                let mut word = c.to_string();
+
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '{' | ';' | '(' | ')') {
+                        break;
+                    }
+
+                    word.push(c);
+                    chars.next();
+                }
+
+                if is_result(&word) {
+                    tokens.push(Token::Result(word));
+                    continue;
+                }
+
+                let san = word.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+
+                if !san.is_empty() && !san.starts_with('$') {
+                    tokens.push(Token::Move(san.to_string()));
+                }
+            }
+        }
+    }
+
+    return tokens;
+}
+
+/// Checks if a token of the movetext is a game result.
+fn is_result(token: &str) -> bool {
+    return matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*");
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PGN: &str = "[Event \"Test\"]
+
+{A short game} 1. e4 e5 {The open game} (1... c5 2. Nf3 (2. c3 d5) 2... d6) 2. Nf3 Nc6 ; Knights first
+3. Bb5 *
+";
+
+    #[test]
+    fn reads_variations_and_comments() {
+        let games = read_games(PGN);
+        assert_eq!(games.len(), 1);
+
+        let game = &games[0].game;
+        let root = game.root();
+        let e4 = game.children(root)[0];
+
+        assert_eq!(game.comment(root), Some("A short game"));
+        assert_eq!(game.moves().len(), 5);
+        assert_eq!(game.moves(), &game.main_line()[..]);
+        assert_eq!(game.children(e4).len(), 2);
+
+        let c5 = game.children(e4)[1];
+        let nf3 = game.children(c5)[0];
+
+        assert_eq!(game.comment(game.children(e4)[0]), Some("The open game"));
+        assert_eq!(game.node_position(nf3).move_to_san(game.node_move(game.children(nf3)[0]).unwrap()), "d6");
+        assert_eq!(game.children(c5).len(), 2);
+        assert_eq!(game.comment(game.current_node()), None);
+        assert_eq!(game.comment(game.parent(game.current_node()).unwrap()), Some("Knights first"));
+    }
+
+    #[test]
+    fn writes_variations_and_comments() {
+        let game = &read_games(PGN)[0].game;
+        let text = write_game(game, &[], "*");
+
+        assert_eq!(text, "\n{A short game} 1. e4 e5 {The open game} (1... c5 2. Nf3 (2. c3 d5) 2... d6) 2.\n\
+                          Nf3 Nc6 {Knights first} 3. Bb5 *\n\n");
+
+        let again = &read_games(&text)[0].game;
+        assert_eq!(write_game(again, &[], "*"), text);
+    }
+
+    #[test]
+    fn reads_and_writes_annotations() {
+        let text = "1. e4 {[%csl Gd5] [%cal Rd7d5,Bg1f3]} 1... e5 {[%cal Gg1f3] Best by test} *";
+        let game = &read_games(text)[0].game;
+        let e4 = game.children(game.root())[0];
+
+        assert_eq!(game.comment(e4), None);
+        assert_eq!(game.annotations(e4).squares.len(), 1);
+        assert_eq!(game.annotations(e4).arrows.len(), 2);
+        assert_eq!(game.comment(game.current_node()), Some("Best by test"));
+        assert_eq!(write_game(game, &[], "*"), format!("\n{}\n\n", text));
+    }
+
+    #[test]
+    fn rejects_unbalanced_variations() {
+        assert!(read_games("1. e4 (1. d4 *").is_empty());
+        assert!(read_games("1. e4 ) e5 *").is_empty());
+    }
+
+    #[test]
+    fn reads_games_from_readers() {
+        let games = read_games_from(io::Cursor::new(PGN)).unwrap();
+
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].game.moves(), read_games(PGN)[0].game.moves());
+        assert!(matches!(read_games_from(&b"1. e4 {\xff} *"[..]), Err(e) if e.kind() == io::ErrorKind::InvalidData));
+    }
+}
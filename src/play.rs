@@ -0,0 +1,395 @@
+use std::io::{self, BufRead, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::build_info;
+use crate::coach;
+use crate::game::Game;
+use crate::interrupt;
+use crate::pgn::PgnGame;
+use crate::position::{Position, WHITE};
+use crate::rating::{self, Rating};
+use crate::recorder::GameRecorder;
+use crate::runner::Engine;
+use crate::search::Score;
+use crate::storage::Storage;
+use crate::strength::Strength;
+
+
+const HELP: &str = "Enter moves in SAN (e.g. Nf3) or long algebraic notation (e.g. g1f3).
+Commands:
+    undo       take back your last move
+    redo       play the moves taken back last again
+    goto <ply> go to the given ply of the current line
+    lines      list the moves already played from here
+    moves      list all legal moves
+    help       show this help
+    quit       end the game";
+
+
+/// The settings of an interactive game against the engine.
+#[derive(Clone, Copy, Debug)]
+pub struct PlayOptions {
+    /// The search depth used by the coach.
+    pub depth: u8,
+    /// The number of centipawns a move may lose before the coach warns or None if the
+    /// coach is disabled.
+    pub coach: Option<i32>,
+    /// The color of the human player.
+    pub human: u8,
+    /// Ctrl-C stops the engine's search and it plays the best move found so far, if the engine
+    /// can be stopped.
+    pub interruptible: bool,
+    /// The weakened strength the engine plays at or None for its full strength.
+    pub strength: Option<Strength>
+}
+
+/// Plays a game between a human entering moves on the input and the engine. The board is
+/// printed to the output after every ply. Returns the game when it is over or the player quits.
+pub fn play<R: BufRead, W: Write>(engine: &mut dyn Engine, options: PlayOptions, input: R, output: W) -> io::Result<Game> {
+    return play_recorded(engine, options, input, output, &mut GameRecorder::new(Position::starting_position()));
+}
+
+/// Plays a game like play and records it with the given recorder, which must start from the
+/// starting position. The recording follows the moves of the current line, so moves taken
+/// back are dropped from it, and the engine's moves are annotated with its evaluation.
+pub fn play_recorded<R: BufRead, W: Write>(engine: &mut dyn Engine, options: PlayOptions, input: R, mut output: W, recorder: &mut GameRecorder) -> io::Result<Game> {
+    let mut game = Game::new();
+    let mut lines = input.lines();
+
+    writeln!(output, "{}", HELP)?;
+
+    loop {
+        recorder.follow(game.moves());
+        writeln!(output, "\n{}\n", game.position())?;
+
+        let position = game.position().clone();
+
+        if let Some(message) = game_over_message(&position) {
+            writeln!(output, "{}", message)?;
+            return Ok(game);
+        }
+
+        if position.turn != options.human {
+            let em = match engine.stop_flag().filter(|_| options.interruptible) {
+                Some(stop) => interrupt::interruptible(&stop, || engine.choose_move(&game)),
+                None       => engine.choose_move(&game)
+            };
+
+            let em = match em {
+                Some(em) => em,
+                None     => {
+                    writeln!(output, "The engine resigns.")?;
+                    return Ok(game);
+                }
+            };
+
+            writeln!(output, "Engine plays {}", position.move_to_san(em.m))?;
+            game.play(em.m);
+            recorder.follow(game.moves());
+
+            if let Some(score) = em.score {
+                recorder.annotate(format!("Engine eval {}", Score::new(score).to_pawns()));
+            }
+
+            continue;
+        }
+
+        write!(output, "Your move: ")?;
+        output.flush()?;
+
+        let line = match lines.next() {
+            Some(line) => line?,
+            None       => return Ok(game)
+        };
+
+        match line.trim() {
+            "quit" => return Ok(game),
+            "help" => writeln!(output, "{}", HELP)?,
+            "moves" => {
+                let moves: Vec<String> = position.clone().get_all_legal_moves().into_iter().map(|m| position.move_to_san(m)).collect();
+                writeln!(output, "{}", moves.join(" "))?;
+            },
+            "undo" => {
+                // Take back the engine's reply as well, so it's the player's turn again.
+                if game.undo().is_none() || (game.position().turn != options.human && game.undo().is_none()) {
+                    writeln!(output, "There is no move to take back.")?;
+                }
+            },
+            "redo" => {
+                if game.redo().is_none() {
+                    writeln!(output, "There is no move to redo.")?;
+                } else if game.position().turn != options.human {
+                    game.redo();
+                }
+            },
+            "lines" => {
+                let moves: Vec<String> = game.continuations().into_iter().map(|m| position.move_to_san(m)).collect();
+                writeln!(output, "{}", moves.join(" "))?;
+            },
+            command if command.starts_with("goto") => {
+                match command["goto".len()..].trim().parse() {
+                    Ok(ply) => if !game.goto(ply) {
+                        writeln!(output, "The current line ends after {} plies.", game.ply())?;
+                    },
+                    Err(_) => writeln!(output, "Usage: goto <ply>")?
+                }
+            },
+            input => {
+                let (m, suffix_matches) = match position.parse_san(input).or_else(|| position.uci_to_move(input).map(|m| (m, true))) {
+                    Some(parsed) => parsed,
+                    None         => {
+                        writeln!(output, "Illegal move: {}", input)?;
+                        continue;
+                    }
+                };
+
+                if !suffix_matches {
+                    writeln!(output, "Warning: The check suffix of {} doesn't match the position after the move.", input)?;
+                }
+
+                // The coach warns about bad moves and lets the player choose another one.
+                if let Some(warning) = options.coach.and_then(|threshold| coach::check_move(&position, m, options.depth, threshold)) {
+                    write!(output, "Coach: {}\nPlay it anyway? [y/N] ", warning)?;
+                    output.flush()?;
+
+                    let answer = match lines.next() {
+                        Some(line) => line?,
+                        None       => return Ok(game)
+                    };
+
+                    if !answer.trim().eq_ignore_ascii_case("y") {
+                        continue;
+                    }
+                }
+
+                game.play(m);
+            }
+        }
+    }
+}
+
+/// Returns the score of white if the game is over in the given position: 1 if white won, 0.5
+/// for a draw and 0 if black won.
+pub fn outcome(position: &Position) -> Option<f64> {
+    let mut position = position.clone();
+    let loss = if position.turn == WHITE { 0.0 } else { 1.0 };
+
+    if position.is_variant_loss() {
+        return Some(loss);
+    }
+
+    if position.get_all_legal_moves().is_empty() {
+        return Some(if position.is_checked(position.turn) { loss } else { 0.5 });
+    }
+
+    if position.is_threefold_repetition() {
+        return Some(0.5);
+    }
+
+    return None;
+}
+
+/// Returns the PGN result of a game with the given outcome, see outcome.
+pub fn result(outcome: Option<f64>) -> &'static str {
+    return match outcome {
+        Some(1.0) => "1-0",
+        Some(0.0) => "0-1",
+        Some(_)   => "1/2-1/2",
+        None      => "*"
+    };
+}
+
+/// Saves a game the player with the given name played against the engine and updates the
+/// player's rating with it. The engine is rated by its strength or else its search depth. Games that aren't over
+/// are saved without changing the rating. Returns the player's old and new rating if the
+/// game was rated.
+pub fn record_game(storage: &mut dyn Storage, player: &str, options: &PlayOptions, game: &Game) -> io::Result<Option<(Rating, Rating)>> {
+    let outcome = outcome(game.position());
+    let result = result(outcome);
+
+    let (engine, opponent) = match options.strength {
+        Some(strength) => (format!("{} (skill {})", build_info::engine_name(), strength.skill()), rating::anchor(strength.elo() as f64)),
+        None           => (format!("{} (depth {})", build_info::engine_name(), options.depth), rating::engine_rating(options.depth))
+    };
+
+    let (white, black) = if options.human == WHITE { (player.to_string(), engine) } else { (engine, player.to_string()) };
+    let tags = vec![(String::from("White"), white), (String::from("Black"), black), (String::from("Date"), today()), (String::from("Result"), result.to_string())];
+
+    storage.save_game(&PgnGame { tags, game: game.clone(), result: Some(result.to_string()) })?;
+
+    let score = match outcome {
+        Some(score) => if options.human == WHITE { score } else { 1.0 - score },
+        None        => return Ok(None)
+    };
+
+    let old = storage.rating(player)?.unwrap_or_default();
+    let new = old.update(&[(opponent, score)]);
+
+    storage.save_rating(player, new)?;
+
+    return Ok(Some((old, new)));
+}
+
+/// Returns the current date in the format of the PGN Date tag, e.g. 2024.03.17.
+fn today() -> String {
+    let days = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() / 86400) as i64;
+
+    // Converts the days since 1970-01-01 into a date of the Gregorian calendar, counting
+    // years from March so the leap day comes last.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    return format!("{:04}.{:02}.{:02}", year, month, day);
+}
+
+/// Returns a message describing how the game ended if it is over in the given position.
+fn game_over_message(position: &Position) -> Option<String> {
+    let mut position = position.clone();
+    let side = if position.turn == WHITE { "White" } else { "Black" };
+
+    if position.is_variant_loss() {
+        return Some(format!("{} lost by the rules of the variant.", side));
+    }
+
+    if position.get_all_legal_moves().is_empty() {
+        if position.is_checked(position.turn) {
+            return Some(format!("Checkmate. {} lost.", side));
+        }
+
+        return Some(String::from("Stalemate."));
+    }
+
+    if position.is_threefold_repetition() {
+        return Some(String::from("Draw by threefold repetition."));
+    }
+
+    return None;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::BLACK;
+    use crate::search::SearchEngine;
+    use crate::storage::{GameQuery, MemoryStorage};
+
+    fn run(input: &str, human: u8) -> (Game, String) {
+        let mut engine = SearchEngine::new(1);
+        let options = PlayOptions { depth: 1, coach: None, human, interruptible: false, strength: None };
+        let mut output = Vec::new();
+        let game = play(&mut engine, options, input.as_bytes(), &mut output).unwrap();
+
+        return (game, String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn engine_replies_to_moves_in_either_notation() {
+        let (game, output) = run("e4\ng1f3\nquit\n", WHITE);
+
+        assert_eq!(game.moves().len(), 4);
+        assert!(output.contains("Engine plays"));
+    }
+
+    #[test]
+    fn undo_takes_back_the_engine_reply_too() {
+        let (game, _) = run("e4\nundo\nquit\n", WHITE);
+        assert!(game.moves().is_empty());
+
+        let (game, output) = run("undo\nquit\n", BLACK);
+        assert_eq!(game.moves().len(), 1);
+        assert!(output.contains("There is no move to take back."));
+    }
+
+    #[test]
+    fn redo_and_goto_navigate_the_history() {
+        let (game, _) = run("e4\nd4\nundo\nundo\nredo\nquit\n", WHITE);
+        assert_eq!(game.ply(), 2);
+
+        let (game, output) = run("e4\nd4\ngoto 2\nlines\ngoto 9\nquit\n", WHITE);
+        assert_eq!(game.ply(), 4);
+        assert!(output.contains("The current line ends after 4 plies."));
+        assert!(output.contains("\nYour move: d4\n"));
+    }
+
+    #[test]
+    fn records_the_current_line() {
+        let mut engine = SearchEngine::new(1);
+        let options = PlayOptions { depth: 1, coach: None, human: WHITE, interruptible: false, strength: None };
+        let mut recorder = GameRecorder::new(Position::starting_position());
+        let game = play_recorded(&mut engine, options, "e4\nd4\nundo\nquit\n".as_bytes(), Vec::new(), &mut recorder).unwrap();
+
+        assert_eq!(recorder.moves().iter().map(|tm| tm.m).collect::<Vec<_>>(), game.moves());
+        assert!(recorder.moves()[1].analysis.as_ref().unwrap().starts_with("Engine eval"));
+        assert_eq!(recorder.moves()[0].analysis, None);
+    }
+
+    #[test]
+    fn rejects_illegal_moves() {
+        let (game, output) = run("e5\nquit\n", WHITE);
+
+        assert!(game.moves().is_empty());
+        assert!(output.contains("Illegal move: e5"));
+
+        let (game, output) = run("e4+\nquit\n", WHITE);
+
+        assert_eq!(game.moves().len(), 2);
+        assert!(output.contains("Warning: The check suffix of e4+ doesn't match the position after the move."));
+    }
+
+    #[test]
+    fn reports_checkmate() {
+        let fen = "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3";
+        let position = Position::from_fen(fen.to_string()).unwrap();
+
+        assert_eq!(game_over_message(&position), Some(String::from("Checkmate. White lost.")));
+        assert_eq!(game_over_message(&Position::starting_position()), None);
+        assert_eq!(outcome(&position), Some(0.0));
+        assert_eq!(outcome(&Position::starting_position()), None);
+    }
+
+    #[test]
+    fn finished_games_update_the_rating() {
+        let mut storage = MemoryStorage::new();
+        let options = PlayOptions { depth: 1, coach: None, human: BLACK, interruptible: false, strength: None };
+
+        let (game, _) = run("e4\nquit\n", WHITE);
+
+        // Fool's mate: the player wins with black.
+        let mut fools_mate = Game::new();
+
+        for m in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+            fools_mate.play(fools_mate.position().uci_to_move(m).unwrap());
+        }
+
+        let (old, new) = record_game(&mut storage, "Anna", &options, &fools_mate).unwrap().unwrap();
+
+        assert_eq!(old, Rating::default());
+        assert!(new.rating > old.rating);
+        assert_eq!(storage.rating("Anna").unwrap(), Some(new));
+
+        // Unfinished games are saved without a rating change.
+        assert_eq!(record_game(&mut storage, "Anna", &options, &game).unwrap(), None);
+        assert_eq!(storage.rating("Anna").unwrap(), Some(new));
+
+        let games = storage.games(&GameQuery { player: Some(String::from("Anna")), ..GameQuery::default() }).unwrap();
+        assert_eq!(games.iter().map(|game| game.result.as_str()).collect::<Vec<_>>(), vec!["0-1", "*"]);
+
+        // Beating the weakest engine gains less than beating a stronger one.
+        let weakened = |skill| PlayOptions { strength: Some(Strength::from_skill(skill)), ..options };
+        let (_, weak_win) = record_game(&mut storage, "Ben", &weakened(0), &fools_mate).unwrap().unwrap();
+        let (_, strong_win) = record_game(&mut storage, "Cleo", &weakened(10), &fools_mate).unwrap().unwrap();
+
+        assert!(weak_win.rating < strong_win.rating);
+        assert!(storage.games(&GameQuery::default()).unwrap()[2].white.ends_with("(skill 0)"));
+        assert_eq!(games[0].game().unwrap().game.main_line().len(), 4);
+        assert_eq!(today().len(), "2024.03.17".len());
+    }
+}
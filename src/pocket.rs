@@ -0,0 +1,151 @@
+use crate::position::{NUM_PIECE_TYPES, PAWN, KNIGHT, BISHOP, ROOK, QUEEN};
+
+
+/// The piece types that can be held in a pocket and dropped onto the board.
+pub const DROPPABLE_PIECES: [u8; 5] = [PAWN, KNIGHT, BISHOP, ROOK, QUEEN];
+
+/// Holds the pieces each player has in hand in drop variants like Crazyhouse and Bughouse.
+/// In Crazyhouse captured pieces go straight to the capturing player's pocket. In Bughouse
+/// they are passed to the partner on the other board, so the pocket is fed from the outside
+/// using add (e.g. by a server relaying the captures between both boards).
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pocket {
+    counts: [[u8; NUM_PIECE_TYPES as usize]; 2]
+}
+
+impl Pocket {
+    /// Construct an empty pocket.
+    pub fn new() -> Pocket {
+        return Pocket::default();
+    }
+
+    /// Returns how many pieces of the given type the player with the given color holds.
+    pub fn count(&self, color: u8, piece_type: u8) -> u8 {
+        return self.counts[color as usize][piece_type as usize];
+    }
+
+    /// Puts a piece of the given type into the pocket of the player with the given color.
+    /// Kings can't be held in a pocket and are ignored.
+    pub fn add(&mut self, color: u8, piece_type: u8) {
+        if DROPPABLE_PIECES.contains(&piece_type) {
+            self.counts[color as usize][piece_type as usize] += 1;
+        }
+    }
+
+    /// Takes a piece of the given type out of the pocket of the player with the given color.
+    /// Returns false if the player doesn't hold such a piece.
+    pub fn remove(&mut self, color: u8, piece_type: u8) -> bool {
+        let count = &mut self.counts[color as usize][piece_type as usize];
+
+        if *count == 0 {
+            return false;
+        }
+
+        *count -= 1;
+
+        return true;
+    }
+
+    /// Returns the types of all pieces the player with the given color can drop.
+    pub fn piece_types(&self, color: u8) -> Vec<u8> {
+        return DROPPABLE_PIECES.iter().copied().filter(|t| self.count(color, *t) > 0).collect();
+    }
+
+    /// Returns true if neither player holds any pieces.
+    pub fn is_empty(&self) -> bool {
+        return *self == Pocket::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+    use crate::moves::Move;
+    use crate::position::{Position, WHITE, BLACK};
+    use crate::variant::Variant;
+
+    fn position(variant: Variant, fen: &str) -> Position {
+        return Position::from_variant_fen(variant, fen.to_string()).unwrap();
+    }
+
+    #[test]
+    fn pocket_counts_pieces() {
+        let mut pocket = Pocket::new();
+
+        pocket.add(WHITE, KNIGHT);
+        pocket.add(WHITE, KNIGHT);
+        pocket.add(BLACK, PAWN);
+        pocket.add(BLACK, crate::position::KING);
+
+        assert_eq!(pocket.count(WHITE, KNIGHT), 2);
+        assert_eq!(pocket.piece_types(BLACK), vec![PAWN]);
+        assert!(pocket.remove(BLACK, PAWN));
+        assert!(!pocket.remove(BLACK, PAWN));
+        assert!(!pocket.is_empty());
+    }
+
+    #[test]
+    fn crazyhouse_captures_go_to_the_pocket() {
+        let pos = position(Variant::Crazyhouse, "4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1");
+        let next = pos.make_move(pos.san_to_move("exd5").unwrap());
+
+        assert_eq!(next.pocket().count(WHITE, PAWN), 1);
+        assert_eq!(next.pocket().count(BLACK, PAWN), 0);
+        assert_eq!(next.get_drop_moves(WHITE).len(), 47);
+        assert!(next.get_drop_moves(BLACK).is_empty());
+    }
+
+    #[test]
+    fn promoted_pieces_turn_back_into_pawns() {
+        let pos = position(Variant::Crazyhouse, "2r1k3/1P6/8/8/8/8/8/4K3 w - - 0 1");
+        let pos = pos.make_move(pos.san_to_move("b8=Q").unwrap());
+        let m = pos.san_to_move("Rxb8").unwrap();
+
+        assert_eq!(pos.captured_piece(m), Some(PAWN));
+        assert_eq!(pos.make_move(m).pocket().count(BLACK, PAWN), 1);
+        assert_eq!(pos.make_move(m).pocket().count(BLACK, QUEEN), 0);
+    }
+
+    #[test]
+    fn drops_follow_the_pawn_rules() {
+        let mut pos = position(Variant::Crazyhouse, "4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        pos.feed_pocket(WHITE, PAWN);
+        pos.feed_pocket(WHITE, KNIGHT);
+
+        let drops = pos.get_drop_moves(WHITE);
+
+        assert_eq!(drops.iter().filter(|m| matches!(m, Move::Drop(PAWN, _))).count(), 48);
+        assert_eq!(drops.iter().filter(|m| matches!(m, Move::Drop(KNIGHT, _))).count(), 62);
+        assert!(!drops.contains(&Move::Drop(PAWN, 0)));
+
+        let m = pos.san_to_move("N@a3").unwrap();
+        assert_eq!(pos.move_to_san(m), "N@a3");
+        assert_eq!(pos.move_to_uci(m), "N@a3");
+        assert_eq!(pos.san_to_move("@e4"), Some(Move::Drop(PAWN, 28)));
+
+        let next = pos.make_move(m);
+        assert_eq!(next.pocket().count(WHITE, KNIGHT), 0);
+        assert_eq!(Move::infer(&pos, &next), Some(m));
+    }
+
+    #[test]
+    fn bughouse_pockets_are_fed_from_the_partner_board() {
+        let mut board = Game::from_position(Position::variant_starting_position(Variant::Bughouse));
+        let partner = position(Variant::Bughouse, "4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1");
+
+        // White captures on the partner board, so the piece goes to the teammate playing
+        // black on this board.
+        let m = partner.san_to_move("exd5").unwrap();
+        let next = partner.make_move(m);
+
+        assert!(next.pocket().is_empty());
+
+        board.feed_pocket(BLACK, partner.captured_piece(m).unwrap());
+        board.play(board.position().san_to_move("e4").unwrap());
+
+        assert_eq!(board.position().pocket().count(BLACK, PAWN), 1);
+        assert!(board.position().clone().get_all_legal_moves().contains(&Move::Drop(PAWN, 35)));
+    }
+}
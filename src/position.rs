@@ -1,20 +1,27 @@
 use std::collections::HashMap;
-use std::collections::hash_map::DefaultHasher;
+use std::fmt;
 use std::hash::{Hasher, Hash};
+use std::mem;
+#[cfg(feature = "nnue")]
+use std::sync::Arc;
 
 use crate::bitboards::{bb_ops, rooks, bishops, knights, kings, pawns};
 use crate::bitboards::bb_ops::coords_to_index;
 use crate::moves::{Move};
+#[cfg(feature = "nnue")]
+use crate::nnue::{Accumulator, Network};
+use crate::pocket::{Pocket, DROPPABLE_PIECES};
+use crate::search;
+use crate::variant::Variant;
+use crate::zobrist;
 
 use lazy_static::lazy_static;
 use regex::Regex;
 
-pub const RANKS: u8 = 8;
-pub const FILES: u8 = 8;
-const NUM_SQUARES: u8 = RANKS * FILES;
-const NUM_PIECE_TYPES: u8 = 6;
+pub use crate::bitboards::geometry::{RANKS, FILES};
+pub const NUM_PIECE_TYPES: u8 = 6;
 
-const NUM_COLORS: u8 = 2;
+pub const NUM_COLORS: u8 = 2;
 pub const WHITE: u8 = 0;
 pub const BLACK: u8 = 1;
 
@@ -25,6 +32,12 @@ pub const BISHOP: u8 = 2;
 pub const KNIGHT: u8 = 1;
 pub const PAWN: u8 = 0;
 
+/// The game phase of the starting position, see Position::phase.
+pub const MAX_PHASE: i32 = 24;
+
+/// How much each piece type contributes to the game phase. Pawns and kings don't count.
+const PHASE_WEIGHTS: [i32; NUM_PIECE_TYPES as usize] = [0, 1, 1, 2, 4, 0];
+
 const WHITE_PAWNS_INIT: u64 = 0xFF00;
 const BLACK_PAWNS_INIT: u64 = 0xFF000000000000;
 
@@ -47,6 +60,7 @@ lazy_static! {
     static ref STD_MOVE_PATTERN: Regex = Regex::new(r"(?P<type>[kqrbn])?(?P<origin>[a-h]?[1-8]?)?(?P<captures>x)?(?P<target>[a-h][1-8])(?P<promotes>=[qrbn])?[+#]?").unwrap();
     static ref LCASTLE_PATTERN: Regex = Regex::new(r"^(o-o-o|0-0-0)$").unwrap();
     static ref SCASTLE_PATTERN: Regex = Regex::new(r"^(o-o|0-0)$").unwrap();
+    static ref DROP_PATTERN: Regex = Regex::new(r"^(?P<type>[pnbrq])?@(?P<target>[a-h][1-8])[+#]?$").unwrap();
 }
 
 /// Given a color return the opponent's color.
@@ -54,11 +68,40 @@ pub fn flip_color(color: u8) -> u8 {
     return 1 - color;
 }
 
+/// Computes the smallest number of captures the pawns on the given bitboard must have made
+/// to get from their starting files to their current ones, since each capture moves a pawn
+/// one file over. Every pawn started on a different file.
+fn pawn_captures(pawns: u64) -> u32 {
+    let files: Vec<u32> = bb_ops::idx_bitscan(pawns).iter().map(|i| (*i % FILES) as u32).collect();
+
+    // best[used] is the cheapest assignment of the first pawns to the set of starting files
+    // given by the bits of used.
+    let mut best = vec![u32::MAX; 1 << FILES];
+    best[0] = 0;
+
+    for used in 0..best.len() {
+        let pawn = (used as u32).count_ones() as usize;
+
+        if best[used] == u32::MAX || pawn >= files.len() {
+            continue;
+        }
+
+        for start in 0..FILES as u32 {
+            if used & (1 << start) == 0 {
+                let next = used | (1 << start);
+                best[next] = best[next].min(best[used] + files[pawn].abs_diff(start));
+            }
+        }
+    }
+
+    return best.iter().enumerate().filter(|(used, _)| used.count_ones() as usize == files.len()).map(|(_, c)| *c).min().unwrap_or(0);
+}
+
 
 /// Convert the index of a file into the corresponding letter.
 pub fn file_to_string(rk: u8) -> Option<char> {
     return match rk {
-        0..=7 => Some((0x61u8 + rk) as char),
+        rk if rk < FILES => Some((0x61u8 + rk) as char),
         _     => None
     };
 }
@@ -75,7 +118,7 @@ pub fn string_to_file(fl: char) -> Option<u8> {
 /// Convert the index of a rank into the corresponding number.
 pub fn rank_to_string(fl: u8) -> Option<char> {
     return match fl {
-        0..=7 => char::from_digit((fl + 1) as u32, 10),
+        fl if fl < RANKS => char::from_digit((fl + 1) as u32, 10),
         _     => None
     }; 
 }
@@ -118,8 +161,8 @@ pub fn coords_to_string(rk: u8, fl: u8) -> Option<String> {
 pub fn string_to_coords(string: String) -> Option<(u8, u8)> {
     let mut chars = string.chars();
 
-    let fl = chars.nth(0).and_then(string_to_file);
-    let rk = chars.nth(0).and_then(string_to_rank);
+    let fl = chars.next().and_then(string_to_file);
+    let rk = chars.next().and_then(string_to_rank);
     return rk.zip(fl);
 }
 
@@ -138,6 +181,29 @@ pub fn piece_to_string(piece_type: u8) -> char {
 }
 
 
+/// Convert a drop of a piece from the pocket into its notation (e.g. N@f3).
+/// The notation is the same in SAN and UCI.
+pub fn drop_to_string(piece_type: u8, target: u8) -> String {
+    let piece = match piece_type {
+        PAWN => 'P',
+        _    => piece_to_string(piece_type)
+    };
+
+    return format!("{}@{}", piece, index_to_string(target).unwrap());
+}
+
+
+/// Convert a character describing a piece in a FEN into the corresponding
+/// integer. Returns None if the character doesn't describe a piece.
+pub fn char_to_piece(c: char) -> Option<u8> {
+    return match c {
+        'P' | 'p' => Some(PAWN),
+        'N' | 'n' | 'B' | 'b' | 'R' | 'r' | 'Q' | 'q' | 'K' | 'k' => Some(string_to_piece(c)),
+        _         => None
+    };
+}
+
+
 /// Convert a string describing a piece into the corresponding
 /// integer.
 pub fn string_to_piece(c: char) -> u8 {
@@ -151,18 +217,39 @@ pub fn string_to_piece(c: char) -> u8 {
     };
 }
 
-#[derive(Clone, Eq)]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Position {
     pub turn: u8,
     piece_bbs: [u64; 12],
     qs_castle: [bool; 2],
     ks_castle: [bool; 2],
     en_passant: Option<u8>,
-    repetitions: HashMap<Position, u8>
+    // The repetitions are part of the game's history rather than the position, so they aren't
+    // serialized. Positions are counted by their hash_key, so positions only repeat with the
+    // same castling rights and en passant capture.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    repetitions: HashMap<u64, u8>,
+    variant: Variant,
+    pocket: Pocket,
+    // Marks the pieces that were promoted from pawns. They turn back into pawns when captured
+    // in drop variants.
+    promoted: u64,
+    // The number of checks each player has given. Only counted in variants like Three-check.
+    checks: [u8; 2],
+    // The Zobrist hash of the position, updated incrementally as moves are made.
+    zobrist: u64,
+    // The Zobrist hash of the pawns alone, e.g. for the pawn hash table.
+    pawn_zobrist: u64,
+    // The hidden layers of the NNUE network the position is evaluated with, updated
+    // incrementally as pieces are added and removed.
+    #[cfg(feature = "nnue")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    accumulator: Option<Box<Accumulator>>
 }
 
 impl Position {
-    /// Constructors ///
+    // Constructors //
     pub fn empty() -> Position {
         return Position {
             turn: WHITE,
@@ -170,19 +257,35 @@ impl Position {
             qs_castle: [true, true],
             ks_castle: [true, true],
             en_passant: None,
-            repetitions: HashMap::new()
-        };
+            repetitions: HashMap::new(),
+            variant: Variant::Standard,
+            pocket: Pocket::new(),
+            promoted: 0,
+            checks: [0; 2],
+            zobrist: 0,
+            pawn_zobrist: 0,
+            #[cfg(feature = "nnue")]
+            accumulator: None
+        }.with_zobrist();
     }
 
     pub fn new(turn: u8, piece_bbs: [u64; 12], qs_castle: [bool; 2], ks_castle: [bool; 2], en_passant: Option<u8>) -> Position {
         return Position {
-            turn: turn,
-            piece_bbs: piece_bbs,
-            qs_castle: qs_castle,
-            ks_castle: ks_castle,
-            en_passant: en_passant,
-            repetitions: HashMap::new()
-        };
+            turn,
+            piece_bbs,
+            qs_castle,
+            ks_castle,
+            en_passant,
+            repetitions: HashMap::new(),
+            variant: Variant::Standard,
+            pocket: Pocket::new(),
+            promoted: 0,
+            checks: [0; 2],
+            zobrist: 0,
+            pawn_zobrist: 0,
+            #[cfg(feature = "nnue")]
+            accumulator: None
+        }.with_zobrist();
     }
 
     /// Construct a Position object encoding the starting position in regular chess.
@@ -206,80 +309,560 @@ impl Position {
             qs_castle: [true, true],
             ks_castle: [true, true],
             en_passant: None,
-            repetitions: HashMap::new()
-        };
+            repetitions: HashMap::new(),
+            variant: Variant::Standard,
+            pocket: Pocket::new(),
+            promoted: 0,
+            checks: [0; 2],
+            zobrist: 0,
+            pawn_zobrist: 0,
+            #[cfg(feature = "nnue")]
+            accumulator: None
+        }.with_zobrist();
+    }
+
+    /// Construct a Position object encoding the starting position of the given variant.
+    pub fn variant_starting_position(variant: Variant) -> Position {
+        return Position::from_variant_fen(variant, variant.starting_fen().to_string()).unwrap();
     }
 
     /// Construct a Position object that represents the position encoded by the given FEN.
     pub fn from_fen(fen: String) -> Option<Position> {
-        let mut fields = fen.split(" ");
+        return Position::from_variant_fen(Variant::Standard, fen);
+    }
+
+    /// Construct a Position object of the given variant from a FEN. The piece placement
+    /// has to describe exactly as many ranks and files as the variant's board has. Besides
+    /// the regular fields the FEN may contain the fields of the variant:
+    ///
+    /// - Drop variants append the pocket to the placement (e.g. .../RNBQKBNR[Qp]) and mark
+    ///   promoted pieces with a tilde (e.g. Q~).
+    /// - Three-check adds the remaining checks (e.g. 3+2) after the en passant square.
+    ///
+    /// Castling rights may be given as files of the castling rooks (Shredder-FEN or X-FEN),
+    /// but only castling with the rooks on the corner squares is supported. Returns None if
+    /// the FEN can't be parsed or doesn't describe a legal setup.
+    pub fn from_variant_fen(variant: Variant, fen: String) -> Option<Position> {
+        let mut fields = fen.split_whitespace();
 
         // Parse the first part of the FEN that encoded the piece placement in the position.
-        let ranks = fields.next().map(|rs| rs.split("/"));
-        let piece_bbs = ranks.map(|rs| {
-            let mut bbs = [0; 12];
+        let (piece_bbs, promoted, pocket) = Position::parse_placement(variant, fields.next()?)?;
+
+        // Only allow "W", "w", "S", "s" to define what turn it is.
+        let turn = fields.next().map(|s| s.to_lowercase())
+                                .filter(|s| *s == "w" || *s == "b")
+                                .map(|s| if s == "w" { WHITE } else { BLACK })?;
 
-            for (ri, r) in rs.enumerate() {
-                let mut fi: u8 = 0;
+        // Parse the castling rights for this position.
+        let (ks_castle, qs_castle) = Position::parse_castling(variant, fields.next()?)?;
 
-                for c in r.chars() {
-                    if c.is_digit(10) {
-                        fi += c.to_digit(10).unwrap() as u8;
-                    } else {
-                        let piece_idx = string_to_piece(c) + NUM_PIECE_TYPES * if c.is_uppercase() { WHITE } else { BLACK };
+        // Parse a potential square to capture en passant.
+        let en_passant = match fields.next() {
+            Some("-") | None => None,
+            Some(s)          => Some(string_to_index(s.to_string())?)
+        };
+
+        let mut position = Position::new(turn, piece_bbs, qs_castle, ks_castle, en_passant);
+        position.variant = variant;
+        position.promoted = promoted;
+        position.pocket = pocket;
+
+        // Parse the remaining checks in Three-check. They can also be given as the number of
+        // checks each side has already given (e.g. +1+0).
+        if let Some(limit) = variant.check_limit() {
+            let field = fields.clone().next().filter(|s| s.contains('+'));
+
+            if let Some(field) = field {
+                fields.next();
+
+                let (given, counts) = match field.strip_prefix('+') {
+                    Some(rest) => (true, rest.split_once('+')?),
+                    None       => (false, field.split_once('+')?)
+                };
+
+                for (color, count) in [(WHITE, counts.0), (BLACK, counts.1)] {
+                    let count: u8 = count.parse().ok().filter(|c| *c <= limit)?;
+
+                    position.checks[color as usize] = if given { count } else { limit - count };
+                }
+            }
+        }
+
+        // The halfmove clock and the move number aren't tracked, but have to be numbers if
+        // they are given.
+        if !fields.all(|s| s.parse::<u32>().is_ok()) {
+            return None;
+        }
+
+        return Some(position.with_zobrist()).filter(|p| p.is_legal_setup());
+    }
+
+    /// Sets the Zobrist hash computed from scratch. Moves only update it incrementally.
+    fn with_zobrist(mut self) -> Position {
+        self.zobrist = self.compute_zobrist();
+        self.pawn_zobrist = self.compute_pawn_zobrist();
+
+        return self;
+    }
+
+    fn compute_zobrist(&self) -> u64 {
+        let mut hash = zobrist::side(self.turn) ^ zobrist::en_passant(self.en_passant);
+
+        for piece_idx in 0..2*NUM_PIECE_TYPES {
+            for sq in bb_ops::idx_bitscan(self.piece_bbs[piece_idx as usize]) {
+                hash ^= zobrist::piece(piece_idx, sq);
+            }
+        }
+
+        for color in [WHITE, BLACK] {
+            if self.ks_castle[color as usize] {
+                hash ^= zobrist::castling(color, true);
+            }
+
+            if self.qs_castle[color as usize] {
+                hash ^= zobrist::castling(color, false);
+            }
+
+            for piece_type in DROPPABLE_PIECES {
+                hash ^= zobrist::pocket(color, piece_type, self.pocket.count(color, piece_type));
+            }
+
+            hash ^= zobrist::checks(color, self.checks[color as usize]);
+        }
+
+        return hash;
+    }
+
+    fn compute_pawn_zobrist(&self) -> u64 {
+        let mut hash = 0;
+
+        for color in [WHITE, BLACK] {
+            for sq in bb_ops::idx_bitscan(self.get_piece_bitboard(color, PAWN)) {
+                hash ^= zobrist::piece(color * NUM_PIECE_TYPES + PAWN, sq);
+            }
+        }
+
+        return hash;
+    }
+
+    /// Parses the piece placement field of a FEN including the pocket and the markers for
+    /// promoted pieces used by drop variants.
+    fn parse_placement(variant: Variant, field: &str) -> Option<([u64; 12], u64, Pocket)> {
+        let geometry = variant.geometry();
+        let mut placement = field;
+        let mut pocket = Pocket::new();
+
+        // The pocket is either given in brackets or as an additional rank.
+        let pocket_str = if let Some(open) = field.find('[') {
+            placement = &field[..open];
+            Some(field[open + 1..].strip_suffix(']')?)
+        } else if field.split('/').count() == geometry.ranks as usize + 1 {
+            let split = field.rfind('/')?;
+            placement = &field[..split];
+            Some(&field[split + 1..])
+        } else {
+            None
+        };
+
+        if let Some(pocket_str) = pocket_str {
+            if !variant.has_drops() {
+                return None;
+            }
+
+            for c in pocket_str.chars().filter(|c| *c != '-') {
+                let piece_type = char_to_piece(c)?;
+                let color = if c.is_uppercase() { WHITE } else { BLACK };
+
+                // No game can fill a pocket with more pieces than there are squares.
+                if pocket.count(color, piece_type) >= geometry.ranks * geometry.files {
+                    return None;
+                }
+
+                pocket.add(color, piece_type);
+            }
+        }
+
+        let mut bbs = [0; 12];
+        let mut promoted = 0;
+        let mut num_ranks = 0;
+
+        for (ri, r) in placement.split('/').enumerate() {
+            let mut fi: u8 = 0;
+
+            if ri >= geometry.ranks as usize {
+                return None;
+            }
+
+            for c in r.chars() {
+                if let Some(empty) = c.to_digit(10) {
+                    // Reject runs of empty squares that don't fit on the rank before they can
+                    // overflow the file counter.
+                    fi += empty as u8;
 
-                        bbs[piece_idx as usize] |= bb_ops::index_lookup_mask((7 - ri as u8) * 8 + fi);
-                        fi += 1;
+                    if fi > geometry.files {
+                        return None;
                     }
+                } else if c == '~' && variant.has_drops() && fi > 0 && fi <= geometry.files {
+                    promoted |= bb_ops::coords_lookup_mask(geometry.ranks - 1 - ri as u8, fi - 1);
+                } else if fi < geometry.files {
+                    let piece_idx = char_to_piece(c)? + NUM_PIECE_TYPES * if c.is_uppercase() { WHITE } else { BLACK };
+
+                    bbs[piece_idx as usize] |= bb_ops::coords_lookup_mask(geometry.ranks - 1 - ri as u8, fi);
+                    fi += 1;
+                } else {
+                    return None;
                 }
             }
 
-            return bbs;
-         });
+            if fi != geometry.files {
+                return None;
+            }
 
-        // Only allow "W", "w", "S", "s" to define what turn it is.
-        let turn = fields.next().map(|s| s.to_lowercase())
-                                .filter(|s| *s == "w" || *s == "b")
-                                .map(|s| if s == "w" { WHITE } else { BLACK });
+            num_ranks += 1;
+        }
 
-        // Parse the castling rights for this position.
-        let castling = fields.next().map(|s| {
-            let ks_castle = [s.contains("K"), s.contains("k")];
-            let qs_castle = [s.contains("Q"), s.contains("q")];
+        if num_ranks != geometry.ranks {
+            return None;
+        }
 
-            return (ks_castle, qs_castle);
-        });
+        return Some((bbs, promoted, pocket));
+    }
 
-        // Parse a potential square to capture en passant.
-        let en_passant = fields.next().and_then(|s| string_to_coords(s.to_string()))
-                                                  .map(|(rk, fl)| coords_to_index(rk, fl));
+    /// Parses the castling field of a FEN. Besides KQkq the files of the castling rooks
+    /// can be given (e.g. HAha).
+    fn parse_castling(variant: Variant, field: &str) -> Option<([bool; 2], [bool; 2])> {
+        let geometry = variant.geometry();
+        let mut ks_castle = [false; 2];
+        let mut qs_castle = [false; 2];
 
-        return turn.zip(piece_bbs).zip(castling).map(| ((turn, piece_bbs), castling) | {
-            return Position::new(turn, piece_bbs, castling.1, castling.0, en_passant);
-        });
+        if field == "-" {
+            return Some((ks_castle, qs_castle));
+        }
+
+        if !variant.has_castling() {
+            return None;
+        }
+
+        for c in field.chars() {
+            let color = if c.is_uppercase() { WHITE } else { BLACK };
+
+            match c.to_ascii_lowercase() {
+                'k' => ks_castle[color as usize] = true,
+                'q' => qs_castle[color as usize] = true,
+                f   => {
+                    let fl = string_to_file(f)?;
+
+                    if fl == geometry.files - 1 {
+                        ks_castle[color as usize] = true;
+                    } else if fl == 0 {
+                        qs_castle[color as usize] = true;
+                    } else {
+                        return None;
+                    }
+                }
+            }
+        }
+
+        return Some((ks_castle, qs_castle));
+    }
+
+    /// Checks if this position is a legal setup: Each side has exactly one king, there are no
+    /// pawns on the first and last rank, the castling rights and the en passant square match
+    /// the placement of the pieces and the player who just moved isn't in check.
+    pub fn is_legal_setup(&self) -> bool {
+        let geometry = self.variant.geometry();
+
+        for color in [WHITE, BLACK] {
+            if self.get_piece_bitboard(color, KING).count_ones() != 1 {
+                return false;
+            }
+
+            let pawns = self.get_piece_bitboard(color, PAWN);
+
+            if pawns & (geometry.rank_mask(0) | geometry.rank_mask(geometry.ranks - 1)) != 0 {
+                return false;
+            }
+
+            let back_rank = geometry.back_rank(color);
+            let king_home = self.get_piece_at(color, coords_to_index(back_rank, 4)) == Some(KING);
+            let rook_at = |fl: u8| self.get_piece_at(color, coords_to_index(back_rank, fl)) == Some(ROOK);
+
+            if self.can_castle_kingside(color) && !(king_home && rook_at(geometry.files - 1)) {
+                return false;
+            }
+
+            if self.can_castle_queenside(color) && !(king_home && rook_at(0)) {
+                return false;
+            }
+        }
+
+        // The en passant square has to be the square skipped by an opposing pawn that just
+        // made a double step.
+        if let Some(ep) = self.en_passant {
+            let (rk, fl) = bb_ops::index_to_coords(ep);
+            let (skipped_rk, pawn_rk) = if self.turn == WHITE { (geometry.ranks - 3, geometry.ranks - 4) } else { (2, 3) };
+
+            if !self.variant.has_double_steps() || rk != skipped_rk
+                || self.get_piece_at(flip_color(self.turn), coords_to_index(pawn_rk, fl)) != Some(PAWN)
+                || self.get_piece_and_color_at(ep).is_some() {
+                return false;
+            }
+        }
+
+        if let Some(limit) = self.variant.check_limit() {
+            if self.checks.iter().any(|c| *c > limit) {
+                return false;
+            }
+        }
+
+        return !self.is_checked(flip_color(self.turn));
+    }
+
+    /// Checks if this position might arise from the starting position in regular chess. See
+    /// reachability_error for the heuristics used.
+    pub fn is_reachable(&self) -> bool {
+        return self.reachability_error().is_none();
+    }
+
+    /// Looks for reasons why this position can't arise from the starting position and
+    /// describes the first one found:
+    ///
+    /// - Pieces beyond the initial set (e.g. a second queen or two bishops on squares of the
+    ///   same color) have to be promoted, which needs a missing pawn for each of them.
+    /// - Pawns off their original file need a capture for every file they moved, so the
+    ///   opponent has to miss at least that many pieces.
+    ///
+    /// Passing these checks doesn't prove that the position is reachable. Positions of other
+    /// variants are never flagged.
+    pub fn reachability_error(&self) -> Option<String> {
+        if self.variant != Variant::Standard {
+            return None;
+        }
+
+        if !self.is_legal_setup() {
+            return Some(String::from("The position isn't a legal setup"));
+        }
+
+        let light_squares = (0..RANKS * FILES).filter(|i| (i / FILES + i % FILES) % 2 == 1).fold(0, |bb, i| bb | (1u64 << i));
+
+        for color in [WHITE, BLACK] {
+            let name = if color == WHITE { "White" } else { "Black" };
+            let count = |piece_type: u8| self.get_piece_bitboard(color, piece_type).count_ones();
+            let bishops = self.get_piece_bitboard(color, BISHOP);
+
+            let promoted = count(QUEEN).saturating_sub(1) + count(ROOK).saturating_sub(2) + count(KNIGHT).saturating_sub(2)
+                + (bishops & light_squares).count_ones().saturating_sub(1) + (bishops & !light_squares).count_ones().saturating_sub(1);
+
+            if promoted > RANKS as u32 - count(PAWN) {
+                return Some(format!("{} has {} promoted pieces but only {} missing pawns", name, promoted, RANKS as u32 - count(PAWN)));
+            }
+
+            let pieces: u32 = (PAWN..=KING).map(|t| self.get_piece_bitboard(flip_color(color), t).count_ones()).sum();
+            let captures = pawn_captures(self.get_piece_bitboard(color, PAWN));
+
+            if captures > 2 * RANKS as u32 - pieces {
+                return Some(format!("{}'s pawns need {} captures but only {} opposing pieces are missing", name, captures, 2 * RANKS as u32 - pieces));
+            }
+        }
+
+        return None;
+    }
+
+    /// Encode this position as a FEN including the fields of the variant (see from_variant_fen).
+    /// The position doesn't keep track of the halfmove clock and the move number, so they are
+    /// always set to 0 and 1.
+    pub fn to_fen(&self) -> String {
+        let geometry = self.variant.geometry();
+        let mut ranks: Vec<String> = Vec::new();
+
+        for rk in (0..geometry.ranks).rev() {
+            let mut rank = String::new();
+            let mut empty = 0;
+
+            for fl in 0..geometry.files {
+                match self.get_piece_and_color_at(coords_to_index(rk, fl)) {
+                    Some((t, c)) => {
+                        if empty > 0 {
+                            rank.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+
+                        let piece = match t {
+                            PAWN => 'P',
+                            _    => piece_to_string(t)
+                        };
+
+                        rank.push(if c == WHITE { piece } else { piece.to_ascii_lowercase() });
+
+                        if bb_ops::index_lookup(self.promoted, coords_to_index(rk, fl)) && self.variant.has_drops() {
+                            rank.push('~');
+                        }
+                    },
+                    None => empty += 1
+                }
+            }
+
+            if empty > 0 {
+                rank.push_str(&empty.to_string());
+            }
+
+            ranks.push(rank);
+        }
+
+        let turn = if self.turn == WHITE { "w" } else { "b" };
+
+        let mut castling = String::new();
+
+        for (allowed, c) in [(self.ks_castle[WHITE as usize], 'K'), (self.qs_castle[WHITE as usize], 'Q'),
+                             (self.ks_castle[BLACK as usize], 'k'), (self.qs_castle[BLACK as usize], 'q')] {
+            if allowed {
+                castling.push(c);
+            }
+        }
+
+        if castling.is_empty() {
+            castling.push('-');
+        }
+
+        let en_passant = self.get_en_passant_square().and_then(index_to_string).unwrap_or_else(|| String::from("-"));
+
+        let mut placement = ranks.join("/");
+
+        if self.variant.has_drops() {
+            placement.push('[');
+
+            for color in [WHITE, BLACK] {
+                for piece_type in [QUEEN, ROOK, BISHOP, KNIGHT, PAWN] {
+                    let piece = if piece_type == PAWN { 'P' } else { piece_to_string(piece_type) };
+                    let piece = if color == WHITE { piece } else { piece.to_ascii_lowercase() };
+
+                    for _ in 0..self.pocket.count(color, piece_type) {
+                        placement.push(piece);
+                    }
+                }
+            }
+
+            placement.push(']');
+        }
+
+        let checks = match self.variant.check_limit() {
+            Some(limit) => format!(" {}+{}", limit - self.checks[WHITE as usize], limit - self.checks[BLACK as usize]),
+            None        => String::new()
+        };
+
+        return format!("{} {} {} {}{} 0 1", placement, turn, castling, en_passant, checks);
     }
 
-    /// Board manipulation functions ///
+    // Board manipulation functions //
 
     /// Add a piece of a given type at the square given by the index.
     fn add_piece(&mut self, color: u8, piece_type: u8, idx: u8) {
         let piece_idx = color * NUM_PIECE_TYPES + piece_type;
 
+        if !bb_ops::index_lookup(self.piece_bbs[piece_idx as usize], idx) {
+            self.zobrist ^= zobrist::piece(piece_idx, idx);
+
+            if piece_type == PAWN {
+                self.pawn_zobrist ^= zobrist::piece(piece_idx, idx);
+            }
+
+            #[cfg(feature = "nnue")]
+            if let Some(accumulator) = &mut self.accumulator {
+                accumulator.add(color, piece_type, idx);
+            }
+        }
+
         self.piece_bbs[piece_idx as usize] = bb_ops::set_idx_bit(self.piece_bbs[piece_idx as usize], idx);
     }
 
     /// Remove a piece from the square given by the index.
     fn remove_piece(&mut self, idx: u8) {
         for piece_idx in 0..2*NUM_PIECE_TYPES {
+            if bb_ops::index_lookup(self.piece_bbs[piece_idx as usize], idx) {
+                self.zobrist ^= zobrist::piece(piece_idx, idx);
+
+                if piece_idx % NUM_PIECE_TYPES == PAWN {
+                    self.pawn_zobrist ^= zobrist::piece(piece_idx, idx);
+                }
+
+                #[cfg(feature = "nnue")]
+                if let Some(accumulator) = &mut self.accumulator {
+                    accumulator.remove(piece_idx / NUM_PIECE_TYPES, piece_idx % NUM_PIECE_TYPES, idx);
+                }
+            }
+
             self.piece_bbs[piece_idx as usize] = bb_ops::erase_idx_bit(self.piece_bbs[piece_idx as usize], idx);
         }
     }
 
+    /// Put a piece into or take it out of a pocket, keeping the hash up to date. Returns false
+    /// if a piece should be taken out that isn't in the pocket.
+    fn update_pocket(&mut self, color: u8, piece_type: u8, add: bool) -> bool {
+        let before = self.pocket.count(color, piece_type);
+
+        let updated = if add {
+            self.pocket.add(color, piece_type);
+            true
+        } else {
+            self.pocket.remove(color, piece_type)
+        };
+
+        self.zobrist ^= zobrist::pocket(color, piece_type, before) ^ zobrist::pocket(color, piece_type, self.pocket.count(color, piece_type));
+
+        return updated;
+    }
+
+    /// Set the square that can be captured en passant.
+    fn set_en_passant(&mut self, en_passant: Option<u8>) {
+        self.zobrist ^= zobrist::en_passant(self.en_passant) ^ zobrist::en_passant(en_passant);
+        self.en_passant = en_passant;
+    }
+
     /// Change whose turn it is by flipping the color.
     pub fn flip_turn(&mut self) {
+        self.zobrist ^= zobrist::side(WHITE) ^ zobrist::side(BLACK);
         self.turn = flip_color(self.turn);
     }
 
+    /// Returns the position with the colors swapped and the board flipped vertically, so the
+    /// other player is to move in the same situation. A correct evaluation scores both the same
+    /// for the player to move.
+    pub fn mirror(&self) -> Position {
+        let ranks = self.variant.geometry().ranks;
+        let flip_bb = |bb: u64| bb.swap_bytes() >> (8 * (8 - ranks as u32));
+        let flip_sq = |sq: u8| (ranks - 1 - sq / 8) * 8 + sq % 8;
+
+        let mut piece_bbs = [0; 12];
+        let mut pocket = Pocket::new();
+
+        for color in [WHITE, BLACK] {
+            for piece_type in 0..NUM_PIECE_TYPES {
+                piece_bbs[(flip_color(color) * NUM_PIECE_TYPES + piece_type) as usize] = flip_bb(self.get_piece_bitboard(color, piece_type));
+
+                for _ in 0..self.pocket.count(color, piece_type) {
+                    pocket.add(flip_color(color), piece_type);
+                }
+            }
+        }
+
+        return Position {
+            turn: flip_color(self.turn),
+            piece_bbs,
+            qs_castle: [self.qs_castle[1], self.qs_castle[0]],
+            ks_castle: [self.ks_castle[1], self.ks_castle[0]],
+            en_passant: self.en_passant.map(flip_sq),
+            repetitions: HashMap::new(),
+            variant: self.variant,
+            pocket,
+            promoted: flip_bb(self.promoted),
+            checks: [self.checks[1], self.checks[0]],
+            zobrist: 0,
+            pawn_zobrist: 0,
+            #[cfg(feature = "nnue")]
+            accumulator: None
+        }.with_zobrist();
+    }
+
     /// Apply a new move to this board. Currently, this function doesn't check the legality of the move
     /// because it's assumed that the given move was generated by the move generator.
     pub fn make_move(&self, m: Move) -> Position {
@@ -297,26 +880,52 @@ impl Position {
                     _       => piece_type
                 };
 
+                // Captured pieces go to the capturing player's pocket in Crazyhouse.
+                if let Some(captured) = self.captured_piece(m).filter(|_| self.variant.keeps_captures()) {
+                    position.update_pocket(position.turn, captured, true);
+                }
+
                 // Remove the captured piece from the target square.
-                match captures {
-                    Some(_) => {
-                        position.remove_piece(target);
-                    }
-                    _       => ()
-                };
+                if captures.is_some() {
+                    position.remove_piece(target);
+                }
+
+                // A pawn capturing en passant removes the pawn next to its origin square.
+                if piece_type == PAWN && self.en_passant == Some(target) {
+                    let captured_sq = match position.turn {
+                        WHITE => target - FILES,
+                        _     => target + FILES
+                    };
+
+                    position.remove_piece(captured_sq);
+                }
 
                 // Add the piece to the target square.
                 position.add_piece(position.turn, tgt_type, target);
 
-                position.en_passant = en_passant;
+                // Keep track of promoted pieces as they move across the board.
+                let was_promoted = promotes_to.is_some() || bb_ops::index_lookup(self.promoted, origin);
+                position.promoted &= !(bb_ops::index_lookup_mask(origin) | bb_ops::index_lookup_mask(target));
+
+                if was_promoted {
+                    position.promoted |= bb_ops::index_lookup_mask(target);
+                }
+
+                position.update_castling_rights(origin);
+                position.update_castling_rights(target);
+                position.set_en_passant(en_passant);
+            },
+            Move::Drop(piece_type, target) => {
+                position.update_pocket(position.turn, piece_type, false);
+                position.add_piece(position.turn, piece_type, target);
+
+                position.set_en_passant(None);
             },
             Move::ShortCastle => {
                 // Determine the squares the pieces are positioned at / will be 
                 // moved to.
-                let h =  match position.turn {
-                    WHITE => bb_ops::coords_to_index(0, 7),
-                    _     => bb_ops::coords_to_index(7, 7)
-                };
+                let geometry = position.variant.geometry();
+                let h = bb_ops::coords_to_index(geometry.back_rank(position.turn), geometry.files - 1);
 
                 let g = h - 1;
                 let f = g - 1;
@@ -324,25 +933,22 @@ impl Position {
 
                 // Move the king to his new square.
                 position.remove_piece(e);
-                position.add_piece(WHITE, KING, g);
+                position.add_piece(position.turn, KING, g);
 
-                // Move the queen to his new square.
+                // Move the rook to its new square.
                 position.remove_piece(h);
-                position.add_piece(WHITE, ROOK, f);
+                position.add_piece(position.turn, ROOK, f);
 
                 // Disallow future castling.
                 position.remove_castling_rights(position.turn);
 
                 // No en passant possible after this move.
-                position.en_passant = None;
+                position.set_en_passant(None);
             },
             Move::LongCastle => {
                 // Determine the squares the pieces are positioned at / will be 
                 // moved to.
-                let e = match position.turn {
-                    WHITE => bb_ops::coords_to_index(0, 4),
-                    _     => bb_ops::coords_to_index(7, 4)
-                };
+                let e = bb_ops::coords_to_index(position.variant.geometry().back_rank(position.turn), 4);
 
                 let d = e - 1;
                 let c = d - 1;
@@ -350,61 +956,203 @@ impl Position {
 
                 // Move the king to his new square.
                 position.remove_piece(e);
-                position.add_piece(WHITE, KING, c);
+                position.add_piece(position.turn, KING, c);
 
-                // Move the queen to his new square.
+                // Move the rook to its new square.
                 position.remove_piece(a);
-                position.add_piece(WHITE, ROOK, d);
+                position.add_piece(position.turn, ROOK, d);
 
                 position.remove_castling_rights(position.turn);
 
                 // No en passant possible after this move.
-                position.en_passant = None;
+                position.set_en_passant(None);
             }
         }
 
         position.flip_turn();
 
-        // Add the current position to the repetitions map of the next position.
-        if !position.repetitions.contains_key(&self) {
-            position.repetitions.insert(self.clone(), 0);
+        if self.variant.check_limit().is_some() && position.is_checked(position.turn) {
+            let checks = &mut position.checks[self.turn as usize];
+
+            position.zobrist ^= zobrist::checks(self.turn, *checks) ^ zobrist::checks(self.turn, *checks + 1);
+            *checks += 1;
         }
 
-        position.repetitions.insert(self.clone(), position.repetitions.get(&self).unwrap() + 1);
+        // Add the current position to the repetitions map of the next position.
+        *position.repetitions.entry(self.zobrist).or_insert(0) += 1;
 
         return position;
     }
 
-    /// Returns true if the side specified by the given color still has the right
-    /// to castle kingside.
-    pub fn can_castle_kingside(&self, color: u8) -> bool {
-        return self.ks_castle[color as usize];
+    /// Passes the turn to the opponent without moving a piece, which is used by the search to
+    /// find positions where even passing wins. The position isn't added to the repetitions.
+    /// Returns the en passant square to give to unmake_null_move.
+    pub fn make_null_move(&mut self) -> Option<u8> {
+        let en_passant = self.en_passant;
+
+        self.set_en_passant(None);
+        self.flip_turn();
+
+        return en_passant;
     }
 
-    /// Returns true if the side specified by the given color still has the right
-    /// to castle queenside.
+    /// Takes back a null move, restoring the en passant square it returned.
+    pub fn unmake_null_move(&mut self, en_passant: Option<u8>) {
+        self.flip_turn();
+        self.set_en_passant(en_passant);
+    }
+
+    /// Returns the square a pawn of the player whose turn it is skips when making a double
+    /// step from origin to target. Following the convention of modern FEN and Polyglot
+    /// implementations, the square is only returned if an opposing pawn could actually
+    /// capture en passant.
+    pub fn en_passant_after_double_step(&self, origin: u8, target: u8) -> Option<u8> {
+        let skipped = (origin + target) / 2;
+
+        // The squares a pawn on the skipped square would attack are exactly the squares
+        // from which opposing pawns could capture en passant.
+        let capturers = pawns::get_pawn_captures(self.turn, skipped) & self.get_piece_bitboard(flip_color(self.turn), PAWN);
+
+        return if capturers != 0 { Some(skipped) } else { None };
+    }
+
+    /// Returns the square that can be captured en passant in this position if an opposing
+    /// pawn is actually able to do so.
+    pub fn get_en_passant_square(&self) -> Option<u8> {
+        return self.en_passant.filter(|ep| {
+            let capturers = pawns::get_pawn_captures(flip_color(self.turn), *ep) & self.get_piece_bitboard(self.turn, PAWN);
+
+            return capturers != 0;
+        });
+    }
+
+    /// Returns the variant this position belongs to.
+    pub fn variant(&self) -> Variant {
+        return self.variant;
+    }
+
+    /// Returns the number of checks the player with the given color has given so far in
+    /// variants that count them.
+    pub fn checks_given(&self, color: u8) -> u8 {
+        return self.checks[color as usize];
+    }
+
+    /// Returns true if the player whose turn it is lost because of a rule of the variant,
+    /// e.g. by receiving the third check in Three-check.
+    pub fn is_variant_loss(&self) -> bool {
+        return self.variant.check_limit().is_some_and(|limit| self.checks[flip_color(self.turn) as usize] >= limit);
+    }
+
+    /// Returns the pieces both players hold in their pockets.
+    pub fn pocket(&self) -> &Pocket {
+        return &self.pocket;
+    }
+
+    /// Puts a piece into the pocket of the player with the given color. This is used to pass
+    /// the pieces captured on the partner's board in Bughouse.
+    pub fn feed_pocket(&mut self, color: u8, piece_type: u8) {
+        self.update_pocket(color, piece_type, true);
+    }
+
+    /// Returns the type of the piece captured by the given move the way it goes into a
+    /// pocket, i.e. promoted pieces turn back into pawns.
+    pub fn captured_piece(&self, m: Move) -> Option<u8> {
+        return match m {
+            Move::StandardMove(_, _, target, Some(captured), _, _) => {
+                if captured != PAWN && bb_ops::index_lookup(self.promoted, target) {
+                    Some(PAWN)
+                } else {
+                    Some(captured)
+                }
+            },
+            _ => None
+        };
+    }
+
+    /// Returns a bitboard marking the squares on which pawns of the given color promote,
+    /// i.e. the opponent's back rank.
+    pub fn get_promotion_squares(&self, color: u8) -> u64 {
+        let geometry = self.variant.geometry();
+
+        return geometry.rank_mask(geometry.back_rank(flip_color(color)));
+    }
+
+    /// Returns true if the side specified by the given color still has the right
+    /// to castle kingside.
+    pub fn can_castle_kingside(&self, color: u8) -> bool {
+        return self.ks_castle[color as usize];
+    }
+
+    /// Returns true if the side specified by the given color still has the right
+    /// to castle queenside.
     pub fn can_castle_queenside(&self, color: u8) -> bool {
         return self.qs_castle[color as usize];
     }
 
     /// Strips the player with the given color off his castling rights.
     pub fn remove_castling_rights(&mut self, color: u8) {
-        self.ks_castle[color as usize] = false;
-        self.qs_castle[color as usize] = false;
+        self.set_castling_rights(color, false);
     }
 
     /// Restores the castling rights for the player of the given color.
     pub fn restore_castling_rights(&mut self, color: u8) {
-        self.ks_castle[color as usize] = true;
-        self.qs_castle[color as usize] = true;
+        self.set_castling_rights(color, true);
+    }
+
+    fn set_castling_rights(&mut self, color: u8, allowed: bool) {
+        self.set_castling_right(color, true, allowed);
+        self.set_castling_right(color, false, allowed);
+    }
+
+    fn set_castling_right(&mut self, color: u8, kingside: bool, allowed: bool) {
+        let rights = if kingside { &mut self.ks_castle } else { &mut self.qs_castle };
+
+        if rights[color as usize] != allowed {
+            self.zobrist ^= zobrist::castling(color, kingside);
+            rights[color as usize] = allowed;
+        }
+    }
+
+    /// Removes the castling rights that depend on a piece on the given square, i.e. both rights
+    /// of a player if it's the king's home square and one if it's a rook's home square. Called
+    /// with the origin and the target of a move, so moving the king or a rook and capturing a
+    /// rook at home lose the rights.
+    fn update_castling_rights(&mut self, sq: u8) {
+        let geometry = self.variant.geometry();
+
+        for color in [WHITE, BLACK] {
+            let back_rank = geometry.back_rank(color);
+
+            if sq == coords_to_index(back_rank, 4) {
+                self.set_castling_rights(color, false);
+            } else if sq == coords_to_index(back_rank, 0) {
+                self.set_castling_right(color, false, false);
+            } else if sq == coords_to_index(back_rank, geometry.files - 1) {
+                self.set_castling_right(color, true, false);
+            }
+        }
     }
 
     /// Returns true if the king with the given color is checked.
     pub fn is_checked(&self, color: u8) -> bool {
-        let attacked_squares = self.get_all_attack_bitboard(flip_color(color));
         let king_bb = self.piece_bbs[(color * NUM_PIECE_TYPES + KING) as usize];
 
-        return attacked_squares & king_bb != 0;
+        return king_bb != 0 && self.is_attacked(king_bb.trailing_zeros() as u8, flip_color(color));
+    }
+
+    /// Checks if a square is attacked by a piece of the given color. Instead of computing all
+    /// attacks of that color, the attacks of each piece type are generated from the square
+    /// and intersected with the pieces of that type, since attacks are symmetric.
+    pub fn is_attacked(&self, sq: u8, color: u8) -> bool {
+        let blockers = self.get_all_piece_bitboard(WHITE) | self.get_all_piece_bitboard(BLACK);
+        let diagonal = self.get_piece_bitboard(color, BISHOP) | self.get_piece_bitboard(color, QUEEN);
+        let straight = self.get_piece_bitboard(color, ROOK) | self.get_piece_bitboard(color, QUEEN);
+
+        return pawns::get_pawn_captures(flip_color(color), sq) & self.get_piece_bitboard(color, PAWN) != 0
+            || knights::get_knight_attacks(sq) & self.get_piece_bitboard(color, KNIGHT) != 0
+            || kings::get_king_attacks(sq) & self.get_piece_bitboard(color, KING) != 0
+            || bishops::get_bishop_attacks(sq, blockers) & diagonal != 0
+            || rooks::get_rook_attacks(sq, blockers) & straight != 0;
     }
 
     fn exposes_friendly_king(&self, m: Move) -> bool {
@@ -421,13 +1169,85 @@ impl Position {
         return self.get_all_legal_moves().is_empty();
     }
 
+    /// Estimates the number of bytes the position occupies including the positions it
+    /// remembers for detecting repetitions.
+    pub fn memory_usage(&self) -> usize {
+        let usage = mem::size_of::<Position>() + self.repetitions.capacity() * (mem::size_of::<u64>() + mem::size_of::<u8>());
+
+        #[cfg(feature = "nnue")]
+        let usage = usage + self.accumulator.as_ref().map_or(0, |_| mem::size_of::<Accumulator>());
+
+        return usage;
+    }
+
+    /// Returns the game phase derived from the remaining pieces besides pawns: MAX_PHASE with
+    /// the full set of pieces of the starting position down to 0 when only kings and pawns are
+    /// left. Pieces in the pockets count as well, and promotions can't raise the phase above
+    /// MAX_PHASE.
+    pub fn phase(&self) -> i32 {
+        let mut phase = 0;
+
+        for color in [WHITE, BLACK] {
+            for piece_type in 0..NUM_PIECE_TYPES {
+                let count = self.get_piece_bitboard(color, piece_type).count_ones() as i32 + self.pocket.count(color, piece_type) as i32;
+
+                phase += count * PHASE_WEIGHTS[piece_type as usize];
+            }
+        }
+
+        return phase.min(MAX_PHASE);
+    }
+
     pub fn is_threefold_repetition(&self) -> bool {
-        return self.repetitions.get(self).filter(|n| **n >= 2).is_some();
+        return self.repetitions.get(&self.zobrist).filter(|n| **n >= 2).is_some();
+    }
+
+    /// Returns the Zobrist hash identifying the position, e.g. for the transposition table.
+    /// Unlike the Hash implementation it includes the castling rights and the en passant
+    /// square, if a pawn can capture on it, because they change the legal moves. Repetitions
+    /// are detected by it as well.
+    pub fn hash_key(&self) -> u64 {
+        return self.zobrist;
+    }
+
+    /// Returns the Zobrist hash of the pawns of both players, which identifies the pawn
+    /// structure, e.g. for the pawn hash table.
+    pub fn pawn_key(&self) -> u64 {
+        return self.pawn_zobrist;
+    }
+
+    /// Returns the position with the accumulator of the network computed from scratch, so it
+    /// and the positions reached from it are evaluated with the network. The network only
+    /// knows regular chess, so positions of other variants and positions without a network
+    /// are evaluated classically.
+    #[cfg(feature = "nnue")]
+    pub fn with_network(&self, network: Option<Arc<Network>>) -> Position {
+        let mut position = self.clone();
+        position.accumulator = network.filter(|_| self.variant == Variant::Standard).map(|network| {
+            let mut accumulator = Accumulator::new(network);
+
+            for piece_idx in 0..2*NUM_PIECE_TYPES {
+                for sq in bb_ops::idx_bitscan(self.piece_bbs[piece_idx as usize]) {
+                    accumulator.add(piece_idx / NUM_PIECE_TYPES, piece_idx % NUM_PIECE_TYPES, sq);
+                }
+            }
+
+            return Box::new(accumulator);
+        });
+
+        return position;
+    }
+
+    /// Evaluates the position with the network from the point of view of the player whose
+    /// turn it is. Returns None if the position has no accumulator, see with_network.
+    #[cfg(feature = "nnue")]
+    pub fn nnue_evaluate(&self) -> Option<i32> {
+        return self.accumulator.as_ref().map(|accumulator| accumulator.evaluate(self.turn));
     }
 
     /// Returns the bitboard with the given index.
     pub fn get_bitboard(&self, idx: usize) -> u64 {
-        return self.piece_bbs[idx as usize];
+        return self.piece_bbs[idx];
     }
 
     /// Returns a bitboard containing all pieces of a given type and
@@ -438,7 +1258,7 @@ impl Position {
 
     /// Returns a bitboard containing all pieces irrespective of type
     /// for a given color.
-    fn get_all_piece_bitboard(&self, color: u8) -> u64 {
+    pub fn get_all_piece_bitboard(&self, color: u8) -> u64 {
         let mut all_pieces = 0;
 
         for piece_type in 0..NUM_PIECE_TYPES {
@@ -531,8 +1351,17 @@ impl Position {
     /// given position can move to (moving and capturing).
     fn get_pawn_moves_bb(&self, color: u8, idx: u8) -> u64 {
         let moves = pawns::get_pawn_moves(color, idx) & !self.get_all_blockers(color);
-        let captures = pawns::get_pawn_captures(color, idx) & self.get_opponent_blockers(color);
-        let double_steps = pawns::get_pawn_double_steps(color, idx) & !self.get_all_blockers(color);
+
+        // The pawn can also capture on the en passant square.
+        let en_passant_bb = self.en_passant.map_or(0, bb_ops::index_lookup_mask);
+        let captures = pawns::get_pawn_captures(color, idx) & (self.get_opponent_blockers(color) | en_passant_bb);
+
+        // A double step is only possible if the square in front of the pawn is empty.
+        let double_steps = if moves != 0 && self.variant.has_double_steps() {
+            pawns::get_pawn_double_steps(color, idx) & !self.get_all_blockers(color)
+        } else {
+            0
+        };
 
         return moves | captures | double_steps;
     }
@@ -616,7 +1445,7 @@ impl Position {
     /// Returns a bitboard marking all squares a piece of the given color, type and position 
     /// is attacking.
     fn get_piece_attacks_bb(&self, color: u8, idx: u8, piece_type: u8) -> u64 {
-        match piece_type {
+        let bb = match piece_type {
             PAWN    => self.get_pawn_attacks_bb(color, idx),
             KNIGHT  => self.get_knight_attacks_bb(color, idx),
             BISHOP  => self.get_bishop_attacks_bb(color, idx),
@@ -624,11 +1453,13 @@ impl Position {
             QUEEN   => self.get_queen_attacks_bb(color, idx),
             KING    => self.get_king_attacks_bb(idx),
             _       => 0
-        }
+        };
+
+        return bb & self.variant.geometry().board_mask();
     }
 
     /// Checks if a piece of a given type and color at a given position attacks a square.
-    fn attacks_square(&self, color: u8, idx: u8, piece_type: u8, sq: u8) -> bool {
+    pub fn attacks_square(&self, color: u8, idx: u8, piece_type: u8, sq: u8) -> bool {
         return bb_ops::index_lookup(self.get_piece_attacks_bb(color, idx, piece_type), sq);
     }
 
@@ -662,16 +1493,13 @@ impl Position {
 
                     let double_step_start_sq = match color {
                         WHITE => bb_ops::coords_to_index(1, fl),
-                        _     => bb_ops::coords_to_index(6, fl)
+                        _     => bb_ops::coords_to_index(RANKS - 2, fl)
                     };
 
-                    let double_step_rank = 3 + (color * 1);
+                    let double_step_rank = 3 + color;
 
-                    if rk == double_step_rank {
-                        match self.get_piece_at(color, double_step_start_sq) {
-                            Some(_) => occupiers.push(double_step_start_sq),
-                            None => ()
-                        };
+                    if self.variant.has_double_steps() && rk == double_step_rank && self.get_piece_at(color, double_step_start_sq).is_some() {
+                        occupiers.push(double_step_start_sq);
                     }
 
                     return occupiers;
@@ -684,7 +1512,7 @@ impl Position {
     /// Returns a bitboard marking all squares a piece with a given color, type
     /// and position can move to.
     fn get_piece_moves_bb(&self, color: u8, idx: u8, piece_type: u8) -> u64 {
-        match piece_type {
+        let bb = match piece_type {
             PAWN    => self.get_pawn_moves_bb(color, idx),
             KNIGHT  => self.get_knight_attacks_bb(color, idx),
             BISHOP  => self.get_bishop_attacks_bb(color, idx),
@@ -692,7 +1520,11 @@ impl Position {
             QUEEN   => self.get_queen_attacks_bb(color, idx),
             KING    => self.get_king_moves_bb(color, idx),
             _       => 0
-        }
+        };
+
+        // The move patterns are generated for the full 8x8 layout, so squares outside of
+        // smaller boards have to be removed.
+        return bb & self.variant.geometry().board_mask();
     }
 
     /// Returns a bitboard marking all squares attacked atleast once by pieces of a given
@@ -709,19 +1541,6 @@ impl Position {
         return bb;
     }
 
-    /// Returns a bitboard marking all squares attacked atleast once by pieces of a given color.
-    /// 
-    /// TODO: compute this once per position.
-    fn get_all_attacks_bb(&self, color: u8) -> u64 {
-        let mut bb: u64 = 0;
-
-        for piece_type in [PAWN, KNIGHT, BISHOP, ROOK, QUEEN, KING] {
-            bb |= self.get_all_piece_attacks_bb(color, piece_type);
-        }
-
-        return bb;
-    }
-
     /// Returns a bitboard marking all squares attacked atleast once by a pawnm of the
     /// given color.
     fn get_all_pawn_attacks_bb(&self, color: u8) -> u64 {
@@ -758,28 +1577,28 @@ impl Position {
         return self.get_all_piece_attacks_bb(color, KING);
     }
 
-    /// Computes all legal moves for a pawn of a given color at a given square. 
-    fn get_pawn_moves(&mut self, color: u8, idx: u8) -> Vec<Move> {
+    /// Computes all pseudo-legal moves for a pawn of a given color at a given square.
+    fn get_pseudo_legal_pawn_moves(&self, color: u8, idx: u8) -> Vec<Move> {
         let move_bb = self.get_piece_moves_bb(color, idx, PAWN);
         let mut moves = Vec::new();
         
         // Only look at all legal double steps for that pawn.
-        let double_step_bb = pawns::get_pawn_double_steps(color, idx);
+        let double_step_bb = move_bb & pawns::get_pawn_double_steps(color, idx);
 
         // Only look at the promotion moves for the pawn.
-        let promotion_bb = move_bb & pawns::get_pawn_promotion_squares(color);
+        let promotion_bb = move_bb & self.get_promotion_squares(color);
 
         // Only look at the non-promotion and non-double step moves for the pawn.
-        let move_bb = move_bb & !pawns::get_pawn_promotion_squares(color);
+        let move_bb = move_bb & !self.get_promotion_squares(color) & !double_step_bb;
 
         // Add all non-promotion moves.
         for target in bb_ops::idx_bitscan(move_bb) {
-            let captures = self.get_piece_at(flip_color(color), target);
-            let m = Move::StandardMove(PAWN, idx, target, captures, None, None);
+            let captures = match self.en_passant {
+                Some(ep) if ep == target => Some(PAWN),
+                _                        => self.get_piece_at(flip_color(color), target)
+            };
 
-            if !self.exposes_friendly_king(m) {
-                moves.push(m);
-            }
+            moves.push(Move::StandardMove(PAWN, idx, target, captures, None, None));
         }
 
         // Add all promotion moves.
@@ -788,34 +1607,21 @@ impl Position {
 
             // Add a move for each piece the pawn can promote to.
             for prom_tgt in [KNIGHT, BISHOP, ROOK, QUEEN] {
-                let m = Move::StandardMove(PAWN, idx, target, captures, Some(prom_tgt), None);
-
-                // Only allow moves that don't expose the king to a check.
-                if !self.exposes_friendly_king(m) {
-                    moves.push(m);
-                }
+                moves.push(Move::StandardMove(PAWN, idx, target, captures, Some(prom_tgt), None));
             } 
         }
 
         // Add all double step moves.
         for target in bb_ops::idx_bitscan(double_step_bb) {
-            let en_passant_square = match self.turn {
-                WHITE => target + FILES,
-                _     => target - FILES
-            };
-
-            let m = Move::StandardMove(PAWN, idx, target, None, None, Some(en_passant_square));
-
-            if !self.exposes_friendly_king(m) {
-                moves.push(m);
-            }
+            moves.push(Move::StandardMove(PAWN, idx, target, None, None, self.en_passant_after_double_step(idx, target)));
         }
 
         return moves;
     }
 
-    /// Computes all legal moves for a King of a given color at a given square. 
-    fn get_king_moves(&self, color: u8, idx: u8) -> Vec<Move> {
+    /// Computes all pseudo-legal moves for a King of a given color at a given square. The king
+    /// never moves to an attacked square and never castles out of, through or into check.
+    fn get_pseudo_legal_king_moves(&self, color: u8, idx: u8) -> Vec<Move> {
         let move_bb = self.get_piece_moves_bb(color, idx, KING);
         let mut moves = Vec::new();
 
@@ -825,45 +1631,49 @@ impl Position {
             moves.push(Move::StandardMove(KING, idx, target, captures, None, None));
         }
 
-        // All squares occupied by a piece or attacked by an opposing piece.
-        let attacked_blocked_squares = self.get_all_attack_bitboard(flip_color(color)) | self.get_all_blockers(color);
+        let attacked = self.get_all_attack_bitboard(flip_color(color));
+        let blocked = self.get_all_blockers(color);
 
-        // All squares that must not be occupied by any piece or attacked by opposing pieces in order for
-        // short castling to be legal.
+        // A king in check can't castle.
+        if self.is_checked(color) {
+            return moves;
+        }
+
+        // The squares between the king and the rook must be empty and the squares the king crosses
+        // or lands on must not be attacked. Kingside these are the same squares.
         let ks_castling = kings::get_ks_castling_squares(color);
 
-        if (attacked_blocked_squares & ks_castling) == 0 && self.can_castle_kingside(color) {
+        if ((attacked | blocked) & ks_castling) == 0 && self.can_castle_kingside(color) {
             moves.push(Move::ShortCastle);
         }
 
-        // All squares that must not be occupied by any piece or attacked by opposing pieces in order for
-        // long castling to be legal.
+        // Queenside the square next to the rook only has to be empty, the rook may pass an attacked
+        // square.
         let qs_castling = kings::get_qs_castling_squares(color);
+        let qs_safe = kings::get_qs_castling_safe_squares(color);
 
-        if (attacked_blocked_squares & qs_castling) == 0 && self.can_castle_queenside(color) {
+        if (blocked & qs_castling) == 0 && (attacked & qs_safe) == 0 && self.can_castle_queenside(color) {
             moves.push(Move::LongCastle);
         }
         
         return moves;
     }
 
-    /// Computes all legal moves of a piece of a given color and type on a given square.
-    pub fn get_piece_moves(&mut self, color: u8, idx: u8, piece_type: u8) -> Vec<Move> {
+    /// Computes all pseudo-legal moves of a piece of a given color and type on a given square, i.e.
+    /// all moves that follow the movement rules of the piece without considering whether they leave
+    /// the own king in check.
+    pub fn get_pseudo_legal_piece_moves(&self, color: u8, idx: u8, piece_type: u8) -> Vec<Move> {
         match piece_type {
-            PAWN => self.get_pawn_moves(color, idx),
-            KING => self.get_king_moves(color, idx),
+            PAWN => self.get_pseudo_legal_pawn_moves(color, idx),
+            KING => self.get_pseudo_legal_king_moves(color, idx),
             _    => {
                 let move_bb = self.get_piece_moves_bb(color, idx, piece_type);
                 let mut moves = Vec::new();
 
                 for target in bb_ops::idx_bitscan(move_bb) {
                     let captures = self.get_piece_at(flip_color(color), target);
-                    let m = Move::StandardMove(piece_type, idx, target, captures, None, None);
 
-                    // Only allow moves that don't expose the king to a check.  
-                    if !self.exposes_friendly_king(m) {
-                        moves.push(m);
-                    }
+                    moves.push(Move::StandardMove(piece_type, idx, target, captures, None, None));
                 }
 
                 return moves;
@@ -871,6 +1681,79 @@ impl Position {
         }        
     }
 
+    /// Returns a bitboard marking all squares a piece of the given type can be dropped on.
+    /// Pawns can't be dropped on the first or last rank.
+    fn get_drop_squares_bb(&self, piece_type: u8) -> u64 {
+        let geometry = self.variant.geometry();
+        let mut bb = geometry.board_mask() & !self.get_all_blockers(self.turn);
+
+        if piece_type == PAWN {
+            bb &= !geometry.rank_mask(0) & !geometry.rank_mask(geometry.ranks - 1);
+        }
+
+        return bb;
+    }
+
+    /// Computes all pseudo-legal drops of the pieces the given player holds in the pocket.
+    pub fn get_drop_moves(&self, color: u8) -> Vec<Move> {
+        let mut moves = Vec::new();
+
+        if !self.variant.has_drops() {
+            return moves;
+        }
+
+        for piece_type in self.pocket.piece_types(color) {
+            for target in bb_ops::idx_bitscan(self.get_drop_squares_bb(piece_type)) {
+                moves.push(Move::Drop(piece_type, target));
+            }
+        }
+
+        return moves;
+    }
+
+    /// Checks if the given move is a drop the player whose turn it is can make according to the
+    /// pieces in the pocket and the occupied squares.
+    pub fn is_drop_possible(&self, m: Move) -> bool {
+        return match m {
+            Move::Drop(piece_type, target) => {
+                self.variant.has_drops() && DROPPABLE_PIECES.contains(&piece_type)
+                    && self.pocket.count(self.turn, piece_type) > 0
+                    && bb_ops::index_lookup(self.get_drop_squares_bb(piece_type), target)
+            },
+            _ => false
+        };
+    }
+
+    /// Computes all pseudo-legal moves in this position for the player whose turn it is. Use is_legal
+    /// to check whether one of them is actually legal.
+    pub fn get_pseudo_legal_moves(&self) -> Vec<Move> {
+        let mut moves: Vec<Move> = Vec::new();
+
+        for piece_type in [PAWN, KNIGHT, BISHOP, ROOK, QUEEN, KING] {
+            for piece in self.get_piece_indices(self.turn, piece_type) {
+                moves.append(&mut self.get_pseudo_legal_piece_moves(self.turn, piece, piece_type));
+            }
+        }
+
+        moves.append(&mut self.get_drop_moves(self.turn));
+
+        return moves;
+    }
+
+    /// Checks if a pseudo-legal move of the player whose turn it is is legal, i.e. it doesn't leave
+    /// the own king in check.
+    pub fn is_legal(&self, m: Move) -> bool {
+        return !self.exposes_friendly_king(m);
+    }
+
+    /// Computes all legal moves of a piece of a given color and type on a given square.
+    pub fn get_piece_moves(&mut self, color: u8, idx: u8, piece_type: u8) -> Vec<Move> {
+        let moves = self.get_pseudo_legal_piece_moves(color, idx, piece_type);
+
+        // Only allow moves that don't expose the king to a check.
+        return moves.into_iter().filter(|m| self.is_legal(*m)).collect();
+    }
+
     /// Computes all legal moves in this position for the player whose turn it is.
     pub fn get_all_legal_moves(&mut self) -> Vec<Move> {
         return self.get_all_moves(self.turn);
@@ -884,6 +1767,9 @@ impl Position {
             moves.append(&mut self.get_all_piece_moves(color, piece_type))
         }
 
+        let drops = self.get_drop_moves(color);
+        moves.extend(drops.into_iter().filter(|m| self.is_legal(*m)));
+
         return moves;
     }
 
@@ -899,55 +1785,238 @@ impl Position {
         return moves;
     }
 
+    /// Computes all legal captures in this position for the player whose turn it is, including en
+    /// passant and promotions that capture. Only the targets occupied by opposing pieces are
+    /// generated, which is cheaper than filtering all legal moves.
+    pub fn get_all_legal_captures(&self) -> Vec<Move> {
+        let color = self.turn;
+        let en_passant_bb = self.en_passant.map_or(0, bb_ops::index_lookup_mask);
+        let mut moves = Vec::new();
+
+        for piece_type in [PAWN, KNIGHT, BISHOP, ROOK, QUEEN, KING] {
+            for idx in self.get_piece_indices(color, piece_type) {
+                let mut targets = self.get_piece_moves_bb(color, idx, piece_type) & self.get_opponent_blockers(color);
+
+                if piece_type == PAWN {
+                    targets |= pawns::get_pawn_captures(color, idx) & en_passant_bb;
+                }
+
+                for target in bb_ops::idx_bitscan(targets) {
+                    // The only capture of an empty square is en passant.
+                    let captures = self.get_piece_at(flip_color(color), target).or(Some(PAWN));
+
+                    if piece_type == PAWN && bb_ops::index_lookup(self.get_promotion_squares(color), target) {
+                        for prom_tgt in [KNIGHT, BISHOP, ROOK, QUEEN] {
+                            moves.push(Move::StandardMove(PAWN, idx, target, captures, Some(prom_tgt), None));
+                        }
+                    } else {
+                        moves.push(Move::StandardMove(piece_type, idx, target, captures, None, None));
+                    }
+                }
+            }
+        }
+
+        return moves.into_iter().filter(|m| self.is_legal(*m)).collect();
+    }
+
+    /// Scores every legal move with a search of the given depth, which includes the move itself,
+    /// so depth 1 statically evaluates the position after each move. The scores are from the
+    /// point of view of the player whose turn it is and the moves are sorted from best to worst.
+    /// This is meant for shallow searches, e.g. to hint moves in a GUI.
+    pub fn evaluate_all_moves(&self, depth: u8) -> Vec<(Move, i32)> {
+        return search::evaluate_moves(self, depth, &search::Limits::default());
+    }
+
+    /// Convert a move into the long algebraic notation used by UCI (e.g. e2e4 or e7e8q).
+    pub fn move_to_uci(&self, m: Move) -> String {
+        let back_rank = self.variant.geometry().back_rank(self.turn);
+
+        let (origin, target, promotes_to) = match m {
+            Move::StandardMove(_, origin, target, _, promotes_to, _) => (origin, target, promotes_to),
+            Move::ShortCastle => (coords_to_index(back_rank, 4), coords_to_index(back_rank, 6), None),
+            Move::LongCastle  => (coords_to_index(back_rank, 4), coords_to_index(back_rank, 2), None),
+            Move::Drop(piece_type, target) => return drop_to_string(piece_type, target)
+        };
+
+        let promotion = promotes_to.map_or(String::new(), |t| piece_to_string(t).to_ascii_lowercase().to_string());
+
+        return format!("{}{}{}", index_to_string(origin).unwrap(), index_to_string(target).unwrap(), promotion);
+    }
+
+    /// Given a move in the long algebraic notation used by UCI, return the corresponding
+    /// legal move. Returns None if there is no such legal move.
+    pub fn uci_to_move(&self, string: &str) -> Option<Move> {
+        let string = string.to_lowercase();
+
+        return self.clone().get_all_legal_moves().into_iter().find(|m| self.move_to_uci(*m) == string);
+    }
+
     /// Given a string representing a move, return the corresponding move.
     pub fn string_to_move(&self, string: &str) -> Option<Move> {
         // Convert the string into a lower case string.
         let lower_s = string.to_lowercase();
         let string = lower_s.as_str();
 
+        // Drops (e.g. N@f3) have to be checked first as the target square would also be
+        // matched by the pattern for standard moves.
+        if let Some(m) = DROP_PATTERN.captures(string) {
+            let piece_type = m.name("type")
+                .and_then(|m| m.as_str().chars().next())
+                .map_or(PAWN, string_to_piece);
+
+            let target = m.name("target")
+                .and_then(|m| string_to_index(String::from(m.as_str())))?;
+
+            return Some(Move::Drop(piece_type, target));
+        }
+
         // Check if the string satisfies the notation rules of a standard chess move.
-        let mv = STD_MOVE_PATTERN.captures(string).map(|m| {
+        let mv = STD_MOVE_PATTERN.captures(string).and_then(|m| {
             // Extract the piece type encoded in the notation by first getting the character 
             // and then decoding it to a piece type.
             let piece_type = m.name("type")
-                .and_then(|m| m.as_str().chars().nth(0))
-                .map_or(PAWN, |m| string_to_piece(m));
+                .and_then(|m| m.as_str().chars().next())
+                .map_or(PAWN, string_to_piece);
 
             
             // Extract the target square and encode it as a number between 0 and 64.
             let target = m.name("target")
-                .and_then(|m| string_to_index(String::from(m.as_str()))).unwrap();
+                .and_then(|m| string_to_index(String::from(m.as_str())))?;
 
             // The list of pieces that could possibly move to the target square.
             let candidates = self.can_move_to(self.turn, piece_type, target);
 
-            let origin = m.name("origin")
-                .and_then(|m| string_to_index(String::from(m.as_str())))
-                .map_or_else(|| *candidates.get(0).unwrap(), |idx| idx);
+            // Without an origin square the first candidate moves. If there is none, the
+            // string doesn't describe a move in this position.
+            let origin = match m.name("origin").and_then(|m| string_to_index(String::from(m.as_str()))) {
+                Some(origin) => origin,
+                None         => *candidates.first()?
+            };
 
             let captures = m.name("captures")
                 .and_then(|_| self.get_piece_at(flip_color(self.turn), target));
 
-            let promotes = None; //m.name("promotes").map(|m| string_to_piece(m.as_str().chars().nth(1).unwrap()));
+            // The promotion group has the form =q, so the piece is the second character.
+            let promotes = m.name("promotes")
+                .and_then(|m| m.as_str().chars().nth(1))
+                .map(string_to_piece);
 
-            return Move::StandardMove(piece_type, origin, target, captures, promotes, None)
+            return Some(Move::StandardMove(piece_type, origin, target, captures, promotes, None));
         });
 
         return mv.or(SCASTLE_PATTERN.captures(string).map(|_| Move::ShortCastle))
                  .or(LCASTLE_PATTERN.captures(string).map(|_| Move::LongCastle));
     }
 
-    pub fn move_to_string(&self, m: Move) -> &str {
-        return "foo";
+    /// Convert a legal move into standard algebraic notation (e.g. Nbd7, exd5+ or Qh4#).
+    pub fn move_to_san(&self, m: Move) -> String {
+        return format!("{}{}", self.move_to_san_without_suffix(m), self.check_suffix(m));
+    }
+
+    /// Returns the suffix marking a move in SAN as giving check (+) or mate (#).
+    fn check_suffix(&self, m: Move) -> &'static str {
+        let mut next = self.make_move(m);
+
+        if !next.is_checked(next.turn) {
+            return "";
+        }
+
+        return if next.get_all_legal_moves().is_empty() { "#" } else { "+" };
+    }
+
+    /// Convert a legal move into standard algebraic notation without the check suffix.
+    fn move_to_san_without_suffix(&self, m: Move) -> String {
+        let (piece_type, origin, target, captures, promotes_to) = match m {
+            Move::StandardMove(piece_type, origin, target, captures, promotes_to, _) => (piece_type, origin, target, captures, promotes_to),
+            Move::ShortCastle => return String::from("O-O"),
+            Move::LongCastle  => return String::from("O-O-O"),
+            Move::Drop(piece_type, target) => return drop_to_string(piece_type, target)
+        };
+
+        let (origin_rk, origin_fl) = bb_ops::index_to_coords(origin);
+        let mut san = String::new();
+
+        if piece_type == PAWN {
+            // Pawn captures are identified by the file the pawn came from.
+            if captures.is_some() {
+                san.push(file_to_string(origin_fl).unwrap());
+            }
+        } else {
+            san.push(piece_to_string(piece_type));
+
+            // Find all other pieces of the same type that could move to the same square.
+            let others: Vec<u8> = self.clone().get_all_piece_moves(self.turn, piece_type).into_iter()
+                .filter_map(|other| match other {
+                    Move::StandardMove(_, o, t, _, _, _) if t == target && o != origin => Some(o),
+                    _ => None
+                })
+                .collect();
+
+            if !others.is_empty() {
+                let same_file = others.iter().any(|o| bb_ops::index_to_coords(*o).1 == origin_fl);
+                let same_rank = others.iter().any(|o| bb_ops::index_to_coords(*o).0 == origin_rk);
+
+                if !same_file {
+                    san.push(file_to_string(origin_fl).unwrap());
+                } else if !same_rank {
+                    san.push(rank_to_string(origin_rk).unwrap());
+                } else {
+                    san.push_str(&index_to_string(origin).unwrap());
+                }
+            }
+        }
+
+        if captures.is_some() {
+            san.push('x');
+        }
+
+        san.push_str(&index_to_string(target).unwrap());
+
+        if let Some(t) = promotes_to {
+            san.push('=');
+            san.push(piece_to_string(t));
+        }
+
+        return san;
+    }
+
+    /// Given a move in standard algebraic notation, return the corresponding legal move.
+    /// Annotation suffixes (e.g. !?) are ignored and so is a check or mate suffix that
+    /// doesn't match the resulting position. Use parse_san to find out about the latter.
+    pub fn san_to_move(&self, string: &str) -> Option<Move> {
+        return self.parse_san(string).map(|(m, _)| m);
+    }
+
+    /// Given a move in standard algebraic notation, return the corresponding legal move
+    /// and whether the check or mate suffix given in the notation is correct.
+    pub fn parse_san(&self, string: &str) -> Option<(Move, bool)> {
+        let string = string.trim_end_matches(['!', '?']);
+        let mut san = string.trim_end_matches(['+', '#']).replace('0', "O");
+        let suffix = &string[san.len()..];
+
+        // Accept pawn drops without the piece letter (e.g. @e4).
+        if san.starts_with('@') {
+            san.insert(0, 'P');
+        }
+
+        // Accept promotions without the equals sign (e.g. e8Q).
+        if let Some(piece) = san.strip_suffix(['N', 'B', 'R', 'Q']).filter(|rest| rest.ends_with(|c: char| c.is_ascii_digit())) {
+            san = format!("{}={}", piece, &san[piece.len()..]);
+        }
+
+        let m = self.clone().get_all_legal_moves().into_iter().find(|m| self.move_to_san_without_suffix(*m) == san)?;
+
+        return Some((m, self.check_suffix(m) == suffix));
     }
 }
 
-impl ToString for Position {
-    fn to_string(&self) -> String {
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let geometry = self.variant.geometry();
         let mut rows: Vec<String> = Vec::new();
 
-        for rk in (0..8).rev() {
-            let col_indices = (0..8).map(|fl| bb_ops::coords_to_index(rk, fl));
+        for rk in (0..geometry.ranks).rev() {
+            let col_indices = (0..geometry.files).map(|fl| bb_ops::coords_to_index(rk, fl));
             let col_strings: Vec<String> = col_indices.map(|idx| {
                 return match self.get_piece_and_color_at(idx) {
                     Some((t, c)) =>  {
@@ -969,7 +2038,7 @@ impl ToString for Position {
             rows.push(col_strings.join(" "));
         }
 
-        return rows.join("\n");
+        return write!(f, "{}", rows.join("\n"));
     }
 }
 
@@ -977,11 +2046,505 @@ impl Hash for Position {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.turn.hash(state);
         self.piece_bbs.hash(state);
+        self.pocket.hash(state);
+        self.checks.hash(state);
     }
 }
 
 impl PartialEq for Position {
     fn eq(&self, other: &Self) -> bool {
-        return self.turn == other.turn && self.piece_bbs == other.piece_bbs;
+        return self.turn == other.turn && self.piece_bbs == other.piece_bbs && self.pocket == other.pocket && self.checks == other.checks;
+    }
+}
+
+impl Eq for Position {}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+    use crate::pgn;
+
+    fn position(fen: &str) -> Position {
+        return Position::from_fen(fen.to_string()).unwrap();
+    }
+
+    fn square(name: &str) -> u8 {
+        return string_to_index(name.to_string()).unwrap();
+    }
+
+    fn promotions(position: &mut Position) -> Vec<Move> {
+        return position.get_all_legal_moves().into_iter()
+            .filter(|m| matches!(m, Move::StandardMove(_, _, _, _, Some(_), _)))
+            .collect();
+    }
+
+    #[test]
+    fn generates_all_promotions() {
+        let mut pos = position("8/4P3/8/8/8/8/8/k3K3 w - - 0 1");
+        let moves = promotions(&mut pos);
+
+        assert_eq!(moves.len(), 4);
+
+        for piece_type in [KNIGHT, BISHOP, ROOK, QUEEN] {
+            assert!(moves.contains(&Move::StandardMove(PAWN, 52, 60, None, Some(piece_type), None)));
+        }
+    }
+
+    #[test]
+    fn generates_capture_promotions() {
+        let mut pos = position("3r4/4P3/8/8/8/8/8/k3K3 w - - 0 1");
+        let moves = promotions(&mut pos);
+
+        assert_eq!(moves.len(), 8);
+        assert!(moves.contains(&Move::StandardMove(PAWN, 52, 59, Some(ROOK), Some(KNIGHT), None)));
+    }
+
+    #[test]
+    fn generates_black_promotions() {
+        let mut pos = position("4k3/8/8/8/8/8/p7/4K3 b - - 0 1");
+        let moves = promotions(&mut pos);
+
+        assert_eq!(moves.len(), 4);
+        assert!(moves.contains(&Move::StandardMove(PAWN, 8, 0, None, Some(QUEEN), None)));
+    }
+
+    #[test]
+    fn make_move_replaces_the_pawn() {
+        let pos = position("8/4P3/8/8/8/8/8/k3K3 w - - 0 1");
+        let next = pos.make_move(Move::StandardMove(PAWN, 52, 60, None, Some(KNIGHT), None));
+
+        assert_eq!(next.get_piece_and_color_at(60), Some((KNIGHT, WHITE)));
+        assert_eq!(next.get_piece_and_color_at(52), None);
+        assert_eq!(next.get_piece_bitboard(WHITE, PAWN), 0);
+    }
+
+    #[test]
+    fn make_move_with_capture_promotion() {
+        let pos = position("3r4/4P3/8/8/8/8/8/k3K3 w - - 0 1");
+        let next = pos.make_move(Move::StandardMove(PAWN, 52, 59, Some(ROOK), Some(QUEEN), None));
+
+        assert_eq!(next.get_piece_and_color_at(59), Some((QUEEN, WHITE)));
+        assert_eq!(next.get_piece_bitboard(BLACK, ROOK), 0);
+    }
+
+    #[test]
+    fn san_of_promotions() {
+        let pos = position("3r4/4P3/8/8/8/8/8/k3K3 w - - 0 1");
+
+        assert_eq!(pos.move_to_san(Move::StandardMove(PAWN, 52, 60, None, Some(KNIGHT), None)), "e8=N");
+        assert_eq!(pos.move_to_san(Move::StandardMove(PAWN, 52, 59, Some(ROOK), Some(QUEEN), None)), "exd8=Q");
+
+        let pos = position("r7/4P1k1/8/8/8/8/8/7K w - - 0 1");
+
+        assert_eq!(pos.move_to_san(Move::StandardMove(PAWN, 52, 60, None, Some(KNIGHT), None)), "e8=N+");
+    }
+
+    #[test]
+    fn parse_san_promotions() {
+        let pos = position("r7/4P1k1/8/8/8/8/8/7K w - - 0 1");
+        let knight = Move::StandardMove(PAWN, 52, 60, None, Some(KNIGHT), None);
+
+        assert_eq!(pos.parse_san("e8=N+"), Some((knight, true)));
+        assert_eq!(pos.parse_san("e8N+"), Some((knight, true)));
+        assert_eq!(pos.parse_san("e8=N"), Some((knight, false)));
+        assert_eq!(pos.parse_san("e8=R").map(|(m, _)| m), Some(Move::StandardMove(PAWN, 52, 60, None, Some(ROOK), None)));
+        assert_eq!(pos.parse_san("e8"), None);
+    }
+
+    #[test]
+    fn uci_promotions() {
+        let pos = position("3r4/4P3/8/8/8/8/8/k3K3 w - - 0 1");
+        let m = Move::StandardMove(PAWN, 52, 59, Some(ROOK), Some(KNIGHT), None);
+
+        assert_eq!(pos.uci_to_move("e7d8n"), Some(m));
+        assert_eq!(pos.uci_to_move("e7e8q"), Some(Move::StandardMove(PAWN, 52, 60, None, Some(QUEEN), None)));
+        assert_eq!(pos.uci_to_move("e7e8"), None);
+        assert_eq!(pos.move_to_uci(m), "e7d8n");
+    }
+
+    #[test]
+    fn string_to_move_keeps_promotions() {
+        let pos = position("8/4P3/8/8/8/8/8/k3K3 w - - 0 1");
+
+        assert_eq!(pos.string_to_move("e8=Q"), Some(Move::StandardMove(PAWN, 52, 60, None, Some(QUEEN), None)));
+    }
+
+    #[test]
+    fn pgn_round_trip_with_promotions() {
+        let start = position("3r4/4P1k1/8/8/8/8/8/K7 w - - 0 1");
+        let mut game = Game::from_position(start);
+
+        game.play(Move::StandardMove(PAWN, 52, 59, Some(ROOK), Some(BISHOP), None));
+
+        let text = pgn::write_game(&game, &[], "*");
+        let games = pgn::read_games(&text);
+
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].game.moves(), game.moves());
+        assert!(text.contains("exd8=B"));
+    }
+
+    #[test]
+    fn removes_the_pawn_captured_en_passant() {
+        let pos = position("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1");
+        let after = pos.make_move(Move::StandardMove(PAWN, square("d4"), square("e3"), None, None, None));
+
+        assert_eq!(after.get_piece_at(BLACK, square("e3")), Some(PAWN));
+        assert_eq!(after.get_piece_at(WHITE, square("e4")), None);
+        assert_eq!(after.get_piece_bitboard(WHITE, PAWN), 0);
+
+        let pos = position("4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 1");
+        let after = pos.make_move(Move::StandardMove(PAWN, square("d5"), square("e6"), None, None, None));
+
+        assert_eq!(after.get_piece_at(WHITE, square("e6")), Some(PAWN));
+        assert_eq!(after.get_piece_bitboard(BLACK, PAWN), 0);
+    }
+
+    #[test]
+    fn en_passant_only_recorded_when_capturable() {
+        let pos = position("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        let next = pos.make_move(pos.uci_to_move("e2e4").unwrap());
+
+        assert_eq!(next.to_fen(), "4k3/8/8/8/4P3/8/8/4K3 b - - 0 1");
+
+        let pos = position("4k3/8/8/8/3p4/8/4P3/4K3 w - - 0 1");
+        let next = pos.make_move(pos.uci_to_move("e2e4").unwrap());
+
+        assert_eq!(next.to_fen(), "4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1");
+    }
+
+    #[test]
+    fn fen_drops_uncapturable_en_passant() {
+        let pos = position("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1");
+
+        assert_eq!(pos.get_en_passant_square(), None);
+        assert_eq!(pos.to_fen(), "4k3/8/8/8/4P3/8/8/4K3 b - - 0 1");
+    }
+
+    #[test]
+    fn repetitions_need_the_same_castling_rights_and_en_passant() {
+        // Returns the plies after which a threefold repetition is claimed.
+        let claims = |fen: &str, moves: &str| -> Vec<usize> {
+            let mut pos = position(fen);
+
+            return moves.split_whitespace().enumerate().filter_map(|(i, m)| {
+                pos = pos.make_move(pos.uci_to_move(m).unwrap());
+
+                return if pos.is_threefold_repetition() { Some(i + 1) } else { None };
+            }).collect();
+        };
+
+        let king_walks = "e1e2 e8e7 e2e1 e7e8 ".repeat(3);
+
+        // The kings lose their castling rights with their first moves, so the positions before
+        // that don't count towards repetitions.
+        assert_eq!(claims("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", &king_walks), vec![10, 11, 12]);
+        assert_eq!(claims("r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1", &king_walks), vec![8, 9, 10, 11, 12]);
+
+        // Black could capture en passant right after e4, so that position doesn't count either.
+        let walks = format!("e2e4 {}", "e8d8 e1d1 d8e8 d1e1 ".repeat(3));
+
+        assert_eq!(claims("4k3/8/8/8/3p4/8/4P3/4K3 w - - 0 1", &walks), vec![10, 11, 12, 13]);
+        assert_eq!(claims("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1", &walks), vec![9, 10, 11, 12, 13]);
+    }
+
+    #[test]
+    fn captures_en_passant() {
+        let pos = position("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1");
+        let m = pos.uci_to_move("d4e3").unwrap();
+
+        assert_eq!(m, Move::StandardMove(PAWN, 27, 20, Some(PAWN), None, None));
+        assert_eq!(pos.make_move(m).to_fen(), "4k3/8/8/8/8/4p3/8/4K3 w - - 0 1");
+    }
+
+    fn assert_incremental_hashes(pos: &mut Position, depth: u8) {
+        assert_eq!(pos.hash_key(), pos.compute_zobrist(), "{}", pos.to_fen());
+        assert_eq!(pos.pawn_key(), pos.compute_pawn_zobrist(), "{}", pos.to_fen());
+
+        if depth > 0 {
+            for m in pos.get_all_legal_moves() {
+                assert_incremental_hashes(&mut pos.make_move(m), depth - 1);
+            }
+        }
+    }
+
+    #[test]
+    fn zobrist_hash_is_updated_incrementally() {
+        let fens = [
+            (Variant::Standard, "r3k2r/2P2ppp/8/pP1pP3/8/8/P1PP1P1P/R3K2R w KQkq d6 0 1"),
+            (Variant::Crazyhouse, "r3k2r/ppp2ppp/8/3pP3/8/8/PPP2PPP/R3K2R[QNp] w KQkq d6 0 1"),
+            (Variant::ThreeCheck, "4k3/8/8/8/8/8/3Q4/4K3 w - - +2+0 0 1")
+        ];
+
+        for (variant, fen) in fens {
+            assert_incremental_hashes(&mut Position::from_variant_fen(variant, fen.to_string()).unwrap(), 2);
+        }
+
+        // Transpositions reach the same hash while the castling rights and the side to move
+        // tell positions apart.
+        let pos = position("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        let moved = pos.make_move(pos.uci_to_move("e1f1").unwrap());
+        let castled = pos.make_move(Move::ShortCastle);
+
+        assert_ne!(pos.hash_key(), position("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").hash_key());
+        assert_ne!(pos.hash_key(), position("r3k2r/8/8/8/8/8/8/R3K2R w Qkq - 0 1").hash_key());
+        assert_eq!(castled.hash_key(), position("r3k2r/8/8/8/8/8/8/R4RK1 b kq - 0 1").hash_key());
+        assert_eq!(moved.hash_key(), moved.compute_zobrist());
+    }
+
+    #[test]
+    fn null_moves_pass_the_turn() {
+        let mut pos = position("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1");
+        let before = pos.clone();
+        let en_passant = pos.make_null_move();
+
+        assert_eq!(en_passant, Some(20));
+        assert_eq!(pos.to_fen(), "4k3/8/8/8/3pP3/8/8/4K3 w - - 0 1");
+        assert_eq!(pos.hash_key(), pos.compute_zobrist());
+
+        pos.unmake_null_move(en_passant);
+
+        assert_eq!(pos.to_fen(), before.to_fen());
+        assert_eq!(pos.hash_key(), before.hash_key());
+    }
+
+    #[test]
+    fn double_steps_need_an_empty_path() {
+        let mut pos = position("4k3/8/8/8/8/4n3/4P3/4K3 w - - 0 1");
+
+        assert_eq!(pos.get_all_piece_moves(WHITE, PAWN), Vec::new());
+
+        let mut pos = position("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+
+        assert_eq!(pos.get_all_piece_moves(WHITE, PAWN).len(), 2);
+    }
+
+    #[test]
+    fn pseudo_legal_moves_include_pinned_pieces() {
+        let mut pos = position("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1");
+        let pinned = pos.get_pseudo_legal_moves().into_iter()
+            .filter(|m| matches!(m, Move::StandardMove(KNIGHT, _, _, _, _, _)))
+            .collect::<Vec<Move>>();
+
+        assert_eq!(pinned.len(), 6);
+        assert!(pinned.iter().all(|m| !pos.is_legal(*m)));
+        assert!(pos.get_all_piece_moves(WHITE, KNIGHT).is_empty());
+    }
+
+    #[test]
+    fn castling_moves_the_pieces_of_the_side_to_move() {
+        let pos = position("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1");
+
+        assert_eq!(pos.make_move(Move::ShortCastle).to_fen(), "r4rk1/8/8/8/8/8/8/R3K2R w KQ - 0 1");
+        assert_eq!(pos.make_move(Move::LongCastle).to_fen(), "2kr3r/8/8/8/8/8/8/R3K2R w KQ - 0 1");
+    }
+
+    #[test]
+    fn generates_castling_moves() {
+        let castles = |fen: &str| {
+            let mut pos = position(fen);
+
+            return pos.get_all_legal_moves().into_iter().filter(|m| matches!(m, Move::ShortCastle | Move::LongCastle)).count();
+        };
+
+        // Both players can castle to both sides, even with the other back rank occupied.
+        assert_eq!(castles("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1"), 2);
+        assert_eq!(castles("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1"), 2);
+        assert_eq!(castles("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1"), 2);
+
+        // Without the rights, with pieces in between, in check or through check.
+        assert_eq!(castles("r3k2r/8/8/8/8/8/8/R3K2R w kq - 0 1"), 0);
+        assert_eq!(castles("r3k2r/8/8/8/8/8/8/RN2K1NR w KQkq - 0 1"), 0);
+        assert_eq!(castles("r3k2r/8/8/8/8/8/8/R2NKN1R w KQkq - 0 1"), 0);
+        assert_eq!(castles("r3k2r/8/8/8/8/8/4r3/R3K2R w KQkq - 0 1"), 0);
+        assert_eq!(castles("r3k2r/8/8/8/8/8/5r2/R3K2R w KQkq - 0 1"), 1);
+        assert_eq!(castles("r3k2r/8/8/8/8/8/3r4/R3K2R w KQkq - 0 1"), 1);
+        assert_eq!(castles("r3k2r/8/8/8/8/8/2r5/R3K2R w KQkq - 0 1"), 1);
+
+        // The queenside rook may pass an attacked square.
+        assert_eq!(castles("r3k2r/8/8/8/8/8/1r6/R3K2R w KQkq - 0 1"), 2);
+        assert_eq!(castles("rn2k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1"), 1);
+    }
+
+    #[test]
+    fn king_and_rook_moves_lose_castling_rights() {
+        let pos = position("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        let after = |uci: &str| pos.make_move(pos.uci_to_move(uci).unwrap());
+
+        assert_eq!(after("h1h2").to_fen(), "r3k2r/8/8/8/8/8/7R/R3K3 b Qkq - 0 1");
+        assert_eq!(after("e1e2").to_fen(), "r3k2r/8/8/8/8/8/4K3/R6R b kq - 0 1");
+        assert_eq!(after("a1a8").to_fen(), "R3k2r/8/8/8/8/8/8/4K2R b Kk - 0 1");
+        assert!(after("a1a8").hash_key() == position("R3k2r/8/8/8/8/8/8/4K2R b Kk - 0 1").hash_key());
+    }
+
+    #[test]
+    fn generates_only_legal_captures() {
+        for fen in ["r1bqkbnr/pppp1ppp/2n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3",
+                    "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+                    "1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1",
+                    "4k3/8/8/8/1b6/8/3N4/4K2r w - - 0 1"] {
+            let mut pos = position(fen);
+            let mut captures = pos.get_all_legal_captures();
+            let mut expected: Vec<Move> = pos.get_all_legal_moves().into_iter()
+                .filter(|m| matches!(m, Move::StandardMove(_, _, _, Some(_), _, _)))
+                .collect();
+
+            captures.sort_by_key(|m| format!("{:?}", m));
+            expected.sort_by_key(|m| format!("{:?}", m));
+
+            assert_eq!(captures, expected, "{}", fen);
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn evaluate_all_moves_ranks_captures_first() {
+        let pos = position("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1");
+        let moves = pos.evaluate_all_moves(1);
+
+        assert_eq!(moves.len(), pos.clone().get_all_legal_moves().len());
+        assert_eq!(pos.move_to_san(moves[0].0), "Rxd5");
+        assert_eq!(moves[0].1, 560);
+        assert!(moves.windows(2).all(|w| w[0].1 >= w[1].1));
+    }
+
+    #[test]
+    fn mirrors_positions() {
+        let pos = position("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQK2R b Kq e3 0 1");
+        let mirrored = pos.mirror();
+
+        assert_eq!(mirrored.to_fen(), "rnbqk2r/pppp1ppp/8/3Pp3/8/8/PPP1PPPP/RNBQKBNR w Qk e6 0 1");
+        assert!(mirrored.mirror() == pos);
+        assert_eq!(mirrored.mirror().hash_key(), pos.hash_key());
+
+        let pos = Position::from_variant_fen(Variant::Gardner, String::from("rnbqk/pp1pp/5/PPpPP/RNBQK b - - 0 1")).unwrap();
+        assert_eq!(pos.mirror().to_fen(), "rnbqk/ppPpp/5/PP1PP/RNBQK w - - 0 1");
+    }
+
+    #[test]
+    fn evaluate_all_moves_finds_mates() {
+        let pos = position("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        let moves = pos.evaluate_all_moves(2);
+
+        assert_eq!(pos.move_to_san(moves[0].0), "Ra8#");
+        assert_eq!(moves[0].1, search::MATE_SCORE - 1);
+    }
+
+    #[test]
+    fn reachability_heuristics() {
+        assert!(position("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").is_reachable());
+
+        // Two promoted queens but only one missing pawn.
+        let pos = position("rnbqkbnr/pppppppp/8/8/8/1QQ5/PPPPPPP1/RNBQKBNR w KQkq - 0 1");
+        assert_eq!(pos.reachability_error(), Some(String::from("White has 2 promoted pieces but only 1 missing pawns")));
+
+        // Two dark-squared bishops with all pawns on the board.
+        assert!(!position("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNB w Qkq - 0 1").is_reachable());
+
+        // The doubled a-pawn needs a capture, so black has to miss a piece.
+        assert!(!position("rnbqkbnr/pppppppp/8/8/8/P7/P1PPPPPP/RNBQKBNR w KQkq - 0 1").is_reachable());
+        assert!(position("rnbqkb1r/pppppppp/8/8/8/P7/P1PPPPPP/RNBQKBNR w KQkq - 0 1").is_reachable());
+    }
+
+    #[test]
+    fn attacked_squares_match_the_attack_maps() {
+        for fen in ["r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+                    "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP2BPPP/R2QKB1R w KQ - 0 1"] {
+            let pos = position(fen);
+
+            for color in [WHITE, BLACK] {
+                // The attack maps leave out the squares of the own pieces.
+                let attacked = pos.get_all_attack_bitboard(color);
+                let own = pos.get_all_piece_bitboard(color);
+
+                for sq in (0..64).filter(|sq| !bb_ops::index_lookup(own, *sq)) {
+                    assert_eq!(pos.is_attacked(sq, color), bb_ops::index_lookup(attacked, sq), "{} in {}", sq, fen);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parsers_reject_malformed_input() {
+        let pos = Position::starting_position();
+
+        assert!(Position::from_fen(format!("{}/8/8/8/8/8/8/8 w - - 0 1", "9".repeat(40))).is_none());
+        assert!(Position::from_variant_fen(Variant::Crazyhouse, String::from("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/9~[] w - - 0 1")).is_none());
+        assert!(Position::from_variant_fen(Variant::Crazyhouse, format!("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[{}] w - - 0 1", "Q".repeat(300))).is_none());
+
+        // No queen can reach e5, so there is no piece to take the origin from.
+        assert_eq!(pos.string_to_move("Qe5"), None);
+        assert_eq!(pos.string_to_move("Nxe5"), None);
+        assert_eq!(pos.parse_san("é4"), None);
+        assert_eq!(pos.uci_to_move("e2e4é"), None);
+    }
+
+    #[test]
+    fn parsers_survive_mutated_input() {
+        const SEEDS: [&str; 4] = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[Qp] w KQkq e3 3+2 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R w HAha - 0 1",
+            "e2e4 e7e8q Nbd7 exd5+ O-O-O 0-0 N@f3 @e4 e8Q# Qh4#!?",
+            "rnbqk/ppppp/5/PPPPP/RNBQK w - - 0 1"
+        ];
+        const ALPHABET: &[char] = &['r', 'n', 'b', 'q', 'k', 'p', 'R', 'N', 'B', 'Q', 'K', 'P', '0', '1', '8', '9',
+                                    ' ', '~', '[', ']', '/', '-', '+', '#', '@', '=', 'x', 'O', 'a', 'h', 'é'];
+
+        let pos = Position::starting_position();
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+        let mut random = move |n: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+
+            return state as usize % n;
+        };
+
+        for i in 0..400 {
+            let mut chars: Vec<char> = SEEDS[i % SEEDS.len()].chars().collect();
+
+            for _ in 0..=random(4) {
+                let at = random(chars.len() + 1);
+
+                match random(3) {
+                    0 => chars.insert(at, ALPHABET[random(ALPHABET.len())]),
+                    1 if at < chars.len() => { chars.remove(at); },
+                    _ => chars.splice(at..at, (0..random(40)).map(|_| ALPHABET[random(ALPHABET.len())])).for_each(drop)
+                }
+            }
+
+            let input: String = chars.into_iter().collect();
+
+            for variant in [Variant::Standard, Variant::Gardner, Variant::Crazyhouse, Variant::ThreeCheck] {
+                Position::from_variant_fen(variant, input.clone());
+            }
+
+            for word in input.split_whitespace() {
+                pos.uci_to_move(word);
+                pos.string_to_move(word);
+                pos.parse_san(word);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_round_trip() {
+        let pos = position("r3k2r/8/8/8/3pP3/8/8/R3K2R b Kq e3 0 1");
+        let json = serde_json::to_string(&pos).unwrap();
+        let back: Position = serde_json::from_str(&json).unwrap();
+
+        assert!(back == pos);
+        assert_eq!(back.to_fen(), pos.to_fen());
+        assert_eq!(back.get_en_passant_square(), Some(20));
+        assert_eq!((back.can_castle_kingside(WHITE), back.can_castle_queenside(WHITE)), (true, false));
+        assert_eq!((back.can_castle_kingside(BLACK), back.can_castle_queenside(BLACK)), (false, true));
+
+        let m = pos.uci_to_move("d4e3").unwrap();
+        let json = serde_json::to_string(&m).unwrap();
+
+        assert_eq!(serde_json::from_str::<Move>(&json).unwrap(), m);
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt;
+
+use crate::moves::Move;
+use crate::position::{Position, WHITE};
+
+
+/// A white first move that defeats all black defences but one. The defence that still
+/// avoids mate is called the refutation.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Try {
+    pub m: Move,
+    pub refutation: Move
+}
+
+/// A black move in the diagram position with the side to move switched and the white
+/// continuations that force mate after it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SetPlay {
+    pub defence: Move,
+    pub mates: Vec<Move>
+}
+
+/// The solution of a directmate problem: the side to move mates in a given number of moves
+/// against any defence.
+pub struct Solution {
+    pub position: Position,
+    pub moves: u32,
+    /// The first moves solving the problem. A sound problem has exactly one key, further
+    /// ones are cooks.
+    pub keys: Vec<Move>,
+    pub tries: Vec<Try>,
+    /// The set play or None if the side to move is in check, so the diagram position can't
+    /// be looked at with the other side to move.
+    pub set_play: Option<Vec<SetPlay>>
+}
+
+/// Solves the directmate in the given number of moves in the position, including its set
+/// play and tries.
+pub fn solve(position: &Position, moves: u32) -> Solution {
+    let mut keys = Vec::new();
+    let mut tries = Vec::new();
+
+    for m in position.clone().get_all_legal_moves() {
+        let after = position.make_move(m);
+
+        if forces_mate(&after, moves) {
+            keys.push(m);
+        } else if let [refutation] = refutations(&after, moves).as_slice() {
+            tries.push(Try { m, refutation: *refutation });
+        }
+    }
+
+    let set_play = switch_sides(position).map(|flipped| {
+        flipped.clone().get_all_legal_moves().into_iter().map(|defence| {
+            let after = flipped.make_move(defence);
+            let mates = (1..moves).find_map(|n| Some(mating_moves(&after, n)).filter(|mates| !mates.is_empty()));
+
+            SetPlay { defence, mates: mates.unwrap_or_default() }
+        }).collect()
+    });
+
+    return Solution { position: position.clone(), moves, keys, tries, set_play };
+}
+
+/// Checks if the side to move can force mate in at most the given number of moves.
+pub fn is_mate_in(position: &Position, moves: u32) -> bool {
+    return moves > 0 && position.clone().get_all_legal_moves().into_iter().any(|m| forces_mate(&position.make_move(m), moves));
+}
+
+/// Returns the moves forcing mate in at most the given number of moves.
+pub fn mating_moves(position: &Position, moves: u32) -> Vec<Move> {
+    return position.clone().get_all_legal_moves().into_iter().filter(|&m| forces_mate(&position.make_move(m), moves)).collect();
+}
+
+/// Checks if the position, which arises after the attacker played the first of the given
+/// number of moves, is mate or every defence runs into mate with the remaining moves.
+fn forces_mate(position: &Position, moves: u32) -> bool {
+    let defences = position.clone().get_all_legal_moves();
+
+    if defences.is_empty() {
+        return position.is_checked(position.turn);
+    }
+
+    return moves > 1 && defences.into_iter().all(|d| is_mate_in(&position.make_move(d), moves - 1));
+}
+
+/// Returns the defences avoiding mate in the position, which arises after the attacker
+/// played the first of the given number of moves. Stalemate isn't a defence by a move, so
+/// no refutations are returned for it.
+fn refutations(position: &Position, moves: u32) -> Vec<Move> {
+    return position.clone().get_all_legal_moves().into_iter().filter(|&d| !is_mate_in(&position.make_move(d), moves - 1)).collect();
+}
+
+/// Returns the position with the other side to move or None if that isn't a legal position.
+fn switch_sides(position: &Position) -> Option<Position> {
+    let fen = position.to_fen();
+    let mut fields: Vec<&str> = fen.split(' ').collect();
+
+    fields[1] = if position.turn == WHITE { "b" } else { "w" };
+    fields[3] = "-";
+
+    return Position::from_variant_fen(position.variant(), fields.join(" "));
+}
+
+impl fmt::Display for Solution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let position = &self.position;
+
+        if let Some(set_play) = &self.set_play {
+            let flipped = switch_sides(position).unwrap();
+
+            for set in set_play.iter().filter(|set| !set.mates.is_empty()) {
+                let after = flipped.make_move(set.defence);
+                let mates: Vec<String> = set.mates.iter().map(|&m| after.move_to_san(m)).collect();
+
+                writeln!(f, "Set play: 1... {} 2. {}", flipped.move_to_san(set.defence), mates.join(", "))?;
+            }
+        }
+
+        for t in &self.tries {
+            let refutation = position.make_move(t.m).move_to_san(t.refutation);
+
+            writeln!(f, "Try: 1. {}? {}!", position.move_to_san(t.m), refutation)?;
+        }
+
+        match self.keys.as_slice() {
+            []   => writeln!(f, "No solution in {} moves.", self.moves)?,
+            keys => {
+                let keys: Vec<String> = keys.iter().map(|&m| format!("1. {}!", position.move_to_san(m))).collect();
+                let label = if keys.len() == 1 { "Key" } else { "Keys (cooked)" };
+
+                writeln!(f, "{}: {}", label, keys.join(" "))?;
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(fen: &str) -> Position {
+        return Position::from_fen(fen.to_string()).unwrap();
+    }
+
+    #[test]
+    fn solves_mate_in_one() {
+        let pos = position("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1");
+        let solution = solve(&pos, 1);
+
+        assert_eq!(solution.keys, vec![pos.san_to_move("Ra8").unwrap()]);
+        assert!(is_mate_in(&pos, 1));
+        assert!(!is_mate_in(&pos, 0));
+    }
+
+    #[test]
+    fn finds_key_tries_and_set_play() {
+        let pos = position("3r3k/6pp/6pp/8/4Q3/8/B7/K3R3 w - - 0 1");
+        let solution = solve(&pos, 2);
+
+        let qa8 = pos.san_to_move("Qa8").unwrap();
+        let refutation = pos.make_move(qa8).san_to_move("Rxa8").unwrap();
+
+        assert_eq!(solution.keys, vec![pos.san_to_move("Qe8+").unwrap()]);
+        assert!(solution.tries.contains(&Try { m: qa8, refutation }));
+
+        let text = solution.to_string();
+
+        assert!(text.contains("Set play: 1... Re8 2. Qxe8#\n"));
+        assert!(text.contains("Try: 1. Qa8? Rxa8!\n"));
+        assert!(text.ends_with("Key: 1. Qe8+!\n"));
+    }
+
+    #[test]
+    fn reports_unsolvable_problems() {
+        let pos = position("3r3k/6pp/6pp/8/8/8/B7/K3R3 w - - 0 1");
+        let solution = solve(&pos, 2);
+
+        assert!(solution.keys.is_empty());
+        assert!(solution.to_string().ends_with("No solution in 2 moves.\n"));
+    }
+
+    #[test]
+    fn no_set_play_when_in_check() {
+        let pos = position("3r3k/6pp/6pp/8/8/8/B7/3K4 w - - 0 1");
+
+        assert_eq!(solve(&pos, 1).set_play, None);
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashSet;
+
+use crate::moves::Move;
+use crate::position::{Position, WHITE, BLACK, PAWN, KING};
+
+
+/// Searches proof games: games from the starting position reaching a target position in an
+/// exact number of plies. The search is a depth-first search pruned by a lower bound of the
+/// moves each side still needs and by a table of positions that are known to fail.
+pub struct ProofGameSearch {
+    target: Position,
+    failed: HashSet<(Position, [bool; 4], Option<u8>, u32)>,
+    limit: usize,
+    found: Vec<Vec<Move>>
+}
+
+impl ProofGameSearch {
+    /// Prepares a search for games reaching the target position.
+    pub fn new(target: Position) -> ProofGameSearch {
+        return ProofGameSearch { target, failed: HashSet::new(), limit: 1, found: Vec::new() };
+    }
+
+    /// Finds up to limit games reaching the target in exactly the given number of plies.
+    /// Asking for two games verifies that a proof game is unique.
+    pub fn find(&mut self, plies: u32, limit: usize) -> Vec<Vec<Move>> {
+        self.limit = limit;
+        self.found.clear();
+        self.failed.clear();
+
+        let start = Position::variant_starting_position(self.target.variant());
+
+        // White moves first, so the target's side to move fixes the parity of the game.
+        if plies.is_multiple_of(2) == (self.target.turn == WHITE) {
+            self.search(&start, plies, &mut Vec::new());
+        }
+
+        return std::mem::take(&mut self.found);
+    }
+
+    /// Finds the shortest game reaching the target with at most the given number of plies.
+    pub fn shortest(&mut self, max_plies: u32) -> Option<Vec<Move>> {
+        return (0..=max_plies).find_map(|plies| self.find(plies, 1).pop());
+    }
+
+    fn search(&mut self, position: &Position, plies: u32, line: &mut Vec<Move>) {
+        if plies == 0 {
+            if self.reached(position) {
+                self.found.push(line.clone());
+            }
+
+            return;
+        }
+
+        if !self.reachable(position, plies) {
+            return;
+        }
+
+        let key = (position.clone(), castling_rights(position), position.get_en_passant_square(), plies);
+
+        if self.failed.contains(&key) {
+            return;
+        }
+
+        let found = self.found.len();
+
+        for m in position.clone().get_all_legal_moves() {
+            line.push(m);
+            self.search(&position.make_move(m), plies - 1, line);
+            line.pop();
+
+            if self.found.len() >= self.limit {
+                return;
+            }
+        }
+
+        if self.found.len() == found {
+            self.failed.insert(key);
+        }
+    }
+
+    /// Checks if the position matches the target including castling rights and en passant.
+    fn reached(&self, position: &Position) -> bool {
+        return *position == self.target
+            && castling_rights(position) == castling_rights(&self.target)
+            && position.get_en_passant_square() == self.target.get_en_passant_square();
+    }
+
+    /// Checks if the target might still be reached in the given number of plies. Captured
+    /// pieces and pawns can't come back and every move puts at most one piece on its target
+    /// square, except for castling which places two.
+    fn reachable(&self, position: &Position, plies: u32) -> bool {
+        for color in [WHITE, BLACK] {
+            let pieces = |p: &Position| (PAWN..=KING).map(|t| p.get_piece_bitboard(color, t).count_ones()).sum::<u32>();
+
+            if pieces(position) < pieces(&self.target) {
+                return false;
+            }
+
+            if position.get_piece_bitboard(color, PAWN).count_ones() < self.target.get_piece_bitboard(color, PAWN).count_ones() {
+                return false;
+            }
+
+            let misplaced: u32 = (PAWN..=KING).map(|t| (self.target.get_piece_bitboard(color, t) & !position.get_piece_bitboard(color, t)).count_ones()).sum();
+            let can_castle = position.can_castle_kingside(color) || position.can_castle_queenside(color);
+            let needed = if can_castle { misplaced.saturating_sub(1) } else { misplaced };
+
+            // The side to move gets the extra ply if the number of plies is odd.
+            let available = if color == position.turn { plies.div_ceil(2) } else { plies / 2 };
+
+            if needed > available {
+                return false;
+            }
+        }
+
+        return true;
+    }
+}
+
+fn castling_rights(position: &Position) -> [bool; 4] {
+    return [position.can_castle_kingside(WHITE), position.can_castle_queenside(WHITE),
+            position.can_castle_kingside(BLACK), position.can_castle_queenside(BLACK)];
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(fen: &str) -> ProofGameSearch {
+        return ProofGameSearch::new(Position::from_fen(fen.to_string()).unwrap());
+    }
+
+    #[test]
+    fn finds_all_move_orders() {
+        let mut search = search("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 0 1");
+        let games = search.find(3, 5);
+
+        assert_eq!(games.len(), 2);
+
+        for game in games {
+            let mut position = Position::starting_position();
+
+            for m in game {
+                position = position.make_move(m);
+            }
+
+            assert_eq!(position.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 0 1");
+        }
+    }
+
+    #[test]
+    fn finds_the_shortest_game() {
+        let mut search = search("rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq - 0 1");
+
+        assert_eq!(search.shortest(5).map(|game| game.len()), Some(1));
+        assert_eq!(search.find(2, 1), Vec::<Vec<Move>>::new());
+        assert_eq!(search.find(3, 1), Vec::<Vec<Move>>::new());
+        assert_eq!(search.find(5, 1).len(), 1);
+    }
+
+    #[test]
+    fn prunes_unreachable_targets() {
+        // Three pawn moves can't be made in two white moves.
+        let mut search = search("rnbqkbnr/pppppppp/8/8/8/PPP5/3PPPPP/RNBQKBNR b KQkq - 0 1");
+
+        assert_eq!(search.shortest(3), None);
+        assert_eq!(search.find(5, 1).len(), 1);
+    }
+}
@@ -0,0 +1,721 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, TcpListener, TcpStream};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::position::Position;
+use crate::search::{self, Limits, Score, MATE_SCORE};
+use crate::tt::{Replacement, TranspositionTable};
+
+
+/// The depth used by /analyze if the request doesn't give one.
+pub const DEFAULT_DEPTH: u8 = 3;
+
+/// Requests for deeper searches are capped, so a single request can't keep the server busy
+/// for long.
+pub const MAX_DEPTH: u8 = 5;
+
+/// The number of positions a single /analyze request may search. Together with the memory
+/// budget this bounds the resources of a request regardless of the position.
+pub const MAX_NODES: u64 = 2_000_000;
+
+/// The number of bytes the search of a single /analyze request may use.
+pub const MAX_MEMORY: usize = 16 * 1024 * 1024;
+
+/// The size of the transposition table of an /analyze request in MB.
+const TABLE_MB: usize = 4;
+
+/// Request bodies larger than this are rejected.
+const MAX_BODY: usize = 64 * 1024;
+
+/// Requests whose request line and headers together are larger than this are rejected.
+const MAX_HEAD: u64 = 8 * 1024;
+
+/// Reading a request or writing a response may stall for at most this long, so idle clients
+/// can't hold on to a worker.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The number of connections served at the same time.
+pub const WORKERS: usize = 4;
+
+/// The number of accepted connections waiting for a worker. Connections beyond that are
+/// turned away right away.
+const QUEUE: usize = 32;
+
+/// The number of /analyze responses the server keeps for repeated requests.
+pub const CACHE_ENTRIES: usize = 1024;
+
+/// The rate limiter forgets clients that have been idle for this long, once it tracks more
+/// than MAX_CLIENTS of them.
+const MAX_CLIENTS: usize = 10_000;
+
+
+/// A response to an HTTP request: the status code and a JSON body.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Response {
+    pub status: u16,
+    pub body: String
+}
+
+impl Response {
+    fn ok(body: String) -> Response {
+        return Response { status: 200, body };
+    }
+
+    fn error(status: u16, message: &str) -> Response {
+        return Response { status, body: format!("{{\"error\":{}}}", json_string(message)) };
+    }
+}
+
+/// Remembers the responses to the most recent /analyze requests, so requests for popular
+/// positions are answered without searching again. Responses are keyed by the hash of the
+/// position and the depth, which are all that determine the analysis. Once full, the least
+/// recently used response is dropped.
+pub struct AnalysisCache {
+    capacity: usize,
+    entries: HashMap<(u64, u8), (u64, Response)>,
+    /// The keys of the entries by the time they were last used.
+    recent: BTreeMap<u64, (u64, u8)>,
+    clock: u64
+}
+
+impl AnalysisCache {
+    pub fn new(capacity: usize) -> AnalysisCache {
+        return AnalysisCache { capacity, entries: HashMap::new(), recent: BTreeMap::new(), clock: 0 };
+    }
+
+    /// Returns the number of cached responses.
+    pub fn len(&self) -> usize {
+        return self.entries.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.entries.is_empty();
+    }
+
+    /// Returns the cached response to the analysis of the position with the given hash at the
+    /// given depth and marks it as used.
+    pub fn get(&mut self, hash: u64, depth: u8) -> Option<Response> {
+        self.clock += 1;
+
+        let (used, response) = self.entries.get_mut(&(hash, depth))?;
+
+        self.recent.remove(used);
+        self.recent.insert(self.clock, (hash, depth));
+        *used = self.clock;
+
+        return Some(response.clone());
+    }
+
+    /// Stores a response, dropping the least recently used one if the cache is full.
+    pub fn insert(&mut self, hash: u64, depth: u8, response: Response) {
+        self.clock += 1;
+
+        if let Some((used, _)) = self.entries.insert((hash, depth), (self.clock, response)) {
+            self.recent.remove(&used);
+        }
+
+        self.recent.insert(self.clock, (hash, depth));
+
+        while self.entries.len() > self.capacity {
+            let (_, key) = self.recent.pop_first().unwrap();
+            self.entries.remove(&key);
+        }
+    }
+}
+
+impl Default for AnalysisCache {
+    fn default() -> AnalysisCache {
+        return AnalysisCache::new(CACHE_ENTRIES);
+    }
+}
+
+/// Who may use the server and how often.
+#[derive(Clone, Default, Debug)]
+pub struct Access {
+    /// The tokens clients authenticate with by sending "Authorization: Bearer <token>". Without
+    /// tokens everyone may use the server.
+    pub tokens: Vec<String>,
+    /// The number of requests a single client (IP address) may send per minute, in bursts of
+    /// up to as many requests. Unlimited if None.
+    pub requests_per_minute: Option<u32>
+}
+
+impl Access {
+    /// Checks if a request with the given value of the Authorization header may use the server.
+    pub fn authorizes(&self, authorization: Option<&str>) -> bool {
+        if self.tokens.is_empty() {
+            return true;
+        }
+
+        let token = match authorization.and_then(|value| value.trim().strip_prefix("Bearer ")) {
+            Some(token) => token.trim(),
+            None        => return false
+        };
+
+        return self.tokens.iter().fold(false, |found, known| found | constant_time_eq(known.as_bytes(), token.as_bytes()));
+    }
+}
+
+/// Compares two byte strings in a time that only depends on their lengths, so the time taken
+/// to reject a token doesn't tell how much of it is right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    return a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0;
+}
+
+/// Limits the requests of each client with a token bucket: every client may send a burst of
+/// requests_per_minute requests, after which the bucket refills at that rate.
+pub struct RateLimiter {
+    requests_per_minute: u32,
+    /// The tokens left in each client's bucket when it was last updated.
+    buckets: HashMap<IpAddr, (f64, Instant)>
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_minute: u32) -> RateLimiter {
+        return RateLimiter { requests_per_minute, buckets: HashMap::new() };
+    }
+
+    /// Takes a token from the client's bucket if there is one at the given time. Returns
+    /// false if the client sent too many requests.
+    pub fn allow(&mut self, client: IpAddr, now: Instant) -> bool {
+        let capacity = self.requests_per_minute as f64;
+        let refill = capacity / 60.0;
+
+        if self.buckets.len() > MAX_CLIENTS {
+            // Clients with full buckets are the same as new ones.
+            self.buckets.retain(|_, (tokens, updated)| *tokens + now.saturating_duration_since(*updated).as_secs_f64() * refill < capacity);
+        }
+
+        let (tokens, updated) = self.buckets.entry(client).or_insert((capacity, now));
+
+        *tokens = (*tokens + now.saturating_duration_since(*updated).as_secs_f64() * refill).min(capacity);
+        *updated = now;
+
+        if *tokens < 1.0 {
+            return false;
+        }
+
+        *tokens -= 1.0;
+
+        return true;
+    }
+
+    /// Returns how long the client has to wait for its next request at the given time.
+    pub fn retry_after(&self, client: IpAddr, now: Instant) -> Duration {
+        let refill = self.requests_per_minute as f64 / 60.0;
+
+        return match self.buckets.get(&client) {
+            Some((tokens, updated)) if refill > 0.0 => {
+                let tokens = tokens + now.saturating_duration_since(*updated).as_secs_f64() * refill;
+                Duration::from_secs_f64(((1.0 - tokens) / refill).max(0.0))
+            },
+            Some(_) => Duration::MAX,
+            None    => Duration::ZERO
+        };
+    }
+}
+
+/// Checks if a request of the given client with the given Authorization header is admitted.
+/// Returns the response refusing it otherwise: 401 without a known token and 429 if the
+/// client exceeded its rate. Requests that fail authentication don't count against the rate.
+pub fn admit(access: &Access, limiter: Option<&mut RateLimiter>, client: IpAddr, authorization: Option<&str>, now: Instant) -> Option<Response> {
+    if !access.authorizes(authorization) {
+        return Some(Response::error(401, "Missing or invalid token"));
+    }
+
+    if let Some(limiter) = limiter {
+        if !limiter.allow(client, now) {
+            let wait = limiter.retry_after(client, now).as_secs().saturating_add(1);
+
+            return Some(Response::error(429, &format!("Too many requests, retry in {} s", wait)));
+        }
+    }
+
+    return None;
+}
+
+/// Serves the analysis endpoints on the given address until the process is stopped.
+/// Connections are handled by WORKERS threads and up to QUEUE more wait for them, further
+/// ones are answered with 503. Reading the request and writing the response time out after
+/// TIMEOUT and the request head is limited to MAX_HEAD bytes, so slow or idle clients can't
+/// block the others. The responses to the last CACHE_ENTRIES different /analyze requests are
+/// cached. Requests are admitted as configured by the access settings.
+///
+/// - POST /analyze takes {"fen": ..., "depth": ...} and returns the best move, its score
+///   in centipawns, the depth reached and the principal variation. The search is bounded by
+///   MAX_NODES and MAX_MEMORY, so it may stop short of the requested depth.
+/// - POST /legal-moves takes {"fen": ...} and returns the legal moves in SAN.
+pub fn serve(address: &str, access: &Access) -> io::Result<()> {
+    return serve_on(TcpListener::bind(address)?, access);
+}
+
+/// The state the workers of a server share.
+struct Server {
+    access: Access,
+    cache: Mutex<AnalysisCache>,
+    limiter: Option<Mutex<RateLimiter>>
+}
+
+/// Serves the analysis endpoints on the connections of the listener, see serve.
+fn serve_on(listener: TcpListener, access: &Access) -> io::Result<()> {
+    let server = Arc::new(Server {
+        access: access.clone(),
+        cache: Mutex::new(AnalysisCache::default()),
+        limiter: access.requests_per_minute.map(|rate| Mutex::new(RateLimiter::new(rate)))
+    });
+
+    let (sender, receiver) = mpsc::sync_channel::<TcpStream>(QUEUE);
+    let receiver = Arc::new(Mutex::new(receiver));
+
+    for _ in 0..WORKERS {
+        let (server, receiver) = (server.clone(), receiver.clone());
+
+        thread::spawn(move || loop {
+            let stream = match receiver.lock().unwrap().recv() {
+                Ok(stream) => stream,
+                Err(_)     => return
+            };
+
+            // A broken connection shouldn't take the server down.
+            if let Err(e) = handle_connection(stream, &server) {
+                eprintln!("Connection failed: {}", e);
+            }
+        });
+    }
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e)     => {
+                eprintln!("Connection failed: {}", e);
+                continue;
+            }
+        };
+
+        if let Err(mpsc::TrySendError::Full(stream)) = sender.try_send(stream) {
+            if let Err(e) = stream.set_write_timeout(Some(TIMEOUT)).and_then(|_| write_response(stream, &Response::error(503, "Server busy, try again later"))) {
+                eprintln!("Connection failed: {}", e);
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+/// Reads a single request from the connection and writes the response.
+fn handle_connection(stream: TcpStream, server: &Server) -> io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let client = stream.peer_addr()?.ip();
+    let mut reader = BufReader::new(stream.try_clone()?.take(MAX_HEAD));
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    let mut content_length = 0;
+    let mut authorization = None;
+
+    loop {
+        let mut header = String::new();
+
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            } else if name.trim().eq_ignore_ascii_case("authorization") {
+                authorization = Some(value.trim().to_string());
+            }
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let path = parts.next().unwrap_or("");
+
+    // The head used up the whole limit unless it ended with an empty line before.
+    let head_too_large = reader.get_ref().limit() == 0;
+
+    // Refused requests are answered before their body is read.
+    let refusal = {
+        let mut limiter = server.limiter.as_ref().map(|limiter| limiter.lock().unwrap());
+
+        admit(&server.access, limiter.as_deref_mut(), client, authorization.as_deref(), Instant::now())
+    };
+
+    let response = if head_too_large {
+        Response::error(431, "Request header too large")
+    } else if let Some(refusal) = refusal {
+        refusal
+    } else if content_length > MAX_BODY {
+        Response::error(413, "Request body too large")
+    } else {
+        let mut body = vec![0; content_length];
+        reader.get_mut().set_limit(content_length as u64);
+        reader.read_exact(&mut body)?;
+
+        handle(method, path, &String::from_utf8_lossy(&body), &server.cache)
+    };
+
+    return write_response(stream, &response);
+}
+
+/// Writes the response and closes the connection.
+fn write_response(mut stream: TcpStream, response: &Response) -> io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        503 => "Service Unavailable",
+        _   => ""
+    };
+
+    write!(stream, "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+           response.status, reason, response.body.len(), response.body)?;
+
+    return stream.flush();
+}
+
+/// Handles a request to the given path with the given body. Analyses are looked up in and
+/// stored into the cache, which isn't locked during the search, so workers can search
+/// different positions at the same time.
+pub fn handle(method: &str, path: &str, body: &str, cache: &Mutex<AnalysisCache>) -> Response {
+    if path != "/analyze" && path != "/legal-moves" {
+        return Response::error(404, "Unknown endpoint");
+    }
+
+    if method != "POST" {
+        return Response::error(405, "Use POST");
+    }
+
+    let fields = match parse_json_object(body) {
+        Some(fields) => fields,
+        None         => return Response::error(400, "Expected a JSON object")
+    };
+
+    let fen = match fields.get("fen") {
+        Some(fen) => fen,
+        None      => return Response::error(400, "Missing fen")
+    };
+
+    let position = match Position::from_fen(fen.clone()) {
+        Some(position) => position,
+        None           => return Response::error(400, "Invalid FEN")
+    };
+
+    if path == "/legal-moves" {
+        let moves: Vec<String> = position.clone().get_all_legal_moves().into_iter().map(|m| json_string(&position.move_to_san(m))).collect();
+
+        return Response::ok(format!("{{\"moves\":[{}]}}", moves.join(",")));
+    }
+
+    let depth = match fields.get("depth").map(|d| d.parse::<u8>()) {
+        Some(Ok(depth)) if depth > 0 => depth.min(MAX_DEPTH),
+        Some(_)                      => return Response::error(400, "Invalid depth"),
+        None                         => DEFAULT_DEPTH
+    };
+
+    if let Some(response) = cache.lock().unwrap().get(position.hash_key(), depth) {
+        return response;
+    }
+
+    let response = analyze(&position, depth);
+    cache.lock().unwrap().insert(position.hash_key(), depth, response.clone());
+
+    return response;
+}
+
+/// Searches the position to the given depth within MAX_NODES and MAX_MEMORY and returns the
+/// response to /analyze.
+fn analyze(position: &Position, depth: u8) -> Response {
+    let limits = Limits { depth: Some(depth), nodes: Some(MAX_NODES), memory: Some(MAX_MEMORY), ..Limits::default() };
+    let mut tt = TranspositionTable::new(TABLE_MB, Replacement::DepthPreferred);
+
+    let (best, score, depth, line) = match search::iterative_deepening_with_table(position, &limits, &mut tt, |_| ()) {
+        Some(iteration) => {
+            (json_string(&position.move_to_san(iteration.best)), iteration.score, iteration.depth, iteration.pv)
+        },
+        // Without legal moves the game is over: mated or stalemated.
+        None => (String::from("null"), if position.is_checked(position.turn) { -MATE_SCORE } else { 0 }, depth, Vec::new())
+    };
+
+    let mut pv = Vec::new();
+    let mut current = position.clone();
+
+    for m in line {
+        pv.push(json_string(&current.move_to_san(m)));
+        current = current.make_move(m);
+    }
+
+    let mate = Score::new(score).mate().map_or(String::from("null"), |n| n.to_string());
+
+    return Response::ok(format!("{{\"bestmove\":{},\"score\":{},\"mate\":{},\"depth\":{},\"pv\":[{}]}}", best, score, mate, depth, pv.join(",")));
+}
+
+/// Encodes a string as a JSON string literal.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut json = String::from("\"");
+
+    for c in s.chars() {
+        match c {
+            '"'  => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c    => json.push(c)
+        }
+    }
+
+    json.push('"');
+
+    return json;
+}
+
+/// Parses a flat JSON object with string, number and boolean values. The values are returned
+/// as strings, so numbers have to be parsed by the caller.
+pub(crate) fn parse_json_object(json: &str) -> Option<HashMap<String, String>> {
+    let mut fields = HashMap::new();
+    let mut chars = json.trim().chars().peekable();
+
+    if chars.next()? != '{' {
+        return None;
+    }
+
+    loop {
+        skip_whitespace(&mut chars);
+
+        match chars.next()? {
+            '}' if fields.is_empty() => break,
+            '"' => (),
+            _   => return None
+        }
+
+        let key = parse_json_string(&mut chars)?;
+
+        skip_whitespace(&mut chars);
+
+        if chars.next()? != ':' {
+            return None;
+        }
+
+        skip_whitespace(&mut chars);
+
+        let value = if chars.peek() == Some(&'"') {
+            chars.next();
+            parse_json_string(&mut chars)?
+        } else {
+            let mut value = String::new();
+
+            while let Some(&c) = chars.peek() {
+                if c == ',' || c == '}' || c.is_whitespace() {
+                    break;
+                }
+
+                value.push(c);
+                chars.next();
+            }
+
+            if value.is_empty() {
+                return None;
+            }
+
+            value
+        };
+
+        fields.insert(key, value);
+        skip_whitespace(&mut chars);
+
+        match chars.next()? {
+            ',' => continue,
+            '}' => break,
+            _   => return None
+        }
+    }
+
+    skip_whitespace(&mut chars);
+
+    return if chars.next().is_none() { Some(fields) } else { None };
+}
+
+/// Parses the rest of a JSON string after the opening quote.
+fn parse_json_string<I: Iterator<Item = char>>(chars: &mut I) -> Option<String> {
+    let mut s = String::new();
+
+    loop {
+        match chars.next()? {
+            '"'  => return Some(s),
+            '\\' => match chars.next()? {
+                'n' => s.push('\n'),
+                't' => s.push('\t'),
+                'r' => s.push('\r'),
+                'u' => {
+                    let code: String = chars.by_ref().take(4).collect();
+                    s.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+                },
+                c   => s.push(c)
+            },
+            c    => s.push(c)
+        }
+    }
+}
+
+fn skip_whitespace<I: Iterator<Item = char>>(chars: &mut std::iter::Peekable<I>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(path: &str, body: &str) -> Response {
+        return handle("POST", path, body, &Mutex::new(AnalysisCache::default()));
+    }
+
+    #[test]
+    fn lists_legal_moves() {
+        let response = post("/legal-moves", r#"{"fen": "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"}"#);
+
+        assert_eq!(response, Response::ok(String::from(r#"{"moves":["e3","e4","Kd1","Kf1","Kd2","Kf2"]}"#)));
+    }
+
+    #[test]
+    fn analyzes_positions() {
+        let response = post("/analyze", r#"{"fen": "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "depth": 2}"#);
+
+        assert_eq!(response.status, 200);
+        assert!(response.body.starts_with(r#"{"bestmove":"Ra8#","score":29999,"mate":1,"depth":1,"pv":["Ra8#"]"#));
+    }
+
+    #[test]
+    fn caches_analyses_by_position_and_depth() {
+        let cache = Mutex::new(AnalysisCache::new(2));
+        let request = |fen: &str, depth: u8| format!(r#"{{"fen": "{}", "depth": {}}}"#, fen, depth);
+        let mate = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1";
+
+        let response = handle("POST", "/analyze", &request(mate, 2), &cache);
+
+        // The move counters don't change the analysis.
+        assert_eq!(handle("POST", "/analyze", &request("6k1/5ppp/8/8/8/8/8/R5K1 w - - 7 30", 2), &cache), response);
+        assert_eq!(cache.lock().unwrap().len(), 1);
+
+        // Cached responses are returned without searching, so a planted one shows up.
+        let hash = Position::from_fen(mate.to_string()).unwrap().hash_key();
+        cache.lock().unwrap().insert(hash, 3, Response::ok(String::from("{}")));
+
+        assert_eq!(handle("POST", "/analyze", &request(mate, 3), &cache).body, "{}");
+
+        // The least recently used entry is dropped first.
+        handle("POST", "/analyze", &request("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", 1), &cache);
+
+        assert_eq!(cache.lock().unwrap().len(), 2);
+        assert!(cache.lock().unwrap().get(hash, 2).is_none());
+        assert!(cache.lock().unwrap().get(hash, 3).is_some());
+    }
+
+    #[test]
+    fn requires_a_known_token() {
+        let open = Access::default();
+        let closed = Access { tokens: vec![String::from("s3cret"), String::from("other")], ..Access::default() };
+        let client = IpAddr::from([127, 0, 0, 1]);
+
+        assert!(open.authorizes(None));
+        assert!(closed.authorizes(Some("Bearer s3cret")) && closed.authorizes(Some(" Bearer other ")));
+
+        for authorization in [None, Some("s3cret"), Some("Bearer s3cre"), Some("Bearer s3cret2"), Some("Basic s3cret")] {
+            assert!(!closed.authorizes(authorization));
+            assert_eq!(admit(&closed, None, client, authorization, Instant::now()).map(|r| r.status), Some(401));
+        }
+
+        assert_eq!(admit(&closed, None, client, Some("Bearer s3cret"), Instant::now()), None);
+    }
+
+    #[test]
+    fn limits_the_rate_of_each_client() {
+        let access = Access { requests_per_minute: Some(3), ..Access::default() };
+        let mut limiter = RateLimiter::new(3);
+        let (client, other) = (IpAddr::from([10, 0, 0, 1]), IpAddr::from([10, 0, 0, 2]));
+        let start = Instant::now();
+
+        // A burst of three requests, then one every 20 seconds.
+        for _ in 0..3 {
+            assert_eq!(admit(&access, Some(&mut limiter), client, None, start), None);
+        }
+
+        let refused = admit(&access, Some(&mut limiter), client, None, start).unwrap();
+
+        assert_eq!(refused.status, 429);
+        assert!(refused.body.contains("retry in 21 s"));
+        assert!(limiter.allow(other, start));
+        assert!(!limiter.allow(client, start + Duration::from_secs(19)));
+        assert!(limiter.allow(client, start + Duration::from_secs(21)));
+        assert!(!limiter.allow(client, start + Duration::from_secs(22)));
+    }
+
+    #[test]
+    fn rejects_bad_requests() {
+        assert_eq!(post("/analyze", "").status, 400);
+        assert_eq!(post("/analyze", r#"{"fen": "8/8 w"}"#), Response::error(400, "Invalid FEN"));
+        assert_eq!(post("/analyze", r#"{"fen": "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "depth": "deep"}"#).status, 400);
+        assert_eq!(post("/unknown", "{}").status, 404);
+        assert_eq!(handle("GET", "/analyze", "", &Mutex::new(AnalysisCache::default())).status, 405);
+    }
+
+    /// Sends a raw request to the server and returns its response.
+    fn exchange(address: std::net::SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        return response;
+    }
+
+    #[test]
+    fn serves_clients_while_others_are_idle() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || serve_on(listener, &Access::default()));
+
+        // Idle connections occupy workers until they time out but don't hold up the others.
+        let idle: Vec<TcpStream> = (1..WORKERS).map(|_| TcpStream::connect(address).unwrap()).collect();
+        let start = Instant::now();
+        let body = r#"{"fen": "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"}"#;
+        let response = exchange(address, &format!("POST /legal-moves HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", body.len(), body));
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with(r#"{"moves":["e3","e4","Kd1","Kf1","Kd2","Kf2"]}"#));
+        assert!(start.elapsed() < TIMEOUT);
+
+        // A head that doesn't end within MAX_HEAD bytes is refused.
+        let request_line = "POST /legal-moves HTTP/1.1\r\n";
+        let response = exchange(address, &format!("{}X-Padding: {}", request_line, "x".repeat(MAX_HEAD as usize - request_line.len() - 11)));
+
+        assert!(response.starts_with("HTTP/1.1 431 Request Header Fields Too Large"));
+        drop(idle);
+    }
+
+    #[test]
+    fn parses_flat_json_objects() {
+        let fields = parse_json_object(r#" { "fen" : "a \"b\" c", "depth":3 } "#).unwrap();
+
+        assert_eq!(fields["fen"], "a \"b\" c");
+        assert_eq!(fields["depth"], "3");
+        assert_eq!(parse_json_object("{}"), Some(HashMap::new()));
+        assert_eq!(parse_json_object(r#"{"fen": }"#), None);
+        assert_eq!(parse_json_object(r#"{"fen": "x"} trailing"#), None);
+    }
+}
@@ -0,0 +1,371 @@
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+use crate::game::Game;
+use crate::position::{flip_color, Position, WHITE};
+use crate::runner::Engine;
+use crate::search::SearchLimits;
+
+
+/// The prompt the server puts in front of its output once logged in.
+const PROMPT: &str = "fics% ";
+
+/// The relations of the player to a game in a style 12 board update that matter for the
+/// engine: it plays the game and it's its move or its opponent's.
+const MY_MOVE: i8 = 1;
+const OPPONENTS_MOVE: i8 = -1;
+
+
+/// A board update in the style 12 format of the Internet Chess Servers, e.g.
+/// "<12> rnbqkbnr pppppppp -------- ... W -1 1 1 1 1 0 7 Anna Ben 1 5 0 39 39 300 300 1 none (0:00) none 0 0 0".
+#[derive(Clone, PartialEq)]
+pub struct Style12 {
+    pub position: Position,
+    /// The number of the game on the server.
+    pub game: u32,
+    pub white: String,
+    pub black: String,
+    /// The relation of the player to the game: 1 if it's their move in their game, -1 if it's
+    /// their opponent's move, 0 if they observe it and other values for examined games.
+    pub relation: i8,
+    /// The time added after each move.
+    pub increment: Duration,
+    /// The time left on the clocks of white and black.
+    pub white_time: Duration,
+    pub black_time: Duration
+}
+
+/// Parses a style 12 board update into the position and the state of the game. Returns None
+/// if the line isn't a valid update.
+pub fn parse_style12(line: &str) -> Option<Style12> {
+    let fields: Vec<&str> = line.strip_prefix("<12> ")?.split_whitespace().collect();
+
+    if fields.len() < 26 {
+        return None;
+    }
+
+    // The ranks are given from the 8th to the 1st with - for empty squares.
+    let mut placement = Vec::new();
+
+    for row in &fields[..8] {
+        let mut rank = String::new();
+        let mut empty = 0;
+
+        for c in row.chars() {
+            if c == '-' {
+                empty += 1;
+                continue;
+            }
+
+            if empty > 0 {
+                rank.push_str(&empty.to_string());
+                empty = 0;
+            }
+
+            rank.push(c);
+        }
+
+        if empty > 0 {
+            rank.push_str(&empty.to_string());
+        }
+
+        placement.push(rank);
+    }
+
+    let turn = match fields[8] {
+        "W" => "w",
+        "B" => "b",
+        _   => return None
+    };
+
+    // The file of a pawn that just moved two squares or -1.
+    let en_passant = match fields[9].parse::<i8>().ok()? {
+        -1           => String::from("-"),
+        file @ 0..=7 => format!("{}{}", (b'a' + file as u8) as char, if turn == "w" { 6 } else { 3 }),
+        _            => return None
+    };
+
+    let castling: String = ["K", "Q", "k", "q"].iter().zip(&fields[10..14]).filter(|(_, &right)| right == "1").map(|(side, _)| *side).collect();
+    let castling = if castling.is_empty() { String::from("-") } else { castling };
+
+    let fen = format!("{} {} {} {} {} {}", placement.join("/"), turn, castling, en_passant, fields[14], fields[25]);
+
+    return Some(Style12 {
+        position: Position::from_fen(fen)?,
+        game: fields[15].parse().ok()?,
+        white: fields[16].to_string(),
+        black: fields[17].to_string(),
+        relation: fields[18].parse().ok()?,
+        increment: Duration::from_secs(fields[20].parse().ok()?),
+        white_time: parse_seconds(fields[23])?,
+        black_time: parse_seconds(fields[24])?
+    });
+}
+
+/// Parses the time left on a clock in seconds. Clocks that ran out show negative times,
+/// which are taken as no time left.
+fn parse_seconds(token: &str) -> Option<Duration> {
+    return Some(Duration::from_secs(token.parse::<i64>().ok()?.max(0) as u64));
+}
+
+
+/// The account the engine logs in with and the games it looks for.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct IcsOptions {
+    /// The user name or "guest" to log in as a guest.
+    pub user: String,
+    pub password: Option<String>,
+    /// The time control of the seek posted after logging in and after each game, as the
+    /// minutes on the clock and the increment in seconds. Without one the engine only
+    /// accepts challenges.
+    pub seek: Option<(u32, u32)>
+}
+
+/// A client for Internet Chess Servers like FICS. It logs in, accepts challenges, posts the
+/// seek of its options and plays its games with the engine.
+pub struct Ics<'a, W: Write> {
+    engine: &'a mut dyn Engine,
+    output: W,
+    options: IcsOptions,
+    game: Game,
+    /// The number of the game the engine plays on the server, None between games.
+    playing: Option<u32>,
+    /// The color the engine plays in its game.
+    color: u8
+}
+
+impl<'a, W: Write> Ics<'a, W> {
+    pub fn new(engine: &'a mut dyn Engine, options: IcsOptions, output: W) -> Ics<'a, W> {
+        return Ics { engine, output, options, game: Game::new(), playing: None, color: WHITE };
+    }
+
+    /// Returns the game the engine plays or played last.
+    pub fn game(&self) -> &Game {
+        return &self.game;
+    }
+
+    /// Reads and handles the output of the server until the connection is closed. Everything
+    /// received is copied to the log. The prompts for the user name and password don't end
+    /// with a line break, so partial lines ending with a colon are handled as well.
+    pub fn run<R: Read, L: Write>(&mut self, mut input: R, mut log: L) -> io::Result<()> {
+        let mut buffer = Vec::new();
+        let mut chunk = [0; 4096];
+
+        loop {
+            let read = input.read(&mut chunk)?;
+
+            if read == 0 {
+                return Ok(());
+            }
+
+            buffer.extend_from_slice(&chunk[..read]);
+
+            while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+
+                log.write_all(line.as_bytes())?;
+                self.handle(line.trim_end())?;
+            }
+
+            let partial = String::from_utf8_lossy(&buffer).into_owned();
+
+            if partial.trim_end().ends_with(':') {
+                writeln!(log, "{}", partial)?;
+                self.handle(partial.trim_end())?;
+                buffer.clear();
+            }
+        }
+    }
+
+    /// Handles a single line of the server's output.
+    pub fn handle(&mut self, line: &str) -> io::Result<()> {
+        let mut line = line.trim_start_matches('\r');
+
+        while let Some(rest) = line.strip_prefix(PROMPT) {
+            line = rest;
+        }
+
+        if line.ends_with("login:") {
+            let user = self.options.user.clone();
+            return self.send(&user);
+        }
+
+        if line.ends_with("password:") {
+            let password = self.options.password.clone().unwrap_or_default();
+            return self.send(&password);
+        }
+
+        // Guests are asked to confirm the name they get.
+        if line.starts_with("Press return to enter the server as") {
+            return self.send("");
+        }
+
+        if line.starts_with("**** Starting FICS session as") {
+            // Board updates in style 12 and unwrapped, so they can be parsed.
+            self.send("set style 12")?;
+            self.send("iset nowrap 1")?;
+
+            return self.seek();
+        }
+
+        if line.starts_with("Challenge:") {
+            return if self.playing.is_none() { self.send("accept") } else { self.send("decline") };
+        }
+
+        if let Some(update) = parse_style12(line) {
+            return self.update(update);
+        }
+
+        // Games end with a line like "{Game 7 (Anna vs. Ben) Anna resigns} 0-1".
+        if let Some(rest) = line.strip_prefix("{Game ") {
+            let game = rest.split_whitespace().next().and_then(|game| game.parse().ok());
+            let over = ["1-0", "0-1", "1/2-1/2", "*"].iter().any(|result| rest.ends_with(&format!("}} {}", result)));
+
+            if over && game.is_some() && game == self.playing {
+                self.playing = None;
+
+                return self.seek();
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Follows a board update of the engine's game and moves if it's the engine's turn.
+    fn update(&mut self, update: Style12) -> io::Result<()> {
+        if update.relation != MY_MOVE && update.relation != OPPONENTS_MOVE {
+            return Ok(());
+        }
+
+        if self.playing != Some(update.game) {
+            if self.playing.is_some() {
+                return Ok(());
+            }
+
+            // A new game starts.
+            self.playing = Some(update.game);
+            self.color = if update.relation == MY_MOVE { update.position.turn } else { flip_color(update.position.turn) };
+            self.game = Game::from_position(update.position.clone());
+            self.engine.new_game();
+        } else if *self.game.position() != update.position {
+            // The moves are followed, so the engine sees repetitions.
+            let mut position = self.game.position().clone();
+            let played = position.get_all_legal_moves().into_iter().find(|&m| position.make_move(m) == update.position);
+
+            match played {
+                Some(m) => self.game.play(m),
+                None    => self.game = Game::from_position(update.position.clone())
+            }
+        }
+
+        if update.relation != MY_MOVE || self.game.position().turn != self.color {
+            return Ok(());
+        }
+
+        let limits = if update.white_time.is_zero() && update.black_time.is_zero() {
+            SearchLimits::default()
+        } else {
+            SearchLimits {
+                wtime: Some(update.white_time),
+                btime: Some(update.black_time),
+                winc: Some(update.increment),
+                binc: Some(update.increment),
+                ..SearchLimits::default()
+            }
+        };
+
+        return match self.engine.choose_move_within(&self.game, &limits) {
+            Some(em) => {
+                // The server understands moves in SAN, without check marks.
+                let san = self.game.position().move_to_san(em.m);
+
+                self.send(san.trim_end_matches(['+', '#']))
+            },
+            None => self.send("resign")
+        };
+    }
+
+    /// Posts the seek of the options, if there is one.
+    fn seek(&mut self) -> io::Result<()> {
+        return match self.options.seek {
+            Some((minutes, increment)) => self.send(&format!("seek {} {}", minutes, increment)),
+            None                       => Ok(())
+        };
+    }
+
+    /// Sends a command to the server.
+    fn send(&mut self, command: &str) -> io::Result<()> {
+        writeln!(self.output, "{}", command)?;
+
+        return self.output.flush();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::BLACK;
+    use crate::search::SearchEngine;
+
+    const START: &str = "<12> rnbqkbnr pppppppp -------- -------- -------- -------- PPPPPPPP RNBQKBNR W -1 1 1 1 1 0 7 Anna Ben 1 5 0 39 39 300 300 1 none (0:00) none 0 0 0";
+    const AFTER_E4: &str = "<12> rnbqkbnr pppppppp -------- -------- ----P--- -------- PPPP-PPP RNBQKBNR B 4 1 1 1 1 0 7 Anna Ben -1 5 0 39 39 298 300 1 P/e2-e4 (0:02) e4 0 0 0";
+
+    #[test]
+    fn parses_style12_board_updates() {
+        let update = parse_style12(AFTER_E4).unwrap();
+
+        assert_eq!(update.position.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+        assert_eq!((update.game, update.white.as_str(), update.black.as_str(), update.relation), (7, "Anna", "Ben", -1));
+        assert_eq!((update.white_time, update.black_time, update.increment), (Duration::from_secs(298), Duration::from_secs(300), Duration::ZERO));
+        assert!(parse_style12(&START.replace(" W ", " X ")).is_none());
+        assert!(parse_style12("<12> rnbqkbnr").is_none());
+    }
+
+    #[test]
+    fn logs_in_and_plays_its_games() {
+        // The prompts arrive without line breaks, each in a read of its own.
+        let session = format!("**** Starting FICS session as GuestABCD(U) ****\nfics% Challenge: Ben (1500) GuestABCD (++++) unrated blitz 5 0.\n{}\n{}\n{{Game 7 (GuestABCD vs. Ben) Ben resigns}} 1-0\n",
+                             START, AFTER_E4);
+        let options = IcsOptions { user: String::from("guest"), password: None, seek: Some((5, 0)) };
+        let mut engine = SearchEngine::new(1);
+        let mut output = Vec::new();
+        let mut log = Vec::new();
+
+        let mut ics = Ics::new(&mut engine, options, &mut output);
+        let server = "login: ".as_bytes().chain("Press return to enter the server as \"GuestABCD\":".as_bytes()).chain(session.as_bytes());
+        ics.run(server, &mut log).unwrap();
+
+        assert_eq!(ics.game().moves().len(), 1);
+        assert_eq!(ics.color, WHITE);
+        assert_eq!(ics.playing, None);
+
+        let output = String::from_utf8(output).unwrap();
+        let commands: Vec<&str> = output.lines().collect();
+
+        assert_eq!(commands[..6], ["guest", "", "set style 12", "iset nowrap 1", "seek 5 0", "accept"]);
+        assert_eq!(commands[7], "seek 5 0");
+        assert_eq!(commands.len(), 8);
+        assert!(String::from_utf8(log).unwrap().starts_with("login: \n"));
+    }
+
+    #[test]
+    fn follows_the_moves_of_its_game() {
+        let mut engine = SearchEngine::new(1);
+        let mut output = Vec::new();
+        let mut ics = Ics::new(&mut engine, IcsOptions { user: String::from("Ben"), password: Some(String::from("secret")), seek: None }, &mut output);
+
+        for line in ["password:", &START.replace(" 1 5 0 ", " -1 5 0 "), &AFTER_E4.replace(" -1 5 0 ", " 1 5 0 "), "<12> observed game"] {
+            ics.handle(line).unwrap();
+        }
+
+        assert_eq!(ics.color, BLACK);
+        assert_eq!(ics.game().moves().len(), 1);
+
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.starts_with("secret\n"));
+        assert_eq!(output.lines().count(), 2);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod http;
+pub mod ics;
+pub mod uci;
+pub mod xboard;
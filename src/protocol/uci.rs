@@ -0,0 +1,525 @@
+use std::io::{self, BufRead, Write};
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::build_info;
+use crate::game::Game;
+#[cfg(feature = "nnue")]
+use crate::nnue::{self, Network};
+use crate::params;
+use crate::position::Position;
+use crate::runner::Engine;
+use crate::search::{Score, SearchLimits};
+use crate::strength::{self, Strength};
+
+
+/// The largest number of lines the MultiPV option accepts.
+const MAX_MULTI_PV: usize = 64;
+
+
+/// A front-end speaking the Universal Chess Interface (UCI).
+pub struct Uci<'a, W: Write> {
+    engine: &'a mut dyn Engine,
+    /// Shared with the thread reading the input, which answers isready during searches.
+    output: Arc<Mutex<W>>,
+    game: Game,
+    /// The number of best lines reported for each search.
+    multi_pv: usize,
+    /// The Skill Level option. Levels below the maximum weaken the engine.
+    skill: u8,
+    /// The UCI_LimitStrength and UCI_Elo options. If set, the Elo rating overrides the skill
+    /// level.
+    limit_strength: bool,
+    elo: u32
+}
+
+impl<'a, W: Write> Uci<'a, W> {
+    pub fn new(engine: &'a mut dyn Engine, output: W) -> Uci<'a, W> {
+        return Uci {
+            engine, output: Arc::new(Mutex::new(output)), game: Game::new(), multi_pv: 1, skill: strength::MAX_SKILL, limit_strength: false, elo: strength::MAX_ELO
+        };
+    }
+
+    /// Returns the game set up by the last position command.
+    pub fn game(&self) -> &Game {
+        return &self.game;
+    }
+
+    /// Reads and handles commands until the input ends or quit is received. The input is read
+    /// on its own thread, so stop, quit and isready reach the engine while it's searching.
+    /// That thread answers isready right away during a search. A go sent during a search is
+    /// handled after it, so every go gets its own bestmove.
+    pub fn run<R: BufRead + Send>(&mut self, input: R) -> io::Result<()> where W: Send {
+        let (sender, receiver) = mpsc::channel();
+        let stop = self.engine.stop_flag();
+        let output = self.output.clone();
+        // The number of go commands read but not handled yet.
+        let searches = &AtomicUsize::new(0);
+
+        return thread::scope(|scope| {
+            // The sender is moved, so the receiver ends with the input.
+            scope.spawn(move || {
+                for line in input.lines() {
+                    let command = line.as_ref().map_or("quit", |line| line.split_whitespace().next().unwrap_or(""));
+                    let searching = searches.load(Ordering::SeqCst) > 0;
+
+                    if command == "isready" && searching {
+                        // Broken output is reported by the thread handling the commands.
+                        let _ = write_line(&output, "readyok");
+                        continue;
+                    }
+
+                    if command == "go" {
+                        searches.fetch_add(1, Ordering::SeqCst);
+                    }
+
+                    // The flag is cleared here rather than by the search, so a stop sent right
+                    // after go isn't lost. A go during a search leaves it alone, so it can't undo
+                    // a stop of the running search.
+                    if let Some(stop) = &stop {
+                        match command {
+                            "go" if !searching => stop.store(false, Ordering::Relaxed),
+                            "stop" | "quit"    => stop.store(true, Ordering::Relaxed),
+                            _                  => ()
+                        }
+                    }
+
+                    let quit = command == "quit";
+
+                    if sender.send(line).is_err() || quit {
+                        break;
+                    }
+                }
+            });
+
+            for line in receiver {
+                let line = line?;
+                let go = line.split_whitespace().next() == Some("go");
+                let handled = self.handle(line.trim());
+
+                if go {
+                    searches.fetch_sub(1, Ordering::SeqCst);
+                }
+
+                if !handled? {
+                    break;
+                }
+            }
+
+            return Ok(());
+        });
+    }
+
+    /// Writes a line of output and flushes it, so the user interface sees it right away.
+    fn send(&self, line: &str) -> io::Result<()> {
+        return write_line(&self.output, line);
+    }
+
+    /// Handles a single command. Returns false if the engine should quit.
+    pub fn handle(&mut self, line: &str) -> io::Result<bool> {
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+
+        match command {
+            "" | "debug" | "stop" | "ponderhit" => (),
+            "quit" => return Ok(false),
+            "uci" => {
+                for line in build_info::uci_id_lines() {
+                    self.send(&line)?;
+                }
+
+                self.send(&format!("option name MultiPV type spin default 1 min 1 max {}", MAX_MULTI_PV))?;
+                self.send(&format!("option name Skill Level type spin default {0} min 0 max {0}", strength::MAX_SKILL))?;
+                self.send("option name UCI_LimitStrength type check default false")?;
+                self.send(&format!("option name UCI_Elo type spin default {1} min {0} max {1}", strength::MIN_ELO, strength::MAX_ELO))?;
+
+                #[cfg(feature = "nnue")]
+                self.send("option name EvalFile type string default <empty>")?;
+
+                for (p, _) in params::values() {
+                    self.send(&format!("option name {} type spin default {} min {} max {}", p.name, p.default, p.min, p.max))?;
+                }
+
+                self.send("uciok")?;
+            },
+            "isready" => self.send("readyok")?,
+            "setoption" => {
+                if !self.set_option(args) {
+                    self.send(&format!("info string invalid option: {}", args))?;
+                }
+            },
+            "ucinewgame" => {
+                self.game = Game::new();
+                self.engine.new_game();
+            },
+            "position" => {
+                match parse_position(args) {
+                    Some(game) => self.game = game,
+                    None       => self.send(&format!("info string invalid position: {}", args))?
+                }
+            },
+            "go" => {
+                match parse_go(args, self.game.position()) {
+                    Some(limits) => self.go(&limits)?,
+                    None         => self.send(&format!("info string invalid limits: {}", args))?
+                }
+            },
+            _ => self.send(&format!("info string unknown command: {}", command))?
+        }
+
+        return Ok(true);
+    }
+
+    /// Searches the current position within the given limits and reports the progress and the
+    /// best move, with all lines if MultiPV is set.
+    fn go(&mut self, limits: &SearchLimits) -> io::Result<()> {
+        let position = self.game.position();
+
+        if self.multi_pv > 1 {
+            let lines = self.engine.choose_lines(&self.game, self.multi_pv, limits);
+
+            for (i, line) in lines.iter().enumerate() {
+                let mut position = position.clone();
+                let mut pv = Vec::new();
+
+                for &m in &line.pv {
+                    pv.push(position.move_to_uci(m));
+                    position = position.make_move(m);
+                }
+
+                let score = line.score.map_or(String::new(), |score| format!(" score {}", Score::new(score)));
+
+                self.send(&format!("info multipv {}{} pv {}", i + 1, score, pv.join(" ")))?;
+            }
+
+            return match lines.first().and_then(|line| line.pv.first()) {
+                Some(&m) => self.send(&format!("bestmove {}", position.move_to_uci(m))),
+                None     => self.send("bestmove 0000")
+            };
+        }
+
+        let output = &self.output;
+        let mut written: io::Result<()> = Ok(());
+        let mut reported = false;
+
+        let result = self.engine.choose_move_reporting(&self.game, limits, &mut |info| {
+            if written.is_ok() {
+                written = write_line(output, &format!("info {}", info.to_uci(position)));
+            }
+
+            reported = true;
+        });
+
+        written?;
+
+        return match result {
+            Some(em) => {
+                // Engines that don't report their progress at least report the final score.
+                if let Some(score) = em.score.filter(|_| !reported) {
+                    self.send(&format!("info score {}", Score::new(score)))?;
+                }
+
+                self.send(&format!("bestmove {}", position.move_to_uci(em.m)))
+            },
+            None => self.send("bestmove 0000")
+        };
+    }
+
+    /// Sets MultiPV, the strength, the NNUE network or an evaluation parameter from the
+    /// arguments of the setoption command, i.e. "name <name> value <value>". Returns false if
+    /// the option is unknown or the value invalid.
+    fn set_option(&mut self, args: &str) -> bool {
+        let (name, value) = match args.strip_prefix("name ").and_then(|rest| rest.split_once(" value ")) {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None                => return false
+        };
+
+        if name.eq_ignore_ascii_case("MultiPV") {
+            return match value.parse() {
+                Ok(lines) if (1..=MAX_MULTI_PV).contains(&lines) => {
+                    self.multi_pv = lines;
+                    true
+                },
+                _ => false
+            };
+        }
+
+        if name.eq_ignore_ascii_case("Skill Level") {
+            match value.parse() {
+                Ok(skill) if skill <= strength::MAX_SKILL => self.skill = skill,
+                _                                         => return false
+            }
+
+            return self.update_strength();
+        }
+
+        if name.eq_ignore_ascii_case("UCI_LimitStrength") {
+            match value.parse() {
+                Ok(limit) => self.limit_strength = limit,
+                _         => return false
+            }
+
+            return self.update_strength();
+        }
+
+        if name.eq_ignore_ascii_case("UCI_Elo") {
+            match value.parse() {
+                Ok(elo) if (strength::MIN_ELO..=strength::MAX_ELO).contains(&elo) => self.elo = elo,
+                _                                                                  => return false
+            }
+
+            return self.update_strength();
+        }
+
+        // An empty path switches back to the classical evaluation.
+        #[cfg(feature = "nnue")]
+        if name.eq_ignore_ascii_case("EvalFile") {
+            match value {
+                "" | "<empty>" => nnue::set_network(None),
+                path           => match Network::load(path) {
+                    Ok(network) => nnue::set_network(Some(network)),
+                    Err(_)      => return false
+                }
+            }
+
+            return true;
+        }
+
+        return value.parse().is_ok_and(|value| params::set(name, value));
+    }
+
+    /// Weakens the engine as set by the strength options. Returns false if the engine's
+    /// strength can't be limited.
+    fn update_strength(&mut self) -> bool {
+        let strength = if self.limit_strength {
+            Some(Strength::from_elo(self.elo))
+        } else if self.skill < strength::MAX_SKILL {
+            Some(Strength::from_skill(self.skill))
+        } else {
+            None
+        };
+
+        return self.engine.set_strength(strength);
+    }
+}
+
+
+/// Writes a line to the shared output and flushes it.
+fn write_line<W: Write>(output: &Mutex<W>, line: &str) -> io::Result<()> {
+    let mut output = output.lock().unwrap();
+    writeln!(output, "{}", line)?;
+
+    return output.flush();
+}
+
+/// Parses the arguments of the position command, e.g. "startpos moves e2e4 e7e5" or
+/// "fen <fen> moves ...", into a game.
+fn parse_position(args: &str) -> Option<Game> {
+    let (setup, moves) = match args.split_once(" moves") {
+        Some((setup, moves)) => (setup.trim(), moves),
+        None                 => (args.trim(), "")
+    };
+
+    let start = if setup == "startpos" {
+        Position::starting_position()
+    } else {
+        Position::from_fen(setup.strip_prefix("fen ")?.to_string())?
+    };
+
+    let mut game = Game::from_position(start);
+
+    for uci in moves.split_whitespace() {
+        let m = game.position().uci_to_move(uci)?;
+        game.play(m);
+    }
+
+    return Some(game);
+}
+
+/// Parses the arguments of the go command, e.g. "depth 6" or "wtime 60000 btime 60000 winc
+/// 1000 binc 1000", into search limits for the given position. Times are given in
+/// milliseconds. The searchmoves are the legal moves following the keyword. Arguments the
+/// engine doesn't support (ponder and mate) are ignored.
+fn parse_go(args: &str, position: &Position) -> Option<SearchLimits> {
+    let mut limits = SearchLimits::default();
+    let mut tokens = args.split_whitespace().peekable();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "infinite" => limits.infinite = true,
+            "ponder"   => (),
+            "searchmoves" => {
+                while let Some(m) = tokens.peek().and_then(|uci| position.uci_to_move(uci)) {
+                    limits.searchmoves.push(m);
+                    tokens.next();
+                }
+            },
+            "depth"     => limits.depth = Some(tokens.next()?.parse().ok()?),
+            "nodes"     => limits.nodes = Some(tokens.next()?.parse().ok()?),
+            "movestogo" => limits.movestogo = Some(tokens.next()?.parse().ok()?),
+            "movetime"  => limits.movetime = Some(parse_millis(tokens.next()?)?),
+            "wtime"     => limits.wtime = Some(parse_millis(tokens.next()?)?),
+            "btime"     => limits.btime = Some(parse_millis(tokens.next()?)?),
+            "winc"      => limits.winc = Some(parse_millis(tokens.next()?)?),
+            "binc"      => limits.binc = Some(parse_millis(tokens.next()?)?),
+            _           => ()
+        }
+    }
+
+    return Some(limits);
+}
+
+/// Parses a time in milliseconds. GUIs send negative times when the clock has run out, which
+/// are taken as no time left.
+fn parse_millis(token: &str) -> Option<Duration> {
+    return Some(Duration::from_millis(token.parse::<i64>().ok()?.max(0) as u64));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::search::SearchEngine;
+
+    fn run(commands: &str) -> String {
+        let mut engine = SearchEngine::new(2);
+        let mut output = Vec::new();
+
+        Uci::new(&mut engine, &mut output).run(Cursor::new(commands.to_string())).unwrap();
+
+        return String::from_utf8(output).unwrap();
+    }
+
+    #[test]
+    fn identifies_itself() {
+        let output = run("uci\nisready\n");
+
+        assert!(output.starts_with("id name RustChess"));
+        assert!(output.ends_with("uciok\nreadyok\n"));
+    }
+
+    #[test]
+    fn exposes_the_evaluation_parameters() {
+        let output = run("uci\nsetoption name BishopPair value 50\nsetoption name BishopPair value -5\nsetoption name Hash value 1\n");
+
+        assert!(output.contains("option name BishopPair type spin default 50 min 0 max 200\n"));
+        assert!(output.ends_with("info string invalid option: name BishopPair value -5\ninfo string invalid option: name Hash value 1\n"));
+    }
+
+    #[test]
+    fn reports_multiple_lines() {
+        let output = run("setoption name MultiPV value 2\nposition fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo\nsetoption name MultiPV value 0\n");
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "info multipv 1 score mate 1 pv a1a8");
+        assert!(lines[1].starts_with("info multipv 2 score cp "));
+        assert_eq!(lines[2], "bestmove a1a8");
+        assert_eq!(lines[3], "info string invalid option: name MultiPV value 0");
+    }
+
+    #[test]
+    fn limits_the_strength() {
+        let mut engine = SearchEngine::new(2);
+        let mut uci = Uci::new(&mut engine, Vec::new());
+
+        for (option, strength) in [("Skill Level value 3", Some(3)), ("UCI_Elo value 1500", Some(3)), ("UCI_LimitStrength value true", Some(10)), ("Skill Level value 21", Some(10)),
+                                   ("UCI_LimitStrength value false", Some(3)), ("Skill Level value 20", None)] {
+            let valid = uci.set_option(&format!("name {}", option));
+
+            assert_eq!(valid, !option.ends_with("21"), "{}", option);
+            assert_eq!(uci.engine.options().iter().find(|(name, _)| name == "Skill Level").map(|(_, value)| value.parse().unwrap()), strength, "{}", option);
+        }
+
+        let output = run("uci
+setoption name Skill Level value 0
+position startpos
+go
+");
+
+        assert!(output.contains("option name UCI_Elo type spin default 2200 min 800 max 2200
+"));
+        assert!(output.contains("\ninfo score cp "));
+        assert!(output.contains("\nbestmove "));
+    }
+
+    #[test]
+    fn searches_the_given_position() {
+        let output = run("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo depth 2\ngo nodes 5000 movetime 100\ngo depth x\nquit\ngo\n");
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines.len(), 5);
+
+        for i in [0, 2] {
+            assert!(lines[i].starts_with("info depth 1 seldepth 1 score mate 1 nodes 28 nps "), "{}", lines[i]);
+            assert!(lines[i].ends_with(" pv a1a8"));
+            assert_eq!(lines[i + 1], "bestmove a1a8");
+        }
+
+        assert_eq!(lines[4], "info string invalid limits: depth x");
+    }
+
+    #[test]
+    fn searches_only_the_given_moves() {
+        let output = run("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo depth 2 searchmoves g1f1 a1a2\n");
+
+        assert!(output.ends_with("\nbestmove a1a2\n") || output.ends_with("\nbestmove g1f1\n"), "{}", output);
+        assert!(!output.contains("mate"));
+    }
+
+    #[test]
+    fn stop_ends_infinite_searches() {
+        // Without the stop the search of the starting position would go on forever.
+        let output = run("position startpos\ngo infinite\nstop\nisready\n");
+
+        assert!(output.contains("info depth 1 seldepth "));
+        assert!(output.contains(" score cp "));
+
+        // The search may still be running when isready arrives, so readyok can come first.
+        assert_eq!(output.matches("bestmove ").count(), 1);
+        assert_eq!(output.matches("readyok\n").count(), 1);
+    }
+
+    #[test]
+    fn parses_the_limits_of_the_go_command() {
+        let start = Position::starting_position();
+        let limits = parse_go("wtime 60000 btime -20 winc 1000 movestogo 20 searchmoves e2e4", &start).unwrap();
+
+        assert_eq!(limits, SearchLimits {
+            wtime: Some(Duration::from_secs(60)), btime: Some(Duration::ZERO), winc: Some(Duration::from_secs(1)),
+            movestogo: Some(20), searchmoves: vec![start.uci_to_move("e2e4").unwrap()], ..SearchLimits::default()
+        });
+        assert_eq!(limits.to_string(), "wtime 60000 btime 0 winc 1000 movestogo 20");
+
+        assert_eq!(parse_go("depth 3 infinite", &start), Some(SearchLimits { infinite: true, ..SearchLimits::depth(3) }));
+        assert_eq!(parse_go("", &start), Some(SearchLimits::default()));
+        assert!(parse_go("depth", &start).is_none());
+        assert!(parse_go("nodes many", &start).is_none());
+
+        // The moves end at the first token that isn't a legal move.
+        let limits = parse_go("searchmoves d2d4 g1f3 e2e5 depth 2", &start).unwrap();
+
+        assert_eq!(limits.searchmoves, ["d2d4", "g1f3"].map(|m| start.uci_to_move(m).unwrap()));
+        assert_eq!(limits.depth, Some(2));
+    }
+
+    #[test]
+    fn parses_moves_after_the_position() {
+        let game = parse_position("startpos moves e2e4 e7e5 g1f3").unwrap();
+
+        assert_eq!(game.moves().len(), 3);
+        assert!(parse_position("startpos moves e2e5").is_none());
+        assert!(parse_position("fen 8/8 w - - 0 1").is_none());
+        assert!(parse_position("startpos moves Qe5").is_none());
+    }
+
+    #[test]
+    fn reports_invalid_positions() {
+        let output = run("position fen 99999999999999999999999999999999/8 w\nposition startpos moves é4\nisready\n");
+
+        assert!(output.starts_with("info string invalid position: fen 9999"));
+        assert!(output.ends_with("info string invalid position: startpos moves é4\nreadyok\n"));
+    }
+}
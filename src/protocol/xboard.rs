@@ -0,0 +1,374 @@
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use crate::build_info;
+use crate::game::Game;
+use crate::position::{flip_color, Position, WHITE, BLACK};
+use crate::runner::Engine;
+use crate::search::SearchLimits;
+use crate::variant::Variant;
+
+
+/// The names of the supported variants in the Chess Engine Communication Protocol.
+const VARIANTS: [(&str, Variant); 5] = [
+    ("normal", Variant::Standard),
+    ("gardner", Variant::Gardner),
+    ("crazyhouse", Variant::Crazyhouse),
+    ("bughouse", Variant::Bughouse),
+    ("3check", Variant::ThreeCheck)
+];
+
+/// The time control set by the level command. Together with the clocks sent by the time and
+/// otim commands it decides how long the engine thinks about its moves.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Level {
+    /// The number of moves per time control or 0 for an incremental time control.
+    pub moves: u32,
+    /// The base time in seconds.
+    pub base: u32,
+    /// The increment per move in seconds.
+    pub increment: u32
+}
+
+/// A front-end speaking the Chess Engine Communication Protocol (CECP) used by xboard,
+/// WinBoard and many tournament managers.
+pub struct XBoard<'a, W: Write> {
+    engine: &'a mut dyn Engine,
+    output: W,
+    game: Game,
+    variant: Variant,
+    /// In force mode the engine only records the moves of both sides without replying.
+    force: bool,
+    /// The color the engine plays.
+    engine_color: u8,
+    level: Option<Level>,
+    /// The time left on the clocks of the engine and its opponent.
+    time: Option<Duration>,
+    otim: Option<Duration>
+}
+
+impl<'a, W: Write> XBoard<'a, W> {
+    pub fn new(engine: &'a mut dyn Engine, output: W) -> XBoard<'a, W> {
+        return XBoard {
+            engine,
+            output,
+            game: Game::new(),
+            variant: Variant::Standard,
+            force: false,
+            engine_color: BLACK,
+            level: None,
+            time: None,
+            otim: None
+        };
+    }
+
+    /// Returns the game played so far.
+    pub fn game(&self) -> &Game {
+        return &self.game;
+    }
+
+    /// Returns the time control set by the last level command.
+    pub fn level(&self) -> Option<Level> {
+        return self.level;
+    }
+
+    /// Returns the limits of the engine's next search: its share of the clocks of the last
+    /// time and otim commands under the time control of the level command. Without a clock
+    /// the limits are empty, so the engine decides on its own how long to search.
+    pub fn search_limits(&self) -> SearchLimits {
+        let time = match self.time {
+            Some(time) => time,
+            None       => return SearchLimits::default()
+        };
+
+        let increment = self.level.map(|level| Duration::from_secs(level.increment as u64));
+        let (wtime, btime) = if self.engine_color == WHITE { (Some(time), self.otim) } else { (self.otim, Some(time)) };
+
+        // The moves of the engine count towards the time control, so each one played in it
+        // leaves one less to go.
+        let movestogo = self.level.filter(|level| level.moves > 0).map(|level| level.moves - (self.game.moves().len() as u32 / 2) % level.moves);
+
+        return SearchLimits { wtime, btime, winc: increment, binc: increment, movestogo, ..SearchLimits::default() };
+    }
+
+    /// Reads and handles commands until the input ends or quit is received.
+    pub fn run<R: BufRead>(&mut self, input: R) -> io::Result<()> {
+        for line in input.lines() {
+            if !self.handle(line?.trim())? {
+                break;
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Handles a single command. Returns false if the engine should quit.
+    pub fn handle(&mut self, line: &str) -> io::Result<bool> {
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+
+        match command {
+            "" | "xboard" | "accepted" | "rejected" | "random" | "post" | "nopost" | "hard" | "easy"
+                | "computer" | "white" | "black" => (),
+            "quit" => return Ok(false),
+            "protover" => {
+                let variants: Vec<&str> = VARIANTS.iter().map(|(name, _)| *name).collect();
+
+                writeln!(self.output, "feature myname=\"{}\" usermove=1 setboard=1 ping=1 sigint=0 sigterm=0 colors=0 variants=\"{}\" done=1",
+                         build_info::engine_name(), variants.join(","))?;
+            },
+            "ping" => writeln!(self.output, "pong {}", args)?,
+            "time" | "otim" => {
+                // The clocks are sent in centiseconds.
+                match args.parse::<u64>() {
+                    Ok(cs) if command == "time" => self.time = Some(Duration::from_millis(cs * 10)),
+                    Ok(cs)                      => self.otim = Some(Duration::from_millis(cs * 10)),
+                    Err(_)                      => writeln!(self.output, "Error (invalid time): {}", args)?
+                }
+            },
+            "new" => {
+                self.variant = Variant::Standard;
+                self.game = Game::new();
+                self.force = false;
+                self.engine_color = BLACK;
+                self.engine.new_game();
+            },
+            "variant" => {
+                match VARIANTS.iter().find(|(name, _)| *name == args) {
+                    Some((_, variant)) => {
+                        self.variant = *variant;
+                        self.game = Game::from_position(Position::variant_starting_position(*variant));
+                    },
+                    None => writeln!(self.output, "Error (unsupported variant): {}", args)?
+                }
+            },
+            "force" => self.force = true,
+            "go" => {
+                self.force = false;
+                self.engine_color = self.game.position().turn;
+                self.engine_move()?;
+            },
+            "playother" => {
+                self.force = false;
+                self.engine_color = flip_color(self.game.position().turn);
+            },
+            "usermove" => self.user_move(args)?,
+            "setboard" => {
+                match Position::from_variant_fen(self.variant, args.to_string()) {
+                    Some(position) => self.game = Game::from_position(position),
+                    None           => writeln!(self.output, "tellusererror Illegal position")?
+                }
+            },
+            "level" => {
+                match parse_level(args) {
+                    Some(level) => self.level = Some(level),
+                    None        => writeln!(self.output, "Error (invalid level): {}", args)?
+                }
+            },
+            "result" => self.force = true,
+            _ => {
+                // Moves may also be sent without the usermove prefix.
+                if self.game.position().uci_to_move(line).is_some() {
+                    self.user_move(line)?;
+                } else {
+                    writeln!(self.output, "Error (unknown command): {}", command)?;
+                }
+            }
+        }
+
+        self.output.flush()?;
+
+        return Ok(true);
+    }
+
+    /// Plays the move of the user and lets the engine reply unless it is in force mode.
+    fn user_move(&mut self, string: &str) -> io::Result<()> {
+        let position = self.game.position();
+
+        let (m, suffix_matches) = match position.uci_to_move(string).map(|m| (m, true)).or_else(|| position.parse_san(string)) {
+            Some(parsed) => parsed,
+            None         => return writeln!(self.output, "Illegal move: {}", string)
+        };
+
+        // Lines starting with # are ignored by the interface, but end up in its debug log.
+        if !suffix_matches {
+            writeln!(self.output, "# The check suffix of {} doesn't match the position after the move.", string)?;
+        }
+
+        self.game.play(m);
+
+        if self.report_result()? {
+            self.force = true;
+            return Ok(());
+        }
+
+        if !self.force && self.game.position().turn == self.engine_color {
+            self.engine_move()?;
+        }
+
+        return Ok(());
+    }
+
+    /// Lets the engine choose and play a move in the current position.
+    fn engine_move(&mut self) -> io::Result<()> {
+        if self.report_result()? {
+            return Ok(());
+        }
+
+        let limits = self.search_limits();
+
+        let em = match self.engine.choose_move_within(&self.game, &limits) {
+            Some(em) => em,
+            None     => return writeln!(self.output, "resign")
+        };
+
+        let uci = self.game.position().move_to_uci(em.m);
+        self.game.play(em.m);
+
+        writeln!(self.output, "move {}", uci)?;
+
+        if self.report_result()? {
+            self.force = true;
+        }
+
+        return Ok(());
+    }
+
+    /// Reports the result if the game is over. Returns true if it is.
+    fn report_result(&mut self) -> io::Result<bool> {
+        let mut position = self.game.position().clone();
+        let winner = if position.turn == WHITE { "0-1" } else { "1-0" };
+        let mated_side = if position.turn == WHITE { "White" } else { "Black" };
+
+        if position.is_variant_loss() {
+            writeln!(self.output, "{} {{{} lost by variant rules}}", winner, mated_side)?;
+        } else if position.get_all_legal_moves().is_empty() {
+            if position.is_checked(position.turn) {
+                writeln!(self.output, "{} {{{} is checkmated}}", winner, mated_side)?;
+            } else {
+                writeln!(self.output, "1/2-1/2 {{Stalemate}}")?;
+            }
+        } else if position.is_threefold_repetition() {
+            writeln!(self.output, "1/2-1/2 {{Draw by repetition}}")?;
+        } else {
+            return Ok(false);
+        }
+
+        return Ok(true);
+    }
+}
+
+/// Parses the arguments of the level command, e.g. "40 5 0" or "0 2:30 1". The base time
+/// is given in minutes, optionally followed by seconds.
+fn parse_level(args: &str) -> Option<Level> {
+    let fields: Vec<&str> = args.split_whitespace().collect();
+
+    if fields.len() != 3 {
+        return None;
+    }
+
+    let (minutes, seconds) = fields[1].split_once(':').unwrap_or((fields[1], "0"));
+    let base = minutes.parse::<u32>().ok()?.checked_mul(60)?.checked_add(seconds.parse().ok()?)?;
+
+    return Some(Level { moves: fields[0].parse().ok()?, base, increment: fields[2].parse().ok()? });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::search::SearchEngine;
+
+    fn run(commands: &str) -> (String, Vec<String>) {
+        let mut engine = SearchEngine::new(2);
+        let mut output = Vec::new();
+
+        let moves = {
+            let mut xboard = XBoard::new(&mut engine, &mut output);
+            xboard.run(Cursor::new(commands)).unwrap();
+
+            xboard.game().moves().iter().map(|m| m.to_string()).collect()
+        };
+
+        return (String::from_utf8(output).unwrap(), moves);
+    }
+
+    #[test]
+    fn announces_features() {
+        let (output, _) = run("xboard\nprotover 2\nping 7\n");
+
+        assert!(output.starts_with("feature myname=\"RustChess"));
+        assert!(output.contains("usermove=1 setboard=1"));
+        assert!(output.ends_with("done=1\npong 7\n"));
+    }
+
+    #[test]
+    fn replies_to_user_moves() {
+        let (output, moves) = run("new\nusermove e2e4\n");
+
+        assert_eq!(moves.len(), 2);
+        assert!(output.starts_with("move "));
+    }
+
+    #[test]
+    fn force_mode_only_records_moves() {
+        let (output, moves) = run("new\nforce\nusermove e2e4\ne7e5\ngo\n");
+
+        assert_eq!(moves.len(), 3);
+        assert_eq!(output.lines().count(), 1);
+        assert!(output.starts_with("move "));
+    }
+
+    #[test]
+    fn warns_about_wrong_check_suffixes() {
+        let (output, moves) = run("new\nforce\nusermove Nf3+\n");
+
+        assert_eq!(moves.len(), 1);
+        assert_eq!(output, "# The check suffix of Nf3+ doesn't match the position after the move.\n");
+    }
+
+    #[test]
+    fn rejects_illegal_moves_and_positions() {
+        let (output, moves) = run("new\nforce\nusermove e2e5\nsetboard 8/8/8/8/8/8/8/8 w - - 0 1\nfoo\n");
+
+        assert!(moves.is_empty());
+        assert_eq!(output, "Illegal move: e2e5\ntellusererror Illegal position\nError (unknown command): foo\n");
+    }
+
+    #[test]
+    fn plays_from_a_set_position_and_reports_mate() {
+        let (output, moves) = run("new\nforce\nsetboard 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\nlevel 40 5 0\ngo\n");
+
+        assert_eq!(moves, vec!["Ra1:a8"]);
+        assert_eq!(output, "move a1a8\n1-0 {Black is checkmated}\n");
+    }
+
+    #[test]
+    fn clocks_limit_the_search() {
+        let mut engine = SearchEngine::new(2);
+        let mut output = Vec::new();
+        let mut xboard = XBoard::new(&mut engine, &mut output);
+
+        assert!(xboard.search_limits().is_empty());
+
+        for command in ["new", "force", "level 40 5 2", "time 30000", "otim 12000", "e2e4", "e7e5", "d2d4", "d7d5", "time nan"] {
+            xboard.handle(command).unwrap();
+        }
+
+        // The engine plays black and two moves of each side were played.
+        let limits = xboard.search_limits();
+        assert_eq!((limits.wtime, limits.btime), (Some(Duration::from_secs(120)), Some(Duration::from_secs(300))));
+        assert_eq!((limits.binc, limits.movestogo), (Some(Duration::from_secs(2)), Some(38)));
+
+        drop(xboard);
+        assert!(String::from_utf8(output).unwrap().ends_with("Error (invalid time): nan\n"));
+    }
+
+    #[test]
+    fn parses_levels() {
+        assert_eq!(parse_level("40 5 0"), Some(Level { moves: 40, base: 300, increment: 0 }));
+        assert_eq!(parse_level("0 2:30 1"), Some(Level { moves: 0, base: 150, increment: 1 }));
+        assert_eq!(parse_level("0 2"), None);
+        assert_eq!(parse_level("0 99999999:0 1"), None);
+    }
+}
@@ -0,0 +1,171 @@
+use std::f64::consts::PI;
+
+
+/// The factor between ratings on the usual scale and the internal scale of Glicko-2.
+const SCALE: f64 = 173.7178;
+
+/// Constrains how fast the volatility changes (the system constant tau of Glicko-2).
+const TAU: f64 = 0.5;
+
+/// The precision to which the new volatility is computed.
+const EPSILON: f64 = 0.000001;
+
+/// The deviation of unrated players. Ratings never get more uncertain than this.
+const MAX_DEVIATION: f64 = 350.0;
+
+/// The estimated rating of the engine searching one ply deep and how much each additional ply
+/// adds. They are rough estimates of the engine's strength against humans, so the engine
+/// serves as a fixed anchor for the ratings of local players.
+const ENGINE_BASE_RATING: f64 = 800.0;
+const ENGINE_RATING_PER_PLY: f64 = 200.0;
+
+/// The deviation of the engine's estimated rating.
+const ENGINE_DEVIATION: f64 = 100.0;
+
+
+/// A Glicko-2 rating: the estimated strength of a player, its uncertainty and how much the
+/// player's strength fluctuates.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Rating {
+    pub rating: f64,
+    /// The standard deviation of the rating. The actual strength lies within twice the
+    /// deviation of the rating with 95% probability.
+    pub deviation: f64,
+    pub volatility: f64
+}
+
+impl Default for Rating {
+    /// Returns the rating of a new player.
+    fn default() -> Rating {
+        return Rating { rating: 1500.0, deviation: MAX_DEVIATION, volatility: 0.06 };
+    }
+}
+
+impl Rating {
+    /// Returns the rating after a rating period in which the given games were played. Each
+    /// game is given by the opponent's rating and the player's score: 1 for a win, 0.5 for a
+    /// draw and 0 for a loss. Without games only the deviation grows.
+    pub fn update(&self, games: &[(Rating, f64)]) -> Rating {
+        let mu = (self.rating - 1500.0) / SCALE;
+        let phi = self.deviation / SCALE;
+
+        if games.is_empty() {
+            let deviation = (phi * phi + self.volatility * self.volatility).sqrt() * SCALE;
+
+            return Rating { deviation: deviation.min(MAX_DEVIATION), ..*self };
+        }
+
+        let mut inverse_variance = 0.0;
+        let mut improvement = 0.0;
+
+        for (opponent, score) in games {
+            let mu_j = (opponent.rating - 1500.0) / SCALE;
+            let g = g(opponent.deviation / SCALE);
+            let expected = 1.0 / (1.0 + (-g * (mu - mu_j)).exp());
+
+            inverse_variance += g * g * expected * (1.0 - expected);
+            improvement += g * (score - expected);
+        }
+
+        let variance = 1.0 / inverse_variance;
+        let delta = variance * improvement;
+        let volatility = self.new_volatility(phi, variance, delta);
+
+        let pre_period = (phi * phi + volatility * volatility).sqrt();
+        let new_phi = 1.0 / (1.0 / (pre_period * pre_period) + 1.0 / variance).sqrt();
+        let new_mu = mu + new_phi * new_phi * improvement;
+
+        return Rating { rating: new_mu * SCALE + 1500.0, deviation: (new_phi * SCALE).min(MAX_DEVIATION), volatility };
+    }
+
+    /// Finds the new volatility with the Illinois algorithm (step 5 of the Glicko-2 paper).
+    fn new_volatility(&self, phi: f64, variance: f64, delta: f64) -> f64 {
+        let a = (self.volatility * self.volatility).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            let denominator = phi * phi + variance + ex;
+
+            return ex * (delta * delta - phi * phi - variance - ex) / (2.0 * denominator * denominator) - (x - a) / (TAU * TAU);
+        };
+
+        let mut lower = a;
+        let mut upper = if delta * delta > phi * phi + variance {
+            (delta * delta - phi * phi - variance).ln()
+        } else {
+            let mut k = 1.0;
+
+            while f(a - k * TAU) < 0.0 {
+                k += 1.0;
+            }
+
+            a - k * TAU
+        };
+
+        let (mut f_lower, mut f_upper) = (f(lower), f(upper));
+
+        while (upper - lower).abs() > EPSILON {
+            let c = lower + (lower - upper) * f_lower / (f_upper - f_lower);
+            let f_c = f(c);
+
+            if f_c * f_upper <= 0.0 {
+                lower = upper;
+                f_lower = f_upper;
+            } else {
+                f_lower /= 2.0;
+            }
+
+            upper = c;
+            f_upper = f_c;
+        }
+
+        return (lower / 2.0).exp();
+    }
+}
+
+/// Reduces the impact of games against opponents with uncertain ratings.
+fn g(phi: f64) -> f64 {
+    return 1.0 / (1.0 + 3.0 * phi * phi / (PI * PI)).sqrt();
+}
+
+/// Returns the estimated rating of the engine searching to the given depth.
+pub fn engine_rating(depth: u8) -> Rating {
+    return anchor(ENGINE_BASE_RATING + ENGINE_RATING_PER_PLY * depth.saturating_sub(1) as f64);
+}
+
+/// Returns the rating of an engine whose strength is estimated to be the given rating.
+pub fn anchor(rating: f64) -> Rating {
+    return Rating { rating, deviation: ENGINE_DEVIATION, ..Rating::default() };
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rating(rating: f64, deviation: f64) -> Rating {
+        return Rating { rating, deviation, volatility: 0.06 };
+    }
+
+    #[test]
+    fn matches_the_example_of_the_glicko2_paper() {
+        let games = [(rating(1400.0, 30.0), 1.0), (rating(1550.0, 100.0), 0.0), (rating(1700.0, 300.0), 0.0)];
+        let updated = rating(1500.0, 200.0).update(&games);
+
+        assert!((updated.rating - 1464.06).abs() < 0.01, "{:?}", updated);
+        assert!((updated.deviation - 151.52).abs() < 0.01, "{:?}", updated);
+        assert!((updated.volatility - 0.05999).abs() < 0.00001, "{:?}", updated);
+    }
+
+    #[test]
+    fn wins_raise_and_idle_periods_blur_ratings() {
+        let player = Rating::default();
+        let engine = engine_rating(3);
+
+        assert!(player.update(&[(engine, 1.0)]).rating > player.rating);
+        assert!(player.update(&[(engine, 0.0)]).rating < player.rating);
+        assert!(player.update(&[(engine, 1.0)]).deviation < player.deviation);
+        assert_eq!(player.update(&[]).deviation, MAX_DEVIATION);
+        assert!(rating(1500.0, 50.0).update(&[]).deviation > 50.0);
+        assert!(engine_rating(4).rating > engine.rating);
+    }
+}
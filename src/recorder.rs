@@ -0,0 +1,235 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::game::Game;
+use crate::moves::Move;
+use crate::pgn;
+use crate::position::Position;
+
+
+/// A move together with the time it was played at, measured from the start of the
+/// recording, and optionally the engine's analysis of the position after the move.
+#[derive(Clone)]
+pub struct TimedMove {
+    pub m: Move,
+    pub elapsed: Duration,
+    pub analysis: Option<String>
+}
+
+/// Records a game while it's being played by timestamping every move.
+pub struct GameRecorder {
+    game: Game,
+    start: Instant,
+    moves: Vec<TimedMove>
+}
+
+impl GameRecorder {
+    /// Starts recording a new game from the given position.
+    pub fn new(position: Position) -> GameRecorder {
+        return GameRecorder {
+            game: Game::from_position(position),
+            start: Instant::now(),
+            moves: Vec::new()
+        };
+    }
+
+    /// Returns the recorded game.
+    pub fn game(&self) -> &Game {
+        return &self.game;
+    }
+
+    /// Returns all moves recorded so far.
+    pub fn moves(&self) -> &[TimedMove] {
+        return &self.moves;
+    }
+
+    /// Plays the given move and records the time it was played at.
+    pub fn record(&mut self, m: Move) {
+        self.game.play(m);
+        self.moves.push(TimedMove { m, elapsed: self.start.elapsed(), analysis: None });
+    }
+
+    /// Attaches the engine's analysis to the last recorded move. The analysis is also stored
+    /// as the move's comment in the game, so it's written to PGN.
+    pub fn annotate(&mut self, analysis: String) {
+        if let Some(tm) = self.moves.last_mut() {
+            self.game.set_comment(analysis.clone());
+            tm.analysis = Some(analysis);
+        }
+    }
+
+    /// Makes the recording follow the given moves of the game being played. Recorded moves
+    /// that were taken back are dropped and the moves played since are recorded now.
+    pub fn follow(&mut self, moves: &[Move]) {
+        let kept = self.moves.iter().zip(moves).take_while(|(tm, m)| tm.m == **m).count();
+
+        if kept < self.moves.len() {
+            self.moves.truncate(kept);
+            self.game = Game::from_position(self.game.starting_position().clone());
+
+            for tm in self.moves.iter() {
+                self.game.play(tm.m);
+
+                if let Some(analysis) = &tm.analysis {
+                    self.game.set_comment(analysis.clone());
+                }
+            }
+        }
+
+        for &m in moves[kept..].iter() {
+            self.record(m);
+        }
+    }
+
+    /// Encodes the recording as PGN with the given tags and result. The comment of every move
+    /// starts with its timestamp in seconds as a [%ts] command, followed by the analysis.
+    pub fn to_pgn(&self, tags: &[(String, String)], result: &str) -> String {
+        let mut game = Game::from_position(self.game.starting_position().clone());
+
+        for tm in self.moves.iter() {
+            game.play(tm.m);
+
+            let comment = match &tm.analysis {
+                Some(analysis) => format!("[%ts {:.3}] {}", tm.elapsed.as_secs_f64(), analysis),
+                None           => format!("[%ts {:.3}]", tm.elapsed.as_secs_f64())
+            };
+
+            game.set_comment(comment);
+        }
+
+        return pgn::write_game(&game, tags, result);
+    }
+
+    /// Reads a recording written by to_pgn from the first game of the PGN text. Moves
+    /// without a timestamp are taken to be played at the same time as the move before.
+    pub fn from_pgn(text: &str) -> Option<GameRecorder> {
+        let pgn = pgn::read_games(text).into_iter().next()?;
+        let mut recorder = GameRecorder::new(pgn.game.starting_position().clone());
+        let mut node = pgn.game.root();
+        let mut last = Duration::ZERO;
+
+        while let Some(&next) = pgn.game.children(node).first() {
+            let (elapsed, analysis) = parse_comment(pgn.game.comment(next).unwrap_or(""));
+            let elapsed = elapsed.unwrap_or(last);
+
+            recorder.game.play(pgn.game.node_move(next).unwrap());
+            recorder.moves.push(TimedMove { m: pgn.game.node_move(next).unwrap(), elapsed, analysis: None });
+
+            if let Some(analysis) = analysis {
+                recorder.annotate(analysis);
+            }
+
+            last = elapsed;
+            node = next;
+        }
+
+        return Some(recorder);
+    }
+
+    /// Replays the recorded game. Between two moves the function waits for the time that
+    /// passed between them during the game divided by the speed factor, i.e. a speed of
+    /// 1.0 replays the game in real time and a speed of 2.0 twice as fast.
+    ///
+    /// The callback is called with the position after each move and the move itself.
+    pub fn replay<F: FnMut(&Position, &TimedMove)>(&self, speed: f64, mut on_move: F) {
+        let mut position = self.game.starting_position().clone();
+        let mut last = Duration::ZERO;
+
+        for tm in self.moves.iter() {
+            if speed > 0.0 {
+                thread::sleep(tm.elapsed.saturating_sub(last).div_f64(speed));
+            }
+
+            last = tm.elapsed;
+            position = position.make_move(tm.m);
+
+            on_move(&position, tm);
+        }
+    }
+}
+
+/// Splits the comment of a recorded move into its timestamp and the analysis.
+fn parse_comment(comment: &str) -> (Option<Duration>, Option<String>) {
+    let comment = comment.trim();
+
+    let (elapsed, rest) = match comment.strip_prefix("[%ts ").and_then(|rest| rest.split_once(']')) {
+        Some((secs, rest)) => (secs.trim().parse().ok().filter(|secs: &f64| secs.is_finite() && *secs >= 0.0).map(Duration::from_secs_f64), rest.trim()),
+        None               => (None, comment)
+    };
+
+    let analysis = if rest.is_empty() { None } else { Some(rest.to_string()) };
+
+    return (elapsed, analysis);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uci(recorder: &GameRecorder, m: &str) -> Move {
+        return recorder.game().position().uci_to_move(m).unwrap();
+    }
+
+    #[test]
+    fn replays_recorded_games() {
+        let mut recorder = GameRecorder::new(Position::starting_position());
+
+        for (m, analysis) in [("e2e4", None), ("e7e5", Some("Engine eval +0.20")), ("g1f3", None), ("b8c6", Some("Engine eval -0.15"))] {
+            thread::sleep(Duration::from_millis(5));
+            recorder.record(uci(&recorder, m));
+
+            if let Some(analysis) = analysis {
+                recorder.annotate(analysis.to_string());
+            }
+        }
+
+        let pgn = recorder.to_pgn(&[(String::from("Result"), String::from("*"))], "*");
+        let replayed = GameRecorder::from_pgn(&pgn).unwrap();
+
+        assert_eq!(replayed.moves().len(), 4);
+
+        for (tm, original) in replayed.moves().iter().zip(recorder.moves()) {
+            assert_eq!(tm.m, original.m);
+            assert_eq!(tm.analysis, original.analysis);
+            assert!((tm.elapsed.as_secs_f64() - original.elapsed.as_secs_f64()).abs() < 0.001);
+        }
+
+        let mut seen = Vec::new();
+        replayed.replay(0.0, |position, tm| seen.push((position.to_fen(), tm.m)));
+
+        assert_eq!(seen.len(), 4);
+        assert_eq!(seen.last().unwrap().0, recorder.game().position().to_fen());
+        assert_eq!(replayed.game().comment(replayed.game().current_node()), Some("Engine eval -0.15"));
+    }
+
+    #[test]
+    fn follows_moves_taken_back() {
+        let mut game = Game::new();
+        let mut recorder = GameRecorder::new(Position::starting_position());
+
+        for m in ["e2e4", "e7e5"] {
+            game.play(game.position().uci_to_move(m).unwrap());
+        }
+
+        recorder.follow(game.moves());
+        recorder.annotate(String::from("Engine eval +0.20"));
+        game.undo();
+        game.play(game.position().uci_to_move("c7c5").unwrap());
+        recorder.follow(game.moves());
+
+        assert_eq!(recorder.moves().iter().map(|tm| tm.m).collect::<Vec<_>>(), game.moves());
+        assert_eq!(recorder.moves()[1].analysis, None);
+        assert_eq!(recorder.game().main_line(), game.moves());
+    }
+
+    #[test]
+    fn reads_moves_without_timestamps() {
+        let recorder = GameRecorder::from_pgn("1. e4 {[%ts 1.500] Engine eval +0.30} e5 {[%ts -2]} 2. Nf3 {[%ts 4.250]} *").unwrap();
+        let times: Vec<f64> = recorder.moves().iter().map(|tm| tm.elapsed.as_secs_f64()).collect();
+
+        assert_eq!(times, vec![1.5, 1.5, 4.25]);
+        assert_eq!(recorder.moves()[0].analysis.as_deref(), Some("Engine eval +0.30"));
+        assert!(GameRecorder::from_pgn("").is_none());
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+
+use crate::game::Game;
+use crate::moves::Move;
+use crate::pgn::{self, PgnGame};
+use crate::position::{Position, WHITE};
+use crate::search::{self, Limits, Score};
+
+
+/// The moves played in a collection of games, counted per position (opening tree). Positions
+/// are identified by their hash keys, so transpositions share their moves.
+#[derive(Clone, Default)]
+pub struct OpeningTree {
+    moves: HashMap<u64, Vec<(Move, u32)>>
+}
+
+impl OpeningTree {
+    pub fn new() -> OpeningTree {
+        return OpeningTree::default();
+    }
+
+    /// Builds the tree of the first plies of the main lines of the games.
+    pub fn from_games(games: &[PgnGame], plies: usize) -> OpeningTree {
+        let mut tree = OpeningTree::new();
+
+        for pgn_game in games {
+            tree.add_game(&pgn_game.game, plies);
+        }
+
+        return tree;
+    }
+
+    /// Counts the moves of the first plies of the main line of the game.
+    pub fn add_game(&mut self, game: &Game, plies: usize) {
+        let mut position = game.starting_position().clone();
+
+        for m in game.main_line().into_iter().take(plies) {
+            let moves = self.moves.entry(position.hash_key()).or_default();
+
+            match moves.iter_mut().find(|(played, _)| *played == m) {
+                Some((_, count)) => *count += 1,
+                None             => moves.push((m, 1))
+            }
+
+            position = position.make_move(m);
+        }
+    }
+
+    /// Returns the moves played in the position and how often, the most frequent first.
+    pub fn moves(&self, position: &Position) -> Vec<(Move, u32)> {
+        let mut moves = self.moves.get(&position.hash_key()).cloned().unwrap_or_default();
+        moves.sort_by_key(|(_, count)| u32::MAX - count);
+
+        return moves;
+    }
+}
+
+/// What a repertoire covers and how the engine picks its moves.
+#[derive(Clone, Copy, Debug)]
+pub struct RepertoireOptions {
+    /// The color the repertoire is for.
+    pub color: u8,
+    /// The number of plies from the starting position the repertoire covers.
+    pub plies: usize,
+    /// The depth of the engine's analysis of the moves of the tree.
+    pub depth: u8,
+    /// The most replies of the opponent kept in a position.
+    pub replies: usize,
+    /// How many centipawns a reply may be worse for the opponent than their best reply of the
+    /// tree to still be prepared for.
+    pub margin: i32
+}
+
+impl Default for RepertoireOptions {
+    fn default() -> RepertoireOptions {
+        return RepertoireOptions { color: WHITE, plies: 10, depth: 3, replies: 3, margin: 50 };
+    }
+}
+
+/// Builds a repertoire from the moves of the opening tree: in each position of the player the
+/// move of the tree the engine likes best and in each position of the opponent the replies of
+/// the tree the engine approves of, best first. The line ends where the tree has no moves.
+/// Each move is commented with its evaluation from white's point of view and the number of
+/// games it was played in.
+pub fn build(tree: &OpeningTree, start: Position, options: &RepertoireOptions) -> Game {
+    let mut game = Game::from_position(start);
+    extend(tree, &mut game, options);
+    game.goto_node(game.root());
+
+    return game;
+}
+
+/// Adds the repertoire moves of the current position and their continuations to the game.
+fn extend(tree: &OpeningTree, game: &mut Game, options: &RepertoireOptions) {
+    if game.ply() >= options.plies {
+        return;
+    }
+
+    let position = game.position().clone();
+    let played = tree.moves(&position);
+
+    if played.is_empty() {
+        return;
+    }
+
+    let limits = Limits { searchmoves: played.iter().map(|(m, _)| *m).collect(), ..Limits::default() };
+    let scores = search::evaluate_moves(&position, options.depth, &limits);
+    let best = scores[0].1;
+
+    let chosen = match position.turn == options.color {
+        true  => &scores[..1],
+        false => &scores[..scores.iter().take(options.replies).take_while(|(_, score)| best - score <= options.margin).count()]
+    };
+
+    let node = game.current_node();
+
+    for &(m, score) in chosen {
+        let games = played.iter().find(|(played, _)| *played == m).map_or(0, |(_, count)| *count);
+        let white_score = if position.turn == WHITE { score } else { -score };
+
+        game.goto_node(node);
+        game.play(m);
+        game.set_comment(format!("{} ({} {})", Score::new(white_score).to_pawns(), games, if games == 1 { "game" } else { "games" }));
+
+        extend(tree, game, options);
+    }
+}
+
+/// Encodes the repertoire as PGN, with an Event tag naming the color it's for.
+pub fn to_pgn(repertoire: &Game, options: &RepertoireOptions) -> String {
+    let color = if options.color == WHITE { "White" } else { "Black" };
+    let tags = [(String::from("Event"), format!("{} repertoire", color))];
+
+    return pgn::write_game(repertoire, &tags, "*");
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::NodeId;
+    use crate::position::BLACK;
+
+    fn games() -> Vec<PgnGame> {
+        let lines = ["1. e4 e5 2. Nf3 Nc6", "1. e4 e5 2. Nf3 d6", "1. e4 e5 2. Nf3 Qg5", "1. e4 c5 2. Nf3 d6", "1. h3 e5"];
+
+        return pgn::read_games(&lines.iter().map(|line| format!("[Result \"*\"]\n\n{} *\n\n", line)).collect::<String>());
+    }
+
+    fn nodes(game: &Game, node: NodeId) -> Vec<NodeId> {
+        return std::iter::once(node).chain(game.children(node).iter().flat_map(|&child| nodes(game, child))).collect();
+    }
+
+    #[test]
+    fn counts_the_moves_of_the_games() {
+        let tree = OpeningTree::from_games(&games(), 2);
+        let start = Position::starting_position();
+        let san = |position: &Position| tree.moves(position).iter().map(|(m, count)| (position.move_to_san(*m), *count)).collect::<Vec<_>>();
+
+        assert_eq!(san(&start), vec![(String::from("e4"), 4), (String::from("h3"), 1)]);
+
+        // Only the first two plies are counted.
+        let e4 = start.make_move(start.san_to_move("e4").unwrap());
+        let e5 = e4.make_move(e4.san_to_move("e5").unwrap());
+        assert_eq!(san(&e4), vec![(String::from("e5"), 3), (String::from("c5"), 1)]);
+        assert!(tree.moves(&e5).is_empty());
+    }
+
+    #[test]
+    fn keeps_one_move_for_the_player_and_the_sound_replies() {
+        let tree = OpeningTree::from_games(&games(), 10);
+        let options = RepertoireOptions { plies: 4, margin: 100, ..RepertoireOptions::default() };
+        let repertoire = build(&tree, Position::starting_position(), &options);
+        let pgn = to_pgn(&repertoire, &options);
+
+        for node in nodes(&repertoire, repertoire.root()) {
+            let position = repertoire.node_position(node);
+
+            assert!(position.turn == BLACK || repertoire.children(node).len() <= 1, "{}", pgn);
+        }
+
+        // The queen sortie loses the queen to Nxg5 and h3 isn't white's best move.
+        assert!(pgn.starts_with("[Event \"White repertoire\"]\n\n1. e4 {"), "{}", pgn);
+        assert!(pgn.contains("(4 games)") && pgn.contains("c5"), "{}", pgn);
+        assert!(!pgn.contains("Qg5") && !pgn.contains("h3"), "{}", pgn);
+
+        // Black prepares an answer to both first moves of the tree.
+        let options = RepertoireOptions { color: BLACK, plies: 2, ..options };
+        let repertoire = build(&tree, Position::starting_position(), &options);
+        assert_eq!(repertoire.children(repertoire.root()).len(), 2);
+        assert!(repertoire.children(repertoire.root()).iter().all(|&node| repertoire.children(node).len() == 1));
+    }
+}
@@ -0,0 +1,264 @@
+use std::fmt;
+
+use crate::game::Game;
+use crate::position::{Position, WHITE, BLACK};
+use crate::search;
+use crate::wdl;
+
+/// Evaluations are clamped to this value (in centipawns) before they are drawn or
+/// compared.
+const GRAPH_CLAMP: i32 = 1000;
+const GRAPH_WIDTH: u32 = 600;
+const GRAPH_HEIGHT: u32 = 200;
+
+/// Minimum centipawn losses for a move to be classified as an inaccuracy, mistake or blunder.
+const INACCURACY_LOSS: i32 = 50;
+const MISTAKE_LOSS: i32 = 100;
+const BLUNDER_LOSS: i32 = 300;
+
+
+/// The classification of a single move based on how much evaluation it gave away.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Judgement {
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder
+}
+
+impl Judgement {
+    /// Classifies a move that lost the given amount of centipawns.
+    pub fn from_loss(loss: i32) -> Judgement {
+        return match loss {
+            l if l >= BLUNDER_LOSS     => Judgement::Blunder,
+            l if l >= MISTAKE_LOSS     => Judgement::Mistake,
+            l if l >= INACCURACY_LOSS  => Judgement::Inaccuracy,
+            _                          => Judgement::Good
+        };
+    }
+
+    /// The symbol the move is marked with in annotations, e.g. ?? for a blunder.
+    pub fn symbol(self) -> &'static str {
+        return match self {
+            Judgement::Good       => "",
+            Judgement::Inaccuracy => "?!",
+            Judgement::Mistake    => "?",
+            Judgement::Blunder    => "??"
+        };
+    }
+}
+
+/// Statistics about the moves of one side.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SideSummary {
+    pub moves: u32,
+    pub accuracy: Option<f64>,
+    pub inaccuracies: u32,
+    pub mistakes: u32,
+    pub blunders: u32
+}
+
+/// A report about a whole game generated from the evaluations of all positions in it.
+pub struct GameReport {
+    /// The evaluation of each position in centipawns from white's perspective, starting
+    /// with the position before the first move.
+    pub evals: Vec<i32>,
+    /// The judgement of each move in the game.
+    pub judgements: Vec<Judgement>,
+    /// The accuracy of each move in the game.
+    pub accuracies: Vec<f64>,
+    /// The summary for white and black.
+    pub sides: [SideSummary; 2]
+}
+
+impl GameReport {
+    /// Generates a report from the evaluations of all positions in a game. The first side to
+    /// move is given by the parameter first.
+    pub fn new(evals: Vec<i32>, first: u8) -> GameReport {
+        let mut judgements = Vec::new();
+        let mut accuracies = Vec::new();
+        let mut sides = [SideSummary::default(); 2];
+
+        for (i, w) in evals.windows(2).enumerate() {
+            let color = if i % 2 == 0 { first } else { 1 - first };
+
+            let judgement = Judgement::from_loss(centipawn_loss(w[0], w[1], color));
+            let side = &mut sides[color as usize];
+
+            side.moves += 1;
+
+            match judgement {
+                Judgement::Inaccuracy => side.inaccuracies += 1,
+                Judgement::Mistake    => side.mistakes += 1,
+                Judgement::Blunder    => side.blunders += 1,
+                Judgement::Good       => ()
+            }
+
+            judgements.push(judgement);
+            accuracies.push(move_accuracy(w[0], w[1], color));
+        }
+
+        for color in [WHITE, BLACK] {
+            let side_accuracies: Vec<f64> = accuracies.iter().enumerate()
+                .filter(|(i, _)| (*i % 2 == 0) == (color == first))
+                .map(|(_, a)| *a)
+                .collect();
+
+            sides[color as usize].accuracy = wdl::game_accuracy(&side_accuracies);
+        }
+
+        return GameReport { evals, judgements, accuracies, sides };
+    }
+
+    /// Renders the evaluation series as an SVG score graph. White's advantage is drawn
+    /// as the area above the center line.
+    pub fn to_svg(&self) -> String {
+        let n = self.evals.len().max(2) - 1;
+        let w = GRAPH_WIDTH as f64;
+        let h = GRAPH_HEIGHT as f64;
+
+        let point = |i: usize, eval: i32| -> (f64, f64) {
+            let x = w * (i as f64) / (n as f64);
+            let y = h / 2.0 - (eval.clamp(-GRAPH_CLAMP, GRAPH_CLAMP) as f64) * h / (2.0 * GRAPH_CLAMP as f64);
+
+            return (x, y);
+        };
+
+        let mut points: Vec<String> = vec![format!("0,{}", h / 2.0)];
+
+        for (i, eval) in self.evals.iter().enumerate() {
+            let (x, y) = point(i, *eval);
+            points.push(format!("{:.1},{:.1}", x, y));
+        }
+
+        points.push(format!("{:.1},{}", point(self.evals.len().max(1) - 1, 0).0, h / 2.0));
+
+        let mut svg = String::new();
+
+        svg.push_str(&format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n", w=GRAPH_WIDTH, h=GRAPH_HEIGHT));
+        svg.push_str(&format!("  <rect width=\"{}\" height=\"{}\" fill=\"#404040\"/>\n", GRAPH_WIDTH, GRAPH_HEIGHT));
+        svg.push_str(&format!("  <polygon points=\"{}\" fill=\"#f0f0f0\"/>\n", points.join(" ")));
+        svg.push_str(&format!("  <line x1=\"0\" y1=\"{y}\" x2=\"{w}\" y2=\"{y}\" stroke=\"#808080\"/>\n", y=h / 2.0, w=GRAPH_WIDTH));
+
+        // Mark mistakes and blunders with dots on the graph.
+        for (i, judgement) in self.judgements.iter().enumerate() {
+            let color = match judgement {
+                Judgement::Mistake => "#e69f00",
+                Judgement::Blunder => "#df5353",
+                _                  => continue
+            };
+
+            let (x, y) = point(i + 1, self.evals[i + 1]);
+            svg.push_str(&format!("  <circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"{}\"/>\n", x, y, color));
+        }
+
+        svg.push_str("</svg>\n");
+
+        return svg;
+    }
+}
+
+impl fmt::Display for GameReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (color, name) in [(WHITE, "White"), (BLACK, "Black")] {
+            let side = &self.sides[color as usize];
+
+            let accuracy = side.accuracy.map_or(String::from("-"), |a| format!("{:.1}%", a));
+
+            writeln!(f, "{}: accuracy {}, {} inaccuracies, {} mistakes, {} blunders", name, accuracy, side.inaccuracies, side.mistakes, side.blunders)?;
+        }
+
+        return Ok(());
+    }
+}
+
+/// Evaluates every position of the game's main line with a search of the given depth and
+/// generates the report from the evaluations.
+pub fn analyze_game(game: &Game, depth: u8) -> GameReport {
+    let mut position = game.starting_position().clone();
+    let mut evals = vec![white_score(&position, depth)];
+
+    for &m in game.moves() {
+        position = position.make_move(m);
+        evals.push(white_score(&position, depth));
+    }
+
+    return GameReport::new(evals, game.starting_position().turn);
+}
+
+/// Searches the position and returns its score from white's perspective.
+fn white_score(position: &Position, depth: u8) -> i32 {
+    let score = match search::search(position, depth) {
+        Some((_, score)) => score,
+        None             => search::negamax(position, depth, 0)
+    };
+
+    return if position.turn == WHITE { score } else { -score };
+}
+
+/// Computes how many centipawns the side with the given color lost by moving from a
+/// position evaluated with before to a position evaluated with after. Both evaluations
+/// are given from white's perspective and capped in order to keep mate scores from
+/// dominating the statistics.
+pub fn centipawn_loss(before: i32, after: i32, color: u8) -> i32 {
+    let before = before.clamp(-GRAPH_CLAMP, GRAPH_CLAMP);
+    let after = after.clamp(-GRAPH_CLAMP, GRAPH_CLAMP);
+
+    let loss = if color == WHITE { before - after } else { after - before };
+
+    return loss.max(0);
+}
+
+/// Computes the accuracy of a move made by the side with the given color based on
+/// the evaluations before and after the move from white's perspective.
+pub fn move_accuracy(before: i32, after: i32, color: u8) -> f64 {
+    let sign = if color == WHITE { 1 } else { -1 };
+
+    return wdl::move_accuracy(wdl::win_probability(sign * before), wdl::win_probability(sign * after));
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_a_short_game() {
+        let mut game = Game::new();
+
+        // The fool's mate: white blunders with 2. g4?? and is mated.
+        for uci in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+            game.play(game.position().uci_to_move(uci).unwrap());
+        }
+
+        let report = analyze_game(&game, 2);
+
+        assert_eq!(report.evals.len(), 5);
+        assert_eq!(report.judgements[2], Judgement::Blunder);
+        assert_eq!((report.sides[WHITE as usize].blunders, report.sides[BLACK as usize].blunders), (1, 0));
+        assert!(report.sides[BLACK as usize].accuracy.unwrap() > report.sides[WHITE as usize].accuracy.unwrap());
+        assert!(report.to_string().starts_with("White: accuracy "));
+
+        let svg = report.to_svg();
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"600\" height=\"200\""));
+        assert!(svg.trim_end().ends_with("</svg>"));
+
+        // One point per position plus the two corners closing the area, and a red dot on the
+        // blunder. The mate is drawn at the bottom edge.
+        let points = svg.split("points=\"").nth(1).unwrap().split('"').next().unwrap();
+
+        assert_eq!(points.split(' ').count(), 7);
+        assert!(points.contains("600.0,200.0"));
+        assert_eq!(svg.matches("<circle").count(), 1);
+        assert!(svg.contains("cx=\"450.0\""));
+    }
+
+    #[test]
+    fn marks_moves_by_their_judgement() {
+        assert_eq!(Judgement::from_loss(20).symbol(), "");
+        assert_eq!(Judgement::from_loss(60).symbol(), "?!");
+        assert_eq!(Judgement::from_loss(150).symbol(), "?");
+        assert_eq!(Judgement::from_loss(300).symbol(), "??");
+    }
+}
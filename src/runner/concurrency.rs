@@ -0,0 +1,207 @@
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+use crate::runner::{self, Engine, GameRecord};
+use crate::runner::openings::OpeningSuite;
+
+
+/// Creates a fresh instance of an engine. Every worker thread plays its games with its
+/// own engine instances.
+pub type EngineFactory = Arc<dyn Fn() -> Box<dyn Engine> + Send + Sync>;
+
+/// Settings for playing games concurrently.
+#[derive(Clone, Copy, Debug)]
+pub struct ConcurrencyConfig {
+    /// The number of games played at the same time.
+    pub concurrency: usize,
+    /// Pin every worker thread to its own core so games don't compete for the same core.
+    pub pin_threads: bool
+}
+
+impl Default for ConcurrencyConfig {
+    fn default() -> ConcurrencyConfig {
+        return ConcurrencyConfig { concurrency: 1, pin_threads: false };
+    }
+}
+
+/// Plays the given number of games using several worker threads. The engines alternate
+/// colors like in play_pairing and each opening of the suite is played twice in a row.
+///
+/// The records are returned in the order the games were scheduled in.
+pub fn play_games(engines: [EngineFactory; 2], openings: OpeningSuite, n: usize, max_plies: usize, config: ConcurrencyConfig) -> Vec<GameRecord> {
+    let mut records = Vec::new();
+
+    play_games_as_finished(engines, Arc::new(openings), 0..n, max_plies, config, |idx, record| {
+        records.push((idx, record));
+
+        return true;
+    });
+
+    records.sort_by_key(|(idx, _)| *idx);
+
+    return records.into_iter().map(|(_, record)| record).collect();
+}
+
+/// Plays the games with the given indices using several worker threads, like play_games.
+/// Every record is passed to the callback on the calling thread as soon as its game is
+/// finished, so the records arrive in the order the games finish in, not in the order they
+/// were scheduled in. Once the callback returns false no more games are started; the games
+/// still running are finished, but their records are dropped.
+pub fn play_games_as_finished<F: FnMut(usize, GameRecord) -> bool>(engines: [EngineFactory; 2], openings: Arc<OpeningSuite>, games: Range<usize>,
+                                                                   max_plies: usize, config: ConcurrencyConfig, mut on_record: F) {
+    let next = Arc::new(AtomicUsize::new(games.start));
+    let stop = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = mpsc::channel();
+
+    let mut workers = Vec::new();
+
+    for worker in 0..config.concurrency.max(1).min(games.len().max(1)) {
+        let engines = engines.clone();
+        let openings = Arc::clone(&openings);
+        let (next, stop) = (Arc::clone(&next), Arc::clone(&stop));
+        let sender = sender.clone();
+        let end = games.end;
+
+        workers.push(thread::spawn(move || {
+            if config.pin_threads {
+                pin_to_core(worker);
+            }
+
+            let mut instances = [engines[0](), engines[1]()];
+
+            while !stop.load(Ordering::SeqCst) {
+                let idx = next.fetch_add(1, Ordering::SeqCst);
+
+                if idx >= end {
+                    break;
+                }
+
+                let record = runner::play_pairing(&mut instances, idx, openings.for_game(idx), max_plies);
+
+                if sender.send((idx, record)).is_err() {
+                    break;
+                }
+            }
+        }));
+    }
+
+    drop(sender);
+
+    for (idx, record) in receiver.iter() {
+        if !on_record(idx, record) {
+            stop.store(true, Ordering::SeqCst);
+            break;
+        }
+    }
+
+    // Workers still playing notice the closed channel when they finish their game.
+    drop(receiver);
+
+    for worker in workers {
+        worker.join().expect("Worker thread panicked!");
+    }
+}
+
+/// Returns the number of cores available to the process.
+pub fn available_cores() -> usize {
+    return thread::available_parallelism().map_or(1, |n| n.get());
+}
+
+/// Pins the calling thread to the Nth of the cores the process may run on (modulo their
+/// number), so pinning respects restrictions like taskset or cgroup cpusets. Returns false
+/// if pinning isn't supported or failed.
+#[cfg(target_os = "linux")]
+pub fn pin_to_core(core: usize) -> bool {
+    extern "C" {
+        fn sched_setaffinity(pid: i32, cpusetsize: usize, mask: *const u64) -> i32;
+    }
+
+    let allowed = allowed_cores();
+
+    if allowed.is_empty() {
+        return false;
+    }
+
+    let cpu = allowed[core % allowed.len()];
+    let mut set = [0u64; CPU_SET_WORDS];
+
+    set[cpu / 64] |= 1 << (cpu % 64);
+
+    // A pid of 0 refers to the calling thread.
+    unsafe {
+        return sched_setaffinity(0, std::mem::size_of_val(&set), set.as_ptr()) == 0;
+    }
+}
+
+/// The number of words of cpu_set_t, which supports up to 1024 cores.
+#[cfg(target_os = "linux")]
+const CPU_SET_WORDS: usize = 16;
+
+/// Returns the indices of the cores the calling thread may run on in ascending order, or
+/// none if they can't be determined.
+#[cfg(target_os = "linux")]
+pub fn allowed_cores() -> Vec<usize> {
+    extern "C" {
+        fn sched_getaffinity(pid: i32, cpusetsize: usize, mask: *mut u64) -> i32;
+    }
+
+    let mut set = [0u64; CPU_SET_WORDS];
+
+    unsafe {
+        if sched_getaffinity(0, std::mem::size_of_val(&set), set.as_mut_ptr()) != 0 {
+            return Vec::new();
+        }
+    }
+
+    return (0..CPU_SET_WORDS * 64).filter(|cpu| set[cpu / 64] & (1 << (cpu % 64)) != 0).collect();
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_to_core(_core: usize) -> bool {
+    return false;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+    use crate::search::SearchEngine;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn pins_to_allowed_cores() {
+        let allowed = allowed_cores();
+
+        assert!(!allowed.is_empty());
+
+        // Pinning a thread restricts it to a single one of the cores allowed before.
+        let pinned = thread::spawn(|| (pin_to_core(allowed_cores().len() + 1), allowed_cores())).join().unwrap();
+
+        assert!(pinned.0);
+        assert_eq!(pinned.1, vec![allowed[1 % allowed.len()]]);
+        assert_eq!(allowed_cores(), allowed);
+    }
+
+    #[test]
+    fn returns_the_records_in_the_scheduled_order() {
+        let factory = || -> EngineFactory { Arc::new(|| -> Box<dyn Engine> { Box::new(SearchEngine::new(1)) }) };
+        let play = |concurrency: usize| {
+            let openings = OpeningSuite::single(Position::starting_position());
+
+            return play_games([factory(), factory()], openings, 4, 6, ConcurrencyConfig { concurrency, pin_threads: false });
+        };
+
+        let sequential = play(1);
+        let concurrent = play(3);
+
+        assert_eq!(concurrent.len(), 4);
+
+        for (idx, (a, b)) in sequential.iter().zip(concurrent.iter()).enumerate() {
+            assert_eq!(b.players, if idx % 2 == 0 { [0, 1] } else { [1, 0] });
+            assert_eq!(a.game.moves(), b.game.moves());
+        }
+    }
+}
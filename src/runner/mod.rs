@@ -0,0 +1,244 @@
+pub mod concurrency;
+pub mod openings;
+pub mod output;
+pub mod pgn;
+pub mod sprt;
+pub mod stats;
+pub mod tournament;
+pub mod uci_engine;
+pub mod worker;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
+
+use crate::game::Game;
+use crate::moves::Move;
+use crate::position::{Position, WHITE};
+use crate::runner::concurrency::{ConcurrencyConfig, EngineFactory};
+use crate::runner::openings::{Opening, OpeningSuite};
+use crate::runner::stats::EngineStats;
+use crate::search::{SearchInfo, SearchLimits};
+use crate::strength::Strength;
+
+
+/// A move chosen by an engine together with the score it assigned to the position in
+/// centipawns from its own perspective.
+#[derive(Clone, Copy)]
+pub struct EngineMove {
+    pub m: Move,
+    pub score: Option<i32>
+}
+
+/// One of the best lines an engine found: the moves starting with the move to play and the
+/// score in centipawns from the point of view of the player to move.
+#[derive(Clone, Debug)]
+pub struct EngineLine {
+    pub pv: Vec<Move>,
+    pub score: Option<i32>
+}
+
+/// Interface for everything that can take part in a match.
+pub trait Engine {
+    /// A name describing the engine and its configuration.
+    fn name(&self) -> String;
+
+    /// The version of the engine if it's known.
+    fn version(&self) -> Option<String> {
+        return None;
+    }
+
+    /// The options the engine plays with as names and values, so games can be reproduced.
+    fn options(&self) -> Vec<(String, String)> {
+        return Vec::new();
+    }
+
+    /// Called before a new game starts.
+    fn new_game(&mut self) {}
+
+    /// Returns the flag that stops the engine's running search when it's set, if the engine
+    /// can be stopped. The search still returns the best move found so far.
+    fn stop_flag(&self) -> Option<Arc<AtomicBool>> {
+        return None;
+    }
+
+    /// Weakens the engine's play to the given strength or restores its full strength with
+    /// None. Returns false if the engine's strength can't be limited.
+    fn set_strength(&mut self, strength: Option<Strength>) -> bool {
+        let _ = strength;
+
+        return false;
+    }
+
+    /// Chooses a move in the current position of the game. Returns None if the engine
+    /// resigns or fails to produce a move.
+    fn choose_move(&mut self, game: &Game) -> Option<EngineMove>;
+
+    /// Like choose_move, but within the given limits. Engines that can't be limited this way
+    /// ignore them.
+    fn choose_move_within(&mut self, game: &Game, limits: &SearchLimits) -> Option<EngineMove> {
+        let _ = limits;
+
+        return self.choose_move(game);
+    }
+
+    /// Like choose_move_within, but the progress of the search is reported to the callback
+    /// while it runs. Engines that don't report their progress never call it.
+    fn choose_move_reporting(&mut self, game: &Game, limits: &SearchLimits, on_info: &mut dyn FnMut(&SearchInfo)) -> Option<EngineMove> {
+        let _ = on_info;
+
+        return self.choose_move_within(game, limits);
+    }
+
+    /// Searches the given number of best moves in the current position of the game (MultiPV)
+    /// within the given limits, best line first. Engines that only find a single move return
+    /// just that.
+    fn choose_lines(&mut self, game: &Game, count: usize, limits: &SearchLimits) -> Vec<EngineLine> {
+        let _ = count;
+
+        return self.choose_move_within(game, limits).map(|em| EngineLine { pv: vec![em.m], score: em.score }).into_iter().collect();
+    }
+}
+
+/// The outcome of a finished game.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    WhiteWins,
+    BlackWins,
+    Draw
+}
+
+impl Outcome {
+    /// Returns the outcome in the notation used by PGN.
+    pub fn to_pgn(self) -> &'static str {
+        return match self {
+            Outcome::WhiteWins => "1-0",
+            Outcome::BlackWins => "0-1",
+            Outcome::Draw      => "1/2-1/2"
+        };
+    }
+
+    /// Returns the outcome of a game lost by the side with the given color.
+    fn loss_for(color: u8) -> Outcome {
+        return if color == WHITE { Outcome::BlackWins } else { Outcome::WhiteWins };
+    }
+}
+
+/// A game played between two engines.
+pub struct GameRecord {
+    /// The indices of the engines that played white and black.
+    pub players: [usize; 2],
+    /// The name of the opening the game started from.
+    pub opening: String,
+    pub game: Game,
+    pub outcome: Outcome,
+    /// The score reported for each move converted to white's perspective.
+    pub scores: Vec<Option<i32>>,
+    /// The time the engine took to choose each move.
+    pub times: Vec<Duration>
+}
+
+/// The moves of a game played by play_game together with how it ended, the reported scores
+/// and the time each move took.
+pub type PlayedGame = (Game, Outcome, Vec<Option<i32>>, Vec<Duration>);
+
+/// Plays a single game between two engines starting at the given position. The game
+/// is adjudicated as a draw after max_plies half moves.
+pub fn play_game(white: &mut dyn Engine, black: &mut dyn Engine, start: Position, max_plies: usize) -> PlayedGame {
+    let mut game = Game::from_position(start);
+    let mut scores = Vec::new();
+    let mut times = Vec::new();
+
+    white.new_game();
+    black.new_game();
+
+    loop {
+        let mut position = game.position().clone();
+        let color = position.turn;
+        let legal_moves = position.get_all_legal_moves();
+
+        if legal_moves.is_empty() {
+            let outcome = if position.is_checked(color) { Outcome::loss_for(color) } else { Outcome::Draw };
+
+            return (game, outcome, scores, times);
+        }
+
+        if position.is_threefold_repetition() || game.moves().len() >= max_plies {
+            return (game, Outcome::Draw, scores, times);
+        }
+
+        let engine: &mut dyn Engine = if color == WHITE { &mut *white } else { &mut *black };
+
+        let start = Instant::now();
+
+        // An engine that doesn't produce a legal move loses the game.
+        let em = match engine.choose_move(&game) {
+            Some(em) if legal_moves.contains(&em.m) => em,
+            _ => return (game, Outcome::loss_for(color), scores, times)
+        };
+
+        times.push(start.elapsed());
+        scores.push(em.score.map(|s| if color == WHITE { s } else { -s }));
+        game.play(em.m);
+    }
+}
+
+/// Plays the game with the given index of a match between two engines. The engines
+/// alternate colors, i.e. the first engine plays white in all games with an even index.
+pub fn play_pairing(engines: &mut [Box<dyn Engine>; 2], idx: usize, opening: &Opening, max_plies: usize) -> GameRecord {
+    let players = if idx.is_multiple_of(2) { [0, 1] } else { [1, 0] };
+
+    let (first, second) = engines.split_at_mut(1);
+    let (white, black) = if players[0] == 0 {
+        (&mut first[0], &mut second[0])
+    } else {
+        (&mut second[0], &mut first[0])
+    };
+
+    let (game, outcome, scores, times) = play_game(white.as_mut(), black.as_mut(), opening.position.clone(), max_plies);
+
+    return GameRecord { players, opening: opening.name.clone(), game, outcome, scores, times };
+}
+
+/// Plays a match between two engines. The engines alternate colors after every game.
+pub struct Match {
+    pub engines: [Box<dyn Engine>; 2],
+    pub max_plies: usize,
+    pub records: Vec<GameRecord>
+}
+
+impl Match {
+    pub fn new(engines: [Box<dyn Engine>; 2], max_plies: usize) -> Match {
+        return Match { engines, max_plies, records: Vec::new() };
+    }
+
+    /// Plays the next game of the match starting from the given opening.
+    pub fn play_next(&mut self, opening: &Opening) -> &GameRecord {
+        let record = play_pairing(&mut self.engines, self.records.len(), opening, self.max_plies);
+
+        self.records.push(record);
+
+        return self.records.last().unwrap();
+    }
+
+    /// Plays the next n games of the match on worker threads with engines created by the
+    /// factories instead of the match's own engines, see concurrency::play_games.
+    pub fn play_concurrently(&mut self, factories: [EngineFactory; 2], openings: &OpeningSuite, n: usize, config: ConcurrencyConfig) {
+        let first = self.records.len();
+        let mut finished = BTreeMap::new();
+
+        concurrency::play_games_as_finished(factories, Arc::new(openings.clone()), first..first + n, self.max_plies, config, |idx, record| {
+            finished.insert(idx, record);
+
+            return true;
+        });
+
+        self.records.extend(finished.into_values());
+    }
+
+    /// Computes the statistics of the engine with the given index over all games played.
+    pub fn stats(&self, engine: usize) -> EngineStats {
+        return EngineStats::from_records(engine, &self.records);
+    }
+}
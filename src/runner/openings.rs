@@ -0,0 +1,112 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::pgn;
+use crate::position::Position;
+
+
+/// A starting position for games of a match.
+#[derive(Clone)]
+pub struct Opening {
+    pub name: String,
+    pub position: Position
+}
+
+impl Opening {
+    /// Parses a line of an EPD file. The name is taken from the id opcode if present.
+    pub fn from_epd(line: &str) -> Option<Opening> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+
+        if fields.len() < 4 {
+            return None;
+        }
+
+        let position = Position::from_fen(fields[..4].join(" "))?;
+        let operations = fields[4..].join(" ");
+
+        let name = operations.split(';')
+            .map(|op| op.trim())
+            .find_map(|op| op.strip_prefix("id "))
+            .map(|id| id.trim().trim_matches('"').to_string())
+            .unwrap_or_else(|| fields[..4].join(" "));
+
+        return Some(Opening { name, position });
+    }
+
+    /// Encodes the opening as a line of an EPD file.
+    pub fn to_epd(&self) -> String {
+        let fen = self.position.to_fen();
+        let fields: Vec<&str> = fen.split(' ').take(4).collect();
+
+        return format!("{} id \"{}\";", fields.join(" "), self.name.replace('"', "'"));
+    }
+}
+
+/// A set of openings the games of a match start from. Every opening is played twice
+/// in a row so each engine gets to play both sides of it.
+#[derive(Clone)]
+pub struct OpeningSuite {
+    pub openings: Vec<Opening>
+}
+
+impl OpeningSuite {
+    /// A suite that only consists of the given position.
+    pub fn single(position: Position) -> OpeningSuite {
+        return OpeningSuite { openings: vec![Opening { name: String::from("start"), position }] };
+    }
+
+    /// Reads all positions of an EPD file.
+    pub fn from_epd(text: &str) -> OpeningSuite {
+        let openings = text.lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(Opening::from_epd)
+            .collect();
+
+        return OpeningSuite { openings };
+    }
+
+    /// Reads all games of a PGN file and uses their final positions as openings. The
+    /// opening is named after the Opening tag or the moves leading to the position.
+    pub fn from_pgn(text: &str) -> OpeningSuite {
+        let openings = pgn::read_games(text).into_iter().map(|pgn_game| {
+            let mut position = pgn_game.game.starting_position().clone();
+            let mut moves = Vec::new();
+
+            for m in pgn_game.game.moves() {
+                moves.push(position.move_to_san(*m));
+                position = position.make_move(*m);
+            }
+
+            let name = pgn_game.tag("Opening").map_or_else(|| moves.join(" "), |o| o.to_string());
+
+            return Opening { name, position: pgn_game.game.position().clone() };
+        }).collect();
+
+        return OpeningSuite { openings };
+    }
+
+    /// Loads an opening suite from an EPD or PGN file depending on the file's extension.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<OpeningSuite> {
+        let text = fs::read_to_string(&path)?;
+
+        let suite = match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some("pgn") => OpeningSuite::from_pgn(&text),
+            Some("epd") => OpeningSuite::from_epd(&text),
+            _           => return Err(io::Error::new(io::ErrorKind::InvalidInput, "Opening suites have to be EPD or PGN files"))
+        };
+
+        if suite.openings.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "The opening suite doesn't contain any positions"));
+        }
+
+        return Ok(suite);
+    }
+
+    /// Returns the opening used by the game with the given index. Two consecutive games
+    /// share the same opening.
+    pub fn for_game(&self, idx: usize) -> &Opening {
+        return &self.openings[(idx / 2) % self.openings.len()];
+    }
+}
@@ -0,0 +1,78 @@
+use crate::runner::{GameRecord, Outcome};
+use crate::runner::sprt::{MatchScore, Sprt};
+
+// Functions producing progress and result lines in the format printed by cutechess-cli,
+// so tools parsing its output can consume the results of a match.
+
+
+/// The line printed when a game starts.
+pub fn started_game(idx: usize, total: usize, white: &str, black: &str) -> String {
+    return format!("Started game {} of {} ({} vs {})", idx + 1, total, white, black);
+}
+
+/// The line printed when a game is finished.
+pub fn finished_game(idx: usize, record: &GameRecord, white: &str, black: &str) -> String {
+    let reason = match record.outcome {
+        Outcome::WhiteWins => "White wins",
+        Outcome::BlackWins => "Black wins",
+        Outcome::Draw      => "Draw"
+    };
+
+    return format!("Finished game {} ({} vs {}): {} {{{}}}", idx + 1, white, black, record.outcome.to_pgn(), reason);
+}
+
+/// The lines summarizing the current score of a match from the first engine's perspective.
+pub fn match_score(score: &MatchScore, names: [&str; 2], sprt: Option<&Sprt>) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    lines.push(format!("Score of {} vs {}: {} - {} - {}  [{:.3}] {}", names[0], names[1], score.wins, score.losses, score.draws,
+                       score.score().unwrap_or(0.0), score.games()));
+
+    if let Some((elo, margin)) = score.elo() {
+        lines.push(format!("Elo difference: {:.1} +/- {:.1}, LOS: {:.1} %, DrawRatio: {:.1} %", elo, margin,
+                           100.0 * score.los().unwrap_or(0.5), 100.0 * score.draw_ratio().unwrap_or(0.0)));
+    }
+
+    let p = score.pentanomial;
+    lines.push(format!("Ptnml(0-2): [{}, {}, {}, {}, {}]", p[0], p[1], p[2], p[3], p[4]));
+
+    if let Some(sprt) = sprt {
+        let llr = sprt.llr(score);
+        let (lower, upper) = sprt.bounds();
+
+        lines.push(format!("SPRT: llr {:.3} ({:.1}%), lbound {:.2}, ubound {:.2}", llr, 100.0 * llr / upper, lower, upper));
+    }
+
+    return lines;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+    use crate::position::Position;
+
+    #[test]
+    fn prints_lines_like_cutechess() {
+        let record = GameRecord {
+            players: [1, 0], opening: String::new(), game: Game::from_position(Position::starting_position()),
+            outcome: Outcome::BlackWins, scores: Vec::new(), times: Vec::new()
+        };
+
+        assert_eq!(started_game(0, 10, "a", "b"), "Started game 1 of 10 (a vs b)");
+        assert_eq!(finished_game(1, &record, "b", "a"), "Finished game 2 (b vs a): 0-1 {Black wins}");
+
+        let score = MatchScore { wins: 12, draws: 5, losses: 8, pentanomial: [1, 2, 3, 4, 2] };
+        let sprt = Sprt { elo0: 0.0, elo1: 5.0, alpha: 0.05, beta: 0.05 };
+
+        assert_eq!(match_score(&score, ["a", "b"], Some(&sprt)), [
+            "Score of a vs b: 12 - 8 - 5  [0.580] 25",
+            "Elo difference: 56.1 +/- 129.1, LOS: 81.4 %, DrawRatio: 20.0 %",
+            "Ptnml(0-2): [1, 2, 3, 4, 2]",
+            "SPRT: llr 0.079 (2.7%), lbound -2.94, ubound 2.94"
+        ]);
+
+        assert_eq!(match_score(&MatchScore::default(), ["a", "b"], None), ["Score of a vs b: 0 - 0 - 0  [0.000] 0", "Ptnml(0-2): [0, 0, 0, 0, 0]"]);
+    }
+}
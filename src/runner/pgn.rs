@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use crate::game::Game;
+use crate::pgn;
+use crate::runner::{Engine, GameRecord};
+use crate::runner::uci_engine::UciLimit;
+use crate::search::Score;
+
+
+/// The name, version and options of an engine taking part in a match.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EngineInfo {
+    pub name: String,
+    pub version: Option<String>,
+    pub options: Vec<(String, String)>
+}
+
+impl EngineInfo {
+    pub fn of(engine: &dyn Engine) -> EngineInfo {
+        return EngineInfo { name: engine.name(), version: engine.version(), options: engine.options() };
+    }
+}
+
+/// The settings of a match that are recorded in the tags of its games, so every game can be
+/// reproduced and audited later.
+#[derive(Clone, Debug)]
+pub struct MatchInfo {
+    pub event: String,
+    /// The engines in the order of the match, i.e. the first one plays white in even games.
+    pub engines: [EngineInfo; 2],
+    /// The limit the engines got for each move if it's known.
+    pub limit: Option<UciLimit>
+}
+
+impl MatchInfo {
+    pub fn new(event: &str, engines: &[Box<dyn Engine>; 2], limit: Option<UciLimit>) -> MatchInfo {
+        let engines = [EngineInfo::of(engines[0].as_ref()), EngineInfo::of(engines[1].as_ref())];
+
+        return MatchInfo { event: event.to_string(), engines, limit };
+    }
+}
+
+/// Encodes the game with the given index of a match as PGN. Besides the usual tags, the
+/// versions and options of both engines and the limit per move are recorded. Every move is
+/// commented with the score the engine reported ([%eval] in pawns from white's perspective),
+/// the time it took ([%emt]) and, if the engines play with a time per move, the time left of
+/// it ([%clk]).
+pub fn write_record(record: &GameRecord, idx: usize, info: &MatchInfo) -> String {
+    let white = &info.engines[record.players[0]];
+    let black = &info.engines[record.players[1]];
+
+    let mut tags = vec![
+        (String::from("Event"), info.event.clone()),
+        (String::from("Site"), String::from("?")),
+        (String::from("Date"), String::from("????.??.??")),
+        (String::from("Round"), (idx + 1).to_string()),
+        (String::from("White"), white.name.clone()),
+        (String::from("Black"), black.name.clone()),
+        (String::from("Result"), record.outcome.to_pgn().to_string())
+    ];
+
+    for (color, engine) in [("White", white), ("Black", black)] {
+        if let Some(version) = &engine.version {
+            tags.push((format!("{}Version", color), version.clone()));
+        }
+
+        if !engine.options.is_empty() {
+            let options: Vec<String> = engine.options.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+
+            tags.push((format!("{}Options", color), options.join("; ")));
+        }
+    }
+
+    tags.push((String::from("TimeControl"), time_control(info.limit)));
+
+    if let Some(limit) = info.limit {
+        tags.push((String::from("Limit"), limit.to_string()));
+    }
+
+    if !record.opening.is_empty() {
+        tags.push((String::from("Opening"), record.opening.clone()));
+    }
+
+    tags.push((String::from("PlyCount"), record.game.moves().len().to_string()));
+
+    return pgn::write_game(&commented_game(record, info.limit), &tags, record.outcome.to_pgn());
+}
+
+/// Returns the TimeControl tag of the limit: a single move per period for a time per move
+/// and "-" for limits that aren't based on time.
+fn time_control(limit: Option<UciLimit>) -> String {
+    return match limit {
+        Some(UciLimit::MoveTime(ms)) => format!("1/{}", ms as f64 / 1000.0),
+        Some(_)                      => String::from("-"),
+        None                         => String::from("?")
+    };
+}
+
+/// Replays the game of the record with a comment holding the score and the time of each move.
+fn commented_game(record: &GameRecord, limit: Option<UciLimit>) -> Game {
+    let mut game = Game::from_position(record.game.starting_position().clone());
+
+    for (i, &m) in record.game.moves().iter().enumerate() {
+        let mut commands = Vec::new();
+
+        if let Some(&Some(score)) = record.scores.get(i) {
+            commands.push(format!("[%eval {}]", eval(score)));
+        }
+
+        if let Some(&time) = record.times.get(i) {
+            if let Some(UciLimit::MoveTime(ms)) = limit {
+                commands.push(format!("[%clk {}]", clock(Duration::from_millis(ms).saturating_sub(time))));
+            }
+
+            commands.push(format!("[%emt {}]", clock(time)));
+        }
+
+        game.play(m);
+
+        if !commands.is_empty() {
+            game.set_comment(commands.join(" "));
+        }
+    }
+
+    return game;
+}
+
+/// Formats a score from white's point of view as the value of an eval command: pawns like
+/// 0.25 or mates like #3 and #-2.
+fn eval(score: i32) -> String {
+    return match Score::new(score) {
+        Score::Centipawns(cp) => format!("{:.2}", cp as f64 / 100.0),
+        mate                  => mate.to_pawns()
+    };
+}
+
+/// Formats a duration as h:mm:ss.fff, the format of the clock commands.
+fn clock(time: Duration) -> String {
+    let secs = time.as_secs();
+
+    return format!("{}:{:02}:{:02}.{:03}", secs / 3600, secs / 60 % 60, secs % 60, time.subsec_millis());
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::Outcome;
+    use crate::search::MATE_SCORE;
+
+    #[test]
+    fn writes_mates_as_mate_evals() {
+        let mut game = Game::new();
+
+        for uci in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+            game.play(game.position().uci_to_move(uci).unwrap());
+        }
+
+        let record = GameRecord { players: [0, 1], opening: String::new(), game, outcome: Outcome::BlackWins,
+                                  scores: vec![Some(-25), Some(-MATE_SCORE + 4), Some(-MATE_SCORE + 2), None], times: Vec::new() };
+        let text = pgn::write_game(&commented_game(&record, None), &[], "0-1");
+
+        assert!(text.contains("{[%eval -0.25]}"));
+        assert!(text.contains("{[%eval #-2]}"));
+        assert!(text.contains("{[%eval #-1]}"));
+        assert!(!text.contains("299."));
+        assert_eq!(eval(MATE_SCORE - 3), "#2");
+    }
+}
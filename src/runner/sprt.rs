@@ -0,0 +1,241 @@
+use crate::runner::{GameRecord, Outcome};
+
+
+/// The results of a match from the perspective of the first engine, counted per game
+/// (trinomial) and per pair of games played with the same opening (pentanomial).
+#[derive(Clone, Copy, Default, Debug)]
+pub struct MatchScore {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    /// The number of game pairs in which the first engine scored 0, 0.5, 1, 1.5 and 2 points.
+    pub pentanomial: [u32; 5]
+}
+
+impl MatchScore {
+    /// Counts the results of all records. Only complete pairs are counted for the
+    /// pentanomial statistics.
+    pub fn from_records(records: &[GameRecord]) -> MatchScore {
+        let mut score = MatchScore::default();
+
+        let points: Vec<u32> = records.iter().map(|record| {
+            let white_points = match record.outcome {
+                Outcome::WhiteWins => 2,
+                Outcome::Draw      => 1,
+                Outcome::BlackWins => 0
+            };
+
+            return if record.players[0] == 0 { white_points } else { 2 - white_points };
+        }).collect();
+
+        for p in points.iter() {
+            match p {
+                2 => score.wins += 1,
+                1 => score.draws += 1,
+                _ => score.losses += 1
+            }
+        }
+
+        for pair in points.chunks_exact(2) {
+            score.pentanomial[(pair[0] + pair[1]) as usize] += 1;
+        }
+
+        return score;
+    }
+
+    /// The number of games played.
+    pub fn games(&self) -> u32 {
+        return self.wins + self.draws + self.losses;
+    }
+
+    /// The fraction of points scored by the first engine.
+    pub fn score(&self) -> Option<f64> {
+        if self.games() == 0 {
+            return None;
+        }
+
+        return Some((self.wins as f64 + 0.5 * self.draws as f64) / self.games() as f64);
+    }
+
+    /// The Elo difference between both engines and the size of its 95% confidence
+    /// interval in one direction.
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        let n = self.games() as f64;
+        let p = self.score()?;
+
+        let variance = (self.wins as f64 * (1.0 - p).powi(2)
+                      + self.draws as f64 * (0.5 - p).powi(2)
+                      + self.losses as f64 * p.powi(2)) / n;
+        let deviation = 1.96 * (variance / n).sqrt();
+
+        let margin = (score_to_elo(p + deviation) - score_to_elo(p - deviation)) / 2.0;
+
+        return Some((score_to_elo(p), margin));
+    }
+
+    /// The likelihood of the first engine being stronger than the second one.
+    pub fn los(&self) -> Option<f64> {
+        let decisive = (self.wins + self.losses) as f64;
+
+        if decisive == 0.0 {
+            return None;
+        }
+
+        return Some(0.5 * (1.0 + erf((self.wins as f64 - self.losses as f64) / (2.0 * decisive).sqrt())));
+    }
+
+    /// The fraction of drawn games.
+    pub fn draw_ratio(&self) -> Option<f64> {
+        return self.score().map(|_| self.draws as f64 / self.games() as f64);
+    }
+}
+
+/// A sequential probability ratio test deciding between the hypotheses that the Elo
+/// difference is elo0 or elo1.
+#[derive(Clone, Copy, Debug)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64
+}
+
+/// The state of a running SPRT.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SprtStatus {
+    Continue,
+    AcceptH0,
+    AcceptH1
+}
+
+impl Sprt {
+    /// The bounds the log-likelihood ratio has to cross to accept H0 or H1.
+    pub fn bounds(&self) -> (f64, f64) {
+        return ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln());
+    }
+
+    /// Computes the log-likelihood ratio of the pentanomial results using the normal
+    /// approximation of the generalized SPRT.
+    pub fn llr(&self, score: &MatchScore) -> f64 {
+        let pairs: u32 = score.pentanomial.iter().sum();
+
+        if pairs == 0 {
+            return 0.0;
+        }
+
+        let n = pairs as f64;
+        let outcomes = [0.0, 0.25, 0.5, 0.75, 1.0];
+
+        let mean = outcomes.iter().zip(score.pentanomial.iter()).map(|(o, c)| o * *c as f64).sum::<f64>() / n;
+        let variance = outcomes.iter().zip(score.pentanomial.iter()).map(|(o, c)| (o - mean).powi(2) * *c as f64).sum::<f64>() / n;
+
+        if variance <= 0.0 {
+            return 0.0;
+        }
+
+        let s0 = elo_to_score(self.elo0);
+        let s1 = elo_to_score(self.elo1);
+
+        return n * (s1 - s0) * (2.0 * mean - s0 - s1) / (2.0 * variance);
+    }
+
+    /// Checks whether the test has reached a decision.
+    pub fn status(&self, score: &MatchScore) -> SprtStatus {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+
+        if llr <= lower {
+            return SprtStatus::AcceptH0;
+        } else if llr >= upper {
+            return SprtStatus::AcceptH1;
+        }
+
+        return SprtStatus::Continue;
+    }
+}
+
+/// Converts an expected score into an Elo difference using the logistic model.
+pub fn score_to_elo(score: f64) -> f64 {
+    return -400.0 * (1.0 / score.clamp(1e-9, 1.0 - 1e-9) - 1.0).log10();
+}
+
+/// Converts an Elo difference into the expected score using the logistic model.
+pub fn elo_to_score(elo: f64) -> f64 {
+    return 1.0 / (1.0 + 10f64.powf(-elo / 400.0));
+}
+
+/// Approximates the error function (Abramowitz and Stegun 7.1.26).
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let y = 1.0 - (((((1.061405429 * t - 1.453152027) * t) + 1.421413741) * t - 0.284496736) * t + 0.254829592) * t * (-x * x).exp();
+
+    return if x >= 0.0 { y } else { -y };
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+    use crate::position::Position;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-3, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn counts_games_and_pairs() {
+        let record = |players: [usize; 2], outcome: Outcome| {
+            return GameRecord { players, opening: String::new(), game: Game::from_position(Position::starting_position()), outcome, scores: Vec::new(), times: Vec::new() };
+        };
+
+        // A won pair, a pair split 1.5 to 0.5 and an unfinished pair.
+        let records = [record([0, 1], Outcome::WhiteWins), record([1, 0], Outcome::BlackWins), record([0, 1], Outcome::Draw),
+                       record([1, 0], Outcome::WhiteWins), record([0, 1], Outcome::BlackWins)];
+        let score = MatchScore::from_records(&records);
+
+        assert_eq!((score.wins, score.draws, score.losses), (2, 1, 2));
+        assert_eq!(score.pentanomial, [0, 1, 0, 0, 1]);
+        assert_close(score.score().unwrap(), 0.5);
+        assert_eq!(MatchScore::default().score(), None);
+    }
+
+    #[test]
+    fn converts_between_scores_and_elo() {
+        assert_close(score_to_elo(0.75), 190.849);
+        assert_close(score_to_elo(0.5), 0.0);
+        assert_close(elo_to_score(100.0), 0.640);
+        assert_close(elo_to_score(score_to_elo(0.3)), 0.3);
+    }
+
+    #[test]
+    fn estimates_elo_and_los() {
+        let score = MatchScore { wins: 60, draws: 20, losses: 20, pentanomial: [0; 5] };
+        let (elo, margin) = score.elo().unwrap();
+
+        assert_close(elo, 147.191);
+        assert_close(margin, 66.015);
+        assert_close(score.draw_ratio().unwrap(), 0.2);
+
+        // cutechess-cli reports a LOS of 81.4 % for 12 wins and 8 losses.
+        assert_close(MatchScore { wins: 12, draws: 5, losses: 8, pentanomial: [0; 5] }.los().unwrap(), 0.814);
+        assert_eq!(MatchScore { wins: 0, draws: 3, losses: 0, pentanomial: [0; 5] }.los(), None);
+    }
+
+    #[test]
+    fn decides_the_sprt() {
+        let sprt = Sprt { elo0: 0.0, elo1: 5.0, alpha: 0.05, beta: 0.05 };
+        let (lower, upper) = sprt.bounds();
+
+        assert_close(lower, -2.944);
+        assert_close(upper, 2.944);
+
+        let score = MatchScore { pentanomial: [0, 10, 50, 30, 10], ..MatchScore::default() };
+
+        assert_close(sprt.llr(&score), 1.734);
+        assert_eq!(sprt.status(&score), SprtStatus::Continue);
+        assert_eq!(sprt.status(&MatchScore { pentanomial: [0, 20, 100, 60, 20], ..MatchScore::default() }), SprtStatus::AcceptH1);
+        assert_eq!(sprt.status(&MatchScore { pentanomial: [20, 60, 100, 20, 0], ..MatchScore::default() }), SprtStatus::AcceptH0);
+        assert_eq!(sprt.llr(&MatchScore::default()), 0.0);
+    }
+}
@@ -0,0 +1,158 @@
+use std::fmt;
+
+use crate::report::{self, GameReport, Judgement};
+use crate::runner::{GameRecord, Outcome};
+use crate::position::{WHITE, BLACK};
+
+
+/// Statistics about the games an engine configuration played in a match.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct EngineStats {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    /// The number of moves with a known centipawn loss.
+    pub judged_moves: u32,
+    pub total_cp_loss: i64,
+    pub blunders: u32,
+    /// The number of games with at least one judged move and the sum of the engine's
+    /// accuracies in them.
+    pub judged_games: u32,
+    pub total_accuracy: f64
+}
+
+impl EngineStats {
+    /// Collects the statistics of the engine with the given index from all records.
+    ///
+    /// Centipawn losses are computed from the scores the engines reported themselves.
+    /// Moves without a reported score are evaluated with the last known score.
+    pub fn from_records(engine: usize, records: &[GameRecord]) -> EngineStats {
+        let mut stats = EngineStats::default();
+
+        for record in records {
+            let color = if record.players[WHITE as usize] == engine { WHITE } else if record.players[BLACK as usize] == engine { BLACK } else { continue };
+
+            match (record.outcome, color) {
+                (Outcome::Draw, _)          => stats.draws += 1,
+                (Outcome::WhiteWins, WHITE) => stats.wins += 1,
+                (Outcome::BlackWins, BLACK) => stats.wins += 1,
+                _                           => stats.losses += 1
+            }
+
+            let mut evals = Vec::new();
+            let mut last = 0;
+
+            for score in record.scores.iter() {
+                last = score.unwrap_or(last);
+                evals.push(last);
+            }
+
+            let game_report = GameReport::new(evals, record.game.starting_position().turn);
+
+            if let Some(accuracy) = game_report.sides[color as usize].accuracy {
+                stats.judged_games += 1;
+                stats.total_accuracy += accuracy;
+            }
+
+            // The report judges the move leading from one score to the next, so the last
+            // move of the game stays unjudged.
+            for (i, judgement) in game_report.judgements.iter().enumerate() {
+                let mover = ((record.game.starting_position().turn as usize + i) % 2) as u8;
+
+                if mover != color {
+                    continue;
+                }
+
+                stats.judged_moves += 1;
+                stats.total_cp_loss += report::centipawn_loss(game_report.evals[i], game_report.evals[i + 1], mover) as i64;
+
+                if *judgement == Judgement::Blunder {
+                    stats.blunders += 1;
+                }
+            }
+        }
+
+        return stats;
+    }
+
+    /// The number of games played.
+    pub fn games(&self) -> u32 {
+        return self.wins + self.draws + self.losses;
+    }
+
+    /// The average centipawn loss per move.
+    pub fn average_centipawn_loss(&self) -> Option<f64> {
+        if self.judged_moves == 0 {
+            return None;
+        }
+
+        return Some(self.total_cp_loss as f64 / self.judged_moves as f64);
+    }
+
+    /// The average accuracy (0 to 100) of the engine's games, like lichess averages the
+    /// accuracies of a player's games.
+    pub fn accuracy(&self) -> Option<f64> {
+        if self.judged_games == 0 {
+            return None;
+        }
+
+        return Some(self.total_accuracy / self.judged_games as f64);
+    }
+
+    /// The fraction of moves that were blunders.
+    pub fn blunder_rate(&self) -> Option<f64> {
+        if self.judged_moves == 0 {
+            return None;
+        }
+
+        return Some(self.blunders as f64 / self.judged_moves as f64);
+    }
+}
+
+impl fmt::Display for EngineStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let acpl = self.average_centipawn_loss().map_or(String::from("-"), |a| format!("{:.1}", a));
+        let accuracy = self.accuracy().map_or(String::from("-"), |a| format!("{:.1}%", a));
+        let blunders = self.blunder_rate().map_or(String::from("-"), |b| format!("{:.1}%", 100.0 * b));
+
+        return write!(f, "+{} ={} -{} (acpl {}, accuracy {}, blunders {})", self.wins, self.draws, self.losses, acpl, accuracy, blunders);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+    use crate::position::Position;
+
+    #[test]
+    fn collects_the_statistics_of_each_engine() {
+        let mut game = Game::from_position(Position::starting_position());
+
+        for uci in ["e2e4", "e7e5", "g1f3", "b8c6"] {
+            game.play(game.position().uci_to_move(uci).unwrap());
+        }
+
+        // White throws away 430 centipawns with its second move.
+        let record = GameRecord { players: [0, 1], opening: String::new(), game, outcome: Outcome::WhiteWins, scores: vec![Some(30), None, Some(30), Some(-400)], times: Vec::new() };
+        let records = [record];
+
+        let first = EngineStats::from_records(0, &records);
+        let second = EngineStats::from_records(1, &records);
+
+        assert_eq!((first.wins, first.judged_moves, first.total_cp_loss, first.blunders), (1, 2, 430, 1));
+        assert_eq!((second.losses, second.judged_moves, second.total_cp_loss, second.blunders), (1, 1, 0, 0));
+        assert_eq!(first.to_string(), "+1 =0 -0 (acpl 215.0, accuracy 46.9%, blunders 50.0%)");
+        assert!(second.accuracy().unwrap() > 99.9);
+        assert_eq!(EngineStats::from_records(2, &records).to_string(), "+0 =0 -0 (acpl -, accuracy -, blunders -)");
+    }
+
+    #[test]
+    fn averages_the_accuracies_of_the_games() {
+        let stats = EngineStats { judged_games: 2, total_accuracy: 90.0 + 70.0, ..EngineStats::default() };
+
+        assert_eq!(stats.accuracy(), Some(80.0));
+        assert_eq!(EngineStats::default().accuracy(), None);
+    }
+}
@@ -0,0 +1,474 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::game::Game;
+use crate::position::Position;
+use crate::runner::{self, Engine, GameRecord, Match, Outcome};
+use crate::runner::concurrency::{self, ConcurrencyConfig, EngineFactory};
+use crate::runner::openings::{Opening, OpeningSuite};
+use crate::runner::output;
+use crate::runner::pgn::{self, EngineInfo, MatchInfo};
+use crate::runner::sprt::{MatchScore, Sprt, SprtStatus};
+use crate::runner::uci_engine::UciLimit;
+
+
+/// A match of a fixed number of games whose state is written to disk after every game,
+/// so an interrupted tournament can be resumed instead of starting over.
+pub struct Tournament {
+    pub games: usize,
+    pub openings: OpeningSuite,
+    pub state_path: PathBuf,
+    pub engine_match: Match,
+    /// An optional SPRT that stops the tournament as soon as it reaches a decision.
+    pub sprt: Option<Sprt>,
+    /// An optional PGN file every finished game is appended to, tagged with the settings
+    /// of the match.
+    pub pgn: Option<(PathBuf, MatchInfo)>,
+    /// The limit per move the engines play with. It's saved with the state, so a resumed
+    /// tournament can restart its engines with the time control the earlier games had.
+    pub limit: Option<UciLimit>,
+    /// Creates the engines of worker threads. With factories the games are played
+    /// concurrently as configured, otherwise one at a time by the engines of the match.
+    pub factories: Option<[EngineFactory; 2]>,
+    pub concurrency: ConcurrencyConfig
+}
+
+impl Tournament {
+    /// Creates a new tournament between two engines.
+    pub fn new(engines: [Box<dyn Engine>; 2], games: usize, max_plies: usize, openings: OpeningSuite, state_path: PathBuf) -> Tournament {
+        return Tournament { games, openings, state_path, engine_match: Match::new(engines, max_plies), sprt: None, pgn: None, limit: None, factories: None,
+                            concurrency: ConcurrencyConfig::default() };
+    }
+
+    /// Resumes the tournament stored at the given path. The engines have to be the same
+    /// ones that played the games stored in the state file, playing with the saved limit.
+    /// Games keep being appended to the PGN file of the tournament, tagged with the settings
+    /// it started with.
+    pub fn resume(engines: [Box<dyn Engine>; 2], state_path: PathBuf) -> io::Result<Tournament> {
+        let content = fs::read_to_string(&state_path)?;
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid tournament state: {}", msg));
+
+        let mut games = None;
+        let mut max_plies = None;
+        let mut openings = Vec::new();
+        let mut sprt = None;
+        let mut names = [None, None];
+        let mut records = Vec::new();
+        let mut pgn_path = None;
+        let mut event = String::new();
+        let mut limit = None;
+        let mut versions = [None, None];
+        let mut options = [Vec::new(), Vec::new()];
+
+        let mut lines = content.lines();
+
+        while let Some(line) = lines.next() {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+
+            match key {
+                "games"     => games = value.parse::<usize>().ok(),
+                "max_plies" => max_plies = value.parse::<usize>().ok(),
+                "sprt"      => sprt = Some(parse_sprt(value).ok_or_else(|| invalid("sprt"))?),
+                "opening"   => openings.push(Opening::from_epd(value).ok_or_else(|| invalid("opening"))?),
+                "engine"    => {
+                    let (idx, name) = parse_engine_line(value).ok_or_else(|| invalid("engine"))?;
+
+                    names[idx] = Some(name.to_string());
+                },
+                "pgn"       => pgn_path = Some(PathBuf::from(value)),
+                "event"     => event = value.to_string(),
+                "limit"     => limit = Some(UciLimit::parse(value).ok_or_else(|| invalid("limit"))?),
+                "version"   => {
+                    let (idx, version) = parse_engine_line(value).ok_or_else(|| invalid("version"))?;
+
+                    versions[idx] = Some(version.to_string());
+                },
+                "option"    => {
+                    let (idx, option) = parse_engine_line(value).ok_or_else(|| invalid("option"))?;
+                    let (name, value) = option.split_once('=').ok_or_else(|| invalid("option"))?;
+
+                    options[idx].push((name.to_string(), value.to_string()));
+                },
+                "game"      => {
+                    let fen = lines.next().and_then(|l| l.strip_prefix("fen ")).ok_or_else(|| invalid("fen"))?;
+                    let moves = lines.next().and_then(|l| l.strip_prefix("moves")).ok_or_else(|| invalid("moves"))?;
+
+                    records.push(parse_record(value, fen, moves).ok_or_else(|| invalid("game"))?);
+                },
+                ""          => (),
+                _           => return Err(invalid(key))
+            }
+        }
+
+        for (idx, engine) in engines.iter().enumerate() {
+            if names[idx].as_deref() != Some(engine.name().as_str()) {
+                return Err(invalid("engines don't match"));
+            }
+        }
+
+        let games = games.ok_or_else(|| invalid("games"))?;
+        let max_plies = max_plies.ok_or_else(|| invalid("max_plies"))?;
+
+        if openings.is_empty() {
+            return Err(invalid("openings"));
+        }
+
+        let pgn = pgn_path.map(|path| {
+            let [first, second] = [0, 1].map(|idx| EngineInfo { name: engines[idx].name(), version: versions[idx].take(), options: std::mem::take(&mut options[idx]) });
+
+            return (path, MatchInfo { event, engines: [first, second], limit });
+        });
+
+        let mut engine_match = Match::new(engines, max_plies);
+        engine_match.records = records;
+
+        return Ok(Tournament { games, openings: OpeningSuite { openings }, state_path, engine_match, sprt, pgn, limit, factories: None,
+                               concurrency: ConcurrencyConfig::default() });
+    }
+
+    /// Writes the current state of the tournament to disk. The state is first written
+    /// to a temporary file so an interruption while saving doesn't corrupt it.
+    pub fn save(&self) -> io::Result<()> {
+        let mut state = String::new();
+
+        state.push_str(&format!("games {}\n", self.games));
+        state.push_str(&format!("max_plies {}\n", self.engine_match.max_plies));
+
+        if let Some(sprt) = self.sprt {
+            state.push_str(&format!("sprt {} {} {} {}\n", sprt.elo0, sprt.elo1, sprt.alpha, sprt.beta));
+        }
+
+        if let Some(limit) = self.limit {
+            state.push_str(&format!("limit {}\n", limit));
+        }
+
+        for opening in self.openings.openings.iter() {
+            state.push_str(&format!("opening {}\n", opening.to_epd()));
+        }
+
+        for (idx, engine) in self.engine_match.engines.iter().enumerate() {
+            state.push_str(&format!("engine {} {}\n", idx, engine.name()));
+        }
+
+        if let Some((path, info)) = &self.pgn {
+            state.push_str(&format!("pgn {}\nevent {}\n", path.display(), info.event));
+
+            for (idx, engine) in info.engines.iter().enumerate() {
+                if let Some(version) = &engine.version {
+                    state.push_str(&format!("version {} {}\n", idx, version));
+                }
+
+                for (name, value) in engine.options.iter() {
+                    state.push_str(&format!("option {} {}={}\n", idx, name, value));
+                }
+            }
+        }
+
+        for record in self.engine_match.records.iter() {
+            state.push_str(&format_record(record));
+        }
+
+        let tmp_path = self.state_path.with_extension("tmp");
+        fs::write(&tmp_path, state)?;
+
+        return fs::rename(tmp_path, &self.state_path);
+    }
+
+    /// Returns true if all games of the tournament have been played or the SPRT reached
+    /// a decision. The SPRT is only checked after complete game pairs.
+    pub fn is_finished(&self) -> bool {
+        let played = self.engine_match.records.len();
+
+        let decided = self.sprt.is_some_and(|sprt| {
+            played.is_multiple_of(2) && sprt.status(&self.score()) != SprtStatus::Continue
+        });
+
+        return played >= self.games || decided;
+    }
+
+    /// Returns the current score from the first engine's perspective.
+    pub fn score(&self) -> MatchScore {
+        return MatchScore::from_records(&self.engine_match.records);
+    }
+
+    /// Plays all remaining games of the tournament and saves the state after each game.
+    /// The progress is printed in the format used by cutechess-cli.
+    ///
+    /// Games played concurrently may finish out of order. They are added to the tournament
+    /// in the order they were scheduled in, so the saved state and the PGN file only ever
+    /// hold the games up to the first unfinished one and resuming continues from there.
+    pub fn run(&mut self) -> io::Result<()> {
+        let names = [self.engine_match.engines[0].name(), self.engine_match.engines[1].name()];
+
+        let factories = match self.factories.clone() {
+            Some(factories) => factories,
+            None            => return self.run_sequentially(&names)
+        };
+
+        if self.is_finished() {
+            return Ok(());
+        }
+
+        let games = self.engine_match.records.len()..self.games;
+        let (openings, max_plies, config) = (Arc::new(self.openings.clone()), self.engine_match.max_plies, self.concurrency);
+        let mut finished = BTreeMap::new();
+        let mut result = Ok(());
+
+        concurrency::play_games_as_finished(factories, openings, games, max_plies, config, |idx, record| {
+            finished.insert(idx, record);
+
+            while let Some(record) = finished.remove(&self.engine_match.records.len()) {
+                result = self.add_game(record, &names);
+
+                // Stops starting new games once the SPRT reached a decision.
+                if result.is_err() || self.is_finished() {
+                    return false;
+                }
+            }
+
+            return true;
+        });
+
+        return result;
+    }
+
+    fn run_sequentially(&mut self, names: &[String; 2]) -> io::Result<()> {
+        while !self.is_finished() {
+            let idx = self.engine_match.records.len();
+            let (white, black) = if idx.is_multiple_of(2) { (&names[0], &names[1]) } else { (&names[1], &names[0]) };
+
+            println!("{}", output::started_game(idx, self.games, white, black));
+
+            let record = runner::play_pairing(&mut self.engine_match.engines, idx, self.openings.for_game(idx), self.engine_match.max_plies);
+
+            self.add_game(record, names)?;
+        }
+
+        return Ok(());
+    }
+
+    /// Adds the next game of the tournament: prints it and the score, appends it to the PGN
+    /// file and saves the state.
+    fn add_game(&mut self, record: GameRecord, names: &[String; 2]) -> io::Result<()> {
+        let idx = self.engine_match.records.len();
+        let (white, black) = if idx.is_multiple_of(2) { (&names[0], &names[1]) } else { (&names[1], &names[0]) };
+
+        println!("{}", output::finished_game(idx, &record, white, black));
+
+        if let Some((path, info)) = &self.pgn {
+            OpenOptions::new().create(true).append(true).open(path)?.write_all(pgn::write_record(&record, idx, info).as_bytes())?;
+        }
+
+        self.engine_match.records.push(record);
+
+        for line in output::match_score(&self.score(), [&names[0], &names[1]], self.sprt.as_ref()) {
+            println!("{}", line);
+        }
+
+        return self.save();
+    }
+}
+
+/// Splits a line about one of the engines into the index of the engine and the rest.
+fn parse_engine_line(value: &str) -> Option<(usize, &str)> {
+    let (idx, rest) = value.split_once(' ')?;
+
+    return Some((idx.parse::<usize>().ok().filter(|i| *i < 2)?, rest));
+}
+
+/// Encodes a finished game as three lines: the players, the outcome and the opening,
+/// the starting position and the moves with their scores and times in milliseconds
+/// (e.g. e2e4:35@1200).
+fn format_record(record: &GameRecord) -> String {
+    let mut position = record.game.starting_position().clone();
+    let mut moves = String::new();
+
+    for (i, (m, score)) in record.game.moves().iter().zip(record.scores.iter()).enumerate() {
+        moves.push(' ');
+        moves.push_str(&position.move_to_uci(*m));
+
+        if let Some(score) = score {
+            moves.push_str(&format!(":{}", score));
+        }
+
+        if let Some(time) = record.times.get(i) {
+            moves.push_str(&format!("@{}", time.as_millis()));
+        }
+
+        position = position.make_move(*m);
+    }
+
+    return format!("game {} {} {} {}\nfen {}\nmoves{}\n", record.players[0], record.players[1], record.outcome.to_pgn(),
+                   record.opening, record.game.starting_position().to_fen(), moves);
+}
+
+/// Decodes a game encoded by format_record.
+fn parse_record(header: &str, fen: &str, moves: &str) -> Option<GameRecord> {
+    let mut fields = header.splitn(4, ' ');
+
+    let white = fields.next()?.parse::<usize>().ok()?;
+    let black = fields.next()?.parse::<usize>().ok()?;
+    let outcome = match fields.next()? {
+        "1-0"     => Outcome::WhiteWins,
+        "0-1"     => Outcome::BlackWins,
+        "1/2-1/2" => Outcome::Draw,
+        _         => return None
+    };
+    let opening = fields.next().unwrap_or("").to_string();
+
+    let mut game = Game::from_position(Position::from_fen(fen.to_string())?);
+    let mut scores = Vec::new();
+    let mut times = Vec::new();
+
+    for token in moves.split_whitespace() {
+        // States written before the times were recorded don't have them.
+        let token = match token.split_once('@') {
+            Some((token, ms)) => {
+                times.push(Duration::from_millis(ms.parse().ok()?));
+                token
+            },
+            None => token
+        };
+
+        let (uci, score) = match token.split_once(':') {
+            Some((uci, score)) => (uci, Some(score.parse::<i32>().ok()?)),
+            None               => (token, None)
+        };
+
+        let m = game.position().uci_to_move(uci)?;
+
+        game.play(m);
+        scores.push(score);
+    }
+
+    return Some(GameRecord { players: [white, black], opening, game, outcome, scores, times });
+}
+
+/// Decodes the parameters of an SPRT given as elo0, elo1, alpha and beta.
+fn parse_sprt(value: &str) -> Option<Sprt> {
+    let params: Vec<f64> = value.split(' ').map(|v| v.parse::<f64>().ok()).collect::<Option<Vec<f64>>>()?;
+
+    if params.len() != 4 {
+        return None;
+    }
+
+    return Some(Sprt { elo0: params[0], elo1: params[1], alpha: params[2], beta: params[3] });
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::SearchEngine;
+
+    fn engines() -> [Box<dyn Engine>; 2] {
+        return [Box::new(SearchEngine::new(1)), Box::new(SearchEngine::new(1))];
+    }
+
+    #[test]
+    fn resumes_saved_tournaments() {
+        let dir = std::env::temp_dir().join(format!("chess-tournament-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let (state_path, pgn_path) = (dir.join("state"), dir.join("games.pgn"));
+        let openings = OpeningSuite::single(Position::from_fen(String::from("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2")).unwrap());
+        let mut tournament = Tournament::new(engines(), 4, 6, openings, state_path.clone());
+        let mut info = MatchInfo::new("Regression test", &tournament.engine_match.engines, Some(UciLimit::Nodes(1000)));
+
+        info.engines[1].options = vec![(String::from("Skill Level"), String::from("5")), (String::from("Hash"), String::from("16"))];
+        tournament.sprt = Some(Sprt { elo0: 0.0, elo1: 5.0, alpha: 0.05, beta: 0.1 });
+        tournament.pgn = Some((pgn_path.clone(), info.clone()));
+        tournament.limit = Some(UciLimit::Nodes(1000));
+
+        for idx in 0..2 {
+            tournament.engine_match.play_next(tournament.openings.for_game(idx));
+        }
+
+        tournament.save().unwrap();
+
+        let mut resumed = Tournament::resume(engines(), state_path.clone()).unwrap();
+        let (path, resumed_info) = resumed.pgn.clone().unwrap();
+
+        assert_eq!((resumed.games, resumed.engine_match.max_plies, resumed.engine_match.records.len()), (4, 6, 2));
+        assert_eq!(resumed.openings.openings[0].position.to_fen(), tournament.openings.openings[0].position.to_fen());
+        assert_eq!(resumed.sprt.unwrap().beta, 0.1);
+        assert_eq!(resumed.engine_match.records[1].game.moves(), tournament.engine_match.records[1].game.moves());
+        assert_eq!(path, pgn_path);
+        assert_eq!((resumed_info.event.as_str(), resumed_info.engines.clone()), ("Regression test", info.engines.clone()));
+        assert_eq!(resumed_info.limit, Some(UciLimit::Nodes(1000)));
+        assert_eq!(resumed.limit, Some(UciLimit::Nodes(1000)));
+
+        // The remaining games are tagged like the ones before the interruption.
+        resumed.run().unwrap();
+
+        let pgn = fs::read_to_string(&pgn_path).unwrap();
+
+        assert_eq!(resumed.engine_match.records.len(), 4);
+        assert_eq!(pgn.matches("[Event \"Regression test\"]").count(), 2);
+        assert_eq!(pgn.matches("[BlackOptions \"Skill Level=5; Hash=16\"]").count(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn plays_games_concurrently_in_order() {
+        let dir = std::env::temp_dir().join(format!("chess-tournament-concurrent-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let (state_path, pgn_path) = (dir.join("state"), dir.join("games.pgn"));
+        let openings = OpeningSuite::from_epd("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - ;\nrnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq - ;\n");
+        let factory = || -> EngineFactory { Arc::new(|| -> Box<dyn Engine> { Box::new(SearchEngine::new(1)) }) };
+
+        let mut sequential = Tournament::new(engines(), 6, 8, openings.clone(), dir.join("sequential"));
+        sequential.run().unwrap();
+
+        let mut tournament = Tournament::new(engines(), 6, 8, openings, state_path.clone());
+        tournament.pgn = Some((pgn_path.clone(), MatchInfo::new("Concurrent", &tournament.engine_match.engines, None)));
+        tournament.factories = Some([factory(), factory()]);
+        tournament.concurrency = ConcurrencyConfig { concurrency: 3, pin_threads: true };
+        tournament.run().unwrap();
+
+        for (a, b) in sequential.engine_match.records.iter().zip(tournament.engine_match.records.iter()) {
+            assert_eq!((a.players, a.game.moves()), (b.players, b.game.moves()));
+        }
+
+        let pgn = fs::read_to_string(&pgn_path).unwrap();
+        let rounds: Vec<&str> = pgn.lines().filter(|line| line.starts_with("[Round ")).collect();
+
+        assert_eq!(tournament.engine_match.records.len(), 6);
+        assert_eq!(rounds, (1..=6).map(|round| format!("[Round \"{}\"]", round)).collect::<Vec<_>>());
+        assert_eq!(Tournament::resume(engines(), state_path).unwrap().engine_match.records.len(), 6);
+
+        // Matches play concurrently the same way.
+        let mut engine_match = Match::new(engines(), 8);
+        engine_match.play_concurrently([factory(), factory()], &tournament.openings, 4, ConcurrencyConfig { concurrency: 2, pin_threads: false });
+
+        assert_eq!(engine_match.records[3].game.moves(), tournament.engine_match.records[3].game.moves());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_states_of_other_engines() {
+        let dir = std::env::temp_dir().join(format!("chess-tournament-engines-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let state_path = dir.join("state");
+        Tournament::new(engines(), 2, 4, OpeningSuite::single(Position::starting_position()), state_path.clone()).save().unwrap();
+
+        let others: [Box<dyn Engine>; 2] = [Box::new(SearchEngine::new(1)), Box::new(crate::mcts::MctsEngine::new(crate::mcts::Simulation::Evaluation))];
+
+        assert!(Tournament::resume(others, state_path.clone()).is_err());
+
+        let resumed = Tournament::resume(engines(), state_path).unwrap();
+
+        assert!(resumed.pgn.is_none());
+        assert_eq!(resumed.limit, None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
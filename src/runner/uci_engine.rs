@@ -0,0 +1,307 @@
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::game::Game;
+use crate::runner::{Engine, EngineMove};
+use crate::search::{Score, SearchLimits};
+
+
+/// The longest an engine may stay silent while an answer is expected. Engines report their
+/// progress while searching, so only a hung engine stays silent for that long.
+pub const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The time an engine gets to quit before its process is killed.
+const QUIT_GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+
+/// The limit engines get for choosing each move.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UciLimit {
+    MoveTime(u64),
+    Nodes(u64),
+    Depth(u32)
+}
+
+impl UciLimit {
+    /// Parses the arguments of the go command the limit is written as, e.g. movetime 100.
+    pub fn parse(s: &str) -> Option<UciLimit> {
+        let tokens: Vec<&str> = s.split_whitespace().collect();
+
+        return match tokens[..] {
+            ["movetime", ms] => ms.parse().ok().map(UciLimit::MoveTime),
+            ["nodes", n]     => n.parse().ok().map(UciLimit::Nodes),
+            ["depth", d]     => d.parse().ok().map(UciLimit::Depth),
+            _                => None
+        };
+    }
+}
+
+impl fmt::Display for UciLimit {
+    /// Writes the limit as the arguments of the go command, e.g. movetime 100.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            UciLimit::MoveTime(ms) => write!(f, "movetime {}", ms),
+            UciLimit::Nodes(n)     => write!(f, "nodes {}", n),
+            UciLimit::Depth(d)     => write!(f, "depth {}", d)
+        };
+    }
+}
+
+/// Describes how to start an external engine speaking the UCI protocol.
+#[derive(Clone, Debug)]
+pub struct UciEngineSpec {
+    pub name: String,
+    pub command: String,
+    pub args: Vec<String>,
+    /// Options sent with setoption after the engine started.
+    pub options: Vec<(String, String)>
+}
+
+/// An external engine running in a child process and speaking the UCI protocol.
+pub struct UciEngine {
+    name: String,
+    /// The name the engine reported for itself, which usually contains its version.
+    id: Option<String>,
+    options: Vec<(String, String)>,
+    limit: UciLimit,
+    process: Child,
+    stdin: ChildStdin,
+    /// The lines the engine sent, read by a thread of their own so reads can time out.
+    lines: Receiver<io::Result<String>>,
+    timeout: Duration
+}
+
+impl UciEngine {
+    /// Starts the engine described by the spec and initializes the UCI protocol.
+    pub fn start(spec: &UciEngineSpec, limit: UciLimit) -> io::Result<UciEngine> {
+        return UciEngine::start_with_timeout(spec, limit, READ_TIMEOUT);
+    }
+
+    /// Starts the engine like start, but gives up on it once it stays silent for the given
+    /// time while an answer is expected.
+    pub fn start_with_timeout(spec: &UciEngineSpec, limit: UciLimit, timeout: Duration) -> io::Result<UciEngine> {
+        let mut process = Command::new(&spec.command)
+            .args(&spec.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let stdin = process.stdin.take().unwrap();
+        let lines = read_lines(process.stdout.take().unwrap());
+
+        let mut engine = UciEngine { name: spec.name.clone(), id: None, options: spec.options.clone(), limit, process, stdin, lines, timeout };
+
+        engine.send("uci")?;
+
+        loop {
+            let line = engine.read_line()?;
+
+            if line == "uciok" {
+                break;
+            }
+
+            if let Some(id) = line.strip_prefix("id name ") {
+                engine.id = Some(id.trim().to_string());
+            }
+        }
+
+        for (name, value) in spec.options.iter() {
+            engine.send(&format!("setoption name {} value {}", name, value))?;
+        }
+
+        engine.send("isready")?;
+        engine.wait_for("readyok")?;
+
+        return Ok(engine);
+    }
+
+    /// Sends a single command to the engine.
+    fn send(&mut self, command: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{}", command)?;
+
+        return self.stdin.flush();
+    }
+
+    /// Reads the next line the engine sent. An engine that doesn't send one in time is
+    /// killed, since it can't be talked to anymore.
+    fn read_line(&mut self) -> io::Result<String> {
+        return match self.lines.recv_timeout(self.timeout) {
+            Ok(line)                            => line.map(|line| line.trim().to_string()),
+            Err(RecvTimeoutError::Disconnected) => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "The engine closed its output")),
+            Err(RecvTimeoutError::Timeout)      => {
+                let _ = self.process.kill();
+
+                Err(io::Error::new(io::ErrorKind::TimedOut, "The engine stopped answering"))
+            }
+        };
+    }
+
+    /// Skips all lines until the engine sends the given line.
+    fn wait_for(&mut self, expected: &str) -> io::Result<()> {
+        while self.read_line()? != expected {}
+
+        return Ok(());
+    }
+
+    /// Sends the game's moves to the engine, starts a search with the given arguments of the go
+    /// command and returns the best move in UCI notation together with the last reported score.
+    fn search(&mut self, game: &Game, limits: &str) -> io::Result<(String, Option<i32>)> {
+        let mut position = game.starting_position().clone();
+        let mut moves = Vec::new();
+
+        for m in game.moves() {
+            moves.push(position.move_to_uci(*m));
+            position = position.make_move(*m);
+        }
+
+        let mut command = format!("position fen {}", game.starting_position().to_fen());
+
+        if !moves.is_empty() {
+            command.push_str(" moves ");
+            command.push_str(&moves.join(" "));
+        }
+
+        self.send(&command)?;
+
+        self.send(&format!("go {}", limits))?;
+
+        let mut score = None;
+
+        loop {
+            let line = self.read_line()?;
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+
+            match tokens.first() {
+                Some(&"info")     => score = parse_score(&tokens).or(score),
+                Some(&"bestmove") => {
+                    let best = tokens.get(1).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing best move"))?;
+
+                    return Ok((best.to_string(), score));
+                },
+                _                 => ()
+            }
+        }
+    }
+}
+
+impl Engine for UciEngine {
+    fn name(&self) -> String {
+        return self.name.clone();
+    }
+
+    fn version(&self) -> Option<String> {
+        return self.id.clone();
+    }
+
+    fn options(&self) -> Vec<(String, String)> {
+        return self.options.clone();
+    }
+
+    fn new_game(&mut self) {
+        let _ = self.send("ucinewgame").and_then(|_| self.send("isready")).and_then(|_| self.wait_for("readyok"));
+    }
+
+    fn choose_move(&mut self, game: &Game) -> Option<EngineMove> {
+        return self.choose_move_within(game, &SearchLimits::default());
+    }
+
+    fn choose_move_within(&mut self, game: &Game, limits: &SearchLimits) -> Option<EngineMove> {
+        let mut go = if limits.is_empty() { self.limit.to_string() } else { limits.to_string() };
+
+        if !limits.searchmoves.is_empty() {
+            let moves: Vec<String> = limits.searchmoves.iter().map(|&m| game.position().move_to_uci(m)).collect();
+            go = format!("{} searchmoves {}", go, moves.join(" "));
+        }
+
+        let (best, score) = self.search(game, &go).ok()?;
+        let m = game.position().uci_to_move(&best)?;
+
+        return Some(EngineMove { m, score });
+    }
+}
+
+impl Drop for UciEngine {
+    /// Asks the engine to quit and kills it if it's still running after a grace period.
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let deadline = Instant::now() + QUIT_GRACE_PERIOD;
+
+        while Instant::now() < deadline {
+            if !matches!(self.process.try_wait(), Ok(None)) {
+                return;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+/// Reads the lines of the engine's output on a thread of their own until the output is
+/// closed or fails.
+fn read_lines(stdout: ChildStdout) -> Receiver<io::Result<String>> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            let failed = line.is_err();
+
+            if sender.send(line).is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    return receiver;
+}
+
+/// Extracts the score of an info line. Mate scores are converted into the large centipawn
+/// values the search uses.
+fn parse_score(tokens: &[&str]) -> Option<i32> {
+    let idx = tokens.iter().position(|t| *t == "score")?;
+    let value = tokens.get(idx + 2)?.parse::<i32>().ok()?;
+
+    return match *tokens.get(idx + 1)? {
+        "cp"   => Some(value),
+        "mate" => Some(Score::Mate(value).value()),
+        _      => None
+    };
+}
+
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// An engine written as a shell script.
+    fn script(script: &str) -> UciEngineSpec {
+        return UciEngineSpec { name: String::from("script"), command: String::from("sh"), args: vec![String::from("-c"), script.to_string()],
+                               options: Vec::new() };
+    }
+
+    #[test]
+    fn gives_up_on_silent_engines() {
+        let start = Instant::now();
+        let error = UciEngine::start_with_timeout(&script("read line; exec sleep 30"), UciLimit::Depth(1), Duration::from_millis(200)).err().unwrap();
+
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn kills_engines_that_ignore_quit() {
+        let engine = script("while read line; do case $line in uci) echo uciok;; isready) echo readyok;; esac; done");
+        let engine = UciEngine::start(&engine, UciLimit::Depth(1)).unwrap();
+        let start = Instant::now();
+
+        drop(engine);
+
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+}
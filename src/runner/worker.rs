@@ -0,0 +1,227 @@
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::game::Game;
+use crate::position::Position;
+use crate::protocol::http::{self, json_string};
+use crate::runner::concurrency::{self, ConcurrencyConfig, EngineFactory};
+use crate::runner::openings::OpeningSuite;
+use crate::runner::sprt::MatchScore;
+use crate::runner::uci_engine::{UciEngine, UciEngineSpec, UciLimit};
+use crate::runner::{Engine, EngineMove};
+
+
+/// The number of plies after which games of jobs that don't say otherwise are adjudicated.
+pub const DEFAULT_MAX_PLIES: usize = 400;
+
+/// The path of the server a worker posts its name to for the next job.
+pub const JOB_PATH: &str = "/worker/job";
+
+/// The path of the server a worker posts the results of a job to.
+pub const RESULTS_PATH: &str = "/worker/results";
+
+
+/// A batch of games assigned to a worker by a distributed testing server.
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub id: String,
+    /// The two engine builds to test against each other.
+    pub engines: [UciEngineSpec; 2],
+    /// The opening book to use. Games start from the regular starting position without one.
+    pub book: Option<PathBuf>,
+    pub limit: UciLimit,
+    /// The number of games to play. Should be even so every opening is played with both colors.
+    pub games: usize,
+    pub max_plies: usize
+}
+
+/// The connection to a distributed testing server. Like with OpenBench, workers fetch jobs,
+/// play them and report the results, but the server has to speak the crate's own protocol
+/// (see HttpJobServer): OpenBench builds the engines from source on its workers and its
+/// endpoints aren't compatible.
+pub trait JobServer {
+    /// Requests the next job. Returns None if the server has no work left.
+    fn fetch_job(&mut self) -> io::Result<Option<Job>>;
+
+    /// Reports the results of a finished job.
+    fn report(&mut self, job: &Job, score: &MatchScore) -> io::Result<()>;
+}
+
+/// Parses a job as the server sends it, a flat JSON object like
+///
+/// {"id": "42", "dev_name": "dev", "dev_command": "./dev", "dev_options": "Hash=16 Threads=1",
+///  "base_name": "base", "base_command": "./base", "book": "book.epd", "limit": "nodes 5000",
+///  "games": 8, "max_plies": 300}
+///
+/// The commands may carry arguments separated by spaces. The book, the options and max_plies
+/// are optional. An object without an id means the server has no work left.
+pub fn parse_job(json: &str) -> io::Result<Option<Job>> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let fields = http::parse_json_object(json).ok_or_else(|| invalid(format!("Invalid job: {}", json)))?;
+    let field = |name: &str| fields.get(name).ok_or_else(|| invalid(format!("The job has no {}", name)));
+
+    let id = match fields.get("id") {
+        Some(id) if id != "null" => id.clone(),
+        _                        => return Ok(None)
+    };
+
+    let engine = |prefix: &str| -> io::Result<UciEngineSpec> {
+        let mut command = field(&format!("{}_command", prefix))?.split_whitespace().map(String::from);
+        let options = fields.get(&format!("{}_options", prefix)).map(|options| {
+            return options.split_whitespace().filter_map(|option| option.split_once('=')).map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        });
+
+        return Ok(UciEngineSpec {
+            name: fields.get(&format!("{}_name", prefix)).cloned().unwrap_or_else(|| prefix.to_string()),
+            command: command.next().ok_or_else(|| invalid(format!("The job has an empty {} command", prefix)))?,
+            args: command.collect(),
+            options: options.unwrap_or_default()
+        });
+    };
+
+    let limit = UciLimit::parse(field("limit")?).ok_or_else(|| invalid(format!("Invalid limit: {}", fields["limit"])))?;
+    let games = field("games")?.parse().map_err(|_| invalid(format!("Invalid number of games: {}", fields["games"])))?;
+    let max_plies = match fields.get("max_plies") {
+        Some(plies) => plies.parse().map_err(|_| invalid(format!("Invalid number of plies: {}", plies)))?,
+        None        => DEFAULT_MAX_PLIES
+    };
+
+    return Ok(Some(Job { id, engines: [engine("dev")?, engine("base")?], book: fields.get("book").map(PathBuf::from), limit, games, max_plies }));
+}
+
+/// Writes the results of a job as the JSON object reported to the server, with the counts
+/// from the first engine's perspective.
+pub fn report_json(job: &Job, score: &MatchScore) -> String {
+    let pentanomial: Vec<String> = score.pentanomial.iter().map(|count| count.to_string()).collect();
+
+    return format!("{{\"id\":{},\"wins\":{},\"draws\":{},\"losses\":{},\"pentanomial\":[{}]}}",
+                   json_string(&job.id), score.wins, score.draws, score.losses, pentanomial.join(","));
+}
+
+/// Talks to a server over HTTP with the crate's own protocol. Jobs are fetched by posting the
+/// worker's name as {"worker": "<name>"} to <url>/worker/job, which answers with a job as
+/// parsed by parse_job. Results are posted to <url>/worker/results as written by report_json.
+#[cfg(feature = "online")]
+pub struct HttpJobServer {
+    url: String,
+    worker: String
+}
+
+#[cfg(feature = "online")]
+impl HttpJobServer {
+    /// Connects to the server at the URL, identifying as the given worker.
+    pub fn new(url: &str, worker: &str) -> HttpJobServer {
+        return HttpJobServer { url: url.trim_end_matches('/').to_string(), worker: worker.to_string() };
+    }
+
+    fn post(&self, path: &str, body: &str) -> io::Result<String> {
+        let response = ureq::post(&format!("{}{}", self.url, path))
+            .set("Content-Type", "application/json")
+            .send_string(body)
+            .map_err(io::Error::other)?;
+
+        return response.into_string();
+    }
+}
+
+#[cfg(feature = "online")]
+impl JobServer for HttpJobServer {
+    fn fetch_job(&mut self) -> io::Result<Option<Job>> {
+        return parse_job(&self.post(JOB_PATH, &format!("{{\"worker\":{}}}", json_string(&self.worker)))?);
+    }
+
+    fn report(&mut self, job: &Job, score: &MatchScore) -> io::Result<()> {
+        return self.post(RESULTS_PATH, &report_json(job, score)).map(|_| ());
+    }
+}
+
+/// Repeatedly fetches jobs from the server, plays them locally and reports the results
+/// until the server runs out of work.
+pub fn run_worker(server: &mut dyn JobServer, config: ConcurrencyConfig) -> io::Result<()> {
+    while let Some(job) = server.fetch_job()? {
+        let score = run_job(&job, config)?;
+
+        server.report(&job, &score)?;
+    }
+
+    return Ok(());
+}
+
+/// Plays all games of a job and returns the score from the first engine's perspective.
+pub fn run_job(job: &Job, config: ConcurrencyConfig) -> io::Result<MatchScore> {
+    let openings = match &job.book {
+        Some(path) => OpeningSuite::load(path)?,
+        None       => OpeningSuite::single(Position::starting_position())
+    };
+
+    // Make sure both engines can be started before spawning any workers.
+    for spec in job.engines.iter() {
+        UciEngine::start(spec, job.limit)?;
+    }
+
+    let factory = |spec: &UciEngineSpec| -> EngineFactory {
+        let spec = spec.clone();
+        let limit = job.limit;
+
+        return Arc::new(move || -> Box<dyn Engine> {
+            return match UciEngine::start(&spec, limit) {
+                Ok(engine) => Box::new(engine),
+                Err(_)     => Box::new(FailedEngine { name: spec.name.clone() })
+            };
+        });
+    };
+
+    let engines = [factory(&job.engines[0]), factory(&job.engines[1])];
+    let records = concurrency::play_games(engines, openings, job.games, job.max_plies, config);
+
+    return Ok(MatchScore::from_records(&records));
+}
+
+/// Stands in for an engine that couldn't be started. It loses all of its games.
+struct FailedEngine {
+    name: String
+}
+
+impl Engine for FailedEngine {
+    fn name(&self) -> String {
+        return self.name.clone();
+    }
+
+    fn choose_move(&mut self, _game: &Game) -> Option<EngineMove> {
+        return None;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_jobs() {
+        let job = parse_job(r#"{"id": "42", "dev_name": "dev", "dev_command": "./chess uci", "dev_options": "Hash=16 Threads=1",
+                                "base_command": "./base", "book": "book.epd", "limit": "nodes 5000", "games": 8}"#).unwrap().unwrap();
+
+        assert_eq!(job.id, "42");
+        assert_eq!((job.engines[0].command.as_str(), job.engines[0].args.clone()), ("./chess", vec![String::from("uci")]));
+        assert_eq!(job.engines[0].options, vec![(String::from("Hash"), String::from("16")), (String::from("Threads"), String::from("1"))]);
+        assert_eq!((job.engines[1].name.as_str(), job.engines[1].options.len()), ("base", 0));
+        assert_eq!(job.book, Some(PathBuf::from("book.epd")));
+        assert!(matches!(job.limit, UciLimit::Nodes(5000)));
+        assert_eq!((job.games, job.max_plies), (8, DEFAULT_MAX_PLIES));
+
+        assert!(parse_job("{}").unwrap().is_none());
+        assert!(parse_job(r#"{"id": null}"#).unwrap().is_none());
+        assert!(parse_job(r#"{"id": "1", "dev_command": "a", "base_command": "b", "limit": "soon", "games": 2}"#).is_err());
+        assert!(parse_job("no work").is_err());
+    }
+
+    #[test]
+    fn reports_results_as_json() {
+        let job = parse_job(r#"{"id": "7", "dev_command": "a", "base_command": "b", "limit": "depth 3", "games": 4}"#).unwrap().unwrap();
+        let score = MatchScore { wins: 2, draws: 1, losses: 1, pentanomial: [0, 0, 1, 1, 0] };
+
+        assert_eq!(report_json(&job, &score), r#"{"id":"7","wins":2,"draws":1,"losses":1,"pentanomial":[0,0,1,1,0]}"#);
+    }
+}
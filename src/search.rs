@@ -0,0 +1,2131 @@
+use std::fmt;
+use std::mem;
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::bitboards::geometry::NUM_SQUARES;
+use crate::build_info;
+use crate::clock::Instant;
+use crate::eval;
+use crate::game::Game;
+use crate::moves::Move;
+#[cfg(feature = "nnue")]
+use crate::nnue;
+use crate::pawn_structure::PawnTable;
+use crate::position::{Position, KNIGHT, BISHOP, ROOK, QUEEN, WHITE};
+use crate::runner::{Engine, EngineLine, EngineMove};
+use crate::strength::Strength;
+use crate::training::Rng;
+use crate::tt::{Bound, Entry, TranspositionTable};
+
+
+/// The score of a position in which the player whose turn it is has been mated. Mates found
+/// further away from the root are scored lower so that the shortest mate is preferred.
+pub const MATE_SCORE: i32 = 30000;
+
+/// A bound larger than any score, used as the initial search window.
+const INFINITY: i32 = MATE_SCORE + 1;
+
+/// The number of nodes searched between two checks of the time limit and the stop flag.
+const CHECK_INTERVAL: u64 = 1024;
+
+/// The time between two progress reports during a long iteration.
+const INFO_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The quiescence search only searches evasions in its first plies. Later positions in check
+/// are evaluated statically, so perpetual checks can't make it run forever.
+const MAX_EVASION_PLY: u8 = 6;
+
+/// Iterations from this depth on are searched with an aspiration window around the score of
+/// the previous iteration. The scores of the first iterations jump around too much.
+const ASPIRATION_DEPTH: u8 = 4;
+
+/// The initial distance of the aspiration window bounds from the guessed score. It's doubled
+/// each time the score falls outside of the window.
+const ASPIRATION_WINDOW: i32 = 30;
+
+/// Positions without a move in the transposition table are first searched this much
+/// shallower to find a move to search first (internal iterative deepening).
+const IID_REDUCTION: u8 = 2;
+
+/// Internal iterative deepening is only worth it for positions at least this far above the
+/// quiescence search.
+const IID_DEPTH: u8 = 4;
+
+/// How much shallower the position after a null move is searched.
+const NULL_MOVE_REDUCTION: u8 = 2;
+
+/// Quiet moves are pruned in positions at most this far above the quiescence search if the
+/// static evaluation plus FUTILITY_MARGIN per remaining ply can't raise alpha.
+const FUTILITY_DEPTH: u8 = 3;
+
+/// The largest gain in centipawns expected from a quiet move per remaining ply.
+const FUTILITY_MARGIN: i32 = 150;
+
+/// Positions at most this far above the quiescence search are pruned if the static evaluation
+/// minus REVERSE_FUTILITY_MARGIN per remaining ply still reaches beta.
+const REVERSE_FUTILITY_DEPTH: u8 = 3;
+
+/// The largest loss in centipawns the opponent is expected to inflict per remaining ply.
+const REVERSE_FUTILITY_MARGIN: i32 = 120;
+
+/// Positions at least this far above the quiescence search are pruned if a capture still
+/// fails high against a beta raised by PROBCUT_MARGIN in a search PROBCUT_REDUCTION plies
+/// shallower (ProbCut).
+const PROBCUT_DEPTH: u8 = 5;
+
+/// How much shallower the captures are searched by ProbCut.
+const PROBCUT_REDUCTION: u8 = 4;
+
+/// How far above beta a shallow search has to be to trust that the full search fails high.
+const PROBCUT_MARGIN: i32 = 200;
+
+/// History scores are halved once one of them exceeds this, so they stay small enough to add
+/// to and recent cutoffs weigh more than old ones.
+const MAX_HISTORY: i32 = 1 << 20;
+
+/// Bounds the number of legal moves in a position for estimating the memory a ply of the search
+/// uses. Positions of regular chess have at most 218 legal moves.
+const MAX_MOVES: usize = 256;
+
+/// The number of moves a share of the clock is planned for if the time control doesn't say
+/// how many moves remain until the next one.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+/// The time kept back on the clock for the communication with the user interface.
+const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+
+/// A move may take up to this many times its share of the clock if the best move keeps
+/// changing, but never more than the HARD_TIME_SHARE-th part of the clock.
+const HARD_TIME_FACTOR: u32 = 4;
+const HARD_TIME_SHARE: u32 = 3;
+
+/// Once the best move stayed the same for this many iterations, the search stops after half
+/// of its share of the clock.
+const STABLE_ITERATIONS: u32 = 3;
+
+/// Scores at least this large are mates. The transposition table stores their distance to
+/// mate from the stored position instead of from the root.
+pub const MATE_BOUND: i32 = MATE_SCORE - 1000;
+
+
+/// The limits of an iterative deepening search. The search stops at whichever limit is hit
+/// first. Without any limits it only stops when the stop flag is set.
+#[derive(Clone, Default, Debug)]
+pub struct Limits {
+    pub depth: Option<u8>,
+    /// The time after which the search is interrupted.
+    pub time: Option<Duration>,
+    /// The time after which no new iteration is started. It's halved once the best move is
+    /// stable and doubled after it changed, but the search never takes longer than the time.
+    pub soft_time: Option<Duration>,
+    /// Setting the flag from another thread interrupts the search.
+    pub stop: Option<Arc<AtomicBool>>,
+    /// Also search quiet checks in the first ply of the quiescence search.
+    pub quiescence_checks: bool,
+    /// Prune positions in which passing the turn still fails high. This is much faster, but
+    /// the scores aren't exact anymore, e.g. in zugzwang.
+    pub null_move: bool,
+    /// Prune positions near the leaves whose static evaluation is far outside of the window
+    /// (futility and reverse futility pruning). Like null_move this trades exact scores for
+    /// speed, since tactics can gain more than the margins.
+    pub futility: bool,
+    /// Prune positions in which a capture fails high by a margin in a shallow search (ProbCut).
+    /// Like the other pruning options this trades exact scores for speed.
+    pub probcut: bool,
+    /// The number of positions the search may visit over all iterations. Unlike the time, this
+    /// limit also applies to the first iteration, so the search is aborted with the best move
+    /// found so far if it's exhausted.
+    pub nodes: Option<u64>,
+    /// Root moves that aren't searched, e.g. the best moves of the earlier lines of a MultiPV
+    /// search.
+    pub excluded: Vec<Move>,
+    /// If any moves are given, only these root moves are searched, e.g. the candidate moves of
+    /// a test position or those of the UCI go searchmoves command.
+    pub searchmoves: Vec<Move>,
+    /// Replies in UCI notation the opponent has to answer the root moves with, e.g. to check
+    /// an idea against a specific defence. Root moves after which none of them is legal are
+    /// answered freely.
+    pub replies: Vec<String>,
+    /// The number of bytes the search may use including the transposition table. A table that
+    /// doesn't fit isn't used and positions deeper than the remaining memory allows are
+    /// evaluated statically.
+    pub memory: Option<usize>
+}
+
+impl Limits {
+    /// Checks if the given root move is searched, i.e. it isn't excluded and is one of the
+    /// searchmoves if there are any.
+    pub fn allows(&self, m: &Move) -> bool {
+        return !self.excluded.contains(m) && (self.searchmoves.is_empty() || self.searchmoves.contains(m));
+    }
+}
+
+/// The constraints callers put on a search, as given by the UCI go command: fixed limits, the
+/// clocks of both players or infinite analysis. Search drivers turn them into the Limits of a
+/// search with apply.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct SearchLimits {
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    /// The time to think about the move.
+    pub movetime: Option<Duration>,
+    /// The time left on the clocks of white and black.
+    pub wtime: Option<Duration>,
+    pub btime: Option<Duration>,
+    /// The time added to the clocks of white and black after each move.
+    pub winc: Option<Duration>,
+    pub binc: Option<Duration>,
+    /// The number of moves until the next time control, None if there is none.
+    pub movestogo: Option<u32>,
+    /// Search until stopped, ignoring the other limits.
+    pub infinite: bool,
+    /// Only search these root moves if any are given.
+    pub searchmoves: Vec<Move>
+}
+
+impl SearchLimits {
+    /// Limits the search to the given depth.
+    pub fn depth(depth: u8) -> SearchLimits {
+        return SearchLimits { depth: Some(depth), ..SearchLimits::default() };
+    }
+
+    /// Returns true if neither limits nor clocks are given, so the search driver decides on
+    /// its own how long to search. The searchmoves don't count, they don't limit the time.
+    pub fn is_empty(&self) -> bool {
+        return SearchLimits { searchmoves: Vec::new(), ..self.clone() } == SearchLimits::default();
+    }
+
+    /// Returns the time the player with the given color should think about the move: the
+    /// movetime if it's given, otherwise an equal share of the clock for each of the moves to
+    /// go plus most of the increment. This is the soft limit of the search.
+    pub fn time_for(&self, color: u8) -> Option<Duration> {
+        if self.movetime.is_some() {
+            return self.movetime;
+        }
+
+        let (clock, increment) = if color == WHITE { (self.wtime?, self.winc) } else { (self.btime?, self.binc) };
+        let left = clock.saturating_sub(MOVE_OVERHEAD);
+        let share = left / self.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).max(1) + increment.unwrap_or_default() * 3 / 4;
+
+        return Some(share.min(left));
+    }
+
+    /// Returns the time after which the search of the player with the given color has to be
+    /// interrupted: the movetime if it's given, otherwise HARD_TIME_FACTOR times the share of
+    /// the clock, but at most the HARD_TIME_SHARE-th part of the clock unless the share is
+    /// more.
+    pub fn hard_time_for(&self, color: u8) -> Option<Duration> {
+        if self.movetime.is_some() {
+            return self.movetime;
+        }
+
+        let clock = if color == WHITE { self.wtime? } else { self.btime? };
+        let share = self.time_for(color)?;
+
+        return Some((share * HARD_TIME_FACTOR).min(clock.saturating_sub(MOVE_OVERHEAD) / HARD_TIME_SHARE).max(share));
+    }
+
+    /// Applies the limits to a search of a position in which the player with the given color
+    /// is to move. Empty limits keep the depth, time and nodes of the given limits, otherwise
+    /// they replace them. The searchmoves are always taken over.
+    pub fn apply(&self, color: u8, limits: Limits) -> Limits {
+        let limits = Limits { searchmoves: self.searchmoves.clone(), ..limits };
+
+        if self.is_empty() {
+            return limits;
+        }
+
+        if self.infinite {
+            return Limits { depth: None, time: None, soft_time: None, nodes: None, ..limits };
+        }
+
+        // A fixed movetime is used up, only clocks leave the search room to stop early.
+        let soft_time = self.time_for(color).filter(|_| self.movetime.is_none());
+
+        return Limits { depth: self.depth, time: self.hard_time_for(color), soft_time, nodes: self.nodes, ..limits };
+    }
+}
+
+impl fmt::Display for SearchLimits {
+    /// Writes the limits as the arguments of the UCI go command, e.g. wtime 60000 btime 60000.
+    /// The searchmoves are left out, since writing moves needs the position.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut args = Vec::new();
+        let times = [("movetime", self.movetime), ("wtime", self.wtime), ("btime", self.btime), ("winc", self.winc), ("binc", self.binc)];
+
+        if let Some(depth) = self.depth {
+            args.push(format!("depth {}", depth));
+        }
+
+        if let Some(nodes) = self.nodes {
+            args.push(format!("nodes {}", nodes));
+        }
+
+        for (name, time) in times {
+            if let Some(time) = time {
+                args.push(format!("{} {}", name, time.as_millis()));
+            }
+        }
+
+        if let Some(movestogo) = self.movestogo {
+            args.push(format!("movestogo {}", movestogo));
+        }
+
+        if self.infinite {
+            args.push(String::from("infinite"));
+        }
+
+        return write!(f, "{}", args.join(" "));
+    }
+}
+
+/// The result of a completed iteration of an iterative deepening search.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Iteration {
+    pub depth: u8,
+    pub best: Move,
+    pub score: i32,
+    /// The number of positions searched in this iteration.
+    pub nodes: u64,
+    /// The principal variation: the best move followed by the best replies the search found.
+    /// It ends early where the score came from the transposition table or a pruned position.
+    pub pv: Vec<Move>
+}
+
+/// The progress of an iterative deepening search, reported after each completed iteration
+/// and periodically during long iterations. The score and principal variation are those of
+/// the last completed iteration, the counters are those of the whole search so far.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SearchInfo {
+    /// The depth of the iteration that completed or is running.
+    pub depth: u8,
+    /// The largest distance from the root of a searched position in this iteration, including
+    /// the quiescence search and extensions.
+    pub seldepth: u8,
+    pub nodes: u64,
+    /// The nodes searched per second.
+    pub nps: u64,
+    pub score: Score,
+    pub pv: Vec<Move>,
+    /// The time since the search started.
+    pub time: Duration
+}
+
+impl SearchInfo {
+    /// Formats the info as the arguments of a UCI info command, e.g. "depth 4 seldepth 9
+    /// score cp 25 nodes 3190 nps 95000 time 33 pv e2e4 e7e5". The moves of the principal
+    /// variation are played from the given root position.
+    pub fn to_uci(&self, root: &Position) -> String {
+        let mut position = root.clone();
+        let mut pv = Vec::new();
+
+        for &m in &self.pv {
+            pv.push(position.move_to_uci(m));
+            position = position.make_move(m);
+        }
+
+        return format!("depth {} seldepth {} score {} nodes {} nps {} time {} pv {}",
+                       self.depth, self.seldepth, self.score, self.nodes, self.nps, self.time.as_millis(), pv.join(" "));
+    }
+}
+
+/// A score as reported to users: either centipawns or the number of moves until mate, which
+/// is negative if the player to move gets mated. The search itself uses plain integers with
+/// mates at MATE_SCORE minus the plies until mate.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Score {
+    Centipawns(i32),
+    Mate(i32)
+}
+
+impl Score {
+    /// Converts a score of the search.
+    pub fn new(score: i32) -> Score {
+        return match score {
+            s if s >= MATE_BOUND  => Score::Mate((MATE_SCORE - s + 1) / 2),
+            s if s <= -MATE_BOUND => Score::Mate(-(MATE_SCORE + s) / 2),
+            s                     => Score::Centipawns(s)
+        };
+    }
+
+    /// Converts the score back into one of the search. Mates are counted from the shortest
+    /// number of plies they take.
+    pub fn value(self) -> i32 {
+        return match self {
+            Score::Centipawns(cp)      => cp,
+            Score::Mate(n) if n > 0    => MATE_SCORE - (2 * n - 1),
+            Score::Mate(n)             => -MATE_SCORE + 2 * -n
+        };
+    }
+
+    /// Returns the number of moves until mate if the score is a mate.
+    pub fn mate(self) -> Option<i32> {
+        return match self {
+            Score::Mate(n) => Some(n),
+            _              => None
+        };
+    }
+
+    /// Formats the score in pawns like +1.25, or mates like #3 and #-2.
+    pub fn to_pawns(self) -> String {
+        return match self {
+            Score::Centipawns(cp) => format!("{:+.2}", cp as f64 / 100.0),
+            Score::Mate(n)        => format!("#{}", n)
+        };
+    }
+}
+
+impl fmt::Display for Score {
+    /// Writes the score the way UCI reports it, e.g. cp 25 or mate -3.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Score::Centipawns(cp) => write!(f, "cp {}", cp),
+            Score::Mate(n)        => write!(f, "mate {}", n)
+        };
+    }
+}
+
+/// Searches the tree of legal moves of the given depth and returns the best move in the
+/// position together with its score from the point of view of the player whose turn it is.
+/// Returns None if there are no legal moves. Of several equally good moves the one generated
+/// first is returned.
+pub fn search(position: &Position, depth: u8) -> Option<(Move, i32)> {
+    return Searcher::new(None, None, false).root(position, depth, None, -INFINITY, INFINITY);
+}
+
+/// Searches the position with increasing depths (1, 2, 3, ...) until a limit is hit. Each
+/// iteration searches the best move of the previous one first. The callback is called after
+/// every completed iteration and the last completed iteration is returned, so an interrupted
+/// search still returns the best move found so far. The first iteration always completes.
+/// Returns None if there are no legal moves.
+pub fn iterative_deepening<F: FnMut(&Iteration)>(position: &Position, limits: &Limits, on_iteration: F) -> Option<Iteration> {
+    return deepen(position, limits, None, on_iteration, &mut |_| ());
+}
+
+/// Like iterative_deepening, but positions are looked up in and stored into the transposition
+/// table. The table keeps its entries, so they can be reused by later searches.
+pub fn iterative_deepening_with_table<F: FnMut(&Iteration)>(position: &Position, limits: &Limits, tt: &mut TranspositionTable, on_iteration: F) -> Option<Iteration> {
+    return deepen(position, limits, Some(tt), on_iteration, &mut |_| ());
+}
+
+/// Like iterative_deepening_with_table, but the progress of the search is reported to the
+/// callback after every completed iteration and every INFO_INTERVAL during longer ones, e.g.
+/// for the info lines of UCI.
+pub fn iterative_deepening_with_info<F: FnMut(&SearchInfo)>(position: &Position, limits: &Limits, tt: &mut TranspositionTable, mut on_info: F) -> Option<Iteration> {
+    return deepen(position, limits, Some(tt), |_| (), &mut on_info);
+}
+
+fn deepen<F: FnMut(&Iteration)>(position: &Position, limits: &Limits, tt: Option<&mut TranspositionTable>, mut on_iteration: F, on_info: &mut dyn FnMut(&SearchInfo)) -> Option<Iteration> {
+    // With a network loaded the tree is evaluated with it, updating the accumulator from the
+    // root on.
+    #[cfg(feature = "nnue")]
+    let position = &position.with_network(nnue::network());
+
+    let start = Instant::now();
+    let deadline = limits.time.map(|time| start + time);
+    let mut last: Option<Iteration> = None;
+    let mut history = History::new();
+    let mut pawns = PawnTable::default();
+    let mut nodes = 0;
+    let mut progress: Option<Progress> = None;
+    let mut clock = TimeManager::new(limits.soft_time);
+
+    let mut tt = tt.filter(|tt| limits.memory.is_none_or(|memory| tt.memory_usage() <= memory));
+    let max_ply = limits.memory.map_or(i32::MAX, |memory| {
+        let fixed = tt.as_ref().map_or(0, |tt| tt.memory_usage()) + history.memory_usage() + pawns.memory_usage();
+
+        return (memory.saturating_sub(fixed) / ply_memory(position)).max(1) as i32;
+    });
+
+    for depth in 1..=limits.depth.unwrap_or(u8::MAX) {
+        if clock.should_stop(start.elapsed()) {
+            break;
+        }
+
+        // The first iteration runs without time limits, so there always is a move to return.
+        let mut searcher = match depth {
+            1 => Searcher::new(None, None, limits.quiescence_checks),
+            _ => Searcher::new(deadline, limits.stop.clone(), limits.quiescence_checks)
+        };
+
+        searcher.tt = tt.as_deref_mut();
+        searcher.history = history;
+        searcher.pawns = pawns;
+        searcher.null_move = limits.null_move;
+        searcher.futility = limits.futility;
+        searcher.probcut = limits.probcut;
+        searcher.excluded = limits.excluded.clone();
+        searcher.searchmoves = limits.searchmoves.clone();
+        searcher.replies = limits.replies.clone();
+        searcher.max_nodes = limits.nodes.map(|max| max.saturating_sub(nodes));
+        searcher.max_ply = max_ply;
+        searcher.on_info = Some(&mut *on_info);
+        searcher.progress = progress.take().map(|progress| Progress { depth, nodes, ..progress });
+
+        let guess = last.as_ref().map(|it| it.score).filter(|_| depth >= ASPIRATION_DEPTH);
+        let result = searcher.aspiration(position, depth, last.as_ref().map(|it| it.best), guess);
+
+        // The history is kept for the next iteration, but the cutoffs of deeper searches
+        // should count more.
+        history = mem::take(&mut searcher.history);
+        history.decay();
+        pawns = mem::take(&mut searcher.pawns);
+        nodes += searcher.nodes;
+
+        let (aborted, searched, seldepth) = (searcher.aborted, searcher.nodes, searcher.seldepth);
+        let pv = mem::take(&mut searcher.frame(0).pv);
+        drop(searcher);
+
+        if aborted {
+            // Only the node budget can cut the first iteration short. The moves searched so far
+            // are still better than none.
+            if last.is_none() {
+                let first = || ordered_moves(position).into_iter().find(|m| limits.allows(m)).map(|m| (m, -eval::evaluate(&position.make_move(m))));
+
+                last = result.or_else(first).map(|(best, score)| Iteration { depth, best, score, nodes: searched, pv: vec![best] });
+            }
+
+            break;
+        }
+
+        let (best, score) = result?;
+        let iteration = Iteration { depth, best, score, nodes: searched, pv };
+
+        on_info(&search_info(start, depth, seldepth, nodes, score, iteration.pv.clone()));
+        on_iteration(&iteration);
+        clock.update(best);
+        progress = Some(Progress { start, reported: Instant::now(), depth, nodes, score, pv: iteration.pv.clone() });
+        last = Some(iteration);
+
+        // Searching deeper can't change a forced mate anymore.
+        if score.abs() >= MATE_SCORE - depth as i32 {
+            break;
+        }
+    }
+
+    return last;
+}
+
+/// Builds the info reported after the given time and number of nodes.
+fn search_info(start: Instant, depth: u8, seldepth: u8, nodes: u64, score: i32, pv: Vec<Move>) -> SearchInfo {
+    let time = start.elapsed();
+    let nps = (nodes as u128 * 1000 / time.as_millis().max(1)) as u64;
+
+    return SearchInfo { depth, seldepth, nodes, nps, score: Score::new(score), pv, time };
+}
+
+/// Searches the tree of legal moves of the given depth using negamax and returns the score of
+/// the position from the point of view of the player whose turn it is. The ply is the distance
+/// of the position from the root of the search and is used to score mates.
+pub fn negamax(position: &Position, depth: u8, ply: i32) -> i32 {
+    return alpha_beta(position, depth, ply, -INFINITY, INFINITY);
+}
+
+/// Negamax with alpha-beta pruning: Moves are only searched as long as they can still change
+/// the score within the window (alpha, beta). The score is exact if it lies inside the window,
+/// otherwise it's a bound on the exact score.
+pub fn alpha_beta(position: &Position, depth: u8, ply: i32, alpha: i32, beta: i32) -> i32 {
+    return Searcher::new(None, None, false).alpha_beta(position, depth, ply, alpha, beta);
+}
+
+/// Searches only the captures of a position until it's quiet, so the static evaluation isn't
+/// applied in the middle of an exchange. The player to move may also stand pat, i.e. take the
+/// static evaluation instead of capturing. Positions in check are searched with all moves.
+pub fn quiescence(position: &Position, ply: i32, alpha: i32, beta: i32) -> i32 {
+    return Searcher::new(None, None, false).quiescence(position, ply, alpha, beta, 0);
+}
+
+/// Counts how often quiet moves caused cutoffs, indexed by the color of the moving player and
+/// the origin and target squares of the moves. Cutoffs further away from the leaves count more.
+/// Castling moves aren't tracked, drops are tracked with the target as origin square.
+#[derive(Clone)]
+struct History {
+    scores: Vec<[[i32; NUM_SQUARES as usize]; NUM_SQUARES as usize]>
+}
+
+impl History {
+    fn new() -> History {
+        return History { scores: vec![[[0; NUM_SQUARES as usize]; NUM_SQUARES as usize]; 2] };
+    }
+
+    /// Returns the number of bytes the table occupies.
+    fn memory_usage(&self) -> usize {
+        return self.scores.len() * mem::size_of::<[[i32; NUM_SQUARES as usize]; NUM_SQUARES as usize]>();
+    }
+
+    fn squares(m: Move) -> Option<(usize, usize)> {
+        return match m {
+            Move::StandardMove(_, origin, target, _, _, _) => Some((origin as usize, target as usize)),
+            Move::Drop(_, target)                          => Some((target as usize, target as usize)),
+            _                                              => None
+        };
+    }
+
+    fn score(&self, color: u8, m: Move) -> i32 {
+        return History::squares(m).map_or(0, |(origin, target)| self.scores[color as usize][origin][target]);
+    }
+
+    fn update(&mut self, color: u8, m: Move, depth: u8) {
+        if let Some((origin, target)) = History::squares(m) {
+            let score = &mut self.scores[color as usize][origin][target];
+
+            *score += depth as i32 * depth as i32;
+
+            if *score > MAX_HISTORY {
+                self.decay();
+            }
+        }
+    }
+
+    /// Halves all scores.
+    fn decay(&mut self) {
+        self.scores.iter_mut().flatten().flatten().for_each(|score| *score /= 2);
+    }
+}
+
+impl Default for History {
+    fn default() -> History {
+        return History::new();
+    }
+}
+
+/// What the search remembers about the positions at one ply.
+#[derive(Clone, Default, Debug)]
+struct Frame {
+    /// Quiet moves that caused a cutoff in a sibling position, the most recent one first. They
+    /// are likely to refute other moves at the same ply as well.
+    killers: [Option<Move>; 2],
+    /// Whether the position at this ply was reached by a null move. Two null moves in a row
+    /// would just search the same position at a lower depth.
+    passed: bool,
+    /// The best line found from the position at this ply, its best move first (triangular PV
+    /// table). The line of the root is the principal variation.
+    pv: Vec<Move>
+}
+
+/// Decides whether to start another iteration within the soft time limit, depending on how
+/// stable the best move has been. The hard limit is enforced by the deadline of the searches.
+struct TimeManager {
+    soft: Option<Duration>,
+    best: Option<Move>,
+    /// The number of completed iterations since the best move last changed.
+    stable: u32
+}
+
+impl TimeManager {
+    fn new(soft: Option<Duration>) -> TimeManager {
+        return TimeManager { soft, best: None, stable: 0 };
+    }
+
+    /// Records the best move of a completed iteration.
+    fn update(&mut self, best: Move) {
+        match self.best {
+            Some(previous) if previous == best => self.stable += 1,
+            Some(_)                            => self.stable = 0,
+            None                               => self.stable = 1
+        }
+
+        self.best = Some(best);
+    }
+
+    /// Checks if the next iteration shouldn't be started after the given time. A best move
+    /// that just changed gets twice the time to settle, a stable one only half of it.
+    fn should_stop(&self, elapsed: Duration) -> bool {
+        let soft = match self.soft {
+            Some(soft) => soft,
+            None       => return false
+        };
+
+        let scaled = match self.stable {
+            0                           => soft * 2,
+            s if s >= STABLE_ITERATIONS => soft / 2,
+            _                           => soft
+        };
+
+        return elapsed >= scaled;
+    }
+}
+
+/// What an iteration needs to report the progress of the search while it runs: the score and
+/// principal variation of the last completed iteration, the depth of the running one and the
+/// nodes of the completed ones.
+struct Progress {
+    start: Instant,
+    /// When the progress was last reported.
+    reported: Instant,
+    depth: u8,
+    nodes: u64,
+    score: i32,
+    pv: Vec<Move>
+}
+
+/// The state of a single search: the limits to check, the transposition table if one is used,
+/// a frame for each ply and the number of nodes searched.
+struct Searcher<'a> {
+    deadline: Option<Instant>,
+    stop: Option<Arc<AtomicBool>>,
+    max_nodes: Option<u64>,
+    /// Positions at this ply are evaluated statically, so the memory used along the searched
+    /// line stays bounded.
+    max_ply: i32,
+    checks: bool,
+    null_move: bool,
+    futility: bool,
+    probcut: bool,
+    excluded: Vec<Move>,
+    searchmoves: Vec<Move>,
+    replies: Vec<String>,
+    tt: Option<&'a mut TranspositionTable>,
+    history: History,
+    pawns: PawnTable,
+    stack: Vec<Frame>,
+    /// Receives the progress reports during the iteration, which are only sent once a first
+    /// iteration completed.
+    on_info: Option<&'a mut dyn FnMut(&SearchInfo)>,
+    progress: Option<Progress>,
+    nodes: u64,
+    /// The largest ply a position was searched at.
+    seldepth: u8,
+    aborted: bool
+}
+
+impl<'a> Searcher<'a> {
+    fn new(deadline: Option<Instant>, stop: Option<Arc<AtomicBool>>, checks: bool) -> Searcher<'a> {
+        return Searcher { deadline, stop, max_nodes: None, max_ply: i32::MAX, checks, null_move: false, futility: false, probcut: false, excluded: Vec::new(), searchmoves: Vec::new(), replies: Vec::new(), tt: None, history: History::new(), pawns: PawnTable::default(), stack: Vec::new(), on_info: None, progress: None, nodes: 0, seldepth: 0, aborted: false };
+    }
+
+    /// Evaluates the position statically, with the pawn structure from the pawn hash table.
+    fn evaluate(&mut self, position: &Position) -> i32 {
+        return eval::evaluate_with_pawn_table(position, &mut self.pawns);
+    }
+
+    fn frame(&mut self, ply: i32) -> &mut Frame {
+        let ply = ply as usize;
+
+        if self.stack.len() <= ply {
+            self.stack.resize(ply + 1, Frame::default());
+        }
+
+        return &mut self.stack[ply];
+    }
+
+    /// Returns the legal moves in the order they are searched: the hash move, the captures of
+    /// the most valuable pieces, the killer moves of the ply and then the remaining moves by
+    /// their history scores.
+    fn ordered_moves(&mut self, position: &Position, ply: i32, hash_move: Option<Move>) -> Vec<Move> {
+        let mut moves = ordered_moves(position);
+        let captures = moves.iter().take_while(|m| capture_order(m) < 0).count();
+        let killers = self.frame(ply).killers;
+
+        moves[captures..].sort_by_key(|m| -self.history.score(position.turn, *m));
+
+        for killer in killers.iter().rev().flatten() {
+            if let Some(index) = moves[captures..].iter().position(|m| m == killer) {
+                let m = moves.remove(captures + index);
+                moves.insert(captures, m);
+            }
+        }
+
+        // The best move of an earlier search of the position is likely still good.
+        if let Some(index) = hash_move.and_then(|first| moves.iter().position(|m| *m == first)) {
+            let m = moves.remove(index);
+            moves.insert(0, m);
+        }
+
+        return moves;
+    }
+
+    /// Searches the root with a narrow window around the guessed score, which cuts off more
+    /// moves than the full window. If the score falls outside of the window, the search is
+    /// repeated with a wider window on that side until it lies inside.
+    fn aspiration(&mut self, position: &Position, depth: u8, first: Option<Move>, guess: Option<i32>) -> Option<(Move, i32)> {
+        let guess = match guess.filter(|guess| guess.abs() < MATE_BOUND) {
+            Some(guess) => guess,
+            None        => return self.root(position, depth, first, -INFINITY, INFINITY)
+        };
+
+        let mut delta = ASPIRATION_WINDOW;
+        let (mut alpha, mut beta) = (guess - delta, guess + delta);
+
+        loop {
+            let result = self.root(position, depth, first, alpha, beta);
+
+            match result {
+                Some((_, score)) if score <= alpha && alpha > -INFINITY => alpha = (alpha - delta).max(-INFINITY),
+                Some((_, score)) if score >= beta && beta < INFINITY    => beta = (beta + delta).min(INFINITY),
+                _                                                       => return result
+            }
+
+            if self.aborted {
+                return result;
+            }
+
+            delta *= 2;
+        }
+    }
+
+    /// Checks if the player to move still fails high after passing the turn, so the position
+    /// doesn't need to be searched, and returns the score in that case. Mates found after
+    /// passing aren't proven, so the score is kept below them. Null moves aren't tried in check, after another null move
+    /// and if the player only has pawns left, because passing could be better than any move
+    /// then (zugzwang).
+    fn null_move_cutoff(&mut self, position: &Position, depth: u8, ply: i32, beta: i32) -> Option<i32> {
+        let pieces = [KNIGHT, BISHOP, ROOK, QUEEN].iter().any(|&t| position.get_piece_bitboard(position.turn, t) != 0);
+
+        if depth <= NULL_MOVE_REDUCTION || ply == 0 || self.frame(ply).passed || !pieces
+            || beta.abs() >= MATE_BOUND || position.is_checked(position.turn) {
+            return None;
+        }
+
+        let mut passed = position.clone();
+        passed.make_null_move();
+
+        self.frame(ply + 1).passed = true;
+        let score = -self.alpha_beta(&passed, depth - 1 - NULL_MOVE_REDUCTION, ply + 1, -beta, -beta + 1);
+        self.frame(ply + 1).passed = false;
+
+        return Some(score.min(MATE_BOUND - 1)).filter(|score| !self.aborted && *score >= beta);
+    }
+
+    /// Searches the captures that win enough material to reach a beta raised by PROBCUT_MARGIN,
+    /// first by the quiescence search and, if that fails high, by a shallow search. Returns
+    /// the score of the first capture that fails high in both, which suggests that the full
+    /// search fails high as well.
+    fn probcut(&mut self, position: &Position, depth: u8, ply: i32, beta: i32) -> Option<i32> {
+        if depth < PROBCUT_DEPTH || ply == 0 || beta.abs() >= MATE_BOUND - PROBCUT_MARGIN || position.is_checked(position.turn) {
+            return None;
+        }
+
+        let raised = beta + PROBCUT_MARGIN;
+        let static_eval = self.evaluate(position);
+
+        for m in ordered_captures(position) {
+            if static_eval + eval::see(position, m) < raised {
+                continue;
+            }
+
+            let next = position.make_move(m);
+            let mut score = -self.quiescence(&next, ply + 1, -raised, -raised + 1, 0);
+
+            if score >= raised {
+                score = -self.alpha_beta(&next, depth - 1 - PROBCUT_REDUCTION, ply + 1, -raised, -raised + 1);
+            }
+
+            if self.aborted {
+                return None;
+            }
+
+            if score >= raised {
+                return Some(score);
+            }
+        }
+
+        return None;
+    }
+
+    /// Returns the legal replies the opponent is forced to answer the root move with, which are
+    /// none if there are no such constraints or none of them is legal in the position.
+    fn forced_replies(&self, position: &Position, ply: i32) -> Vec<Move> {
+        if ply != 1 || self.replies.is_empty() {
+            return Vec::new();
+        }
+
+        return position.clone().get_all_legal_moves().into_iter().filter(|&m| self.replies.contains(&position.move_to_uci(m))).collect();
+    }
+
+    /// Returns the static evaluation if futility pruning applies to the position. It doesn't
+    /// in check and at the root, where no move may be pruned.
+    fn futility_eval(&mut self, position: &Position, depth: u8, ply: i32) -> Option<i32> {
+        if !self.futility || ply == 0 || depth > FUTILITY_DEPTH.max(REVERSE_FUTILITY_DEPTH) || position.is_checked(position.turn) {
+            return None;
+        }
+
+        return Some(self.evaluate(position));
+    }
+
+    /// Makes the given move followed by the best line of the next ply the best line of the
+    /// given ply.
+    fn update_pv(&mut self, ply: i32, m: Move) {
+        let mut pv = mem::take(&mut self.frame(ply + 1).pv);
+        pv.insert(0, m);
+
+        self.frame(ply).pv = pv;
+    }
+
+    /// Remembers a quiet move that caused a cutoff at the given ply.
+    fn store_killer(&mut self, ply: i32, m: Move) {
+        let killers = &mut self.frame(ply).killers;
+
+        if killers[0] != Some(m) {
+            killers[1] = killers[0];
+            killers[0] = Some(m);
+        }
+    }
+
+    /// Searches all moves in the root position, the given move first. Like alpha_beta the
+    /// score is only exact if it lies inside the window (alpha, beta).
+    fn root(&mut self, position: &Position, depth: u8, first: Option<Move>, mut alpha: i32, beta: i32) -> Option<(Move, i32)> {
+        let mut moves = ordered_moves(position);
+        moves.retain(|m| !self.excluded.contains(m) && (self.searchmoves.is_empty() || self.searchmoves.contains(m)));
+
+        if let Some(index) = first.and_then(|first| moves.iter().position(|m| *m == first)) {
+            let m = moves.remove(index);
+            moves.insert(0, m);
+        }
+
+        let mut best: Option<(Move, i32)> = None;
+        self.frame(0).pv.clear();
+
+        for m in moves {
+            if self.should_stop() {
+                self.aborted = true;
+            }
+
+            let score = -self.alpha_beta(&position.make_move(m), depth.saturating_sub(1), 1, -beta, -alpha);
+
+            if self.aborted {
+                break;
+            }
+
+            if best.is_none_or(|(_, best)| score > best) {
+                best = Some((m, score));
+                self.update_pv(0, m);
+            }
+
+            alpha = alpha.max(score);
+
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        return best;
+    }
+
+    fn alpha_beta(&mut self, position: &Position, depth: u8, ply: i32, mut alpha: i32, mut beta: i32) -> i32 {
+        if self.visit(ply) {
+            return 0;
+        }
+
+        // Positions left early have no line of their own.
+        self.frame(ply).pv.clear();
+
+        if position.is_variant_loss() {
+            return -MATE_SCORE + ply;
+        }
+
+        // Neither side can do better than mating right away, so if a shorter mate has been
+        // found already, this position can't improve on it (mate distance pruning).
+        alpha = alpha.max(-MATE_SCORE + ply);
+        beta = beta.min(MATE_SCORE - ply - 1);
+
+        if alpha >= beta {
+            return alpha;
+        }
+
+        if ply >= self.max_ply {
+            return self.evaluate(position);
+        }
+
+        // Positions in check are searched one ply deeper, so forced sequences of checks and
+        // evasions aren't cut off at the horizon (check extension).
+        let depth = if position.is_checked(position.turn) { depth.saturating_add(1) } else { depth };
+
+        if depth == 0 {
+            return self.quiescence(position, ply, alpha, beta, 0);
+        }
+
+        // A forced reply changes the score of the position, so it's neither taken from nor
+        // stored in the table and nothing is pruned before the reply is searched.
+        let forced = self.forced_replies(position, ply);
+        let free = forced.is_empty();
+
+        let key = position.hash_key();
+        let mut hash_move = None;
+
+        if let Some(entry) = self.tt.as_ref().filter(|_| free).and_then(|tt| tt.probe(key)) {
+            let score = score_from_table(entry.score, ply);
+
+            if entry.depth >= depth {
+                match entry.bound {
+                    Bound::Exact                   => return score,
+                    Bound::Lower if score >= beta  => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _                              => ()
+                }
+            }
+
+            hash_move = entry.best;
+        }
+
+        let static_eval = self.futility_eval(position, depth, ply).filter(|_| free);
+
+        // The player to move is so far ahead that the opponent won't catch up in the
+        // remaining plies (reverse futility pruning).
+        if let Some(eval) = static_eval.filter(|_| depth <= REVERSE_FUTILITY_DEPTH && beta.abs() < MATE_BOUND) {
+            let score = eval - REVERSE_FUTILITY_MARGIN * depth as i32;
+
+            if score >= beta {
+                return score;
+            }
+        }
+
+        if let Some(score) = (self.null_move && free).then(|| self.null_move_cutoff(position, depth, ply, beta)).flatten() {
+            return score;
+        }
+
+        if let Some(score) = (self.probcut && free).then(|| self.probcut(position, depth, ply, beta)).flatten() {
+            return score;
+        }
+
+        // Quiet moves can't make up for being this far behind (futility pruning). The bound is
+        // the best score a pruned move is assumed to reach.
+        let futile = static_eval
+            .filter(|_| depth <= FUTILITY_DEPTH && alpha.abs() < MATE_BOUND)
+            .map(|eval| eval + FUTILITY_MARGIN * depth as i32)
+            .filter(|bound| *bound <= alpha);
+
+        // Without a move from the table, a shallower search of the position finds one, which is
+        // likely good enough to cause a cutoff or raise alpha if searched first.
+        if hash_move.is_none() && depth >= IID_DEPTH && self.tt.is_some() && free {
+            self.alpha_beta(position, depth - IID_REDUCTION, ply, alpha, beta);
+
+            if self.aborted {
+                return 0;
+            }
+
+            hash_move = self.tt.as_ref().and_then(|tt| tt.probe(key)).and_then(|entry| entry.best);
+            self.frame(ply).pv.clear();
+        }
+
+        let mut moves = self.ordered_moves(position, ply, hash_move);
+
+        if !free {
+            moves.retain(|m| forced.contains(m));
+        }
+
+        // Checkmate or stalemate.
+        if moves.is_empty() {
+            return if position.is_checked(position.turn) { -MATE_SCORE + ply } else { 0 };
+        }
+
+        let original_alpha = alpha;
+        let mut best = -INFINITY;
+        let mut best_move = None;
+
+        for (i, m) in moves.into_iter().enumerate() {
+            let next = position.make_move(m);
+
+            // The first move is always searched, so there is a best move.
+            if let Some(bound) = futile.filter(|_| i > 0 && is_quiet(position, m) && !next.is_checked(next.turn)) {
+                best = best.max(bound);
+                continue;
+            }
+
+            let score = -self.alpha_beta(&next, depth - 1, ply + 1, -beta, -alpha);
+
+            if score > best {
+                best = score;
+                best_move = Some(m);
+                self.update_pv(ply, m);
+            }
+
+            alpha = alpha.max(score);
+
+            if alpha >= beta {
+                if position.captured_piece(m).is_none() {
+                    self.store_killer(ply, m);
+                    self.history.update(position.turn, m, depth);
+                }
+
+                break;
+            }
+        }
+
+        // The scores of an interrupted search are incomplete.
+        if self.aborted {
+            return best;
+        }
+
+        if let Some(tt) = self.tt.as_mut().filter(|_| free) {
+            let bound = if best <= original_alpha {
+                Bound::Upper
+            } else if best >= beta {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+
+            tt.store(Entry { key, depth, score: score_to_table(best, ply), bound, best: best_move });
+        }
+
+        return best;
+    }
+
+    /// The quiescence search at the given distance from its start. Quiet checks are only
+    /// searched in its first ply and evasions only in its first plies, otherwise checks and
+    /// evasions could go on forever.
+    fn quiescence(&mut self, position: &Position, ply: i32, mut alpha: i32, beta: i32, qply: u8) -> i32 {
+        if self.visit(ply) {
+            return 0;
+        }
+
+        // The principal variation ends where the quiescence search begins.
+        self.frame(ply).pv.clear();
+
+        if position.is_variant_loss() {
+            return -MATE_SCORE + ply;
+        }
+
+        if ply >= self.max_ply {
+            return self.evaluate(position);
+        }
+
+        let in_check = qply < MAX_EVASION_PLY && position.is_checked(position.turn);
+
+        let mut best = -INFINITY;
+
+        let moves = if in_check {
+            let moves = ordered_moves(position);
+
+            if moves.is_empty() {
+                return -MATE_SCORE + ply;
+            }
+
+            moves
+        } else {
+            let stand_pat = self.evaluate(position);
+
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+
+            best = stand_pat;
+            alpha = alpha.max(stand_pat);
+
+            let mut moves = ordered_captures(position);
+
+            if qply == 0 && self.checks {
+                moves.extend(position.clone().get_all_legal_moves().into_iter().filter(|&m| {
+                    let next = position.make_move(m);
+                    return position.captured_piece(m).is_none() && next.is_checked(next.turn);
+                }));
+            }
+
+            moves
+        };
+
+        for m in moves {
+            let score = -self.quiescence(&position.make_move(m), ply + 1, -beta, -alpha, qply + 1);
+
+            best = best.max(score);
+            alpha = alpha.max(score);
+
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        return best;
+    }
+
+    /// Counts a visited position at the given ply and checks the limits. Returns true if the
+    /// search is aborted. The node budget is checked at every node, so it's never exceeded.
+    fn visit(&mut self, ply: i32) -> bool {
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply.clamp(0, u8::MAX as i32) as u8);
+
+        if self.max_nodes.is_some_and(|max| self.nodes > max) || self.nodes.is_multiple_of(CHECK_INTERVAL) && self.should_stop() {
+            self.aborted = true;
+        }
+
+        if self.nodes.is_multiple_of(CHECK_INTERVAL) {
+            self.report();
+        }
+
+        return self.aborted;
+    }
+
+    /// Reports the progress if INFO_INTERVAL passed since the last report.
+    fn report(&mut self) {
+        if let (Some(on_info), Some(progress)) = (self.on_info.as_mut(), self.progress.as_mut()) {
+            if progress.reported.elapsed() >= INFO_INTERVAL {
+                on_info(&search_info(progress.start, progress.depth, self.seldepth, progress.nodes + self.nodes, progress.score, progress.pv.clone()));
+                progress.reported = Instant::now();
+            }
+        }
+    }
+
+    fn should_stop(&self) -> bool {
+        return self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            || self.stop.as_ref().is_some_and(|stop| stop.load(Ordering::Relaxed));
+    }
+}
+
+/// Checks if a move neither captures nor promotes. Checks are detected separately.
+fn is_quiet(position: &Position, m: Move) -> bool {
+    return position.captured_piece(m).is_none() && !matches!(m, Move::StandardMove(_, _, _, _, Some(_), _));
+}
+
+/// Estimates the bytes the search uses for each ply of the searched line below the given root:
+/// the position, the list of its moves and the frame.
+fn ply_memory(root: &Position) -> usize {
+    return root.memory_usage() + MAX_MOVES * mem::size_of::<Move>() + mem::size_of::<Frame>();
+}
+
+/// Follows the best moves stored in the transposition table from the given position, e.g. to
+/// get the principal variation after a search. The line ends at the first position without a
+/// stored move or after max_len moves.
+pub fn table_line(position: &Position, tt: &TranspositionTable, max_len: usize) -> Vec<Move> {
+    let mut line = Vec::new();
+    let mut position = position.clone();
+
+    while line.len() < max_len {
+        let m = match tt.probe(position.hash_key()).and_then(|entry| entry.best) {
+            Some(m) if position.clone().get_all_legal_moves().contains(&m) => m,
+            _ => break
+        };
+
+        line.push(m);
+        position = position.make_move(m);
+    }
+
+    return line;
+}
+
+/// Searches the given number of best moves in the position (MultiPV). Each line is searched with the
+/// best moves of the earlier lines excluded at the root. Returns the last completed iteration
+/// of each line, best line first. The limits apply to each line on its own. Returns fewer
+/// lines if there are fewer legal moves.
+pub fn multi_pv(position: &Position, limits: &Limits, tt: &mut TranspositionTable, lines: usize) -> Vec<Iteration> {
+    let mut limits = limits.clone();
+    let mut found: Vec<Iteration> = Vec::new();
+
+    while found.len() < lines {
+        let iteration = match iterative_deepening_with_table(position, &limits, tt, |_| ()) {
+            Some(iteration) => iteration,
+            None            => break
+        };
+
+        limits.excluded.push(iteration.best);
+        found.push(iteration);
+    }
+
+    // Lines searched later can come out ahead when an earlier search was cut short.
+    found.sort_by_key(|iteration| -iteration.score);
+
+    return found;
+}
+
+/// Scores the root moves the limits allow with a search of the given depth like
+/// Position::evaluate_all_moves, with the opponent answering them with the forced replies of
+/// the limits if there are any. The other limits are ignored. The moves are sorted from best
+/// to worst.
+pub fn evaluate_moves(position: &Position, depth: u8, limits: &Limits) -> Vec<(Move, i32)> {
+    let mut searcher = Searcher::new(None, None, false);
+    searcher.replies = limits.replies.clone();
+
+    let mut moves: Vec<(Move, i32)> = position.clone().get_all_legal_moves().into_iter()
+        .filter(|m| limits.allows(m))
+        .map(|m| (m, -searcher.alpha_beta(&position.make_move(m), depth.saturating_sub(1), 1, -INFINITY, INFINITY)))
+        .collect();
+
+    // The sort is stable, so moves with equal scores stay in the order of the move generator.
+    moves.sort_by_key(|(_, score)| -score);
+
+    return moves;
+}
+
+/// Returns the legal moves with captures of the most valuable pieces first, which makes
+/// cutoffs happen earlier.
+fn ordered_moves(position: &Position) -> Vec<Move> {
+    let mut moves = position.clone().get_all_legal_moves();
+
+    moves.sort_by_key(capture_order);
+
+    return moves;
+}
+
+/// Converts a score from the point of view of the root into one for the transposition table by
+/// counting mates from the position at the given ply.
+fn score_to_table(score: i32, ply: i32) -> i32 {
+    return match score {
+        s if s >= MATE_BOUND  => s + ply,
+        s if s <= -MATE_BOUND => s - ply,
+        s                     => s
+    };
+}
+
+/// Reverses score_to_table for a position at the given ply.
+fn score_from_table(score: i32, ply: i32) -> i32 {
+    return match score {
+        s if s >= MATE_BOUND  => s - ply,
+        s if s <= -MATE_BOUND => s + ply,
+        s                     => s
+    };
+}
+
+/// Returns the legal captures, the most valuable pieces first.
+fn ordered_captures(position: &Position) -> Vec<Move> {
+    let mut moves = position.get_all_legal_captures();
+
+    moves.sort_by_key(capture_order);
+
+    return moves;
+}
+
+/// The sort key putting captures of the most valuable pieces first.
+fn capture_order(m: &Move) -> i32 {
+    return match m {
+        Move::StandardMove(_, _, _, Some(captured), _, _) => -eval::PIECE_VALUES[*captured as usize],
+        _                                                  => 0
+    };
+}
+
+/// Returns the principal variation of a search of the given depth: the best move followed by
+/// the best replies, each found by a search of the remaining depth.
+pub fn principal_variation(position: &Position, depth: u8) -> Vec<Move> {
+    let mut line = Vec::new();
+    let mut position = position.clone();
+
+    for remaining in (1..=depth).rev() {
+        let m = match position.evaluate_all_moves(remaining).first() {
+            Some((m, _)) => *m,
+            None         => break
+        };
+
+        line.push(m);
+        position = position.make_move(m);
+    }
+
+    return line;
+}
+
+/// An iterative deepening search running on its own thread, for using the search as a library
+/// without blocking the caller. The search can be stopped at any time and the last completed
+/// iteration can be polled while it runs.
+///
+/// Like iterative_deepening, the first iteration always completes, so stopping the search
+/// right away still gives a move.
+pub struct SearchHandle {
+    stop: Arc<AtomicBool>,
+    progress: Arc<Mutex<Option<Iteration>>>,
+    infos: mpsc::Receiver<SearchInfo>,
+    thread: JoinHandle<Option<Iteration>>
+}
+
+impl SearchHandle {
+    /// Starts searching the position with the given limits. The stop flag of the limits is
+    /// used if it's given, so a flag can be shared by several searches.
+    pub fn spawn(position: Position, limits: Limits) -> SearchHandle {
+        return SearchHandle::start(position, limits, None);
+    }
+
+    /// Like spawn, but the search uses the given transposition table. The table is dropped
+    /// when the search ends.
+    pub fn spawn_with_table(position: Position, limits: Limits, tt: TranspositionTable) -> SearchHandle {
+        return SearchHandle::start(position, limits, Some(tt));
+    }
+
+    fn start(position: Position, mut limits: Limits, mut tt: Option<TranspositionTable>) -> SearchHandle {
+        let stop = limits.stop.get_or_insert_with(|| Arc::new(AtomicBool::new(false))).clone();
+        let progress = Arc::new(Mutex::new(None));
+        let shared = progress.clone();
+        let (sender, infos) = mpsc::channel();
+
+        let thread = thread::spawn(move || {
+            let report = |iteration: &Iteration| *shared.lock().unwrap() = Some(iteration.clone());
+
+            // The handle may have been dropped without waiting for the result.
+            return deepen(&position, &limits, tt.as_mut(), report, &mut |info| { let _ = sender.send(info.clone()); });
+        });
+
+        return SearchHandle { stop, progress, infos, thread };
+    }
+
+    /// Asks the search to stop. The search ends shortly after; the result is still fetched
+    /// with await_result.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns the last completed iteration or None if the first one is still running.
+    pub fn progress(&self) -> Option<Iteration> {
+        return self.progress.lock().unwrap().clone();
+    }
+
+    /// Returns the progress reports sent since the last call, oldest first. They are sent like
+    /// those of iterative_deepening_with_info.
+    pub fn infos(&self) -> Vec<SearchInfo> {
+        return self.infos.try_iter().collect();
+    }
+
+    /// Checks if the search has ended, so await_result won't block.
+    pub fn is_finished(&self) -> bool {
+        return self.thread.is_finished();
+    }
+
+    /// Waits for the search to end and returns the last completed iteration. Returns None if
+    /// there are no legal moves.
+    pub fn await_result(self) -> Option<Iteration> {
+        return self.thread.join().expect("the search thread panicked");
+    }
+}
+
+/// The engine playing the moves found by the search at a fixed depth. This is the engine
+/// core shared by the protocol front-ends and the match runner.
+pub struct SearchEngine {
+    pub depth: u8,
+    /// The time to think about a move. The engine searches deeper and deeper until the time
+    /// is up or the depth is reached.
+    pub movetime: Option<Duration>,
+    /// Remembers searched positions across moves. It's cleared when a new game starts.
+    pub tt: TranspositionTable,
+    /// Setting the flag from another thread stops the running search, which then returns the
+    /// best move found so far. Whoever starts a search has to clear it.
+    pub stop: Arc<AtomicBool>,
+    /// Weakens the engine's play if given.
+    pub strength: Option<Strength>,
+    /// Chooses among the best moves of a weakened engine.
+    rng: Rng
+}
+
+impl SearchEngine {
+    pub fn new(depth: u8) -> SearchEngine {
+        return SearchEngine {
+            depth, movetime: None, tt: TranspositionTable::default(), stop: Arc::new(AtomicBool::new(false)), strength: None, rng: Rng::from_time()
+        };
+    }
+
+    /// Replaces the transposition table, e.g. to change its size or replacement scheme.
+    pub fn with_table(mut self, tt: TranspositionTable) -> SearchEngine {
+        self.tt = tt;
+        return self;
+    }
+
+    /// Limits the time the engine thinks about each move.
+    pub fn with_movetime(mut self, movetime: Duration) -> SearchEngine {
+        self.movetime = Some(movetime);
+        return self;
+    }
+
+    /// Weakens the engine's play, with the given seed for its random choices.
+    pub fn with_strength(mut self, strength: Strength, seed: u64) -> SearchEngine {
+        self.strength = Some(strength);
+        self.rng = Rng::new(seed);
+        return self;
+    }
+
+    fn limits(&self) -> Limits {
+        return Limits {
+            depth: Some(self.depth), time: self.movetime, stop: Some(self.stop.clone()),
+            null_move: true, futility: true, probcut: true, ..Limits::default()
+        };
+    }
+}
+
+impl Engine for SearchEngine {
+    fn name(&self) -> String {
+        return build_info::engine_name();
+    }
+
+    fn version(&self) -> Option<String> {
+        return Some(format!("{} ({})", build_info::VERSION, build_info::GIT_COMMIT));
+    }
+
+    fn options(&self) -> Vec<(String, String)> {
+        let mut options = vec![(String::from("Depth"), self.depth.to_string()), (String::from("HashSlots"), self.tt.slots().to_string())];
+
+        if let Some(movetime) = self.movetime {
+            options.push((String::from("MoveTime"), movetime.as_millis().to_string()));
+        }
+
+        if let Some(strength) = self.strength {
+            options.push((String::from("Skill Level"), strength.skill().to_string()));
+        }
+
+        return options;
+    }
+
+    fn new_game(&mut self) {
+        self.tt.clear();
+    }
+
+    fn stop_flag(&self) -> Option<Arc<AtomicBool>> {
+        return Some(self.stop.clone());
+    }
+
+    fn choose_move(&mut self, game: &Game) -> Option<EngineMove> {
+        return self.choose_move_within(game, &SearchLimits::default());
+    }
+
+    fn choose_move_within(&mut self, game: &Game, limits: &SearchLimits) -> Option<EngineMove> {
+        return self.choose_move_reporting(game, limits, &mut |_| ());
+    }
+
+    fn set_strength(&mut self, strength: Option<Strength>) -> bool {
+        self.strength = strength;
+        return true;
+    }
+
+    fn choose_move_reporting(&mut self, game: &Game, limits: &SearchLimits, on_info: &mut dyn FnMut(&SearchInfo)) -> Option<EngineMove> {
+        let mut limits = limits.apply(game.position().turn, self.limits());
+
+        // A weakened engine searches its best moves no deeper than its strength allows and
+        // picks one of them at random.
+        if let Some(strength) = self.strength {
+            limits.depth = Some(limits.depth.map_or(strength.depth(), |depth| depth.min(strength.depth())));
+
+            let lines = multi_pv(game.position(), &limits, &mut self.tt, strength.candidates());
+            let chosen = strength.choose(&lines, &mut self.rng)?;
+
+            return Some(EngineMove { m: chosen.best, score: Some(chosen.score) });
+        }
+
+        let iteration = iterative_deepening_with_info(game.position(), &limits, &mut self.tt, on_info)?;
+
+        return Some(EngineMove { m: iteration.best, score: Some(iteration.score) });
+    }
+
+    fn choose_lines(&mut self, game: &Game, count: usize, limits: &SearchLimits) -> Vec<EngineLine> {
+        let limits = limits.apply(game.position().turn, self.limits());
+
+        return multi_pv(game.position(), &limits, &mut self.tt, count).into_iter()
+            .map(|iteration| EngineLine { pv: iteration.pv, score: Some(iteration.score) })
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::{WHITE, BLACK};
+    use crate::tt::Replacement;
+
+    fn position(fen: &str) -> Position {
+        return Position::from_fen(fen.to_string()).unwrap();
+    }
+
+    /// Plain negamax without pruning to compare the scores with. It extends checks like the
+    /// search.
+    fn minimax(position: &Position, depth: u8, ply: i32) -> i32 {
+        let depth = if position.is_checked(position.turn) { depth + 1 } else { depth };
+
+        if depth == 0 {
+            return quiescence(position, ply, -INFINITY, INFINITY);
+        }
+
+        let moves = position.clone().get_all_legal_moves();
+
+        if moves.is_empty() {
+            return if position.is_checked(position.turn) { -MATE_SCORE + ply } else { 0 };
+        }
+
+        return moves.into_iter().map(|m| -minimax(&position.make_move(m), depth - 1, ply + 1)).max().unwrap();
+    }
+
+    #[test]
+    fn pruning_keeps_the_scores() {
+        // The reference searches every leaf with a full window, so it's slow in busy positions.
+        for (fen, max_depth) in [("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1", 2),
+                                 ("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", 3),
+                                 ("r5k1/5ppp/2p5/8/8/8/8/3QK3 w - - 0 1", 3)] {
+            let pos = position(fen);
+
+            for depth in 1..=max_depth {
+                assert_eq!(negamax(&pos, depth, 0), minimax(&pos, depth, 0), "{} at depth {}", fen, depth);
+                assert_eq!(search(&pos, depth).map(|(_, score)| score), Some(minimax(&pos, depth, 0)));
+            }
+        }
+    }
+
+    #[test]
+    fn killer_moves_are_tried_after_captures() {
+        let pos = position("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1");
+        let (capture, killer, other) = (pos.uci_to_move("d2d5").unwrap(), pos.uci_to_move("e1e2").unwrap(), pos.uci_to_move("e1f1").unwrap());
+        let mut searcher = Searcher::new(None, None, false);
+
+        searcher.store_killer(2, other);
+        searcher.store_killer(2, killer);
+        searcher.store_killer(2, killer);
+
+        assert_eq!(searcher.stack[2].killers, [Some(killer), Some(other)]);
+        assert_eq!(searcher.ordered_moves(&pos, 2, None)[..3], [capture, killer, other]);
+        assert_eq!(searcher.ordered_moves(&pos, 2, Some(other))[..3], [other, capture, killer]);
+        assert_eq!(searcher.ordered_moves(&pos, 1, None), ordered_moves(&pos));
+
+        // A search remembers the quiet moves refuting the opponent's moves.
+        let mut searcher = Searcher::new(None, None, false);
+        searcher.alpha_beta(&position("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1"), 3, 0, -INFINITY, INFINITY);
+
+        assert!(searcher.stack.iter().any(|frame| frame.killers[0].is_some()));
+    }
+
+    #[test]
+    fn history_orders_quiet_moves() {
+        let pos = position("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1");
+        let [capture, killer, good, better] = ["d2d5", "e1e2", "d2h2", "d2a2"].map(|m| pos.uci_to_move(m).unwrap());
+        let mut searcher = Searcher::new(None, None, false);
+
+        searcher.store_killer(0, killer);
+        searcher.history.update(WHITE, good, 2);
+        searcher.history.update(WHITE, better, 3);
+        searcher.history.update(BLACK, killer, 4);
+
+        assert_eq!(searcher.ordered_moves(&pos, 0, None)[..4], [capture, killer, better, good]);
+
+        searcher.history.update(WHITE, good, 4);
+        assert_eq!(searcher.history.score(WHITE, good), 20);
+        assert_eq!(searcher.ordered_moves(&pos, 0, None)[..4], [capture, killer, good, better]);
+
+        // Older cutoffs count less.
+        searcher.history.decay();
+        assert_eq!(searcher.history.score(WHITE, good), 10);
+        assert_eq!(searcher.history.score(WHITE, Move::ShortCastle), 0);
+    }
+
+    #[test]
+    fn null_move_pruning_searches_fewer_nodes() {
+        let pos = position("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1");
+        let exact = Limits { depth: Some(4), ..Limits::default() };
+        let pruned = Limits { null_move: true, ..exact.clone() };
+
+        let full = iterative_deepening(&pos, &exact, |_| ()).unwrap();
+        let fast = iterative_deepening(&pos, &pruned, |_| ()).unwrap();
+
+        assert!(fast.nodes < full.nodes, "{} nodes with and {} without null moves", fast.nodes, full.nodes);
+
+        // Mates are still found.
+        let pos = position("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        let mate = iterative_deepening(&pos, &Limits { depth: Some(4), null_move: true, ..Limits::default() }, |_| ()).unwrap();
+
+        assert_eq!(pos.move_to_san(mate.best), "Ra8#");
+        assert_eq!(mate.score, MATE_SCORE - 1);
+    }
+
+    #[test]
+    fn futility_pruning_searches_fewer_nodes() {
+        let pos = position("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP2BPPP/R2QKB1R w KQ - 0 1");
+        let exact = Limits { depth: Some(4), ..Limits::default() };
+        let pruned = Limits { futility: true, ..exact.clone() };
+
+        let full = iterative_deepening(&pos, &exact, |_| ()).unwrap();
+        let fast = iterative_deepening(&pos, &pruned, |_| ()).unwrap();
+
+        assert!(fast.nodes < full.nodes, "{} nodes with and {} without futility pruning", fast.nodes, full.nodes);
+
+        // Mates are still found when far behind.
+        let pos = position("6k1/5ppp/8/7q/8/8/5PPP/R5K1 w - - 0 1");
+        let mate = iterative_deepening(&pos, &Limits { depth: Some(3), futility: true, ..Limits::default() }, |_| ()).unwrap();
+
+        assert_eq!(pos.move_to_san(mate.best), "Ra8#");
+        assert_eq!(mate.score, MATE_SCORE - 1);
+    }
+
+    #[test]
+    fn node_budgets_bound_all_iterations() {
+        let pos = position("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP2BPPP/R2QKB1R w KQ - 0 1");
+
+        for budget in [1, 50, 5_000, 50_000] {
+            let mut nodes = 0;
+            let limits = Limits { depth: Some(10), nodes: Some(budget), ..Limits::default() };
+            let last = iterative_deepening(&pos, &limits, |iteration| nodes += iteration.nodes).unwrap();
+
+            assert!(nodes <= budget, "{} nodes with a budget of {}", nodes, budget);
+            assert!(pos.clone().get_all_legal_moves().contains(&last.best));
+        }
+    }
+
+    #[test]
+    fn search_limits_share_the_clock() {
+        let limits = SearchLimits { wtime: Some(Duration::from_secs(60)), winc: Some(Duration::from_secs(1)), btime: Some(Duration::from_millis(100)), binc: Some(Duration::from_secs(10)), ..SearchLimits::default() };
+
+        assert_eq!(limits.time_for(WHITE), Some(Duration::from_millis(59_950) / 30 + Duration::from_millis(750)));
+        assert_eq!(limits.time_for(BLACK), Some(Duration::from_millis(50)));
+        assert_eq!(SearchLimits { movestogo: Some(1), ..limits.clone() }.time_for(WHITE), Some(Duration::from_millis(59_950)));
+        assert_eq!(SearchLimits { movetime: Some(Duration::from_millis(5)), ..limits }.time_for(BLACK), Some(Duration::from_millis(5)));
+        assert_eq!(SearchLimits::depth(3).time_for(WHITE), None);
+    }
+
+    #[test]
+    fn hard_limits_leave_room_for_unstable_moves() {
+        let limits = SearchLimits { wtime: Some(Duration::from_secs(60)), btime: Some(Duration::from_millis(3050)), movestogo: Some(4), ..SearchLimits::default() };
+
+        // Four times the share, or a third of the clock if that is less, but never less than the share.
+        assert_eq!(limits.hard_time_for(WHITE), Some(Duration::from_millis(59_950) / 3));
+        assert_eq!(SearchLimits { movestogo: Some(30), ..limits.clone() }.hard_time_for(WHITE), Some(Duration::from_millis(59_950) / 30 * 4));
+        assert_eq!(SearchLimits { movestogo: Some(1), ..limits.clone() }.hard_time_for(BLACK), Some(Duration::from_secs(3)));
+        assert_eq!(SearchLimits { movetime: Some(Duration::from_millis(5)), ..limits }.hard_time_for(WHITE), Some(Duration::from_millis(5)));
+        assert_eq!(SearchLimits::depth(3).hard_time_for(WHITE), None);
+    }
+
+    #[test]
+    fn stable_best_moves_end_the_search_early() {
+        let pos = Position::starting_position();
+        let moves = pos.clone().get_all_legal_moves();
+        let second = Duration::from_secs(1);
+        let mut clock = TimeManager::new(Some(second));
+
+        assert!(!clock.should_stop(Duration::ZERO) && !clock.should_stop(second * 3 / 2));
+
+        clock.update(moves[0]);
+        assert!(!clock.should_stop(second * 3 / 4) && clock.should_stop(second));
+
+        clock.update(moves[0]);
+        clock.update(moves[0]);
+        assert!(clock.should_stop(second / 2));
+
+        // A new best move gets more time.
+        clock.update(moves[1]);
+        assert!(!clock.should_stop(second * 3 / 2) && clock.should_stop(second * 2));
+        assert!(!TimeManager::new(None).should_stop(second * 1000));
+    }
+
+    #[test]
+    fn search_limits_replace_the_defaults_of_the_driver() {
+        let defaults = Limits { depth: Some(6), nodes: Some(1000), null_move: true, ..Limits::default() };
+
+        let limits = SearchLimits::default().apply(WHITE, defaults.clone());
+        assert_eq!((limits.depth, limits.nodes), (Some(6), Some(1000)));
+
+        let limits = SearchLimits::depth(2).apply(WHITE, defaults.clone());
+        assert_eq!((limits.depth, limits.nodes, limits.null_move), (Some(2), None, true));
+
+        let limits = SearchLimits { infinite: true, ..SearchLimits::depth(2) }.apply(WHITE, defaults.clone());
+        assert_eq!((limits.depth, limits.time, limits.nodes), (None, None, None));
+
+        let clocks = SearchLimits { wtime: Some(Duration::from_secs(30)), btime: Some(Duration::from_secs(30)), ..SearchLimits::default() };
+        let limits = clocks.apply(WHITE, defaults.clone());
+        assert_eq!((limits.soft_time, limits.time), (Some(Duration::from_millis(29_950) / 30), Some(Duration::from_millis(29_950) / 30 * 4)));
+
+        let limits = SearchLimits { movetime: Some(Duration::from_secs(1)), ..clocks }.apply(WHITE, defaults);
+        assert_eq!((limits.soft_time, limits.time), (None, Some(Duration::from_secs(1))));
+
+        // The engine searches as deep as it's asked to.
+        let game = Game::from_position(position("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1"));
+        let mut engine = SearchEngine::new(1);
+
+        assert_eq!(engine.choose_move_within(&game, &SearchLimits::depth(2)).map(|em| em.score), Some(Some(MATE_SCORE - 1)));
+    }
+
+    #[test]
+    fn memory_budgets_limit_the_table_and_the_depth() {
+        let pos = position("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        let mut tt = TranspositionTable::new(1, Replacement::Always);
+        let limits = Limits { depth: Some(3), memory: Some(tt.memory_usage() / 2), ..Limits::default() };
+
+        // The table doesn't fit, so it's left untouched.
+        let mate = iterative_deepening_with_table(&pos, &limits, &mut tt, |_| ()).unwrap();
+
+        assert_eq!(pos.move_to_san(mate.best), "Ra8#");
+        assert!(tt.probe(pos.hash_key()).is_none());
+
+        // With memory for a single ply every reply is evaluated statically.
+        let pos = position("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1");
+        let shallow = Limits { depth: Some(4), memory: Some(1), ..Limits::default() };
+
+        let best = pos.clone().get_all_legal_moves().into_iter().map(|m| -eval::evaluate(&pos.make_move(m))).max();
+
+        assert_eq!(iterative_deepening(&pos, &shallow, |_| ()).map(|iteration| iteration.score), best);
+    }
+
+    #[test]
+    fn internal_iterative_deepening_orders_fresh_positions() {
+        // Without iterative deepening at the root, no position has a move in the table.
+        let pos = position("2r3k1/pp3ppp/2n1b3/3p4/3P4/2N1B3/PP3PPP/2R3K1 w - - 0 1");
+        let mut plain = Searcher::new(None, None, false);
+        let expected = plain.root(&pos, 5, None, -INFINITY, INFINITY);
+
+        let mut tt = TranspositionTable::new(1, Replacement::DepthPreferred);
+        let mut searcher = Searcher::new(None, None, false);
+        searcher.tt = Some(&mut tt);
+
+        assert_eq!(searcher.root(&pos, 5, None, -INFINITY, INFINITY).map(|(_, score)| score), expected.map(|(_, score)| score));
+        assert!(searcher.nodes < plain.nodes, "{} nodes with and {} without the table", searcher.nodes, plain.nodes);
+    }
+
+    #[test]
+    fn probcut_searches_fewer_nodes() {
+        let pos = position("r1bq1rk1/ppp2ppp/2np1n2/2b1p3/2B1P3/2PP1N2/PP3PPP/RNBQ1RK1 w - - 0 7");
+        let exact = Limits { depth: Some(6), null_move: true, futility: true, ..Limits::default() };
+        let pruned = Limits { probcut: true, ..exact.clone() };
+
+        let full = iterative_deepening(&pos, &exact, |_| ()).unwrap();
+        let fast = iterative_deepening(&pos, &pruned, |_| ()).unwrap();
+
+        assert!(fast.nodes < full.nodes, "{} nodes with and {} without ProbCut", fast.nodes, full.nodes);
+        assert_eq!((fast.best, fast.score), (full.best, full.score));
+    }
+
+    #[test]
+    fn scores_distinguish_mates_from_centipawns() {
+        assert_eq!(Score::new(25), Score::Centipawns(25));
+        assert_eq!(Score::new(MATE_SCORE - 1), Score::Mate(1));
+        assert_eq!(Score::new(MATE_SCORE - 5), Score::Mate(3));
+        assert_eq!(Score::new(-MATE_SCORE + 4), Score::Mate(-2));
+
+        for score in [-120, MATE_SCORE - 1, MATE_SCORE - 5, -MATE_SCORE, -MATE_SCORE + 4] {
+            assert_eq!(Score::new(score).value(), score);
+        }
+
+        assert_eq!(Score::Mate(3).to_string(), "mate 3");
+        assert_eq!(Score::Mate(-2).to_pawns(), "#-2");
+        assert_eq!(Score::Centipawns(-35).to_string(), "cp -35");
+        assert_eq!(Score::Centipawns(125).to_pawns(), "+1.25");
+    }
+
+    #[test]
+    fn mate_distance_pruning_keeps_the_shortest_mate() {
+        // Ra8 mates right away, other moves mate later at best.
+        let pos = position("6k1/5ppp/8/8/8/8/1R6/R5K1 w - - 0 1");
+        let mate = iterative_deepening(&pos, &Limits { depth: Some(4), ..Limits::default() }, |_| ()).unwrap();
+
+        assert_eq!(Score::new(mate.score), Score::Mate(1));
+        assert_eq!(pos.move_to_san(mate.best), "Ra8#");
+
+        // Once a mate in one is found, positions three plies deep can't beat it.
+        let mut searcher = Searcher::new(None, None, false);
+
+        assert_eq!(searcher.alpha_beta(&pos, 5, 3, MATE_SCORE - 1, INFINITY), MATE_SCORE - 1);
+        assert_eq!(searcher.nodes, 1);
+    }
+
+    #[test]
+    fn multi_pv_searches_distinct_lines() {
+        let pos = position("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1");
+        let limits = Limits { depth: Some(3), ..Limits::default() };
+        let mut tt = TranspositionTable::new(1, Replacement::DepthPreferred);
+        let lines = multi_pv(&pos, &limits, &mut tt, 3);
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines.windows(2).all(|w| w[0].score >= w[1].score && w[0].best != w[1].best));
+        assert_eq!(lines[0].score, iterative_deepening(&pos, &limits, |_| ()).unwrap().score);
+
+        for iteration in &lines {
+            assert_eq!(iteration.pv[0], iteration.best);
+        }
+
+        // There are only as many lines as legal moves.
+        let pos = position("7k/8/8/8/8/8/8/K7 w - - 0 1");
+        assert_eq!(multi_pv(&pos, &limits, &mut tt, 10).len(), 3);
+    }
+
+    /// The settings the engine plays with, so the tests below catch regressions of the pruning.
+    fn engine_limits(nodes: u64) -> Limits {
+        return Limits { nodes: Some(nodes), null_move: true, futility: true, probcut: true, ..Limits::default() };
+    }
+
+    #[test]
+    fn finds_forced_mates_within_a_node_budget() {
+        let suite = [("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1", 2),
+                     ("k7/8/2K5/8/8/8/8/6Q1 w - - 0 1", 2),
+                     ("4k3/8/3K4/8/8/8/8/7R w - - 0 1", 2),
+                     ("r5rk/5p1p/5R2/4B3/8/8/7P/7K w - - 0 1", 3),
+                     ("r1b3kr/ppp1Bp1p/1b6/n2P4/2p3q1/2Q2N2/P4PPP/RN2R1K1 w - - 0 1", 3),
+                     ("r6k/6pp/8/6N1/2Q5/8/8/6K1 w - - 0 1", 4)];
+
+        for (fen, moves) in suite {
+            let pos = position(fen);
+            let mut tt = TranspositionTable::new(1, Replacement::DepthPreferred);
+            let mate = iterative_deepening_with_table(&pos, &engine_limits(200_000), &mut tt, |_| ()).unwrap();
+
+            assert_eq!(Score::new(mate.score), Score::Mate(moves), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn wins_basic_endgames_within_a_node_budget() {
+        for fen in ["k7/8/2K5/8/8/8/8/6Q1 w - - 0 1", "3k4/8/4K3/8/8/8/8/7R w - - 0 1", "8/8/8/8/8/3k4/8/R3K3 w - - 0 1"] {
+            let mut game = Game::from_position(position(fen));
+            let mut engine = SearchEngine::new(1);
+            let limits = SearchLimits { nodes: Some(60_000), ..SearchLimits::default() };
+
+            while let Some(em) = engine.choose_move_within(&game, &limits) {
+                assert!(game.moves().len() < 40, "no mate in 20 moves from {}", fen);
+                game.play(em.m);
+            }
+
+            assert!(game.position().is_checked(BLACK), "stalemate in {}", game.position().to_fen());
+        }
+    }
+
+    #[test]
+    fn searchmoves_restrict_the_root_moves() {
+        let pos = position("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        let [mate, king, rook] = ["a1a8", "g1f1", "a1a2"].map(|m| pos.uci_to_move(m).unwrap());
+        let limits = Limits { depth: Some(3), searchmoves: vec![king, rook], ..Limits::default() };
+
+        let best = iterative_deepening(&pos, &limits, |_| ()).unwrap();
+
+        assert!(best.best == king || best.best == rook);
+        assert!(best.score < MATE_BOUND);
+        assert!(!limits.allows(&mate));
+
+        // The excluded moves are left out of the searchmoves, as MultiPV does.
+        let mut tt = TranspositionTable::new(1, Replacement::Always);
+        let lines = multi_pv(&pos, &Limits { depth: Some(2), searchmoves: vec![mate, king], ..Limits::default() }, &mut tt, 3);
+
+        assert_eq!(lines.iter().map(|iteration| iteration.best).collect::<Vec<Move>>(), vec![mate, king]);
+
+        // Budgets that run out in the first iteration still return one of the searchmoves.
+        let starved = Limits { nodes: Some(1), ..limits };
+        assert!(iterative_deepening(&pos, &starved, |_| ()).is_some_and(|iteration| iteration.best == king || iteration.best == rook));
+    }
+
+    #[test]
+    fn forced_replies_check_an_idea() {
+        // Qh5 only mates if black defends e5 with Nf6.
+        let pos = position("r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/8/PPPP1PPP/RNBQK1NR w KQkq - 2 3");
+        let queen = pos.uci_to_move("d1h5").unwrap();
+        let limits = Limits { depth: Some(3), replies: vec![String::from("g8f6")], ..Limits::default() };
+
+        assert_eq!(evaluate_moves(&pos, 3, &limits)[0], (queen, MATE_SCORE - 3));
+        assert!(evaluate_moves(&pos, 3, &Limits::default())[0].1 < MATE_BOUND);
+        assert!(iterative_deepening(&pos, &limits, |_| ()).is_some_and(|iteration| iteration.best == queen && iteration.score == MATE_SCORE - 3));
+
+        // The table doesn't keep the forced scores for later searches.
+        let mut tt = TranspositionTable::new(1, Replacement::Always);
+        iterative_deepening_with_table(&pos, &limits, &mut tt, |_| ());
+        assert!(iterative_deepening_with_table(&pos, &Limits { depth: Some(3), ..Limits::default() }, &mut tt, |_| ()).unwrap().score < MATE_BOUND);
+
+        let excluded = Limits { excluded: vec![queen], ..limits };
+        assert!(evaluate_moves(&pos, 3, &excluded).iter().all(|(m, score)| *m != queen && *score < MATE_BOUND));
+    }
+
+    #[test]
+    fn aspiration_windows_widen_until_the_score_fits() {
+        let pos = position("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1");
+        let exact = search(&pos, 3).unwrap();
+
+        for guess in [None, Some(exact.1), Some(exact.1 - 200), Some(exact.1 + 1000), Some(MATE_SCORE - 1)] {
+            let mut searcher = Searcher::new(None, None, false);
+
+            assert_eq!(searcher.aspiration(&pos, 3, None, guess), Some(exact), "guess {:?}", guess);
+        }
+
+        // A narrow window fails low or high with a bound on the score.
+        let mut searcher = Searcher::new(None, None, false);
+        let (_, low) = searcher.root(&pos, 3, None, exact.1 + 100, exact.1 + 200).unwrap();
+        let (_, high) = searcher.root(&pos, 3, None, exact.1 - 200, exact.1 - 100).unwrap();
+
+        assert!(low <= exact.1 + 100 && high >= exact.1 - 100);
+
+        let pos = position("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1");
+        let last = iterative_deepening(&pos, &Limits { depth: Some(ASPIRATION_DEPTH), ..Limits::default() }, |_| ()).unwrap();
+
+        assert_eq!(last.score, search(&pos, ASPIRATION_DEPTH).unwrap().1);
+    }
+
+    #[test]
+    fn check_extensions_find_mates_behind_the_horizon() {
+        // Rc8+ Ka7/Kb7 R2c7# needs three plies, but both white moves are checks.
+        let pos = position("k7/8/8/K7/1pR2p2/p7/2R5/8 w - - 0 1");
+        let (m, score) = search(&pos, 2).unwrap();
+
+        assert_eq!(pos.move_to_san(m), "Rc8+");
+        assert_eq!(score, MATE_SCORE - 3);
+    }
+
+    #[test]
+    fn finds_the_best_move() {
+        let pos = position("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        let (m, score) = search(&pos, 2).unwrap();
+
+        assert_eq!(pos.move_to_san(m), "Ra8#");
+        assert_eq!(score, MATE_SCORE - 1);
+
+        let pos = position("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1");
+
+        assert_eq!(search(&pos, 1).map(|(m, _)| pos.move_to_san(m)), Some(String::from("Rxd5")));
+    }
+
+    #[test]
+    fn iterative_deepening_reports_each_iteration() {
+        let pos = position("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1");
+        let limits = Limits { depth: Some(3), ..Limits::default() };
+        let mut depths = Vec::new();
+
+        let last = iterative_deepening(&pos, &limits, |it| depths.push(it.depth)).unwrap();
+
+        assert_eq!(depths, vec![1, 2, 3]);
+        assert_eq!(last.score, search(&pos, 3).unwrap().1);
+    }
+
+    #[test]
+    fn search_infos_report_each_iteration_and_long_ones() {
+        let pos = position("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1");
+        let limits = Limits { depth: Some(4), ..Limits::default() };
+        let mut tt = TranspositionTable::new(1, Replacement::DepthPreferred);
+        let mut infos = Vec::new();
+
+        let last = iterative_deepening_with_info(&pos, &limits, &mut tt, |info| infos.push(info.clone())).unwrap();
+
+        assert_eq!(infos.iter().map(|info| info.depth).collect::<Vec<u8>>(), vec![1, 2, 3, 4]);
+        assert!(infos.windows(2).all(|w| w[0].nodes < w[1].nodes && w[0].time <= w[1].time));
+
+        let info = infos.last().unwrap();
+
+        assert_eq!((info.pv[0], info.score), (last.best, Score::new(last.score)));
+        assert!(info.seldepth >= 4 && info.pv.len() <= 4);
+        assert!(info.to_uci(&pos).starts_with(&format!("depth 4 seldepth {} score {} nodes {}", info.seldepth, info.score, info.nodes)));
+
+        // Long iterations repeat the last completed one with the counters so far.
+        let mut reported = Vec::new();
+        let mut on_info = |info: &SearchInfo| reported.push(info.clone());
+        let mut searcher = Searcher::new(None, None, false);
+        let start = Instant::now() - INFO_INTERVAL;
+
+        searcher.on_info = Some(&mut on_info);
+        searcher.progress = Some(Progress { start, reported: start, depth: 5, nodes: 100, score: 20, pv: vec![last.best] });
+        searcher.root(&pos, 3, None, -INFINITY, INFINITY);
+        drop(searcher);
+
+        assert_eq!(reported.len(), 1);
+        assert_eq!((reported[0].depth, reported[0].score, reported[0].nodes), (5, Score::Centipawns(20), 100 + CHECK_INTERVAL));
+    }
+
+    #[test]
+    fn iterations_carry_the_principal_variation() {
+        let pos = position("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1");
+
+        for depth in 1..=4 {
+            let iteration = iterative_deepening(&pos, &Limits { depth: Some(depth), ..Limits::default() }, |_| ()).unwrap();
+            let mut current = pos.clone();
+
+            // Without a table or pruning, the line reaches the full depth.
+            assert_eq!(iteration.pv.len(), depth as usize);
+            assert_eq!(iteration.pv[0], iteration.best);
+
+            for &m in &iteration.pv {
+                assert!(current.clone().get_all_legal_moves().contains(&m));
+                current = current.make_move(m);
+            }
+        }
+
+        // Mating lines end with the mate.
+        let pos = position("r5rk/5p1p/5R2/4B3/8/8/7P/7K w - - 0 1");
+        let mate = iterative_deepening(&pos, &Limits { depth: Some(5), ..Limits::default() }, |_| ()).unwrap();
+        let sans: Vec<String> = mate.pv.iter().scan(pos.clone(), |current, &m| {
+            let san = current.move_to_san(m);
+            *current = current.make_move(m);
+            return Some(san);
+        }).collect();
+
+        assert_eq!(sans.len(), 5);
+        assert!(sans[0] == "Ra6+" && sans[4].ends_with('#'), "{:?}", sans);
+    }
+
+    #[test]
+    fn iterative_deepening_stops_at_mate() {
+        let pos = position("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        let last = iterative_deepening(&pos, &Limits { depth: Some(5), ..Limits::default() }, |_| ()).unwrap();
+
+        // The quiescence search already detects the mate after the mating move.
+        assert_eq!((last.depth, pos.move_to_san(last.best)), (1, String::from("Ra8#")));
+    }
+
+    #[test]
+    fn interrupted_search_returns_the_last_completed_iteration() {
+        let pos = position("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1");
+        let stop = Arc::new(AtomicBool::new(true));
+        let limits = Limits { stop: Some(stop), ..Limits::default() };
+
+        let last = iterative_deepening(&pos, &limits, |_| ()).unwrap();
+        assert_eq!(last.depth, 1);
+
+        let limits = Limits { time: Some(Duration::from_millis(50)), ..Limits::default() };
+        let last = iterative_deepening(&pos, &limits, |_| ()).unwrap();
+        assert!(last.depth < u8::MAX);
+    }
+
+    #[test]
+    fn search_handles_can_be_polled_and_stopped() {
+        let pos = position("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1");
+        let handle = SearchHandle::spawn(pos.clone(), Limits::default());
+
+        while handle.progress().is_none() {
+            thread::yield_now();
+        }
+
+        assert!(!handle.is_finished());
+
+        handle.stop();
+        assert!(!handle.infos().is_empty());
+
+        let last = handle.await_result().unwrap();
+        assert!(pos.clone().get_all_legal_moves().contains(&last.best));
+
+        // Finished searches give the same result as a blocking search.
+        let limits = Limits { depth: Some(3), ..Limits::default() };
+        let tt = || TranspositionTable::new(1, Replacement::Always);
+        let handle = SearchHandle::spawn_with_table(pos.clone(), limits.clone(), tt());
+
+        assert_eq!(handle.await_result(), iterative_deepening_with_table(&pos, &limits, &mut tt(), |_| ()));
+    }
+
+    #[test]
+    fn quiescence_resolves_exchanges() {
+        // The knight is defended by a pawn, so taking it loses the exchange.
+        let pos = position("4k3/2p5/3n4/8/8/8/3R4/4K3 w - - 0 1");
+
+        assert_eq!(eval::evaluate(&pos), 158);
+        assert_eq!(quiescence(&pos, 0, -INFINITY, INFINITY), 158);
+        assert_eq!(search(&pos, 1).map(|(m, _)| pos.move_to_san(m) == "Rxd6"), Some(false));
+
+        // Standing pat isn't possible in check and mates are found.
+        let pos = position("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1");
+        assert_eq!(quiescence(&pos, 3, -INFINITY, INFINITY), -MATE_SCORE + 3);
+    }
+
+    #[test]
+    fn quiescence_searches_checks_on_request() {
+        // Only the check Nf6+ wins the queen with a fork.
+        let pos = position("6k1/8/8/7q/4N3/8/8/6K1 w - - 0 1");
+
+        assert_eq!(Searcher::new(None, None, false).quiescence(&pos, 0, -INFINITY, INFINITY, 0), -661);
+        assert_eq!(Searcher::new(None, None, true).quiescence(&pos, 0, -INFINITY, INFINITY, 0), 233);
+    }
+
+    #[test]
+    fn transposition_table_saves_nodes() {
+        let pos = position("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1");
+        let limits = Limits { depth: Some(4), ..Limits::default() };
+        let mut tt = TranspositionTable::new(1, Replacement::DepthPreferred);
+
+        let without = iterative_deepening(&pos, &limits, |_| ()).unwrap();
+        let with = iterative_deepening_with_table(&pos, &limits, &mut tt, |_| ()).unwrap();
+
+        assert_eq!(with.score, without.score);
+        assert!(with.nodes < without.nodes);
+        assert!(tt.probe(pos.make_move(with.best).hash_key()).is_some());
+
+        // Searching again finds everything in the table.
+        let again = iterative_deepening_with_table(&pos, &limits, &mut tt, |_| ()).unwrap();
+        assert!(again.nodes < with.nodes);
+    }
+
+    #[test]
+    fn transposition_table_keeps_mate_distances() {
+        let pos = position("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        let mut tt = TranspositionTable::new(1, Replacement::Always);
+
+        for _ in 0..2 {
+            let last = iterative_deepening_with_table(&pos, &Limits { depth: Some(3), ..Limits::default() }, &mut tt, |_| ()).unwrap();
+            assert_eq!(last.score, MATE_SCORE - 1);
+        }
+
+        assert_eq!(score_from_table(score_to_table(MATE_SCORE - 5, 3), 1), MATE_SCORE - 3);
+    }
+
+    #[test]
+    fn no_move_without_legal_moves() {
+        let pos = position("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1");
+
+        assert_eq!(search(&pos, 2), None);
+        assert_eq!(negamax(&pos, 2, 0), -MATE_SCORE);
+        assert_eq!(iterative_deepening(&pos, &Limits::default(), |_| ()), None);
+    }
+}
@@ -0,0 +1,125 @@
+use std::fmt;
+
+use crate::game::Game;
+
+
+/// Legal move statistics of a game: how many moves each side could choose from over time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BranchingStats {
+    /// The number of legal moves in the position before each move of the game.
+    pub counts: Vec<usize>
+}
+
+impl BranchingStats {
+    /// Counts the legal moves in every position of the game's current line in which a move
+    /// was played.
+    pub fn new(game: &Game) -> BranchingStats {
+        let mut position = game.starting_position().clone();
+        let mut counts = Vec::new();
+
+        for &m in game.moves() {
+            counts.push(position.clone().get_all_legal_moves().len());
+            position = position.make_move(m);
+        }
+
+        return BranchingStats { counts };
+    }
+
+    /// Returns the average number of legal moves or None if no move was played.
+    pub fn mean(&self) -> Option<f64> {
+        if self.counts.is_empty() {
+            return None;
+        }
+
+        return Some(self.counts.iter().sum::<usize>() as f64 / self.counts.len() as f64);
+    }
+
+    /// Returns the smallest and the largest number of legal moves.
+    pub fn range(&self) -> Option<(usize, usize)> {
+        return Some((*self.counts.iter().min()?, *self.counts.iter().max()?));
+    }
+
+    /// Returns the forced sequences of the game as the index of their first ply and their
+    /// length. A sequence is forced if the side to move had only one legal move in each of
+    /// its plies.
+    pub fn forced_sequences(&self) -> Vec<(usize, usize)> {
+        let mut sequences: Vec<(usize, usize)> = Vec::new();
+
+        for (ply, &count) in self.counts.iter().enumerate() {
+            if count != 1 {
+                continue;
+            }
+
+            match sequences.last_mut() {
+                Some((start, len)) if *start + *len == ply => *len += 1,
+                _                                          => sequences.push((ply, 1))
+            }
+        }
+
+        return sequences;
+    }
+}
+
+impl fmt::Display for BranchingStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mean = self.mean().map_or(String::from("-"), |mean| format!("{:.1}", mean));
+        let range = self.range().map_or(String::from("-"), |(min, max)| format!("{}-{}", min, max));
+        let forced = self.forced_sequences().iter().map(|(start, len)| format!("{}+{}", start + 1, len)).collect::<Vec<String>>();
+
+        write!(f, "{} plies, mean branching factor {}, range {}", self.counts.len(), mean, range)?;
+
+        if !forced.is_empty() {
+            write!(f, ", forced sequences at plies {}", forced.join(" "))?;
+        }
+
+        return Ok(());
+    }
+}
+
+/// Computes the average number of legal moves at each ply over a set of games, i.e. the
+/// branching factor over time. Games only count for the plies they reached.
+pub fn mean_per_ply(stats: &[BranchingStats]) -> Vec<f64> {
+    let plies = stats.iter().map(|s| s.counts.len()).max().unwrap_or(0);
+
+    return (0..plies).map(|ply| {
+        let counts: Vec<usize> = stats.iter().filter_map(|s| s.counts.get(ply).copied()).collect();
+
+        counts.iter().sum::<usize>() as f64 / counts.len() as f64
+    }).collect();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn;
+
+    fn stats(pgn: &str) -> BranchingStats {
+        return BranchingStats::new(&pgn::read_games(pgn)[0].game);
+    }
+
+    #[test]
+    fn counts_legal_moves_before_each_move() {
+        let stats = stats("[FEN \"4k3/8/8/8/8/8/8/R3K3 w - - 0 1\"]\n\n1. Ra7 *");
+
+        assert_eq!(stats.counts, vec![15]);
+        assert_eq!(stats.range(), Some((15, 15)));
+        assert!((stats.mean().unwrap() - 15.0).abs() < 1e-9);
+        assert_eq!(BranchingStats { counts: Vec::new() }.mean(), None);
+    }
+
+    #[test]
+    fn finds_forced_sequences() {
+        let stats = BranchingStats { counts: vec![20, 1, 1, 5, 1, 30] };
+
+        assert_eq!(stats.forced_sequences(), vec![(1, 2), (4, 1)]);
+        assert_eq!(stats.to_string(), "6 plies, mean branching factor 9.7, range 1-30, forced sequences at plies 2+2 5+1");
+    }
+
+    #[test]
+    fn averages_over_games() {
+        let games = [BranchingStats { counts: vec![20, 20, 30] }, BranchingStats { counts: vec![20, 22] }];
+
+        assert_eq!(mean_per_ply(&games), vec![20.0, 21.0, 30.0]);
+    }
+}
@@ -0,0 +1,364 @@
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+use std::io;
+
+use std::fmt;
+
+use crate::game::Game;
+use crate::pgn::{self, PgnGame};
+use crate::position::{Position, WHITE};
+use crate::rating::Rating;
+
+
+/// A game in a storage together with the fields it can be found by.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StoredGame {
+    /// The number the storage gave the game when it was saved.
+    pub id: u64,
+    pub white: String,
+    pub black: String,
+    /// The date of the game as given by the PGN Date tag, e.g. 2024.03.17.
+    pub date: String,
+    pub result: String,
+    /// The whole game encoded as PGN.
+    pub pgn: String
+}
+
+impl StoredGame {
+    /// Reads the game back from its PGN.
+    pub fn game(&self) -> Option<PgnGame> {
+        return pgn::read_games(&self.pgn).pop();
+    }
+}
+
+/// The result of analyzing a position.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Analysis {
+    pub fen: String,
+    pub depth: u8,
+    /// The score from the point of view of the player to move.
+    pub score: i32,
+    /// The principal variation as moves in UCI notation.
+    pub pv: Vec<String>
+}
+
+/// A puzzle and how the user fared with it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PuzzleRecord {
+    pub fen: String,
+    /// The moves solving the puzzle in UCI notation.
+    pub solution: Vec<String>,
+    pub attempts: u32,
+    pub solved: u32
+}
+
+/// The first move of a game that leads to a position none of the games of a storage reached.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Novelty {
+    /// The number of moves of the game before the novelty.
+    pub ply: usize,
+    /// The move number as written in PGN, counting from 1 like the PGN writer.
+    pub number: usize,
+    pub white: bool,
+    /// The novelty in SAN.
+    pub san: String,
+    /// The number of games of the storage that reached the position before the novelty.
+    pub games: usize
+}
+
+impl fmt::Display for Novelty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let dots = if self.white { "." } else { "..." };
+        let games = if self.games == 1 { "game" } else { "games" };
+
+        return write!(f, "Novelty on move {}: {}{} {}, leaving {} known {}", self.number, self.number, dots, self.san, self.games, games);
+    }
+}
+
+/// Restricts the games returned by a storage. Games have to match all given criteria.
+#[derive(Clone, Default, Debug)]
+pub struct GameQuery {
+    /// A player who played either color.
+    pub player: Option<String>,
+    /// The first and last dates in the format of the PGN Date tag, both included.
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// The hash key of a position reached in the main line of the game.
+    pub position: Option<u64>,
+    /// The pawn key of a position reached in the main line of the game, i.e. the game had the
+    /// same pawn skeleton at some point, wherever the pieces stood.
+    pub pawns: Option<u64>
+}
+
+impl GameQuery {
+    /// Checks if the game with the given hash keys and pawn keys of its main line matches the
+    /// query.
+    pub fn matches(&self, game: &StoredGame, hashes: &[u64], pawn_keys: &[u64]) -> bool {
+        return self.player.as_ref().is_none_or(|player| game.white == *player || game.black == *player)
+            && self.from.as_ref().is_none_or(|from| game.date >= *from)
+            && self.to.as_ref().is_none_or(|to| game.date <= *to)
+            && self.position.is_none_or(|hash| hashes.contains(&hash))
+            && self.pawns.is_none_or(|key| pawn_keys.contains(&key));
+    }
+}
+
+/// Interface for the databases the command line, the user interfaces and the bots keep their
+/// games, analyses and puzzles in.
+pub trait Storage {
+    /// Saves a game and returns the id it can be found by.
+    fn save_game(&mut self, game: &PgnGame) -> io::Result<u64>;
+
+    /// Returns the games matching the query in the order they were saved.
+    fn games(&self, query: &GameQuery) -> io::Result<Vec<StoredGame>>;
+
+    /// Saves the analysis of a position. It replaces an earlier one unless that is deeper.
+    fn save_analysis(&mut self, analysis: &Analysis) -> io::Result<()>;
+
+    /// Returns the deepest analysis of the position with the given hash key.
+    fn analysis(&self, hash: u64) -> io::Result<Option<Analysis>>;
+
+    /// Saves a puzzle, replacing the record of the same position.
+    fn save_puzzle(&mut self, puzzle: &PuzzleRecord) -> io::Result<()>;
+
+    /// Returns the puzzle of the position with the given hash key.
+    fn puzzle(&self, hash: u64) -> io::Result<Option<PuzzleRecord>>;
+
+    /// Saves the rating of the player with the given name.
+    fn save_rating(&mut self, player: &str, rating: Rating) -> io::Result<()>;
+
+    /// Returns the rating of the player with the given name if the player was rated before.
+    fn rating(&self, player: &str) -> io::Result<Option<Rating>>;
+}
+
+/// Returns the fields a game is stored with except for its id.
+pub fn describe(game: &PgnGame) -> StoredGame {
+    let tag = |name| game.tag(name).unwrap_or("?").to_string();
+    let result = game.result.clone().unwrap_or_else(|| tag("Result"));
+
+    return StoredGame { id: 0, white: tag("White"), black: tag("Black"), date: tag("Date"), pgn: pgn::write_game(&game.game, &game.tags, &result), result };
+}
+
+/// Returns the hash keys of the positions of the main line of the game, starting with the
+/// starting position.
+pub fn main_line_hashes(game: &Game) -> Vec<u64> {
+    return main_line_keys(game, Position::hash_key);
+}
+
+/// Returns the pawn keys of the pawn structures of the main line of the game in the order they
+/// came up. Moves that don't change the pawns don't repeat the key.
+pub fn main_line_pawn_keys(game: &Game) -> Vec<u64> {
+    let mut keys = main_line_keys(game, Position::pawn_key);
+    keys.dedup();
+
+    return keys;
+}
+
+fn main_line_keys(game: &Game, key: fn(&Position) -> u64) -> Vec<u64> {
+    let mut position = game.starting_position().clone();
+    let mut keys = vec![key(&position)];
+
+    for m in game.main_line() {
+        position = position.make_move(m);
+        keys.push(key(&position));
+    }
+
+    return keys;
+}
+
+/// Finds the first move of the main line of the game leading out of the games of the storage,
+/// looking the positions up by their hash keys. Transpositions back into known positions
+/// don't count, the game is out of theory once it left it. Returns None if every position of
+/// the game was reached before, e.g. by the game itself.
+pub fn find_novelty(storage: &dyn Storage, game: &Game) -> io::Result<Option<Novelty>> {
+    let start = game.starting_position();
+    let offset = if start.turn == WHITE { 0 } else { 1 };
+    let games = |hash| storage.games(&GameQuery { position: Some(hash), ..GameQuery::default() }).map(|games| games.len());
+
+    let mut position = start.clone();
+    let mut known = games(position.hash_key())?;
+
+    for (ply, m) in game.main_line().into_iter().enumerate() {
+        let next = position.make_move(m);
+        let reached = games(next.hash_key())?;
+
+        if reached == 0 {
+            return Ok(Some(Novelty { ply, number: (ply + offset) / 2 + 1, white: position.turn == WHITE, san: position.move_to_san(m), games: known }));
+        }
+
+        position = next;
+        known = reached;
+    }
+
+    return Ok(None);
+}
+
+/// Returns the hash key of the position with the given FEN.
+pub(crate) fn fen_hash(fen: &str) -> io::Result<u64> {
+    return Position::from_fen(fen.to_string())
+        .map(|position| position.hash_key())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid FEN: {}", fen)));
+}
+
+/// A storage keeping everything in memory, e.g. for tests or sessions that don't need to
+/// outlive the process.
+#[derive(Default)]
+pub struct MemoryStorage {
+    games: Vec<(StoredGame, Vec<u64>, Vec<u64>)>,
+    analyses: Vec<(u64, Analysis)>,
+    puzzles: Vec<(u64, PuzzleRecord)>,
+    ratings: Vec<(String, Rating)>
+}
+
+impl MemoryStorage {
+    pub fn new() -> MemoryStorage {
+        return MemoryStorage::default();
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn save_game(&mut self, game: &PgnGame) -> io::Result<u64> {
+        let id = self.games.len() as u64 + 1;
+
+        self.games.push((StoredGame { id, ..describe(game) }, main_line_hashes(&game.game), main_line_pawn_keys(&game.game)));
+
+        return Ok(id);
+    }
+
+    fn games(&self, query: &GameQuery) -> io::Result<Vec<StoredGame>> {
+        return Ok(self.games.iter().filter(|(game, hashes, pawn_keys)| query.matches(game, hashes, pawn_keys)).map(|(game, _, _)| game.clone()).collect());
+    }
+
+    fn save_analysis(&mut self, analysis: &Analysis) -> io::Result<()> {
+        let hash = fen_hash(&analysis.fen)?;
+
+        match self.analyses.iter_mut().find(|(h, _)| *h == hash) {
+            Some((_, stored)) if stored.depth > analysis.depth => (),
+            Some((_, stored))                                  => *stored = analysis.clone(),
+            None                                               => self.analyses.push((hash, analysis.clone()))
+        }
+
+        return Ok(());
+    }
+
+    fn analysis(&self, hash: u64) -> io::Result<Option<Analysis>> {
+        return Ok(self.analyses.iter().find(|(h, _)| *h == hash).map(|(_, analysis)| analysis.clone()));
+    }
+
+    fn save_puzzle(&mut self, puzzle: &PuzzleRecord) -> io::Result<()> {
+        let hash = fen_hash(&puzzle.fen)?;
+
+        self.puzzles.retain(|(h, _)| *h != hash);
+        self.puzzles.push((hash, puzzle.clone()));
+
+        return Ok(());
+    }
+
+    fn puzzle(&self, hash: u64) -> io::Result<Option<PuzzleRecord>> {
+        return Ok(self.puzzles.iter().find(|(h, _)| *h == hash).map(|(_, puzzle)| puzzle.clone()));
+    }
+
+    fn save_rating(&mut self, player: &str, rating: Rating) -> io::Result<()> {
+        self.ratings.retain(|(name, _)| name != player);
+        self.ratings.push((player.to_string(), rating));
+
+        return Ok(());
+    }
+
+    fn rating(&self, player: &str) -> io::Result<Option<Rating>> {
+        return Ok(self.ratings.iter().find(|(name, _)| name == player).map(|(_, rating)| *rating));
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    const GAMES: &str = "[White \"Anna\"]\n[Black \"Ben\"]\n[Date \"2024.03.17\"]\n\n1. e4 e5 2. Nf3 Nc6 1-0\n\n\
+                         [White \"Ben\"]\n[Black \"Carl\"]\n[Date \"2024.05.02\"]\n\n1. d4 d5 1/2-1/2\n";
+
+    /// Checks the queries and records of a storage. Shared with the tests of the backends.
+    pub(crate) fn check_storage(storage: &mut dyn Storage) {
+        let games = pgn::read_games(GAMES);
+
+        assert_eq!(storage.save_game(&games[0]).unwrap(), 1);
+        assert_eq!(storage.save_game(&games[1]).unwrap(), 2);
+
+        let ids = |query: GameQuery| storage.games(&query).unwrap().iter().map(|game| game.id).collect::<Vec<u64>>();
+        let after_e4 = Position::starting_position().make_move(Position::starting_position().uci_to_move("e2e4").unwrap());
+
+        assert_eq!(ids(GameQuery::default()), vec![1, 2]);
+        assert_eq!(ids(GameQuery { player: Some(String::from("Ben")), ..GameQuery::default() }), vec![1, 2]);
+        assert_eq!(ids(GameQuery { player: Some(String::from("Carl")), ..GameQuery::default() }), vec![2]);
+        assert_eq!(ids(GameQuery { from: Some(String::from("2024.04.01")), ..GameQuery::default() }), vec![2]);
+        assert_eq!(ids(GameQuery { to: Some(String::from("2024.03.17")), ..GameQuery::default() }), vec![1]);
+        assert_eq!(ids(GameQuery { position: Some(after_e4.hash_key()), ..GameQuery::default() }), vec![1]);
+        assert_eq!(ids(GameQuery { player: Some(String::from("Anna")), position: Some(Position::starting_position().hash_key()), ..GameQuery::default() }), vec![1]);
+
+        // After 1. e4 e5 the pawns stand as after 1. e3 e6 2. e4 e5, but the pieces don't.
+        let pawns = |fen: &str| Some(Position::from_fen(fen.to_string()).unwrap().pawn_key());
+        assert_eq!(ids(GameQuery { pawns: pawns("rnbqk2r/pppp1ppp/5n2/2b1p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1"), ..GameQuery::default() }), vec![1]);
+        assert_eq!(ids(GameQuery { pawns: pawns("4k3/ppp2ppp/8/3p4/3P4/8/PPP2PPP/4K3 w - - 0 1"), ..GameQuery::default() }), Vec::<u64>::new());
+        assert_eq!(ids(GameQuery { pawns: pawns("4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 b - - 0 1"), ..GameQuery::default() }), vec![1, 2]);
+
+        let stored = &storage.games(&GameQuery::default()).unwrap()[1];
+        assert_eq!((stored.white.as_str(), stored.result.as_str()), ("Ben", "1/2-1/2"));
+        assert_eq!(stored.game().unwrap().game.main_line().len(), 2);
+
+        let fen = after_e4.to_fen();
+        let shallow = Analysis { fen: fen.clone(), depth: 4, score: -20, pv: vec![String::from("e7e5")] };
+        let deep = Analysis { depth: 8, score: -35, ..shallow.clone() };
+
+        storage.save_analysis(&shallow).unwrap();
+        storage.save_analysis(&deep).unwrap();
+        storage.save_analysis(&shallow).unwrap();
+        assert_eq!(storage.analysis(after_e4.hash_key()).unwrap(), Some(deep));
+        assert_eq!(storage.analysis(Position::starting_position().hash_key()).unwrap(), None);
+        assert!(storage.save_analysis(&Analysis { fen: String::from("8/8/8/8/8/8/8/8 w - - 0 1"), ..shallow }).is_err());
+
+        let puzzle = PuzzleRecord { fen: String::from("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1"), solution: vec![String::from("a1a8")], attempts: 1, solved: 0 };
+        let hash = Position::from_fen(puzzle.fen.clone()).unwrap().hash_key();
+
+        storage.save_puzzle(&puzzle).unwrap();
+        storage.save_puzzle(&PuzzleRecord { attempts: 2, solved: 1, ..puzzle.clone() }).unwrap();
+        assert_eq!(storage.puzzle(hash).unwrap(), Some(PuzzleRecord { attempts: 2, solved: 1, ..puzzle }));
+
+        let rating = Rating { rating: 1620.5, deviation: 80.25, volatility: 0.059 };
+
+        storage.save_rating("Anna", Rating::default()).unwrap();
+        storage.save_rating("Anna", rating).unwrap();
+        assert_eq!(storage.rating("Anna").unwrap(), Some(rating));
+        assert_eq!(storage.rating("Ben").unwrap(), None);
+    }
+
+    #[test]
+    fn memory_storage_queries_and_keeps_records() {
+        check_storage(&mut MemoryStorage::new());
+    }
+
+    #[test]
+    fn finds_the_first_move_out_of_the_database() {
+        let mut storage = MemoryStorage::new();
+
+        for game in pgn::read_games(GAMES) {
+            storage.save_game(&game).unwrap();
+        }
+
+        let novelty = |moves: &str| {
+            let game = pgn::read_games(&format!("[Result \"*\"]\n\n{} *\n", moves)).pop().unwrap();
+
+            return find_novelty(&storage, &game.game).unwrap();
+        };
+
+        let black = novelty("1. e4 e5 2. Nf3 Nf6 3. Nxe5").unwrap();
+        assert_eq!((black.ply, black.number, black.white, black.san.as_str(), black.games), (3, 2, false, "Nf6", 1));
+        assert_eq!(black.to_string(), "Novelty on move 2: 2... Nf6, leaving 1 known game");
+
+        let white = novelty("1. c4 e5").unwrap();
+        assert_eq!((white.ply, white.games), (0, 2));
+        assert_eq!(white.to_string(), "Novelty on move 1: 1. c4, leaving 2 known games");
+
+        assert_eq!(novelty("1. d4 e5").map(|novelty| novelty.san), Some(String::from("e5")));
+        assert_eq!(novelty("1. e4 e5 2. Nf3 Nc6"), None);
+    }
+}
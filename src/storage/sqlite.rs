@@ -0,0 +1,237 @@
+use std::io;
+use std::path::Path;
+
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+
+use crate::pgn::{self, PgnGame};
+use crate::rating::Rating;
+use crate::storage::{self, Analysis, GameQuery, PuzzleRecord, Storage, StoredGame};
+
+
+/// The tables of the database. Hash keys are stored as the signed integers SQLite supports,
+/// with the same bits as the u64 keys.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS games (
+        id INTEGER PRIMARY KEY,
+        white TEXT NOT NULL,
+        black TEXT NOT NULL,
+        date TEXT NOT NULL,
+        result TEXT NOT NULL,
+        pgn TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS games_white ON games (white);
+    CREATE INDEX IF NOT EXISTS games_black ON games (black);
+    CREATE INDEX IF NOT EXISTS games_date ON games (date);
+    CREATE TABLE IF NOT EXISTS positions (
+        hash INTEGER NOT NULL,
+        game INTEGER NOT NULL REFERENCES games (id),
+        PRIMARY KEY (hash, game)
+    ) WITHOUT ROWID;
+    CREATE TABLE IF NOT EXISTS pawn_structures (
+        key INTEGER NOT NULL,
+        game INTEGER NOT NULL REFERENCES games (id),
+        PRIMARY KEY (key, game)
+    ) WITHOUT ROWID;
+    CREATE TABLE IF NOT EXISTS analyses (
+        hash INTEGER PRIMARY KEY,
+        fen TEXT NOT NULL,
+        depth INTEGER NOT NULL,
+        score INTEGER NOT NULL,
+        pv TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS puzzles (
+        hash INTEGER PRIMARY KEY,
+        fen TEXT NOT NULL,
+        solution TEXT NOT NULL,
+        attempts INTEGER NOT NULL,
+        solved INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS ratings (
+        player TEXT PRIMARY KEY,
+        rating REAL NOT NULL,
+        deviation REAL NOT NULL,
+        volatility REAL NOT NULL
+    );";
+
+/// A storage in an SQLite database file.
+pub struct SqliteStorage {
+    connection: Connection
+}
+
+impl SqliteStorage {
+    /// Opens the database at the given path, creating it if it doesn't exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<SqliteStorage> {
+        return SqliteStorage::with_connection(Connection::open(path).map_err(to_io)?);
+    }
+
+    /// Opens a database that only lives in memory.
+    pub fn in_memory() -> io::Result<SqliteStorage> {
+        return SqliteStorage::with_connection(Connection::open_in_memory().map_err(to_io)?);
+    }
+
+    fn with_connection(connection: Connection) -> io::Result<SqliteStorage> {
+        connection.execute_batch(SCHEMA).map_err(to_io)?;
+
+        let mut storage = SqliteStorage { connection };
+        storage.index_pawn_structures()?;
+
+        return Ok(storage);
+    }
+
+    /// Indexes the pawn structures of the games saved before the database had the table for
+    /// them. Every game has at least the pawn structure of its starting position.
+    fn index_pawn_structures(&mut self) -> io::Result<()> {
+        let transaction = self.connection.transaction().map_err(to_io)?;
+
+        let games: Vec<(i64, String)> = transaction
+            .prepare("SELECT id, pgn FROM games WHERE id NOT IN (SELECT game FROM pawn_structures)").map_err(to_io)?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?))).map_err(to_io)?
+            .collect::<Result<_, _>>().map_err(to_io)?;
+
+        for (id, pgn) in games {
+            if let Some(game) = pgn::read_games(&pgn).pop() {
+                insert_pawn_keys(&transaction, &game, id)?;
+            }
+        }
+
+        return transaction.commit().map_err(to_io);
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn save_game(&mut self, game: &PgnGame) -> io::Result<u64> {
+        let stored = storage::describe(game);
+        let transaction = self.connection.transaction().map_err(to_io)?;
+
+        transaction.execute("INSERT INTO games (white, black, date, result, pgn) VALUES (?1, ?2, ?3, ?4, ?5)",
+                            params![stored.white, stored.black, stored.date, stored.result, stored.pgn]).map_err(to_io)?;
+
+        let id = transaction.last_insert_rowid();
+
+        for hash in storage::main_line_hashes(&game.game) {
+            transaction.execute("INSERT OR IGNORE INTO positions (hash, game) VALUES (?1, ?2)", params![hash as i64, id]).map_err(to_io)?;
+        }
+
+        insert_pawn_keys(&transaction, game, id)?;
+
+        transaction.commit().map_err(to_io)?;
+
+        return Ok(id as u64);
+    }
+
+    fn games(&self, query: &GameQuery) -> io::Result<Vec<StoredGame>> {
+        let mut statement = self.connection.prepare(
+            "SELECT id, white, black, date, result, pgn FROM games
+             WHERE (?1 IS NULL OR white = ?1 OR black = ?1) AND (?2 IS NULL OR date >= ?2) AND (?3 IS NULL OR date <= ?3)
+                   AND (?4 IS NULL OR id IN (SELECT game FROM positions WHERE hash = ?4))
+                   AND (?5 IS NULL OR id IN (SELECT game FROM pawn_structures WHERE key = ?5))
+             ORDER BY id").map_err(to_io)?;
+
+        let keys = (query.position.map(|hash| hash as i64), query.pawns.map(|key| key as i64));
+        let rows = statement.query_map(params![query.player, query.from, query.to, keys.0, keys.1], |row| {
+            return Ok(StoredGame { id: row.get::<_, i64>(0)? as u64, white: row.get(1)?, black: row.get(2)?, date: row.get(3)?, result: row.get(4)?, pgn: row.get(5)? });
+        }).map_err(to_io)?;
+
+        return rows.collect::<Result<_, _>>().map_err(to_io);
+    }
+
+    fn save_analysis(&mut self, analysis: &Analysis) -> io::Result<()> {
+        let hash = storage::fen_hash(&analysis.fen)? as i64;
+
+        self.connection.execute(
+            "INSERT INTO analyses (hash, fen, depth, score, pv) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT (hash) DO UPDATE SET fen = excluded.fen, depth = excluded.depth, score = excluded.score, pv = excluded.pv
+             WHERE excluded.depth >= analyses.depth",
+            params![hash, analysis.fen, analysis.depth, analysis.score, analysis.pv.join(" ")]).map_err(to_io)?;
+
+        return Ok(());
+    }
+
+    fn analysis(&self, hash: u64) -> io::Result<Option<Analysis>> {
+        return self.connection.query_row("SELECT fen, depth, score, pv FROM analyses WHERE hash = ?1", params![hash as i64], |row| {
+            return Ok(Analysis { fen: row.get(0)?, depth: row.get(1)?, score: row.get(2)?, pv: words(row.get(3)?) });
+        }).optional().map_err(to_io);
+    }
+
+    fn save_puzzle(&mut self, puzzle: &PuzzleRecord) -> io::Result<()> {
+        let hash = storage::fen_hash(&puzzle.fen)? as i64;
+
+        self.connection.execute("INSERT OR REPLACE INTO puzzles (hash, fen, solution, attempts, solved) VALUES (?1, ?2, ?3, ?4, ?5)",
+                                params![hash, puzzle.fen, puzzle.solution.join(" "), puzzle.attempts, puzzle.solved]).map_err(to_io)?;
+
+        return Ok(());
+    }
+
+    fn puzzle(&self, hash: u64) -> io::Result<Option<PuzzleRecord>> {
+        return self.connection.query_row("SELECT fen, solution, attempts, solved FROM puzzles WHERE hash = ?1", params![hash as i64], |row| {
+            return Ok(PuzzleRecord { fen: row.get(0)?, solution: words(row.get(1)?), attempts: row.get(2)?, solved: row.get(3)? });
+        }).optional().map_err(to_io);
+    }
+
+    fn save_rating(&mut self, player: &str, rating: Rating) -> io::Result<()> {
+        self.connection.execute("INSERT OR REPLACE INTO ratings (player, rating, deviation, volatility) VALUES (?1, ?2, ?3, ?4)",
+                                params![player, rating.rating, rating.deviation, rating.volatility]).map_err(to_io)?;
+
+        return Ok(());
+    }
+
+    fn rating(&self, player: &str) -> io::Result<Option<Rating>> {
+        return self.connection.query_row("SELECT rating, deviation, volatility FROM ratings WHERE player = ?1", params![player], |row| {
+            return Ok(Rating { rating: row.get(0)?, deviation: row.get(1)?, volatility: row.get(2)? });
+        }).optional().map_err(to_io);
+    }
+}
+
+/// Indexes the pawn structures of the main line of the game with the given id.
+fn insert_pawn_keys(transaction: &Transaction, game: &PgnGame, id: i64) -> io::Result<()> {
+    for key in storage::main_line_pawn_keys(&game.game) {
+        transaction.execute("INSERT OR IGNORE INTO pawn_structures (key, game) VALUES (?1, ?2)", params![key as i64, id]).map_err(to_io)?;
+    }
+
+    return Ok(());
+}
+
+/// Splits a list of moves stored as a single string.
+fn words(text: String) -> Vec<String> {
+    return text.split_whitespace().map(String::from).collect();
+}
+
+fn to_io(e: rusqlite::Error) -> io::Error {
+    return io::Error::other(e);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests::check_storage;
+
+    #[test]
+    fn sqlite_storage_queries_and_keeps_records() {
+        check_storage(&mut SqliteStorage::in_memory().unwrap());
+    }
+
+    #[test]
+    fn databases_persist_between_connections() {
+        let path = std::env::temp_dir().join(format!("chess-storage-{}.sqlite", std::process::id()));
+        let games = crate::pgn::read_games("[White \"Anna\"]\n\n1. e4 *\n");
+
+        SqliteStorage::open(&path).unwrap().save_game(&games[0]).unwrap();
+        let stored = SqliteStorage::open(&path).unwrap().games(&GameQuery::default());
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(stored.unwrap().iter().map(|game| game.white.as_str()).collect::<Vec<_>>(), vec!["Anna"]);
+    }
+
+    #[test]
+    fn indexes_the_pawn_structures_of_older_databases() {
+        let mut storage = SqliteStorage::in_memory().unwrap();
+        let games = crate::pgn::read_games("[White \"Anna\"]\n\n1. e4 *\n");
+
+        storage.save_game(&games[0]).unwrap();
+        storage.connection.execute("DELETE FROM pawn_structures", []).unwrap();
+        storage.index_pawn_structures().unwrap();
+
+        let key = crate::position::Position::starting_position().pawn_key();
+        assert_eq!(storage.games(&GameQuery { pawns: Some(key), ..GameQuery::default() }).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,131 @@
+use crate::search::Iteration;
+use crate::training::Rng;
+
+
+/// The skill levels range from 0 to MAX_SKILL, which is the full strength of the engine.
+pub const MAX_SKILL: u8 = 20;
+
+/// The Elo range a weakened engine can be set to. Skill level 0 plays at about MIN_ELO and
+/// each level adds an equal share of the range. These are rough estimates against club
+/// players, not calibrated ratings.
+pub const MIN_ELO: u32 = 800;
+pub const MAX_ELO: u32 = 2200;
+
+/// The number of best moves a weakened engine chooses from.
+const CANDIDATES: usize = 4;
+
+/// How many centipawns worse a move may be at skill level 0 to be chosen about 1/e times as
+/// often as the best move. It falls linearly with the skill level, by an equal share at each
+/// level, and reaches no randomness at all at MAX_SKILL.
+const MAX_TEMPERATURE: f64 = 200.0;
+
+
+/// Weakens the play of the engine: it searches shallower and picks randomly among its best
+/// moves, preferring better ones more the higher the skill level is.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Strength {
+    skill: u8
+}
+
+impl Strength {
+    /// The strength of the given skill level, at most MAX_SKILL.
+    pub fn from_skill(skill: u8) -> Strength {
+        return Strength { skill: skill.min(MAX_SKILL) };
+    }
+
+    /// The skill level playing closest to the given Elo rating.
+    pub fn from_elo(elo: u32) -> Strength {
+        let elo = elo.clamp(MIN_ELO, MAX_ELO);
+        let step = (MAX_ELO - MIN_ELO) as f64 / MAX_SKILL as f64;
+
+        return Strength::from_skill(((elo - MIN_ELO) as f64 / step).round() as u8);
+    }
+
+    pub fn skill(&self) -> u8 {
+        return self.skill;
+    }
+
+    /// The estimated Elo rating of the engine at this strength.
+    pub fn elo(&self) -> u32 {
+        return MIN_ELO + (MAX_ELO - MIN_ELO) * self.skill as u32 / MAX_SKILL as u32;
+    }
+
+    /// The deepest the engine searches at this strength, from 1 at skill level 0 to 8.
+    pub fn depth(&self) -> u8 {
+        return 1 + self.skill * 7 / MAX_SKILL;
+    }
+
+    /// The number of best moves the engine chooses from.
+    pub fn candidates(&self) -> usize {
+        return if self.skill >= MAX_SKILL { 1 } else { CANDIDATES };
+    }
+
+    /// The score difference in centipawns that makes a move about 1/e times as likely to be
+    /// chosen as the best one.
+    fn temperature(&self) -> f64 {
+        return MAX_TEMPERATURE * (MAX_SKILL - self.skill) as f64 / MAX_SKILL as f64;
+    }
+
+    /// Chooses one of the candidate lines of a MultiPV search, each with a probability
+    /// proportional to exp((score - best) / temperature). Returns None without lines.
+    pub fn choose<'a>(&self, lines: &'a [Iteration], rng: &mut Rng) -> Option<&'a Iteration> {
+        let best = lines.iter().map(|line| line.score).max()?;
+        let temperature = self.temperature();
+
+        if temperature <= 0.0 {
+            return lines.iter().find(|line| line.score == best);
+        }
+
+        let weights: Vec<f64> = lines.iter().map(|line| ((line.score - best) as f64 / temperature).exp()).collect();
+        let mut pick = rng.next_u64() as f64 / u64::MAX as f64 * weights.iter().sum::<f64>();
+
+        for (line, weight) in lines.iter().zip(&weights) {
+            if pick < *weight {
+                return Some(line);
+            }
+
+            pick -= weight;
+        }
+
+        return lines.last();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    fn lines(scores: &[i32]) -> Vec<Iteration> {
+        let moves = Position::starting_position().clone().get_all_legal_moves();
+
+        return scores.iter().zip(moves).map(|(&score, m)| Iteration { depth: 1, best: m, score, nodes: 1, pv: vec![m] }).collect();
+    }
+
+    #[test]
+    fn maps_between_skill_levels_and_elo() {
+        assert_eq!(Strength::from_elo(MIN_ELO).skill(), 0);
+        assert_eq!(Strength::from_elo(MAX_ELO + 500).skill(), MAX_SKILL);
+        assert_eq!(Strength::from_elo(1500).elo(), 1500);
+        assert_eq!(Strength::from_skill(30), Strength::from_skill(MAX_SKILL));
+        assert_eq!((Strength::from_skill(0).depth(), Strength::from_skill(MAX_SKILL).depth()), (1, 8));
+    }
+
+    #[test]
+    fn weaker_levels_choose_worse_moves_more_often() {
+        let lines = lines(&[50, 20, -300, -900]);
+        let mut rng = Rng::new(5);
+        let mut best_count = |skill| (0..1000).filter(|_| Strength::from_skill(skill).choose(&lines, &mut rng).unwrap().score == 50).count();
+
+        let (weak, strong, full) = (best_count(0), best_count(15), best_count(MAX_SKILL));
+
+        assert!(weak < strong && strong < full, "{} {} {}", weak, strong, full);
+        assert_eq!(full, 1000);
+
+        // Blunders are rare even for the weakest level.
+        let blunders = (0..1000).filter(|_| Strength::from_skill(0).choose(&lines, &mut rng).unwrap().score == -900).count();
+        assert!(blunders < 10);
+        assert!(Strength::from_skill(0).choose(&[], &mut rng).is_none());
+    }
+}
@@ -0,0 +1,147 @@
+//! Test suites of positions with known best moves, like WAC or the Bratko-Kopec test, given
+//! as EPD lines with bm (best move) and am (avoid move) operations in SAN.
+
+use std::fmt;
+
+use crate::moves::Move;
+use crate::position::Position;
+use crate::search;
+
+
+/// A position of a test suite.
+#[derive(Clone)]
+pub struct TestPosition {
+    pub id: String,
+    pub position: Position,
+    /// The moves that solve the position. Any of them counts.
+    pub best_moves: Vec<Move>,
+    /// The moves that must not be played.
+    pub avoid_moves: Vec<Move>
+}
+
+/// The outcome of searching a test position.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestResult {
+    pub id: String,
+    /// The move the search found in SAN, None if there's no legal move.
+    pub found: Option<String>,
+    pub solved: bool
+}
+
+impl TestPosition {
+    /// Parses a line of an EPD file. The id is taken from the id operation and defaults to
+    /// the position. Returns None if the position is invalid, a move isn't legal in it or
+    /// there's neither a bm nor an am operation.
+    pub fn from_epd(line: &str) -> Option<TestPosition> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+
+        if fields.len() < 4 {
+            return None;
+        }
+
+        let position = Position::from_fen(fields[..4].join(" "))?;
+        let operations = fields[4..].join(" ");
+
+        let mut id = fields[..4].join(" ");
+        let mut best_moves = Vec::new();
+        let mut avoid_moves = Vec::new();
+
+        for operation in operations.split(';').map(|op| op.trim()) {
+            let (opcode, operands) = operation.split_once(' ').unwrap_or((operation, ""));
+            let moves = || operands.split_whitespace().map(|san| position.parse_san(san).map(|(m, _)| m)).collect::<Option<Vec<Move>>>();
+
+            match opcode {
+                "id" => id = operands.trim().trim_matches('"').to_string(),
+                "bm" => best_moves = moves()?,
+                "am" => avoid_moves = moves()?,
+                _    => ()
+            }
+        }
+
+        if best_moves.is_empty() && avoid_moves.is_empty() {
+            return None;
+        }
+
+        return Some(TestPosition { id, position, best_moves, avoid_moves });
+    }
+
+    /// Searches the position to the given depth and checks the move found.
+    pub fn solve(&self, depth: u8) -> TestResult {
+        let found = search::search(&self.position, depth).map(|(m, _)| m);
+
+        let solved = found.is_some_and(|m| {
+            return (self.best_moves.is_empty() || self.best_moves.contains(&m)) && !self.avoid_moves.contains(&m);
+        });
+
+        return TestResult { id: self.id.clone(), found: found.map(|m| self.position.move_to_san(m)), solved };
+    }
+
+    /// The moves of the bm operation in SAN.
+    pub fn best_sans(&self) -> Vec<String> {
+        return self.best_moves.iter().map(|m| self.position.move_to_san(*m)).collect();
+    }
+
+    /// The moves of the am operation in SAN.
+    pub fn avoid_sans(&self) -> Vec<String> {
+        return self.avoid_moves.iter().map(|m| self.position.move_to_san(*m)).collect();
+    }
+}
+
+impl fmt::Display for TestResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}: {} {}", self.id, self.found.as_deref().unwrap_or("-"), if self.solved { "solved" } else { "failed" });
+    }
+}
+
+/// Reads all test positions of an EPD file. Empty lines and comments starting with # are
+/// skipped, invalid lines are returned as errors with their line number.
+pub fn read_suite(text: &str) -> Result<Vec<TestPosition>, usize> {
+    let mut positions = Vec::new();
+
+    for (i, line) in text.lines().enumerate().map(|(i, line)| (i, line.trim())) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        positions.push(TestPosition::from_epd(line).ok_or(i + 1)?);
+    }
+
+    return Ok(positions);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUITE: &str = "# Mates and a hanging queen
+6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8#; id \"back rank\";
+4k3/8/8/3q4/8/8/3R4/4K3 w - - bm Rxd5; id \"queen\";
+
+4k3/8/8/3q4/8/8/3R4/4K3 w - - am Rd1; id \"avoid\";";
+
+    #[test]
+    fn reads_suites() {
+        let suite = read_suite(SUITE).unwrap();
+
+        assert_eq!(suite.len(), 3);
+        assert_eq!((suite[0].id.as_str(), suite[0].best_sans()), ("back rank", vec![String::from("Ra8#")]));
+        assert_eq!((suite[2].best_moves.len(), suite[2].avoid_sans()), (0, vec![String::from("Rd1")]));
+
+        assert!(TestPosition::from_epd("4k3/8/8/8/8/8/8/4K3 w - - id \"no moves\";").is_none());
+        assert!(TestPosition::from_epd("4k3/8/8/8/8/8/8/4K3 w - - bm Qd4;").is_none());
+        assert_eq!(read_suite("8/8/8 w\n").err(), Some(1));
+    }
+
+    #[test]
+    fn solves_positions() {
+        let results: Vec<TestResult> = read_suite(SUITE).unwrap().iter().map(|p| p.solve(2)).collect();
+
+        assert!(results.iter().all(|r| r.solved));
+        assert_eq!(results[0].to_string(), "back rank: Ra8# solved");
+
+        let failed = TestPosition::from_epd("4k3/8/8/3q4/8/8/3R4/4K3 w - - am Rxd5; id \"greedy\";").unwrap().solve(2);
+
+        assert_eq!(failed, TestResult { id: String::from("greedy"), found: Some(String::from("Rxd5")), solved: false });
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::bitboards::{bb_ops, knights};
+use crate::bitboards::geometry::NUM_SQUARES;
+use crate::position::{self, WHITE, BLACK};
+
+
+/// A small xorshift random number generator. Training doesn't need good randomness, so this
+/// avoids depending on a random number crate.
+#[derive(Clone, Debug)]
+pub struct Rng(u64);
+
+impl Rng {
+    /// Creates a generator with the given seed. The same seed always gives the same numbers.
+    pub fn new(seed: u64) -> Rng {
+        // Xorshift gets stuck at 0.
+        return Rng(seed.max(1));
+    }
+
+    /// Creates a generator seeded with the current time.
+    pub fn from_time() -> Rng {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(1, |d| d.as_nanos() as u64);
+
+        return Rng::new(nanos);
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+
+        return self.0;
+    }
+
+    /// Returns a number in the range 0..n.
+    pub fn below(&mut self, n: u64) -> u64 {
+        return self.next_u64() % n;
+    }
+
+    /// Returns the index of a random square of the 8x8 board.
+    pub fn square(&mut self) -> u8 {
+        return self.below(NUM_SQUARES as u64) as u8;
+    }
+}
+
+/// Returns the color of the square with the given index, i.e. WHITE for light squares and BLACK
+/// for dark squares. a1 is dark.
+pub fn square_color(sq: u8) -> u8 {
+    let (rk, fl) = bb_ops::index_to_coords(sq);
+
+    return if (rk + fl) % 2 == 0 { BLACK } else { WHITE };
+}
+
+/// Finds a shortest path of a knight from one square to another using the knight move masks.
+/// The path starts with the first square and ends with the second one.
+pub fn knight_path(from: u8, to: u8) -> Vec<u8> {
+    let mut previous: [Option<u8>; NUM_SQUARES as usize] = [None; NUM_SQUARES as usize];
+    let mut queue = VecDeque::from([from]);
+
+    previous[from as usize] = Some(from);
+
+    while let Some(sq) = queue.pop_front() {
+        if sq == to {
+            break;
+        }
+
+        for next in bb_ops::idx_bitscan(knights::get_knight_attacks(sq)) {
+            if previous[next as usize].is_none() {
+                previous[next as usize] = Some(sq);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    // Every square can be reached by a knight on the 8x8 board.
+    let mut path = vec![to];
+
+    while *path.last().unwrap() != from {
+        path.push(previous[*path.last().unwrap() as usize].unwrap());
+    }
+
+    path.reverse();
+
+    return path;
+}
+
+/// A puzzle asking for the number of moves a knight needs from one square to another.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct KnightPuzzle {
+    pub from: u8,
+    pub to: u8,
+    /// A shortest path from the first to the second square.
+    pub path: Vec<u8>
+}
+
+impl KnightPuzzle {
+    /// Generates a random puzzle that takes at least two moves to solve.
+    pub fn random(rng: &mut Rng) -> KnightPuzzle {
+        loop {
+            let (from, to) = (rng.square(), rng.square());
+            let path = knight_path(from, to);
+
+            if path.len() > 2 {
+                return KnightPuzzle { from, to, path };
+            }
+        }
+    }
+
+    /// Returns the number of moves of the shortest solution.
+    pub fn moves(&self) -> usize {
+        return self.path.len() - 1;
+    }
+}
+
+/// Asks the given number of questions on the input alternating between the color of a random
+/// square and a knight puzzle. Returns the number of correct answers.
+pub fn quiz<R: BufRead, W: Write>(rng: &mut Rng, rounds: usize, input: R, mut output: W) -> io::Result<usize> {
+    let mut lines = input.lines();
+    let mut correct = 0;
+
+    for round in 0..rounds {
+        let (question, answer, solution) = if round % 2 == 0 {
+            let sq = rng.square();
+            let color = if square_color(sq) == WHITE { "light" } else { "dark" };
+
+            (format!("Is {} light or dark?", name(sq)), String::from(color), String::from(color))
+        } else {
+            let puzzle = KnightPuzzle::random(rng);
+            let path: Vec<String> = puzzle.path.iter().map(|&sq| name(sq)).collect();
+
+            (format!("How many moves does a knight need from {} to {}?", name(puzzle.from), name(puzzle.to)),
+             puzzle.moves().to_string(),
+             format!("{} ({})", puzzle.moves(), path.join("-")))
+        };
+
+        write!(output, "{} ", question)?;
+        output.flush()?;
+
+        let line = match lines.next() {
+            Some(line) => line?,
+            None       => break
+        };
+
+        if line.trim().eq_ignore_ascii_case(&answer) {
+            correct += 1;
+            writeln!(output, "Correct.")?;
+        } else {
+            writeln!(output, "Wrong, the answer is {}.", solution)?;
+        }
+    }
+
+    writeln!(output, "{} of {} correct.", correct, rounds)?;
+
+    return Ok(correct);
+}
+
+fn name(sq: u8) -> String {
+    return position::index_to_string(sq).unwrap_or_default();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sq(name: &str) -> u8 {
+        return position::string_to_index(name.to_string()).unwrap();
+    }
+
+    #[test]
+    fn knows_square_colors() {
+        assert_eq!(square_color(sq("a1")), BLACK);
+        assert_eq!(square_color(sq("h1")), WHITE);
+        assert_eq!(square_color(sq("d1")), WHITE);
+        assert_eq!(square_color(sq("e4")), WHITE);
+        assert_eq!(square_color(sq("h8")), BLACK);
+    }
+
+    #[test]
+    fn finds_shortest_knight_paths() {
+        assert_eq!(knight_path(sq("g1"), sq("g1")), vec![sq("g1")]);
+        assert_eq!(knight_path(sq("g1"), sq("f3")), vec![sq("g1"), sq("f3")]);
+        assert_eq!(knight_path(sq("a1"), sq("h8")).len() - 1, 6);
+        assert_eq!(knight_path(sq("a1"), sq("b2")).len() - 1, 4);
+
+        let path = knight_path(sq("b1"), sq("e7"));
+        assert!(path.windows(2).all(|w| bb_ops::index_lookup(knights::get_knight_attacks(w[0]), w[1])));
+    }
+
+    #[test]
+    fn quiz_counts_correct_answers() {
+        let mut rng = Rng::new(7);
+        let color = if square_color(rng.clone().square()) == WHITE { "light" } else { "dark" };
+        let mut output = Vec::new();
+
+        let correct = quiz(&mut rng, 2, format!("{}\n0\n", color).as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert_eq!(correct, 1);
+        assert!(output.contains("Correct.") && output.contains("Wrong, the answer is"));
+        assert!(output.ends_with("1 of 2 correct.\n"));
+    }
+}
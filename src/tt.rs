@@ -0,0 +1,319 @@
+use std::convert::TryInto;
+use std::io::{self, Read, Write};
+use std::mem;
+
+use crate::moves::Move;
+
+
+/// The size of the transposition table used if no size is configured.
+pub const DEFAULT_SIZE_MB: usize = 16;
+
+/// The start of a saved table, followed by the version of the format.
+const MAGIC: &[u8; 4] = b"RCTT";
+const VERSION: u8 = 1;
+
+/// The number of bytes of a saved entry: the key, depth, score, bound and best move.
+const ENTRY_BYTES: usize = 8 + 1 + 4 + 1 + 7;
+
+/// Marks a missing square or piece in a saved move.
+const NONE: u8 = u8::MAX;
+
+
+/// How a stored score relates to the exact score of the position. Scores outside of the search
+/// window are only bounds because the search stopped early.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Bound {
+    Exact,
+    /// The exact score is at least the stored score (the search failed high).
+    Lower,
+    /// The exact score is at most the stored score (the search failed low).
+    Upper
+}
+
+/// The result of searching a position stored in the transposition table.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Entry {
+    /// The full hash of the position, used to detect collisions of different positions in the
+    /// same slot.
+    pub key: u64,
+    pub depth: u8,
+    pub score: i32,
+    pub bound: Bound,
+    pub best: Option<Move>
+}
+
+/// Decides whether a new entry replaces the one in its slot.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Replacement {
+    /// The newest entry always wins.
+    Always,
+    /// Entries of other positions are only replaced by searches that were at least as deep.
+    /// Entries of the same position are always updated.
+    DepthPreferred
+}
+
+/// A fixed-size hash table remembering the results of searched positions, so positions reached
+/// by different move orders are only searched once. Each position maps to a single slot given by
+/// its hash.
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+    replacement: Replacement
+}
+
+impl TranspositionTable {
+    /// Creates a table using about the given number of megabytes. The table always has at least
+    /// one slot.
+    pub fn new(size_mb: usize, replacement: Replacement) -> TranspositionTable {
+        let slots = (size_mb * 1024 * 1024 / mem::size_of::<Option<Entry>>()).max(1);
+
+        return TranspositionTable { entries: vec![None; slots], replacement };
+    }
+
+    /// Returns the number of bytes the table occupies.
+    pub fn memory_usage(&self) -> usize {
+        return self.entries.len() * mem::size_of::<Option<Entry>>();
+    }
+
+    /// Returns the number of slots.
+    pub fn slots(&self) -> usize {
+        return self.entries.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.entries.iter().all(Option::is_none);
+    }
+
+    /// Removes all entries, e.g. before a new game.
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+    }
+
+    /// Returns the entry of the position with the given hash if it's stored.
+    pub fn probe(&self, key: u64) -> Option<&Entry> {
+        return self.entries[self.slot(key)].as_ref().filter(|entry| entry.key == key);
+    }
+
+    /// Stores an entry, possibly replacing the one in its slot according to the replacement
+    /// scheme.
+    pub fn store(&mut self, entry: Entry) {
+        let slot = self.slot(entry.key);
+
+        let replace = match (self.replacement, &self.entries[slot]) {
+            (Replacement::Always, _)                 => true,
+            (Replacement::DepthPreferred, None)      => true,
+            (Replacement::DepthPreferred, Some(old)) => old.key == entry.key || entry.depth >= old.depth
+        };
+
+        if replace {
+            self.entries[slot] = Some(entry);
+        }
+    }
+
+    /// Returns how full the table is in permille, estimated from the first thousand slots like
+    /// the UCI hashfull info.
+    pub fn hashfull(&self) -> usize {
+        let sample = &self.entries[..self.entries.len().min(1000)];
+
+        return sample.iter().filter(|entry| entry.is_some()).count() * 1000 / sample.len();
+    }
+
+    /// Writes the table, so a long analysis can be continued after a restart with read_from.
+    /// Only the stored entries are written.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let replacement = match self.replacement {
+            Replacement::Always         => 0,
+            Replacement::DepthPreferred => 1
+        };
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION, replacement])?;
+        writer.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+
+        for entry in self.entries.iter().flatten() {
+            writer.write_all(&encode_entry(entry))?;
+        }
+
+        return writer.flush();
+    }
+
+    /// Reads a table written by write_to. It has the size of the saved table.
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<TranspositionTable> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid transposition table: {}", message));
+
+        let mut header = [0; 14];
+        reader.read_exact(&mut header)?;
+
+        if &header[..4] != MAGIC || header[4] != VERSION {
+            return Err(invalid("unknown format"));
+        }
+
+        let replacement = match header[5] {
+            0 => Replacement::Always,
+            1 => Replacement::DepthPreferred,
+            _ => return Err(invalid("unknown replacement scheme"))
+        };
+
+        let slots = u64::from_le_bytes(header[6..].try_into().unwrap());
+
+        if slots == 0 || slots > isize::MAX as u64 / mem::size_of::<Option<Entry>>() as u64 {
+            return Err(invalid("impossible size"));
+        }
+
+        let mut table = TranspositionTable { entries: vec![None; slots as usize], replacement };
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        if bytes.len() % ENTRY_BYTES != 0 {
+            return Err(invalid("truncated entry"));
+        }
+
+        for chunk in bytes.chunks(ENTRY_BYTES) {
+            let entry = decode_entry(chunk).ok_or_else(|| invalid("corrupt entry"))?;
+            let slot = table.slot(entry.key);
+
+            table.entries[slot] = Some(entry);
+        }
+
+        return Ok(table);
+    }
+
+    fn slot(&self, key: u64) -> usize {
+        return (key % self.entries.len() as u64) as usize;
+    }
+}
+
+fn encode_entry(entry: &Entry) -> [u8; ENTRY_BYTES] {
+    let mut bytes = [0; ENTRY_BYTES];
+
+    bytes[..8].copy_from_slice(&entry.key.to_le_bytes());
+    bytes[8] = entry.depth;
+    bytes[9..13].copy_from_slice(&entry.score.to_le_bytes());
+    bytes[13] = match entry.bound {
+        Bound::Exact => 0,
+        Bound::Lower => 1,
+        Bound::Upper => 2
+    };
+
+    // The kind of the move followed by its fields.
+    let option = |value: Option<u8>| value.unwrap_or(NONE);
+
+    bytes[14..].copy_from_slice(&match entry.best {
+        None                            => [0, 0, 0, 0, 0, 0, 0],
+        Some(Move::StandardMove(piece, origin, target, captures, promotes_to, en_passant)) => {
+            [1, piece, origin, target, option(captures), option(promotes_to), option(en_passant)]
+        },
+        Some(Move::ShortCastle)         => [2, 0, 0, 0, 0, 0, 0],
+        Some(Move::LongCastle)          => [3, 0, 0, 0, 0, 0, 0],
+        Some(Move::Drop(piece, target)) => [4, piece, target, 0, 0, 0, 0]
+    });
+
+    return bytes;
+}
+
+fn decode_entry(bytes: &[u8]) -> Option<Entry> {
+    let bound = match bytes[13] {
+        0 => Bound::Exact,
+        1 => Bound::Lower,
+        2 => Bound::Upper,
+        _ => return None
+    };
+
+    let option = |value: u8| if value == NONE { None } else { Some(value) };
+    let fields = &bytes[14..];
+
+    let best = match fields[0] {
+        0 => None,
+        1 => Some(Move::StandardMove(fields[1], fields[2], fields[3], option(fields[4]), option(fields[5]), option(fields[6]))),
+        2 => Some(Move::ShortCastle),
+        3 => Some(Move::LongCastle),
+        4 => Some(Move::Drop(fields[1], fields[2])),
+        _ => return None
+    };
+
+    return Some(Entry {
+        key: u64::from_le_bytes(bytes[..8].try_into().ok()?),
+        depth: bytes[8],
+        score: i32::from_le_bytes(bytes[9..13].try_into().ok()?),
+        bound,
+        best
+    });
+}
+
+impl Default for TranspositionTable {
+    fn default() -> TranspositionTable {
+        return TranspositionTable::new(DEFAULT_SIZE_MB, Replacement::DepthPreferred);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: u64, depth: u8) -> Entry {
+        return Entry { key, depth, score: depth as i32, bound: Bound::Exact, best: None };
+    }
+
+    #[test]
+    fn sizes_the_table_in_megabytes() {
+        let table = TranspositionTable::new(1, Replacement::Always);
+
+        assert_eq!(table.slots(), 1024 * 1024 / mem::size_of::<Option<Entry>>());
+        assert_eq!(TranspositionTable::new(0, Replacement::Always).slots(), 1);
+    }
+
+    #[test]
+    fn probes_stored_entries() {
+        let mut table = TranspositionTable::new(1, Replacement::Always);
+        let slots = table.slots() as u64;
+
+        table.store(entry(5, 3));
+
+        assert_eq!(table.probe(5), Some(&entry(5, 3)));
+        assert_eq!(table.probe(5 + slots), None);
+        assert_eq!(table.probe(6), None);
+
+        table.clear();
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn replaces_entries_by_scheme() {
+        let mut always = TranspositionTable::new(0, Replacement::Always);
+        let mut deeper = TranspositionTable::new(0, Replacement::DepthPreferred);
+
+        for table in [&mut always, &mut deeper] {
+            table.store(entry(1, 4));
+            table.store(entry(2, 2));
+        }
+
+        assert_eq!(always.probe(2), Some(&entry(2, 2)));
+        assert_eq!(deeper.probe(1), Some(&entry(1, 4)));
+
+        // The same position is always updated.
+        deeper.store(entry(1, 1));
+        assert_eq!(deeper.probe(1), Some(&entry(1, 1)));
+        assert_eq!(deeper.hashfull(), 1000);
+    }
+
+    #[test]
+    fn saved_tables_can_be_read_back() {
+        let mut table = TranspositionTable::new(1, Replacement::Always);
+        let moves = [Some(Move::StandardMove(0, 12, 28, None, None, Some(20))), Some(Move::LongCastle), Some(Move::Drop(1, 35)), None];
+
+        for (key, &best) in moves.iter().enumerate() {
+            table.store(Entry { key: key as u64 * 1000, depth: 9, score: -(key as i32), bound: Bound::Lower, best });
+        }
+
+        let mut bytes = Vec::new();
+        table.write_to(&mut bytes).unwrap();
+        let read = TranspositionTable::read_from(bytes.as_slice()).unwrap();
+
+        assert_eq!(read.slots(), table.slots());
+        assert_eq!(read.entries, table.entries);
+        assert_eq!(bytes.len(), 14 + 4 * ENTRY_BYTES);
+
+        assert!(TranspositionTable::read_from(&bytes[..bytes.len() - 1]).is_err());
+        assert!(TranspositionTable::read_from(&b"RCTX"[..]).is_err());
+    }
+}
@@ -0,0 +1,266 @@
+use std::fmt;
+
+use crate::bitboards::geometry::{self, Geometry};
+
+
+/// The chess variants the move generator supports. Each variant defines the board it is played
+/// on, its starting position and the rules that deviate from regular chess.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Variant {
+    /// Regular chess on an 8x8 board.
+    #[default]
+    Standard,
+    /// Gardner's minichess on a 5x5 board. Pawns can't make double steps and there is no
+    /// castling.
+    Gardner,
+    /// Captured pieces go to the capturing player's pocket and can be dropped onto the board
+    /// instead of making a regular move.
+    Crazyhouse,
+    /// Played on two boards by two teams. Captured pieces are passed to the partner, so the
+    /// pocket of a board is only filled from the outside.
+    Bughouse,
+    /// Regular chess that is also won by giving check three times.
+    ThreeCheck
+}
+
+impl Variant {
+    /// Returns the dimensions of the board this variant is played on.
+    pub fn geometry(&self) -> Geometry {
+        return match self {
+            Variant::Gardner  => Geometry::new(5, 5),
+            _                 => geometry::STANDARD
+        };
+    }
+
+    /// Returns the FEN describing the starting position of this variant.
+    pub fn starting_fen(&self) -> &'static str {
+        return match self {
+            Variant::Gardner  => "rnbqk/ppppp/5/PPPPP/RNBQK w - - 0 1",
+            _                 => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        };
+    }
+
+    /// Returns true if pawns on their starting rank can advance two squares.
+    pub fn has_double_steps(&self) -> bool {
+        return *self != Variant::Gardner;
+    }
+
+    /// Returns true if the kings can castle.
+    pub fn has_castling(&self) -> bool {
+        return *self != Variant::Gardner;
+    }
+
+    /// Returns true if pieces held in a pocket can be dropped onto the board.
+    pub fn has_drops(&self) -> bool {
+        return *self == Variant::Crazyhouse || *self == Variant::Bughouse;
+    }
+
+    /// Returns true if captured pieces go to the capturing player's own pocket.
+    pub fn keeps_captures(&self) -> bool {
+        return *self == Variant::Crazyhouse;
+    }
+
+    /// Returns the number of checks that win the game or None if checks are not counted.
+    pub fn check_limit(&self) -> Option<u8> {
+        return match self {
+            Variant::ThreeCheck => Some(3),
+            _                   => None
+        };
+    }
+
+    /// Returns the name of this variant as used in the PGN Variant tag.
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Variant::Standard   => "Standard",
+            Variant::Gardner    => "Gardner",
+            Variant::Crazyhouse => "Crazyhouse",
+            Variant::Bughouse   => "Bughouse",
+            Variant::ThreeCheck => "Three-check"
+        };
+    }
+
+    /// Finds the variant with the given name, ignoring case.
+    pub fn from_name(name: &str) -> Option<Variant> {
+        let variants = [Variant::Standard, Variant::Gardner, Variant::Crazyhouse, Variant::Bughouse, Variant::ThreeCheck];
+
+        return variants.iter().copied().find(|v| v.name().eq_ignore_ascii_case(name));
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.name());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moves::Move;
+    use crate::game::Game;
+    use crate::perft;
+    use crate::pgn;
+    use crate::position::{self, Position, WHITE, BLACK, PAWN, ROOK, QUEEN, KING};
+
+    fn variant_position(variant: Variant, fen: &str) -> Position {
+        return Position::from_variant_fen(variant, fen.to_string()).unwrap();
+    }
+
+    fn gardner(fen: &str) -> Position {
+        return Position::from_variant_fen(Variant::Gardner, fen.to_string()).unwrap();
+    }
+
+    #[test]
+    fn gardner_starting_position_round_trips() {
+        let pos = gardner(Variant::Gardner.starting_fen());
+
+        assert_eq!(pos.variant(), Variant::Gardner);
+        assert_eq!(pos.to_fen(), Variant::Gardner.starting_fen());
+        assert_eq!(pos.to_string(), "r n b q k\np p p p p\n_ _ _ _ _\nP P P P P\nR N B Q K");
+    }
+
+    #[test]
+    fn gardner_fen_must_match_board_size() {
+        assert!(Position::from_variant_fen(Variant::Gardner, Variant::Standard.starting_fen().to_string()).is_none());
+        assert!(Position::from_variant_fen(Variant::Gardner, "rnbqk/ppppp/5/PPPPP w - - 0 1".to_string()).is_none());
+        assert!(Position::from_variant_fen(Variant::Gardner, "rnbqk/pppppp/4/PPPPP/RNBQK w - - 0 1".to_string()).is_none());
+        assert!(Position::from_fen(Variant::Gardner.starting_fen().to_string()).is_none());
+    }
+
+    #[test]
+    fn gardner_perft() {
+        let pos = gardner(Variant::Gardner.starting_fen());
+
+        assert_eq!(perft::perft(&pos, 1), 7);
+        assert_eq!(perft::perft(&pos, 2), 53);
+    }
+
+    #[test]
+    fn gardner_moves_stay_on_the_board() {
+        let pos = gardner("4k/5/2R2/5/K4 w - - 0 1");
+
+        let mut targets: Vec<String> = pos.get_pseudo_legal_moves().into_iter()
+            .filter_map(|m| match m {
+                Move::StandardMove(ROOK, _, target, _, _, _) => position::index_to_string(target),
+                _                                            => None
+            })
+            .collect();
+        targets.sort();
+
+        assert_eq!(targets, vec!["a3", "b3", "c1", "c2", "c4", "c5", "d3", "e3"]);
+    }
+
+    #[test]
+    fn gardner_pawns_promote_on_the_fifth_rank_without_double_steps() {
+        let pos = gardner("k4/3P1/5/P4/4K w - - 0 1");
+        let moves = pos.get_pseudo_legal_moves();
+
+        assert!(moves.contains(&Move::StandardMove(PAWN, 27, 35, None, Some(QUEEN), None)));
+        assert!(!moves.iter().any(|m| matches!(m, Move::StandardMove(PAWN, 8, 24, _, _, _))));
+        assert_eq!(pos.make_move(Move::StandardMove(PAWN, 27, 35, None, Some(QUEEN), None)).to_fen(), "k2Q1/5/5/P4/4K b - - 0 1");
+    }
+
+    #[test]
+    fn gardner_pgn_round_trips() {
+        let start = gardner(Variant::Gardner.starting_fen());
+        let mut game = Game::from_position(start);
+
+        for san in ["b3", "axb3", "cxb3"] {
+            let m = game.position().san_to_move(san).unwrap();
+            game.play(m);
+        }
+
+        let text = pgn::write_game(&game, &[], "*");
+        assert!(text.starts_with("[Variant \"Gardner\"]\n\n1. b3 axb3 2. cxb3 *"));
+
+        let games = pgn::read_games(&text);
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].game.position().variant(), Variant::Gardner);
+        assert_eq!(games[0].game.position().to_fen(), game.position().to_fen());
+    }
+
+    fn round_trip(variant: Variant, fen: &str) -> String {
+        return Position::from_variant_fen(variant, fen.to_string()).unwrap().to_fen();
+    }
+
+    fn is_rejected(variant: Variant, fen: &str) -> bool {
+        return Position::from_variant_fen(variant, fen.to_string()).is_none();
+    }
+
+    #[test]
+    fn standard_fen_round_trips() {
+        let fen = "r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1";
+
+        assert_eq!(round_trip(Variant::Standard, fen), fen);
+        assert_eq!(round_trip(Variant::Standard, "r3k2r/8/8/8/8/8/8/R3K2R w HAha - 0 1"), "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    }
+
+    #[test]
+    fn illegal_setups_are_rejected() {
+        // Castling without the rook on its square.
+        assert!(is_rejected(Variant::Standard, "r3k3/8/8/8/8/8/8/R3K2R b KQkq - 0 1"));
+        // Castling with a rook that isn't in the corner.
+        assert!(is_rejected(Variant::Standard, "r3k2r/8/8/8/8/8/8/R3K1R1 w GAha - 0 1"));
+        // Two white kings and no black king.
+        assert!(is_rejected(Variant::Standard, "8/8/8/8/8/8/8/K3K3 w - - 0 1"));
+        // A pawn on the last rank.
+        assert!(is_rejected(Variant::Standard, "P3k3/8/8/8/8/8/8/4K3 w - - 0 1"));
+        // The player who just moved is in check.
+        assert!(is_rejected(Variant::Standard, "4k3/8/8/8/8/8/4R3/4K3 w - - 0 1"));
+        // There is no pawn that could have made a double step.
+        assert!(is_rejected(Variant::Standard, "4k3/8/8/8/8/8/8/4K3 w - d6 0 1"));
+        // Pockets and check counts only exist in their variants.
+        assert!(is_rejected(Variant::Standard, "4k3/8/8/8/8/8/8/4K3[Q] w - - 0 1"));
+        assert!(is_rejected(Variant::Standard, "4k3/8/8/8/8/8/8/4K3 w - - 3+3 0 1"));
+        // Unknown pieces.
+        assert!(is_rejected(Variant::Standard, "4k3/8/8/8/8/8/8/3XK3 w - - 0 1"));
+    }
+
+    #[test]
+    fn gardner_fen_has_no_castling_rights() {
+        assert!(is_rejected(Variant::Gardner, "rnbqk/ppppp/5/PPPPP/RNBQK w Qq - 0 1"));
+        assert_eq!(round_trip(Variant::Gardner, "4k/5/5/5/K4 b - - 0 1"), "4k/5/5/5/K4 b - - 0 1");
+    }
+
+    #[test]
+    fn crazyhouse_fen_round_trips() {
+        let fen = "r1bQ~k2r/pppp1ppp/2n5/8/8/8/PPPP1PPP/RNB1KBNR[QRnpp] b KQ - 0 1";
+
+        assert_eq!(round_trip(Variant::Crazyhouse, fen), fen);
+        assert_eq!(round_trip(Variant::Crazyhouse, "4k3/8/8/8/8/8/8/4K3/nQ w - - 0 1"), "4k3/8/8/8/8/8/8/4K3[Qn] w - - 0 1");
+        assert_eq!(round_trip(Variant::Crazyhouse, "4k3/8/8/8/8/8/8/4K3 w - - 0 1"), "4k3/8/8/8/8/8/8/4K3[] w - - 0 1");
+
+        let pos = variant_position(Variant::Crazyhouse, fen);
+        assert_eq!(pos.pocket().count(BLACK, PAWN), 2);
+        assert_eq!(pos.captured_piece(Move::StandardMove(KING, 60, 59, Some(QUEEN), None, None)), Some(PAWN));
+    }
+
+    #[test]
+    fn bughouse_fen_round_trips() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[Bb] w KQkq - 0 1";
+
+        assert_eq!(round_trip(Variant::Bughouse, fen), fen);
+    }
+
+    #[test]
+    fn three_check_fen_round_trips() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3+1 0 1";
+
+        assert_eq!(round_trip(Variant::ThreeCheck, fen), fen);
+        assert_eq!(round_trip(Variant::ThreeCheck, "4k3/8/8/8/8/8/8/4K3 w - - +1+0 0 1"), "4k3/8/8/8/8/8/8/4K3 w - - 2+3 0 1");
+        assert_eq!(round_trip(Variant::ThreeCheck, "4k3/8/8/8/8/8/8/4K3 w - - 0 1"), "4k3/8/8/8/8/8/8/4K3 w - - 3+3 0 1");
+        assert!(is_rejected(Variant::ThreeCheck, "4k3/8/8/8/8/8/8/4K3 w - - 4+3 0 1"));
+    }
+
+    #[test]
+    fn three_check_counts_checks() {
+        let pos = variant_position(Variant::ThreeCheck, "4k3/8/8/8/8/8/8/R3K3 w - - 1+3 0 1");
+        let next = pos.make_move(pos.san_to_move("Ra8+").unwrap());
+
+        assert_eq!(next.checks_given(WHITE), 3);
+        assert!(next.is_variant_loss());
+        assert!(!pos.is_variant_loss());
+        assert_eq!(next.to_fen(), "R3k3/8/8/8/8/8/8/4K3 b - - 0+3 0 1");
+    }
+}
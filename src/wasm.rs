@@ -0,0 +1,91 @@
+use wasm_bindgen::prelude::*;
+
+use crate::position::{Position, WHITE};
+use crate::search::{self, Limits};
+
+
+/// The number of bytes a search started from JavaScript may use. Browsers limit the memory of
+/// a WebAssembly instance, so the search has to stay well below that.
+const MAX_MEMORY: usize = 8 * 1024 * 1024;
+
+
+/// A position exposed to JavaScript. Moves are passed as strings in SAN or long algebraic
+/// notation and returned in long algebraic notation.
+#[wasm_bindgen]
+pub struct Board {
+    position: Position
+}
+
+#[wasm_bindgen]
+impl Board {
+    /// Creates a board with the regular starting position.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Board {
+        return Board { position: Position::starting_position() };
+    }
+
+    /// Creates a board from a FEN. Throws an error if the FEN isn't valid.
+    #[wasm_bindgen(js_name = fromFen)]
+    pub fn from_fen(fen: &str) -> Result<Board, JsError> {
+        return match Position::from_fen(fen.to_string()) {
+            Some(position) => Ok(Board { position }),
+            None           => Err(JsError::new(&format!("Invalid FEN: {}", fen)))
+        };
+    }
+
+    /// Returns the FEN of the current position.
+    #[wasm_bindgen(js_name = toFen)]
+    pub fn to_fen(&self) -> String {
+        return self.position.to_fen();
+    }
+
+    /// Returns "w" or "b" depending on the side to move.
+    pub fn turn(&self) -> String {
+        return String::from(if self.position.turn == WHITE { "w" } else { "b" });
+    }
+
+    /// Returns all legal moves in long algebraic notation.
+    #[wasm_bindgen(js_name = legalMoves)]
+    pub fn legal_moves(&self) -> Vec<String> {
+        return self.position.clone().get_all_legal_moves().into_iter().map(|m| self.position.move_to_uci(m)).collect();
+    }
+
+    /// Checks if the side to move is in check.
+    #[wasm_bindgen(js_name = isCheck)]
+    pub fn is_check(&self) -> bool {
+        return self.position.is_checked(self.position.turn);
+    }
+
+    /// Searches the position up to the given depth and returns the best move in long algebraic
+    /// notation or undefined if there are no legal moves. The search visits at most maxNodes
+    /// positions and stays within a fixed memory budget, so it may stop short of the depth.
+    #[wasm_bindgen(js_name = bestMove)]
+    pub fn best_move(&self, depth: u8, max_nodes: u32) -> Option<String> {
+        let limits = Limits { depth: Some(depth.max(1)), nodes: Some(max_nodes as u64), memory: Some(MAX_MEMORY), ..Limits::default() };
+
+        return search::iterative_deepening(&self.position, &limits, |_| ()).map(|iteration| self.position.move_to_uci(iteration.best));
+    }
+
+    /// Plays the given move and returns it in long algebraic notation. Throws an error if the
+    /// move isn't legal.
+    #[wasm_bindgen(js_name = makeMove)]
+    pub fn make_move(&mut self, m: &str) -> Result<String, JsError> {
+        let parsed = self.position.san_to_move(m).or_else(|| self.position.uci_to_move(m));
+
+        return match parsed {
+            Some(parsed) => {
+                let uci = self.position.move_to_uci(parsed);
+                self.position = self.position.make_move(parsed);
+
+                Ok(uci)
+            },
+            None => Err(JsError::new(&format!("Illegal move: {}", m)))
+        };
+    }
+}
+
+impl Default for Board {
+    fn default() -> Board {
+        return Board::new();
+    }
+}
@@ -0,0 +1,72 @@
+/// Scale of the logistic curve mapping centipawns to winning chances. The value is
+/// fitted on a large number of online games.
+const WIN_PROBABILITY_SCALE: f64 = 0.00368208;
+
+
+/// Converts an evaluation in centipawns into the expected winning chances in percent
+/// (0 to 100) for the side the evaluation is given for.
+pub fn win_probability(cp: i32) -> f64 {
+    let chances = 2.0 / (1.0 + (-WIN_PROBABILITY_SCALE * cp as f64).exp()) - 1.0;
+
+    return 50.0 + 50.0 * chances;
+}
+
+/// Computes the accuracy (0 to 100) of a move that changed the winning chances of
+/// the moving side from win_before to win_after (both in percent).
+pub fn move_accuracy(win_before: f64, win_after: f64) -> f64 {
+    let accuracy = 103.1668 * (-0.04354 * (win_before - win_after).max(0.0)).exp() - 3.1669;
+
+    return accuracy.clamp(0.0, 100.0);
+}
+
+/// Combines the accuracies of all moves of one side into a single accuracy for the game.
+/// The harmonic mean is mixed in so a few very bad moves weigh more than many good ones.
+pub fn game_accuracy(accuracies: &[f64]) -> Option<f64> {
+    if accuracies.is_empty() {
+        return None;
+    }
+
+    let n = accuracies.len() as f64;
+    let mean = accuracies.iter().sum::<f64>() / n;
+    let harmonic = n / accuracies.iter().map(|a| 1.0 / a.max(1.0)).sum::<f64>();
+
+    return Some((mean + harmonic) / 2.0);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_centipawns_to_winning_chances() {
+        assert_eq!(win_probability(0), 50.0);
+        assert!((win_probability(100) - 59.1).abs() < 0.1);
+        assert!((win_probability(100) + win_probability(-100) - 100.0).abs() < 1e-9);
+        assert!(win_probability(1000) > 97.0 && win_probability(-1000) < 3.0);
+    }
+
+    #[test]
+    fn rates_moves_by_the_winning_chances_they_lose() {
+        // Keeping or improving the chances is a perfect move.
+        assert!(move_accuracy(60.0, 60.0) > 99.99);
+        assert_eq!(move_accuracy(40.0, 70.0), move_accuracy(60.0, 60.0));
+        assert!((move_accuracy(60.0, 50.0) - 63.6).abs() < 0.1);
+        assert_eq!(move_accuracy(100.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn combines_the_accuracies_of_a_game() {
+        // A game of only best moves is about 100% accurate.
+        let best_moves: Vec<f64> = [30, 45, 20, 60].iter().map(|&cp| move_accuracy(win_probability(cp), win_probability(cp))).collect();
+
+        assert!(game_accuracy(&best_moves).unwrap() > 99.9);
+        assert_eq!(game_accuracy(&[]), None);
+
+        // A single bad move weighs more than in the plain mean.
+        let accuracy = game_accuracy(&[100.0, 100.0, 100.0, 10.0]).unwrap();
+
+        assert!((accuracy - (77.5 + 400.0 / 13.0) / 2.0).abs() < 1e-9);
+        assert!(accuracy < 77.5);
+    }
+}
@@ -0,0 +1,131 @@
+use crate::bitboards::bb_ops;
+use crate::bitboards::geometry::NUM_SQUARES;
+use crate::position::{NUM_PIECE_TYPES, WHITE};
+
+
+/// The number of distinct pocket sizes and check counts with their own keys. Larger counts
+/// wrap around, which only weakens the hash for positions that can't occur in practice.
+const MAX_COUNT: usize = 32;
+
+const NUM_PIECES: usize = 2 * NUM_PIECE_TYPES as usize;
+const NUM_FILES: usize = 8;
+
+/// All random keys, generated at compile time from a fixed seed so hashes are the same in
+/// every run of the program.
+struct Keys {
+    pieces: [[u64; NUM_SQUARES as usize]; NUM_PIECES],
+    side: u64,
+    castling: [[u64; 2]; 2],
+    en_passant: [u64; NUM_FILES],
+    pocket: [[[u64; MAX_COUNT]; NUM_PIECE_TYPES as usize]; 2],
+    checks: [[u64; MAX_COUNT]; 2]
+}
+
+static KEYS: Keys = generate_keys();
+
+/// The splitmix64 generator, which is simple enough to run in a const fn.
+const fn splitmix(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+
+    return (state, z ^ (z >> 31));
+}
+
+const fn generate_keys() -> Keys {
+    let mut keys = Keys {
+        pieces: [[0; NUM_SQUARES as usize]; NUM_PIECES],
+        side: 0,
+        castling: [[0; 2]; 2],
+        en_passant: [0; NUM_FILES],
+        pocket: [[[0; MAX_COUNT]; NUM_PIECE_TYPES as usize]; 2],
+        checks: [[0; MAX_COUNT]; 2]
+    };
+
+    let mut state = 0x5EED;
+    let mut key;
+
+    let mut i = 0;
+    while i < NUM_PIECES * NUM_SQUARES as usize {
+        (state, key) = splitmix(state);
+        keys.pieces[i / NUM_SQUARES as usize][i % NUM_SQUARES as usize] = key;
+        i += 1;
+    }
+
+    (state, keys.side) = splitmix(state);
+
+    i = 0;
+    while i < 4 {
+        (state, key) = splitmix(state);
+        keys.castling[i / 2][i % 2] = key;
+        i += 1;
+    }
+
+    i = 0;
+    while i < NUM_FILES {
+        (state, key) = splitmix(state);
+        keys.en_passant[i] = key;
+        i += 1;
+    }
+
+    // Empty pockets and zero checks keep the key 0, so they don't change the hash of
+    // positions in variants without them.
+    i = 0;
+    while i < 2 * NUM_PIECE_TYPES as usize * MAX_COUNT {
+        let count = i % MAX_COUNT;
+
+        if count > 0 {
+            (state, key) = splitmix(state);
+            keys.pocket[i / (NUM_PIECE_TYPES as usize * MAX_COUNT)][i / MAX_COUNT % NUM_PIECE_TYPES as usize][count] = key;
+        }
+
+        i += 1;
+    }
+
+    i = 0;
+    while i < 2 * MAX_COUNT {
+        if i % MAX_COUNT > 0 {
+            (state, key) = splitmix(state);
+            keys.checks[i / MAX_COUNT][i % MAX_COUNT] = key;
+        }
+
+        i += 1;
+    }
+
+    return keys;
+}
+
+/// Returns the key of the piece with the given bitboard index standing on the given square.
+pub fn piece(piece_idx: u8, sq: u8) -> u64 {
+    return KEYS.pieces[piece_idx as usize][sq as usize];
+}
+
+/// Returns the key toggled whenever the turn changes. It is part of the hash while black is
+/// to move.
+pub fn side(turn: u8) -> u64 {
+    return if turn == WHITE { 0 } else { KEYS.side };
+}
+
+/// Returns the key of one castling right of the player with the given color.
+pub fn castling(color: u8, kingside: bool) -> u64 {
+    return KEYS.castling[color as usize][kingside as usize];
+}
+
+/// Returns the key of an en passant square. Only the file matters because the rank follows
+/// from the side to move.
+pub fn en_passant(sq: Option<u8>) -> u64 {
+    return sq.map_or(0, |sq| KEYS.en_passant[bb_ops::index_to_coords(sq).1 as usize]);
+}
+
+/// Returns the key of the player with the given color holding the given number of pieces of
+/// a type in the pocket.
+pub fn pocket(color: u8, piece_type: u8, count: u8) -> u64 {
+    return KEYS.pocket[color as usize][piece_type as usize][count as usize % MAX_COUNT];
+}
+
+/// Returns the key of the player with the given color having given the number of checks.
+pub fn checks(color: u8, count: u8) -> u64 {
+    return KEYS.checks[color as usize][count as usize % MAX_COUNT];
+}
@@ -0,0 +1,18 @@
+use chess::{Game, Move, Position, Variant};
+use chess::position::{PAWN, WHITE};
+
+#[test]
+fn generates_moves_through_the_public_api() {
+    let mut position = Position::starting_position();
+    assert_eq!(position.get_all_legal_moves().len(), 20);
+
+    let m = position.san_to_move("e4").unwrap();
+    assert_eq!(m, Move::StandardMove(PAWN, 12, 28, None, None, None));
+    assert_eq!(chess::bb_ops::index_lookup_mask(28), 1 << 28);
+
+    let mut game = Game::from_position(Position::variant_starting_position(Variant::Standard));
+    game.play(m);
+
+    assert_eq!(game.position().turn, chess::position::flip_color(WHITE));
+    assert_eq!(game.position().to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+}
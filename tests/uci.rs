@@ -0,0 +1,220 @@
+//! Drives the engine binary through scripted UCI conversations, including the odd things GUIs
+//! do, and checks that the responses follow the protocol.
+
+// The explicit `return` style is used throughout the crate.
+#![allow(clippy::needless_return)]
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use chess::Position;
+
+
+/// How long the engine may take to answer before the conversation fails.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+
+/// A running engine speaking UCI on its stdin and stdout.
+struct Engine {
+    process: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>
+}
+
+impl Engine {
+    fn start() -> Engine {
+        let mut process = Command::new(env!("CARGO_BIN_EXE_chess"))
+            .arg("uci")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let stdin = process.stdin.take().unwrap();
+        let stdout = BufReader::new(process.stdout.take().unwrap());
+        let (sender, lines) = mpsc::channel();
+
+        // Reading on a thread of its own lets the conversation time out.
+        thread::spawn(move || {
+            for line in stdout.lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        return Engine { process, stdin, lines };
+    }
+
+    fn send(&mut self, command: &str) {
+        writeln!(self.stdin, "{}", command).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    /// Returns the next line of output.
+    fn read(&mut self) -> String {
+        return self.lines.recv_timeout(TIMEOUT).expect("the engine didn't answer in time");
+    }
+
+    /// Returns the lines up to and including the first one starting with the given prefix.
+    fn read_until(&mut self, prefix: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        loop {
+            let line = self.read();
+            let done = line.starts_with(prefix);
+
+            lines.push(line);
+
+            if done {
+                return lines;
+            }
+        }
+    }
+
+    /// Sends isready and returns the lines written before readyok.
+    fn sync(&mut self) -> Vec<String> {
+        self.send("isready");
+
+        let mut lines = self.read_until("readyok");
+        lines.pop();
+
+        return lines;
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+/// Returns the move of a bestmove line, checking that it's legal in the position.
+fn best_move(line: &str, position: &Position) -> String {
+    let m = line.strip_prefix("bestmove ").expect("not a bestmove line");
+
+    assert!(position.uci_to_move(m).is_some(), "{} is illegal in {}", m, position.to_fen());
+
+    return m.to_string();
+}
+
+#[test]
+fn handshake_lists_id_and_options_before_uciok() {
+    let mut engine = Engine::start();
+    engine.send("uci");
+
+    let lines = engine.read_until("uciok");
+
+    assert!(lines[0].starts_with("id name "));
+    assert!(lines.iter().any(|line| line.starts_with("id author ")));
+    assert!(lines[..lines.len() - 1].iter().all(|line| line.starts_with("id ") || line.starts_with("option name ") || line.starts_with("info string ")));
+    assert!(engine.sync().is_empty());
+}
+
+#[test]
+fn searches_report_info_before_a_legal_bestmove() {
+    let mut engine = Engine::start();
+    engine.send("ucinewgame");
+    engine.send("position startpos moves e2e4 e7e5 g1f3");
+    engine.send("go depth 3");
+
+    let lines = engine.read_until("bestmove");
+    let (best, infos) = lines.split_last().unwrap();
+    let mut position = Position::starting_position();
+
+    for m in ["e2e4", "e7e5", "g1f3"] {
+        position = position.make_move(position.uci_to_move(m).unwrap());
+    }
+
+    best_move(best, &position);
+    assert!(infos.iter().all(|line| line.starts_with("info depth ") && line.contains(" score ") && line.contains(" pv ")));
+    assert!(infos.last().unwrap().starts_with("info depth 3 "));
+}
+
+#[test]
+fn immediate_stop_still_gives_a_bestmove() {
+    let mut engine = Engine::start();
+    engine.send("position startpos");
+    engine.send("go infinite");
+    engine.send("stop");
+
+    let lines = engine.read_until("bestmove");
+    best_move(lines.last().unwrap(), &Position::starting_position());
+
+    // Nothing else follows the bestmove.
+    assert!(engine.sync().is_empty());
+}
+
+#[test]
+fn isready_is_answered_during_searches() {
+    let mut engine = Engine::start();
+    engine.send("position startpos");
+    engine.send("go infinite");
+
+    // The search runs until stopped, so readyok has to come from the running search.
+    assert!(engine.sync().iter().all(|line| line.starts_with("info ")));
+
+    engine.send("stop");
+    best_move(engine.read_until("bestmove").last().unwrap(), &Position::starting_position());
+}
+
+#[test]
+fn duplicate_go_gets_its_own_bestmove() {
+    let mut engine = Engine::start();
+    engine.send("position startpos");
+    engine.send("go infinite");
+    engine.send("go depth 1");
+    engine.send("stop");
+
+    for _ in 0..2 {
+        best_move(engine.read_until("bestmove").last().unwrap(), &Position::starting_position());
+    }
+
+    assert!(engine.sync().iter().all(|line| !line.starts_with("bestmove")));
+
+    // The stop of the first search doesn't leak into later ones.
+    engine.send("go infinite");
+    assert!(engine.sync().iter().all(|line| !line.starts_with("bestmove")));
+
+    engine.send("stop");
+    best_move(engine.read_until("bestmove").last().unwrap(), &Position::starting_position());
+}
+
+#[test]
+fn bad_commands_are_reported_without_quitting() {
+    let mut engine = Engine::start();
+
+    engine.send("position startpos moves e2e5");
+    engine.send("xyzzy");
+    engine.send("stop");
+
+    assert_eq!(engine.sync(), ["info string invalid position: startpos moves e2e5", "info string unknown command: xyzzy"]);
+
+    // Until the go is handled it counts as a search, so isready could overtake the answer.
+    engine.send("go depth many");
+
+    assert_eq!(engine.read(), "info string invalid limits: depth many");
+    assert!(engine.sync().is_empty());
+}
+
+#[test]
+fn quit_ends_a_running_search() {
+    let mut engine = Engine::start();
+    engine.send("position startpos");
+    engine.send("go infinite");
+    engine.send("quit");
+
+    for _ in 0..TIMEOUT.as_millis() / 10 {
+        if engine.process.try_wait().unwrap().is_some() {
+            return;
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    panic!("the engine didn't quit");
+}
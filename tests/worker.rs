@@ -0,0 +1,62 @@
+//! Runs a worker against a server stand-in, with the engine binary playing both sides of the
+//! jobs.
+
+// The explicit `return` style is used throughout the crate.
+#![allow(clippy::needless_return)]
+
+use std::io;
+
+use chess::runner::concurrency::ConcurrencyConfig;
+use chess::runner::sprt::MatchScore;
+use chess::runner::uci_engine::{UciEngineSpec, UciLimit};
+use chess::runner::worker::{self, Job, JobServer};
+
+
+/// Hands out the given jobs and remembers the reported results.
+struct MockServer {
+    jobs: Vec<Job>,
+    reports: Vec<(String, MatchScore)>
+}
+
+impl JobServer for MockServer {
+    fn fetch_job(&mut self) -> io::Result<Option<Job>> {
+        return Ok(self.jobs.pop());
+    }
+
+    fn report(&mut self, job: &Job, score: &MatchScore) -> io::Result<()> {
+        self.reports.push((job.id.clone(), *score));
+
+        return Ok(());
+    }
+}
+
+fn engine(name: &str) -> UciEngineSpec {
+    return UciEngineSpec { name: name.to_string(), command: env!("CARGO_BIN_EXE_chess").to_string(), args: vec![String::from("uci")], options: Vec::new() };
+}
+
+fn job(id: &str, games: usize) -> Job {
+    return Job { id: id.to_string(), engines: [engine("dev"), engine("base")], book: None, limit: UciLimit::Depth(1), games, max_plies: 40 };
+}
+
+#[test]
+fn plays_and_reports_all_jobs() {
+    let mut server = MockServer { jobs: vec![job("2", 4), job("1", 2)], reports: Vec::new() };
+
+    worker::run_worker(&mut server, ConcurrencyConfig { concurrency: 2, ..ConcurrencyConfig::default() }).unwrap();
+
+    let ids: Vec<&str> = server.reports.iter().map(|(id, _)| id.as_str()).collect();
+    assert_eq!(ids, ["1", "2"]);
+
+    for ((_, score), games) in server.reports.iter().zip([2, 4]) {
+        assert_eq!(score.wins + score.draws + score.losses, games);
+        assert_eq!(score.pentanomial.iter().sum::<u32>(), games / 2);
+    }
+}
+
+#[test]
+fn fails_jobs_whose_engines_cannot_start() {
+    let mut broken = job("1", 2);
+    broken.engines[1].command = String::from("/nonexistent/engine");
+
+    assert!(worker::run_job(&broken, ConcurrencyConfig::default()).is_err());
+}